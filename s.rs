@@ -0,0 +1,15 @@
+// Generated by crustyc 0.1.0 from /tmp/sz/s.crst
+// Options: --emit=binary
+// Do not edit: changes will be lost when this file is regenerated.
+#![allow(unused_parens)]
+
+pub fn main() {
+    let x: char = 'a';
+    let outer: usize = std::mem::size_of::<char>();
+    if (outer > 0) {
+        let x: i64 = (2 as i64);
+        let inner: usize = std::mem::size_of::<char>();
+        let align: usize = std::mem::align_of::<i64>();
+        println!("{} {} {}", outer, inner, align);
+    }
+}
@@ -0,0 +1,316 @@
+// Copyright (c) 2026 Mark Ferrell
+// Licensed under the MIT License. See LICENSE.txt in the project root.
+
+//! Serialized AST archives for external analyzers.
+//!
+//! An archive bundles the parsed AST with a symbol table that gives every
+//! declaration a stable numeric ID and the span of its name in the source.
+//! The binary encoding is a four byte magic, a little-endian format version,
+//! and the archive body in postcard; the same body is also available as JSON.
+
+use crate::ast::{File, Item};
+use crate::error::Span;
+use crate::utils::locate_item;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Leading bytes of every binary archive
+pub const ARCHIVE_MAGIC: [u8; 4] = *b"CAST";
+
+/// Binary format version, bumped whenever the encoded shape changes
+pub const ARCHIVE_VERSION: u16 = 6;
+
+/// Stable identifier of a declaration within one archive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct SymbolId(pub u32);
+
+/// Kind of declaration a symbol refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ArchivedSymbolKind {
+    Function,
+    Method,
+    Struct,
+    Enum,
+    Trait,
+    Typedef,
+    Namespace,
+    Const,
+    Static,
+    Macro,
+}
+
+/// A declaration in the archive's symbol table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchivedSymbol {
+    pub id: SymbolId,
+    /// Path-qualified name, e.g. `geometry::Point::area`
+    pub path: String,
+    pub kind: ArchivedSymbolKind,
+    /// Enclosing namespace or struct
+    pub parent: Option<SymbolId>,
+    /// Span of the declared name, when it could be located in the source
+    pub span: Option<Span>,
+}
+
+/// A parsed file together with its resolved symbol table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AstArchive {
+    pub source_path: String,
+    pub file: File,
+    pub symbols: Vec<ArchivedSymbol>,
+}
+
+/// Archive encoding and decoding errors
+#[derive(Debug, Clone, PartialEq)]
+#[allow(dead_code)]
+pub enum ArchiveError {
+    /// The input does not start with [`ARCHIVE_MAGIC`]
+    BadMagic,
+    /// The archive was written by an incompatible format version
+    UnsupportedVersion(u16),
+    /// The body could not be encoded or decoded
+    Encoding(String),
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchiveError::BadMagic => write!(f, "not a Crusty AST archive"),
+            ArchiveError::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported archive version {} (expected {})",
+                version, ARCHIVE_VERSION
+            ),
+            ArchiveError::Encoding(message) => write!(f, "archive encoding error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError {}
+
+#[allow(dead_code)] // Readers are used by external analyzers
+impl AstArchive {
+    /// Build an archive, assigning symbol IDs in declaration order
+    pub fn new(source_path: impl Into<String>, file: File, source: &str) -> Self {
+        let mut symbols = Vec::new();
+        collect_symbols(&file.items, "", None, source, &mut symbols);
+        Self {
+            source_path: source_path.into(),
+            file,
+            symbols,
+        }
+    }
+
+    /// Look up a symbol by its path-qualified name
+    pub fn symbol(&self, path: &str) -> Option<&ArchivedSymbol> {
+        self.symbols.iter().find(|symbol| symbol.path == path)
+    }
+
+    /// Look up a symbol by ID
+    pub fn symbol_by_id(&self, id: SymbolId) -> Option<&ArchivedSymbol> {
+        self.symbols.get(id.0 as usize)
+    }
+
+    /// Encode as the compact binary format
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ArchiveError> {
+        let mut bytes = ARCHIVE_MAGIC.to_vec();
+        bytes.extend_from_slice(&ARCHIVE_VERSION.to_le_bytes());
+        postcard::to_extend(self, bytes).map_err(|e| ArchiveError::Encoding(e.to_string()))
+    }
+
+    /// Decode the compact binary format
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ArchiveError> {
+        let body = bytes
+            .strip_prefix(&ARCHIVE_MAGIC[..])
+            .ok_or(ArchiveError::BadMagic)?;
+        let (version, body) = body.split_at_checked(2).ok_or(ArchiveError::BadMagic)?;
+        let version = u16::from_le_bytes([version[0], version[1]]);
+        if version != ARCHIVE_VERSION {
+            return Err(ArchiveError::UnsupportedVersion(version));
+        }
+        postcard::from_bytes(body).map_err(|e| ArchiveError::Encoding(e.to_string()))
+    }
+
+    /// Encode as pretty-printed JSON
+    pub fn to_json(&self) -> Result<String, ArchiveError> {
+        serde_json::to_string_pretty(self).map_err(|e| ArchiveError::Encoding(e.to_string()))
+    }
+
+    /// Decode from JSON
+    pub fn from_json(json: &str) -> Result<Self, ArchiveError> {
+        serde_json::from_str(json).map_err(|e| ArchiveError::Encoding(e.to_string()))
+    }
+}
+
+/// Record every named declaration in `items`, recursing into namespaces and
+/// struct and trait methods
+fn collect_symbols(
+    items: &[Item],
+    prefix: &str,
+    parent: Option<SymbolId>,
+    source: &str,
+    symbols: &mut Vec<ArchivedSymbol>,
+) {
+    for item in items {
+        let (name, kind) = match item {
+            Item::Function(func) => (&func.name.name, ArchivedSymbolKind::Function),
+            Item::Struct(struct_def) => (&struct_def.name.name, ArchivedSymbolKind::Struct),
+            Item::Enum(enum_def) => (&enum_def.name.name, ArchivedSymbolKind::Enum),
+            Item::Trait(trait_def) => (&trait_def.name.name, ArchivedSymbolKind::Trait),
+            Item::Typedef(typedef) => (&typedef.name.name, ArchivedSymbolKind::Typedef),
+            Item::Namespace(namespace) => (&namespace.name.name, ArchivedSymbolKind::Namespace),
+            Item::Const(const_item) => (&const_item.name.name, ArchivedSymbolKind::Const),
+            Item::Static(static_item) => (&static_item.name.name, ArchivedSymbolKind::Static),
+            Item::MacroDefinition(macro_def) => (&macro_def.name.name, ArchivedSymbolKind::Macro),
+            Item::Extern(extern_block) => {
+                for func in &extern_block.functions {
+                    let path = format!("{}{}", prefix, func.name.name);
+                    let kind = ArchivedSymbolKind::Function;
+                    push_symbol(symbols, &path, &func.name.name, kind, parent, source);
+                }
+                continue;
+            }
+            Item::Impl(_) | Item::Import(_) | Item::Export(_) | Item::StaticAssert(_) => continue,
+        };
+
+        let path = format!("{}{}", prefix, name);
+        let id = push_symbol(symbols, &path, name, kind, parent, source);
+
+        match item {
+            Item::Namespace(namespace) => {
+                collect_symbols(
+                    &namespace.items,
+                    &format!("{}::", path),
+                    Some(id),
+                    source,
+                    symbols,
+                );
+            }
+            Item::Struct(struct_def) => {
+                for method in &struct_def.methods {
+                    let method_path = format!("{}::{}", path, method.name.name);
+                    push_symbol(
+                        symbols,
+                        &method_path,
+                        &method.name.name,
+                        ArchivedSymbolKind::Method,
+                        Some(id),
+                        source,
+                    );
+                }
+            }
+            Item::Trait(trait_def) => {
+                for method in &trait_def.methods {
+                    let method_path = format!("{}::{}", path, method.name.name);
+                    push_symbol(
+                        symbols,
+                        &method_path,
+                        &method.name.name,
+                        ArchivedSymbolKind::Method,
+                        Some(id),
+                        source,
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn push_symbol(
+    symbols: &mut Vec<ArchivedSymbol>,
+    path: &str,
+    name: &str,
+    kind: ArchivedSymbolKind,
+    parent: Option<SymbolId>,
+    source: &str,
+) -> SymbolId {
+    let id = SymbolId(symbols.len() as u32);
+    symbols.push(ArchivedSymbol {
+        id,
+        path: path.to_string(),
+        kind,
+        parent,
+        span: locate_item(source, name),
+    });
+    id
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Position;
+    use crate::parser::Parser;
+
+    const SOURCE: &str = "\
+#define __SQUARE__(x) ((x) * (x))
+
+struct Point {
+    int x;
+    int y;
+
+    int sum(&self) {
+        return self.x + self.y;
+    }
+}
+
+int area(int w, int h) {
+    return w * h;
+}
+";
+
+    fn archive() -> AstArchive {
+        let file = Parser::new(SOURCE).unwrap().parse_file().unwrap();
+        AstArchive::new("shapes.crst", file, SOURCE)
+    }
+
+    #[test]
+    fn test_symbols_have_ids_paths_and_spans() {
+        let archive = archive();
+        let paths: Vec<&str> = archive.symbols.iter().map(|s| s.path.as_str()).collect();
+        assert_eq!(paths, vec!["__SQUARE__", "Point", "Point::sum", "area"]);
+
+        let sum = archive.symbol("Point::sum").unwrap();
+        assert_eq!(sum.kind, ArchivedSymbolKind::Method);
+        assert_eq!(sum.parent, Some(SymbolId(1)));
+        assert_eq!(archive.symbol_by_id(SymbolId(3)).unwrap().path, "area");
+        assert_eq!(
+            archive.symbol("area").unwrap().span.map(|span| span.start),
+            Some(Position::new(12, 5))
+        );
+    }
+
+    #[test]
+    fn test_binary_round_trip() {
+        let archive = archive();
+        let bytes = archive.to_bytes().unwrap();
+        assert_eq!(&bytes[..4], &ARCHIVE_MAGIC);
+        assert_eq!(AstArchive::from_bytes(&bytes).unwrap(), archive);
+    }
+
+    #[test]
+    fn test_binary_is_smaller_than_json() {
+        let archive = archive();
+        let json = archive.to_json().unwrap();
+        assert!(archive.to_bytes().unwrap().len() < json.len());
+        assert_eq!(AstArchive::from_json(&json).unwrap(), archive);
+    }
+
+    #[test]
+    fn test_rejects_foreign_and_future_archives() {
+        assert_eq!(AstArchive::from_bytes(b"nope"), Err(ArchiveError::BadMagic));
+
+        let mut bytes = archive().to_bytes().unwrap();
+        bytes[4] = 99;
+        assert_eq!(
+            AstArchive::from_bytes(&bytes),
+            Err(ArchiveError::UnsupportedVersion(99))
+        );
+
+        let truncated = &archive().to_bytes().unwrap()[..12];
+        assert!(matches!(
+            AstArchive::from_bytes(truncated),
+            Err(ArchiveError::Encoding(_))
+        ));
+    }
+}
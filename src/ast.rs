@@ -3,20 +3,26 @@
 
 //! Abstract Syntax Tree definitions for Crusty and Rust programs.
 
+use crate::error::{Position, Span};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
 /// Represents a complete source file
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct File {
     pub items: Vec<Item>,
     pub doc_comments: Vec<String>,
 }
 
 /// Top-level items in a program
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[allow(dead_code)]
 pub enum Item {
     Function(Function),
     Struct(Struct),
     Enum(Enum),
+    Trait(Trait),
+    Impl(Impl),
     Typedef(Typedef),
     Namespace(Namespace),
     Import(Import),
@@ -25,13 +31,16 @@ pub enum Item {
     Const(Const),
     Static(Static),
     MacroDefinition(MacroDefinition),
+    StaticAssert(StaticAssert),
 }
 
 /// Function declaration
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Function {
     pub visibility: Visibility,
     pub name: Ident,
+    /// Type parameters of a generic function, as `T` in `T max(T)(T a, T b)`
+    pub generics: Vec<Ident>,
     pub params: Vec<Param>,
     pub return_type: Option<Type>,
     pub body: Block,
@@ -40,10 +49,12 @@ pub struct Function {
 }
 
 /// Struct definition
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Struct {
     pub visibility: Visibility,
     pub name: Ident,
+    /// Type parameters of a generic struct, as `T` in `struct Pair(T) { T first; }`
+    pub generics: Vec<Ident>,
     pub fields: Vec<Field>,
     pub methods: Vec<Function>,
     pub doc_comments: Vec<String>,
@@ -51,7 +62,7 @@ pub struct Struct {
 }
 
 /// Enum definition
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Enum {
     pub visibility: Visibility,
     pub name: Ident,
@@ -61,22 +72,30 @@ pub struct Enum {
 }
 
 /// Attribute (e.g., #[derive(Debug)], #[test])
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Attribute {
     pub name: Ident,
     pub args: Vec<AttributeArg>,
 }
 
 /// Attribute argument
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum AttributeArg {
     Ident(Ident),
     Literal(Literal),
-    NameValue { name: Ident, value: Literal },
+    NameValue {
+        name: Ident,
+        value: Literal,
+    },
+    /// Nested arguments, as `all(unix, target_arch = "x86_64")` in `#[cfg]`
+    List {
+        name: Ident,
+        args: Vec<AttributeArg>,
+    },
 }
 
 /// Type alias (typedef)
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Typedef {
     pub visibility: Visibility,
     pub name: Ident,
@@ -85,7 +104,7 @@ pub struct Typedef {
 }
 
 /// Namespace declaration
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Namespace {
     pub name: Ident,
     pub items: Vec<Item>,
@@ -93,28 +112,105 @@ pub struct Namespace {
 }
 
 /// Import directive (#import)
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Import {
     pub path: Vec<Ident>,
     pub alias: Option<Ident>,
 }
 
 /// Export directive (#export)
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Export {
     pub path: Vec<Ident>,
     pub alias: Option<Ident>,
 }
 
+/// Trait declaration: `trait Shape { float area(&self); }`, also written
+/// `interface Shape { ... }`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Trait {
+    pub visibility: Visibility,
+    pub name: Ident,
+    pub methods: Vec<MethodSignature>,
+    pub doc_comments: Vec<String>,
+    pub attributes: Vec<Attribute>,
+}
+
+/// Method declared by a trait, with a default body when the trait gives one
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MethodSignature {
+    pub name: Ident,
+    pub params: Vec<Param>,
+    pub return_type: Option<Type>,
+    /// Default implementation inherited by impls that don't define the method
+    #[serde(default)]
+    pub body: Option<Block>,
+}
+
+impl MethodSignature {
+    /// The method as an implementing type's method: its default body, or an
+    /// empty one when the trait gives none
+    pub fn as_function(&self) -> Function {
+        Function {
+            visibility: Visibility::Public,
+            name: self.name.clone(),
+            generics: Vec::new(),
+            params: self.params.clone(),
+            return_type: self.return_type.clone(),
+            body: self.body.clone().unwrap_or_else(|| Block::new(Vec::new())),
+            doc_comments: Vec::new(),
+            attributes: Vec::new(),
+        }
+    }
+}
+
+/// Implementation of a trait for a type: `impl Shape for Circle { ... }`,
+/// or for every instance of a generic struct: `impl Shape for Pair(T)`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Impl {
+    pub trait_name: Ident,
+    /// Type parameters of a generic impl, as `T` in `Pair(T)`
+    #[serde(default)]
+    pub generics: Vec<Ident>,
+    /// The implementing type; `Pair<T>` for `Pair(T)`
+    pub ty: Type,
+    pub methods: Vec<Function>,
+    /// Where `impl` starts in the source, for parsed impls; line 0 for one
+    /// built by the compiler
+    #[serde(default)]
+    pub position: Position,
+}
+
+/// Impls are equal when they implement the same trait for the same type
+/// with the same methods, wherever they were parsed from
+impl PartialEq for Impl {
+    fn eq(&self, other: &Self) -> bool {
+        self.trait_name == other.trait_name
+            && self.generics == other.generics
+            && self.ty == other.ty
+            && self.methods == other.methods
+    }
+}
+
 /// Extern block
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Extern {
     pub abi: Option<String>,
-    pub items: Vec<Item>,
+    pub functions: Vec<ForeignFunction>,
+}
+
+/// Function declared in an extern block: a signature resolved by the linker
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ForeignFunction {
+    pub name: Ident,
+    pub params: Vec<Param>,
+    pub return_type: Option<Type>,
+    /// Accepts further arguments after `params`, as C's `...`
+    pub variadic: bool,
 }
 
 /// Constant declaration
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Const {
     pub visibility: Visibility,
     pub name: Ident,
@@ -124,7 +220,7 @@ pub struct Const {
 }
 
 /// Static variable declaration
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Static {
     pub visibility: Visibility,
     pub name: Ident,
@@ -134,17 +230,39 @@ pub struct Static {
     pub doc_comments: Vec<String>,
 }
 
+/// Compile-time assertion: `static_assert(condition, "message");`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StaticAssert {
+    pub condition: Expression,
+    pub message: String,
+    /// Where `static_assert` starts in the source, for parsed assertions;
+    /// line 0 for one built by the compiler
+    #[serde(default)]
+    pub position: Position,
+}
+
+/// Assertions are equal when their conditions and messages are, wherever
+/// they were parsed from
+impl PartialEq for StaticAssert {
+    fn eq(&self, other: &Self) -> bool {
+        self.condition == other.condition && self.message == other.message
+    }
+}
+
 /// Macro definition (#define)
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct MacroDefinition {
     pub name: Ident,
     pub params: Vec<Ident>,
     pub body: Vec<crate::lexer::Token>,
     pub delimiter: MacroDelimiter,
+    /// Accepts further arguments after `params`, as C's `...`, which the
+    /// body refers to as `__VA_ARGS__`
+    pub variadic: bool,
 }
 
 /// Macro invocation delimiter style
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum MacroDelimiter {
     None,     // __MACRO__ (no parameters, no delimiters)
     Parens,   // __MACRO__(args)
@@ -153,7 +271,7 @@ pub enum MacroDelimiter {
 }
 
 /// Statement types
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[allow(dead_code)]
 pub enum Statement {
     Let {
@@ -184,6 +302,10 @@ pub enum Statement {
         condition: Expression,
         body: Block,
     },
+    DoWhile {
+        body: Block,
+        condition: Expression,
+    },
     For {
         label: Option<Ident>,
         init: Box<Statement>,
@@ -204,6 +326,11 @@ pub enum Statement {
     },
     Break(Option<Ident>),
     Continue(Option<Ident>),
+    Unsafe(Block),
+    /// Block scoping the declarations in it, as `{ init; while ... }` for a
+    /// lowered `for`; made by lowering to HIR, not written in source
+    Block(Block),
+    StaticAssert(StaticAssert),
     NestedFunction {
         name: Ident,
         params: Vec<Param>,
@@ -213,7 +340,7 @@ pub enum Statement {
 }
 
 /// Expression types
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[allow(dead_code)]
 pub enum Expression {
     Literal(Literal),
@@ -246,6 +373,19 @@ pub enum Expression {
     Sizeof {
         ty: Type,
     },
+    /// `sizeof(expr)`: the size of the type of `expr`, which is not evaluated
+    SizeofExpr {
+        expr: Box<Expression>,
+    },
+    /// `alignof(T)`: the alignment of `T` in bytes
+    Alignof {
+        ty: Type,
+    },
+    /// `alignof(expr)`: the alignment of the type of `expr`, which is not
+    /// evaluated
+    AlignofExpr {
+        expr: Box<Expression>,
+    },
     Ternary {
         condition: Box<Expression>,
         then_expr: Box<Expression>,
@@ -269,6 +409,7 @@ pub enum Expression {
     MacroCall {
         name: Ident,
         args: Vec<Token>,
+        delimiter: MacroDelimiter,
     },
     RustBlock {
         tokens: Vec<Token>,
@@ -298,10 +439,51 @@ pub enum Expression {
         left: Box<Expression>,
         right: Box<Expression>,
     },
+    /// Pattern match: `match (expr) { pattern => value, ... }`
+    Match {
+        expr: Box<Expression>,
+        arms: Vec<MatchArm>,
+    },
+    /// Lambda: `fn(params) -> type { body }`, an anonymous function using
+    /// the variables in scope where it is written
+    Lambda {
+        params: Vec<Param>,
+        return_type: Option<Type>,
+        body: Block,
+        /// Whether it captures the variables it uses by value rather than
+        /// by reference; set by lowering to HIR, not written in source
+        #[serde(default)]
+        by_value: bool,
+    },
+    /// `expr`, which is not a boolean, tested for truth as C does it; made
+    /// by lowering to HIR, not written in source
+    TruthTest {
+        expr: Box<Expression>,
+        test: Truthiness,
+    },
+    /// `(ty)expr` from the integer type `from` to one that cannot hold every
+    /// value of it, in a function returning `T?`; made by lowering to HIR,
+    /// not written in source
+    CheckedCast {
+        expr: Box<Expression>,
+        from: Type,
+        ty: Type,
+    },
+}
+
+/// How a non-boolean value used as a condition is tested, C-style
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Truthiness {
+    /// An `Option`, true when it holds a value
+    Option,
+    /// A raw pointer, true when it is not null
+    Pointer,
+    /// An integer, true when it is not zero (only in `IntConditions::Compat`)
+    Integer,
 }
 
 /// Type expressions
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[allow(dead_code)]
 pub enum Type {
     Primitive(PrimitiveType),
@@ -335,17 +517,28 @@ pub enum Type {
     Fallible {
         ty: Box<Type>,
     },
+    /// `volatile T`: every access to a `T` through a pointer to this type is
+    /// a volatile read or write
+    Volatile {
+        ty: Box<Type>,
+    },
     Auto,
 }
 
 /// Primitive types
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum PrimitiveType {
     Int,
+    I8,
+    I16,
     I32,
     I64,
+    Isize,
+    U8,
+    U16,
     U32,
     U64,
+    Usize,
     Float,
     F32,
     F64,
@@ -354,14 +547,153 @@ pub enum PrimitiveType {
     Void,
 }
 
+/// Suffixes a number literal may end with, and the types they give it
+const LITERAL_SUFFIXES: &[(&str, PrimitiveType)] = &[
+    ("i8", PrimitiveType::I8),
+    ("i16", PrimitiveType::I16),
+    ("i32", PrimitiveType::I32),
+    ("i64", PrimitiveType::I64),
+    ("isize", PrimitiveType::Isize),
+    ("u8", PrimitiveType::U8),
+    ("u16", PrimitiveType::U16),
+    ("u32", PrimitiveType::U32),
+    ("u64", PrimitiveType::U64),
+    ("usize", PrimitiveType::Usize),
+    ("f32", PrimitiveType::F32),
+    ("f64", PrimitiveType::F64),
+];
+
+impl PrimitiveType {
+    /// The type a literal suffix such as `u32` or `f64` gives a number
+    pub fn from_suffix(suffix: &str) -> Option<Self> {
+        LITERAL_SUFFIXES
+            .iter()
+            .find(|(name, _)| *name == suffix)
+            .map(|(_, ty)| ty.clone())
+    }
+
+    /// The literal suffix that gives a number this type
+    pub fn suffix(&self) -> Option<&'static str> {
+        LITERAL_SUFFIXES
+            .iter()
+            .find(|(_, ty)| ty == self)
+            .map(|(name, _)| *name)
+    }
+
+    /// Whether this is one of the signed or unsigned integer types
+    pub fn is_integer(&self) -> bool {
+        matches!(
+            self,
+            PrimitiveType::Int
+                | PrimitiveType::I8
+                | PrimitiveType::I16
+                | PrimitiveType::I32
+                | PrimitiveType::I64
+                | PrimitiveType::Isize
+                | PrimitiveType::U8
+                | PrimitiveType::U16
+                | PrimitiveType::U32
+                | PrimitiveType::U64
+                | PrimitiveType::Usize
+        )
+    }
+
+    /// The values a literal of this integer type can hold
+    pub fn integer_range(&self) -> Option<(i128, i128)> {
+        let range = match self {
+            PrimitiveType::Int | PrimitiveType::I32 => (i32::MIN as i128, i32::MAX as i128),
+            PrimitiveType::I8 => (i8::MIN as i128, i8::MAX as i128),
+            PrimitiveType::I16 => (i16::MIN as i128, i16::MAX as i128),
+            PrimitiveType::I64 | PrimitiveType::Isize => (i64::MIN as i128, i64::MAX as i128),
+            PrimitiveType::U8 => (0, u8::MAX as i128),
+            PrimitiveType::U16 => (0, u16::MAX as i128),
+            PrimitiveType::U32 => (0, u32::MAX as i128),
+            PrimitiveType::U64 | PrimitiveType::Usize => (0, u64::MAX as i128),
+            _ => return None,
+        };
+        Some(range)
+    }
+}
+
+impl fmt::Display for PrimitiveType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            PrimitiveType::Int => "int",
+            PrimitiveType::Float => "float",
+            PrimitiveType::Bool => "bool",
+            PrimitiveType::Char => "char",
+            PrimitiveType::Void => "void",
+            suffixed => suffixed.suffix().unwrap_or_default(),
+        };
+        f.write_str(name)
+    }
+}
+
+/// Writes `items` separated by commas
+fn comma_separated(f: &mut fmt::Formatter<'_>, items: &[Type]) -> fmt::Result {
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            f.write_str(", ")?;
+        }
+        write!(f, "{}", item)?;
+    }
+    Ok(())
+}
+
+/// A type as diagnostics and site names print it
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Primitive(prim) => write!(f, "{}", prim),
+            Type::Ident(ident) => f.write_str(&ident.name),
+            Type::Pointer { ty, mutable } => {
+                write!(f, "*{} {}", if *mutable { "mut" } else { "const" }, ty)
+            }
+            Type::Reference { ty, mutable } => {
+                write!(f, "&{}{}", if *mutable { "mut " } else { "" }, ty)
+            }
+            Type::Array {
+                ty,
+                size: Some(size),
+            } => write!(f, "[{}; {}]", ty, size),
+            Type::Array { ty, size: None } | Type::Slice { ty } => write!(f, "[{}]", ty),
+            Type::Tuple { types } => {
+                f.write_str("(")?;
+                comma_separated(f, types)?;
+                f.write_str(")")
+            }
+            Type::Generic { base, args } => {
+                write!(f, "{}<", base)?;
+                comma_separated(f, args)?;
+                f.write_str(">")
+            }
+            Type::Function {
+                params,
+                return_type,
+            } => {
+                f.write_str("fn(")?;
+                comma_separated(f, params)?;
+                f.write_str(")")?;
+                match return_type.as_ref() {
+                    Type::Primitive(PrimitiveType::Void) => Ok(()),
+                    ty => write!(f, " -> {}", ty),
+                }
+            }
+            Type::Fallible { ty } => write!(f, "{}?", ty),
+            Type::Volatile { ty } => write!(f, "volatile {}", ty),
+            Type::Auto => f.write_str("_"),
+        }
+    }
+}
+
 /// Placeholder for token stream (will be properly defined in lexer module)
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Token {
     pub kind: TokenKind,
     pub text: String,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[allow(dead_code)]
 pub enum TokenKind {
     Ident,
@@ -373,14 +705,18 @@ pub enum TokenKind {
 }
 
 /// Function parameter
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Param {
     pub name: Ident,
     pub ty: Type,
+    /// `T... name`: takes the remaining arguments of a call, which are
+    /// collected into a slice, so `ty` is `&T[]`
+    #[serde(default)]
+    pub variadic: bool,
 }
 
 /// Struct field
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Field {
     pub visibility: Visibility,
     pub name: Ident,
@@ -390,28 +726,57 @@ pub struct Field {
 }
 
 /// Enum variant
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EnumVariant {
     pub name: Ident,
     pub value: Option<i64>,
+    /// Payload of a tagged-union variant, e.g. `Circle(float radius)`
+    pub fields: Vec<Param>,
 }
 
 /// Switch case
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SwitchCase {
     pub values: Vec<Expression>,
     pub body: Block,
 }
 
+/// Match arm: `pattern => value`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body: Expression,
+}
+
+/// Pattern of a match arm
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Pattern {
+    /// `_`, matching anything
+    Wildcard,
+    /// A literal value: `1`, `-1`, `'a'`, `"text"`, `true`
+    Literal(Literal),
+    /// A name bound to the matched value
+    Binding(Ident),
+    /// An enum variant, with patterns for its payload: `Color.Red`,
+    /// `Option.Some(x)`
+    Variant {
+        enum_name: Ident,
+        variant: Ident,
+        fields: Vec<Pattern>,
+    },
+    /// A tuple of patterns: `(0, _)`
+    Tuple(Vec<Pattern>),
+}
+
 /// Visibility modifier
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Visibility {
     Public,
     Private,
 }
 
 /// Binary operators
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[allow(dead_code)]
 pub enum BinaryOp {
     // Arithmetic
@@ -450,8 +815,28 @@ pub enum BinaryOp {
     ShrAssign,
 }
 
+impl BinaryOp {
+    /// Whether the operator stores into its left operand
+    pub fn is_assignment(&self) -> bool {
+        matches!(
+            self,
+            BinaryOp::Assign
+                | BinaryOp::AddAssign
+                | BinaryOp::SubAssign
+                | BinaryOp::MulAssign
+                | BinaryOp::DivAssign
+                | BinaryOp::ModAssign
+                | BinaryOp::BitAndAssign
+                | BinaryOp::BitOrAssign
+                | BinaryOp::BitXorAssign
+                | BinaryOp::ShlAssign
+                | BinaryOp::ShrAssign
+        )
+    }
+}
+
 /// Unary operators
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum UnaryOp {
     Not,
     Neg,
@@ -464,47 +849,247 @@ pub enum UnaryOp {
 }
 
 /// Literal values
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Literal {
     Int(i64),
+    /// `0xFF`, `0o77` or `0b1010`: an integer written in another radix,
+    /// which codegen writes back in the same radix
+    RadixInt(i64, Radix),
     Float(f64),
+    /// `42u32` or `3.5f32`: a number whose suffix spells out its type, which
+    /// codegen writes with the same suffix
+    Suffixed(Box<Literal>, PrimitiveType),
     String(String),
+    /// `r"..."` or `r#"..."#`: text without escapes, which codegen writes
+    /// as a Rust raw string
+    RawString(String),
+    ByteString(Vec<u8>),
+    /// `L"..."` or `u"..."`: UTF-16 text, lowered as chosen by the code generator
+    WideString(String),
     Char(char),
     Bool(bool),
     Null,
 }
 
+impl Literal {
+    /// Value of an integer literal, in whatever radix it was written
+    pub fn int_value(&self) -> Option<i64> {
+        match self {
+            Literal::Int(value) | Literal::RadixInt(value, _) => Some(*value),
+            Literal::Suffixed(literal, _) => literal.int_value(),
+            _ => None,
+        }
+    }
+
+    /// The number literal with its sign flipped, as `-` before it in a
+    /// pattern writes it
+    pub fn negated(&self) -> Option<Literal> {
+        match self {
+            Literal::Int(value) => Some(Literal::Int(-value)),
+            Literal::RadixInt(value, radix) => Some(Literal::RadixInt(-value, *radix)),
+            Literal::Float(value) => Some(Literal::Float(-value)),
+            Literal::Suffixed(literal, ty) => literal
+                .negated()
+                .map(|literal| Literal::Suffixed(Box::new(literal), ty.clone())),
+            _ => None,
+        }
+    }
+}
+
+/// Radix of a non-decimal integer literal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Radix {
+    Binary,
+    Octal,
+    Hex,
+}
+
+impl Radix {
+    /// The radix a `0b`, `0o` or `0x` prefix (either case) selects
+    pub fn from_prefix(prefix: &str) -> Option<Self> {
+        match prefix {
+            "0b" | "0B" => Some(Radix::Binary),
+            "0o" | "0O" => Some(Radix::Octal),
+            "0x" | "0X" => Some(Radix::Hex),
+            _ => None,
+        }
+    }
+
+    pub fn base(self) -> u32 {
+        match self {
+            Radix::Binary => 2,
+            Radix::Octal => 8,
+            Radix::Hex => 16,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Radix::Binary => "binary",
+            Radix::Octal => "octal",
+            Radix::Hex => "hexadecimal",
+        }
+    }
+
+    /// `value` written in this radix, as Crusty and Rust both spell it
+    pub fn format(self, value: i64) -> String {
+        let sign = if value < 0 { "-" } else { "" };
+        let magnitude = value.unsigned_abs();
+        match self {
+            Radix::Binary => format!("{}0b{:b}", sign, magnitude),
+            Radix::Octal => format!("{}0o{:o}", sign, magnitude),
+            Radix::Hex => format!("{}0x{:X}", sign, magnitude),
+        }
+    }
+}
+
 /// Identifier
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Ident {
     pub name: String,
+    /// Where the name is written in the source, for parsed identifiers; at
+    /// line 0 for those the compiler makes
+    #[serde(default)]
+    pub span: Span,
+}
+
+// Identifiers are the same wherever they are written
+impl fmt::Debug for Ident {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Ident").field("name", &self.name).finish()
+    }
+}
+
+impl PartialEq for Ident {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Eq for Ident {}
+
+impl std::hash::Hash for Ident {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+    }
 }
 
 impl Ident {
     pub fn new(name: impl Into<String>) -> Self {
-        Self { name: name.into() }
+        Self {
+            name: name.into(),
+            span: Span::default(),
+        }
+    }
+
+    /// The identifier `name`, written at `span` in the source
+    pub fn spanned(name: impl Into<String>, span: Span) -> Self {
+        Self {
+            name: name.into(),
+            span,
+        }
+    }
+
+    /// Where the name starts in the source, for parsed identifiers
+    pub fn position(&self) -> Option<Position> {
+        (self.span.start.line != 0).then_some(self.span.start)
     }
 }
 
 /// Block of statements
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Block {
     pub statements: Vec<Statement>,
+    /// Where each statement starts in the source, for parsed blocks; a
+    /// block built by the compiler has fewer positions than statements
+    #[serde(default)]
+    pub positions: Vec<Position>,
+}
+
+/// Blocks are equal when their statements are, wherever they were parsed from
+impl PartialEq for Block {
+    fn eq(&self, other: &Self) -> bool {
+        self.statements == other.statements
+    }
 }
 
 impl Block {
     pub fn new(statements: Vec<Statement>) -> Self {
-        Self { statements }
+        Self {
+            statements,
+            positions: Vec::new(),
+        }
+    }
+
+    /// A parsed block, with the position of each statement
+    pub fn with_positions(statements: Vec<Statement>, positions: Vec<Position>) -> Self {
+        Self {
+            statements,
+            positions,
+        }
     }
 
     #[allow(dead_code)]
     pub fn empty() -> Self {
-        Self {
-            statements: Vec::new(),
+        Self::new(Vec::new())
+    }
+
+    /// Whether every path through the block leaves it with `return`, `break`
+    /// or `continue`
+    pub fn ends_in_jump(&self) -> bool {
+        match self.statements.last() {
+            Some(Statement::Return(_) | Statement::Break(_) | Statement::Continue(_)) => true,
+            Some(Statement::If {
+                then_block,
+                else_block: Some(else_block),
+                ..
+            }) => then_block.ends_in_jump() && else_block.ends_in_jump(),
+            _ => false,
         }
     }
 }
 
+impl Statement {
+    /// The variable tested by a null guard: `if (!p) { ... }` or
+    /// `if (p == NULL) { ... }` with no else branch, whose body always jumps
+    /// out. Past such a guard `p` is known to hold a value.
+    pub fn null_guard(&self) -> Option<&Ident> {
+        let Statement::If {
+            condition,
+            then_block,
+            else_block: None,
+        } = self
+        else {
+            return None;
+        };
+        let name = match condition {
+            Expression::Unary {
+                op: UnaryOp::Not,
+                expr,
+            } => match expr.as_ref() {
+                Expression::Ident(name) => name,
+                // As lowering to HIR makes the test explicit
+                Expression::TruthTest { expr, .. } => match expr.as_ref() {
+                    Expression::Ident(name) => name,
+                    _ => return None,
+                },
+                _ => return None,
+            },
+            Expression::Binary {
+                op: BinaryOp::Eq,
+                left,
+                right,
+            } => match (left.as_ref(), right.as_ref()) {
+                (Expression::Ident(name), Expression::Literal(Literal::Null))
+                | (Expression::Literal(Literal::Null), Expression::Ident(name)) => name,
+                _ => return None,
+            },
+            _ => return None,
+        };
+        then_block.ends_in_jump().then_some(name)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -529,6 +1114,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("main"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::empty(),
@@ -546,14 +1132,17 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("add"),
+            generics: vec![],
             params: vec![
                 Param {
                     name: Ident::new("a"),
                     ty: Type::Primitive(PrimitiveType::I32),
+                    variadic: false,
                 },
                 Param {
                     name: Ident::new("b"),
                     ty: Type::Primitive(PrimitiveType::I32),
+                    variadic: false,
                 },
             ],
             return_type: Some(Type::Primitive(PrimitiveType::I32)),
@@ -573,6 +1162,7 @@ mod tests {
         let struct_def = Struct {
             visibility: Visibility::Public,
             name: Ident::new("Point"),
+            generics: vec![],
             fields: vec![
                 Field {
                     visibility: Visibility::Public,
@@ -609,14 +1199,17 @@ mod tests {
                 EnumVariant {
                     name: Ident::new("Red"),
                     value: Some(0),
+                    fields: vec![],
                 },
                 EnumVariant {
                     name: Ident::new("Green"),
                     value: Some(1),
+                    fields: vec![],
                 },
                 EnumVariant {
                     name: Ident::new("Blue"),
                     value: Some(2),
+                    fields: vec![],
                 },
             ],
             doc_comments: vec![],
@@ -891,6 +1484,7 @@ mod tests {
         let original = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::empty(),
@@ -949,6 +1543,56 @@ mod tests {
         assert_eq!(ops.len(), 18);
     }
 
+    #[test]
+    fn test_null_guard() {
+        let guard = |condition, then_block: Vec<Statement>| Statement::If {
+            condition,
+            then_block: Block::new(then_block),
+            else_block: None,
+        };
+        let p = || Box::new(Expression::Ident(Ident::new("p")));
+        let not_p = Expression::Unary {
+            op: UnaryOp::Not,
+            expr: p(),
+        };
+        let p_is_null = Expression::Binary {
+            op: BinaryOp::Eq,
+            left: Box::new(Expression::Literal(Literal::Null)),
+            right: p(),
+        };
+
+        let stmt = guard(not_p.clone(), vec![Statement::Return(None)]);
+        assert_eq!(stmt.null_guard(), Some(&Ident::new("p")));
+        let stmt = guard(p_is_null, vec![Statement::Break(None)]);
+        assert_eq!(stmt.null_guard(), Some(&Ident::new("p")));
+
+        // The body must leave the block
+        let stmt = guard(
+            not_p,
+            vec![Statement::Expr(Expression::Ident(Ident::new("x")))],
+        );
+        assert_eq!(stmt.null_guard(), None);
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let source = "#define __SQ__(x) ((x) * (x))\nstruct P {\n    int x;\n}\nenum Color {\n    Red,\n    Blue,\n}\ntrait Shape {\n    float area(&self);\n}\nint f(P p, Option<int> o) {\n    var int total = __SQ__(p.x);\n    for (int i = 0; i < 3; i++) {\n        total += i;\n    }\n    switch (total) {\n        case 1: { return 1; }\n        default: { break; }\n    }\n    return total > 2 ? total : -1;\n}\n";
+        let file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+
+        let json = serde_json::to_string(&file).unwrap();
+        assert_eq!(serde_json::from_str::<File>(&json).unwrap(), file);
+        // Unit variants are their names, others objects keyed by them
+        assert!(json.contains("\"op\":\"Lt\""), "{}", json);
+        assert!(
+            json.contains("{\"Ident\":{\"name\":\"total\",\"span\":{\"start\":{\"line\":17,\"column\":13},\"end\":{\"line\":17,\"column\":18}}}}"),
+            "{}",
+            json
+        );
+    }
+
     #[test]
     fn test_unary_operators() {
         let ops = [
@@ -0,0 +1,748 @@
+// Copyright (c) 2026 Mark Ferrell
+// Licensed under the MIT License. See LICENSE.txt in the project root.
+
+//! An approximate ownership and borrow check of function bodies.
+//!
+//! rustc checks the generated Rust in full, but its errors speak of the
+//! generated code. This pass catches the obvious cases first and reports
+//! them against the Crusty source:
+//!
+//! ```text
+//! typedef const int* View;
+//! View largest() {
+//!     int best = 0;
+//!     return (View)&best;  // a pointer to a local outlives it
+//! }
+//!
+//! Point q = p;
+//! print(p.x);             // 'p' was moved into 'q'
+//! ```
+//!
+//! A struct without `#[derive(Copy)]` moves when it is assigned, passed by
+//! value or stored in another value; using it afterwards, on any path, is
+//! an error until it is assigned again. Moves in a loop body are followed
+//! into the next iteration. The analysis is deliberately shallow: only
+//! local variables and parameters declared with their type are followed,
+//! moves out of fields and captures by nested functions are left to rustc,
+//! and macro arguments and `rust { }` blocks are opaque.
+
+use crate::ast::{BinaryOp, Block, Expression, Param, Statement, Type, UnaryOp};
+use crate::error::Position;
+use crate::macros::pattern_bindings;
+use std::collections::{BTreeMap, HashSet};
+
+/// A use of a moved value, or a reference or pointer that outlives what it
+/// refers to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BorrowFinding {
+    /// Function the finding is in
+    pub function: String,
+    /// Where the statement starts, when it was parsed from source
+    pub position: Option<Position>,
+    pub message: String,
+    /// How to fix it
+    pub help: String,
+}
+
+impl std::fmt::Display for BorrowFinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.position {
+            Some(position) => write!(
+                f,
+                "in function '{}', line {}: {}",
+                self.function, position.line, self.message
+            ),
+            None => write!(f, "in function '{}': {}", self.function, self.message),
+        }
+    }
+}
+
+/// A local variable or parameter, identified by the order it is declared in
+#[derive(Debug, Clone)]
+struct Decl {
+    name: String,
+    ty: Option<Type>,
+    parameter: bool,
+    /// Whether assigning or passing the value moves it
+    moves: bool,
+}
+
+/// Where each moved variable was moved, or `None` where no path reaches
+type State = Option<BTreeMap<usize, Option<Position>>>;
+
+fn join(a: State, b: State) -> State {
+    match (a, b) {
+        (Some(mut a), Some(b)) => {
+            for (decl, position) in b {
+                a.entry(decl).or_insert(position);
+            }
+            Some(a)
+        }
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+/// The states at the `break`s and `continue`s of a loop
+struct LoopFrame {
+    label: Option<String>,
+    breaks: State,
+    continues: State,
+}
+
+/// Check the body of the function `function` with `params`, returning
+/// `return_type` with its typedefs resolved; `moves` tells whether a value
+/// of a type moves rather than being copied
+pub fn check_function(
+    function: &str,
+    params: &[Param],
+    return_type: Option<&Type>,
+    body: &Block,
+    moves: &dyn Fn(&Type) -> bool,
+) -> Vec<BorrowFinding> {
+    let mut checker = Checker {
+        function: function.to_string(),
+        returns: returned(return_type),
+        moves,
+        findings: Vec::new(),
+        reported: HashSet::new(),
+        reporting: true,
+        position: None,
+        decls: Vec::new(),
+        scopes: vec![Vec::new()],
+        loops: Vec::new(),
+    };
+    for param in params {
+        checker.declare(&param.name.name, Some(&param.ty), true);
+    }
+    checker.block(body, Some(BTreeMap::new()));
+    checker.findings
+}
+
+/// What a function returning `return_type` returns, when it is a reference
+/// or pointer
+fn returned(return_type: Option<&Type>) -> Option<&'static str> {
+    match return_type {
+        Some(Type::Reference { .. }) => Some("reference"),
+        Some(Type::Pointer { .. }) => Some("pointer"),
+        _ => None,
+    }
+}
+
+/// Whether the places `a` and `b` (a variable, or a field or element of
+/// one) may share memory, so borrowing both, one of them mutably, conflicts
+///
+/// Different fields of a struct are disjoint; elements are not told apart.
+pub fn places_overlap(a: &Expression, b: &Expression) -> bool {
+    match (place_path(a), place_path(b)) {
+        (Some((root_a, path_a)), Some((root_b, path_b))) => {
+            root_a == root_b
+                && path_a.iter().zip(&path_b).all(|(a, b)| match (a, b) {
+                    (Some(a), Some(b)) => a == b,
+                    _ => true,
+                })
+        }
+        _ => false,
+    }
+}
+
+/// The variable `place` is in and the fields leading to it, with `None`
+/// for each element indexed; places reached through a pointer have none
+fn place_path(place: &Expression) -> Option<(&str, Vec<Option<&str>>)> {
+    match place {
+        Expression::Ident(ident) => Some((&ident.name, Vec::new())),
+        Expression::FieldAccess { expr, field } => {
+            let (root, mut path) = place_path(expr)?;
+            path.push(Some(&field.name));
+            Some((root, path))
+        }
+        Expression::Index { expr, .. } => {
+            let (root, mut path) = place_path(expr)?;
+            path.push(None);
+            Some((root, path))
+        }
+        _ => None,
+    }
+}
+
+struct Checker<'m> {
+    function: String,
+    /// What the function returns when it is a reference or pointer
+    returns: Option<&'static str>,
+    moves: &'m dyn Fn(&Type) -> bool,
+    findings: Vec<BorrowFinding>,
+    /// Findings made so far, each reported once
+    reported: HashSet<String>,
+    /// Off while a loop body is followed the first time
+    reporting: bool,
+    /// Start of the statement being checked
+    position: Option<Position>,
+    decls: Vec<Decl>,
+    /// For each open block, the names it declares
+    scopes: Vec<Vec<(String, usize)>>,
+    loops: Vec<LoopFrame>,
+}
+
+impl Checker<'_> {
+    fn report(&mut self, message: String, help: String) {
+        if self.reporting && self.reported.insert(message.clone()) {
+            self.findings.push(BorrowFinding {
+                function: self.function.clone(),
+                position: self.position,
+                message,
+                help,
+            });
+        }
+    }
+
+    fn declare(&mut self, name: &str, ty: Option<&Type>, parameter: bool) -> usize {
+        let id = self.decls.len();
+        self.decls.push(Decl {
+            name: name.to_string(),
+            ty: ty.cloned(),
+            parameter,
+            moves: ty.is_some_and(|ty| (self.moves)(ty)),
+        });
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push((name.to_string(), id));
+        }
+        id
+    }
+
+    fn lookup(&self, name: &str) -> Option<usize> {
+        self.scopes
+            .iter()
+            .rev()
+            .flat_map(|scope| scope.iter().rev())
+            .find(|(declared, _)| declared == name)
+            .map(|&(_, id)| id)
+    }
+
+    fn block(&mut self, block: &Block, mut state: State) -> State {
+        self.scopes.push(Vec::new());
+        let enclosing = self.position;
+        for (i, stmt) in block.statements.iter().enumerate() {
+            if state.is_none() {
+                break;
+            }
+            self.position = block.positions.get(i).copied().or(enclosing);
+            state = self.statement(stmt, state);
+        }
+        self.position = enclosing;
+        self.scopes.pop();
+        state
+    }
+
+    fn statement(&mut self, stmt: &Statement, mut state: State) -> State {
+        match stmt {
+            Statement::Let { name, ty, init, .. } | Statement::Var { name, ty, init } => {
+                if let Some(init) = init {
+                    self.moved_expression(init, &mut state);
+                }
+                // A struct or compound literal gives a variable declared
+                // without a type the literal's type
+                let ty = ty.as_ref().or(match init {
+                    Some(Expression::StructInit { ty, .. }) => Some(ty),
+                    Some(Expression::Cast { ty, expr })
+                        if matches!(expr.as_ref(), Expression::StructInit { .. }) =>
+                    {
+                        Some(ty)
+                    }
+                    _ => None,
+                });
+                let id = self.declare(&name.name, ty, false);
+                if let Some(moved) = &mut state {
+                    moved.remove(&id);
+                }
+                state
+            }
+            Statement::Const { name, value, .. } => {
+                self.expression(value, &mut state);
+                self.declare(&name.name, None, false);
+                state
+            }
+            Statement::Expr(expr) => {
+                self.expression(expr, &mut state);
+                state
+            }
+            Statement::Return(value) => {
+                if let Some(value) = value {
+                    self.check_returned_reference(value);
+                    self.moved_expression(value, &mut state);
+                }
+                None
+            }
+            Statement::If {
+                condition,
+                then_block,
+                else_block,
+            } => {
+                self.expression(condition, &mut state);
+                let then_state = self.block(then_block, state.clone());
+                let else_state = match else_block {
+                    Some(else_block) => self.block(else_block, state),
+                    None => state,
+                };
+                join(then_state, else_state)
+            }
+            Statement::While {
+                label,
+                condition,
+                body,
+            } => {
+                let endless = matches!(
+                    condition,
+                    Expression::Literal(crate::ast::Literal::Bool(true))
+                );
+                self.follow_loop(label, state, endless, |checker, mut head| {
+                    checker.expression(condition, &mut head);
+                    let exit = head.clone();
+                    (checker.block(body, head), exit)
+                })
+            }
+            Statement::DoWhile { body, condition } => {
+                self.follow_loop(&None, state, false, |checker, head| {
+                    let mut end = checker.block(body, head);
+                    let continues = checker
+                        .loops
+                        .last_mut()
+                        .and_then(|frame| frame.continues.take());
+                    end = join(end, continues);
+                    checker.expression(condition, &mut end);
+                    (end.clone(), end)
+                })
+            }
+            Statement::For {
+                label,
+                init,
+                condition,
+                increment,
+                body,
+            } => {
+                self.scopes.push(Vec::new());
+                let state = self.statement(init, state);
+                let after = self.follow_loop(label, state, false, |checker, mut head| {
+                    checker.expression(condition, &mut head);
+                    let exit = head.clone();
+                    let end = checker.block(body, head);
+                    let continues = checker
+                        .loops
+                        .last_mut()
+                        .and_then(|frame| frame.continues.take());
+                    let mut end = join(end, continues);
+                    checker.expression(increment, &mut end);
+                    (end, exit)
+                });
+                self.scopes.pop();
+                after
+            }
+            Statement::ForIn {
+                label,
+                var,
+                iter,
+                body,
+            } => {
+                self.expression(iter, &mut state);
+                self.follow_loop(label, state, false, |checker, head| {
+                    let exit = head.clone();
+                    checker.scopes.push(Vec::new());
+                    checker.declare(&var.name, None, false);
+                    let end = checker.block(body, head);
+                    checker.scopes.pop();
+                    (end, exit)
+                })
+            }
+            Statement::Switch {
+                expr,
+                cases,
+                default,
+            } => {
+                self.expression(expr, &mut state);
+                let mut after = match default {
+                    Some(default) => self.block(default, state.clone()),
+                    None => state.clone(),
+                };
+                for case in cases {
+                    let case_state = self.block(&case.body, state.clone());
+                    after = join(after, case_state);
+                }
+                after
+            }
+            Statement::Break(label) | Statement::Continue(label) => {
+                let target = self.loops.iter().rposition(|frame| match label {
+                    Some(label) => frame.label.as_deref() == Some(label.name.as_str()),
+                    None => true,
+                });
+                if let Some(target) = target {
+                    let frame = &mut self.loops[target];
+                    if matches!(stmt, Statement::Break(_)) {
+                        frame.breaks = join(frame.breaks.take(), state);
+                    } else {
+                        frame.continues = join(frame.continues.take(), state);
+                    }
+                }
+                None
+            }
+            Statement::Unsafe(block) | Statement::Block(block) => self.block(block, state),
+            Statement::StaticAssert(_) => state,
+            Statement::NestedFunction { name, .. } => {
+                self.declare(&name.name, None, false);
+                state
+            }
+        }
+    }
+
+    /// Follow a loop whose body `run` checks from the state at its head,
+    /// returning the state at the end of the body and where the loop exits
+    ///
+    /// The body is followed once to find what it moves, then again from
+    /// the head as the next iteration sees it, reporting what it finds.
+    fn follow_loop(
+        &mut self,
+        label: &Option<crate::ast::Ident>,
+        entry: State,
+        endless: bool,
+        mut run: impl FnMut(&mut Self, State) -> (State, State),
+    ) -> State {
+        self.loops.push(LoopFrame {
+            label: label.as_ref().map(|label| label.name.clone()),
+            breaks: None,
+            continues: None,
+        });
+        let reporting = std::mem::replace(&mut self.reporting, false);
+        let (end, _) = run(self, entry.clone());
+        self.reporting = reporting;
+        let frame = self.loops.last_mut().expect("loop frame");
+        let continues = frame.continues.take();
+        frame.breaks = None;
+        let head = join(entry, join(end, continues));
+        let (_, exit) = run(self, head);
+        let frame = self.loops.pop().expect("loop frame");
+        let exit = if endless { None } else { exit };
+        join(exit, frame.breaks)
+    }
+
+    /// Check an expression whose value is moved out, as an initializer,
+    /// argument or returned value is
+    fn moved_expression(&mut self, expr: &Expression, state: &mut State) {
+        match expr {
+            Expression::Ident(ident) => {
+                self.use_variable(&ident.name, state);
+                let Some(id) = self.lookup(&ident.name) else {
+                    return;
+                };
+                if let (true, Some(moved)) = (self.decls[id].moves, state.as_mut()) {
+                    moved.entry(id).or_insert(self.position);
+                }
+            }
+            Expression::Ternary {
+                condition,
+                then_expr,
+                else_expr,
+            } => {
+                self.expression(condition, state);
+                let mut else_state = state.clone();
+                self.moved_expression(then_expr, state);
+                self.moved_expression(else_expr, &mut else_state);
+                *state = join(state.take(), else_state);
+            }
+            _ => self.expression(expr, state),
+        }
+    }
+
+    /// Report a read of `name` after it was moved
+    fn use_variable(&mut self, name: &str, state: &State) {
+        let Some(id) = self.lookup(name) else {
+            return;
+        };
+        let Some(moved_at) = state.as_ref().and_then(|moved| moved.get(&id)) else {
+            return;
+        };
+        let decl = &self.decls[id];
+        let message = match (moved_at, self.position) {
+            (Some(moved_at), Some(position)) if moved_at.line >= position.line => format!(
+                "'{}' is used after it was moved on line {}, in an earlier iteration of the loop",
+                name, moved_at.line
+            ),
+            (Some(moved_at), _) => format!(
+                "'{}' is used after it was moved on line {}",
+                name, moved_at.line
+            ),
+            (None, _) => format!("'{}' is used after it was moved", name),
+        };
+        let type_name = match &decl.ty {
+            Some(Type::Ident(ty)) => ty.name.clone(),
+            _ => "its type".to_string(),
+        };
+        let help = format!(
+            "pass a reference, `&{}`, instead of moving it, or give {} `#[derive(Clone, Copy)]` if it is plain data",
+            decl.name, type_name
+        );
+        self.report(message, help);
+    }
+
+    /// Report `return &local;`, which would outlive the local
+    fn check_returned_reference(&mut self, mut value: &Expression) {
+        // `(int*)&local` is the pointer `&local` converted
+        while let Expression::Cast { expr, .. } = value {
+            value = expr;
+        }
+        let Expression::Unary {
+            op: UnaryOp::Ref,
+            expr,
+        } = value
+        else {
+            return;
+        };
+        let Some(returned) = self.returns else {
+            return;
+        };
+        let Some((root, _)) = place_path(expr) else {
+            return;
+        };
+        let Some(id) = self.lookup(root) else {
+            return;
+        };
+        let decl = &self.decls[id];
+        // A field of what a reference refers to lives on after the function
+        let owned = match &decl.ty {
+            Some(Type::Reference { .. } | Type::Pointer { .. } | Type::Slice { .. }) => false,
+            Some(_) => true,
+            None => matches!(expr.as_ref(), Expression::Ident(_)),
+        };
+        if !owned {
+            return;
+        }
+        let kind = if decl.parameter {
+            "parameter"
+        } else {
+            "local variable"
+        };
+        let message = format!(
+            "returns a {} to {} '{}', which is dropped when the function returns",
+            returned, kind, decl.name
+        );
+        let help = if decl.parameter {
+            format!(
+                "take '{}' by reference, or return the value instead",
+                decl.name
+            )
+        } else {
+            "return the value instead, or store it where the caller can reach".to_string()
+        };
+        self.report(message, help);
+    }
+
+    fn expression(&mut self, expr: &Expression, state: &mut State) {
+        match expr {
+            Expression::Ident(ident) => self.use_variable(&ident.name, state),
+            Expression::Binary { op, left, right } if *op == BinaryOp::Assign => {
+                self.moved_expression(right, state);
+                match left.as_ref() {
+                    // Assigning a variable gives it a value again
+                    Expression::Ident(ident) => {
+                        if let (Some(id), Some(moved)) = (self.lookup(&ident.name), state.as_mut())
+                        {
+                            moved.remove(&id);
+                        }
+                    }
+                    left => self.expression(left, state),
+                }
+            }
+            Expression::Call { func, args } => {
+                self.expression(func, state);
+                for arg in args {
+                    self.moved_expression(arg, state);
+                }
+            }
+            Expression::MethodCall { receiver, args, .. } => {
+                self.expression(receiver, state);
+                for arg in args {
+                    self.moved_expression(arg, state);
+                }
+            }
+            Expression::TypeScopedCall { args, .. }
+            | Expression::ExplicitGenericCall { args, .. } => {
+                for arg in args {
+                    self.moved_expression(arg, state);
+                }
+            }
+            Expression::StructInit { fields, .. } => {
+                for (_, value) in fields {
+                    self.moved_expression(value, state);
+                }
+            }
+            Expression::ArrayLit { elements } | Expression::TupleLit { elements } => {
+                for element in elements {
+                    self.moved_expression(element, state);
+                }
+            }
+            Expression::Ternary {
+                condition,
+                then_expr,
+                else_expr,
+            } => {
+                self.expression(condition, state);
+                let mut else_state = state.clone();
+                self.expression(then_expr, state);
+                self.expression(else_expr, &mut else_state);
+                *state = join(state.take(), else_state);
+            }
+            Expression::Match { expr, arms } => {
+                self.expression(expr, state);
+                let entry = state.clone();
+                let mut after: State = None;
+                for arm in arms {
+                    let mut bound = HashSet::new();
+                    pattern_bindings(&arm.pattern, &mut bound);
+                    self.scopes.push(Vec::new());
+                    for name in &bound {
+                        self.declare(name, None, false);
+                    }
+                    let mut arm_state = entry.clone();
+                    self.expression(&arm.body, &mut arm_state);
+                    self.scopes.pop();
+                    after = join(after, arm_state);
+                }
+                if !arms.is_empty() {
+                    *state = after;
+                }
+            }
+            // The body sees what was moved before the lambda is made
+            Expression::Lambda {
+                params,
+                return_type,
+                body,
+                ..
+            } => {
+                let returns = std::mem::replace(&mut self.returns, returned(return_type.as_ref()));
+                self.scopes.push(Vec::new());
+                for param in params {
+                    self.declare(&param.name.name, Some(&param.ty), true);
+                }
+                self.block(body, state.clone());
+                self.scopes.pop();
+                self.returns = returns;
+            }
+            // Not evaluated, or opaque
+            Expression::SizeofExpr { .. }
+            | Expression::AlignofExpr { .. }
+            | Expression::MacroCall { .. }
+            | Expression::RustBlock { .. } => {}
+            _ => {
+                for child in crate::macros::child_expressions(expr) {
+                    self.expression(child, state);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Item;
+    use crate::parser::Parser;
+
+    fn findings(source: &str) -> Vec<String> {
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+        let moves = |ty: &Type| matches!(ty, Type::Ident(name) if name.name == "Point");
+        file.items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Function(func) => Some(func),
+                _ => None,
+            })
+            .flat_map(|func| {
+                check_function(
+                    &func.name.name,
+                    &func.params,
+                    func.return_type.as_ref(),
+                    &func.body,
+                    &moves,
+                )
+            })
+            .map(|finding| finding.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_use_after_move() {
+        assert_eq!(
+            findings(
+                "struct Point { int x; }\nint show(Point p) {\n    return p.x;\n}\nint main() {\n    let Point p = (Point){ .x = 1 };\n    let Point q = p;\n    return p.x + q.x;\n}\n"
+            ),
+            ["in function 'main', line 8: 'p' is used after it was moved on line 7"]
+        );
+        // A compound literal types a local declared without one
+        assert_eq!(
+            findings(
+                "struct Point { int x; }\nint show(Point p) {\n    return p.x;\n}\nint main() {\n    let c = (Point){ .x = 1 };\n    show(c);\n    return c.x;\n}\n"
+            ),
+            ["in function 'main', line 8: 'c' is used after it was moved on line 7"]
+        );
+        // Assigning again, moving on a path that returns, and copied types
+        // are fine
+        assert!(findings(
+            "struct Point { int x; }\nint show(Point p) {\n    return p.x;\n}\nint main(bool c) {\n    var Point p = (Point){ .x = 1 };\n    show(p);\n    p = (Point){ .x = 2 };\n    if (c) {\n        return show(p);\n    }\n    int n = 1;\n    int m = n;\n    return p.x + n + m;\n}\n"
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_moves_in_branches_and_loops() {
+        assert_eq!(
+            findings(
+                "struct Point { int x; }\nint show(Point p) {\n    return p.x;\n}\nint f(bool c, Point p) {\n    if (c) {\n        show(p);\n    }\n    return p.x;\n}\nvoid g(Point p) {\n    for (int i = 0; i < 3; i++) {\n        show(p);\n    }\n}\n"
+            ),
+            [
+                "in function 'f', line 9: 'p' is used after it was moved on line 7",
+                "in function 'g', line 13: 'p' is used after it was moved on line 13, in an earlier iteration of the loop",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_returned_reference_to_local() {
+        assert_eq!(
+            findings(
+                "struct Point { int x; }\nint* largest() {\n    int best = 0;\n    return (int*)&best;\n}\nint* field(&Point p) {\n    return &p.x;\n}\nint* own(Point p) {\n    return &p.x;\n}\n"
+            ),
+            [
+                "in function 'largest', line 4: returns a pointer to local variable 'best', which is dropped when the function returns",
+                "in function 'own', line 10: returns a pointer to parameter 'p', which is dropped when the function returns",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_places_overlap() {
+        let parse = |place: &str| {
+            let file = Parser::new(&format!("void f() {{\n    {};\n}}\n", place))
+                .unwrap()
+                .parse_file()
+                .unwrap();
+            match &file.items[0] {
+                Item::Function(func) => match &func.body.statements[0] {
+                    Statement::Expr(expr) => expr.clone(),
+                    other => panic!("expected an expression, got {:?}", other),
+                },
+                other => panic!("expected a function, got {:?}", other),
+            }
+        };
+        assert!(places_overlap(&parse("a"), &parse("a.items[0]")));
+        assert!(places_overlap(&parse("a[0]"), &parse("a[1]")));
+        assert!(!places_overlap(&parse("a.x"), &parse("a.y")));
+        assert!(!places_overlap(&parse("a"), &parse("b")));
+    }
+
+    #[test]
+    fn test_use_after_move_in_lambda() {
+        assert_eq!(
+            findings(
+                "struct Point { int x; }\nint show(Point p) {\n    return p.x;\n}\nint main() {\n    let Point p = (Point){ .x = 1 };\n    show(p);\n    let get = fn(Point q) -> int {\n        return p.x + show(q);\n    };\n    return 0;\n}\n"
+            ),
+            ["in function 'main', line 9: 'p' is used after it was moved on line 7"]
+        );
+    }
+}
@@ -0,0 +1,291 @@
+// Copyright (c) 2026 Mark Ferrell
+// Licensed under the MIT License. See LICENSE.txt in the project root.
+
+//! `#[cfg(...)]` and `#[target_feature(...)]` attributes.
+//!
+//! Both are forwarded to the generated Rust unchanged. With `--target`, the
+//! compiler also evaluates each item's `#[cfg]` against the target's
+//! configuration, as reported by `rustc --print cfg`, and drops the items it
+//! rules out before semantic analysis. Per-target variants of one function
+//! can then share a name:
+//!
+//! ```text
+//! #[cfg(target_os = "linux")]
+//! u64 page_size() { return 4096; }
+//!
+//! #[cfg(not(target_os = "linux"))]
+//! u64 page_size() { return 16384; }
+//! ```
+
+use crate::ast::{Attribute, AttributeArg, File, Item, Literal};
+use std::collections::HashSet;
+use std::process::Command;
+
+/// The configuration options set for a target: bare names such as `unix`
+/// and `name = "value"` pairs such as `target_os = "linux"`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TargetCfg {
+    options: HashSet<(String, Option<String>)>,
+}
+
+impl TargetCfg {
+    /// Ask rustc for the configuration of `target`
+    pub fn query(target: &str) -> Result<Self, String> {
+        let output = Command::new("rustc")
+            .args(["--print", "cfg", "--target", target])
+            .output()
+            .map_err(|e| format!("Failed to execute rustc: {}", e))?;
+        if !output.status.success() {
+            return Err(format!(
+                "cannot read the configuration of target '{}': {}",
+                target,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(Self::parse(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    /// Parse the output of `rustc --print cfg`: one `name` or
+    /// `name="value"` per line
+    pub fn parse(text: &str) -> Self {
+        let options = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| match line.split_once('=') {
+                Some((name, value)) => {
+                    (name.to_string(), Some(value.trim_matches('"').to_string()))
+                }
+                None => (line.to_string(), None),
+            })
+            .collect();
+        Self { options }
+    }
+
+    /// Whether the bare option `name`, such as `unix`, is set
+    pub fn is_set(&self, name: &str) -> bool {
+        self.options.contains(&(name.to_string(), None))
+    }
+
+    /// Value of the option `name`, such as `target_os`, if it is set
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|(option, value)| option == name && value.is_some())
+            .and_then(|(_, value)| value.as_deref())
+    }
+
+    /// Whether the `#[cfg]` predicate `predicate` holds; `check_cfg` must
+    /// accept the attribute first
+    pub fn evaluate(&self, predicate: &AttributeArg) -> bool {
+        match predicate {
+            AttributeArg::Ident(name) => self.options.contains(&(name.name.clone(), None)),
+            AttributeArg::NameValue {
+                name,
+                value: Literal::String(value),
+            } => self
+                .options
+                .contains(&(name.name.clone(), Some(value.clone()))),
+            AttributeArg::List { name, args } => match name.name.as_str() {
+                "all" => args.iter().all(|arg| self.evaluate(arg)),
+                "any" => args.iter().any(|arg| self.evaluate(arg)),
+                "not" => !args.iter().all(|arg| self.evaluate(arg)),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Whether every `#[cfg]` among `attributes` holds
+    pub fn enables(&self, attributes: &[Attribute]) -> bool {
+        attributes
+            .iter()
+            .filter(|attr| attr.name.name == "cfg")
+            .all(|attr| attr.args.iter().all(|arg| self.evaluate(arg)))
+    }
+
+    /// Remove the items of `file`, and the methods of its structs, that a
+    /// `#[cfg]` rules out for this target; returns the names removed
+    pub fn strip_disabled_items(&self, file: &mut File) -> Vec<String> {
+        let mut removed = Vec::new();
+        self.strip_items(&mut file.items, &mut removed);
+        removed
+    }
+
+    fn strip_items(&self, items: &mut Vec<Item>, removed: &mut Vec<String>) {
+        items.retain(|item| {
+            let (name, attributes) = match item {
+                Item::Function(func) => (&func.name, &func.attributes),
+                Item::Struct(struct_def) => (&struct_def.name, &struct_def.attributes),
+                Item::Enum(enum_def) => (&enum_def.name, &enum_def.attributes),
+                Item::Trait(trait_def) => (&trait_def.name, &trait_def.attributes),
+                _ => return true,
+            };
+            let enabled = self.enables(attributes);
+            if !enabled {
+                removed.push(name.name.clone());
+            }
+            enabled
+        });
+        for item in items {
+            match item {
+                Item::Struct(struct_def) => struct_def.methods.retain(|method| {
+                    let enabled = self.enables(&method.attributes);
+                    if !enabled {
+                        removed.push(format!("{}.{}", struct_def.name.name, method.name.name));
+                    }
+                    enabled
+                }),
+                Item::Namespace(namespace) => self.strip_items(&mut namespace.items, removed),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Check the predicate of a `#[cfg]`: one option name, `name = "value"`,
+/// or `all(...)`, `any(...)` or `not(...)` of predicates
+pub fn check_cfg(attr: &Attribute) -> Result<(), String> {
+    match attr.args.as_slice() {
+        [predicate] => check_predicate(predicate),
+        _ => {
+            Err("#[cfg] takes a single predicate, as in #[cfg(target_os = \"linux\")]".to_string())
+        }
+    }
+}
+
+fn check_predicate(predicate: &AttributeArg) -> Result<(), String> {
+    match predicate {
+        AttributeArg::Ident(_)
+        | AttributeArg::NameValue {
+            value: Literal::String(_),
+            ..
+        } => Ok(()),
+        AttributeArg::NameValue { name, .. } => Err(format!(
+            "the value of '{}' in #[cfg] must be a string",
+            name.name
+        )),
+        AttributeArg::List { name, args } => match name.name.as_str() {
+            "all" | "any" => args.iter().try_for_each(check_predicate),
+            "not" if args.len() == 1 => check_predicate(&args[0]),
+            "not" => Err("not() in #[cfg] takes a single predicate".to_string()),
+            other => Err(format!(
+                "unknown #[cfg] operator '{}'; expected all, any or not",
+                other
+            )),
+        },
+        AttributeArg::Literal(_) => {
+            Err("#[cfg] predicates are option names, not literals".to_string())
+        }
+    }
+}
+
+/// Check a `#[target_feature(enable = "avx2,fma")]`: one or more `enable`
+/// arguments naming features
+pub fn check_target_feature(attr: &Attribute) -> Result<(), String> {
+    if attr.args.is_empty() {
+        return Err("#[target_feature] needs enable = \"feature\"".to_string());
+    }
+    for arg in &attr.args {
+        match arg {
+            AttributeArg::NameValue {
+                name,
+                value: Literal::String(features),
+            } if name.name == "enable" => {
+                let valid = features.split(',').all(|feature| {
+                    !feature.is_empty()
+                        && feature
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'))
+                });
+                if !valid {
+                    return Err(format!(
+                        "invalid feature list \"{}\" in #[target_feature]; expected names separated by commas",
+                        features
+                    ));
+                }
+            }
+            _ => {
+                return Err(
+                    "#[target_feature] takes only enable = \"feature\" arguments".to_string(),
+                )
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINUX: &str = "debug_assertions\ntarget_arch=\"x86_64\"\ntarget_os=\"linux\"\nunix\n";
+
+    fn attribute(source: &str) -> Attribute {
+        let file = crate::parser::Parser::new(&format!("{}\nvoid f() {{}}\n", source))
+            .unwrap()
+            .parse_file()
+            .unwrap();
+        match &file.items[0] {
+            Item::Function(func) => func.attributes[0].clone(),
+            item => panic!("expected a function, found {:?}", item),
+        }
+    }
+
+    #[test]
+    fn test_evaluate_predicates() {
+        let cfg = TargetCfg::parse(LINUX);
+        let holds = |source: &str| {
+            let attr = attribute(source);
+            check_cfg(&attr).unwrap();
+            cfg.enables(&[attr])
+        };
+        assert!(holds("#[cfg(unix)]"));
+        assert!(!holds("#[cfg(windows)]"));
+        assert!(holds("#[cfg(target_os = \"linux\")]"));
+        assert!(holds(
+            "#[cfg(all(unix, target_arch = \"x86_64\", not(target_os = \"macos\")))]"
+        ));
+        assert!(!holds("#[cfg(any(windows, target_arch = \"arm\"))]"));
+        assert!(!holds("#[cfg(any())]"));
+        assert!(holds("#[cfg(all())]"));
+    }
+
+    #[test]
+    fn test_malformed_attributes() {
+        let cfg_error = |source: &str| check_cfg(&attribute(source)).unwrap_err();
+        assert_eq!(
+            cfg_error("#[cfg(unix, windows)]"),
+            "#[cfg] takes a single predicate, as in #[cfg(target_os = \"linux\")]"
+        );
+        assert_eq!(
+            cfg_error("#[cfg(either(unix))]"),
+            "unknown #[cfg] operator 'either'; expected all, any or not"
+        );
+        assert_eq!(
+            cfg_error("#[cfg(target_pointer_width = 64)]"),
+            "the value of 'target_pointer_width' in #[cfg] must be a string"
+        );
+
+        let feature = |source: &str| check_target_feature(&attribute(source));
+        assert_eq!(feature("#[target_feature(enable = \"avx2,fma\")]"), Ok(()));
+        assert!(feature("#[target_feature(enable = \"avx2,\")]").is_err());
+        assert!(feature("#[target_feature(avx2)]").is_err());
+    }
+
+    #[test]
+    fn test_strip_disabled_items() {
+        let source = "#[cfg(windows)]\nint page() { return 1; }\n#[cfg(unix)]\nint page() { return 2; }\nstruct Clock {\n    #[cfg(target_os = \"macos\")]\n    int mach(&self) { return 0; }\n}\n";
+        let mut file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+        let removed = TargetCfg::parse(LINUX).strip_disabled_items(&mut file);
+        assert_eq!(removed, vec!["page", "Clock.mach"]);
+        assert_eq!(file.items.len(), 2);
+        let Item::Struct(clock) = &file.items[1] else {
+            panic!("expected the struct");
+        };
+        assert!(clock.methods.is_empty());
+    }
+}
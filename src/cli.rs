@@ -3,17 +3,26 @@
 
 //! Command-line interface module for crustyc compiler.
 
+use crate::compile_db::{CompilationDatabase, CompileCommand};
+use crate::error::{CompilerError, Diagnostic};
+use crate::lints::{Level, LevelSource, LintLevels, Warning};
 use clap::{Parser, ValueEnum};
 use std::path::{Path, PathBuf};
 
 /// Crusty compiler - bidirectional transpiler between Crusty and Rust
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 #[command(name = "crustyc")]
 #[command(author, version, about, long_about = None)]
 pub struct CompilerOptions {
     /// Input source file path
     pub input_file: PathBuf,
 
+    /// Further Crusty files, or directories of them, compiled into the same
+    /// crate as modules that share their public items with each other and
+    /// with the input file
+    #[arg(value_name = "MODULES")]
+    pub inputs: Vec<PathBuf>,
+
     /// Output file path (like rustc -o)
     #[arg(short = 'o', long = "out")]
     pub output_file: Option<PathBuf>,
@@ -22,7 +31,7 @@ pub struct CompilerOptions {
     #[arg(long = "out-dir")]
     pub out_dir: Option<PathBuf>,
 
-    /// Output mode: what to emit (auto, rust, binary, ast)
+    /// Output mode: what to emit
     /// Auto mode detects from output file extension or defaults to binary
     #[arg(long = "emit", default_value = "auto")]
     pub emit: EmitMode,
@@ -39,6 +48,297 @@ pub struct CompilerOptions {
     /// Skip rustc invocation (only generate code)
     #[arg(long = "no-compile")]
     pub no_compile: bool,
+
+    /// Only check the source: parse and analyze it, report diagnostics and
+    /// write nothing
+    #[arg(long = "check", conflicts_with_all = ["output_file", "emit", "no_compile"])]
+    pub check_only: bool,
+
+    /// Report the functions, types, macros and globals that nothing reachable
+    /// from the entry point uses, across every imported module, as text (the
+    /// default when the flag is given without a value) or JSON; writes nothing
+    #[arg(
+        long = "dead-code",
+        value_name = "FORMAT",
+        num_args = 0..=1,
+        default_missing_value = "text",
+        conflicts_with_all = ["output_file", "emit", "no_compile", "check_only"]
+    )]
+    pub dead_code: Option<crate::deadcode::DeadCodeFormat>,
+
+    /// Write each module to its own `<name>.rs` beside the generated file,
+    /// declared there with `pub mod name;`, instead of inline
+    #[arg(long = "split-modules")]
+    pub split_modules: bool,
+
+    /// Group generated items into sections (imports, macros, types, constants,
+    /// functions) instead of keeping source order
+    #[arg(long = "group-items")]
+    pub group_items: bool,
+
+    /// Omit the provenance header from generated code
+    #[arg(long = "no-header")]
+    pub no_header: bool,
+
+    /// Additional lint to silence with #![allow(...)] in generated Rust (repeatable)
+    #[arg(long = "allow-lint", value_name = "LINT")]
+    pub allow_lints: Vec<String>,
+
+    /// Do not silence the default lints (unused_parens) in generated Rust
+    #[arg(long = "no-default-allows")]
+    pub no_default_allows: bool,
+
+    /// Run clippy on the generated Rust and report its machine-applicable
+    /// suggestions as warnings against the Crusty source
+    #[arg(long = "clippy")]
+    pub clippy: bool,
+
+    /// Write a compilation database (default: compile_commands.json) describing
+    /// every compiled file, for external tools and the language server
+    #[arg(
+        long = "compile-db",
+        value_name = "PATH",
+        num_args = 0..=1,
+        default_missing_value = crate::compile_db::COMPILE_DB_FILE_NAME
+    )]
+    pub compile_db: Option<PathBuf>,
+
+    /// Write how long each phase, file and item took (default:
+    /// crustyc.folded) as folded stacks for flamegraph tools, or as a Chrome
+    /// trace when the path ends in .json
+    #[arg(
+        long = "self-profile",
+        value_name = "PATH",
+        num_args = 0..=1,
+        default_missing_value = crate::timings::PROFILE_FILE_NAME
+    )]
+    pub self_profile: Option<PathBuf>,
+
+    /// Allocate struct and array locals of at least this many bytes on the
+    /// heap with Box::new (0 disables)
+    #[arg(
+        long = "heap-threshold",
+        value_name = "BYTES",
+        default_value_t = crate::codegen::DEFAULT_HEAP_THRESHOLD
+    )]
+    pub heap_threshold: usize,
+
+    /// Stop reporting semantic errors after this many (0 reports them all)
+    #[arg(
+        long = "error-limit",
+        value_name = "N",
+        default_value_t = crate::semantic::DEFAULT_ERROR_LIMIT
+    )]
+    pub error_limit: usize,
+
+    /// Fail when the program's syntax tree has more than N nodes once its
+    /// macros are expanded (0 for no limit)
+    #[arg(
+        long = "max-ast-nodes",
+        value_name = "N",
+        default_value_t = crate::limits::DEFAULT_MAX_AST_NODES
+    )]
+    pub max_ast_nodes: usize,
+
+    /// Fail a macro use whose expansion produces more than N tokens (0 for
+    /// no limit)
+    #[arg(
+        long = "max-expansion-tokens",
+        value_name = "N",
+        default_value_t = crate::limits::DEFAULT_MAX_EXPANSION_TOKENS
+    )]
+    pub max_expansion_tokens: usize,
+
+    /// Give up evaluating a constant expression after N steps (0 for no
+    /// limit)
+    #[arg(
+        long = "max-const-eval-steps",
+        value_name = "N",
+        default_value_t = crate::limits::DEFAULT_MAX_CONST_EVAL_STEPS
+    )]
+    pub max_const_eval_steps: usize,
+
+    /// Convert self tail-recursive functions into loops so deep recursion
+    /// cannot overflow the stack
+    #[arg(long = "tail-calls")]
+    pub tail_calls: bool,
+
+    /// Turn #define macros with parameters whose body is an expression into
+    /// inline functions, where the types of their uses can be inferred
+    #[arg(long = "inline-macros")]
+    pub inline_macros: bool,
+
+    /// If/else chains testing one variable against constants: warn about
+    /// each with a suggested switch (warn; the default when the flag is
+    /// given without a value), or rewrite them into switches (rewrite)
+    #[arg(
+        long = "switch-ladders",
+        value_name = "MODE",
+        num_args = 0..=1,
+        default_value = "off",
+        default_missing_value = "warn"
+    )]
+    pub switch_ladders: crate::switch_ladders::SwitchLadders,
+
+    /// Accept enum variants written without their enum (`Red` for
+    /// `Color.Red`), warning about each one
+    #[arg(long = "unqualified-variants")]
+    pub unqualified_variants: bool,
+
+    /// Integers as conditions (`if (n)`, `!n`): rejected with a suggested
+    /// comparison (strict), or tested against zero as C does (compat; the
+    /// default when the flag is given without a value)
+    #[arg(
+        long = "int-conditions",
+        value_name = "MODE",
+        num_args = 0..=1,
+        default_value = "strict",
+        default_missing_value = "compat"
+    )]
+    pub int_conditions: crate::semantic::IntConditions,
+
+    /// Reads of unassigned variables, repeated assignments of a `let` and
+    /// unreachable statements: reported as warnings (warn), or as errors
+    /// (deny; the default when the flag is given without a value)
+    #[arg(
+        long = "flow-checks",
+        value_name = "LEVEL",
+        num_args = 0..=1,
+        default_value = "warn",
+        default_missing_value = "deny"
+    )]
+    pub flow_checks: crate::dataflow::FlowChecks,
+
+    /// Do not report the lint LINT (repeatable)
+    #[arg(short = 'A', long = "allow", value_name = "LINT")]
+    pub allow: Vec<String>,
+
+    /// Report the lint LINT as a warning (repeatable)
+    #[arg(short = 'W', long = "warn", value_name = "LINT")]
+    pub warn: Vec<String>,
+
+    /// Report the lint LINT as an error, failing the compilation
+    /// (repeatable); wins over --warn, which wins over --allow. The short
+    /// form is -E, since -D defines a macro
+    #[arg(short = 'E', long = "deny", value_name = "LINT")]
+    pub deny: Vec<String>,
+
+    /// Print generated Rust line counts per function and flag functions that
+    /// grew beyond RATIO times their Crusty size (default: 3)
+    #[arg(
+        long = "report-size",
+        value_name = "RATIO",
+        num_args = 0..=1,
+        default_missing_value = "3"
+    )]
+    pub report_size: Option<f64>,
+
+    /// Quote the Crusty source of each function and type in comments above
+    /// its generated code
+    #[arg(long = "source-comments")]
+    pub source_comments: bool,
+
+    /// Rust representation of L"..." wide string literals: a Vec<u16>
+    /// (utf16) or a &U16CStr from the widestring crate (widestring)
+    #[arg(long = "wide-strings", value_name = "MAPPING", default_value = "utf16")]
+    pub wide_strings: crate::codegen::WideStringMapping,
+
+    /// Define NAME for #ifdef and #ifndef (repeatable)
+    #[arg(short = 'D', long = "define", value_name = "NAME")]
+    pub defines: Vec<String>,
+
+    /// Build a library of the public items instead of an executable, so no
+    /// `main` is needed: an rlib (the default when the flag is given without
+    /// a value) or a cdylib
+    #[arg(long = "lib", value_name = "KIND", num_args = 0..=1, default_missing_value = "rlib")]
+    pub lib: Option<crate::rustc::LibraryKind>,
+
+    /// Start the program with function NAME instead of `main`; it takes no
+    /// parameters and returns void or an integer exit status
+    #[arg(long = "entry", value_name = "NAME", conflicts_with = "lib")]
+    pub entry: Option<String>,
+
+    /// Generate `#![no_std]` code for targets without an operating system
+    /// and build it into an object file for the target's linker, with the
+    /// entry function exported as the C symbol `main`
+    #[arg(long = "freestanding", conflicts_with = "lib")]
+    pub freestanding: bool,
+
+    /// Build the `#[bench]` functions into an optimized harness instead of
+    /// the program, run it, and print the time a call of each takes
+    #[arg(
+        long = "bench",
+        conflicts_with_all = ["lib", "entry", "freestanding", "target", "emit", "check_only", "no_compile"]
+    )]
+    pub bench: bool,
+
+    /// Build the `#[test]` functions into a test harness instrumented for
+    /// coverage, run it, and print how often each Crusty line ran
+    #[arg(
+        long = "coverage",
+        conflicts_with_all = ["lib", "entry", "freestanding", "target", "emit", "check_only", "no_compile", "bench"]
+    )]
+    pub coverage: bool,
+
+    /// Write a JSON source map (`main.rs.map` for `main.rs`) mapping each
+    /// line of the generated Rust back to the Crusty file, line and column
+    /// it came from
+    #[arg(long = "emit-sourcemap")]
+    pub emit_sourcemap: bool,
+
+    /// Build with debug info for gdb and lldb, mark `#[inline]` functions
+    /// `#[track_caller]` so their panics report the line that called them,
+    /// and write the source map as `--emit-sourcemap` does
+    #[arg(short = 'g', long = "debug")]
+    pub debug: bool,
+
+    /// What a failed run-time check, such as an array bound, does: panic,
+    /// abort the process without unwinding (abort), or return an error from
+    /// functions returning a fallible type and panic elsewhere (result)
+    #[arg(long = "panic", value_name = "POLICY", default_value = "panic")]
+    pub panic: crate::codegen::PanicPolicy,
+
+    /// Generate the code of each file twice and fail if the two differ,
+    /// naming the first item that does
+    #[arg(long = "check-determinism")]
+    pub check_determinism: bool,
+
+    /// Write a hash of the code generated for each item (`main.rs.hashes`
+    /// for `main.rs`), for build caches to tell which items changed
+    #[arg(long = "emit-item-hashes")]
+    pub emit_item_hashes: bool,
+
+    /// Build for the target triple TARGET, skipping the items whose
+    /// `#[cfg(...)]` does not hold for it
+    #[arg(long = "target", value_name = "TARGET")]
+    pub target: Option<String>,
+
+    /// Color diagnostics: auto (when stderr is a terminal and NO_COLOR is
+    /// not set), always or never
+    #[arg(long = "color", value_name = "WHEN", default_value = "auto")]
+    pub color: ColorChoice,
+}
+
+/// When diagnostics are colored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether to color output written to stderr
+    pub fn enabled(self) -> bool {
+        use std::io::IsTerminal;
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                std::env::var_os("NO_COLOR").is_none() && std::io::stderr().is_terminal()
+            }
+        }
+    }
 }
 
 /// Output mode for the compiler
@@ -52,6 +352,15 @@ pub enum EmitMode {
     Binary,
     /// Output AST in human-readable format
     Ast,
+    /// Output AST as JSON
+    AstJson,
+    /// Output AST and symbol table as a binary archive for external analyzers
+    AstBin,
+    /// Output the token stream, one token per line with its position
+    Tokens,
+    /// Output the analyzed program as Crusty, with macros expanded,
+    /// shorthand spelled out and inferred types written in
+    CrustyDesugared,
 }
 
 /// Source language for parsing
@@ -99,6 +408,9 @@ impl CompilerOptions {
                         match ext {
                             "rs" => EmitMode::Rust,
                             "ast" => EmitMode::Ast,
+                            "json" => EmitMode::AstJson,
+                            "crast" => EmitMode::AstBin,
+                            "tokens" => EmitMode::Tokens,
                             _ => EmitMode::Binary, // Default to binary for executables
                         }
                     } else {
@@ -112,6 +424,202 @@ impl CompilerOptions {
         }
     }
 
+    /// The files to compile as modules alongside the input file, with each
+    /// directory given replaced by the Crusty files under it
+    pub fn module_inputs(&self) -> crate::error::Result<Vec<PathBuf>> {
+        let root = self.input_file.canonicalize().ok();
+        let mut files = Vec::new();
+        for input in &self.inputs {
+            if input.is_dir() {
+                let found = discover_source_files(input, "crst")?;
+                files.extend(
+                    found
+                        .into_iter()
+                        .filter(|file| file.canonicalize().ok() != root),
+                );
+            } else {
+                files.push(input.clone());
+            }
+        }
+        Ok(files)
+    }
+
+    /// Lints to silence in generated Rust: the defaults plus any --allow-lint
+    pub fn get_lint_allows(&self) -> Vec<String> {
+        let mut lints: Vec<String> = if self.no_default_allows {
+            Vec::new()
+        } else {
+            crate::codegen::DEFAULT_LINT_ALLOWS
+                .iter()
+                .map(|s| s.to_string())
+                .collect()
+        };
+        for lint in &self.allow_lints {
+            if !lints.contains(lint) {
+                lints.push(lint.clone());
+            }
+        }
+        lints
+    }
+
+    /// Levels of the lints named by --allow, --warn and --deny
+    pub fn lint_levels(&self) -> crate::error::Result<LintLevels> {
+        let mut levels = LintLevels::new();
+        let flags = [
+            (&self.allow, Level::Allow),
+            (&self.warn, Level::Warn),
+            (&self.deny, Level::Deny),
+        ];
+        for (names, level) in flags {
+            for name in names {
+                levels.set(name, level).map_err(CompilerError::Usage)?;
+            }
+        }
+        Ok(levels)
+    }
+
+    /// Command-line flags that affect how a file is parsed and what code is
+    /// generated for it
+    fn code_flags(&self) -> Vec<String> {
+        let mut flags = vec![format!("--emit={}", value_name(self.get_emit_mode()))];
+        if let Some(lang) = self.absorb {
+            flags.push(format!("--absorb={}", value_name(lang)));
+        }
+        if self.group_items {
+            flags.push("--group-items".to_string());
+        }
+        if self.split_modules {
+            flags.push("--split-modules".to_string());
+        }
+        if self.no_default_allows {
+            flags.push("--no-default-allows".to_string());
+        }
+        for lint in &self.allow_lints {
+            flags.push(format!("--allow-lint={}", lint));
+        }
+        if self.heap_threshold != crate::codegen::DEFAULT_HEAP_THRESHOLD {
+            flags.push(format!("--heap-threshold={}", self.heap_threshold));
+        }
+        if self.tail_calls {
+            flags.push("--tail-calls".to_string());
+        }
+        if self.inline_macros {
+            flags.push("--inline-macros".to_string());
+        }
+        if self.switch_ladders == crate::switch_ladders::SwitchLadders::Rewrite {
+            flags.push("--switch-ladders=rewrite".to_string());
+        }
+        if self.unqualified_variants {
+            flags.push("--unqualified-variants".to_string());
+        }
+        if self.int_conditions != crate::semantic::IntConditions::default() {
+            flags.push(format!(
+                "--int-conditions={}",
+                value_name(self.int_conditions)
+            ));
+        }
+        if self.flow_checks != crate::dataflow::FlowChecks::default() {
+            flags.push(format!("--flow-checks={}", value_name(self.flow_checks)));
+        }
+        if self.source_comments {
+            flags.push("--source-comments".to_string());
+        }
+        if self.debug {
+            flags.push("--debug".to_string());
+        }
+        if self.panic != crate::codegen::PanicPolicy::default() {
+            flags.push(format!("--panic={}", value_name(self.panic)));
+        }
+        if self.wide_strings != crate::codegen::WideStringMapping::default() {
+            flags.push(format!("--wide-strings={}", value_name(self.wide_strings)));
+        }
+        for name in &self.defines {
+            flags.push(format!("--define={}", name));
+        }
+        if let Some(kind) = self.lib {
+            flags.push(format!("--lib={}", value_name(kind)));
+        }
+        if let Some(ref entry) = self.entry {
+            flags.push(format!("--entry={}", entry));
+        }
+        if self.freestanding {
+            flags.push("--freestanding".to_string());
+        }
+        if self.bench {
+            flags.push("--bench".to_string());
+        }
+        if self.coverage {
+            flags.push("--coverage".to_string());
+        }
+        if let Some(ref target) = self.target {
+            flags.push(format!("--target={}", target));
+        }
+        flags
+    }
+
+    /// Provenance header recording the compiler version, source file and the
+    /// options that affect generated code
+    pub fn provenance_header(&self) -> Vec<String> {
+        vec![
+            format!(
+                "Generated by crustyc {} from {}",
+                env!("CARGO_PKG_VERSION"),
+                self.input_file.display()
+            ),
+            format!("Options: {}", self.code_flags().join(" ")),
+            "Do not edit: changes will be lost when this file is regenerated.".to_string(),
+        ]
+    }
+
+    /// Compilation database entry for the input file
+    pub fn compile_command(
+        &self,
+        base_dir: &Path,
+        output: &Path,
+        defines: Vec<String>,
+    ) -> crate::compile_db::CompileCommand {
+        let directory = std::env::current_dir()
+            .map(|dir| dir.display().to_string())
+            .unwrap_or_else(|_| ".".to_string());
+        let file = self.input_file.display().to_string();
+
+        let mut arguments = vec!["crustyc".to_string(), file.clone()];
+        arguments.extend(self.inputs.iter().map(|input| input.display().to_string()));
+        arguments.extend(self.code_flags());
+        if self.no_header {
+            arguments.push("--no-header".to_string());
+        }
+        arguments.push("-o".to_string());
+        arguments.push(output.display().to_string());
+
+        let mut include_paths = vec![base_dir.display().to_string()];
+        if let Some(parent) = self.input_file.parent() {
+            if parent != base_dir && !parent.as_os_str().is_empty() {
+                include_paths.push(parent.display().to_string());
+            }
+        }
+
+        crate::compile_db::CompileCommand {
+            directory,
+            file,
+            output: output.display().to_string(),
+            arguments,
+            language: value_name(self.get_source_language()),
+            defines,
+            include_paths,
+        }
+    }
+
+    /// Crate name of a library built from the input file
+    pub fn crate_name(&self) -> String {
+        crate::rustc::crate_name(
+            self.input_file
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("output"),
+        )
+    }
+
     /// Get the output file path, using a default if not specified
     pub fn get_output_path(&self) -> PathBuf {
         if let Some(ref path) = self.output_file {
@@ -124,23 +632,74 @@ impl CompilerOptions {
                 .and_then(|s| s.to_str())
                 .unwrap_or("output");
 
-            match self.get_emit_mode() {
-                EmitMode::Auto => PathBuf::from(input_stem), // Should not happen after get_emit_mode()
-                EmitMode::Rust => PathBuf::from(format!("{}.rs", input_stem)),
-                EmitMode::Binary => PathBuf::from(input_stem),
-                EmitMode::Ast => PathBuf::from(format!("{}.ast", input_stem)),
+            match (self.get_emit_mode(), self.lib) {
+                (EmitMode::Auto, _) => PathBuf::from(input_stem), // Should not happen after get_emit_mode()
+                (EmitMode::Rust, Some(_)) => PathBuf::from("lib.rs"),
+                (EmitMode::Rust, None) => PathBuf::from(format!("{}.rs", input_stem)),
+                (EmitMode::Binary, Some(kind)) => PathBuf::from(kind.file_name(&self.crate_name())),
+                (EmitMode::Binary, None) if self.freestanding => {
+                    PathBuf::from(format!("{}.o", input_stem))
+                }
+                (EmitMode::Binary, None) => PathBuf::from(input_stem),
+                (EmitMode::Ast, _) => PathBuf::from(format!("{}.ast", input_stem)),
+                (EmitMode::AstJson, _) => PathBuf::from(format!("{}.json", input_stem)),
+                (EmitMode::AstBin, _) => PathBuf::from(format!("{}.crast", input_stem)),
+                (EmitMode::Tokens, _) => PathBuf::from(format!("{}.tokens", input_stem)),
+                (EmitMode::CrustyDesugared, _) => {
+                    PathBuf::from(format!("{}.desugared.crst", input_stem))
+                }
             }
         }
     }
 }
 
-/// Read source file from disk
+/// Command-line spelling of a value enum, e.g. `ast-bin`
+fn value_name(value: impl ValueEnum) -> String {
+    value
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default()
+}
+
+/// Path of the source map written for the generated Rust file `rust_file`:
+/// `main.rs.map` for `main.rs`
+pub fn source_map_path(rust_file: &Path) -> PathBuf {
+    let mut path = rust_file.as_os_str().to_owned();
+    path.push(".map");
+    PathBuf::from(path)
+}
+
+/// Path of the item hashes written for the generated Rust file
+/// `rust_file`: `main.rs.hashes` for `main.rs`
+pub fn item_hashes_path(rust_file: &Path) -> PathBuf {
+    let mut path = rust_file.as_os_str().to_owned();
+    path.push(".hashes");
+    PathBuf::from(path)
+}
+
+/// Fail when a second generation of a file, `second`, differs from the
+/// first, whose items are `items`
+fn check_determinism(
+    first: &str,
+    second: &str,
+    items: &[crate::codegen::GeneratedItem],
+) -> crate::error::Result<()> {
+    match crate::determinism::compare(first, second, items) {
+        Some(divergence) => Err(CompilerError::CodeGen(crate::error::CodeGenError::new(
+            divergence.to_string(),
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Read source file from disk; the error names the file
 pub fn read_source_file(path: &PathBuf) -> Result<String, std::io::Error> {
     std::fs::read_to_string(path)
+        .map_err(|e| std::io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))
 }
 
 /// Write generated code to output file
-pub fn write_output_file(path: &PathBuf, content: &str) -> Result<(), std::io::Error> {
+pub fn write_output_file(path: &Path, content: &str) -> Result<(), std::io::Error> {
     // Create parent directories if they don't exist
     if let Some(parent) = path.parent() {
         std::fs::create_dir_all(parent)?;
@@ -211,6 +770,11 @@ pub fn run_compiler(options: &CompilerOptions) -> crate::error::Result<()> {
 
     // Check if input is a directory (batch mode) or a single file
     if options.input_file.is_dir() {
+        if !options.inputs.is_empty() {
+            return Err(CompilerError::CodeGen(crate::error::CodeGenError::new(
+                "a directory input compiles each of its files separately; give the root file first to compile several files into one crate",
+            )));
+        }
         // Batch transpilation mode
         return run_batch_compilation(options);
     }
@@ -219,6 +783,134 @@ pub fn run_compiler(options: &CompilerOptions) -> crate::error::Result<()> {
     run_single_file_compilation(options)
 }
 
+/// Print the diagnostics for `error`, showing the offending line of the
+/// file each one is in
+pub fn report_error(options: &CompilerOptions, error: &CompilerError) {
+    // I/O and rustc errors name their own files, and errors about the
+    // command line or the compilation as a whole are in none; the others
+    // without a file are in the file being compiled
+    let in_root = options.input_file.is_file()
+        && !matches!(
+            error,
+            CompilerError::Io(_)
+                | CompilerError::RustcInvocation(_)
+                | CompilerError::Usage(_)
+                | CompilerError::DeniedLints(_)
+        );
+    let root = options.input_file.display().to_string();
+    for diagnostic in error.diagnostics() {
+        let diagnostic = match diagnostic.file {
+            None if in_root => diagnostic.in_file(&root),
+            _ => diagnostic,
+        };
+        let source = diagnostic
+            .file
+            .as_ref()
+            .and_then(|file| std::fs::read_to_string(file).ok());
+        eprintln!(
+            "{}",
+            diagnostic.render(source.as_deref(), options.color.enabled())
+        );
+    }
+}
+
+/// Print a diagnostic that does not stop the compilation
+fn report(options: &CompilerOptions, diagnostic: Diagnostic) {
+    eprintln!("{}", diagnostic.render(None, options.color.enabled()));
+}
+
+/// Reports the warnings of lints at the levels the command line and the
+/// attributes of the items they are about give them, counting those denied
+struct LintReporter<'a> {
+    options: &'a CompilerOptions,
+    levels: LintLevels,
+    denied: usize,
+}
+
+impl<'a> LintReporter<'a> {
+    fn new(options: &'a CompilerOptions) -> crate::error::Result<Self> {
+        Ok(Self {
+            options,
+            levels: options.lint_levels()?,
+            denied: 0,
+        })
+    }
+
+    /// Report `warning`, found among `items`, as `diagnostic`, showing the
+    /// offending line of `source`
+    fn report(
+        &mut self,
+        warning: &Warning,
+        items: &[crate::ast::Item],
+        diagnostic: Diagnostic,
+        source: Option<&str>,
+    ) {
+        let (level, from) = self.levels.level_of(warning, items);
+        let name = warning.lint.name;
+        let note = match from {
+            LevelSource::Default => format!("`#[{}({})]` on by default", level, name),
+            LevelSource::CommandLine => format!("`--{} {}` given on the command line", level, name),
+            LevelSource::Attribute(item) => format!("`#[{}({})]` on '{}'", level, name, item),
+        };
+        let mut diagnostic = diagnostic.with_child("note", note);
+        match level {
+            Level::Allow => return,
+            Level::Warn => {}
+            Level::Deny => {
+                diagnostic.severity = crate::error::Severity::Error;
+                self.denied += 1;
+            }
+        }
+        eprintln!(
+            "{}",
+            diagnostic.render(source, self.options.color.enabled())
+        );
+    }
+
+    /// Report `warning`, found among `items` of the file at `path`, showing
+    /// the line it points at from `source`, or from the file when `source`
+    /// is not given
+    fn report_in(
+        &mut self,
+        warning: &Warning,
+        items: &[crate::ast::Item],
+        path: &Path,
+        source: Option<&str>,
+    ) {
+        let mut diagnostic =
+            Diagnostic::warning(warning.to_string()).in_file(path.display().to_string());
+        let Some(position) = warning.position else {
+            self.report(warning, items, diagnostic, None);
+            return;
+        };
+        diagnostic = diagnostic.with_span(crate::error::Span::new(position, position));
+        let read = source
+            .is_none()
+            .then(|| std::fs::read_to_string(path).ok())
+            .flatten();
+        self.report(warning, items, diagnostic, source.or(read.as_deref()));
+    }
+
+    /// Fail once a phase has reported a denied lint
+    fn check(&self) -> crate::error::Result<()> {
+        if self.denied == 0 {
+            return Ok(());
+        }
+        Err(CompilerError::DeniedLints(self.denied))
+    }
+}
+
+/// The Rust generated for a crate: the root file's code, with its modules
+/// inline or declared, the files of split modules, and where each item and
+/// statement came from
+struct GeneratedCrate {
+    code: String,
+    /// Name and code of each module written beside the root's file
+    module_files: Vec<(String, String)>,
+    source_map: crate::source_map::SourceMap,
+    item_hashes: crate::determinism::ItemHashes,
+}
+
 /// Run compilation for a single source file
 fn run_single_file_compilation(options: &CompilerOptions) -> crate::error::Result<()> {
     // For single file mode, use the file's parent directory as base
@@ -227,23 +919,46 @@ fn run_single_file_compilation(options: &CompilerOptions) -> crate::error::Resul
         .parent()
         .unwrap_or(std::path::Path::new("."))
         .to_path_buf();
-    run_single_file_compilation_with_base(options, &base_dir)
+    let command = run_single_file_compilation_with_base(options, &base_dir)?;
+
+    if let Some(ref db_path) = options.compile_db {
+        // Merge into an existing database so per-file invocations accumulate
+        let mut db = CompilationDatabase::load(db_path).unwrap_or_default();
+        db.push(command);
+        db.write(db_path)?;
+    }
+    Ok(())
 }
 
 /// Run compilation for a single source file with a specified base directory
 /// The base_dir is used to preserve directory structure when using --out-dir
+/// Returns the compilation database entry describing the compilation
 fn run_single_file_compilation_with_base(
     options: &CompilerOptions,
     base_dir: &Path,
-) -> crate::error::Result<()> {
+) -> crate::error::Result<CompileCommand> {
+    // Analysis and generation recurse once per expression level, as deep as
+    // the parser does
+    crate::utils::on_deep_stack("crusty-compiler", || compile_file(options, base_dir))
+}
+
+/// Compile one source file on the calling thread, for
+/// `run_single_file_compilation_with_base`
+fn compile_file(
+    options: &CompilerOptions,
+    base_dir: &Path,
+) -> crate::error::Result<CompileCommand> {
     use crate::ast::File;
-    use crate::codegen::{CodeGenerator, TargetLanguage};
+    use crate::codegen::TargetLanguage;
     use crate::error::CompilerError;
     use crate::parser::Parser;
-    use crate::semantic::SemanticAnalyzer;
+    use crate::pretty::PrettyPrinter;
 
     let source_lang = options.get_source_language();
     let emit_mode = options.get_emit_mode();
+    let timings = crate::timings::Timings::new();
+    let mut lints = LintReporter::new(options)?;
+    let root_name = options.input_file.display().to_string();
 
     // Step 1: Read source file
     let source = read_source_file(&options.input_file)?;
@@ -252,14 +967,33 @@ fn run_single_file_compilation_with_base(
         println!("Read {} bytes from source file", source.len());
     }
 
+    // Conditional blocks are resolved first; skipped lines become empty, so
+    // later passes that map back to the source see the same line numbers
+    let source = match source_lang {
+        SourceLanguage::Crusty => timings
+            .time("preprocess", &root_name, || {
+                crate::preprocess::preprocess(&source, &options.defines)
+            })
+            .map_err(|e| e.with_snippet(&source))?,
+        SourceLanguage::Rust => source,
+    };
+
+    // The token stream needs only the lexer, so it is written even for
+    // source that does not parse
+    if emit_mode == EmitMode::Tokens && source_lang == SourceLanguage::Crusty {
+        let output_path = write_tokens(options, &source)?;
+        return Ok(options.compile_command(base_dir, &output_path, Vec::new()));
+    }
+
     // Step 2: Parse source based on detected/specified language
     let ast: File = match source_lang {
         SourceLanguage::Crusty => {
             if options.verbose {
                 println!("Parsing Crusty source...");
             }
-            let mut parser = Parser::new(&source)?;
-            parser.parse_file()?
+            timings.time("parse", &root_name, || -> crate::error::Result<File> {
+                Ok(Parser::new(&source)?.parse_file_partial().into_file()?)
+            })?
         }
         SourceLanguage::Rust => {
             // TODO: Implement Rust parsing with syn crate (task 20)
@@ -272,44 +1006,345 @@ fn run_single_file_compilation_with_base(
     if options.verbose {
         println!("Parsed {} items", ast.items.len());
     }
+    let defines = crate::macros::macro_names(&ast);
+
+    // Step 3: Handle AST emit modes
+    if matches!(
+        emit_mode,
+        EmitMode::Ast | EmitMode::AstJson | EmitMode::AstBin
+    ) {
+        let output_path = write_syntax_tree(options, emit_mode, ast, &source)?;
+        return Ok(options.compile_command(base_dir, &output_path, defines));
+    }
+
+    // Step 4: Expand #define macros, then run semantic analysis
+    let mut modules = crate::modules::load_modules(&options.input_file, &ast, &options.defines)?;
+    crate::modules::load_inputs(
+        &options.input_file,
+        &options.module_inputs()?,
+        &mut modules,
+        &options.defines,
+        &timings,
+    )?;
+    // Dead code is found before macros are expanded, since expansion removes
+    // their definitions
+    if let Some(format) = options.dead_code {
+        report_dead_code(options, format, &source, &ast, &modules)?;
+        return Ok(options.compile_command(base_dir, &options.get_output_path(), defines));
+    }
+    let mut program = check_program(options, &mut lints, ast, modules, &source, &timings)?;
+
+    // --check stops once the source is known to be valid Crusty
+    if options.check_only {
+        if options.verbose {
+            println!("Check passed");
+            print!("{}", timings.format());
+        }
+        write_self_profile(options, &timings)?;
+        return Ok(options.compile_command(base_dir, &options.get_output_path(), defines));
+    }
 
-    // Step 3: Handle AST emit mode
-    if emit_mode == EmitMode::Ast {
-        let ast_output = format!("{:#?}", ast);
+    if emit_mode == EmitMode::CrustyDesugared {
+        let desugared = crate::desugar::desugar(&program.ast, program.analyzer.local_types());
+        let crusty = PrettyPrinter::new(TargetLanguage::Crusty)
+            .format_ast_as_crusty(&desugared)
+            .map_err(|e| CompilerError::CodeGen(crate::error::CodeGenError::new(e)))?;
         let output_path = options.get_output_path();
-        write_output_file(&output_path, &ast_output)?;
+        write_output_file(&output_path, &crusty)?;
 
         if options.verbose {
-            println!("Wrote AST to: {:?}", output_path);
+            println!("Wrote desugared Crusty to: {:?}", output_path);
         }
-        return Ok(());
+        write_self_profile(options, &timings)?;
+        return Ok(options.compile_command(base_dir, &output_path, defines));
+    }
+
+    // Step 5: Generate target code (always Rust for now)
+    let (mut generated, generator) = generate_program(options, &mut program, &source, &timings)?;
+
+    // Step 6: Write output file
+    let (output_path, rust_output_path) = output_paths(options, base_dir, emit_mode)?;
+    write_generated(options, &rust_output_path, &mut generated)?;
+
+    // Step 6b: Optionally map clippy findings back to the Crusty source
+    if options.clippy {
+        report_clippy_warnings(
+            &mut lints,
+            &program.ast,
+            &rust_output_path,
+            &generator,
+            &source,
+        );
+        lints.check()?;
+    }
+
+    // Step 7: Optionally invoke rustc
+    if emit_mode == EmitMode::Binary && !options.no_compile {
+        build_binary(
+            options,
+            &rust_output_path,
+            &output_path,
+            &generated.source_map,
+            &source,
+            &timings,
+        )?;
+    }
+
+    if options.verbose {
+        print!("{}", timings.format());
+    }
+    write_self_profile(options, &timings)?;
+    Ok(options.compile_command(base_dir, &output_path, defines))
+}
+
+/// A program that passed analysis: the root file and the modules it
+/// imports, the platform they are built for, and the analyzers that checked
+/// them, whose findings guide generation
+struct CheckedProgram {
+    ast: crate::ast::File,
+    modules: Vec<crate::modules::Module>,
+    platform: crate::platform::Platform,
+    analyzer: crate::semantic::SemanticAnalyzer,
+    module_analyzers: Vec<crate::semantic::SemanticAnalyzer>,
+    equality_impls: std::collections::BTreeMap<String, crate::semantic::EqualityImpl>,
+}
+
+/// Write the syntax tree of the root file as `emit_mode` asks: Rust's debug
+/// format, JSON or the binary archive. Returns the path written
+fn write_syntax_tree(
+    options: &CompilerOptions,
+    emit_mode: EmitMode,
+    ast: crate::ast::File,
+    source: &str,
+) -> crate::error::Result<PathBuf> {
+    use crate::error::{CodeGenError, CompilerError};
+
+    let output_path = options.get_output_path();
+    let (bytes, what) = match emit_mode {
+        EmitMode::AstJson => (
+            serde_json::to_string_pretty(&ast)
+                .map_err(|e| CompilerError::CodeGen(CodeGenError::new(e.to_string())))?
+                .into_bytes(),
+            "AST as JSON",
+        ),
+        EmitMode::AstBin => (
+            crate::archive::AstArchive::new(options.input_file.display().to_string(), ast, source)
+                .to_bytes()
+                .map_err(|e| CompilerError::CodeGen(CodeGenError::new(e.to_string())))?,
+            "AST archive",
+        ),
+        _ => (format!("{:#?}", ast).into_bytes(), "AST"),
+    };
+    if let Some(parent) = output_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&output_path, bytes)?;
+
+    if options.verbose {
+        println!("Wrote {} to: {:?}", what, output_path);
+    }
+    Ok(output_path)
+}
+
+/// Expand the macros of the root file and its modules, then analyze them
+/// and run the lints that follow analysis. Fails on the first file with
+/// errors, or once a denied lint was reported
+fn check_program(
+    options: &CompilerOptions,
+    lints: &mut LintReporter,
+    mut ast: crate::ast::File,
+    mut modules: Vec<crate::modules::Module>,
+    source: &str,
+    timings: &crate::timings::Timings,
+) -> crate::error::Result<CheckedProgram> {
+    use crate::error::CompilerError;
+    use crate::platform::Platform;
+
+    let root_name = options.input_file.display().to_string();
+    // With --target, the items a #[cfg] rules out for it are dropped, so
+    // per-target variants of an item can share its name, and the rest are
+    // analyzed and generated for the target's platform
+    let platform = match options.target {
+        Some(ref target) => strip_disabled_items(options, target, &mut ast, &mut modules)?,
+        None => Platform::host(),
+    };
+    if options.inline_macros {
+        promote_inline_macros(options, &mut ast, &mut modules);
     }
+    expand_all_macros(options, lints, &mut ast, source, &mut modules, timings)?;
+    crate::modules::share_input_items(&mut ast, &mut modules)?;
+    check_ast_size(options, &ast, &modules)?;
 
-    // Step 4: Run semantic analysis
     if options.verbose {
         println!("Running semantic analysis...");
     }
 
-    let mut analyzer = SemanticAnalyzer::new();
-    analyzer.analyze(&ast)?;
+    // Crusty files named by #import or on the command line are checked
+    // first, and their analyzers kept for generating them
+    let mut module_analyzers = Vec::new();
+    let mut equality_impls = std::collections::BTreeMap::new();
+    let analyzed = analyze_modules(options, &modules, &platform, timings);
+    for (module, (result, module_analyzer)) in modules.iter().zip(analyzed) {
+        result.map_err(|errors| CompilerError::in_module(&module.path, errors))?;
+        for warning in module_analyzer.warnings() {
+            lints.report_in(warning, &module.file.items, &module.path, None);
+        }
+        equality_impls.extend(module_analyzer.equality_impls().clone());
+        module_analyzers.push(module_analyzer);
+    }
+
+    let mut analyzer = new_analyzer(options, &platform);
+    for module in &modules {
+        analyzer.add_module(&module.name, &module.file);
+    }
+    // The root file sees every module, making this the cross-file check
+    let checked = timings.time("check", &root_name, || analyzer.analyze(&ast));
+    for (item, start, duration) in analyzer.item_timings() {
+        timings.record_item("check", &root_name, item, *start, *duration);
+    }
+    checked?;
+    // A struct compared only in the file importing it is generated with its
+    // module, so every generator sees the comparisons of every file
+    equality_impls.extend(analyzer.equality_impls().clone());
+
+    for warning in analyzer.warnings() {
+        lints.report_in(warning, &ast.items, &options.input_file, Some(source));
+    }
 
     if options.verbose {
         println!("Semantic analysis passed");
     }
 
-    // Step 5: Generate target code (always Rust for now)
+    check_entry_point(options, &ast, options.get_emit_mode())?;
+
+    timings.time("lint", &root_name, || {
+        report_no_effect_statements(lints, &ast, &options.input_file, Some(source));
+        for module in &modules {
+            report_no_effect_statements(lints, &module.file, &module.path, None);
+        }
+        report_unused(lints, &ast, source, &modules);
+        convert_switch_ladders(options, lints, &mut ast, &mut modules);
+    });
+    lints.check()?;
+
+    Ok(CheckedProgram {
+        ast,
+        modules,
+        platform,
+        analyzer,
+        module_analyzers,
+        equality_impls,
+    })
+}
+
+/// Generate the Rust crate for a checked program, after the rewrites asked
+/// for, and report where it allocates on the heap and how much it grew.
+/// Returns the crate and the root file's generator, which knows where each
+/// generated item came from
+fn generate_program(
+    options: &CompilerOptions,
+    program: &mut CheckedProgram,
+    source: &str,
+    timings: &crate::timings::Timings,
+) -> crate::error::Result<(GeneratedCrate, crate::codegen::CodeGenerator)> {
+    use crate::semantic::SemanticAnalyzer;
+
+    let root_name = options.input_file.display().to_string();
+    if options.tail_calls {
+        eliminate_tail_calls(options, &mut program.ast, &mut program.modules);
+    }
+
     if options.verbose {
         println!("Generating Rust code...");
     }
 
-    let mut generator = CodeGenerator::new(TargetLanguage::Rust);
-    let generated_code = generator.generate(&ast);
+    // The benchmark harness is the program's main
+    if options.bench {
+        program.ast.items.retain(
+            |item| !matches!(item, crate::ast::Item::Function(func) if func.name.name == "main"),
+        );
+    }
+
+    // Lowered after the rewrites above, so codegen sees the final program
+    let hir = timings.time("lower", &root_name, || {
+        lower_checked(&program.ast, &program.analyzer)
+    });
+
+    let mut generator = new_generator(
+        options,
+        &program.platform,
+        &program.modules,
+        program.equality_impls.clone(),
+    );
+    if !options.no_header {
+        generator.set_header(options.provenance_header());
+    }
+    generator.set_lint_allows(options.get_lint_allows());
+    generator.set_entry(options.entry.clone());
+    generator.set_freestanding(options.freestanding);
+    generator.set_bench(options.bench);
+    generator.set_clock_start(
+        program.analyzer.calls_clock()
+            || program
+                .module_analyzers
+                .iter()
+                .any(SemanticAnalyzer::calls_clock),
+    );
+    generator.set_track_caller(options.debug);
+    generator.set_embedded_source(options.source_comments.then(|| source.to_string()));
+    use_analysis(&mut generator, &program.analyzer);
+    let code = timings.time("codegen", &root_name, || generator.generate_hir(&hir));
+    if options.check_determinism {
+        let items = generator.generated_items().to_vec();
+        check_determinism(&code, &generator.generate_hir(&hir), &items)?;
+    }
+    let mut item_hashes = crate::determinism::ItemHashes::new(String::new());
+    item_hashes.add(&code, generator.generated_items(), None);
+    // Named after the generated file once its path is known
+    let mut source_map = crate::source_map::SourceMap::new(String::new());
+    source_map.add_source(
+        &options.input_file.display().to_string(),
+        source,
+        0,
+        generator.generated_items(),
+        generator.generated_statements(),
+    );
+    let mut generated = GeneratedCrate {
+        code,
+        module_files: Vec::new(),
+        source_map,
+        item_hashes,
+    };
+    generate_modules(
+        options,
+        &program.modules,
+        &program.module_analyzers,
+        &program.platform,
+        program.equality_impls.clone(),
+        timings,
+        &mut generated,
+    )?;
+    report_heap_allocations(options, generator.heap_allocations(), &options.input_file);
+
+    if let Some(budget) = options.report_size {
+        let sizes = crate::size_report::measure(&program.ast, source, generator.generated_items());
+        print!("{}", crate::size_report::format_report(&sizes, budget));
+    }
 
     if options.verbose {
-        println!("Generated {} bytes of code", generated_code.len());
+        println!("Generated {} bytes of code", generated.code.len());
     }
+    Ok((generated, generator))
+}
 
-    // Step 6: Write output file
+/// Where the compilation's output goes, and where the Rust it generates is
+/// written: the same file, except when building a binary, which is compiled
+/// from a `.rs` file named after it
+fn output_paths(
+    options: &CompilerOptions,
+    base_dir: &Path,
+    emit_mode: EmitMode,
+) -> crate::error::Result<(PathBuf, PathBuf)> {
     let output_path = if let Some(ref out_dir) = options.out_dir {
         // Using --out-dir: compute output path preserving directory structure
         ensure_output_dir(out_dir)?;
@@ -331,56 +1366,949 @@ fn run_single_file_compilation_with_base(
     } else {
         output_path.clone()
     };
+    Ok((output_path, rust_output_path))
+}
 
-    write_output_file(&rust_output_path, &generated_code)?;
-
-    if options.verbose {
-        println!("Wrote Rust code to: {:?}", rust_output_path);
+/// Promote the function-like macros of `ast` and `modules` that can be
+/// typed into inline functions, for `--inline-macros`
+fn promote_inline_macros(
+    options: &CompilerOptions,
+    ast: &mut crate::ast::File,
+    modules: &mut [crate::modules::Module],
+) {
+    let files = std::iter::once(ast).chain(modules.iter_mut().map(|m| &mut m.file));
+    for file in files {
+        let promotions = crate::inline_macros::promote_macros(file);
+        if options.verbose {
+            for promotion in &promotions {
+                println!(
+                    "Promoted macro `{}` into inline function `{}`",
+                    promotion.macro_name, promotion.function
+                );
+            }
+        }
     }
+}
 
-    // Step 7: Optionally invoke rustc
-    if emit_mode == EmitMode::Binary && !options.no_compile {
-        if options.verbose {
-            println!("Invoking rustc...");
+/// Rewrite the if/else chains of `ast` and `modules` that compare one value
+/// against constants into switches, or warn about them, as
+/// `--switch-ladders` asks
+fn convert_switch_ladders(
+    options: &CompilerOptions,
+    lints: &mut LintReporter,
+    ast: &mut crate::ast::File,
+    modules: &mut [crate::modules::Module],
+) {
+    use crate::switch_ladders::SwitchLadders;
+
+    let files = std::iter::once((ast, &options.input_file))
+        .chain(modules.iter_mut().map(|m| (&mut m.file, &m.path)));
+    for (file, path) in files {
+        let ladders = crate::switch_ladders::convert_switch_ladders(file, options.switch_ladders);
+        for ladder in ladders {
+            if options.switch_ladders == SwitchLadders::Rewrite {
+                if options.verbose {
+                    println!(
+                        "Converted if/else chain on `{}` in `{}` into a switch",
+                        ladder.subject, ladder.function
+                    );
+                }
+            } else {
+                let warning = Warning::new(
+                    &crate::lints::SWITCH_LADDERS,
+                    format!(
+                        "in function '{}', the if/else chain comparing '{}' against {} values could be a switch",
+                        ladder.function, ladder.subject, ladder.values
+                    ),
+                )
+                .in_item(ladder.function);
+                lints.report_in(&warning, &file.items, path, None);
+            }
         }
+    }
+}
 
-        use crate::rustc;
-        let rustc_result = rustc::invoke_rustc(&rust_output_path, &output_path, options.verbose)
-            .map_err(CompilerError::RustcInvocation)?;
+/// Check each of `modules`, seeing the public items of the others, as the
+/// results and analyzers in module order
+///
+/// Each module has an analyzer of its own, so they are checked in parallel.
+/// Inputs may import each other, so every other module is visible; the
+/// analyzer keeps their private items out of reach.
+fn analyze_modules(
+    options: &CompilerOptions,
+    modules: &[crate::modules::Module],
+    platform: &crate::platform::Platform,
+    timings: &crate::timings::Timings,
+) -> Vec<(
+    Result<(), Vec<crate::error::SemanticError>>,
+    crate::semantic::SemanticAnalyzer,
+)> {
+    use rayon::prelude::*;
+
+    modules
+        .par_iter()
+        .enumerate()
+        .map(|(i, module)| {
+            let module_name = module.path.display().to_string();
+            timings.time("analyze", &module_name, || {
+                let mut module_analyzer = new_analyzer(options, platform);
+                for (j, dependency) in modules.iter().enumerate() {
+                    if j != i {
+                        module_analyzer.add_module(&dependency.name, &dependency.file);
+                    }
+                }
+                // Pool threads have small stacks
+                let result = crate::utils::on_deep_stack("crusty-analyzer", || {
+                    module_analyzer.analyze(&module.file)
+                });
+                for (item, start, duration) in module_analyzer.item_timings() {
+                    timings.record_item("analyze", &module_name, item, *start, *duration);
+                }
+                (result, module_analyzer)
+            })
+        })
+        .collect()
+}
 
-        if !rustc_result.is_success() {
-            return Err(CompilerError::RustcInvocation(
-                rustc_result
-                    .error_message()
-                    .unwrap_or_else(|| "Unknown rustc error".to_string()),
-            ));
-        }
+/// Lower `file` to HIR with what `analyzer` found checking it
+fn lower_checked(
+    file: &crate::ast::File,
+    analyzer: &crate::semantic::SemanticAnalyzer,
+) -> crate::hir::Hir {
+    crate::hir::lower(file, &analyzer.analysis())
+}
 
-        if options.verbose {
-            println!("Compilation successful: {:?}", output_path);
+/// Note each local of the file at `path` that codegen boxed
+fn report_heap_allocations(
+    options: &CompilerOptions,
+    allocations: &[crate::codegen::HeapAllocation],
+    path: &Path,
+) {
+    for allocation in allocations {
+        report(
+            options,
+            heap_note(allocation).in_file(path.display().to_string()),
+        );
+    }
+}
+
+/// Compile the generated Rust at `rust_path` into `output_path`, as a
+/// library, a freestanding or benchmark binary, a coverage test harness or
+/// a plain binary as the options ask
+fn compile_rust(
+    options: &CompilerOptions,
+    rust_path: &Path,
+    output_path: &Path,
+) -> Result<crate::rustc::RustcResult, String> {
+    use crate::rustc;
+
+    match options.lib {
+        Some(kind) => rustc::invoke_rustc_library(
+            rust_path,
+            output_path,
+            kind,
+            &options.crate_name(),
+            options.target.as_deref(),
+            options.debug,
+            options.verbose,
+        ),
+        None if options.freestanding => rustc::invoke_rustc_freestanding(
+            rust_path,
+            output_path,
+            options.target.as_deref(),
+            options.debug,
+            options.verbose,
+        ),
+        None => {
+            let mut flags = if options.bench {
+                vec!["-C".to_string(), "opt-level=3".to_string()]
+            } else if options.coverage {
+                vec![
+                    "--test".to_string(),
+                    "-C".to_string(),
+                    "instrument-coverage".to_string(),
+                ]
+            } else {
+                rustc::target_flags(options.target.as_deref())
+            };
+            flags.extend(rustc::debug_flags(options.debug));
+            // The test harness reports failed tests by unwinding
+            if !options.coverage {
+                flags.extend(rustc::panic_flags(options.panic));
+            }
+            if flags.is_empty() {
+                rustc::invoke_rustc(rust_path, output_path, options.verbose)
+            } else {
+                rustc::invoke_rustc_with_flags(rust_path, output_path, &flags, options.verbose)
+            }
         }
     }
+}
 
+/// Generate `modules`, checked by `analyzers`, after the root file's items
+/// in `generated`, leaving the line numbers used by --report-size and
+/// --clippy unchanged
+///
+/// Each module is inline in a `pub mod name { ... }`, or with
+/// `--split-modules` declared with `pub mod name;` and written to a file of
+/// its own beside the root's.
+fn generate_modules(
+    options: &CompilerOptions,
+    modules: &[crate::modules::Module],
+    analyzers: &[crate::semantic::SemanticAnalyzer],
+    platform: &crate::platform::Platform,
+    equality_impls: std::collections::BTreeMap<String, crate::semantic::EqualityImpl>,
+    timings: &crate::timings::Timings,
+    generated: &mut GeneratedCrate,
+) -> crate::error::Result<()> {
+    let mut module_generator = new_generator(options, platform, modules, equality_impls);
+    module_generator.set_in_module(true);
+    for (module, module_analyzer) in modules.iter().zip(analyzers) {
+        use_analysis(&mut module_generator, module_analyzer);
+        generated.code.push('\n');
+        let module_name = module.path.display().to_string();
+        let hir = timings.time("lower", &module_name, || {
+            lower_checked(&module.file, module_analyzer)
+        });
+        if options.split_modules {
+            generated
+                .code
+                .push_str(&format!("pub mod {};\n", module.name));
+            let code = timings.time("codegen", &module_name, || {
+                module_generator.generate_hir(&hir)
+            });
+            if options.check_determinism {
+                let items = module_generator.generated_items().to_vec();
+                check_determinism(&code, &module_generator.generate_hir(&hir), &items)?;
+            }
+            generated.item_hashes.add(
+                &code,
+                module_generator.generated_items(),
+                Some(&module.name),
+            );
+            generated
+                .module_files
+                .push((format!("{}.rs", module.name), code));
+        } else {
+            // The module's items are indented one level inside `pub mod name {`
+            let offset = generated.code.matches('\n').count() + 1;
+            let code = timings.time("codegen", &module_name, || {
+                module_generator.generate_module_hir(&module.name, &hir)
+            });
+            if options.check_determinism {
+                let items = module_generator.generated_items().to_vec();
+                let again = module_generator.generate_module_hir(&module.name, &hir);
+                check_determinism(&code, &again, &items)?;
+            }
+            generated.item_hashes.add(
+                &code,
+                module_generator.generated_items(),
+                Some(&module.name),
+            );
+            generated.code.push_str(&code);
+            generated.source_map.add_source(
+                &module_name,
+                &std::fs::read_to_string(&module.path).unwrap_or_default(),
+                offset,
+                module_generator.generated_items(),
+                module_generator.generated_statements(),
+            );
+        }
+        report_heap_allocations(options, module_generator.heap_allocations(), &module.path);
+    }
     Ok(())
 }
 
-/// Run batch compilation for multiple source files in a directory
-fn run_batch_compilation(options: &CompilerOptions) -> crate::error::Result<()> {
-    use crate::error::CompilerError;
+/// Size in bytes from which locals are boxed, when they are; freestanding
+/// targets have no allocator to box them with
+fn heap_threshold(options: &CompilerOptions) -> Option<usize> {
+    Some(options.heap_threshold).filter(|&bytes| bytes > 0 && !options.freestanding)
+}
 
+/// Write `generated` to `rust_output_path`, with each split module's file
+/// beside it and, when asked for, its source map and item hashes
+fn write_generated(
+    options: &CompilerOptions,
+    rust_output_path: &Path,
+    generated: &mut GeneratedCrate,
+) -> crate::error::Result<()> {
+    write_output_file(rust_output_path, &generated.code)?;
     if options.verbose {
-        println!("Batch compilation mode: discovering source files...");
+        println!("Wrote Rust code to: {:?}", rust_output_path);
     }
 
-    // Determine the file extension to look for based on source language
-    let source_lang = options.get_source_language();
-    let extension = match source_lang {
-        SourceLanguage::Crusty => "crst",
-        SourceLanguage::Rust => "rs",
-    };
+    // rustc finds the file of `pub mod name;` beside the crate root
+    for (file_name, code) in &generated.module_files {
+        let module_path = rust_output_path.with_file_name(file_name);
+        write_output_file(&module_path, code)?;
+        if options.verbose {
+            println!("Wrote module to: {:?}", module_path);
+        }
+    }
 
-    // Discover all source files recursively
-    let source_files = discover_source_files(&options.input_file, extension)?;
+    generated.source_map.file = rust_output_path.display().to_string();
+    if options.emit_sourcemap || options.debug {
+        let map_path = source_map_path(rust_output_path);
+        write_output_file(&map_path, &generated.source_map.to_json())?;
+        if options.verbose {
+            println!("Wrote source map to: {:?}", map_path);
+        }
+    }
+
+    if options.emit_item_hashes {
+        generated.item_hashes.file = rust_output_path.display().to_string();
+        let hashes_path = item_hashes_path(rust_output_path);
+        write_output_file(&hashes_path, &generated.item_hashes.to_json())?;
+        if options.verbose {
+            println!("Wrote item hashes to: {:?}", hashes_path);
+        }
+    }
+    Ok(())
+}
+
+/// Compile the Rust written to `rust_output_path` into `output_path`,
+/// showing rustc's errors against the Crusty `source` they came from, then
+/// run the benchmarks or coverage report the options ask for
+fn build_binary(
+    options: &CompilerOptions,
+    rust_output_path: &Path,
+    output_path: &Path,
+    source_map: &crate::source_map::SourceMap,
+    source: &str,
+    timings: &crate::timings::Timings,
+) -> crate::error::Result<()> {
+    if options.verbose {
+        println!("Invoking rustc...");
+    }
+
+    let root = options.input_file.display().to_string();
+    let source_text = |file: &str| {
+        if file == root {
+            Some(source.to_string())
+        } else {
+            std::fs::read_to_string(file).ok()
+        }
+    };
+    let rustc_start = std::time::Instant::now();
+    let rustc_result = compile_rust(options, rust_output_path, output_path)
+        .map_err(CompilerError::RustcInvocation)?;
+    timings.record("rustc", &root, rustc_start.elapsed());
+
+    if !rustc_result.is_success() {
+        return Err(CompilerError::RustcInvocation(
+            rustc_result
+                .translated_error_message(source_map, source_text)
+                .unwrap_or_else(|| "Unknown rustc error".to_string()),
+        ));
+    }
+
+    if options.verbose {
+        println!("Compilation successful: {:?}", output_path);
+    }
+
+    if options.bench {
+        run_benchmarks(output_path)?;
+    }
+    if options.coverage {
+        report_coverage(
+            options,
+            output_path,
+            rust_output_path,
+            source_map,
+            source_text,
+        )?;
+    }
+    Ok(())
+}
+
+/// A semantic analyzer checking as the options ask for `platform`, shared by
+/// the root file and the modules it imports
+fn new_analyzer(
+    options: &CompilerOptions,
+    platform: &crate::platform::Platform,
+) -> crate::semantic::SemanticAnalyzer {
+    let mut analyzer = crate::semantic::SemanticAnalyzer::new();
+    analyzer.set_allow_unqualified_variants(options.unqualified_variants);
+    analyzer.set_int_conditions(options.int_conditions);
+    analyzer.set_flow_checks(options.flow_checks);
+    analyzer.set_freestanding(options.freestanding);
+    analyzer.set_error_limit(options.error_limit);
+    analyzer.set_const_eval_limit(options.max_const_eval_steps);
+    analyzer.set_profile_items(options.self_profile.is_some());
+    analyzer.set_platform(platform.clone());
+    analyzer
+}
+
+/// Turn the self tail recursion of `ast` and `modules` into loops, for
+/// `--tail-calls`
+fn eliminate_tail_calls(
+    options: &CompilerOptions,
+    ast: &mut crate::ast::File,
+    modules: &mut [crate::modules::Module],
+) {
+    let files = std::iter::once(ast).chain(modules.iter_mut().map(|m| &mut m.file));
+    for file in files {
+        let rewritten = crate::tailcall::eliminate_tail_calls(file);
+        if options.verbose {
+            for name in &rewritten {
+                println!("Converted tail recursion in `{}` into a loop", name);
+            }
+        }
+    }
+}
+
+/// Drop the items of `ast` and `modules` whose #[cfg] does not hold for
+/// `target`, returning the target's platform
+fn strip_disabled_items(
+    options: &CompilerOptions,
+    target: &str,
+    ast: &mut crate::ast::File,
+    modules: &mut [crate::modules::Module],
+) -> crate::error::Result<crate::platform::Platform> {
+    let cfg = crate::cfg::TargetCfg::query(target).map_err(CompilerError::RustcInvocation)?;
+    let files = std::iter::once(ast).chain(modules.iter_mut().map(|m| &mut m.file));
+    for file in files {
+        for name in cfg.strip_disabled_items(file) {
+            if options.verbose {
+                println!(
+                    "Skipping `{}`: its #[cfg] does not hold for {}",
+                    name, target
+                );
+            }
+        }
+    }
+    Ok(crate::platform::Platform::from_cfg(&cfg))
+}
+
+/// Expand the #define macros of `ast`, whose text is `source`, and of
+/// `modules`, reporting the warnings expansion finds
+fn expand_all_macros(
+    options: &CompilerOptions,
+    lints: &mut LintReporter,
+    ast: &mut crate::ast::File,
+    source: &str,
+    modules: &mut [crate::modules::Module],
+    timings: &crate::timings::Timings,
+) -> crate::error::Result<()> {
+    let root_name = options.input_file.display().to_string();
+    let warnings = timings.time("expand", &root_name, || {
+        crate::macros::expand_macros(ast, options.max_expansion_tokens)
+    })?;
+    for warning in warnings {
+        lints.report_in(&warning, &ast.items, &options.input_file, Some(source));
+    }
+    for module in modules {
+        let module_name = module.path.display().to_string();
+        let warnings = timings
+            .time("expand", &module_name, || {
+                crate::macros::expand_macros(&mut module.file, options.max_expansion_tokens)
+            })
+            .map_err(|errors| CompilerError::in_module(&module.path, errors))?;
+        for warning in warnings {
+            lints.report_in(&warning, &module.file.items, &module.path, None);
+        }
+    }
+    Ok(())
+}
+
+/// A Rust code generator set up as the options ask, shared by the root file
+/// and the modules it imports
+fn new_generator(
+    options: &CompilerOptions,
+    platform: &crate::platform::Platform,
+    modules: &[crate::modules::Module],
+    equality_impls: std::collections::BTreeMap<String, crate::semantic::EqualityImpl>,
+) -> crate::codegen::CodeGenerator {
+    use crate::codegen::{CodeGenerator, ItemOrder, TargetLanguage};
+
+    let mut generator = CodeGenerator::new(TargetLanguage::Rust);
+    if options.group_items {
+        generator.set_item_order(ItemOrder::Sectioned);
+    }
+    generator.set_heap_threshold(heap_threshold(options));
+    generator.set_platform(platform.clone());
+    generator.set_wide_strings(options.wide_strings);
+    generator.set_panic_policy(options.panic);
+    generator.set_local_modules(modules.iter().map(|m| m.name.clone()).collect());
+    generator.set_equality_impls(equality_impls);
+    generator
+}
+
+/// Give `generator` what `analyzer` learned about the file it generates
+fn use_analysis(
+    generator: &mut crate::codegen::CodeGenerator,
+    analyzer: &crate::semantic::SemanticAnalyzer,
+) {
+    generator.set_unqualified_variants(analyzer.unqualified_variants().clone());
+    generator.set_generic_bounds(analyzer.generic_bounds().clone());
+}
+
+/// Write the tokens of `source`, one per line with its position, kind and
+/// text, returning the path written
+fn write_tokens(options: &CompilerOptions, source: &str) -> crate::error::Result<PathBuf> {
+    let tokens = crate::lexer::Lexer::new(source).tokenize()?;
+    let token_output: String = tokens
+        .iter()
+        .map(|token| {
+            let start = token.span.start;
+            format!(
+                "{}:{} {:?} {:?}\n",
+                start.line, start.column, token.kind, token.text
+            )
+        })
+        .collect();
+    let output_path = options.get_output_path();
+    write_output_file(&output_path, &token_output)?;
+
+    if options.verbose {
+        println!("Wrote {} tokens to: {:?}", tokens.len(), output_path);
+    }
+    Ok(output_path)
+}
+
+/// Write the timings to the `--self-profile` path, as a Chrome trace when
+/// it ends in `.json` and as folded stacks otherwise
+fn write_self_profile(
+    options: &CompilerOptions,
+    timings: &crate::timings::Timings,
+) -> crate::error::Result<()> {
+    let Some(path) = &options.self_profile else {
+        return Ok(());
+    };
+    let profile = if path.extension().is_some_and(|ext| ext == "json") {
+        timings.chrome_trace()
+    } else {
+        timings.folded()
+    };
+    write_output_file(path, &profile)?;
+    if options.verbose {
+        println!("Wrote self-profile to: {:?}", path);
+    }
+    Ok(())
+}
+
+/// Run the benchmark harness built at `harness`, which prints the timings
+fn run_benchmarks(harness: &Path) -> crate::error::Result<()> {
+    // A bare file name would be searched for on PATH
+    let status = std::process::Command::new(Path::new(".").join(harness)).status()?;
+    if !status.success() {
+        return Err(CompilerError::Io(std::io::Error::other(format!(
+            "benchmark harness {} failed: {}",
+            harness.display(),
+            status
+        ))));
+    }
+    Ok(())
+}
+
+/// Run the coverage-instrumented test harness built at `harness` and print
+/// how often each line of the Crusty sources ran
+fn report_coverage(
+    options: &CompilerOptions,
+    harness: &Path,
+    rust_file: &Path,
+    source_map: &crate::source_map::SourceMap,
+    source_text: impl Fn(&str) -> Option<String>,
+) -> crate::error::Result<()> {
+    use crate::coverage;
+
+    let (passed, lcov) = coverage::run_tests(harness, options.verbose)
+        .map_err(|e| CompilerError::Io(std::io::Error::other(e)))?;
+    let counts = coverage::parse_lcov(&lcov, rust_file);
+    let files = coverage::map_to_source(source_map, &counts);
+    print!("{}", coverage::format_report(&files, source_text));
+    if !passed {
+        return Err(CompilerError::Io(std::io::Error::other(format!(
+            "test harness {} failed",
+            harness.display()
+        ))));
+    }
+    Ok(())
+}
+
+/// Print the items of the program that its entry points never reach
+fn report_dead_code(
+    options: &CompilerOptions,
+    format: crate::deadcode::DeadCodeFormat,
+    source: &str,
+    ast: &crate::ast::File,
+    modules: &[crate::modules::Module],
+) -> crate::error::Result<()> {
+    use crate::deadcode::{find_dead_code, DeadCodeFormat, SourceFile};
+
+    let root_path = options.input_file.display().to_string();
+    let module_paths: Vec<String> = modules
+        .iter()
+        .map(|module| module.path.display().to_string())
+        .collect();
+    let module_sources = modules
+        .iter()
+        .map(|module| std::fs::read_to_string(&module.path))
+        .collect::<std::io::Result<Vec<String>>>()?;
+
+    let mut files = vec![SourceFile {
+        path: &root_path,
+        source,
+        file: ast,
+    }];
+    for ((module, path), source) in modules.iter().zip(&module_paths).zip(&module_sources) {
+        files.push(SourceFile {
+            path,
+            source,
+            file: &module.file,
+        });
+    }
+
+    // A library is entered through its public items rather than a function
+    let entry = match options.lib {
+        Some(_) => None,
+        None => Some(options.entry.as_deref().unwrap_or("main")),
+    };
+    let report = find_dead_code(&files, entry);
+    match format {
+        DeadCodeFormat::Text => print!("{}", report.to_text()),
+        DeadCodeFormat::Json => println!("{}", report.to_json()),
+    }
+    Ok(())
+}
+
+/// Warn about the expression statements of `file` that have no effect,
+/// showing each in `source`, the text of `path`, read when not given
+fn report_no_effect_statements(
+    lints: &mut LintReporter,
+    file: &crate::ast::File,
+    path: &Path,
+    source: Option<&str>,
+) {
+    let found = crate::no_effect::find_no_effect_statements(file);
+    if found.is_empty() {
+        return;
+    }
+    let source = source
+        .map(str::to_string)
+        .or_else(|| std::fs::read_to_string(path).ok());
+    for statement in found {
+        let warning = Warning::new(
+            &crate::lints::NO_EFFECT,
+            "expression statement has no effect",
+        )
+        .in_item(statement.function);
+        let mut diagnostic = Diagnostic::warning(warning.to_string())
+            .in_file(path.display().to_string())
+            .with_label("its value is discarded")
+            .with_child(
+                "help",
+                "assign the value, as in `x = x + 1;`, or remove the statement",
+            );
+        if let Some(position) = statement.position {
+            diagnostic = diagnostic.with_span(crate::error::Span::new(position, position));
+        }
+        lints.report(&warning, &file.items, diagnostic, source.as_deref());
+    }
+}
+
+/// Warn about the unused variables, parameters, private functions and
+/// fields of the root file, whose text is `source`, and of its modules
+fn report_unused(
+    lints: &mut LintReporter,
+    ast: &crate::ast::File,
+    source: &str,
+    modules: &[crate::modules::Module],
+) {
+    // A field may be read only by a file importing its struct
+    let read_fields: std::collections::HashSet<String> = std::iter::once(ast)
+        .chain(modules.iter().map(|module| &module.file))
+        .flat_map(crate::unused::read_fields)
+        .collect();
+    let module_sources: Vec<(&crate::ast::File, &Path, Option<String>)> = modules
+        .iter()
+        .map(|module| {
+            let text = std::fs::read_to_string(&module.path).ok();
+            (&module.file, module.path.as_path(), text)
+        })
+        .collect();
+    let options = lints.options;
+    let files = std::iter::once((ast, options.input_file.as_path(), Some(source))).chain(
+        module_sources
+            .iter()
+            .map(|(file, path, text)| (*file, *path, text.as_deref())),
+    );
+    for (file, path, text) in files {
+        let found = crate::unused::find_unused(
+            file,
+            text.unwrap_or_default(),
+            &read_fields,
+            options.entry.as_deref(),
+        );
+        for unused in found {
+            let (label, help) = match unused.kind {
+                crate::unused::UnusedKind::Function => (
+                    "declared here",
+                    "call it, remove it, or mark it `#[allow(unused)]`".to_string(),
+                ),
+                crate::unused::UnusedKind::Field(_) => (
+                    "declared here",
+                    "read it, remove it, or mark it `#[allow(unused)]`".to_string(),
+                ),
+                crate::unused::UnusedKind::Parameter | crate::unused::UnusedKind::Variable => (
+                    "declared here",
+                    format!(
+                        "use it, or name it `_{}` to show it is unused on purpose",
+                        unused.name
+                    ),
+                ),
+            };
+            let item = match &unused.kind {
+                crate::unused::UnusedKind::Function => Some(unused.name.clone()),
+                crate::unused::UnusedKind::Field(ty) => Some(ty.clone()),
+                _ => unused.function.clone(),
+            };
+            let mut warning = Warning::new(&crate::lints::UNUSED, unused.to_string());
+            warning.item = item;
+            let mut diagnostic = Diagnostic::warning(warning.to_string())
+                .in_file(path.display().to_string())
+                .with_label(label)
+                .with_child("help", help);
+            if let Some(position) = unused.position {
+                diagnostic = diagnostic.with_span(crate::error::Span::new(position, position));
+            }
+            lints.report(&warning, &file.items, diagnostic, text);
+        }
+    }
+}
+
+/// The note telling that `allocation` was moved to the heap, pointing at
+/// its declaration
+fn heap_note(allocation: &crate::codegen::HeapAllocation) -> Diagnostic {
+    let diagnostic = Diagnostic::note(allocation.to_string());
+    match allocation.position {
+        Some(position) => diagnostic.with_span(crate::error::Span::new(position, position)),
+        None => diagnostic,
+    }
+}
+
+/// Fail when the syntax trees of the root file and its modules together
+/// have more nodes than `--max-ast-nodes` allows
+fn check_ast_size(
+    options: &CompilerOptions,
+    ast: &crate::ast::File,
+    modules: &[crate::modules::Module],
+) -> crate::error::Result<()> {
+    use crate::limits::{count_ast_nodes, exceeds, format_count};
+
+    let nodes = count_ast_nodes(ast)
+        + modules
+            .iter()
+            .map(|module| count_ast_nodes(&module.file))
+            .sum::<usize>();
+    if options.verbose {
+        println!("The program has {} syntax tree nodes", nodes);
+    }
+    if !exceeds(nodes, options.max_ast_nodes) {
+        return Ok(());
+    }
+    Err(CompilerError::Semantic(vec![crate::error::SemanticError::new(
+        crate::error::Span::new(
+            crate::error::Position::new(0, 0),
+            crate::error::Position::new(0, 0),
+        ),
+        crate::error::SemanticErrorKind::InvalidOperation,
+        format!(
+            "the program has {} syntax tree nodes once its macros are expanded, more than the limit of {}; raise it with --max-ast-nodes",
+            nodes,
+            format_count(options.max_ast_nodes)
+        ),
+    )]))
+}
+
+/// Check the function the program starts with: `main` or the `--entry`
+/// function must exist, take no parameters and return void or an integer.
+/// With `--bench`, the harness starts the program instead, and there must be
+/// `#[bench]` functions for it to call, without parameters. With
+/// `--coverage`, the test harness does, and there must be `#[test]`
+/// functions for it to run
+///
+/// rustc's own error for a missing main does not suggest --lib, and the
+/// `main` generated for `--entry` would clash with a Crusty `main`.
+fn check_entry_point(
+    options: &CompilerOptions,
+    ast: &crate::ast::File,
+    emit_mode: EmitMode,
+) -> crate::error::Result<()> {
+    use crate::ast::{Item, PrimitiveType, Type};
+    use crate::error::CompilerError;
+
+    let error = |message: String| {
+        CompilerError::Semantic(vec![crate::error::SemanticError::new(
+            crate::error::Span::new(
+                crate::error::Position::new(0, 0),
+                crate::error::Position::new(0, 0),
+            ),
+            crate::error::SemanticErrorKind::InvalidOperation,
+            message,
+        )])
+    };
+    let function = |name: &str| {
+        ast.items.iter().find_map(|item| match item {
+            Item::Function(func) if func.name.name == name => Some(func),
+            _ => None,
+        })
+    };
+
+    if options.bench {
+        let benches: Vec<_> = ast
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Function(func)
+                    if func
+                        .attributes
+                        .iter()
+                        .any(|attr| attr.name.name == crate::codegen::BENCH_ATTRIBUTE) =>
+                {
+                    Some(func)
+                }
+                _ => None,
+            })
+            .collect();
+        if benches.is_empty() {
+            return Err(error(
+                "no #[bench] functions to run; mark the functions to time with #[bench]"
+                    .to_string(),
+            ));
+        }
+        return match benches
+            .iter()
+            .find(|func| !func.params.is_empty() || !func.generics.is_empty())
+        {
+            Some(func) => Err(error(format!(
+                "#[bench] function '{}' must take no parameters or type parameters",
+                func.name.name
+            ))),
+            None => Ok(()),
+        };
+    }
+
+    if options.coverage {
+        let has_tests = ast.items.iter().any(|item| {
+            matches!(item, Item::Function(func)
+                if func.attributes.iter().any(|attr| attr.name.name == "test"))
+        });
+        if !has_tests {
+            return Err(error(
+                "no #[test] functions to run; --coverage measures the lines the #[test] functions run"
+                    .to_string(),
+            ));
+        }
+        return Ok(());
+    }
+
+    let name = options.entry.as_deref().unwrap_or("main");
+    let Some(entry) = function(name) else {
+        if options.entry.is_some() {
+            return Err(error(format!(
+                "no function '{}' to use as the entry point",
+                name
+            )));
+        }
+        let builds_executable = emit_mode == EmitMode::Binary
+            && !options.no_compile
+            && options.lib.is_none()
+            && !options.freestanding;
+        if builds_executable {
+            return Err(error(
+                "no 'main' function to build an executable from; pass --lib to build a library"
+                    .to_string(),
+            ));
+        }
+        return Ok(());
+    };
+    if options.lib.is_some() {
+        return Ok(());
+    }
+
+    let returns_status = match &entry.return_type {
+        None | Some(Type::Primitive(PrimitiveType::Void)) => true,
+        Some(Type::Primitive(primitive)) => primitive.is_integer(),
+        Some(_) => false,
+    };
+    if options.entry.is_some() && (!entry.params.is_empty() || !returns_status) {
+        return Err(error(format!(
+            "entry point '{}' must take no parameters and return void or an integer",
+            name
+        )));
+    }
+    if name != "main" && !options.freestanding && function("main").is_some() {
+        return Err(error(format!(
+            "function 'main' conflicts with the 'main' generated to start '{}'; rename it or drop --entry",
+            name
+        )));
+    }
+    Ok(())
+}
+
+/// Run clippy on generated code and print its suggestions as Crusty warnings
+///
+/// Clippy findings fail the compilation only with `--deny clippy`; if clippy
+/// is unavailable the step is skipped with a note.
+fn report_clippy_warnings(
+    lints: &mut LintReporter,
+    ast: &crate::ast::File,
+    rust_file: &Path,
+    generator: &crate::codegen::CodeGenerator,
+    source: &str,
+) {
+    use crate::clippy;
+    use crate::rustc;
+
+    let options = lints.options;
+    match rustc::invoke_clippy(rust_file, options.verbose) {
+        Ok(result) => {
+            let suggestions = clippy::parse_suggestions(&result.stderr);
+            let file = options.input_file.display().to_string();
+            for found in
+                clippy::map_to_crusty(&suggestions, generator.generated_items(), &file, source)
+            {
+                let mut warning = Warning::new(&crate::lints::CLIPPY, found.message.clone());
+                warning.item = found.item.clone();
+                let mut diagnostic = Diagnostic::warning(found.message)
+                    .with_code(found.lint)
+                    .in_file(file.clone())
+                    .with_child(
+                        "help",
+                        format!("clippy suggests (as Rust): `{}`", found.replacement),
+                    );
+                if let Some(item) = &found.item {
+                    diagnostic = diagnostic.with_label(format!("in `{}`", item));
+                }
+                if let Some(location) = found.location {
+                    diagnostic = diagnostic.with_span(crate::error::Span::new(location, location));
+                }
+                lints.report(&warning, &ast.items, diagnostic, Some(source));
+            }
+        }
+        Err(e) => report(options, Diagnostic::note(format!("skipping clippy: {}", e))),
+    }
+}
+
+/// Run batch compilation for multiple source files in a directory
+fn run_batch_compilation(options: &CompilerOptions) -> crate::error::Result<()> {
+    use crate::error::CompilerError;
+    use rayon::prelude::*;
+
+    if options.verbose {
+        println!("Batch compilation mode: discovering source files...");
+    }
+
+    // Determine the file extension to look for based on source language
+    let source_lang = options.get_source_language();
+    let extension = match source_lang {
+        SourceLanguage::Crusty => "crst",
+        SourceLanguage::Rust => "rs",
+    };
+
+    // Discover all source files recursively
+    let source_files = discover_source_files(&options.input_file, extension)?;
 
     if options.verbose {
         println!("Found {} source files", source_files.len());
@@ -396,39 +2324,52 @@ fn run_batch_compilation(options: &CompilerOptions) -> crate::error::Result<()>
         )));
     }
 
-    // Ensure output directory exists
-    let out_dir = options.out_dir.as_ref().ok_or_else(|| {
-        CompilerError::CodeGen(crate::error::CodeGenError::new(
-            "--out-dir is required for batch compilation",
-        ))
-    })?;
-    ensure_output_dir(out_dir)?;
+    // Ensure output directory exists; --check and --dead-code write nothing
+    if !options.check_only && options.dead_code.is_none() {
+        let out_dir = options.out_dir.as_ref().ok_or_else(|| {
+            CompilerError::CodeGen(crate::error::CodeGenError::new(
+                "--out-dir is required for batch compilation",
+            ))
+        })?;
+        ensure_output_dir(out_dir)?;
+    }
 
     // Compile each file
     let mut errors = Vec::new();
+    let mut compile_db = CompilationDatabase::new();
     let mut success_count = 0;
 
     // Store the base directory for preserving structure
     let base_dir = options.input_file.clone();
 
-    for source_file in &source_files {
+    // Files are compiled independently on a thread pool; results are
+    // reported in discovery order
+    let timings = crate::timings::Timings::new();
+    let results: Vec<_> = source_files
+        .par_iter()
+        .map(|source_file| {
+            // Create a modified options struct for this file
+            let file_options = CompilerOptions {
+                input_file: source_file.clone(),
+                output_file: None,
+                verbose: false,     // Suppress per-file verbose output
+                self_profile: None, // The batch's profile covers every file
+                ..options.clone()
+            };
+            timings.time("compile", &source_file.display().to_string(), || {
+                run_single_file_compilation_with_base(&file_options, &base_dir)
+            })
+        })
+        .collect();
+
+    for (source_file, result) in source_files.iter().zip(results) {
         if options.verbose {
             println!("Compiling: {:?}", source_file);
         }
 
-        // Create a modified options struct for this file
-        let file_options = CompilerOptions {
-            input_file: source_file.clone(),
-            output_file: None,
-            out_dir: options.out_dir.clone(),
-            emit: options.emit,
-            absorb: options.absorb,
-            verbose: false, // Suppress per-file verbose output
-            no_compile: options.no_compile,
-        };
-
-        match run_single_file_compilation_with_base(&file_options, &base_dir) {
-            Ok(()) => {
+        match result {
+            Ok(command) => {
+                compile_db.push(command);
                 success_count += 1;
                 if options.verbose {
                     println!("  ✓ Success");
@@ -443,12 +2384,19 @@ fn run_batch_compilation(options: &CompilerOptions) -> crate::error::Result<()>
         }
     }
 
+    // Describe the files that compiled, even if others failed
+    if let Some(ref db_path) = options.compile_db {
+        compile_db.write(db_path)?;
+    }
+
     // Report results
     if options.verbose {
         println!("\nBatch compilation complete:");
         println!("  Success: {}/{}", success_count, source_files.len());
         println!("  Errors: {}", errors.len());
+        print!("{}", timings.format());
     }
+    write_self_profile(options, &timings)?;
 
     if !errors.is_empty() {
         // Report all errors
@@ -500,6 +2448,15 @@ fn discover_source_files(dir: &PathBuf, extension: &str) -> Result<Vec<PathBuf>,
 mod tests {
     use super::*;
 
+    /// Options as parsed from a command line naming only `test.crst`, with
+    /// color off, for tests to override the fields they are about
+    fn default_options() -> CompilerOptions {
+        CompilerOptions {
+            color: ColorChoice::Never,
+            ..CompilerOptions::try_parse_from(["crustyc", "test.crst"]).unwrap()
+        }
+    }
+
     #[test]
     fn test_emit_mode_values() {
         // Test that emit modes can be created
@@ -514,6 +2471,23 @@ mod tests {
         assert_eq!(ast, EmitMode::Ast);
     }
 
+    #[test]
+    fn test_emit_help_lists_every_mode() {
+        use clap::CommandFactory;
+
+        let mut command = CompilerOptions::command();
+        let help = command.render_long_help().to_string();
+        for mode in EmitMode::value_variants() {
+            let name = mode.to_possible_value().unwrap();
+            assert!(
+                help.contains(&format!("- {}:", name.get_name())),
+                "{}",
+                help
+            );
+        }
+        assert!(!help.contains("(auto, rust, binary, ast)"), "{}", help);
+    }
+
     #[test]
     fn test_source_language_values() {
         let crusty = SourceLanguage::Crusty;
@@ -527,12 +2501,7 @@ mod tests {
     fn test_auto_detect_source_language_crusty() {
         let opts = CompilerOptions {
             input_file: PathBuf::from("test.crst"),
-            output_file: None,
-            out_dir: None,
-            emit: EmitMode::Auto,
-            absorb: None,
-            verbose: false,
-            no_compile: false,
+            ..default_options()
         };
 
         assert_eq!(opts.get_source_language(), SourceLanguage::Crusty);
@@ -542,12 +2511,7 @@ mod tests {
     fn test_auto_detect_source_language_rust() {
         let opts = CompilerOptions {
             input_file: PathBuf::from("test.rs"),
-            output_file: None,
-            out_dir: None,
-            emit: EmitMode::Auto,
-            absorb: None,
-            verbose: false,
-            no_compile: false,
+            ..default_options()
         };
 
         assert_eq!(opts.get_source_language(), SourceLanguage::Rust);
@@ -557,12 +2521,8 @@ mod tests {
     fn test_explicit_absorb_overrides_detection() {
         let opts = CompilerOptions {
             input_file: PathBuf::from("test.crst"),
-            output_file: None,
-            out_dir: None,
-            emit: EmitMode::Auto,
             absorb: Some(SourceLanguage::Rust),
-            verbose: false,
-            no_compile: false,
+            ..default_options()
         };
 
         assert_eq!(opts.get_source_language(), SourceLanguage::Rust);
@@ -573,11 +2533,7 @@ mod tests {
         let opts = CompilerOptions {
             input_file: PathBuf::from("test.crst"),
             output_file: Some(PathBuf::from("output.rs")),
-            out_dir: None,
-            emit: EmitMode::Auto,
-            absorb: None,
-            verbose: false,
-            no_compile: false,
+            ..default_options()
         };
 
         assert_eq!(opts.get_emit_mode(), EmitMode::Rust);
@@ -588,11 +2544,7 @@ mod tests {
         let opts = CompilerOptions {
             input_file: PathBuf::from("test.crst"),
             output_file: Some(PathBuf::from("output.ast")),
-            out_dir: None,
-            emit: EmitMode::Auto,
-            absorb: None,
-            verbose: false,
-            no_compile: false,
+            ..default_options()
         };
 
         assert_eq!(opts.get_emit_mode(), EmitMode::Ast);
@@ -602,12 +2554,7 @@ mod tests {
     fn test_auto_detect_emit_mode_defaults_to_binary() {
         let opts = CompilerOptions {
             input_file: PathBuf::from("test.crst"),
-            output_file: None,
-            out_dir: None,
-            emit: EmitMode::Auto,
-            absorb: None,
-            verbose: false,
-            no_compile: false,
+            ..default_options()
         };
 
         assert_eq!(opts.get_emit_mode(), EmitMode::Binary);
@@ -618,11 +2565,8 @@ mod tests {
         let opts = CompilerOptions {
             input_file: PathBuf::from("test.crst"),
             output_file: Some(PathBuf::from("output.rs")),
-            out_dir: None,
             emit: EmitMode::Binary,
-            absorb: None,
-            verbose: false,
-            no_compile: false,
+            ..default_options()
         };
 
         assert_eq!(opts.get_emit_mode(), EmitMode::Binary);
@@ -633,11 +2577,7 @@ mod tests {
         let opts = CompilerOptions {
             input_file: PathBuf::from("test.crst"),
             output_file: Some(PathBuf::from("custom_output.rs")),
-            out_dir: None,
-            emit: EmitMode::Auto,
-            absorb: None,
-            verbose: false,
-            no_compile: false,
+            ..default_options()
         };
 
         assert_eq!(opts.get_output_path(), PathBuf::from("custom_output.rs"));
@@ -647,12 +2587,8 @@ mod tests {
     fn test_get_output_path_default_rust() {
         let opts = CompilerOptions {
             input_file: PathBuf::from("test.crst"),
-            output_file: None,
-            out_dir: None,
             emit: EmitMode::Rust,
-            absorb: None,
-            verbose: false,
-            no_compile: false,
+            ..default_options()
         };
 
         assert_eq!(opts.get_output_path(), PathBuf::from("test.rs"));
@@ -662,12 +2598,8 @@ mod tests {
     fn test_get_output_path_default_binary() {
         let opts = CompilerOptions {
             input_file: PathBuf::from("test.crst"),
-            output_file: None,
-            out_dir: None,
             emit: EmitMode::Binary,
-            absorb: None,
-            verbose: false,
-            no_compile: false,
+            ..default_options()
         };
 
         assert_eq!(opts.get_output_path(), PathBuf::from("test"));
@@ -677,12 +2609,8 @@ mod tests {
     fn test_get_output_path_default_ast() {
         let opts = CompilerOptions {
             input_file: PathBuf::from("test.crst"),
-            output_file: None,
-            out_dir: None,
             emit: EmitMode::Ast,
-            absorb: None,
-            verbose: false,
-            no_compile: false,
+            ..default_options()
         };
 
         assert_eq!(opts.get_output_path(), PathBuf::from("test.ast"));
@@ -730,11 +2658,9 @@ int add(int a, int b) {
         let options = CompilerOptions {
             input_file: input_path.clone(),
             output_file: Some(PathBuf::from("test_add_12345.rs")),
-            out_dir: None,
             emit: EmitMode::Rust,
-            absorb: None,
-            verbose: false,
             no_compile: true,
+            ..default_options()
         };
 
         let result = run_compiler(&options);
@@ -747,99 +2673,1400 @@ int add(int a, int b) {
     }
 
     #[test]
-    fn test_run_compiler_with_nonexistent_file() {
-        let options = CompilerOptions {
-            input_file: PathBuf::from("nonexistent_file_99999.crst"),
-            output_file: None,
-            out_dir: None,
-            emit: EmitMode::Auto,
-            absorb: None,
-            verbose: false,
-            no_compile: true,
-        };
-
-        let result = run_compiler(&options);
-        assert!(result.is_err());
-    }
-
-    #[test]
-    fn test_run_compiler_ast_mode() {
+    fn test_run_compiler_group_items() {
         use std::fs;
 
         let test_source = r#"
 int main() {
     return 0;
 }
+
+struct Point {
+    int x;
+}
 "#;
-        let input_path = PathBuf::from("test_ast_12345.crst");
+        let input_path = PathBuf::from("test_group_items_12345.crst");
+        let output_path = PathBuf::from("test_group_items_12345.rs");
         fs::write(&input_path, test_source).unwrap();
 
         let options = CompilerOptions {
             input_file: input_path.clone(),
-            output_file: Some(PathBuf::from("test_ast_12345.ast")),
-            out_dir: None,
-            emit: EmitMode::Ast,
-            absorb: None,
-            verbose: false,
+            output_file: Some(output_path.clone()),
+            emit: EmitMode::Rust,
             no_compile: true,
+            group_items: true,
+            ..default_options()
         };
 
         let result = run_compiler(&options);
+        let output = fs::read_to_string(&output_path).unwrap_or_default();
 
         // Clean up
         let _ = fs::remove_file(&input_path);
-        let _ = fs::remove_file("test_ast_12345.ast");
+        let _ = fs::remove_file(&output_path);
 
         assert!(result.is_ok());
+        assert!(output.find("struct Point").unwrap() < output.find("fn main").unwrap());
     }
 
     #[test]
-    fn test_run_compiler_rust_source_not_implemented() {
-        use std::fs;
+    fn test_lint_allows_defaults_and_extras() {
+        let mut opts = CompilerOptions::try_parse_from([
+            "crustyc",
+            "test.crst",
+            "--allow-lint",
+            "dead_code",
+            "--allow-lint",
+            "unused_parens",
+        ])
+        .unwrap();
+        assert_eq!(opts.get_lint_allows(), vec!["unused_parens", "dead_code"]);
+
+        opts.no_default_allows = true;
+        assert_eq!(opts.get_lint_allows(), vec!["dead_code", "unused_parens"]);
+    }
 
-        let test_source = "fn main() {}";
-        let input_path = PathBuf::from("test_rust_12345.rs");
-        fs::write(&input_path, test_source).unwrap();
+    #[test]
+    fn test_provenance_header() {
+        let opts = CompilerOptions::try_parse_from([
+            "crustyc",
+            "src/main.crst",
+            "--emit",
+            "rust",
+            "--group-items",
+        ])
+        .unwrap();
+        let header = opts.provenance_header();
+
+        assert_eq!(
+            header[0],
+            format!(
+                "Generated by crustyc {} from src/main.crst",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+        assert_eq!(header[1], "Options: --emit=rust --group-items");
+    }
 
-        let options = CompilerOptions {
-            input_file: input_path.clone(),
-            output_file: None,
-            out_dir: None,
-            emit: EmitMode::Auto,
-            absorb: None, // Will auto-detect as Rust from .rs extension
-            verbose: false,
-            no_compile: true,
-        };
+    #[test]
+    fn test_compile_db_flag_default_path() {
+        let opts =
+            CompilerOptions::try_parse_from(["crustyc", "main.crst", "--compile-db"]).unwrap();
+        assert_eq!(
+            opts.compile_db,
+            Some(PathBuf::from("compile_commands.json"))
+        );
 
-        let result = run_compiler(&options);
+        let opts =
+            CompilerOptions::try_parse_from(["crustyc", "main.crst", "--compile-db=db.json"])
+                .unwrap();
+        assert_eq!(opts.compile_db, Some(PathBuf::from("db.json")));
+    }
 
-        // Clean up
-        let _ = fs::remove_file(&input_path);
+    #[test]
+    fn test_source_comments_flag_is_recorded_in_header() {
+        let opts =
+            CompilerOptions::try_parse_from(["crustyc", "main.crst", "--source-comments"]).unwrap();
+        assert!(opts.source_comments);
+        assert!(opts.provenance_header()[1].ends_with("--source-comments"));
+    }
 
-        // Should fail because Rust parsing is not yet implemented
-        assert!(result.is_err());
+    #[test]
+    fn test_wide_strings_flag() {
+        use crate::codegen::WideStringMapping;
+
+        let opts = CompilerOptions::try_parse_from(["crustyc", "main.crst"]).unwrap();
+        assert_eq!(opts.wide_strings, WideStringMapping::Utf16);
+        assert!(!opts.provenance_header()[1].contains("--wide-strings"));
+
+        let opts =
+            CompilerOptions::try_parse_from(["crustyc", "main.crst", "--wide-strings=widestring"])
+                .unwrap();
+        assert_eq!(opts.wide_strings, WideStringMapping::WideString);
+        assert!(opts.provenance_header()[1].ends_with("--wide-strings=widestring"));
     }
 
     #[test]
-    fn test_auto_mode_with_crst_file() {
-        use std::fs;
+    fn test_debug_flag() {
+        let opts = CompilerOptions::try_parse_from(["crustyc", "main.crst"]).unwrap();
+        assert!(!opts.debug);
 
-        let test_source = r#"
-int square(int x) {
-    return x * x;
-}
-"#;
-        let input_path = PathBuf::from("test_auto_12345.crst");
-        fs::write(&input_path, test_source).unwrap();
+        let opts = CompilerOptions::try_parse_from(["crustyc", "main.crst", "-g"]).unwrap();
+        assert!(opts.debug);
+        assert!(opts.provenance_header()[1].ends_with("--debug"));
+    }
 
-        let options = CompilerOptions {
-            input_file: input_path.clone(),
-            output_file: Some(PathBuf::from("test_auto_12345.rs")),
-            out_dir: None,
-            emit: EmitMode::Auto, // Should auto-detect Rust from .rs output
-            absorb: None,         // Should auto-detect Crusty from .crst input
-            verbose: false,
+    #[test]
+    fn test_panic_flag() {
+        use crate::codegen::PanicPolicy;
+
+        let opts = CompilerOptions::try_parse_from(["crustyc", "main.crst"]).unwrap();
+        assert_eq!(opts.panic, PanicPolicy::Panic);
+        assert!(!opts.provenance_header()[1].contains("--panic"));
+
+        let opts =
+            CompilerOptions::try_parse_from(["crustyc", "main.crst", "--panic=result"]).unwrap();
+        assert_eq!(opts.panic, PanicPolicy::Result);
+        assert!(opts.provenance_header()[1].ends_with("--panic=result"));
+        assert!(
+            CompilerOptions::try_parse_from(["crustyc", "main.crst", "--panic=unwind"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_lib_flag_and_default_outputs() {
+        use crate::rustc::LibraryKind;
+
+        let opts = CompilerOptions::try_parse_from(["crustyc", "math-utils.crst"]).unwrap();
+        assert_eq!(opts.lib, None);
+        assert_eq!(opts.get_output_path(), PathBuf::from("math-utils"));
+
+        let opts =
+            CompilerOptions::try_parse_from(["crustyc", "math-utils.crst", "--lib"]).unwrap();
+        assert_eq!(opts.lib, Some(LibraryKind::Rlib));
+        assert_eq!(opts.get_output_path(), PathBuf::from("libmath_utils.rlib"));
+        assert!(opts.provenance_header()[1].ends_with("--lib=rlib"));
+
+        let opts = CompilerOptions::try_parse_from([
+            "crustyc",
+            "math-utils.crst",
+            "--lib=cdylib",
+            "--emit=rust",
+        ])
+        .unwrap();
+        assert_eq!(opts.lib, Some(LibraryKind::Cdylib));
+        assert_eq!(opts.get_output_path(), PathBuf::from("lib.rs"));
+    }
+
+    #[test]
+    fn test_entry_point_checks() {
+        use std::fs;
+
+        let input_path = PathBuf::from("test_entry_12345.crst");
+        let output_path = PathBuf::from("test_entry_12345.rs");
+        let run = |source: &str, flags: &[&str]| {
+            fs::write(&input_path, source).unwrap();
+            let mut args = vec![
+                "crustyc",
+                "test_entry_12345.crst",
+                "--emit=rust",
+                "-o",
+                "test_entry_12345.rs",
+            ];
+            args.extend_from_slice(flags);
+            let result = run_compiler(&CompilerOptions::try_parse_from(args).unwrap());
+            result.err().map(|e| e.to_string()).unwrap_or_default()
+        };
+
+        let started = run("int start() { return 0; }\n", &["--entry", "start"]);
+        let code = fs::read_to_string(&output_path).unwrap_or_default();
+        let missing = run("int start() { return 0; }\n", &["--entry", "begin"]);
+        let with_params = run("int start(int n) { return n; }\n", &["--entry", "start"]);
+        let clashing = run("void main() {}\nvoid start() {}\n", &["--entry", "start"]);
+        let freestanding = run("void reset() {}\n", &["--freestanding"]);
+        let timed = run("void reset() {\n    sleep(1);\n}\n", &["--freestanding"]);
+
+        let _ = fs::remove_file(&input_path);
+        let _ = fs::remove_file(&output_path);
+
+        assert_eq!(started, "");
+        assert!(
+            code.contains("fn main() {\n    std::process::exit(start());\n}"),
+            "{}",
+            code
+        );
+        assert!(code.contains("--entry=start"), "{}", code);
+        assert!(
+            missing.contains("no function 'begin' to use as the entry point"),
+            "{}",
+            missing
+        );
+        assert!(
+            with_params.contains("must take no parameters and return void or an integer"),
+            "{}",
+            with_params
+        );
+        assert!(
+            clashing.contains("function 'main' conflicts"),
+            "{}",
+            clashing
+        );
+        assert_eq!(freestanding, "");
+        assert!(
+            timed.contains("sleep() needs the standard library"),
+            "{}",
+            timed
+        );
+
+        let opts =
+            CompilerOptions::try_parse_from(["crustyc", "blink.crst", "--freestanding"]).unwrap();
+        assert_eq!(opts.get_output_path(), PathBuf::from("blink.o"));
+        assert!(CompilerOptions::try_parse_from([
+            "crustyc",
+            "blink.crst",
+            "--freestanding",
+            "--lib"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_bench_checks() {
+        use std::fs;
+
+        let input_path = PathBuf::from("test_bench_12345.crst");
+        let run = |source: &str| {
+            fs::write(&input_path, source).unwrap();
+            let args = ["crustyc", "test_bench_12345.crst", "--bench"];
+            let result = run_compiler(&CompilerOptions::try_parse_from(args).unwrap());
+            result.err().map(|e| e.to_string()).unwrap_or_default()
+        };
+
+        let unmarked = run("int fib(int n) { return n; }\n");
+        let with_params = run("#[bench]\nint fib(int n) { return n; }\n");
+
+        let _ = fs::remove_file(&input_path);
+
+        assert!(
+            unmarked.contains("no #[bench] functions to run"),
+            "{}",
+            unmarked
+        );
+        assert!(
+            with_params.contains("#[bench] function 'fib' must take no parameters"),
+            "{}",
+            with_params
+        );
+        for conflicting in ["--lib", "--emit=rust", "--freestanding"] {
+            assert!(CompilerOptions::try_parse_from([
+                "crustyc",
+                "fib.crst",
+                "--bench",
+                conflicting
+            ])
+            .is_err());
+        }
+    }
+
+    #[test]
+    fn test_coverage_checks() {
+        use std::fs;
+
+        let input_path = PathBuf::from("test_coverage_12345.crst");
+        fs::write(&input_path, "int sign(int x) { return x; }\n").unwrap();
+        let args = ["crustyc", "test_coverage_12345.crst", "--coverage"];
+        let untested = run_compiler(&CompilerOptions::try_parse_from(args).unwrap())
+            .err()
+            .map(|e| e.to_string())
+            .unwrap_or_default();
+        let _ = fs::remove_file(&input_path);
+
+        assert!(
+            untested.contains("no #[test] functions to run"),
+            "{}",
+            untested
+        );
+        let opts = CompilerOptions::try_parse_from(["crustyc", "sign.crst", "--coverage"]).unwrap();
+        assert!(opts.code_flags().contains(&"--coverage".to_string()));
+        for conflicting in ["--lib", "--bench", "--no-compile"] {
+            assert!(CompilerOptions::try_parse_from([
+                "crustyc",
+                "sign.crst",
+                "--coverage",
+                conflicting
+            ])
+            .is_err());
+        }
+    }
+
+    #[test]
+    fn test_run_compiler_emits_source_map() {
+        use std::fs;
+
+        let input_path = PathBuf::from("test_source_map_12345.crst");
+        let output_path = PathBuf::from("test_source_map_12345.rs");
+        let map_path = source_map_path(&output_path);
+        fs::write(
+            &input_path,
+            "int twice(int n) {\n    let m = n * 2;\n    return m;\n}\n",
+        )
+        .unwrap();
+
+        let opts = CompilerOptions::try_parse_from([
+            "crustyc",
+            "test_source_map_12345.crst",
+            "--emit=rust",
+            "--no-header",
+            "--emit-sourcemap",
+            "-o",
+            "test_source_map_12345.rs",
+        ])
+        .unwrap();
+        let result = run_compiler(&opts);
+        let map = fs::read_to_string(&map_path).unwrap_or_default();
+
+        let _ = fs::remove_file(&input_path);
+        let _ = fs::remove_file(&output_path);
+        let _ = fs::remove_file(&map_path);
+
+        assert!(result.is_ok(), "{:?}", result.err());
+        assert_eq!(map_path, PathBuf::from("test_source_map_12345.rs.map"));
+        let map: crate::source_map::SourceMap = serde_json::from_str(&map).unwrap();
+        assert_eq!(map.file, "test_source_map_12345.rs");
+        assert_eq!(map.sources, vec!["test_source_map_12345.crst"]);
+        let lines: Vec<(usize, usize)> = map
+            .mappings
+            .iter()
+            .map(|m| (m.generated_line, m.line))
+            .collect();
+        assert_eq!(lines, vec![(3, 1), (4, 2), (5, 3)]);
+    }
+
+    #[test]
+    fn test_run_compiler_checks_determinism_and_emits_item_hashes() {
+        use std::fs;
+
+        let input_path = PathBuf::from("test_item_hashes_12345.crst");
+        let output_path = PathBuf::from("test_item_hashes_12345.rs");
+        let hashes_path = item_hashes_path(&output_path);
+        fs::write(
+            &input_path,
+            "struct Point {\n    int x;\n}\nint twice(int n) {\n    return n * 2;\n}\n",
+        )
+        .unwrap();
+
+        let opts = CompilerOptions::try_parse_from([
+            "crustyc",
+            "test_item_hashes_12345.crst",
+            "--emit=rust",
+            "--check-determinism",
+            "--emit-item-hashes",
+            "-o",
+            "test_item_hashes_12345.rs",
+        ])
+        .unwrap();
+        let result = run_compiler(&opts);
+        let hashes = fs::read_to_string(&hashes_path).unwrap_or_default();
+
+        let _ = fs::remove_file(&input_path);
+        let _ = fs::remove_file(&output_path);
+        let _ = fs::remove_file(&hashes_path);
+
+        assert!(result.is_ok(), "{:?}", result.err());
+        assert_eq!(
+            hashes_path,
+            PathBuf::from("test_item_hashes_12345.rs.hashes")
+        );
+        let hashes: crate::determinism::ItemHashes = serde_json::from_str(&hashes).unwrap();
+        assert_eq!(hashes.file, "test_item_hashes_12345.rs");
+        let names: Vec<&str> = hashes.items.iter().map(|item| item.name.as_str()).collect();
+        assert_eq!(names, ["Point", "twice"]);
+        assert!(hashes.items.iter().all(|item| item.hash.len() == 16));
+    }
+
+    #[test]
+    fn test_run_compiler_with_target_skips_disabled_items() {
+        use std::fs;
+
+        // Needs rustc to report the host's configuration
+        let Some(host) = std::process::Command::new("rustc")
+            .arg("-vV")
+            .output()
+            .ok()
+            .and_then(|output| {
+                String::from_utf8_lossy(&output.stdout)
+                    .lines()
+                    .find_map(|line| line.strip_prefix("host: ").map(str::to_string))
+            })
+        else {
+            return;
+        };
+
+        let input_path = PathBuf::from("test_target_cfg_12345.crst");
+        let output_path = PathBuf::from("test_target_cfg_12345.rs");
+        fs::write(
+            &input_path,
+            "#[cfg(any())]\nint level() { return 0; }\n#[cfg(all())]\nint level() { return 1; }\n",
+        )
+        .unwrap();
+
+        let mut opts = CompilerOptions::try_parse_from([
+            "crustyc",
+            "test_target_cfg_12345.crst",
+            "--emit=rust",
+            "-o",
+            "test_target_cfg_12345.rs",
+        ])
+        .unwrap();
+        let without_target = run_compiler(&opts);
+        opts.target = Some(host.clone());
+        let with_target = run_compiler(&opts);
+        let code = fs::read_to_string(&output_path).unwrap_or_default();
+
+        let _ = fs::remove_file(&input_path);
+        let _ = fs::remove_file(&output_path);
+
+        assert!(without_target
+            .unwrap_err()
+            .to_string()
+            .contains("'level' already defined"));
+        assert!(with_target.is_ok(), "{:?}", with_target.err());
+        assert!(code.contains("#[cfg(all())]\npub fn level()"), "{}", code);
+        assert!(!code.contains("any()"), "{}", code);
+        assert!(code.contains(&format!("--target={}", host)), "{}", code);
+    }
+
+    #[test]
+    fn test_run_compiler_lowers_for_the_target() {
+        use std::fs;
+
+        // Needs rustc to describe both targets
+        let targets = ["x86_64-unknown-linux-gnu", "x86_64-pc-windows-msvc"];
+        if targets
+            .iter()
+            .any(|target| crate::cfg::TargetCfg::query(target).is_err())
+        {
+            return;
+        }
+
+        let input_path = PathBuf::from("test_target_platform_12345.crst");
+        let output_path = PathBuf::from("test_target_platform_12345.rs");
+        fs::write(
+            &input_path,
+            "#[cfg(windows)]\nint family() { return 1; }\n#[cfg(unix)]\nint family() { return 2; }\nlong ticks(long t) {\n    return t + 1;\n}\nvoid main() {\n    static_assert(sizeof(long) == 8, \"long is 64 bits\");\n    raise(SIGABRT);\n}\n",
+        )
+        .unwrap();
+        let mut opts = CompilerOptions::try_parse_from([
+            "crustyc",
+            "test_target_platform_12345.crst",
+            "--emit=rust",
+            "-o",
+            "test_target_platform_12345.rs",
+        ])
+        .unwrap();
+
+        opts.target = Some(targets[0].to_string());
+        let linux = run_compiler(&opts);
+        let linux_code = fs::read_to_string(&output_path).unwrap_or_default();
+        opts.target = Some(targets[1].to_string());
+        let windows = run_compiler(&opts);
+
+        let _ = fs::remove_file(&input_path);
+        let _ = fs::remove_file(&output_path);
+
+        assert!(linux.is_ok(), "{:?}", linux.err());
+        assert!(
+            linux_code.contains("pub fn ticks(t: i64) -> i64"),
+            "{}",
+            linux_code
+        );
+        assert!(linux_code.contains("raise(6)"), "{}", linux_code);
+        assert!(linux_code.contains("return 2;"), "{}", linux_code);
+        assert!(windows
+            .unwrap_err()
+            .to_string()
+            .contains("static assertion failed: long is 64 bits"));
+    }
+
+    #[test]
+    fn test_color_option() {
+        let parse = |args: &[&str]| {
+            CompilerOptions::try_parse_from(["crustyc", "test.crst"].iter().chain(args)).unwrap()
+        };
+        assert_eq!(parse(&[]).color, ColorChoice::Auto);
+        assert!(parse(&["--color", "always"]).color.enabled());
+        assert!(!parse(&["--color=never"]).color.enabled());
+        assert!(
+            CompilerOptions::try_parse_from(["crustyc", "test.crst", "--color", "often"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_report_size_flag_default_budget() {
+        let opts = CompilerOptions::try_parse_from(["crustyc", "main.crst"]).unwrap();
+        assert_eq!(opts.report_size, None);
+
+        let opts =
+            CompilerOptions::try_parse_from(["crustyc", "main.crst", "--report-size"]).unwrap();
+        assert_eq!(
+            opts.report_size,
+            Some(crate::size_report::DEFAULT_EXPANSION_BUDGET)
+        );
+
+        let opts =
+            CompilerOptions::try_parse_from(["crustyc", "main.crst", "--report-size=5"]).unwrap();
+        assert_eq!(opts.report_size, Some(5.0));
+    }
+
+    #[test]
+    fn test_run_compiler_with_tail_calls() {
+        use std::fs;
+
+        let input_path = PathBuf::from("test_tail_calls_12345.crst");
+        let output_path = PathBuf::from("test_tail_calls_12345.rs");
+        fs::write(
+            &input_path,
+            "int sum(int n, int acc) {\n    if (n == 0) { return acc; }\n    return sum(n - 1, acc + n);\n}\n",
+        )
+        .unwrap();
+
+        let mut opts = CompilerOptions::try_parse_from([
+            "crustyc",
+            "test_tail_calls_12345.crst",
+            "--emit=rust",
+            "--tail-calls",
+            "-o",
+            "test_tail_calls_12345.rs",
+        ])
+        .unwrap();
+        let result = run_compiler(&opts);
+        let code = fs::read_to_string(&output_path).unwrap_or_default();
+
+        opts.tail_calls = false;
+        let _ = run_compiler(&opts);
+        let plain = fs::read_to_string(&output_path).unwrap_or_default();
+
+        let _ = fs::remove_file(&input_path);
+        let _ = fs::remove_file(&output_path);
+
+        assert!(result.is_ok());
+        assert!(
+            code.contains("Options: --emit=rust --tail-calls"),
+            "{}",
+            code
+        );
+        assert!(code.contains("continue 'tail_call;"), "{}", code);
+        assert!(!plain.contains("'tail_call"), "{}", plain);
+    }
+
+    #[test]
+    fn test_run_compiler_with_inline_macros() {
+        use std::fs;
+
+        let input_path = PathBuf::from("test_inline_macros_12345.crst");
+        let output_path = PathBuf::from("test_inline_macros_12345.rs");
+        fs::write(
+            &input_path,
+            "#define __SQUARE__(x) x * x\nint area(int w) {\n    return __SQUARE__(w + 1);\n}\n",
+        )
+        .unwrap();
+
+        let mut opts = CompilerOptions::try_parse_from([
+            "crustyc",
+            "test_inline_macros_12345.crst",
+            "--emit=rust",
+            "--inline-macros",
+            "-o",
+            "test_inline_macros_12345.rs",
+        ])
+        .unwrap();
+        let result = run_compiler(&opts);
+        let code = fs::read_to_string(&output_path).unwrap_or_default();
+
+        opts.inline_macros = false;
+        let _ = run_compiler(&opts);
+        let plain = fs::read_to_string(&output_path).unwrap_or_default();
+
+        let _ = fs::remove_file(&input_path);
+        let _ = fs::remove_file(&output_path);
+
+        assert!(result.is_ok());
+        assert!(
+            code.contains("Options: --emit=rust --inline-macros"),
+            "{}",
+            code
+        );
+        assert!(code.contains("return square((w + 1));"), "{}", code);
+        assert!(plain.contains("return ((w + 1) * (w + 1));"), "{}", plain);
+    }
+
+    #[test]
+    fn test_run_compiler_with_defines() {
+        use std::fs;
+
+        let input_path = PathBuf::from("test_defines_12345.crst");
+        let output_path = PathBuf::from("test_defines_12345.rs");
+        fs::write(
+            &input_path,
+            "#ifdef VERBOSE
+int level() { return 2; }
+#else
+int level() { return 0 }
+#endif
+",
+        )
+        .unwrap();
+
+        let mut opts = CompilerOptions::try_parse_from([
+            "crustyc",
+            "test_defines_12345.crst",
+            "--emit=rust",
+            "-D",
+            "VERBOSE",
+            "-o",
+            "test_defines_12345.rs",
+        ])
+        .unwrap();
+        let result = run_compiler(&opts);
+        let code = fs::read_to_string(&output_path).unwrap_or_default();
+
+        // The #else branch is missing a semicolon, so it only fails to
+        // parse once it is compiled in
+        opts.defines.clear();
+        let without = run_compiler(&opts);
+
+        let _ = fs::remove_file(&input_path);
+        let _ = fs::remove_file(&output_path);
+
+        assert!(result.is_ok(), "{:?}", result);
+        assert!(code.contains("--define=VERBOSE"), "{}", code);
+        assert!(code.contains("return 2;"), "{}", code);
+        let err = without.unwrap_err().to_string();
+        assert!(err.starts_with("Parse error at 4:"), "{}", err);
+    }
+
+    #[test]
+    fn test_run_compiler_with_int_conditions() {
+        use std::fs;
+
+        let input_path = PathBuf::from("test_int_conditions_12345.crst");
+        let output_path = PathBuf::from("test_int_conditions_12345.rs");
+        fs::write(
+            &input_path,
+            "int count(int n) {\n    var int left = n;\n    var int steps = 0;\n    while (left) {\n        left = left - 1;\n        steps = steps + 1;\n    }\n    return steps;\n}\n",
+        )
+        .unwrap();
+
+        let opts = CompilerOptions::try_parse_from([
+            "crustyc",
+            "test_int_conditions_12345.crst",
+            "--emit=rust",
+            "-o",
+            "test_int_conditions_12345.rs",
+        ])
+        .unwrap();
+        assert_eq!(opts.int_conditions, crate::semantic::IntConditions::Strict);
+        let strict = run_compiler(&opts);
+
+        // A bare --int-conditions selects compat mode
+        let opts = CompilerOptions::try_parse_from([
+            "crustyc",
+            "test_int_conditions_12345.crst",
+            "--emit=rust",
+            "-o",
+            "test_int_conditions_12345.rs",
+            "--int-conditions",
+        ])
+        .unwrap();
+        assert_eq!(opts.int_conditions, crate::semantic::IntConditions::Compat);
+        let compat = run_compiler(&opts);
+        let code = fs::read_to_string(&output_path).unwrap_or_default();
+
+        let _ = fs::remove_file(&input_path);
+        let _ = fs::remove_file(&output_path);
+
+        assert!(strict
+            .unwrap_err()
+            .to_string()
+            .contains("while condition must be boolean, found int; write 'left != 0'"));
+        assert!(compat.is_ok());
+        assert!(
+            code.contains("Options: --emit=rust --int-conditions=compat"),
+            "{}",
+            code
+        );
+        assert!(code.contains("while (left != 0) {"), "{}", code);
+    }
+
+    #[test]
+    fn test_run_compiler_with_unqualified_variants() {
+        use std::fs;
+
+        let input_path = PathBuf::from("test_unqualified_variants_12345.crst");
+        let output_path = PathBuf::from("test_unqualified_variants_12345.rs");
+        fs::write(
+            &input_path,
+            "enum Color { Red, Green }\n\nvoid main() {\n    Color c = Green;\n}\n",
+        )
+        .unwrap();
+
+        let mut opts = CompilerOptions::try_parse_from([
+            "crustyc",
+            "test_unqualified_variants_12345.crst",
+            "--emit=rust",
+            "-o",
+            "test_unqualified_variants_12345.rs",
+        ])
+        .unwrap();
+        let strict = run_compiler(&opts);
+
+        opts.unqualified_variants = true;
+        let compat = run_compiler(&opts);
+        let code = fs::read_to_string(&output_path).unwrap_or_default();
+
+        let _ = fs::remove_file(&input_path);
+        let _ = fs::remove_file(&output_path);
+
+        assert!(strict.is_err());
+        assert!(compat.is_ok());
+        assert!(code.contains("let c: Color = Color::Green;"), "{}", code);
+    }
+
+    #[test]
+    fn test_run_compiler_with_imported_modules() {
+        use std::fs;
+
+        let dir = std::env::temp_dir().join("crustyc_modules_cli_12345");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("main.crst"),
+            "#import shapes.area\n\nint main() {\n    return area(2, 3);\n}\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("shapes.crst"),
+            "#import util.clamp\n\nint area(int w, int h) {\n    return clamp(w) * clamp(h);\n}\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("util.crst"),
+            "int clamp(int x) {\n    return x;\n}\n",
+        )
+        .unwrap();
+
+        let output_path = dir.join("main.rs");
+        let opts = CompilerOptions::try_parse_from([
+            "crustyc".as_ref(),
+            dir.join("main.crst").as_os_str(),
+            "--emit=rust".as_ref(),
+            "-o".as_ref(),
+            output_path.as_os_str(),
+        ])
+        .unwrap();
+        let result = run_compiler(&opts);
+        let code = fs::read_to_string(&output_path).unwrap_or_default();
+
+        // A module that fails to check is reported against its own file
+        fs::write(
+            dir.join("util.crst"),
+            "int clamp(int x) {\n    return y;\n}\n",
+        )
+        .unwrap();
+        let failed = run_compiler(&opts);
+
+        let _ = fs::remove_dir_all(&dir);
+
+        assert!(result.is_ok(), "{:?}", result);
+        assert!(code.contains("use crate::shapes::area;"), "{}", code);
+        let util = code.find("pub mod util {").expect(&code);
+        let shapes = code.find("pub mod shapes {").expect(&code);
+        assert!(util < shapes, "{}", code);
+        assert!(code.contains("    use crate::util::clamp;"), "{}", code);
+        let err = failed.unwrap_err().to_string();
+        assert!(err.contains("util.crst: undefined variable 'y'"), "{}", err);
+    }
+
+    #[test]
+    fn test_run_compiler_writes_compile_db() {
+        use std::fs;
+
+        let dir = std::env::temp_dir().join("crustyc_compile_db_cli_12345");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let first = dir.join("first.crst");
+        let second = dir.join("second.crst");
+        let db_path = dir.join("compile_commands.json");
+        fs::write(&first, "#define __LIMIT__ 10\nint main() { return 0; }\n").unwrap();
+        fs::write(&second, "int helper() { return 1; }\n").unwrap();
+
+        for (input, output) in [(&first, "first.rs"), (&second, "second.rs")] {
+            let mut options = CompilerOptions::try_parse_from([
+                "crustyc",
+                input.to_str().unwrap(),
+                "--emit=rust",
+            ])
+            .unwrap();
+            options.output_file = Some(dir.join(output));
+            options.compile_db = Some(db_path.clone());
+            assert!(run_compiler(&options).is_ok());
+        }
+
+        let db = CompilationDatabase::load(&db_path);
+        let _ = fs::remove_dir_all(&dir);
+        let db = db.unwrap();
+
+        assert_eq!(db.commands().len(), 2);
+        let entry = db.find(&first).unwrap();
+        assert_eq!(entry.defines, vec!["__LIMIT__".to_string()]);
+        assert_eq!(entry.language, "crusty");
+        assert_eq!(entry.include_paths, vec![dir.display().to_string()]);
+        assert_eq!(entry.output, dir.join("first.rs").display().to_string());
+        assert!(entry.arguments.contains(&"--emit=rust".to_string()));
+        assert!(db.find(&second).unwrap().defines.is_empty());
+    }
+
+    #[test]
+    fn test_run_compiler_writes_header_unless_disabled() {
+        use std::fs;
+
+        let input_path = PathBuf::from("test_header_12345.crst");
+        let output_path = PathBuf::from("test_header_12345.rs");
+        fs::write(&input_path, "int main() { return 0; }\n").unwrap();
+
+        let mut options = CompilerOptions::try_parse_from([
+            "crustyc",
+            "test_header_12345.crst",
+            "-o",
+            "test_header_12345.rs",
+            "--no-compile",
+        ])
+        .unwrap();
+        let with_header =
+            run_compiler(&options).map(|_| fs::read_to_string(&output_path).unwrap_or_default());
+
+        options.no_header = true;
+        options.no_default_allows = true;
+        let without_header =
+            run_compiler(&options).map(|_| fs::read_to_string(&output_path).unwrap_or_default());
+
+        // Clean up
+        let _ = fs::remove_file(&input_path);
+        let _ = fs::remove_file(&output_path);
+
+        let with_header = with_header.unwrap();
+        assert!(with_header.starts_with("// Generated by crustyc"));
+        assert!(with_header.contains("#![allow(unused_parens)]"));
+        let without_header = without_header.unwrap();
+        assert!(without_header.starts_with("pub fn main()"));
+    }
+
+    #[test]
+    fn test_run_compiler_with_nonexistent_file() {
+        let options = CompilerOptions {
+            input_file: PathBuf::from("nonexistent_file_99999.crst"),
             no_compile: true,
+            ..default_options()
+        };
+
+        let result = run_compiler(&options);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_run_compiler_ast_mode() {
+        use std::fs;
+
+        let test_source = r#"
+int main() {
+    return 0;
+}
+"#;
+        let input_path = PathBuf::from("test_ast_12345.crst");
+        fs::write(&input_path, test_source).unwrap();
+
+        let options = CompilerOptions {
+            input_file: input_path.clone(),
+            output_file: Some(PathBuf::from("test_ast_12345.ast")),
+            emit: EmitMode::Ast,
+            no_compile: true,
+            ..default_options()
+        };
+
+        let result = run_compiler(&options);
+
+        // Clean up
+        let _ = fs::remove_file(&input_path);
+        let _ = fs::remove_file("test_ast_12345.ast");
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_run_compiler_check_mode() {
+        use std::fs;
+
+        let input_path = PathBuf::from("test_check_12345.crst");
+        fs::write(&input_path, "int main() {\n    return 0;\n}\n").unwrap();
+        let options =
+            CompilerOptions::try_parse_from(["crustyc", "--check", "test_check_12345.crst"])
+                .unwrap();
+        assert!(options.check_only);
+        let passed = run_compiler(&options);
+        let wrote_output = PathBuf::from("test_check_12345").exists();
+
+        fs::write(
+            &input_path,
+            "int main() {\n    int x = true;\n    return y;\n}\n",
+        )
+        .unwrap();
+        let failed = run_compiler(&options);
+
+        // Clean up
+        let _ = fs::remove_file(&input_path);
+        let _ = fs::remove_file("test_check_12345");
+
+        assert!(passed.is_ok(), "{:?}", passed);
+        assert!(!wrote_output);
+        match failed {
+            Err(CompilerError::Semantic(errors)) => assert_eq!(errors.len(), 2),
+            other => panic!("expected semantic errors, got {:?}", other),
+        }
+
+        assert!(CompilerOptions::try_parse_from([
+            "crustyc",
+            "--check",
+            "main.crst",
+            "-o",
+            "main.rs"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_run_compiler_resource_limits() {
+        use std::fs;
+
+        let input_path = PathBuf::from("test_resource_limits_12345.crst");
+        fs::write(
+            &input_path,
+            "#define __TWICE__(x) x + x\nstatic_assert(1 + 2 + 3 == 6, \"sum\");\nint main() {\n    return __TWICE__(3);\n}\n",
+        )
+        .unwrap();
+        let check = |args: &[&str]| {
+            let mut argv = vec!["crustyc", "--check", "test_resource_limits_12345.crst"];
+            argv.extend_from_slice(args);
+            run_compiler(&CompilerOptions::try_parse_from(argv).unwrap())
+        };
+        let within = check(&[]);
+        let nodes = check(&["--max-ast-nodes", "5"]);
+        let tokens = check(&["--max-expansion-tokens", "2"]);
+        let steps = check(&["--max-const-eval-steps", "2"]);
+        let _ = fs::remove_file(&input_path);
+
+        assert!(within.is_ok(), "{:?}", within);
+        let message = |result: crate::error::Result<()>| match result {
+            Err(CompilerError::Semantic(errors)) => errors[0].message.clone(),
+            other => panic!("expected semantic errors, got {:?}", other),
+        };
+        assert_eq!(
+            message(nodes),
+            "the program has 13 syntax tree nodes once its macros are expanded, more than the limit of 5; raise it with --max-ast-nodes"
+        );
+        assert_eq!(message(tokens), "macro expansion exceeded 2 tokens");
+        assert!(message(steps).contains("evaluation exceeded 2 steps"));
+    }
+
+    #[test]
+    fn test_run_compiler_emit_crusty_desugared() {
+        use std::fs;
+
+        let input_path = PathBuf::from("test_desugared_12345.crst");
+        let output_path = PathBuf::from("test_desugared_12345.desugared.crst");
+        fs::write(
+            &input_path,
+            "#define __TWICE__(x) (x + x)\nint clamp(int n) {\n    var total = __TWICE__(n);\n    total += 1;\n    return total > 9 ? 9 : total;\n}\nint main() {\n    return clamp(3);\n}\n",
+        )
+        .unwrap();
+        let options = CompilerOptions::try_parse_from([
+            "crustyc",
+            "test_desugared_12345.crst",
+            "--emit=crusty-desugared",
+        ])
+        .unwrap();
+        let result = run_compiler(&options);
+        let desugared = fs::read_to_string(&output_path);
+        let _ = fs::remove_file(&input_path);
+        let _ = fs::remove_file(&output_path);
+
+        assert!(result.is_ok(), "{:?}", result);
+        let desugared = desugared.unwrap();
+        assert!(
+            desugared.contains(
+                "    var total = (int)(n + n);\n    total = (total + 1);\n    if ((total > 9)) {\n        return 9;\n    } else {\n        return total;\n    }\n"
+            ),
+            "{}",
+            desugared
+        );
+        // The output is Crusty the compiler accepts
+        assert!(
+            crate::parser::Parser::new(&desugared)
+                .unwrap()
+                .parse_file()
+                .is_ok(),
+            "{}",
+            desugared
+        );
+    }
+
+    #[test]
+    fn test_run_compiler_self_profile() {
+        use std::fs;
+
+        let input_path = PathBuf::from("test_self_profile_12345.crst");
+        fs::write(
+            &input_path,
+            "int twice(int x) {\n    return x * 2;\n}\nint main() {\n    return twice(3);\n}\n",
+        )
+        .unwrap();
+        let profile = |path: &str| {
+            let options = CompilerOptions::try_parse_from([
+                "crustyc",
+                "--check",
+                "test_self_profile_12345.crst",
+                &format!("--self-profile={}", path),
+            ])
+            .unwrap();
+            let result = run_compiler(&options);
+            let profile = fs::read_to_string(path);
+            let _ = fs::remove_file(path);
+            (result, profile)
+        };
+        let (folded_result, folded) = profile("test_self_profile_12345.folded");
+        let (trace_result, trace) = profile("test_self_profile_12345.json");
+        let _ = fs::remove_file(&input_path);
+
+        assert!(folded_result.is_ok(), "{:?}", folded_result);
+        assert!(trace_result.is_ok(), "{:?}", trace_result);
+        let folded = folded.unwrap();
+        let stacks: Vec<&str> = folded
+            .lines()
+            .map(|line| line.rsplit_once(' ').unwrap().0)
+            .collect();
+        assert_eq!(
+            stacks,
+            [
+                "crustyc;preprocess;test_self_profile_12345.crst",
+                "crustyc;parse;test_self_profile_12345.crst",
+                "crustyc;expand;test_self_profile_12345.crst",
+                "crustyc;check;test_self_profile_12345.crst",
+                "crustyc;check;test_self_profile_12345.crst;twice",
+                "crustyc;check;test_self_profile_12345.crst;main",
+                "crustyc;lint;test_self_profile_12345.crst",
+            ]
+        );
+        let trace: serde_json::Value = serde_json::from_str(&trace.unwrap()).unwrap();
+        assert!(trace["traceEvents"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|event| event["cat"] == "check" && event["name"] == "twice"));
+    }
+
+    #[test]
+    fn test_run_compiler_lint_levels() {
+        use std::fs;
+
+        let input_path = PathBuf::from("test_lint_levels_12345.crst");
+        let check = |args: &[&str]| {
+            let mut argv = vec!["crustyc", "--check", "test_lint_levels_12345.crst"];
+            argv.extend_from_slice(args);
+            run_compiler(&CompilerOptions::try_parse_from(argv).unwrap())
+        };
+
+        fs::write(
+            &input_path,
+            "void main() {\n    var int x = 1;\n    x + 1;\n}\n",
+        )
+        .unwrap();
+        let warned = check(&[]);
+        let denied = check(&["-E", "no_effect"]);
+        let allowed = check(&["--deny", "no_effect", "-A", "no_effect"]);
+        let unknown = check(&["-W", "no_efect"]);
+        let defined = check(&["-D", "no_effect"]);
+        fs::write(
+            &input_path,
+            "#[allow(no_effect)]\nvoid main() {\n    var int x = 1;\n    x + 1;\n}\n",
+        )
+        .unwrap();
+        let attribute = check(&["--deny", "no_effect"]);
+        let _ = fs::remove_file(&input_path);
+
+        assert!(warned.is_ok(), "{:?}", warned);
+        // The summary is not a diagnostic of its own, so it has no code or
+        // location
+        let denied = denied.unwrap_err();
+        assert!(
+            matches!(denied, CompilerError::DeniedLints(1)),
+            "{:?}",
+            denied
+        );
+        let summary = &denied.diagnostics()[0];
+        assert_eq!(summary.message, "aborting due to 1 denied lint");
+        assert_eq!((&summary.code, &summary.span), (&None, &None));
+        assert!(allowed.is_err());
+        let unknown = unknown.unwrap_err();
+        assert!(matches!(unknown, CompilerError::Usage(_)), "{:?}", unknown);
+        assert!(unknown.to_string().contains("unknown lint 'no_efect'"));
+        assert_eq!(unknown.diagnostics()[0].code, None);
+        // -D defines a macro rather than denying a lint
+        assert!(defined.is_ok(), "{:?}", defined);
+        assert!(attribute.is_ok(), "{:?}", attribute);
+    }
+
+    #[test]
+    fn test_lint_flag_short_forms() {
+        let opts = CompilerOptions::try_parse_from([
+            "crustyc",
+            "main.crst",
+            "-A",
+            "unused",
+            "-W",
+            "flow",
+            "-E",
+            "shadowing",
+            "-D",
+            "DEBUG",
+        ])
+        .unwrap();
+        assert_eq!(opts.allow, ["unused"]);
+        assert_eq!(opts.warn, ["flow"]);
+        assert_eq!(opts.deny, ["shadowing"]);
+        assert_eq!(opts.defines, ["DEBUG"]);
+    }
+
+    #[test]
+    fn test_run_compiler_dead_code_mode() {
+        use std::fs;
+
+        let input_path = PathBuf::from("test_dead_code_12345.crst");
+        fs::write(
+            &input_path,
+            "int unused() {\n    return 1;\n}\nint main() {\n    return 0;\n}\n",
+        )
+        .unwrap();
+        let options = CompilerOptions::try_parse_from([
+            "crustyc",
+            "--dead-code=json",
+            "test_dead_code_12345.crst",
+        ])
+        .unwrap();
+        assert_eq!(
+            options.dead_code,
+            Some(crate::deadcode::DeadCodeFormat::Json)
+        );
+        let result = run_compiler(&options);
+        let wrote_output = PathBuf::from("test_dead_code_12345").exists();
+
+        // Clean up
+        let _ = fs::remove_file(&input_path);
+        let _ = fs::remove_file("test_dead_code_12345");
+
+        assert!(result.is_ok(), "{:?}", result);
+        assert!(!wrote_output);
+
+        let bare = CompilerOptions::try_parse_from(["crustyc", "main.crst", "--dead-code"]);
+        assert_eq!(
+            bare.unwrap().dead_code,
+            Some(crate::deadcode::DeadCodeFormat::Text)
+        );
+        assert!(CompilerOptions::try_parse_from([
+            "crustyc",
+            "--dead-code",
+            "--check",
+            "main.crst"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_run_compiler_multiple_inputs() {
+        use std::fs;
+
+        let dir = std::env::temp_dir().join("crusty_cli_multiple_inputs");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("lib")).unwrap();
+        fs::write(
+            dir.join("main.crst"),
+            "void main() {\n    __println__(\"{}\", twice(3));\n}\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("lib/twice.crst"),
+            "int twice(int x) {\n    return add(x, x);\n}\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("lib/add.crst"),
+            "int add(int a, int b) {\n    return a + b;\n}\n",
+        )
+        .unwrap();
+
+        let root = dir.join("main.crst");
+        let output = dir.join("out/main.rs");
+        let mut options = CompilerOptions::try_parse_from([
+            "crustyc".as_ref(),
+            root.as_os_str(),
+            dir.join("lib").as_os_str(),
+            "-o".as_ref(),
+            output.as_os_str(),
+        ])
+        .unwrap();
+        let combined = run_compiler(&options).map(|_| fs::read_to_string(&output).unwrap());
+
+        options.split_modules = true;
+        let split = run_compiler(&options).map(|_| {
+            (
+                fs::read_to_string(&output).unwrap(),
+                fs::read_to_string(dir.join("out/twice.rs")).unwrap(),
+            )
+        });
+        let _ = fs::remove_dir_all(&dir);
+
+        let combined = combined.unwrap();
+        assert!(
+            combined.contains("use crate::twice::twice;"),
+            "{}",
+            combined
+        );
+        assert!(combined.contains("pub mod add {"), "{}", combined);
+        assert!(
+            combined.contains("    use crate::add::add;"),
+            "{}",
+            combined
+        );
+        let (root, twice) = split.unwrap();
+        assert!(root.contains("pub mod add;\n"), "{}", root);
+        assert!(root.contains("pub mod twice;\n"), "{}", root);
+        assert!(twice.starts_with("use crate::add::add;"), "{}", twice);
+    }
+
+    #[test]
+    fn test_run_compiler_ast_bin_mode() {
+        use crate::archive::AstArchive;
+        use std::fs;
+
+        let input_path = PathBuf::from("test_ast_bin_12345.crst");
+        fs::write(&input_path, "int main() {\n    return 0;\n}\n").unwrap();
+
+        let mut options = CompilerOptions::try_parse_from([
+            "crustyc",
+            "test_ast_bin_12345.crst",
+            "-o",
+            "test_ast_bin_12345.crast",
+        ])
+        .unwrap();
+        assert_eq!(options.get_emit_mode(), EmitMode::AstBin);
+        options.no_compile = true;
+
+        let result = run_compiler(&options);
+        let bytes = fs::read("test_ast_bin_12345.crast");
+
+        // Clean up
+        let _ = fs::remove_file(&input_path);
+        let _ = fs::remove_file("test_ast_bin_12345.crast");
+
+        assert!(result.is_ok());
+        let archive = AstArchive::from_bytes(&bytes.unwrap()).unwrap();
+        assert_eq!(archive.source_path, "test_ast_bin_12345.crst");
+        assert_eq!(archive.symbol("main").unwrap().id.0, 0);
+    }
+
+    #[test]
+    fn test_run_compiler_tokens_mode() {
+        use std::fs;
+
+        let input_path = PathBuf::from("test_emit_12345.crst");
+        fs::write(&input_path, "int main() {\n    return 0;\n}\n").unwrap();
+
+        let options = CompilerOptions::try_parse_from([
+            "crustyc",
+            "test_emit_12345.crst",
+            "-o",
+            "test_emit_12345.tokens",
+        ])
+        .unwrap();
+        assert_eq!(options.get_emit_mode(), EmitMode::Tokens);
+        let tokens_result = run_compiler(&options);
+        let tokens = fs::read_to_string("test_emit_12345.tokens");
+
+        // Clean up
+        let _ = fs::remove_file(&input_path);
+        let _ = fs::remove_file("test_emit_12345.tokens");
+
+        assert!(tokens_result.is_ok(), "{:?}", tokens_result);
+        let tokens = tokens.unwrap();
+        assert!(
+            tokens.starts_with("1:1 Int \"int\"\n1:5 Ident(\"main\") \"main\"\n"),
+            "{}",
+            tokens
+        );
+        assert!(tokens.ends_with("3:1 RBrace \"}\"\n"), "{}", tokens);
+    }
+
+    #[test]
+    fn test_run_compiler_ast_json_mode() {
+        use std::fs;
+
+        let input_path = PathBuf::from("test_ast_json_12345.crst");
+        fs::write(&input_path, "int main() {\n    return 0;\n}\n").unwrap();
+
+        let options = CompilerOptions::try_parse_from([
+            "crustyc",
+            "test_ast_json_12345.crst",
+            "-o",
+            "test_ast_json_12345.json",
+        ])
+        .unwrap();
+        assert_eq!(options.get_emit_mode(), EmitMode::AstJson);
+        let result = run_compiler(&options);
+        let json = fs::read_to_string("test_ast_json_12345.json");
+
+        // Clean up
+        let _ = fs::remove_file(&input_path);
+        let _ = fs::remove_file("test_ast_json_12345.json");
+
+        assert!(result.is_ok(), "{:?}", result);
+        let file: crate::ast::File = serde_json::from_str(&json.unwrap()).unwrap();
+        assert!(matches!(
+            &file.items[0],
+            crate::ast::Item::Function(func) if func.name.name == "main"
+        ));
+    }
+
+    #[test]
+    fn test_run_compiler_rust_source_not_implemented() {
+        use std::fs;
+
+        let test_source = "fn main() {}";
+        let input_path = PathBuf::from("test_rust_12345.rs");
+        fs::write(&input_path, test_source).unwrap();
+
+        let options = CompilerOptions {
+            input_file: input_path.clone(),
+            absorb: None, // Will auto-detect as Rust from .rs extension
+            no_compile: true,
+            ..default_options()
+        };
+
+        let result = run_compiler(&options);
+
+        // Clean up
+        let _ = fs::remove_file(&input_path);
+
+        // Should fail because Rust parsing is not yet implemented
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_auto_mode_with_crst_file() {
+        use std::fs;
+
+        let test_source = r#"
+int square(int x) {
+    return x * x;
+}
+"#;
+        let input_path = PathBuf::from("test_auto_12345.crst");
+        fs::write(&input_path, test_source).unwrap();
+
+        let options = CompilerOptions {
+            input_file: input_path.clone(),
+            output_file: Some(PathBuf::from("test_auto_12345.rs")),
+            emit: EmitMode::Auto, // Should auto-detect Rust from .rs output
+            absorb: None,         // Should auto-detect Crusty from .crst input
+            no_compile: true,
+            ..default_options()
         };
 
         let result = run_compiler(&options);
@@ -867,12 +4094,10 @@ int add(int a, int b) {
 
         let options = CompilerOptions {
             input_file: input_path.clone(),
-            output_file: None,
             out_dir: Some(out_dir.clone()),
             emit: EmitMode::Rust,
-            absorb: None,
-            verbose: false,
             no_compile: true,
+            ..default_options()
         };
 
         let result = run_compiler(&options);
@@ -964,12 +4189,10 @@ int multiply(int a, int b) {
 
         let options = CompilerOptions {
             input_file: test_dir.clone(),
-            output_file: None,
             out_dir: Some(out_dir.clone()),
             emit: EmitMode::Rust,
-            absorb: None,
-            verbose: false,
             no_compile: true,
+            ..default_options()
         };
 
         let result = run_compiler(&options);
@@ -985,6 +4208,55 @@ int multiply(int a, int b) {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_batch_compilation_writes_compile_db() {
+        use std::fs;
+
+        let test_dir = PathBuf::from("test_batch_db_12345");
+        let out_dir = PathBuf::from("test_batch_db_output_12345");
+        fs::create_dir_all(test_dir.join("sub")).unwrap();
+        fs::write(test_dir.join("top.crst"), "int top() { return 0; }").unwrap();
+        fs::write(
+            test_dir.join("sub/nested.crst"),
+            "int nested() { return 1; }",
+        )
+        .unwrap();
+        fs::write(test_dir.join("broken.crst"), "int broken( {").unwrap();
+
+        let options = CompilerOptions {
+            input_file: test_dir.clone(),
+            out_dir: Some(out_dir.clone()),
+            emit: EmitMode::Rust,
+            no_compile: true,
+            compile_db: Some(out_dir.join("compile_commands.json")),
+            ..default_options()
+        };
+
+        let result = run_compiler(&options);
+        let db = CompilationDatabase::load(&out_dir.join("compile_commands.json"));
+
+        // Clean up
+        let _ = fs::remove_dir_all(&test_dir);
+        let _ = fs::remove_dir_all(&out_dir);
+
+        // The broken file fails the batch but the others are still described
+        assert!(result.is_err());
+        let db = db.unwrap();
+        assert_eq!(db.commands().len(), 2);
+        let nested = db.find(&test_dir.join("sub/nested.crst")).unwrap();
+        assert_eq!(
+            nested.output,
+            out_dir.join("sub/nested.rs").display().to_string()
+        );
+        assert_eq!(
+            nested.include_paths,
+            vec![
+                test_dir.display().to_string(),
+                test_dir.join("sub").display().to_string()
+            ]
+        );
+    }
+
     #[test]
     fn test_batch_compilation_requires_out_dir() {
         use std::fs;
@@ -995,12 +4267,10 @@ int multiply(int a, int b) {
 
         let options = CompilerOptions {
             input_file: test_dir.clone(),
-            output_file: None,
             out_dir: None, // Missing --out-dir
             emit: EmitMode::Rust,
-            absorb: None,
-            verbose: false,
             no_compile: true,
+            ..default_options()
         };
 
         let result = run_compiler(&options);
@@ -0,0 +1,233 @@
+// Copyright (c) 2026 Mark Ferrell
+// Licensed under the MIT License. See LICENSE.txt in the project root.
+
+//! Mapping clippy findings on generated Rust back to Crusty source.
+
+use crate::codegen::GeneratedItem;
+use crate::error::Position;
+use crate::utils::locate_item;
+use std::fmt;
+
+/// Clippy lints that only describe the shape of the translation (Crusty
+/// requires explicit `return`, codegen parenthesizes every binary expression)
+/// and so have nothing to say about the original source.
+const TRANSLATION_ARTIFACT_LINTS: &[&str] = &["clippy::needless_return", "clippy::double_parens"];
+
+/// A machine-applicable clippy suggestion on the generated Rust code
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClippySuggestion {
+    pub lint: String,
+    pub message: String,
+    pub line: usize,
+    pub column: usize,
+    pub replacement: String,
+}
+
+/// A clippy suggestion mapped back to the Crusty item it came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrustyWarning {
+    pub file: String,
+    pub lint: String,
+    pub message: String,
+    pub item: Option<String>,
+    pub location: Option<Position>,
+    pub replacement: String,
+}
+
+impl fmt::Display for CrustyWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "warning[{}]: {}", self.lint, self.message)?;
+        match (&self.location, &self.item) {
+            (Some(location), Some(item)) => {
+                writeln!(f, "  --> {}:{} (in `{}`)", self.file, location, item)?
+            }
+            (None, Some(item)) => writeln!(f, "  --> {} (in `{}`)", self.file, item)?,
+            _ => writeln!(f, "  --> {}", self.file)?,
+        }
+        write!(
+            f,
+            "  = help: clippy suggests (as Rust): `{}`",
+            self.replacement
+        )
+    }
+}
+
+/// Extract machine-applicable clippy suggestions from `--error-format=json` output
+pub fn parse_suggestions(json_output: &str) -> Vec<ClippySuggestion> {
+    let mut suggestions = Vec::new();
+
+    for line in json_output.lines() {
+        let Ok(diagnostic) = serde_json::from_str::<serde_json::Value>(line) else {
+            continue;
+        };
+        let Some(lint) = diagnostic["code"]["code"].as_str() else {
+            continue;
+        };
+        if !lint.starts_with("clippy::") || TRANSLATION_ARTIFACT_LINTS.contains(&lint) {
+            continue;
+        }
+
+        let children = diagnostic["children"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+        let applicable = children
+            .iter()
+            .flat_map(|child| child["spans"].as_array().cloned().unwrap_or_default())
+            .find(|span| span["suggestion_applicability"] == "MachineApplicable");
+
+        if let Some(span) = applicable {
+            suggestions.push(ClippySuggestion {
+                lint: lint.to_string(),
+                message: diagnostic["message"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+                line: span["line_start"].as_u64().unwrap_or(0) as usize,
+                column: span["column_start"].as_u64().unwrap_or(0) as usize,
+                replacement: span["suggested_replacement"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+            });
+        }
+    }
+
+    suggestions
+}
+
+/// Map suggestions on generated code back to the Crusty items that produced them
+pub fn map_to_crusty(
+    suggestions: &[ClippySuggestion],
+    items: &[GeneratedItem],
+    file: &str,
+    source: &str,
+) -> Vec<CrustyWarning> {
+    suggestions
+        .iter()
+        .map(|suggestion| {
+            // The enclosing item is the last one starting at or before the line
+            let item = items
+                .iter()
+                .rev()
+                .find(|item| item.line <= suggestion.line)
+                .map(|item| item.name.clone());
+            let location = item
+                .as_deref()
+                .and_then(|name| locate_item(source, name))
+                .map(|span| span.start);
+
+            CrustyWarning {
+                file: file.to_string(),
+                lint: suggestion.lint.clone(),
+                message: unescape_identifiers(&suggestion.message),
+                item,
+                location,
+                replacement: unescape_identifiers(&suggestion.replacement),
+            }
+        })
+        .collect()
+}
+
+/// `text` with the raw identifiers codegen writes for Crusty names that are
+/// Rust keywords (`r#type`) turned back into the names (`type`)
+fn unescape_identifiers(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(index) = rest.find("r#") {
+        let (before, after) = rest.split_at(index);
+        let starts_identifier = !before.ends_with(|ch: char| ch.is_alphanumeric() || ch == '_')
+            && after[2..].starts_with(|ch: char| ch.is_alphabetic() || ch == '_');
+        result.push_str(before);
+        if !starts_identifier {
+            result.push_str("r#");
+        }
+        rest = &after[2..];
+    }
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLIPPY_OUTPUT: &str = concat!(
+        r#"{"$message_type":"diagnostic","message":"manual `RangeInclusive::contains` implementation","code":{"code":"clippy::manual_range_contains","explanation":null},"level":"warning","spans":[{"line_start":4,"column_start":8,"is_primary":true}],"children":[{"message":"use","code":null,"level":"help","spans":[{"line_start":4,"column_start":8,"suggested_replacement":"(1..=9).contains(&x)","suggestion_applicability":"MachineApplicable"}],"children":[]}]}"#,
+        "\n",
+        r#"{"$message_type":"diagnostic","message":"unneeded `return` statement","code":{"code":"clippy::needless_return","explanation":null},"level":"warning","spans":[],"children":[{"message":"remove","code":null,"level":"help","spans":[{"line_start":5,"column_start":5,"suggested_replacement":"x","suggestion_applicability":"MachineApplicable"}],"children":[]}]}"#,
+        "\n",
+        r#"{"$message_type":"diagnostic","message":"unnecessary parentheses","code":{"code":"unused_parens","explanation":null},"level":"warning","spans":[],"children":[]}"#,
+        "\n",
+        r#"{"$message_type":"diagnostic","message":"this could be simplified","code":{"code":"clippy::some_lint","explanation":null},"level":"warning","spans":[],"children":[{"message":"try","code":null,"level":"help","spans":[{"line_start":2,"column_start":1,"suggested_replacement":"y","suggestion_applicability":"MaybeIncorrect"}],"children":[]}]}"#,
+        "\n",
+        "not json\n",
+    );
+
+    #[test]
+    fn test_parse_suggestions_keeps_machine_applicable_clippy_lints() {
+        let suggestions = parse_suggestions(CLIPPY_OUTPUT);
+        assert_eq!(
+            suggestions,
+            vec![ClippySuggestion {
+                lint: "clippy::manual_range_contains".to_string(),
+                message: "manual `RangeInclusive::contains` implementation".to_string(),
+                line: 4,
+                column: 8,
+                replacement: "(1..=9).contains(&x)".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_map_to_crusty_finds_enclosing_item() {
+        let source = "struct Point {\n    int x;\n}\n\nbool in_range(int x) {\n    return x >= 1 && x <= 9;\n}\n";
+        let items = vec![
+            GeneratedItem {
+                name: "Point".to_string(),
+                line: 1,
+                end_line: 2,
+            },
+            GeneratedItem {
+                name: "in_range".to_string(),
+                line: 3,
+                end_line: 5,
+            },
+        ];
+        let warnings = map_to_crusty(
+            &parse_suggestions(CLIPPY_OUTPUT),
+            &items,
+            "range.crst",
+            source,
+        );
+
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].item.as_deref(), Some("in_range"));
+        assert_eq!(warnings[0].location, Some(Position::new(5, 6)));
+
+        let rendered = warnings[0].to_string();
+        assert!(rendered.starts_with("warning[clippy::manual_range_contains]:"));
+        assert!(rendered.contains("--> range.crst:5:6 (in `in_range`)"));
+        assert!(rendered.contains("`(1..=9).contains(&x)`"));
+    }
+
+    #[test]
+    fn test_unescape_identifiers() {
+        assert_eq!(
+            unescape_identifiers("t.r#type == r#ref.len()"),
+            "t.type == ref.len()"
+        );
+        assert_eq!(
+            unescape_identifiers("r#\"raw\"# for r#"),
+            "r#\"raw\"# for r#"
+        );
+        assert_eq!(unescape_identifiers("bar#x"), "bar#x");
+    }
+
+    #[test]
+    fn test_map_to_crusty_without_items() {
+        let warnings = map_to_crusty(&parse_suggestions(CLIPPY_OUTPUT), &[], "a.crst", "");
+        assert_eq!(warnings[0].item, None);
+        assert_eq!(warnings[0].location, None);
+    }
+}
@@ -4,8 +4,16 @@
 //! Code generation module for emitting Rust or Crusty source code.
 
 use crate::ast::*;
-use crate::semantic::{Capture, CaptureKind};
-use std::collections::HashMap;
+use crate::consteval::{self, ConstValue};
+use crate::error::Position;
+use crate::hir::{LocalTypes, Site};
+use crate::platform::Platform;
+use crate::pretty::{self, Doc};
+use crate::semantic::{Capture, CaptureKind, EqualityImpl, GenericBounds};
+use crate::tailcall::{child_blocks, child_blocks_mut};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
 
 /// Target language for code generation
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -15,13 +23,238 @@ pub enum TargetLanguage {
     Crusty,
 }
 
+/// Lints silenced by default in generated Rust
+///
+/// Kept minimal: only lints triggered by the shape of the translation itself
+/// (expressions are fully parenthesized) rather than by the user's code.
+pub const DEFAULT_LINT_ALLOWS: &[&str] = &["unused_parens"];
+
+/// Order in which top-level items are emitted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ItemOrder {
+    /// Emit items in source order
+    #[default]
+    Source,
+    /// Group items into sections (imports, macros, types, constants, foreign
+    /// items, functions, modules), keeping source order within each section
+    Sectioned,
+}
+
+/// Rust representation of `L"..."` wide string literals
+///
+/// Both are NUL-terminated UTF-16, as Windows APIs taking a `wchar_t*` expect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum WideStringMapping {
+    /// A `Vec<u16>` built with `str::encode_utf16`, needing no dependencies
+    #[default]
+    Utf16,
+    /// A `&'static U16CStr` from the `widestring` crate's `u16cstr!` macro
+    #[value(name = "widestring")]
+    WideString,
+}
+
+/// What happens when a check in the generated code, such as an array
+/// bound, fails at run time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum PanicPolicy {
+    /// Panic, unwinding the stack
+    #[default]
+    Panic,
+    /// Panic, aborting the process without unwinding
+    Abort,
+    /// Return an error from functions returning a fallible type, and panic
+    /// elsewhere
+    Result,
+}
+
+/// Locals at least this many bytes large are allocated with `Box::new`
+pub const DEFAULT_HEAP_THRESHOLD: usize = 64 * 1024;
+
+/// Struct attributes choosing where locals of that struct live (`#[heap]`
+/// always boxes them, `#[stack]` never does); consumed by codegen and not
+/// emitted as Rust attributes
+const ALLOCATION_ATTRIBUTES: &[&str] = &["heap", "stack"];
+
+/// Function attribute marking a benchmark for `--bench`; not emitted as a
+/// Rust attribute, since Rust's `#[bench]` needs the unstable test crate
+pub const BENCH_ATTRIBUTE: &str = "bench";
+
+/// A batch of calls to a benchmark this long or longer is timed
+const BENCH_BATCH_MILLIS: u32 = 100;
+
+/// The static at the crate root holding when the program started, which
+/// `clock()` counts from
+const CLOCK_START: &str = "__CRUSTY_CLOCK_START";
+
+/// Why a local was moved to the heap
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeapReason {
+    /// The local's struct type is marked `#[heap]`
+    Attribute,
+    /// The local's estimated size in bytes reached the heap threshold
+    Size(usize),
+}
+
+/// A local that codegen allocated with `Box::new` instead of on the stack
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeapAllocation {
+    pub function: Option<String>,
+    pub name: String,
+    /// Where the local is declared, when it was parsed from source
+    pub position: Option<Position>,
+    pub ty: String,
+    pub reason: HeapReason,
+}
+
+impl fmt::Display for HeapAllocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}`", self.name)?;
+        if let Some(function) = &self.function {
+            write!(f, " in `{}`", function)?;
+        }
+        match self.reason {
+            HeapReason::Attribute => write!(
+                f,
+                " is allocated with Box::new because `{}` is marked #[heap]",
+                self.ty
+            ),
+            HeapReason::Size(size) => write!(
+                f,
+                " ({}, {} bytes) is allocated with Box::new to avoid overflowing the stack",
+                self.ty, size
+            ),
+        }
+    }
+}
+
+/// A top-level item in the generated output and the lines it spans
+///
+/// Used to map diagnostics reported against generated code back to the
+/// Crusty item they came from, and to measure how much code each item
+/// expanded into.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GeneratedItem {
+    pub name: String,
+    pub line: usize,
+    /// Last line of the item's output (inclusive)
+    pub end_line: usize,
+}
+
+/// Line of generated code on which a statement parsed from Crusty starts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GeneratedStatement {
+    pub line: usize,
+    /// Where the statement starts in the Crusty source
+    pub source: Position,
+}
+
+impl GeneratedItem {
+    /// Number of generated lines, including attributes and doc comments
+    pub fn line_count(&self) -> usize {
+        self.end_line + 1 - self.line
+    }
+}
+
 /// Code generator that produces source code from AST
+#[derive(Clone)]
 pub struct CodeGenerator {
     target: TargetLanguage,
     indent_level: usize,
     output: String,
     /// Capture information for nested functions: function_name -> captures
-    nested_function_captures: HashMap<String, Vec<Capture>>,
+    nested_function_captures: BTreeMap<String, Vec<Capture>>,
+    /// Maximum line width for wrapping long expressions (None = never wrap)
+    max_width: Option<usize>,
+    /// Ordering of top-level items
+    item_order: ItemOrder,
+    /// Comment lines emitted at the top of the generated file
+    header: Vec<String>,
+    /// Lints silenced with a crate-level `#![allow(...)]`
+    lint_allows: Vec<String>,
+    /// Start lines of the named items generated by the last `generate` call
+    generated_items: Vec<GeneratedItem>,
+    /// Start lines of the parsed statements generated by the last `generate` call
+    generated_statements: Vec<GeneratedStatement>,
+    /// Bytes of output whose newlines have been counted, and their count
+    counted_lines: (usize, usize),
+    /// Locals at least this many bytes large are boxed (None = never)
+    heap_threshold: Option<usize>,
+    /// Field types of the structs in the file being generated
+    struct_fields: HashMap<String, Vec<Type>>,
+    /// Explicit `#[heap]` (true) or `#[stack]` (false) placement per struct
+    struct_placement: HashMap<String, bool>,
+    /// Locals boxed by the last `generate` call
+    heap_allocations: Vec<HeapAllocation>,
+    /// Name of the function currently being generated
+    current_function: Option<String>,
+    /// The type whose methods are being generated
+    method_owner: Option<Type>,
+    /// How HIR sites name the function currently being generated
+    site_function: Option<String>,
+    /// Where the statement being generated starts
+    statement_position: Position,
+    /// Option-typed parameters and locals in scope in the current function,
+    /// and whether each is declared with `var`
+    option_locals: HashMap<String, bool>,
+    /// Pointer-to-volatile parameters and locals in scope in the current function
+    volatile_locals: HashSet<String>,
+    /// Array and slice parameters and locals in scope, with their types
+    array_locals: HashMap<String, Type>,
+    /// Locals in scope in the current function that are allocated with
+    /// `Box::new`, and used through a dereference
+    boxed_locals: HashSet<String>,
+    /// Values of the constants declared in the current function
+    const_values: HashMap<String, ConstValue>,
+    /// Variant names of the enums in the file being generated
+    enum_variants: HashMap<String, Vec<String>>,
+    /// Bare enum variants to qualify: variant -> enum
+    unqualified_variants: BTreeMap<String, String>,
+    /// Crusty source quoted in comments above each item it declares
+    embedded_source: Option<String>,
+    /// Rust representation of wide string literals
+    wide_strings: WideStringMapping,
+    /// Function started by the generated entry point, when it is not `main`
+    /// or the program is freestanding
+    entry: Option<String>,
+    /// Generate `#![no_std]`/`#![no_main]` code for targets without an OS
+    freestanding: bool,
+    /// Generate a `main` that times the `#[bench]` functions
+    bench: bool,
+    /// Start the clock `clock()` reads at the start of the program
+    clock_start: bool,
+    /// Mark `#[inline]` functions `#[track_caller]`
+    track_caller: bool,
+    /// What a failed run-time check does
+    panic_policy: PanicPolicy,
+    /// Whether the function currently being generated returns a fallible type
+    fallible_return: bool,
+    /// Functions declared in the file's extern blocks
+    foreign_functions: HashMap<String, ForeignFunction>,
+    /// Names of the free functions defined in the file
+    defined_functions: HashSet<String>,
+    /// Names of the traits declared in the file, whose pointers and
+    /// references are trait objects
+    traits: HashSet<String>,
+    /// Names of the modules compiled from other Crusty files
+    local_modules: HashSet<String>,
+    /// Those of the modules the file imports whole, `#import util`, whose
+    /// functions it calls as `util.twice(3)`
+    imported_modules: HashSet<String>,
+    /// Whether the file is one of those modules rather than the crate root,
+    /// where the modules are declared
+    in_module: bool,
+    /// Items of the file named like a Rust prelude item they shadow
+    shadowed_prelude: HashSet<String>,
+    /// Structs and enums compared with `==` and how they implement PartialEq
+    equality_impls: BTreeMap<String, EqualityImpl>,
+    /// Enclosing do-while loops whose bodies are wrapped in labeled blocks
+    do_while_depth: usize,
+    /// Traits the type parameters of each generic function need
+    generic_bounds: GenericBounds,
+    /// Types of the locals declared at each site, from the HIR
+    local_types: LocalTypes,
+    /// The platform the generated code is built for
+    platform: Platform,
 }
 
 impl CodeGenerator {
@@ -31,20 +264,228 @@ impl CodeGenerator {
             target,
             indent_level: 0,
             output: String::new(),
-            nested_function_captures: HashMap::new(),
+            nested_function_captures: BTreeMap::new(),
+            max_width: None,
+            item_order: ItemOrder::Source,
+            header: Vec::new(),
+            lint_allows: Vec::new(),
+            generated_items: Vec::new(),
+            generated_statements: Vec::new(),
+            counted_lines: (0, 0),
+            heap_threshold: None,
+            struct_fields: HashMap::new(),
+            struct_placement: HashMap::new(),
+            heap_allocations: Vec::new(),
+            current_function: None,
+            method_owner: None,
+            site_function: None,
+            statement_position: Position::new(0, 0),
+            option_locals: HashMap::new(),
+            volatile_locals: HashSet::new(),
+            array_locals: HashMap::new(),
+            boxed_locals: HashSet::new(),
+            const_values: HashMap::new(),
+            enum_variants: HashMap::new(),
+            unqualified_variants: BTreeMap::new(),
+            embedded_source: None,
+            wide_strings: WideStringMapping::Utf16,
+            entry: None,
+            freestanding: false,
+            bench: false,
+            clock_start: false,
+            track_caller: false,
+            panic_policy: PanicPolicy::Panic,
+            fallible_return: false,
+            foreign_functions: HashMap::new(),
+            defined_functions: HashSet::new(),
+            traits: HashSet::new(),
+            shadowed_prelude: HashSet::new(),
+            local_modules: HashSet::new(),
+            imported_modules: HashSet::new(),
+            in_module: false,
+            equality_impls: BTreeMap::new(),
+            local_types: BTreeMap::new(),
+            do_while_depth: 0,
+            generic_bounds: BTreeMap::new(),
+            platform: Platform::host(),
         }
     }
 
     /// Set capture information for nested functions from semantic analysis
     #[allow(dead_code)] // Used in tests
-    pub fn set_captures(&mut self, captures: HashMap<String, Vec<Capture>>) {
+    pub fn set_captures(&mut self, captures: BTreeMap<String, Vec<Capture>>) {
         self.nested_function_captures = captures;
     }
 
+    /// Set the names of the modules compiled from other Crusty files, whose
+    /// imports are anchored at the crate root
+    pub fn set_local_modules(&mut self, modules: HashSet<String>) {
+        self.local_modules = modules;
+    }
+
+    /// Generate the files given as modules of the crate rather than its
+    /// root, so the modules they import whole are brought into scope
+    pub fn set_in_module(&mut self, in_module: bool) {
+        self.in_module = in_module;
+    }
+
+    /// Set the structs and enums that need a `PartialEq` implementation
+    /// because they are compared with `==`, from semantic analysis
+    pub fn set_equality_impls(&mut self, impls: BTreeMap<String, EqualityImpl>) {
+        self.equality_impls = impls;
+    }
+
+    /// Set the traits generic functions' type parameters need, from semantic
+    /// analysis
+    pub fn set_generic_bounds(&mut self, bounds: GenericBounds) {
+        self.generic_bounds = bounds;
+    }
+
+    /// Set the maximum line width used to wrap long expressions
+    pub fn set_max_width(&mut self, width: Option<usize>) {
+        self.max_width = width;
+    }
+
+    /// Set the ordering of top-level items
+    pub fn set_item_order(&mut self, order: ItemOrder) {
+        self.item_order = order;
+    }
+
+    /// Set the comment lines emitted at the top of the generated file
+    pub fn set_header(&mut self, header: Vec<String>) {
+        self.header = header;
+    }
+
+    /// Set the lints silenced with `#![allow(...)]` in generated Rust
+    pub fn set_lint_allows(&mut self, lints: Vec<String>) {
+        self.lint_allows = lints;
+    }
+
+    /// Set the bare enum variants accepted by semantic analysis, which are
+    /// emitted qualified with their enum
+    pub fn set_unqualified_variants(&mut self, variants: BTreeMap<String, String>) {
+        self.unqualified_variants = variants;
+    }
+
+    /// Quote the Crusty source of each item in comments above its output, so
+    /// the translation can be reviewed side by side (None = no quoting)
+    pub fn set_embedded_source(&mut self, source: Option<String>) {
+        self.embedded_source = source;
+    }
+
+    /// Set the Rust representation of `L"..."` wide string literals
+    pub fn set_wide_strings(&mut self, mapping: WideStringMapping) {
+        self.wide_strings = mapping;
+    }
+
+    /// Start the program with `function` instead of `main`, through a
+    /// generated `main` that calls it (None = `main`)
+    pub fn set_entry(&mut self, function: Option<String>) {
+        self.entry = function;
+    }
+
+    /// Generate freestanding code: `#![no_std]` and `#![no_main]`, a panic
+    /// handler, and the entry function exported as the C symbol `main` for
+    /// the target's startup code to call
+    pub fn set_freestanding(&mut self, freestanding: bool) {
+        self.freestanding = freestanding;
+    }
+
+    /// Generate a `main` that calls each `#[bench]` function in batches of
+    /// doubling size until a batch takes long enough to time, and prints
+    /// the time per call
+    pub fn set_bench(&mut self, bench: bool) {
+        self.bench = bench;
+    }
+
+    /// Define the clock `clock()` reads, as a static at the crate root, and
+    /// start it first thing in the entry function; set for the root file of
+    /// a program calling `clock()` in any of its files
+    pub fn set_clock_start(&mut self, clock_start: bool) {
+        self.clock_start = clock_start;
+    }
+
+    /// Mark `#[inline]` functions `#[track_caller]`, so a panic in one, such
+    /// as a promoted macro, reports the line that called it
+    pub fn set_track_caller(&mut self, track_caller: bool) {
+        self.track_caller = track_caller;
+    }
+
+    /// Make failed run-time checks follow `policy`; only `Result` changes
+    /// the generated code, the others how rustc builds it
+    pub fn set_panic_policy(&mut self, policy: PanicPolicy) {
+        self.panic_policy = policy;
+    }
+
+    /// Generate for `platform` instead of the host, which decides the type
+    /// `long` becomes, type sizes and the numbers of the C signals
+    pub fn set_platform(&mut self, platform: Platform) {
+        self.platform = platform;
+    }
+
+    /// Box struct and array locals at least `threshold` bytes large (None = never)
+    pub fn set_heap_threshold(&mut self, threshold: Option<usize>) {
+        self.heap_threshold = threshold;
+    }
+
+    /// Locals allocated with `Box::new` by the last `generate` call
+    pub fn heap_allocations(&self) -> &[HeapAllocation] {
+        &self.heap_allocations
+    }
+
+    /// Named items produced by the last `generate` call, in output order
+    pub fn generated_items(&self) -> &[GeneratedItem] {
+        &self.generated_items
+    }
+
+    /// Parsed statements produced by the last `generate` call, in output order
+    pub fn generated_statements(&self) -> &[GeneratedStatement] {
+        &self.generated_statements
+    }
+
+    /// Generate source code from a program lowered to HIR, reading the types
+    /// of untyped locals from it
+    pub fn generate_hir(&mut self, hir: &crate::hir::Hir) -> String {
+        self.local_types = hir.locals.clone();
+        self.generate(&hir.file)
+    }
+
     /// Generate source code from a File AST
     pub fn generate(&mut self, file: &File) -> String {
         self.output.clear();
         self.indent_level = 0;
+        self.generated_items.clear();
+        self.generated_statements.clear();
+        self.counted_lines = (0, 0);
+        self.heap_allocations.clear();
+        self.struct_fields.clear();
+        self.struct_placement.clear();
+        self.enum_variants.clear();
+        self.foreign_functions.clear();
+        self.defined_functions.clear();
+        self.traits.clear();
+        self.imported_modules.clear();
+        self.shadowed_prelude.clear();
+        self.collect_struct_layouts(&file.items);
+
+        // Generate the provenance header and crate-level lint attributes
+        for line in &self.header.clone() {
+            self.write_line(&format!("// {}", line));
+        }
+        if self.target == TargetLanguage::Rust && !self.lint_allows.is_empty() {
+            self.write_line(&format!("#![allow({})]", self.lint_allows.join(", ")));
+        }
+        let freestanding = self.target == TargetLanguage::Rust && self.freestanding;
+        if freestanding {
+            self.write_line("#![no_std]");
+            self.write_line("#![no_main]");
+        }
+        if !self.header.is_empty()
+            || (self.target == TargetLanguage::Rust && !self.lint_allows.is_empty())
+            || freestanding
+        {
+            self.write_line("");
+        }
 
         // Generate doc comments for the file
         for comment in &file.doc_comments {
@@ -55,17 +496,430 @@ impl CodeGenerator {
             self.write_line("");
         }
 
-        // Generate all items
-        for (i, item) in file.items.iter().enumerate() {
-            if i > 0 {
+        match self.item_order {
+            ItemOrder::Source => {
+                let items: Vec<&Item> = file
+                    .items
+                    .iter()
+                    .filter(|item| !self.imports_declared_module(item))
+                    .collect();
+                for (i, item) in items.into_iter().enumerate() {
+                    if i > 0 {
+                        self.write_line("");
+                    }
+                    self.generate_item(item);
+                }
+            }
+            ItemOrder::Sectioned => self.generate_sectioned_items(&file.items),
+        }
+
+        if self.target == TargetLanguage::Rust {
+            self.generate_entry_point(&file.items);
+            if self.clock_start {
                 self.write_line("");
+                self.write_line(&format!(
+                    "static {}: std::sync::OnceLock<std::time::Instant> = std::sync::OnceLock::new();",
+                    CLOCK_START
+                ));
             }
-            self.generate_item(item);
         }
 
         self.output.clone()
     }
 
+    /// Generate the `main` starting an `--entry` function, or the exported
+    /// entry point and panic handler of a freestanding program
+    fn generate_entry_point(&mut self, items: &[Item]) {
+        if self.bench {
+            self.generate_bench_harness(items);
+            return;
+        }
+        let name = self.entry.clone().unwrap_or_else(|| "main".to_string());
+        let entry = items.iter().find_map(|item| match item {
+            Item::Function(func) if func.name.name == name => Some(func),
+            _ => None,
+        });
+        let returns_int = entry.is_some_and(|func| {
+            !matches!(
+                func.return_type,
+                None | Some(Type::Primitive(PrimitiveType::Void))
+            )
+        });
+        let call = format!("{}()", rust_identifier(&name));
+        let status = match entry.and_then(|func| func.return_type.as_ref()) {
+            Some(Type::Primitive(PrimitiveType::Int | PrimitiveType::I32)) => call.clone(),
+            _ => format!("{} as i32", call),
+        };
+
+        if self.freestanding {
+            if entry.is_some() {
+                self.write_line("");
+                self.write_line("#[export_name = \"main\"]");
+                self.write_line("pub extern \"C\" fn __crusty_entry() -> i32 {");
+                if returns_int {
+                    self.write_line(&format!("    {}", status));
+                } else {
+                    self.write_line(&format!("    {};", call));
+                    self.write_line("    0");
+                }
+                self.write_line("}");
+            }
+            self.write_line("");
+            self.write_line("#[panic_handler]");
+            self.write_line("fn __crusty_panic(_info: &core::panic::PanicInfo) -> ! {");
+            self.write_line("    loop {}");
+            self.write_line("}");
+        } else if entry.is_some() && name != "main" {
+            self.write_line("");
+            self.write_line("fn main() {");
+            if returns_int {
+                self.write_line(&format!("    std::process::exit({});", status));
+            } else {
+                self.write_line(&format!("    {};", call));
+            }
+            self.write_line("}");
+        }
+    }
+
+    /// Generate the `main` timing the `#[bench]` functions of `items`, in
+    /// source order
+    fn generate_bench_harness(&mut self, items: &[Item]) {
+        let benches: Vec<&Function> = items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Function(func)
+                    if func
+                        .attributes
+                        .iter()
+                        .any(|attr| attr.name.name == BENCH_ATTRIBUTE) =>
+                {
+                    Some(func)
+                }
+                _ => None,
+            })
+            .collect();
+        self.write_line("");
+        self.write_line("fn main() {");
+        for func in benches {
+            self.write_line(&format!(
+                "    __crusty_bench(\"{}\", {});",
+                func.name.name,
+                rust_identifier(&func.name.name)
+            ));
+        }
+        self.write_line("}");
+        self.write_line("");
+        self.write_line("fn __crusty_bench<T>(name: &str, mut f: impl FnMut() -> T) {");
+        self.write_line("    let mut iterations: u32 = 1;");
+        self.write_line("    loop {");
+        self.write_line("        let start = std::time::Instant::now();");
+        self.write_line("        for _ in 0..iterations {");
+        // The benchmark is called through an opaque reference, so a call
+        // with constant arguments cannot be hoisted out of the loop
+        self.write_line("            std::hint::black_box(std::hint::black_box(&mut f)());");
+        self.write_line("        }");
+        self.write_line("        let elapsed = start.elapsed();");
+        self.write_line(&format!(
+            "        if elapsed.as_millis() >= {} || iterations == 1 << 30 {{",
+            BENCH_BATCH_MILLIS
+        ));
+        self.write_line(
+            "            let per_call = elapsed.as_nanos() as f64 / f64::from(iterations);",
+        );
+        self.write_line(
+            "            println!(\"bench {:<24} {:>14.1} ns/iter ({} iterations)\", name, per_call, iterations);",
+        );
+        self.write_line("            return;");
+        self.write_line("        }");
+        self.write_line("        iterations *= 2;");
+        self.write_line("    }");
+        self.write_line("}");
+    }
+
+    /// Record field types and explicit placement of every struct in `items`,
+    /// along with enum variants, free function names and foreign function
+    /// signatures, and the Rust prelude names the file's items shadow
+    fn collect_struct_layouts(&mut self, items: &[Item]) {
+        for item in items {
+            if let Some(name) = item_name(item).filter(|name| rust_prelude_path(name).is_some()) {
+                self.shadowed_prelude.insert(name.to_string());
+            }
+            match item {
+                Item::Struct(struct_def) => {
+                    let name = struct_def.name.name.clone();
+                    let fields = struct_def.fields.iter().map(|f| f.ty.clone()).collect();
+                    self.struct_fields.insert(name.clone(), fields);
+                    for attr in &struct_def.attributes {
+                        match attr.name.name.as_str() {
+                            "heap" => self.struct_placement.insert(name.clone(), true),
+                            "stack" => self.struct_placement.insert(name.clone(), false),
+                            _ => None,
+                        };
+                    }
+                }
+                Item::Enum(enum_def) => {
+                    let variants = enum_def
+                        .variants
+                        .iter()
+                        .map(|v| v.name.name.clone())
+                        .collect();
+                    self.enum_variants
+                        .insert(enum_def.name.name.clone(), variants);
+                }
+                Item::Function(func) => {
+                    self.defined_functions.insert(func.name.name.clone());
+                }
+                Item::Trait(trait_def) => {
+                    self.traits.insert(trait_def.name.name.clone());
+                }
+                Item::Extern(extern_block) => {
+                    for func in &extern_block.functions {
+                        self.foreign_functions
+                            .insert(func.name.name.clone(), func.clone());
+                    }
+                }
+                Item::Import(import) => {
+                    if let [module] = import.path.as_slice() {
+                        if self.local_modules.contains(&module.name) {
+                            self.imported_modules.insert(module.name.clone());
+                        }
+                    }
+                }
+                Item::Namespace(namespace) => self.collect_struct_layouts(&namespace.items),
+                _ => {}
+            }
+        }
+    }
+
+    /// Whether `ty` names a trait declared in the file, in Rust
+    fn is_trait(&self, ty: &Type) -> bool {
+        self.target == TargetLanguage::Rust
+            && matches!(ty, Type::Ident(name) if self.traits.contains(&name.name))
+    }
+
+    /// Whether a failed run-time check, such as an array bound, returns an
+    /// error rather than panicking: under the `result` panic policy, in a
+    /// function returning a fallible type
+    fn returns_check_errors(&self) -> bool {
+        self.target == TargetLanguage::Rust
+            && self.panic_policy == PanicPolicy::Result
+            && self.fallible_return
+    }
+
+    /// Whether `ty` is a reference to a struct defined in the file
+    fn is_struct_reference(&self, ty: &Type) -> bool {
+        matches!(ty, Type::Reference { ty, .. }
+            if matches!(ty.as_ref(), Type::Ident(name) if self.struct_fields.contains_key(&name.name)))
+    }
+
+    /// Qualified name of the enum variant `expr` refers to, if any
+    ///
+    /// Covers `Color.Red`, `Color::Red`, `@Color.Red` and, for variants
+    /// accepted unqualified, bare `Red`.
+    fn enum_variant_path(&self, expr: &Expression) -> Option<String> {
+        let (enum_name, variant) = match expr {
+            Expression::FieldAccess { expr, field } => match expr.as_ref() {
+                Expression::Ident(ty) => (ty.name.as_str(), field.name.as_str()),
+                _ => return None,
+            },
+            Expression::TypeScopedCall {
+                ty: Type::Ident(ty),
+                method,
+                args,
+            } if args.is_empty() => (ty.name.as_str(), method.name.as_str()),
+            Expression::Ident(ident) => (
+                self.unqualified_variants.get(&ident.name)?.as_str(),
+                ident.name.as_str(),
+            ),
+            _ => return None,
+        };
+        if !self
+            .enum_variants
+            .get(enum_name)?
+            .iter()
+            .any(|v| v == variant)
+        {
+            return None;
+        }
+        let separator = match self.target {
+            TargetLanguage::Rust => "::",
+            TargetLanguage::Crusty => ".",
+        };
+        Some(format!(
+            "{}{}{}",
+            self.identifier(enum_name),
+            separator,
+            self.identifier(variant)
+        ))
+    }
+
+    /// Estimated size of a value of type `ty` in bytes, ignoring padding
+    fn type_size(&self, ty: &Type, depth: usize) -> Option<usize> {
+        if depth > 32 {
+            return None;
+        }
+        match ty {
+            Type::Primitive(primitive) => Some(self.platform.size_of(primitive)),
+            Type::Ident(ident) if self.platform.c_type(&ident.name).is_some() => {
+                Some(self.platform.long_width)
+            }
+            Type::Pointer { .. } | Type::Reference { .. } => Some(self.platform.pointer_width),
+            Type::Array { ty, size } => (*size)?.checked_mul(self.type_size(ty, depth + 1)?),
+            Type::Tuple { types } => types.iter().map(|ty| self.type_size(ty, depth + 1)).sum(),
+            Type::Ident(ident) => self
+                .struct_fields
+                .get(&ident.name)?
+                .iter()
+                .map(|ty| self.type_size(ty, depth + 1))
+                .sum(),
+            _ => None,
+        }
+    }
+
+    /// Decide whether a local of type `ty` should be allocated on the heap
+    fn heap_reason(&self, ty: &Type) -> Option<HeapReason> {
+        if let Type::Ident(ident) = ty {
+            match self.struct_placement.get(&ident.name) {
+                Some(true) => return Some(HeapReason::Attribute),
+                Some(false) => return None,
+                None => {}
+            }
+        }
+        let threshold = self.heap_threshold?;
+        let size = self.type_size(ty, 0)?;
+        (size >= threshold).then_some(HeapReason::Size(size))
+    }
+
+    /// Write `name[: type] = init` for a Rust local, boxing the value when it
+    /// is too large for the stack
+    ///
+    /// A boxed local is used through `(*name)`, so it stays a value of its
+    /// declared type, and assigning it as a whole boxes the new value.
+    fn write_rust_local(&mut self, name: &Ident, ty: Option<&Type>, init: Option<&Expression>) {
+        // The statement being written, when it was parsed from source
+        let line = self.current_line();
+        let position = self
+            .generated_statements
+            .last()
+            .filter(|statement| statement.line == line)
+            .map(|statement| statement.source);
+        self.write(&rust_identifier(&name.name));
+
+        let local_ty = ty.or(match init {
+            Some(Expression::Cast { ty, .. } | Expression::CheckedCast { ty, .. }) => Some(ty),
+            _ => None,
+        });
+        let boxed = local_ty.and_then(|local_ty| {
+            self.heap_reason(local_ty)
+                .map(|reason| (self.generate_type_string(local_ty), reason))
+        });
+
+        if let Some((ty, reason)) = boxed {
+            let boxed = self.prelude_name("Box");
+            self.write(&format!(": {}<{}>", boxed, ty));
+            if let Some(init) = init {
+                self.write(&format!(" = {}::new(", boxed));
+                self.write_expression(init);
+                self.write(")");
+            }
+            self.heap_allocations.push(HeapAllocation {
+                function: self.current_function.clone(),
+                name: name.name.clone(),
+                position,
+                ty,
+                reason,
+            });
+            // Added after the initializer, which may use a local it shadows
+            self.boxed_locals.insert(name.name.clone());
+            return;
+        }
+
+        if let Some(ty) = ty {
+            self.write(": ");
+            self.write(&self.generate_type_string(ty));
+        }
+        if let Some(init) = init {
+            self.write(" = ");
+            match ty.and_then(|ty| string_array_init(ty, init)) {
+                Some((elem, size, lit)) => self.write(&string_array_initializer(elem, size, lit)),
+                None => self.write_expression(init),
+            }
+        }
+        self.boxed_locals.remove(&name.name);
+    }
+
+    /// Write a string-initialized character array as the C declarator it came
+    /// from (`let char buf[8] = "abc";`)
+    fn write_crusty_string_array(
+        &mut self,
+        keyword: &str,
+        name: &Ident,
+        ty: &Option<Type>,
+        init: &Option<Expression>,
+    ) {
+        let (Some(ty), Some(init)) = (ty, init) else {
+            return;
+        };
+        let Some((elem, size, lit)) = string_array_init(ty, init) else {
+            return;
+        };
+        let declaration = format!(
+            "{} {} {}[{}] = {};\n",
+            keyword,
+            self.generate_type_string(elem),
+            name.name,
+            size,
+            self.generate_literal_string(lit)
+        );
+        self.write(&declaration);
+    }
+
+    /// Write `name` declared as `ty` in C style, with array dimensions after
+    /// the name: `int grid[2][3]`
+    fn write_crusty_declarator(&mut self, name: &Ident, ty: &Type) {
+        let mut elem = ty;
+        let mut dimensions = String::new();
+        while let Type::Array {
+            ty,
+            size: Some(size),
+        } = elem
+        {
+            dimensions.push_str(&format!("[{}]", size));
+            elem = ty;
+        }
+        let declarator = format!(
+            "{} {}{}",
+            self.generate_type_string(elem),
+            name.name,
+            dimensions
+        );
+        self.write(&declarator);
+    }
+
+    /// Generate items grouped by section, each introduced by a header comment
+    fn generate_sectioned_items(&mut self, items: &[Item]) {
+        let mut sorted: Vec<&Item> = items
+            .iter()
+            .filter(|item| !self.imports_declared_module(item))
+            .collect();
+        // sort_by_key is stable, so source order is kept within a section
+        sorted.sort_by_key(|item| item_section(item));
+
+        let mut current_section = None;
+        for (i, item) in sorted.into_iter().enumerate() {
+            if i > 0 {
+                self.write_line("");
+            }
+            let section = item_section(item);
+            if current_section != Some(section) {
+                self.write_line(&format!("// {}", section.title()));
+                self.write_line("");
+                current_section = Some(section);
+            }
+            self.generate_item(item);
+        }
+    }
+
     /// Write a line with current indentation
     fn write_line(&mut self, text: &str) {
         if !text.is_empty() {
@@ -75,11 +929,43 @@ impl CodeGenerator {
         self.output.push('\n');
     }
 
+    /// Line of the output the next write starts on
+    fn current_line(&mut self) -> usize {
+        let (counted, lines) = self.counted_lines;
+        let lines = match self.output.get(counted..) {
+            Some(rest) => lines + rest.matches('\n').count(),
+            None => self.output.matches('\n').count(),
+        };
+        self.counted_lines = (self.output.len(), lines);
+        lines + 1
+    }
+
     /// Write text without newline
     fn write(&mut self, text: &str) {
         self.output.push_str(text);
     }
 
+    /// Write an expression at the current position, wrapping it when it would
+    /// overflow the maximum width (leaving room for a trailing `;`)
+    fn write_expression(&mut self, expr: &Expression) {
+        let flat = self.generate_expression_string(expr);
+        let column = self.current_column();
+        match self.max_width {
+            Some(width) if column + flat.chars().count() + 1 > width => {
+                let doc = Doc::concat(vec![self.generate_expression_doc(expr), Doc::text(";")]);
+                let rendered = pretty::render(&doc, width, self.indent_level * 4, column);
+                self.write(rendered.trim_end_matches(';'));
+            }
+            _ => self.write(&flat),
+        }
+    }
+
+    /// Column of the next character written to the output
+    fn current_column(&self) -> usize {
+        let line_start = self.output.rfind('\n').map_or(0, |i| i + 1);
+        self.output[line_start..].chars().count()
+    }
+
     /// Write current indentation
     fn write_indent(&mut self) {
         for _ in 0..self.indent_level {
@@ -101,10 +987,25 @@ impl CodeGenerator {
 
     /// Generate code for a top-level item
     fn generate_item(&mut self, item: &Item) {
+        if let Some(name) = item_name(item) {
+            self.write_embedded_source(name);
+        }
+        let generated = item_name(item).map(|name| {
+            let line = self.output.matches('\n').count() + 1;
+            self.generated_items.push(GeneratedItem {
+                name: name.to_string(),
+                line,
+                end_line: line,
+            });
+            self.generated_items.len() - 1
+        });
+
         match item {
             Item::Function(func) => self.generate_function(func),
             Item::Struct(struct_def) => self.generate_struct(struct_def),
             Item::Enum(enum_def) => self.generate_enum(enum_def),
+            Item::Trait(trait_def) => self.generate_trait(trait_def),
+            Item::Impl(impl_block) => self.generate_impl(impl_block),
             Item::Typedef(typedef) => self.generate_typedef(typedef),
             Item::Namespace(namespace) => self.generate_namespace(namespace),
             Item::Import(import_item) => self.generate_import(import_item),
@@ -113,42 +1014,123 @@ impl CodeGenerator {
             Item::Const(const_item) => self.generate_const(const_item),
             Item::Static(static_item) => self.generate_static(static_item),
             Item::MacroDefinition(macro_def) => self.generate_macro_definition(macro_def),
+            Item::StaticAssert(assertion) => self.generate_static_assert(assertion),
+        }
+
+        if let Some(index) = generated {
+            let end_line = self.output.matches('\n').count();
+            let item = &mut self.generated_items[index];
+            item.end_line = end_line.max(item.line);
         }
     }
 
-    fn generate_function(&mut self, func: &Function) {
-        // Generate attributes
-        for attr in &func.attributes {
+    /// Quote the source lines declaring `name` as `// line | text` comments
+    ///
+    /// Items the source cannot be searched for, such as macros, are skipped.
+    fn write_embedded_source(&mut self, name: &str) {
+        let Some(source) = &self.embedded_source else {
+            return;
+        };
+        let Some(span) = crate::utils::locate_item_extent(source, name) else {
+            return;
+        };
+        let quoted: Vec<String> = source
+            .lines()
+            .enumerate()
+            .take(span.end.line)
+            .skip(span.start.line - 1)
+            .map(|(i, text)| format!("// {:>4} | {}", i + 1, text).trim_end().to_string())
+            .collect();
+        for line in quoted {
+            self.write_line(&line);
+        }
+    }
+
+    /// Generate `#[name(args)]` attribute lines (same syntax in both targets)
+    fn generate_attributes(&mut self, attributes: &[Attribute]) {
+        for attr in attributes {
+            if self.target == TargetLanguage::Rust
+                && (ALLOCATION_ATTRIBUTES.contains(&attr.name.name.as_str())
+                    || attr.name.name == BENCH_ATTRIBUTE)
+            {
+                continue;
+            }
+            // rustc warns about lint names it does not know, so the lints
+            // only crustyc reports are left out of its lint attributes
+            let mut attr = attr.clone();
+            if self.target == TargetLanguage::Rust
+                && crate::lints::Level::from_attribute(&attr.name.name).is_some()
+            {
+                attr.args.retain(|arg| {
+                    !matches!(arg, AttributeArg::Ident(name) if crate::lints::is_crusty_only(&name.name))
+                });
+                if attr.args.is_empty() {
+                    continue;
+                }
+            }
             self.write_indent();
             self.write("#[");
             self.write(&attr.name.name);
 
             // Generate attribute arguments if present
             if !attr.args.is_empty() {
-                self.write("(");
-                for (i, arg) in attr.args.iter().enumerate() {
-                    if i > 0 {
-                        self.write(", ");
-                    }
-                    match arg {
-                        AttributeArg::Ident(ident) => {
-                            self.write(&ident.name);
-                        }
-                        AttributeArg::Literal(lit) => {
-                            self.write(&self.generate_literal_string(lit));
-                        }
-                        AttributeArg::NameValue { name, value } => {
-                            self.write(&name.name);
-                            self.write(" = ");
-                            self.write(&self.generate_literal_string(value));
-                        }
-                    }
-                }
-                self.write(")");
+                self.write(&format!("({})", self.attribute_args_string(&attr.args)));
             }
 
             self.write("]\n");
         }
+    }
+
+    /// Comma-separated attribute arguments, without the parentheses
+    fn attribute_args_string(&self, args: &[AttributeArg]) -> String {
+        args.iter()
+            .map(|arg| match arg {
+                AttributeArg::Ident(ident) => ident.name.clone(),
+                AttributeArg::Literal(lit) => self.generate_literal_string(lit),
+                AttributeArg::NameValue { name, value } => {
+                    format!("{} = {}", name.name, self.generate_literal_string(value))
+                }
+                AttributeArg::List { name, args } => {
+                    format!("{}({})", name.name, self.attribute_args_string(args))
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn generate_function(&mut self, func: &Function) {
+        self.generate_function_with_visibility(func, Some(&func.visibility));
+    }
+
+    /// Generate `method` of the type `owner`, as
+    /// `generate_function_with_visibility`
+    fn generate_method(
+        &mut self,
+        owner: &Type,
+        method: &Function,
+        visibility: Option<&Visibility>,
+    ) {
+        let enclosing = self.method_owner.replace(owner.clone());
+        self.generate_function_with_visibility(method, visibility);
+        self.method_owner = enclosing;
+    }
+
+    /// Generate `func`, with no visibility keyword when `visibility` is None,
+    /// as for the methods of a trait impl
+    fn generate_function_with_visibility(
+        &mut self,
+        func: &Function,
+        visibility: Option<&Visibility>,
+    ) {
+        self.generate_attributes(&func.attributes);
+        let has_attribute = |name: &str| func.attributes.iter().any(|a| a.name.name == name);
+        if self.target == TargetLanguage::Rust
+            && self.track_caller
+            && has_attribute("inline")
+            && !has_attribute("track_caller")
+        {
+            self.write_line("#[track_caller]");
+        }
 
         // Generate doc comments
         for comment in &func.doc_comments {
@@ -158,20 +1140,104 @@ impl CodeGenerator {
         // Generate function signature
         self.write_indent();
 
+        self.generate_signature(
+            visibility,
+            &func.name,
+            &func.generics,
+            &func.params,
+            func.return_type.as_ref(),
+        );
+        self.write(" ");
+        let enclosing = self.current_function.replace(func.name.name.clone());
+        let site_function = crate::hir::site_function(self.method_owner.as_ref(), &func.name.name);
+        let enclosing_site = self.site_function.replace(site_function);
+        let option_params = func
+            .params
+            .iter()
+            .filter(|param| is_option_type(&param.ty))
+            .map(|param| (param.name.name.clone(), false))
+            .collect();
+        let volatile_params = func
+            .params
+            .iter()
+            .filter(|param| is_volatile_pointer(&param.ty))
+            .map(|param| param.name.name.clone())
+            .collect();
+        let array_params = func
+            .params
+            .iter()
+            .filter(|param| array_type(&param.ty).is_some())
+            .map(|param| (param.name.name.clone(), param.ty.clone()))
+            .collect();
+        let enclosing_options = std::mem::replace(&mut self.option_locals, option_params);
+        let enclosing_volatiles = std::mem::replace(&mut self.volatile_locals, volatile_params);
+        let enclosing_arrays = std::mem::replace(&mut self.array_locals, array_params);
+        let enclosing_boxes = std::mem::take(&mut self.boxed_locals);
+        let enclosing_consts = std::mem::take(&mut self.const_values);
+        let enclosing_fallible = std::mem::replace(
+            &mut self.fallible_return,
+            matches!(func.return_type, Some(Type::Fallible { .. })),
+        );
+        if self.starts_clock(func) {
+            self.write("{\n");
+            self.indent();
+            self.write_line(&format!(
+                "crate::{}.get_or_init(std::time::Instant::now);",
+                CLOCK_START
+            ));
+            self.generate_statements(&func.body.statements, &func.body.positions, true);
+            self.dedent();
+            self.write_indent();
+            self.write("}");
+        } else {
+            self.generate_block(&func.body);
+        }
+        self.option_locals = enclosing_options;
+        self.volatile_locals = enclosing_volatiles;
+        self.array_locals = enclosing_arrays;
+        self.boxed_locals = enclosing_boxes;
+        self.const_values = enclosing_consts;
+        self.fallible_return = enclosing_fallible;
+        self.current_function = enclosing;
+        self.site_function = enclosing_site;
+        self.write("\n");
+    }
+
+    /// Whether `func`, being generated, is the entry function of a program
+    /// calling `clock()`, which starts the clock before anything else
+    fn starts_clock(&self, func: &Function) -> bool {
+        self.clock_start
+            && self.target == TargetLanguage::Rust
+            && !self.bench
+            && self.method_owner.is_none()
+            && self.indent_level == 0
+            && func.name.name == self.entry.as_deref().unwrap_or("main")
+    }
+
+    /// Generate a function signature up to its parameter list and return
+    /// type; `visibility` as for `generate_function_with_visibility`
+    fn generate_signature(
+        &mut self,
+        visibility: Option<&Visibility>,
+        name: &Ident,
+        generics: &[Ident],
+        params: &[Param],
+        return_type: Option<&Type>,
+    ) {
         match self.target {
             TargetLanguage::Rust => {
                 // Rust syntax: pub fn name(params) -> return_type { }
-                match func.visibility {
-                    Visibility::Public => self.write("pub "),
-                    Visibility::Private => {} // No keyword for private
+                if visibility == Some(&Visibility::Public) {
+                    self.write("pub ");
                 }
 
                 self.write("fn ");
-                self.write(&func.name.name);
+                self.write(&rust_identifier(&name.name));
+                self.write(&self.generic_params_string(&name.name, generics));
                 self.write("(");
 
                 // Parameters
-                for (i, param) in func.params.iter().enumerate() {
+                for (i, param) in params.iter().enumerate() {
                     if i > 0 {
                         self.write(", ");
                     }
@@ -193,13 +1259,13 @@ impl CodeGenerator {
                             }
                             _ => {
                                 // Fallback to regular parameter syntax
-                                self.write(&param.name.name);
+                                self.write(&rust_identifier(&param.name.name));
                                 self.write(": ");
                                 self.write(&self.generate_type_string(&param.ty));
                             }
                         }
                     } else {
-                        self.write(&param.name.name);
+                        self.write(&rust_identifier(&param.name.name));
                         self.write(": ");
                         self.write(&self.generate_type_string(&param.ty));
                     }
@@ -208,7 +1274,7 @@ impl CodeGenerator {
                 self.write(")");
 
                 // Return type (void becomes no annotation)
-                if let Some(ref return_type) = func.return_type {
+                if let Some(return_type) = return_type {
                     if !matches!(return_type, Type::Primitive(PrimitiveType::Void)) {
                         self.write(" -> ");
                         self.write(&self.generate_type_string(return_type));
@@ -217,26 +1283,52 @@ impl CodeGenerator {
             }
             TargetLanguage::Crusty => {
                 // Crusty syntax: static? return_type name(params) { }
-                if matches!(func.visibility, Visibility::Private) {
+                if visibility == Some(&Visibility::Private) {
                     self.write("static ");
                 }
 
                 // Return type comes first in Crusty (C-style)
-                if let Some(ref return_type) = func.return_type {
+                if let Some(return_type) = return_type {
                     self.write(&self.generate_type_string(return_type));
                 } else {
                     self.write("void");
                 }
                 self.write(" ");
-                self.write(&func.name.name);
+                self.write(&name.name);
+                if !generics.is_empty() {
+                    let generics: Vec<&str> =
+                        generics.iter().map(|param| param.name.as_str()).collect();
+                    self.write(&format!("({})", generics.join(", ")));
+                }
                 self.write("(");
 
                 // Parameters
-                for (i, param) in func.params.iter().enumerate() {
+                for (i, param) in params.iter().enumerate() {
                     if i > 0 {
                         self.write(", ");
                     }
-                    self.write(&self.generate_type_string(&param.ty));
+
+                    // Self parameters are written without a type in Crusty
+                    if param.name.name == "self" {
+                        match &param.ty {
+                            Type::Reference { mutable: true, .. } => self.write("&mut self"),
+                            Type::Reference { mutable: false, .. } => self.write("&self"),
+                            _ => self.write("self"),
+                        }
+                        continue;
+                    }
+
+                    match &param.ty {
+                        Type::Reference { ty, .. } if param.variadic => {
+                            let element = match ty.as_ref() {
+                                Type::Slice { ty } => ty.as_ref(),
+                                ty => ty,
+                            };
+                            self.write(&self.generate_type_string(element));
+                            self.write("...");
+                        }
+                        ty => self.write(&self.generate_type_string(ty)),
+                    }
                     self.write(" ");
                     self.write(&param.name.name);
                 }
@@ -244,58 +1336,96 @@ impl CodeGenerator {
                 self.write(")");
             }
         }
+    }
 
-        self.write(" ");
-        self.generate_block(&func.body);
-        self.write("\n");
+    /// The type parameters `generics` of the generic function, struct or
+    /// impl `owner` as a Rust parameter list, each with the bounds semantic
+    /// analysis inferred for it, as in `<T: PartialOrd>`; empty when there
+    /// are none
+    fn generic_params_string(&self, owner: &str, generics: &[Ident]) -> String {
+        if generics.is_empty() {
+            return String::new();
+        }
+        let bounds = self.generic_bounds.get(owner);
+        let generics: Vec<String> = generics
+            .iter()
+            .map(|param| {
+                let traits: Vec<String> = bounds
+                    .and_then(|bounds| bounds.get(&param.name))
+                    .into_iter()
+                    .flatten()
+                    .map(|trait_name| {
+                        let bound = bound_string(trait_name, &param.name);
+                        self.prelude_name(&bound).into_owned()
+                    })
+                    .collect();
+                let param_name = rust_identifier(&param.name);
+                if traits.is_empty() {
+                    param_name.into_owned()
+                } else {
+                    format!("{}: {}", param_name, traits.join(" + "))
+                }
+            })
+            .collect();
+        format!("<{}>", generics.join(", "))
     }
 
-    fn generate_struct(&mut self, struct_def: &Struct) {
-        // Generate attributes
-        for attr in &struct_def.attributes {
-            self.write_indent();
-            self.write("#[");
-            self.write(&attr.name.name);
-
-            if !attr.args.is_empty() {
-                self.write("(");
-                for (i, arg) in attr.args.iter().enumerate() {
-                    if i > 0 {
-                        self.write(", ");
-                    }
-                    match arg {
-                        AttributeArg::Ident(ident) => {
-                            self.write(&ident.name);
-                        }
-                        AttributeArg::Literal(lit) => {
-                            self.write(&self.generate_literal_string(lit));
-                        }
-                        AttributeArg::NameValue { name, value } => {
-                            self.write(&name.name);
-                            self.write(" = ");
-                            self.write(&self.generate_literal_string(value));
-                        }
-                    }
-                }
-                self.write(")");
+    /// The type of a struct literal as a Rust path; type arguments take the
+    /// turbofish in expression position, as in `Pair::<i32> { .. }`
+    fn struct_path_string(&self, ty: &Type) -> String {
+        match ty {
+            Type::Generic { base, args } => {
+                let args: Vec<String> = args
+                    .iter()
+                    .map(|arg| self.generate_type_string(arg))
+                    .collect();
+                format!("{}::<{}>", self.generate_type_string(base), args.join(", "))
             }
-
-            self.write("]\n");
+            _ => self.generate_type_string(ty),
         }
+    }
+
+    fn generate_struct(&mut self, struct_def: &Struct) {
+        self.generate_attributes(&struct_def.attributes);
 
         // Generate doc comments
         for comment in &struct_def.doc_comments {
             self.write_line(&format!("/// {}", comment));
         }
 
+        if self.target == TargetLanguage::Crusty {
+            self.generate_crusty_struct(struct_def);
+            return;
+        }
+
+        let equality = self.equality_impls.get(&struct_def.name.name).copied();
+        if equality == Some(EqualityImpl::Derive) {
+            self.write_line(&format!("#[derive({})]", self.prelude_name("PartialEq")));
+        }
+
         // Generate struct definition
         self.write_indent();
         match struct_def.visibility {
             Visibility::Public => self.write("pub "),
             Visibility::Private => {}
         }
+        // A generic struct declares its type parameters bare; the bounds its
+        // methods need go on their impl blocks
+        let name = rust_identifier(&struct_def.name.name);
+        let type_args = if struct_def.generics.is_empty() {
+            String::new()
+        } else {
+            let params: Vec<Cow<str>> = struct_def
+                .generics
+                .iter()
+                .map(|param| rust_identifier(&param.name))
+                .collect();
+            format!("<{}>", params.join(", "))
+        };
+        let impl_params = self.generic_params_string(&struct_def.name.name, &struct_def.generics);
         self.write("struct ");
-        self.write(&struct_def.name.name);
+        self.write(&name);
+        self.write(&type_args);
         self.write(" {\n");
         self.indent();
 
@@ -309,7 +1439,7 @@ impl CodeGenerator {
                 Visibility::Public => self.write("pub "),
                 Visibility::Private => {}
             }
-            self.write(&field.name.name);
+            self.write(&rust_identifier(&field.name.name));
             self.write(": ");
             self.write(&self.generate_type_string(&field.ty));
             self.write(",\n");
@@ -322,61 +1452,105 @@ impl CodeGenerator {
         if !struct_def.methods.is_empty() {
             self.write_line("");
             self.write_indent();
-            self.write("impl ");
-            self.write(&struct_def.name.name);
-            self.write(" {\n");
+            self.write(&format!("impl{} {}{} {{\n", impl_params, name, type_args));
             self.indent();
 
             for (i, method) in struct_def.methods.iter().enumerate() {
                 if i > 0 {
                     self.write_line("");
                 }
-                self.generate_function(method);
+                self.generate_method(
+                    &Type::Ident(struct_def.name.clone()),
+                    method,
+                    Some(&method.visibility),
+                );
             }
 
             self.dedent();
             self.write_line("}");
         }
+
+        // `==` forwards to the struct's own eq method, which as an inherent
+        // method takes precedence over PartialEq::eq in the path
+        if equality == Some(EqualityImpl::Method) {
+            self.write_line("");
+            self.write_line(&format!(
+                "impl{} {} for {}{} {{",
+                impl_params,
+                self.prelude_name("PartialEq"),
+                name,
+                type_args
+            ));
+            self.indent();
+            self.write_line("fn eq(&self, other: &Self) -> bool {");
+            self.indent();
+            self.write_line(&format!("{}::eq(self, other)", name));
+            self.dedent();
+            self.write_line("}");
+            self.dedent();
+            self.write_line("}");
+        }
     }
 
-    fn generate_enum(&mut self, enum_def: &Enum) {
-        // Generate attributes
-        for attr in &enum_def.attributes {
-            self.write_indent();
-            self.write("#[");
-            self.write(&attr.name.name);
+    /// Generate a struct in Crusty syntax: C-style fields with methods inline
+    fn generate_crusty_struct(&mut self, struct_def: &Struct) {
+        self.write_indent();
+        self.write("struct ");
+        self.write(&struct_def.name.name);
+        if !struct_def.generics.is_empty() {
+            let params: Vec<&str> = struct_def
+                .generics
+                .iter()
+                .map(|param| param.name.as_str())
+                .collect();
+            self.write(&format!("({})", params.join(", ")));
+        }
+        self.write(" {\n");
+        self.indent();
 
-            if !attr.args.is_empty() {
-                self.write("(");
-                for (i, arg) in attr.args.iter().enumerate() {
-                    if i > 0 {
-                        self.write(", ");
-                    }
-                    match arg {
-                        AttributeArg::Ident(ident) => {
-                            self.write(&ident.name);
-                        }
-                        AttributeArg::Literal(lit) => {
-                            self.write(&self.generate_literal_string(lit));
-                        }
-                        AttributeArg::NameValue { name, value } => {
-                            self.write(&name.name);
-                            self.write(" = ");
-                            self.write(&self.generate_literal_string(value));
-                        }
-                    }
-                }
-                self.write(")");
+        for field in &struct_def.fields {
+            for comment in &field.doc_comments {
+                self.write_line(&format!("/// {}", comment));
+            }
+            self.write_indent();
+            if field.visibility == Visibility::Private {
+                self.write("static ");
             }
+            self.write(&self.generate_type_string(&field.ty));
+            self.write(" ");
+            self.write(&field.name.name);
+            self.write(";\n");
+        }
 
-            self.write("]\n");
+        for (i, method) in struct_def.methods.iter().enumerate() {
+            if i > 0 || !struct_def.fields.is_empty() {
+                self.write_line("");
+            }
+            self.generate_method(
+                &Type::Ident(struct_def.name.clone()),
+                method,
+                Some(&method.visibility),
+            );
         }
 
+        self.dedent();
+        self.write_line("}");
+    }
+
+    fn generate_enum(&mut self, enum_def: &Enum) {
+        self.generate_attributes(&enum_def.attributes);
+
         // Generate doc comments
         for comment in &enum_def.doc_comments {
             self.write_line(&format!("/// {}", comment));
         }
 
+        if self.target == TargetLanguage::Rust
+            && self.equality_impls.get(&enum_def.name.name) == Some(&EqualityImpl::Derive)
+        {
+            self.write_line(&format!("#[derive({})]", self.prelude_name("PartialEq")));
+        }
+
         // Generate enum definition
         self.write_indent();
         match enum_def.visibility {
@@ -384,15 +1558,32 @@ impl CodeGenerator {
             Visibility::Private => {}
         }
         self.write("enum ");
-        self.write(&enum_def.name.name);
+        self.write(&self.identifier(&enum_def.name.name));
         self.write(" {\n");
         self.indent();
 
-        // Generate variants with discriminants
+        // Generate variants with discriminants; a tagged union's variants
+        // carry payloads instead
+        let tagged = enum_def.variants.iter().any(|v| !v.fields.is_empty());
         for variant in &enum_def.variants {
             self.write_indent();
-            self.write(&variant.name.name);
-            if let Some(value) = variant.value {
+            self.write(&self.identifier(&variant.name.name));
+            if !variant.fields.is_empty() {
+                let fields: Vec<String> = variant
+                    .fields
+                    .iter()
+                    .map(|field| match self.target {
+                        TargetLanguage::Rust => self.generate_type_string(&field.ty),
+                        TargetLanguage::Crusty => format!(
+                            "{} {}",
+                            self.generate_type_string(&field.ty),
+                            field.name.name
+                        ),
+                    })
+                    .collect();
+                self.write(&format!("({})", fields.join(", ")));
+            }
+            if let Some(value) = variant.value.filter(|_| !tagged) {
                 self.write(&format!(" = {}", value));
             }
             self.write(",\n");
@@ -402,6 +1593,82 @@ impl CodeGenerator {
         self.write_line("}");
     }
 
+    fn generate_trait(&mut self, trait_def: &Trait) {
+        self.generate_attributes(&trait_def.attributes);
+
+        for comment in &trait_def.doc_comments {
+            self.write_line(&format!("/// {}", comment));
+        }
+
+        self.write_indent();
+        if self.target == TargetLanguage::Rust && trait_def.visibility == Visibility::Public {
+            self.write("pub ");
+        }
+        self.write("trait ");
+        self.write(&self.identifier(&trait_def.name.name));
+        self.write(" {\n");
+        self.indent();
+
+        // Trait methods take the trait's visibility; those with a default
+        // body are written in full
+        for method in &trait_def.methods {
+            if method.body.is_some() {
+                self.generate_method(
+                    &Type::Ident(trait_def.name.clone()),
+                    &method.as_function(),
+                    None,
+                );
+                continue;
+            }
+            self.write_indent();
+            self.generate_signature(
+                None,
+                &method.name,
+                &[],
+                &method.params,
+                method.return_type.as_ref(),
+            );
+            self.write(";\n");
+        }
+
+        self.dedent();
+        self.write_line("}");
+    }
+
+    fn generate_impl(&mut self, impl_block: &Impl) {
+        self.write_indent();
+        if impl_block.generics.is_empty() {
+            self.write(&format!(
+                "impl {} for {} {{\n",
+                self.identifier(&impl_block.trait_name.name),
+                self.generate_type_string(&impl_block.ty)
+            ));
+        } else if self.target == TargetLanguage::Rust {
+            // The bounds are those the impl's methods need
+            self.write(&format!(
+                "impl{} {} for {} {{\n",
+                self.generic_params_string(&impl_header(impl_block), &impl_block.generics),
+                self.identifier(&impl_block.trait_name.name),
+                self.generate_type_string(&impl_block.ty)
+            ));
+        } else {
+            self.write(&format!("{} {{\n", impl_header(impl_block)));
+        }
+        self.indent();
+
+        // Methods of a trait impl take the trait's visibility, so they are
+        // written without `pub`
+        for (i, method) in impl_block.methods.iter().enumerate() {
+            if i > 0 {
+                self.write_line("");
+            }
+            self.generate_method(&impl_block.ty, method, None);
+        }
+
+        self.dedent();
+        self.write_line("}");
+    }
+
     fn generate_typedef(&mut self, typedef: &Typedef) {
         // Generate doc comments
         for comment in &typedef.doc_comments {
@@ -419,7 +1686,9 @@ impl CodeGenerator {
         let type_str = self.generate_type_string(&typedef.target);
         self.write_line(&format!(
             "{}type {} = {};",
-            visibility, typedef.name.name, type_str
+            visibility,
+            rust_identifier(&typedef.name.name),
+            type_str
         ));
     }
 
@@ -428,41 +1697,216 @@ impl CodeGenerator {
         self.write_line("// TODO: generate_namespace");
     }
 
+    /// Whether `item` imports a module whole into the crate root, which
+    /// already declares it, so the import generates nothing
+    fn imports_declared_module(&self, item: &Item) -> bool {
+        match item {
+            Item::Import(Import {
+                path, alias: None, ..
+            }) if self.target == TargetLanguage::Rust && !self.in_module => {
+                matches!(path.as_slice(), [module] if self.local_modules.contains(&module.name))
+            }
+            _ => false,
+        }
+    }
+
     fn generate_import(&mut self, import_item: &Import) {
         // #import module.path → use module::path;
-        self.write("use ");
-        for (i, ident) in import_item.path.iter().enumerate() {
-            if i > 0 {
-                self.write("::");
+        self.generate_use("use", "import", &import_item.path, &import_item.alias);
+    }
+
+    fn generate_export(&mut self, export_item: &Export) {
+        // #export module.path → pub use module::path;
+        self.generate_use("pub use", "export", &export_item.path, &export_item.alias);
+    }
+
+    /// Generate a `use` declaration, or the Crusty `#directive` it came from
+    ///
+    /// Paths into modules compiled from other Crusty files are anchored at
+    /// the crate root, where those modules are emitted.
+    fn generate_use(
+        &mut self,
+        keyword: &str,
+        directive: &str,
+        path: &[Ident],
+        alias: &Option<Ident>,
+    ) {
+        let segments: Vec<&str> = path.iter().map(|ident| ident.name.as_str()).collect();
+        self.write_indent();
+        match self.target {
+            TargetLanguage::Rust => {
+                self.write(keyword);
+                self.write(" ");
+                if segments
+                    .first()
+                    .is_some_and(|first| self.local_modules.contains(*first))
+                {
+                    self.write("crate::");
+                }
+                let segments: Vec<_> = segments
+                    .iter()
+                    .map(|segment| rust_identifier(segment))
+                    .collect();
+                self.write(&segments.join("::"));
+                if let Some(alias) = alias {
+                    self.write(" as ");
+                    self.write(&rust_identifier(&alias.name));
+                }
+                self.write_line(";");
+            }
+            TargetLanguage::Crusty => {
+                self.write_line(&format!("#{} {}", directive, segments.join(".")));
             }
-            self.write(&ident.name);
         }
-        if let Some(alias) = &import_item.alias {
-            self.write(" as ");
-            self.write(&alias.name);
+    }
+
+    /// Generate a module lowered to HIR as an inline `pub mod name { ... }`,
+    /// as `generate_module`
+    pub fn generate_module_hir(&mut self, name: &str, hir: &crate::hir::Hir) -> String {
+        self.local_types = hir.locals.clone();
+        self.generate_module(name, &hir.file)
+    }
+
+    /// Generate a module compiled from another Crusty file as an inline
+    /// `pub mod name { ... }`
+    ///
+    /// The module body is generated like a file, without the provenance
+    /// header, and indented one level.
+    pub fn generate_module(&mut self, name: &str, file: &File) -> String {
+        let header = std::mem::take(&mut self.header);
+        let body = self.generate(file);
+        self.header = header;
+
+        let mut module = format!("pub mod {} {{\n", name);
+        for line in body.lines() {
+            if !line.is_empty() {
+                module.push_str("    ");
+                module.push_str(line);
+            }
+            module.push('\n');
         }
-        self.write_line(";");
+        module.push_str("}\n");
+        module
     }
 
-    fn generate_export(&mut self, export_item: &Export) {
-        // #export module.path → pub use module::path;
-        self.write("pub use ");
-        for (i, ident) in export_item.path.iter().enumerate() {
-            if i > 0 {
-                self.write("::");
+    /// Generate an extern block of foreign function declarations
+    ///
+    /// Rust requires extern blocks to be marked `unsafe`, and the
+    /// signatures use the `std::ffi` C types for `char` and `void` pointees.
+    fn generate_extern(&mut self, extern_block: &Extern) {
+        self.write_indent();
+        match self.target {
+            TargetLanguage::Rust => self.write("unsafe extern "),
+            TargetLanguage::Crusty => self.write("extern "),
+        }
+        if let Some(abi) = &extern_block.abi {
+            self.write(&format!("\"{}\" ", abi));
+        }
+        self.write("{\n");
+        self.indent_level += 1;
+
+        for func in &extern_block.functions {
+            self.write_indent();
+            let mut params: Vec<String> = match self.target {
+                TargetLanguage::Rust => func
+                    .params
+                    .iter()
+                    .map(|p| {
+                        format!(
+                            "{}: {}",
+                            rust_identifier(&p.name.name),
+                            self.foreign_type_string(&p.ty)
+                        )
+                    })
+                    .collect(),
+                TargetLanguage::Crusty => func
+                    .params
+                    .iter()
+                    .map(|p| format!("{} {}", self.generate_type_string(&p.ty), p.name.name))
+                    .collect(),
+            };
+            if func.variadic {
+                params.push("...".to_string());
             }
-            self.write(&ident.name);
+
+            match self.target {
+                TargetLanguage::Rust => {
+                    self.write(&format!(
+                        "pub fn {}({})",
+                        rust_identifier(&func.name.name),
+                        params.join(", ")
+                    ));
+                    if let Some(return_type) = &func.return_type {
+                        self.write(&format!(" -> {}", self.foreign_type_string(return_type)));
+                    }
+                }
+                TargetLanguage::Crusty => {
+                    let return_type = func
+                        .return_type
+                        .as_ref()
+                        .map(|ty| self.generate_type_string(ty))
+                        .unwrap_or_else(|| "void".to_string());
+                    self.write(&format!(
+                        "{} {}({})",
+                        return_type,
+                        func.name.name,
+                        params.join(", ")
+                    ));
+                }
+            }
+            self.write(";\n");
         }
-        if let Some(alias) = &export_item.alias {
-            self.write(" as ");
-            self.write(&alias.name);
+
+        self.indent_level -= 1;
+        self.write_line("}");
+    }
+
+    /// Rust type of a foreign function parameter or return value
+    ///
+    /// Pointers to `char` and `void` become pointers to `c_char` and
+    /// `c_void` so the declaration matches the C ABI.
+    fn foreign_type_string(&self, ty: &Type) -> String {
+        match ty {
+            Type::Pointer { ty, mutable } => {
+                let pointee = match ty.as_ref() {
+                    Type::Primitive(PrimitiveType::Char) => "std::ffi::c_char".to_string(),
+                    Type::Primitive(PrimitiveType::Void) => "std::ffi::c_void".to_string(),
+                    other => self.foreign_type_string(other),
+                };
+                format!("*{} {}", if *mutable { "mut" } else { "const" }, pointee)
+            }
+            _ => self.generate_type_string(ty),
         }
-        self.write_line(";");
     }
 
-    fn generate_extern(&mut self, _extern_block: &Extern) {
-        // Placeholder
-        self.write_line("// TODO: generate_extern");
+    /// Arguments of a call, converting string literals passed for the `char*`
+    /// parameters of a foreign function to NUL-terminated C strings
+    fn call_argument_strings(&self, func: &Expression, args: &[Expression]) -> Vec<String> {
+        let foreign = match (self.target, func) {
+            (TargetLanguage::Rust, Expression::Ident(ident)) => {
+                self.foreign_functions.get(&ident.name)
+            }
+            _ => None,
+        };
+        args.iter()
+            .enumerate()
+            .map(|(i, arg)| {
+                let param = foreign.and_then(|f| f.params.get(i)).map(|p| &p.ty);
+                match (param, arg) {
+                    (
+                        Some(ty @ Type::Pointer { ty: pointee, .. }),
+                        Expression::Literal(Literal::String(text)),
+                    ) if matches!(pointee.as_ref(), Type::Primitive(PrimitiveType::Char)) => {
+                        format!(
+                            "({:?}.as_ptr() as {})",
+                            format!("{}\0", text),
+                            self.foreign_type_string(ty)
+                        )
+                    }
+                    _ => self.generate_expression_string(arg),
+                }
+            })
+            .collect()
     }
 
     fn generate_const(&mut self, _const_item: &Const) {
@@ -475,7 +1919,40 @@ impl CodeGenerator {
         self.write_line("// TODO: generate_static");
     }
 
+    /// Generate a static assertion
+    ///
+    /// The semantic pass already evaluated the condition; Rust still gets a
+    /// const assertion so conditions it could not evaluate, such as the size
+    /// of a struct, are checked by rustc.
+    fn generate_static_assert(&mut self, assertion: &StaticAssert) {
+        let condition = self.generate_expression_string(&assertion.condition);
+        let message = self.generate_literal_string(&Literal::String(assertion.message.clone()));
+        match self.target {
+            TargetLanguage::Rust => {
+                // Braces would make the message a format string in Rust 2021
+                // but not in 2015, so such messages are passed as an argument
+                let format = if message.contains(['{', '}']) {
+                    format!("\"{{}}\", {}", message)
+                } else {
+                    message
+                };
+                self.write_line(&format!(
+                    "const _: () = assert!({}, {});",
+                    condition, format
+                ));
+            }
+            TargetLanguage::Crusty => {
+                self.write_line(&format!("static_assert({}, {});", condition, message))
+            }
+        }
+    }
+
     fn generate_macro_definition(&mut self, macro_def: &MacroDefinition) {
+        if self.target == TargetLanguage::Crusty {
+            self.generate_crusty_macro_definition(macro_def);
+            return;
+        }
+
         // Translate #define to Rust macro_rules!
         // Remove double-underscore prefix and suffix from macro name
         let rust_name = macro_def
@@ -509,6 +1986,14 @@ impl CodeGenerator {
                 self.write(&format!("${}:expr", param.name));
             }
         }
+        // Further arguments repeat, and `__VA_ARGS__` expands to all of them
+        if macro_def.variadic {
+            if macro_def.params.is_empty() {
+                self.write("$($va_args:expr),*");
+            } else {
+                self.write("$(, $va_args:expr)*");
+            }
+        }
 
         self.write(") => {{\n");
         self.indent();
@@ -525,7 +2010,11 @@ impl CodeGenerator {
                 }
             });
 
-            if is_param {
+            if macro_def.variadic
+                && matches!(token.kind, crate::lexer::TokenKind::Ident(ref name) if name == "__VA_ARGS__")
+            {
+                self.write("$($va_args),*");
+            } else if is_param {
                 // Replace parameter with $param
                 if let crate::lexer::TokenKind::Ident(ref name) = token.kind {
                     self.write(&format!("${}", name));
@@ -551,26 +2040,458 @@ impl CodeGenerator {
         }
         self.write("\n");
 
-        self.dedent();
-        self.write_indent();
-        self.write("}};\n");
+        self.dedent();
+        self.write_indent();
+        self.write("}};\n");
+
+        self.dedent();
+        self.write_line("}");
+    }
+
+    /// Generate a #define in Crusty syntax, preserving the parameter delimiter
+    fn generate_crusty_macro_definition(&mut self, macro_def: &MacroDefinition) {
+        let mut line = format!("#define {}", macro_def.name.name);
+
+        let params = macro_def
+            .params
+            .iter()
+            .map(|p| p.name.as_str())
+            .chain(macro_def.variadic.then_some("..."))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let (open, close) = macro_delimiter_pair(&macro_def.delimiter);
+        line.push_str(open);
+        line.push_str(&params);
+        line.push_str(close);
+
+        let body = join_token_texts(macro_def.body.iter().map(|t| t.text.as_str()));
+        if !body.is_empty() {
+            line.push(' ');
+            line.push_str(&body);
+        }
+
+        self.write_line(&line);
+    }
+
+    /// Generate a block of statements
+    fn generate_block(&mut self, block: &Block) {
+        self.write("{\n");
+        self.indent();
+
+        // Declarations and null guards in the block only last until it
+        // ends, where the locals they shadow or narrow are back in scope
+        let enclosing_options = self.option_locals.clone();
+        let enclosing_volatiles = self.volatile_locals.clone();
+        let enclosing_arrays = self.array_locals.clone();
+        let enclosing_boxes = self.boxed_locals.clone();
+        self.generate_statements(&block.statements, &block.positions, true);
+        self.option_locals = enclosing_options;
+        self.volatile_locals = enclosing_volatiles;
+        self.array_locals = enclosing_arrays;
+        self.boxed_locals = enclosing_boxes;
+
+        self.dedent();
+        self.write_indent();
+        self.write("}");
+    }
+
+    /// Generate a statement sequence; `at_end` is set when nothing follows it
+    /// in the enclosing block
+    ///
+    /// In Rust, guards are flattened: `if (c) { return a; } else { rest }`
+    /// becomes the `if` followed by `rest`, and a guard on an Option-typed
+    /// local such as `if (!p) { return NULL; }` becomes
+    /// `let Some(p) = p else { return Option::None; };`. The else branch is
+    /// only hoisted when its declarations cannot shadow later statements.
+    fn generate_statements(
+        &mut self,
+        statements: &[Statement],
+        positions: &[Position],
+        at_end: bool,
+    ) {
+        let enclosing = self.statement_position;
+        for (i, stmt) in statements.iter().enumerate() {
+            let at_end = at_end && i + 1 == statements.len();
+            self.statement_position = positions.get(i).copied().unwrap_or(enclosing);
+            if let Some(&source) = positions.get(i) {
+                let line = self.current_line();
+                self.generated_statements
+                    .push(GeneratedStatement { line, source });
+            }
+            if self.target == TargetLanguage::Rust {
+                if let Some(name) = stmt
+                    .null_guard()
+                    .filter(|name| self.option_locals.contains_key(&name.name))
+                {
+                    let Statement::If { then_block, .. } = stmt else {
+                        unreachable!("option guards are if statements");
+                    };
+                    // The value is rebound, and stays mutable when the local
+                    // was or is assigned after the guard
+                    let mutable =
+                        self.option_locals[&name.name] || assigns(&statements[i + 1..], &name.name);
+                    self.write_indent();
+                    self.write(&format!(
+                        "let {0}({1}{2}) = {2} else ",
+                        self.prelude_name("Some"),
+                        if mutable { "mut " } else { "" },
+                        rust_identifier(&name.name)
+                    ));
+                    self.generate_block(then_block);
+                    self.write(";\n");
+                    self.option_locals.remove(&name.name);
+                    continue;
+                }
+                if let Statement::If {
+                    condition,
+                    then_block,
+                    else_block: Some(else_block),
+                } = stmt
+                {
+                    if then_block.ends_in_jump() && (at_end || !declares_locals(else_block)) {
+                        self.generate_if(condition, then_block, None);
+                        self.generate_statements(
+                            &else_block.statements,
+                            &else_block.positions,
+                            at_end,
+                        );
+                        continue;
+                    }
+                }
+            }
+            self.generate_statement(stmt);
+        }
+        self.statement_position = enclosing;
+    }
+
+    /// Generate the statements of `body` one after another, as the body of
+    /// a loop whose condition the generated code checks itself
+    fn generate_loop_statements(&mut self, body: &Block) {
+        let enclosing = self.statement_position;
+        for (i, stmt) in body.statements.iter().enumerate() {
+            self.statement_position = body.positions.get(i).copied().unwrap_or(enclosing);
+            self.generate_statement(stmt);
+        }
+        self.statement_position = enclosing;
+    }
+
+    fn generate_if(
+        &mut self,
+        condition: &Expression,
+        then_block: &Block,
+        else_block: Option<&Block>,
+    ) {
+        self.write_indent();
+        self.write("if ");
+        match self.target {
+            TargetLanguage::Rust => {
+                self.write(&self.generate_expression_string(condition));
+            }
+            TargetLanguage::Crusty => {
+                self.write("(");
+                self.write(&self.generate_expression_string(condition));
+                self.write(")");
+            }
+        }
+        self.write(" ");
+        self.generate_block(then_block);
+        if let Some(else_block) = else_block {
+            self.write(" else ");
+            self.generate_block(else_block);
+        }
+        self.write("\n");
+    }
+
+    /// Generate `expr` as the receiver of a method call, parenthesized unless
+    /// it is a plain name
+    fn receiver_string(&self, expr: &Expression) -> String {
+        match expr {
+            Expression::Ident(ident) => self.identifier(&ident.name).into_owned(),
+            _ => format!("({})", self.generate_expression_string(expr)),
+        }
+    }
+
+    /// Generate `do { body } while (cond);`
+    ///
+    /// Rust has no do-while, so the body runs at the top of a `loop` that
+    /// breaks once the condition fails. A `continue` in the body must still
+    /// test the condition; such bodies are wrapped in a labeled block that
+    /// `continue` breaks out of, with `break` aimed at the loop by label.
+    fn generate_do_while(&mut self, body: &Block, condition: &Expression) {
+        self.write_indent();
+        if self.target == TargetLanguage::Crusty {
+            self.write("do ");
+            self.generate_block(body);
+            self.write(" while (");
+            self.write(&self.generate_expression_string(condition));
+            self.write(");\n");
+            return;
+        }
+
+        if has_loop_jump(body, |stmt| matches!(stmt, Statement::Continue(None))) {
+            self.do_while_depth += 1;
+            let suffix = match self.do_while_depth {
+                1 => String::new(),
+                depth => format!("_{}", depth),
+            };
+            let loop_label = Ident::new(format!("do_while{}", suffix));
+            let body_label = Ident::new(format!("do_continue{}", suffix));
+            if has_loop_jump(body, |stmt| matches!(stmt, Statement::Break(None))) {
+                self.write(&format!("'{}: ", loop_label.name));
+            }
+            let mut body = body.clone();
+            label_loop_jumps(&mut body, &loop_label, &body_label);
+
+            self.write("loop {\n");
+            self.indent();
+            self.write_indent();
+            self.write(&format!("'{}: ", body_label.name));
+            self.generate_block(&body);
+            self.write("\n");
+            self.do_while_depth -= 1;
+        } else {
+            self.write("loop {\n");
+            self.indent();
+            if declares_locals(body) {
+                // Keep the body's locals from shadowing names in the condition
+                self.write_indent();
+                self.generate_block(body);
+                self.write("\n");
+            } else {
+                self.generate_loop_statements(body);
+            }
+        }
+
+        self.write_indent();
+        self.write("if !(");
+        self.write(&self.generate_expression_string(condition));
+        self.write(") { break; }\n");
+        self.dedent();
+        self.write_indent();
+        self.write("}\n");
+    }
+
+    /// Record whether a newly declared local shadows an Option-typed,
+    /// pointer-to-volatile or array name
+    fn track_local(&mut self, name: &Ident, ty: Option<&Type>, mutable: bool) {
+        let found = self.local_type(name);
+        let ty = found.as_ref().or(ty);
+        if ty.is_some_and(is_option_type) {
+            self.option_locals.insert(name.name.clone(), mutable);
+        } else {
+            self.option_locals.remove(&name.name);
+        }
+
+        if ty.is_some_and(is_volatile_pointer) {
+            self.volatile_locals.insert(name.name.clone());
+        } else {
+            self.volatile_locals.remove(&name.name);
+        }
+
+        match ty.filter(|ty| array_type(ty).is_some()) {
+            Some(ty) => self.array_locals.insert(name.name.clone(), ty.clone()),
+            None => self.array_locals.remove(&name.name),
+        };
+    }
+
+    /// The type semantic analysis found for the local `name` declared by
+    /// the statement being generated
+    fn local_type(&self, name: &Ident) -> Option<Type> {
+        let site = Site::new(self.site_function.clone()?, self.statement_position);
+        self.local_types
+            .get(&site)?
+            .iter()
+            .find(|(local, _)| *local == name.name)
+            .map(|(_, ty)| ty.clone())
+    }
+
+    /// Whether `func(args)` calls the `memcpy` builtin rather than a
+    /// function declared in the file
+    fn is_memcpy_builtin(&self, func: &Expression, args: &[Expression]) -> bool {
+        args.len() == 3
+            && matches!(func, Expression::Ident(ident)
+            if ident.name == "memcpy"
+                && !self.foreign_functions.contains_key("memcpy")
+                && !self.defined_functions.contains("memcpy"))
+    }
+
+    /// The C time function `func(args)` calls, when it is a builtin rather
+    /// than a function declared in the file
+    fn time_builtin<'a>(&self, func: &'a Expression, args: &[Expression]) -> Option<&'a str> {
+        let Expression::Ident(ident) = func else {
+            return None;
+        };
+        let name = ident.name.as_str();
+        let arity = crate::semantic::time_builtin_arity(name)?;
+        let builtin = (args.len() == arity || crate::semantic::is_null_time_call(name, args))
+            && !self.foreign_functions.contains_key(name)
+            && !self.defined_functions.contains(name);
+        builtin.then_some(name)
+    }
+
+    /// Generate a C time function as its `std::time` or `std::thread`
+    /// equivalent
+    ///
+    /// Failures Rust reports as errors get the results C gives them: `-1`
+    /// from `time()` when the system clock reads before 1970, and no sleep
+    /// at all for a negative duration. `clock()` counts the wall-clock
+    /// microseconds since the program started, `CLOCKS_PER_SEC` being a
+    /// million, on a monotonic `Instant` held in a static at the crate
+    /// root, since the standard library cannot read the processor time C's
+    /// `clock()` measures.
+    fn generate_time_builtin(&self, name: &str, args: &[Expression]) -> String {
+        let sleep = |unit: &str| {
+            format!(
+                "std::thread::sleep(std::time::Duration::{}(<u64 as std::convert::TryFrom<_>>::try_from({}).unwrap_or(0)))",
+                unit,
+                self.generate_expression_string(&args[0])
+            )
+        };
+        match name {
+            "time" => "std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(-1, |elapsed| elapsed.as_secs() as i64)".to_string(),
+            "clock" => format!(
+                "(crate::{}.get_or_init(std::time::Instant::now).elapsed().as_micros() as i64)",
+                CLOCK_START
+            ),
+            "difftime" => format!(
+                "(({} - {}) as f64)",
+                self.generate_expression_string(&args[0]),
+                self.generate_expression_string(&args[1])
+            ),
+            "sleep" => sleep("from_secs"),
+            "usleep" => sleep("from_micros"),
+            _ => unreachable!("time_builtin only returns names of time builtins"),
+        }
+    }
+
+    /// The C signal function `func(args)` calls, when it is a builtin rather
+    /// than a function declared in the file
+    fn signal_builtin<'a>(&self, func: &'a Expression, args: &[Expression]) -> Option<&'a str> {
+        let Expression::Ident(ident) = func else {
+            return None;
+        };
+        let name = ident.name.as_str();
+        let builtin = crate::semantic::signal_builtin_arity(name)? == args.len()
+            && !self.foreign_functions.contains_key(name)
+            && !self.defined_functions.contains(name);
+        builtin.then_some(name)
+    }
+
+    /// Generate `signal(sig, handler)` or `raise(sig)` as a call into the C
+    /// library, declared in a block of its own
+    ///
+    /// C calls handlers with its own calling convention, so a handler is
+    /// wrapped in an `extern "C"` function defined in the same block. The
+    /// wrapper aborts rather than unwind into C if the handler panics.
+    fn generate_signal_builtin(&self, name: &str, args: &[Expression]) -> String {
+        let signum = self.generate_expression_string(&args[0]);
+        if name == "raise" {
+            return format!(
+                "{{ unsafe extern \"C\" {{ fn raise(signum: i32) -> i32; }} unsafe {{ raise({}) }} }}",
+                signum
+            );
+        }
+
+        let (wrapper, handler) = match &args[1] {
+            Expression::Ident(ident) => match crate::semantic::signal_disposition(&ident.name) {
+                Some(value) if !self.defined_functions.contains(&ident.name) => {
+                    (String::new(), value.to_string())
+                }
+                _ => (
+                    format!(
+                        "extern \"C\" fn __crusty_on_signal(signum: i32) {{ {}(signum) }} ",
+                        self.identifier(&ident.name)
+                    ),
+                    "__crusty_on_signal as usize".to_string(),
+                ),
+            },
+            other => (String::new(), self.generate_expression_string(other)),
+        };
+        format!(
+            "{{ {}unsafe extern \"C\" {{ fn signal(signum: i32, handler: usize) -> usize; }} unsafe {{ signal({}, {}) }}; }}",
+            wrapper, signum, handler
+        )
+    }
+
+    /// Generate `memcpy(dst, src, bytes)` as a `copy_from_slice` of the
+    /// elements the byte count covers
+    ///
+    /// A constant count covering both arrays copies them whole; otherwise
+    /// both are sliced to the element count, which panics at runtime if it
+    /// exceeds either of them.
+    fn generate_memcpy(&self, args: &[Expression]) -> String {
+        let [dst, src, bytes] = args else {
+            unreachable!("is_memcpy_builtin checks the argument count")
+        };
+        let dst_code = self.generate_expression_string(dst);
+        let src_code = self.generate_expression_string(src);
+        let array = |expr: &Expression| match expr {
+            Expression::Ident(ident) => self.array_locals.get(&ident.name).and_then(array_type),
+            _ => None,
+        };
 
-        self.dedent();
-        self.write_line("}");
+        let (elem, dst_len) = match array(dst).or(array(src)) {
+            Some((elem, _)) => (Some(elem), array(dst).and_then(|(_, len)| len)),
+            None => (None, None),
+        };
+        let src_len = array(src).and_then(|(_, len)| len);
+        let constant_count = match (
+            crate::consteval::evaluate(bytes, &HashMap::new(), &self.platform),
+            elem.map(|elem| crate::consteval::primitive_size(elem, &self.platform)),
+        ) {
+            (Ok(crate::consteval::ConstValue::Int(bytes)), Some(Ok(size))) if size > 0 => {
+                Some(bytes as usize / size)
+            }
+            _ => None,
+        };
+
+        if constant_count.is_some() && constant_count == dst_len && dst_len == src_len {
+            return format!("{}.copy_from_slice(&{})", dst_code, src_code);
+        }
+        let count = match (constant_count, elem) {
+            (Some(count), _) => count.to_string(),
+            (None, Some(elem)) => format!(
+                "({} as usize) / std::mem::size_of::<{}>()",
+                self.generate_expression_string(bytes),
+                self.generate_type_string(elem)
+            ),
+            (None, None) => format!(
+                "({} as usize) / std::mem::size_of_val(&{}[0])",
+                self.generate_expression_string(bytes),
+                src_code
+            ),
+        };
+        format!(
+            "{}[..{}].copy_from_slice(&{}[..{}])",
+            dst_code, count, src_code, count
+        )
     }
 
-    /// Generate a block of statements
-    fn generate_block(&mut self, block: &Block) {
-        self.write("{\n");
-        self.indent();
+    /// Whether `left op right` assigns through a pointer to volatile
+    fn is_volatile_store(&self, op: &BinaryOp, left: &Expression) -> bool {
+        (*op == BinaryOp::Assign || compound_assign_base(op).is_some())
+            && self.volatile_target(left).is_some()
+    }
 
-        for stmt in &block.statements {
-            self.generate_statement(stmt);
+    /// The pointer dereferenced by `expr` when it is a volatile access such as
+    /// `*reg` or `*(volatile u32*)addr`, generated as a Rust expression
+    fn volatile_target(&self, expr: &Expression) -> Option<String> {
+        if self.target != TargetLanguage::Rust {
+            return None;
         }
-
-        self.dedent();
-        self.write_indent();
-        self.write("}");
+        let Expression::Unary {
+            op: UnaryOp::Deref,
+            expr: pointer,
+        } = expr
+        else {
+            return None;
+        };
+        let volatile = match pointer.as_ref() {
+            Expression::Ident(ident) => self.volatile_locals.contains(&ident.name),
+            Expression::Cast { ty, .. } => is_volatile_pointer(ty),
+            _ => false,
+        };
+        volatile.then(|| self.generate_expression_string(pointer))
     }
 
     /// Generate a statement
@@ -582,6 +2503,7 @@ impl CodeGenerator {
                 init,
                 mutable,
             } => {
+                self.track_local(name, ty.as_ref(), *mutable);
                 self.write_indent();
                 match self.target {
                     TargetLanguage::Rust => {
@@ -589,22 +2511,21 @@ impl CodeGenerator {
                         if *mutable {
                             self.write("mut ");
                         }
-                        self.write(&name.name);
-                        if let Some(ref ty) = ty {
-                            self.write(": ");
-                            self.write(&self.generate_type_string(ty));
-                        }
-                        if let Some(ref init) = init {
-                            self.write(" = ");
-                            self.write(&self.generate_expression_string(init));
-                        }
+                        self.write_rust_local(name, ty.as_ref(), init.as_ref());
                         self.write(";\n");
                     }
+                    TargetLanguage::Crusty if is_string_array_local(ty, init) => {
+                        self.write_crusty_string_array("let", name, ty, init);
+                    }
                     TargetLanguage::Crusty => {
                         // Crusty uses C-style syntax - no type annotations for let
                         // If there's a type but no cast in the init, we need to generate a cast
                         self.write("let ");
-                        self.write(&name.name);
+                        // Without an initializer to cast, the type is declared
+                        match (ty, init) {
+                            (Some(ty), None) => self.write_crusty_declarator(name, ty),
+                            _ => self.write(&name.name),
+                        }
                         if let Some(ref init) = init {
                             self.write(" = ");
                             // If there's a type annotation in the AST but the init is not a cast,
@@ -616,34 +2537,34 @@ impl CodeGenerator {
                                     self.write(")");
                                 }
                             }
-                            self.write(&self.generate_expression_string(init));
+                            self.write_expression(init);
                         }
                         self.write(";\n");
                     }
                 }
             }
             Statement::Var { name, ty, init } => {
+                self.track_local(name, ty.as_ref(), true);
                 self.write_indent();
                 match self.target {
                     TargetLanguage::Rust => {
                         // var is translated to let mut
                         self.write("let mut ");
-                        self.write(&name.name);
-                        if let Some(ref ty) = ty {
-                            self.write(": ");
-                            self.write(&self.generate_type_string(ty));
-                        }
-                        if let Some(ref init) = init {
-                            self.write(" = ");
-                            self.write(&self.generate_expression_string(init));
-                        }
+                        self.write_rust_local(name, ty.as_ref(), init.as_ref());
                         self.write(";\n");
                     }
+                    TargetLanguage::Crusty if is_string_array_local(ty, init) => {
+                        self.write_crusty_string_array("var", name, ty, init);
+                    }
                     TargetLanguage::Crusty => {
                         // Crusty uses C-style syntax - no type annotations for var
                         // If there's a type but no cast in the init, we need to generate a cast
                         self.write("var ");
-                        self.write(&name.name);
+                        // Without an initializer to cast, the type is declared
+                        match (ty, init) {
+                            (Some(ty), None) => self.write_crusty_declarator(name, ty),
+                            _ => self.write(&name.name),
+                        }
                         if let Some(ref init) = init {
                             self.write(" = ");
                             // If there's a type annotation in the AST but the init is not a cast,
@@ -655,22 +2576,26 @@ impl CodeGenerator {
                                     self.write(")");
                                 }
                             }
-                            self.write(&self.generate_expression_string(init));
+                            self.write_expression(init);
                         }
                         self.write(";\n");
                     }
                 }
             }
             Statement::Const { name, ty, value } => {
+                if let Ok(constant) = consteval::evaluate(value, &self.const_values, &self.platform)
+                {
+                    self.const_values.insert(name.name.clone(), constant);
+                }
                 self.write_indent();
                 match self.target {
                     TargetLanguage::Rust => {
                         self.write("const ");
-                        self.write(&name.name);
+                        self.write(&rust_identifier(&name.name));
                         self.write(": ");
                         self.write(&self.generate_type_string(ty));
                         self.write(" = ");
-                        self.write(&self.generate_expression_string(value));
+                        self.write_expression(value);
                         self.write(";\n");
                     }
                     TargetLanguage::Crusty => {
@@ -685,14 +2610,29 @@ impl CodeGenerator {
                             self.write(&self.generate_type_string(ty));
                             self.write(")");
                         }
-                        self.write(&self.generate_expression_string(value));
+                        self.write_expression(value);
                         self.write(";\n");
                     }
                 }
             }
+            Statement::StaticAssert(assertion) => self.generate_static_assert(assertion),
+            // Crusty parses assignments only as statements, unparenthesized
+            Statement::Expr(Expression::Binary { op, left, right })
+                if self.target == TargetLanguage::Crusty
+                    && (*op == BinaryOp::Assign || compound_assign_base(op).is_some()) =>
+            {
+                self.write_indent();
+                let assignment = format!(
+                    "{} {} {};\n",
+                    self.generate_expression_string(left),
+                    self.generate_binary_op_string(op),
+                    self.generate_expression_string(right)
+                );
+                self.write(&assignment);
+            }
             Statement::Expr(expr) => {
                 self.write_indent();
-                self.write(&self.generate_expression_string(expr));
+                self.write_expression(expr);
                 self.write(";\n");
             }
             Statement::Return(expr) => {
@@ -700,7 +2640,7 @@ impl CodeGenerator {
                 self.write("return");
                 if let Some(ref expr) = expr {
                     self.write(" ");
-                    self.write(&self.generate_expression_string(expr));
+                    self.write_expression(expr);
                 }
                 self.write(";\n");
             }
@@ -709,25 +2649,7 @@ impl CodeGenerator {
                 then_block,
                 else_block,
             } => {
-                self.write_indent();
-                self.write("if ");
-                match self.target {
-                    TargetLanguage::Rust => {
-                        self.write(&self.generate_expression_string(condition));
-                    }
-                    TargetLanguage::Crusty => {
-                        self.write("(");
-                        self.write(&self.generate_expression_string(condition));
-                        self.write(")");
-                    }
-                }
-                self.write(" ");
-                self.generate_block(then_block);
-                if let Some(ref else_block) = else_block {
-                    self.write(" else ");
-                    self.generate_block(else_block);
-                }
-                self.write("\n");
+                self.generate_if(condition, then_block, else_block.as_ref());
             }
             Statement::While {
                 label,
@@ -750,13 +2672,19 @@ impl CodeGenerator {
                         }
                     }
                 }
-                self.write("while ");
                 match self.target {
+                    TargetLanguage::Rust
+                        if matches!(condition, Expression::Literal(Literal::Bool(true))) =>
+                    {
+                        // Only `loop` diverges, so code after it type-checks
+                        self.write("loop");
+                    }
                     TargetLanguage::Rust => {
+                        self.write("while ");
                         self.write(&self.generate_expression_string(condition));
                     }
                     TargetLanguage::Crusty => {
-                        self.write("(");
+                        self.write("while (");
                         self.write(&self.generate_expression_string(condition));
                         self.write(")");
                     }
@@ -765,6 +2693,7 @@ impl CodeGenerator {
                 self.generate_block(body);
                 self.write("\n");
             }
+            Statement::DoWhile { body, condition } => self.generate_do_while(body, condition),
             Statement::For {
                 label,
                 init,
@@ -809,9 +2738,7 @@ impl CodeGenerator {
                         self.write(") { break; }\n");
 
                         // Body
-                        for stmt in &body.statements {
-                            self.generate_statement(stmt);
-                        }
+                        self.generate_loop_statements(body);
 
                         // Increment
                         self.write_indent();
@@ -888,7 +2815,7 @@ impl CodeGenerator {
                     }
                 }
                 self.write("for ");
-                self.write(&var.name);
+                self.write(&self.identifier(&var.name));
                 self.write(" in ");
                 self.write(&self.generate_expression_string(iter));
                 self.write(" ");
@@ -914,7 +2841,7 @@ impl CodeGenerator {
                                 if i > 0 {
                                     self.write(" | ");
                                 }
-                                self.write(&self.generate_expression_string(value));
+                                self.write(&self.case_pattern_string(value));
                             }
                             self.write(" => ");
                             self.generate_block(&case.body);
@@ -976,13 +2903,25 @@ impl CodeGenerator {
                             self.write(&label.name);
                         }
                         TargetLanguage::Crusty => {
-                            self.write(" .");
+                            // Labels are referenced without the dot
+                            self.write(" ");
                             self.write(&label.name);
                         }
                     }
                 }
                 self.write(";\n");
             }
+            Statement::Unsafe(block) => {
+                self.write_indent();
+                self.write("unsafe ");
+                self.generate_block(block);
+                self.write("\n");
+            }
+            Statement::Block(block) => {
+                self.write_indent();
+                self.generate_block(block);
+                self.write("\n");
+            }
             Statement::Continue(label) => {
                 self.write_indent();
                 self.write("continue");
@@ -994,7 +2933,8 @@ impl CodeGenerator {
                             self.write(&label.name);
                         }
                         TargetLanguage::Crusty => {
-                            self.write(" .");
+                            // Labels are referenced without the dot
+                            self.write(" ");
                             self.write(&label.name);
                         }
                     }
@@ -1022,7 +2962,7 @@ impl CodeGenerator {
                     self.write("mut ");
                 }
 
-                self.write(&name.name);
+                self.write(&rust_identifier(&name.name));
                 self.write(" = |");
 
                 // Generate parameters
@@ -1030,7 +2970,7 @@ impl CodeGenerator {
                     if i > 0 {
                         self.write(", ");
                     }
-                    self.write(&param.name.name);
+                    self.write(&rust_identifier(&param.name.name));
                     self.write(": ");
                     let param_type = self.generate_type_string(&param.ty);
                     self.write(&param_type);
@@ -1046,17 +2986,101 @@ impl CodeGenerator {
                 }
 
                 self.write(" ");
+                let enclosing_fallible = std::mem::replace(
+                    &mut self.fallible_return,
+                    matches!(return_type, Some(Type::Fallible { .. })),
+                );
                 self.generate_block(body);
+                self.fallible_return = enclosing_fallible;
                 self.write(";\n");
             }
         }
     }
 
+    /// A switch case value as a Rust pattern, which cannot parenthesize a
+    /// negated literal the way `-(1)` does in an expression
+    fn case_pattern_string(&self, value: &Expression) -> String {
+        match value {
+            Expression::Unary {
+                op: UnaryOp::Neg,
+                expr,
+            } if matches!(expr.as_ref(), Expression::Literal(_)) => {
+                format!("-{}", self.generate_expression_string(expr))
+            }
+            // A pattern cannot be an expression, so fold constant ones
+            Expression::Binary { .. }
+            | Expression::Unary { .. }
+            | Expression::Ternary { .. }
+            | Expression::Cast { .. }
+            | Expression::Sizeof { .. } => {
+                match consteval::evaluate(value, &self.const_values, &self.platform) {
+                    Ok(ConstValue::Int(constant)) => constant.to_string(),
+                    Ok(ConstValue::Bool(constant)) => constant.to_string(),
+                    _ => self.generate_expression_string(value),
+                }
+            }
+            _ => self.generate_expression_string(value),
+        }
+    }
+
     /// Generate an expression and return as string
     pub fn generate_expression_string(&self, expr: &Expression) -> String {
+        if let Some(path) = self.enum_variant_path(expr) {
+            return path;
+        }
         match expr {
             Expression::Literal(lit) => self.generate_literal_string(lit),
-            Expression::Ident(ident) => ident.name.clone(),
+            Expression::Ident(ident)
+                if self.target == TargetLanguage::Rust
+                    && ident.name == crate::semantic::CLOCKS_PER_SEC =>
+            {
+                "1_000_000_i64".to_string()
+            }
+            Expression::Ident(ident)
+                if self.target == TargetLanguage::Rust
+                    && self.platform.signal_number(&ident.name).is_some() =>
+            {
+                self.platform
+                    .signal_number(&ident.name)
+                    .unwrap_or_default()
+                    .to_string()
+            }
+            Expression::Ident(ident)
+                if self.target == TargetLanguage::Rust
+                    && self.boxed_locals.contains(&ident.name) =>
+            {
+                format!("(*{})", self.identifier(&ident.name))
+            }
+            Expression::Ident(ident) => self.identifier(&ident.name).into_owned(),
+            // Assigning a boxed local as a whole boxes the new value
+            Expression::Binary {
+                op: BinaryOp::Assign,
+                left,
+                right,
+            } if self.target == TargetLanguage::Rust
+                && matches!(left.as_ref(), Expression::Ident(ident) if self.boxed_locals.contains(&ident.name)) =>
+            {
+                format!(
+                    "({} = {}::new({}))",
+                    self.receiver_string(left),
+                    self.prelude_name("Box"),
+                    self.generate_expression_string(right)
+                )
+            }
+            // Volatile stores: *reg = v and *reg op= v
+            Expression::Binary { op, left, right } if self.is_volatile_store(op, left) => {
+                let pointer = self.volatile_target(left).unwrap_or_default();
+                let value = self.generate_expression_string(right);
+                match compound_assign_base(op) {
+                    Some(base) => format!(
+                        "{0}.write_volatile(({0}.read_volatile() {1} {2}))",
+                        pointer,
+                        self.generate_binary_op_string(&base),
+                        value
+                    ),
+                    None => format!("{}.write_volatile({})", pointer, value),
+                }
+            }
             Expression::Binary { op, left, right } => {
                 format!(
                     "({} {} {})",
@@ -1065,21 +3089,64 @@ impl CodeGenerator {
                     self.generate_expression_string(right)
                 )
             }
+            Expression::Unary { .. } if self.volatile_target(expr).is_some() => {
+                format!(
+                    "{}.read_volatile()",
+                    self.volatile_target(expr).unwrap_or_default()
+                )
+            }
             Expression::Unary { op, expr } => self.generate_unary_expression_string(op, expr),
+            Expression::Call { func, args }
+                if self.target == TargetLanguage::Rust && self.is_memcpy_builtin(func, args) =>
+            {
+                self.generate_memcpy(args)
+            }
+            Expression::Call { func, args }
+                if self.target == TargetLanguage::Rust
+                    && self.time_builtin(func, args).is_some() =>
+            {
+                let name = self.time_builtin(func, args).unwrap_or_default();
+                self.generate_time_builtin(name, args)
+            }
+            Expression::Call { func, args }
+                if self.target == TargetLanguage::Rust
+                    && self.signal_builtin(func, args).is_some() =>
+            {
+                let name = self.signal_builtin(func, args).unwrap_or_default();
+                self.generate_signal_builtin(name, args)
+            }
             Expression::Call { func, args } => {
-                let mut result = self.generate_expression_string(func);
-                result.push('(');
-                for (i, arg) in args.iter().enumerate() {
-                    if i > 0 {
-                        result.push_str(", ");
-                    }
-                    result.push_str(&self.generate_expression_string(arg));
-                }
-                result.push(')');
-                result
+                format!(
+                    "{}({})",
+                    self.callee_string(func),
+                    self.call_argument_strings(func, args).join(", ")
+                )
             }
             Expression::FieldAccess { expr, field } => {
-                format!("{}.{}", self.generate_expression_string(expr), field.name)
+                format!(
+                    "{}.{}",
+                    self.generate_expression_string(expr),
+                    self.identifier(&field.name)
+                )
+            }
+            Expression::Index { expr, index } if self.returns_check_errors() => {
+                let array = self.generate_expression_string(expr);
+                let index = self.generate_expression_string(index);
+                if evaluates_alike(expr) {
+                    // The index is checked before the array is borrowed, so
+                    // this works on either side of an assignment
+                    format!(
+                        "{}[{{ let __crusty_index = {}; if __crusty_index >= {}.len() {{ return Err(\"index out of bounds\".into()); }} __crusty_index }}]",
+                        array, index, array
+                    )
+                } else {
+                    // An array computed by a call is evaluated once, and
+                    // only read
+                    format!(
+                        "(*{}.get({}).ok_or(\"index out of bounds\")?)",
+                        array, index
+                    )
+                }
             }
             Expression::Index { expr, index } => {
                 format!(
@@ -1088,36 +3155,191 @@ impl CodeGenerator {
                     self.generate_expression_string(index)
                 )
             }
-            Expression::Cast { expr, ty } => {
+            // Under the `result` panic policy, a cast that would change the
+            // value returns an error instead
+            Expression::CheckedCast { expr, from, ty } if self.returns_check_errors() => {
                 format!(
+                    "(<{} as std::convert::TryFrom<{}>>::try_from({}).map_err(|_| \"cast out of range\")?)",
+                    self.generate_type_string(ty),
+                    self.generate_type_string(from),
+                    self.generate_expression_string(expr)
+                )
+            }
+            Expression::CheckedCast { expr, ty, .. } => {
+                self.generate_expression_string(&Expression::Cast {
+                    expr: expr.clone(),
+                    ty: ty.clone(),
+                })
+            }
+            Expression::Cast { expr, ty } => match (self.target, expr.as_ref()) {
+                // (Point){ .x = 1 } names the struct type rather than casting
+                (
+                    TargetLanguage::Rust,
+                    Expression::StructInit {
+                        ty: Type::Auto,
+                        fields,
+                    },
+                ) => self.generate_expression_string(&Expression::StructInit {
+                    ty: ty.clone(),
+                    fields: fields.clone(),
+                }),
+                // `&n` passed for a `var &` parameter, cast to it by lowering
+                (
+                    TargetLanguage::Rust,
+                    Expression::Unary {
+                        op: UnaryOp::Ref,
+                        expr: place,
+                    },
+                ) if matches!(ty, Type::Reference { mutable: true, .. }) => {
+                    format!("&mut ({})", self.generate_expression_string(place))
+                }
+                // (&Derived)base reinterprets a struct reference, which Rust
+                // only allows through a raw pointer
+                (TargetLanguage::Rust, _) if self.is_struct_reference(ty) => {
+                    let Type::Reference {
+                        ty: target,
+                        mutable,
+                    } = ty
+                    else {
+                        unreachable!()
+                    };
+                    let (borrow, from) = match mutable {
+                        true => ("&mut *", "from_mut"),
+                        false => ("&*", "from_ref"),
+                    };
+                    format!(
+                        "unsafe {{ {}std::ptr::{}({}).cast::<{}>() }}",
+                        borrow,
+                        from,
+                        self.generate_expression_string(expr),
+                        self.generate_type_string(target)
+                    )
+                }
+                (TargetLanguage::Rust, _) => format!(
                     "({} as {})",
                     self.generate_expression_string(expr),
                     self.generate_type_string(ty)
-                )
+                ),
+                (TargetLanguage::Crusty, _) => format!(
+                    "({}){}",
+                    self.generate_type_string(ty),
+                    self.generate_expression_string(expr)
+                ),
+            },
+            Expression::Sizeof { ty } | Expression::Alignof { ty } => {
+                let operator = match expr {
+                    Expression::Sizeof { .. } => "size",
+                    _ => "align",
+                };
+                match self.target {
+                    TargetLanguage::Rust => format!(
+                        "std::mem::{}_of::<{}>()",
+                        operator,
+                        self.generate_type_string(ty)
+                    ),
+                    TargetLanguage::Crusty => {
+                        format!("{}of({})", operator, self.generate_type_string(ty))
+                    }
+                }
             }
-            Expression::Sizeof { ty } => {
-                format!("std::mem::size_of::<{}>()", self.generate_type_string(ty))
+            Expression::SizeofExpr { expr: operand }
+            | Expression::AlignofExpr { expr: operand } => {
+                let operator = match expr {
+                    Expression::SizeofExpr { .. } => "size",
+                    _ => "align",
+                };
+                let operand_string = self.generate_expression_string(operand);
+                match self.target {
+                    // Lowering measures the type analysis found instead;
+                    // without it, Rust measures the value
+                    TargetLanguage::Rust => {
+                        format!("std::mem::{}_of_val(&{})", operator, operand_string)
+                    }
+                    TargetLanguage::Crusty => format!("{}of({})", operator, operand_string),
+                }
             }
+            Expression::TruthTest { expr, test } => match (self.target, test) {
+                (TargetLanguage::Rust, Truthiness::Option) => {
+                    format!("{}.is_some()", self.receiver_string(expr))
+                }
+                (TargetLanguage::Rust, Truthiness::Pointer) => {
+                    format!("!{}.is_null()", self.receiver_string(expr))
+                }
+                (TargetLanguage::Rust, Truthiness::Integer) => {
+                    format!("({} != 0)", self.generate_expression_string(expr))
+                }
+                (TargetLanguage::Crusty, _) => self.generate_expression_string(expr),
+            },
             Expression::Ternary {
                 condition,
                 then_expr,
                 else_expr,
             } => {
+                let condition = self.generate_expression_string(condition);
+                let condition = match self.target {
+                    TargetLanguage::Rust => condition,
+                    TargetLanguage::Crusty => format!("({})", condition),
+                };
+                // Nested conditionals in the else branch continue the chain
+                let else_branch = match else_expr.as_ref() {
+                    Expression::Ternary { .. } => self.generate_expression_string(else_expr),
+                    _ => format!("{{ {} }}", self.generate_expression_string(else_expr)),
+                };
                 format!(
-                    "if {} {{ {} }} else {{ {} }}",
-                    self.generate_expression_string(condition),
+                    "if {} {{ {} }} else {}",
+                    condition,
                     self.generate_expression_string(then_expr),
-                    self.generate_expression_string(else_expr)
+                    else_branch
                 )
             }
+            Expression::Match { expr, arms } => {
+                let arms: Vec<String> = arms
+                    .iter()
+                    .map(|arm| {
+                        format!(
+                            "{} => {}",
+                            self.generate_pattern_string(&arm.pattern),
+                            self.generate_expression_string(&arm.body)
+                        )
+                    })
+                    .collect();
+                format!("{} {{ {} }}", self.match_head(expr), arms.join(", "))
+            }
+            Expression::Lambda {
+                params,
+                return_type,
+                body,
+                by_value,
+            } => self.lambda_string(params, return_type.as_ref(), body, *by_value),
+            Expression::StructInit { ty, fields } if self.target == TargetLanguage::Crusty => {
+                // Designated initializer: (Type){ .field = value }
+                let mut result = String::new();
+                if !matches!(ty, Type::Auto) {
+                    result.push('(');
+                    result.push_str(&self.generate_type_string(ty));
+                    result.push(')');
+                }
+                result.push_str("{ ");
+                for (i, (name, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        result.push_str(", ");
+                    }
+                    result.push('.');
+                    result.push_str(&name.name);
+                    result.push_str(" = ");
+                    result.push_str(&self.generate_expression_string(value));
+                }
+                result.push_str(" }");
+                result
+            }
             Expression::StructInit { ty, fields } => {
-                let mut result = self.generate_type_string(ty);
+                let mut result = self.struct_path_string(ty);
                 result.push_str(" { ");
                 for (i, (name, value)) in fields.iter().enumerate() {
                     if i > 0 {
                         result.push_str(", ");
                     }
-                    result.push_str(&name.name);
+                    result.push_str(&self.identifier(&name.name));
                     result.push_str(": ");
                     result.push_str(&self.generate_expression_string(value));
                 }
@@ -1164,17 +3386,30 @@ impl CodeGenerator {
                 }
                 result
             }
-            Expression::MacroCall { name, args } => {
-                let mut result = name.name.clone();
-                result.push('!');
-                result.push('(');
-                for (i, token) in args.iter().enumerate() {
-                    if i > 0 {
-                        result.push_str(", ");
-                    }
-                    result.push_str(&token.text);
-                }
-                result.push(')');
+            Expression::MacroCall {
+                name,
+                args,
+                delimiter,
+            } => {
+                let is_crusty_macro = name.name.starts_with("__") && name.name.ends_with("__");
+                let mut result = match self.target {
+                    // __println__(...) → println!(...)
+                    TargetLanguage::Rust if is_crusty_macro => format!(
+                        "{}!",
+                        name.name
+                            .trim_start_matches("__")
+                            .trim_end_matches("__")
+                            .to_lowercase()
+                    ),
+                    // Double-underscore names are invoked without the bang in Crusty
+                    TargetLanguage::Crusty if is_crusty_macro => name.name.clone(),
+                    _ => format!("{}!", name.name),
+                };
+                let (open, close) = macro_delimiter_pair(delimiter);
+                result.push_str(open);
+                let args = self.macro_arg_texts(args);
+                result.push_str(&join_token_texts(args.iter().map(|text| text.as_ref())));
+                result.push_str(close);
                 result
             }
             Expression::RustBlock { tokens } => {
@@ -1194,9 +3429,13 @@ impl CodeGenerator {
                 method,
                 args,
             } => {
-                let mut result = self.generate_expression_string(receiver);
+                // `.` binds tighter than a prefix operator on the receiver
+                let mut result = match receiver.as_ref() {
+                    Expression::Unary { .. } => self.receiver_string(receiver),
+                    _ => self.generate_expression_string(receiver),
+                };
                 result.push('.');
-                result.push_str(&method.name);
+                result.push_str(&self.identifier(&method.name));
                 result.push('(');
                 for (i, arg) in args.iter().enumerate() {
                     if i > 0 {
@@ -1211,7 +3450,7 @@ impl CodeGenerator {
                 // Translate @Type.method() to Type::method()
                 let mut result = self.generate_type_string(ty);
                 result.push_str("::");
-                result.push_str(&method.name);
+                result.push_str(&self.identifier(&method.name));
                 result.push('(');
                 for (i, arg) in args.iter().enumerate() {
                     if i > 0 {
@@ -1238,40 +3477,409 @@ impl CodeGenerator {
                     result.push_str(&self.generate_type_string(gen));
                 }
                 result.push_str(">::");
-                result.push_str(&method.name);
+                result.push_str(&self.identifier(&method.name));
                 result.push('(');
                 for (i, arg) in args.iter().enumerate() {
                     if i > 0 {
                         result.push_str(", ");
                     }
-                    result.push_str(&self.generate_expression_string(arg));
+                    result.push_str(&self.generate_expression_string(arg));
+                }
+                result.push(')');
+                result
+            }
+            Expression::Comma { left, right } => {
+                // Comma expression: (left, right)
+                // In Rust, we use a block with both expressions
+                let mut result = String::from("{ ");
+                result.push_str(&self.generate_expression_string(left));
+                result.push_str("; ");
+                result.push_str(&self.generate_expression_string(right));
+                result.push_str(" }");
+                result
+            }
+        }
+    }
+
+    /// Build a layout document for an expression
+    ///
+    /// Laid out flat the document renders exactly like
+    /// `generate_expression_string`; argument lists, initializers and binary
+    /// chains are grouped so they can break across lines.
+    fn generate_expression_doc(&self, expr: &Expression) -> Doc {
+        match expr {
+            Expression::Binary { op, left, .. } if self.is_volatile_store(op, left) => {
+                Doc::text(self.generate_expression_string(expr))
+            }
+            Expression::Binary { op, .. } => {
+                // Flatten a left-nested chain of the same operator into one
+                // group so that every operand breaks consistently
+                let mut operands = Vec::new();
+                let mut current = expr;
+                while let Expression::Binary {
+                    op: inner_op,
+                    left,
+                    right,
+                } = current
+                {
+                    if inner_op != op {
+                        break;
+                    }
+                    operands.push(right.as_ref());
+                    current = left.as_ref();
+                }
+                operands.push(current);
+                operands.reverse();
+
+                let operand_doc = |operand: &Expression| self.generate_expression_doc(operand);
+                let op_text = format!(" {}", self.generate_binary_op_string(op));
+                let mut rest = Vec::new();
+                for operand in &operands[1..] {
+                    rest.push(Doc::text(op_text.clone()));
+                    rest.push(Doc::Line);
+                    rest.push(operand_doc(operand));
+                    rest.push(Doc::text(")"));
+                }
+                Doc::group(Doc::concat(vec![
+                    Doc::text("(".repeat(operands.len() - 1)),
+                    operand_doc(operands[0]),
+                    Doc::nest(4, Doc::concat(rest)),
+                ]))
+            }
+            Expression::Call { func, args }
+                if matches!(func.as_ref(), Expression::Ident(ident)
+                    if self.foreign_functions.contains_key(&ident.name))
+                    || (self.target == TargetLanguage::Rust
+                        && (self.is_memcpy_builtin(func, args)
+                            || self.time_builtin(func, args).is_some()
+                            || self.signal_builtin(func, args).is_some())) =>
+            {
+                Doc::text(self.generate_expression_string(expr))
+            }
+            Expression::Call { func, args } => {
+                let callee = Doc::text(self.callee_string(func));
+                self.generate_argument_list_doc(callee, args)
+            }
+            Expression::MethodCall {
+                receiver,
+                method,
+                args,
+            } => {
+                let callee = Doc::text(format!(
+                    "{}.{}",
+                    self.generate_expression_string(receiver),
+                    self.identifier(&method.name)
+                ));
+                self.generate_argument_list_doc(callee, args)
+            }
+            Expression::TypeScopedCall { .. } if self.enum_variant_path(expr).is_some() => {
+                Doc::text(self.generate_expression_string(expr))
+            }
+            Expression::TypeScopedCall { ty, method, args } => {
+                let callee = Doc::text(format!(
+                    "{}::{}",
+                    self.generate_type_string(ty),
+                    self.identifier(&method.name)
+                ));
+                self.generate_argument_list_doc(callee, args)
+            }
+            Expression::ArrayLit { elements } => {
+                let elements = elements
+                    .iter()
+                    .map(|e| self.generate_expression_doc(e))
+                    .collect();
+                Doc::group(Doc::concat(vec![
+                    Doc::text("["),
+                    Doc::nest(
+                        4,
+                        Doc::concat(vec![Doc::SoftLine, Doc::join(elements, ",")]),
+                    ),
+                    Doc::SoftLine,
+                    Doc::text("]"),
+                ]))
+            }
+            Expression::StructInit { ty, fields } => {
+                let (open, assign) = match self.target {
+                    TargetLanguage::Rust => (format!("{} {{", self.struct_path_string(ty)), ": "),
+                    TargetLanguage::Crusty if matches!(ty, Type::Auto) => ("{".to_string(), " = "),
+                    TargetLanguage::Crusty => {
+                        (format!("({}){{", self.generate_type_string(ty)), " = ")
+                    }
+                };
+                let prefix = match self.target {
+                    TargetLanguage::Rust => "",
+                    TargetLanguage::Crusty => ".",
+                };
+                let fields = fields
+                    .iter()
+                    .map(|(name, value)| {
+                        Doc::concat(vec![
+                            Doc::text(format!(
+                                "{}{}{}",
+                                prefix,
+                                self.identifier(&name.name),
+                                assign
+                            )),
+                            self.generate_expression_doc(value),
+                        ])
+                    })
+                    .collect();
+                Doc::group(Doc::concat(vec![
+                    Doc::text(open),
+                    Doc::nest(4, Doc::concat(vec![Doc::Line, Doc::join(fields, ",")])),
+                    Doc::Line,
+                    Doc::text("}"),
+                ]))
+            }
+            Expression::Match { expr, arms } => {
+                let arms = arms
+                    .iter()
+                    .map(|arm| {
+                        Doc::concat(vec![
+                            Doc::text(format!(
+                                "{} => ",
+                                self.generate_pattern_string(&arm.pattern)
+                            )),
+                            self.generate_expression_doc(&arm.body),
+                        ])
+                    })
+                    .collect();
+                Doc::group(Doc::concat(vec![
+                    Doc::text(format!("{} {{", self.match_head(expr))),
+                    Doc::nest(4, Doc::concat(vec![Doc::Line, Doc::join(arms, ",")])),
+                    Doc::Line,
+                    Doc::text("}"),
+                ]))
+            }
+            Expression::Cast { expr: inner, ty } => match (self.target, inner.as_ref()) {
+                (
+                    TargetLanguage::Rust,
+                    Expression::StructInit {
+                        ty: Type::Auto,
+                        fields,
+                    },
+                ) => self.generate_expression_doc(&Expression::StructInit {
+                    ty: ty.clone(),
+                    fields: fields.clone(),
+                }),
+                (TargetLanguage::Crusty, _) => Doc::concat(vec![
+                    Doc::text(format!("({})", self.generate_type_string(ty))),
+                    self.generate_expression_doc(inner),
+                ]),
+                _ => Doc::text(self.generate_expression_string(expr)),
+            },
+            _ => Doc::text(self.generate_expression_string(expr)),
+        }
+    }
+
+    /// A lambda as a Rust closure, `move` when it captures by value, or in
+    /// Crusty as written; its body is indented as a block of the statement
+    /// it is in
+    fn lambda_string(
+        &self,
+        params: &[Param],
+        return_type: Option<&Type>,
+        body: &Block,
+        by_value: bool,
+    ) -> String {
+        let mut result = match self.target {
+            TargetLanguage::Rust => {
+                let params: Vec<String> = params
+                    .iter()
+                    .map(|param| {
+                        format!(
+                            "{}: {}",
+                            rust_identifier(&param.name.name),
+                            self.generate_type_string(&param.ty)
+                        )
+                    })
+                    .collect();
+                let capture = if by_value { "move " } else { "" };
+                format!("{}|{}|", capture, params.join(", "))
+            }
+            TargetLanguage::Crusty => {
+                let params: Vec<String> = params
+                    .iter()
+                    .map(|param| {
+                        format!(
+                            "{} {}",
+                            self.generate_type_string(&param.ty),
+                            param.name.name
+                        )
+                    })
+                    .collect();
+                format!("fn({})", params.join(", "))
+            }
+        };
+        if let Some(ty) = return_type {
+            result.push_str(" -> ");
+            result.push_str(&self.generate_type_string(ty));
+        }
+        result.push(' ');
+
+        // The body is generated by a copy of the generator, as statements
+        // are written to the output rather than returned
+        let mut generator = CodeGenerator {
+            output: String::new(),
+            counted_lines: (0, 0),
+            generated_items: Vec::new(),
+            generated_statements: Vec::new(),
+            heap_allocations: Vec::new(),
+            fallible_return: matches!(return_type, Some(Type::Fallible { .. })),
+            ..self.clone()
+        };
+        generator.generate_block(body);
+        result.push_str(&generator.output);
+        result
+    }
+
+    /// The texts of the argument tokens of a macro call, with boxed locals
+    /// dereferenced, variants of the file's enums written `Enum::Variant`,
+    /// functions of modules imported whole `util::twice`, and names that are
+    /// Rust keywords escaped, as they are elsewhere
+    fn macro_arg_texts<'t>(&self, args: &'t [crate::ast::Token]) -> Vec<Cow<'t, str>> {
+        let mut texts = Vec::with_capacity(args.len());
+        let mut after_path = false;
+        for (i, token) in args.iter().enumerate() {
+            let rust = self.target == TargetLanguage::Rust;
+            let boxed = rust
+                && !after_path
+                && crate::macros::is_name_token(token)
+                && self.boxed_locals.contains(&token.text);
+            // `Color.Blue`, the enum not itself after a path separator, or
+            // `util.twice` for a module imported whole
+            let variant = rust
+                && token.text == "."
+                && i >= 1
+                && (i < 2 || !matches!(args[i - 2].text.as_str(), "." | "::"))
+                && (self.imported_modules.contains(&args[i - 1].text)
+                    || args.get(i + 1).is_some_and(|variant| {
+                        self.enum_variants
+                            .get(&args[i - 1].text)
+                            .is_some_and(|variants| variants.contains(&variant.text))
+                    }));
+            texts.push(if boxed {
+                Cow::Owned(format!("(*{})", rust_identifier(&token.text)))
+            } else if variant {
+                Cow::Borrowed("::")
+            } else if rust
+                && crate::macros::is_name_token(token)
+                && crate::lexer::word_token(&token.text).is_none()
+            {
+                // A Crusty name that is a Rust keyword, as `s.type`
+                rust_identifier(&token.text)
+            } else {
+                Cow::Borrowed(token.text.as_str())
+            });
+            after_path = matches!(token.text.as_str(), "." | "::");
+        }
+        texts
+    }
+
+    /// `match` and the matched value, as they open a match expression
+    fn match_head(&self, expr: &Expression) -> String {
+        let expr = self.generate_expression_string(expr);
+        match self.target {
+            TargetLanguage::Rust => format!("match {}", expr),
+            TargetLanguage::Crusty => format!("match ({})", expr),
+        }
+    }
+
+    /// Generate a match arm pattern as string
+    ///
+    /// `Option` and `Result` variants lower to Rust's prelude `Some`, `None`,
+    /// `Ok` and `Err`.
+    fn generate_pattern_string(&self, pattern: &Pattern) -> String {
+        match pattern {
+            Pattern::Wildcard => "_".to_string(),
+            Pattern::Literal(lit) => self.generate_literal_string(lit),
+            Pattern::Binding(name) => self.identifier(&name.name).into_owned(),
+            Pattern::Variant {
+                enum_name,
+                variant,
+                fields,
+            } => {
+                let path = match (self.target, enum_name.name.as_str()) {
+                    (TargetLanguage::Rust, "Option" | "Result")
+                        if !self.shadowed_prelude.contains(&enum_name.name) =>
+                    {
+                        self.prelude_name(&variant.name).into_owned()
+                    }
+                    (TargetLanguage::Rust, _) => format!(
+                        "{}::{}",
+                        rust_identifier(&enum_name.name),
+                        rust_identifier(&variant.name)
+                    ),
+                    (TargetLanguage::Crusty, _) => format!("{}.{}", enum_name.name, variant.name),
+                };
+                if fields.is_empty() {
+                    path
+                } else {
+                    let fields: Vec<String> = fields
+                        .iter()
+                        .map(|field| self.generate_pattern_string(field))
+                        .collect();
+                    format!("{}({})", path, fields.join(", "))
                 }
-                result.push(')');
-                result
             }
-            Expression::Comma { left, right } => {
-                // Comma expression: (left, right)
-                // In Rust, we use a block with both expressions
-                let mut result = String::from("{ ");
-                result.push_str(&self.generate_expression_string(left));
-                result.push_str("; ");
-                result.push_str(&self.generate_expression_string(right));
-                result.push_str(" }");
-                result
+            Pattern::Tuple(patterns) => {
+                let patterns: Vec<String> = patterns
+                    .iter()
+                    .map(|pattern| self.generate_pattern_string(pattern))
+                    .collect();
+                match (self.target, patterns.as_slice()) {
+                    // `(p)` is only a parenthesized pattern in Rust
+                    (TargetLanguage::Rust, [single]) => format!("({},)", single),
+                    _ => format!("({})", patterns.join(", ")),
+                }
             }
         }
     }
 
+    /// Build a layout document for `callee(arg, ...)`
+    fn generate_argument_list_doc(&self, callee: Doc, args: &[Expression]) -> Doc {
+        let args = args
+            .iter()
+            .map(|arg| self.generate_expression_doc(arg))
+            .collect();
+        Doc::group(Doc::concat(vec![
+            callee,
+            Doc::text("("),
+            Doc::nest(4, Doc::concat(vec![Doc::SoftLine, Doc::join(args, ",")])),
+            Doc::SoftLine,
+            Doc::text(")"),
+        ]))
+    }
+
     /// Generate a literal value as string
     fn generate_literal_string(&self, lit: &Literal) -> String {
         match lit {
             Literal::Int(n) => n.to_string(),
-            Literal::Float(f) => f.to_string(),
+            Literal::RadixInt(n, radix) => radix.format(*n),
+            Literal::Float(f) => format!("{:?}", f),
+            Literal::Suffixed(literal, ty) => format!(
+                "{}{}",
+                self.generate_literal_string(literal),
+                ty.suffix().unwrap_or_default()
+            ),
             Literal::String(s) => format!("\"{}\"", s.escape_default()),
+            Literal::RawString(s) => raw_string(s),
+            Literal::ByteString(bytes) => format!("b\"{}\"", bytes.escape_ascii()),
+            Literal::WideString(s) => match (self.target, self.wide_strings) {
+                (TargetLanguage::Rust, WideStringMapping::Utf16) => format!(
+                    "\"{}\\0\".encode_utf16().collect::<{}<u16>>()",
+                    s.escape_default(),
+                    self.prelude_name("Vec")
+                ),
+                (TargetLanguage::Rust, WideStringMapping::WideString) => {
+                    format!("widestring::u16cstr!(\"{}\")", s.escape_default())
+                }
+                (TargetLanguage::Crusty, _) => format!("L\"{}\"", s.escape_default()),
+            },
             Literal::Char(c) => format!("'{}'", c.escape_default()),
             Literal::Bool(b) => b.to_string(),
             Literal::Null => match self.target {
-                TargetLanguage::Rust => "Option::None".to_string(),
+                TargetLanguage::Rust => format!("{}::None", self.prelude_name("Option")),
                 TargetLanguage::Crusty => "NULL".to_string(),
             },
         }
@@ -1315,7 +3923,16 @@ impl CodeGenerator {
     /// Generate a unary expression as string
     fn generate_unary_expression_string(&self, op: &UnaryOp, expr: &Expression) -> String {
         match op {
-            UnaryOp::Not => format!("!({})", self.generate_expression_string(expr)),
+            UnaryOp::Not => match expr {
+                Expression::TruthTest { expr, test } => match test {
+                    Truthiness::Option => format!("{}.is_none()", self.receiver_string(expr)),
+                    Truthiness::Pointer => format!("{}.is_null()", self.receiver_string(expr)),
+                    Truthiness::Integer => {
+                        format!("({} == 0)", self.generate_expression_string(expr))
+                    }
+                },
+                _ => format!("!({})", self.generate_expression_string(expr)),
+            },
             UnaryOp::Neg => format!("-({})", self.generate_expression_string(expr)),
             UnaryOp::Ref => format!("&({})", self.generate_expression_string(expr)),
             UnaryOp::Deref => format!("*({})", self.generate_expression_string(expr)),
@@ -1352,11 +3969,62 @@ impl CodeGenerator {
         }
     }
 
+    /// `name` as an identifier of the target language, escaped when it is a
+    /// Rust keyword
+    fn identifier<'n>(&self, name: &'n str) -> Cow<'n, str> {
+        match self.target {
+            TargetLanguage::Rust => rust_identifier(name),
+            TargetLanguage::Crusty => Cow::Borrowed(name),
+        }
+    }
+
+    /// The Rust prelude item `name`, by its full path when an item of the
+    /// file shadows it
+    fn prelude_name<'n>(&self, name: &'n str) -> Cow<'n, str> {
+        match rust_prelude_path(name) {
+            Some(path) if self.shadowed_prelude.contains(name) => Cow::Borrowed(path),
+            _ => Cow::Borrowed(name),
+        }
+    }
+
+    /// The function a call invokes; in Rust, free functions named like a
+    /// prelude function are called through `self::` so they cannot silently
+    /// resolve to the prelude's
+    fn callee_string(&self, func: &Expression) -> String {
+        let callee = self.generate_expression_string(func);
+        match func {
+            Expression::Ident(ident)
+                if self.target == TargetLanguage::Rust
+                    && self.defined_functions.contains(&ident.name)
+                    && self.shadowed_prelude.contains(&ident.name) =>
+            {
+                format!("self::{}", callee)
+            }
+            _ => callee,
+        }
+    }
+
     /// Generate a type as string
     pub fn generate_type_string(&self, ty: &Type) -> String {
         match ty {
             Type::Primitive(prim) => self.generate_primitive_type_string(prim),
-            Type::Ident(ident) => ident.name.clone(),
+            // C's long is as wide as the platform makes it
+            Type::Ident(ident) if self.target == TargetLanguage::Rust => {
+                match self.platform.c_type(&ident.name) {
+                    Some(primitive) => self.generate_primitive_type_string(&primitive),
+                    None => self.identifier(&ident.name).into_owned(),
+                }
+            }
+            Type::Ident(ident) => self.identifier(&ident.name).into_owned(),
+            // A pointer to a trait owns a value of some type implementing it
+            Type::Pointer { ty, .. } if self.is_trait(ty) => {
+                format!("Box<dyn {}>", self.generate_type_string(ty))
+            }
+            Type::Reference { ty, mutable } if self.is_trait(ty) => format!(
+                "&{}dyn {}",
+                if *mutable { "mut " } else { "" },
+                self.generate_type_string(ty)
+            ),
             Type::Pointer { ty, mutable } => {
                 // Translate pointers to raw pointers
                 if *mutable {
@@ -1410,6 +4078,7 @@ impl CodeGenerator {
                 params,
                 return_type,
             } => {
+                // The same in both languages; a void function returns nothing
                 let mut result = String::from("fn(");
                 for (i, param) in params.iter().enumerate() {
                     if i > 0 {
@@ -1417,16 +4086,27 @@ impl CodeGenerator {
                     }
                     result.push_str(&self.generate_type_string(param));
                 }
-                result.push_str(") -> ");
-                result.push_str(&self.generate_type_string(return_type));
+                result.push(')');
+                if !matches!(return_type.as_ref(), Type::Primitive(PrimitiveType::Void)) {
+                    result.push_str(" -> ");
+                    result.push_str(&self.generate_type_string(return_type));
+                }
                 result
             }
             Type::Fallible { ty } => {
                 format!(
-                    "Result<{}, Box<dyn std::error::Error>>",
-                    self.generate_type_string(ty)
+                    "{}<{}, {}<dyn std::error::Error>>",
+                    self.prelude_name("Result"),
+                    self.generate_type_string(ty),
+                    self.prelude_name("Box")
                 )
             }
+            Type::Volatile { ty } => match self.target {
+                // Rust has no volatile types; accesses through the pointer
+                // become read_volatile/write_volatile calls instead
+                TargetLanguage::Rust => self.generate_type_string(ty),
+                TargetLanguage::Crusty => format!("volatile {}", self.generate_type_string(ty)),
+            },
             Type::Auto => String::from("_"),
         }
     }
@@ -1436,10 +4116,16 @@ impl CodeGenerator {
         match self.target {
             TargetLanguage::Rust => match prim {
                 PrimitiveType::Int => "i32".to_string(),
+                PrimitiveType::I8 => "i8".to_string(),
+                PrimitiveType::I16 => "i16".to_string(),
                 PrimitiveType::I32 => "i32".to_string(),
                 PrimitiveType::I64 => "i64".to_string(),
+                PrimitiveType::Isize => "isize".to_string(),
+                PrimitiveType::U8 => "u8".to_string(),
+                PrimitiveType::U16 => "u16".to_string(),
                 PrimitiveType::U32 => "u32".to_string(),
                 PrimitiveType::U64 => "u64".to_string(),
+                PrimitiveType::Usize => "usize".to_string(),
                 PrimitiveType::Float => "f64".to_string(),
                 PrimitiveType::F32 => "f32".to_string(),
                 PrimitiveType::F64 => "f64".to_string(),
@@ -1449,10 +4135,16 @@ impl CodeGenerator {
             },
             TargetLanguage::Crusty => match prim {
                 PrimitiveType::Int => "int".to_string(),
+                PrimitiveType::I8 => "i8".to_string(),
+                PrimitiveType::I16 => "i16".to_string(),
                 PrimitiveType::I32 => "i32".to_string(),
                 PrimitiveType::I64 => "i64".to_string(),
+                PrimitiveType::Isize => "isize".to_string(),
+                PrimitiveType::U8 => "u8".to_string(),
+                PrimitiveType::U16 => "u16".to_string(),
                 PrimitiveType::U32 => "u32".to_string(),
                 PrimitiveType::U64 => "u64".to_string(),
+                PrimitiveType::Usize => "usize".to_string(),
                 PrimitiveType::Float => "float".to_string(),
                 PrimitiveType::F32 => "f32".to_string(),
                 PrimitiveType::F64 => "f64".to_string(),
@@ -1464,6 +4156,528 @@ impl CodeGenerator {
     }
 }
 
+fn is_option_type(ty: &Type) -> bool {
+    matches!(ty, Type::Generic { base, .. } if matches!(base.as_ref(), Type::Ident(ident) if ident.name == "Option"))
+}
+
+/// A trait impl as written in Crusty, `impl Shape for Pair(T)`; names the
+/// impl in messages and owns the bounds of its type parameters
+pub(crate) fn impl_header(impl_block: &Impl) -> String {
+    let ty = match &impl_block.ty {
+        Type::Generic { base, .. } if !impl_block.generics.is_empty() => {
+            let params: Vec<&str> = impl_block
+                .generics
+                .iter()
+                .map(|param| param.name.as_str())
+                .collect();
+            format!(
+                "{}({})",
+                CodeGenerator::new(TargetLanguage::Crusty).generate_type_string(base),
+                params.join(", ")
+            )
+        }
+        ty => CodeGenerator::new(TargetLanguage::Crusty).generate_type_string(ty),
+    };
+    format!("impl {} for {}", impl_block.trait_name.name, ty)
+}
+
+/// The operator applied by a compound assignment (`Add` for `+=`)
+pub(crate) fn compound_assign_base(op: &BinaryOp) -> Option<BinaryOp> {
+    Some(match op {
+        BinaryOp::AddAssign => BinaryOp::Add,
+        BinaryOp::SubAssign => BinaryOp::Sub,
+        BinaryOp::MulAssign => BinaryOp::Mul,
+        BinaryOp::DivAssign => BinaryOp::Div,
+        BinaryOp::ModAssign => BinaryOp::Mod,
+        BinaryOp::BitAndAssign => BinaryOp::BitAnd,
+        BinaryOp::BitOrAssign => BinaryOp::BitOr,
+        BinaryOp::BitXorAssign => BinaryOp::BitXor,
+        BinaryOp::ShlAssign => BinaryOp::Shl,
+        BinaryOp::ShrAssign => BinaryOp::Shr,
+        _ => return None,
+    })
+}
+
+/// `text` as a raw string literal, with as few `#` as keep it from ending
+/// early; the same in Rust and Crusty
+fn raw_string(text: &str) -> String {
+    let hashes = (0..)
+        .find(|&n| !text.contains(&format!("\"{}", "#".repeat(n))))
+        .unwrap_or_default();
+    let hashes = "#".repeat(hashes);
+    format!("r{}\"{}\"{}", hashes, text, hashes)
+}
+
+/// The element type, length and literal of a `char[N]` or `u8[N]` array
+/// initialized from a string literal, as in `char buf[8] = "abc";`
+fn string_array_init<'a>(
+    ty: &'a Type,
+    init: &'a Expression,
+) -> Option<(&'a Type, usize, &'a Literal)> {
+    let Type::Array {
+        ty: elem,
+        size: Some(size),
+    } = ty
+    else {
+        return None;
+    };
+    let Expression::Literal(lit) = init else {
+        return None;
+    };
+    match (elem.as_ref(), lit) {
+        (Type::Primitive(PrimitiveType::Char), Literal::String(_)) => Some((elem, *size, lit)),
+        (Type::Primitive(PrimitiveType::U8), Literal::String(_) | Literal::ByteString(_)) => {
+            Some((elem, *size, lit))
+        }
+        _ => None,
+    }
+}
+
+/// Whether a local is a character array initialized from a string literal
+fn is_string_array_local(ty: &Option<Type>, init: &Option<Expression>) -> bool {
+    matches!((ty, init), (Some(ty), Some(init)) if string_array_init(ty, init).is_some())
+}
+
+/// Rust initializer for a character array filled from a string literal,
+/// zero-padded to `size` elements like C does: a byte string for `u8`
+/// arrays, an array of `char`s otherwise
+fn string_array_initializer(elem: &Type, size: usize, lit: &Literal) -> String {
+    let bytes = match lit {
+        Literal::String(s) if matches!(elem, Type::Primitive(PrimitiveType::Char)) => {
+            let padding = size.saturating_sub(s.chars().count());
+            let chars: Vec<String> = s
+                .chars()
+                .chain(std::iter::repeat_n('\0', padding))
+                .map(|c| format!("'{}'", c.escape_default()))
+                .collect();
+            return format!("[{}]", chars.join(", "));
+        }
+        Literal::String(s) => s.as_bytes(),
+        Literal::ByteString(bytes) => bytes.as_slice(),
+        _ => unreachable!("string_array_init only accepts string literals"),
+    };
+    let mut padded = bytes.to_vec();
+    padded.resize(size.max(bytes.len()), 0);
+    format!("*b\"{}\"", padded.escape_ascii())
+}
+
+/// Element type and length of an array, slice, or reference to one
+fn array_type(ty: &Type) -> Option<(&Type, Option<usize>)> {
+    match ty {
+        Type::Array { ty, size } => Some((ty, *size)),
+        Type::Slice { ty } => Some((ty, None)),
+        Type::Reference { ty, .. } => array_type(ty),
+        _ => None,
+    }
+}
+
+/// Whether `ty` points to a volatile value, as in `volatile u32*`
+fn is_volatile_pointer(ty: &Type) -> bool {
+    matches!(ty, Type::Pointer { ty, .. } if matches!(ty.as_ref(), Type::Volatile { .. }))
+}
+
+/// Whether `block` holds a `jump` statement targeting the loop `block` is the
+/// body of
+fn has_loop_jump(block: &Block, jump: fn(&Statement) -> bool) -> bool {
+    block.statements.iter().any(|stmt| match stmt {
+        stmt if jump(stmt) => true,
+        stmt if is_loop(stmt) => false,
+        stmt => child_blocks(stmt)
+            .into_iter()
+            .any(|child| has_loop_jump(child, jump)),
+    })
+}
+
+/// Aim the bare `break` and `continue` statements of a loop body at labels:
+/// `break` at the loop, `continue` at the labeled block wrapping the body
+fn label_loop_jumps(block: &mut Block, loop_label: &Ident, body_label: &Ident) {
+    for stmt in &mut block.statements {
+        match stmt {
+            Statement::Break(None) => *stmt = Statement::Break(Some(loop_label.clone())),
+            Statement::Continue(None) => *stmt = Statement::Break(Some(body_label.clone())),
+            stmt if is_loop(stmt) => {}
+            stmt => {
+                for child in child_blocks_mut(stmt) {
+                    label_loop_jumps(child, loop_label, body_label);
+                }
+            }
+        }
+    }
+}
+
+/// Rust keywords a Crusty identifier may spell, written as raw identifiers
+/// (`r#type`) in generated Rust
+///
+/// `self`, `Self`, `super` and `crate` cannot be raw identifiers and are left
+/// as they are, keeping their meaning in Rust paths.
+const RUST_KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let",
+    "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return",
+    "static", "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use",
+    "virtual", "where", "while", "yield",
+];
+
+/// `name` as a Rust identifier: `r#name` when it is a Rust keyword
+pub fn rust_identifier(name: &str) -> Cow<'_, str> {
+    if RUST_KEYWORDS.contains(&name) {
+        Cow::Owned(format!("r#{}", name))
+    } else {
+        Cow::Borrowed(name)
+    }
+}
+
+/// Items of the Rust prelude, and of the `Option` and `Result` variants it
+/// brings into scope, by their full paths
+const RUST_PRELUDE: &[(&str, &str)] = &[
+    ("AsMut", "std::convert::AsMut"),
+    ("AsRef", "std::convert::AsRef"),
+    ("Box", "std::boxed::Box"),
+    ("Clone", "std::clone::Clone"),
+    ("Copy", "std::marker::Copy"),
+    ("Default", "std::default::Default"),
+    ("DoubleEndedIterator", "std::iter::DoubleEndedIterator"),
+    ("Drop", "std::ops::Drop"),
+    ("Eq", "std::cmp::Eq"),
+    ("Err", "std::result::Result::Err"),
+    ("ExactSizeIterator", "std::iter::ExactSizeIterator"),
+    ("Extend", "std::iter::Extend"),
+    ("Fn", "std::ops::Fn"),
+    ("FnMut", "std::ops::FnMut"),
+    ("FnOnce", "std::ops::FnOnce"),
+    ("From", "std::convert::From"),
+    ("FromIterator", "std::iter::FromIterator"),
+    ("Into", "std::convert::Into"),
+    ("IntoIterator", "std::iter::IntoIterator"),
+    ("Iterator", "std::iter::Iterator"),
+    ("None", "std::option::Option::None"),
+    ("Ok", "std::result::Result::Ok"),
+    ("Option", "std::option::Option"),
+    ("Ord", "std::cmp::Ord"),
+    ("PartialEq", "std::cmp::PartialEq"),
+    ("PartialOrd", "std::cmp::PartialOrd"),
+    ("Result", "std::result::Result"),
+    ("Send", "std::marker::Send"),
+    ("Sized", "std::marker::Sized"),
+    ("Some", "std::option::Option::Some"),
+    ("String", "std::string::String"),
+    ("Sync", "std::marker::Sync"),
+    ("ToOwned", "std::borrow::ToOwned"),
+    ("ToString", "std::string::ToString"),
+    ("TryFrom", "std::convert::TryFrom"),
+    ("TryInto", "std::convert::TryInto"),
+    ("Unpin", "std::marker::Unpin"),
+    ("Vec", "std::vec::Vec"),
+    ("align_of", "std::mem::align_of"),
+    ("align_of_val", "std::mem::align_of_val"),
+    ("drop", "std::mem::drop"),
+    ("size_of", "std::mem::size_of"),
+    ("size_of_val", "std::mem::size_of_val"),
+];
+
+/// Full path of the Rust prelude item named `name`, if there is one
+pub fn rust_prelude_path(name: &str) -> Option<&'static str> {
+    RUST_PRELUDE
+        .binary_search_by(|(item, _)| (*item).cmp(name))
+        .ok()
+        .map(|index| RUST_PRELUDE[index].1)
+}
+
+/// The Rust bound for a trait a type parameter `param` needs; the traits of
+/// value-producing operators name their output, `std::ops::Add<Output = T>`
+fn bound_string(trait_name: &str, param: &str) -> String {
+    match trait_name {
+        "Copy" | "PartialEq" | "PartialOrd" => trait_name.to_string(),
+        _ if trait_name.ends_with("Assign") => format!("std::ops::{}", trait_name),
+        _ => format!("std::ops::{}<Output = {}>", trait_name, param),
+    }
+}
+
+/// Whether `block` modifies a place reached through `var`, such as
+/// `var.x = 1` or `var.count++`, which needs `var` to borrow mutably
+/// Whether `statements` assign the variable `var`, or a field or element
+/// of it
+fn assigns(statements: &[Statement], var: &str) -> bool {
+    statements.iter().any(|stmt| {
+        statement_expressions(stmt)
+            .into_iter()
+            .any(|expr| expression_assigns(expr, var))
+            || child_blocks(stmt)
+                .into_iter()
+                .any(|child| assigns(&child.statements, var))
+    })
+}
+
+fn expression_assigns(expr: &Expression, var: &str) -> bool {
+    assignment_target(expr).is_some_and(|target| place_root(target) == Some(var))
+        || subexpressions(expr)
+            .into_iter()
+            .any(|sub| expression_assigns(sub, var))
+}
+
+/// The place `expr` stores into, when it assigns, increments or decrements
+fn assignment_target(expr: &Expression) -> Option<&Expression> {
+    match expr {
+        Expression::Binary { op, left, .. }
+            if *op == BinaryOp::Assign || compound_assign_base(op).is_some() =>
+        {
+            Some(left)
+        }
+        Expression::Unary {
+            op: UnaryOp::PreInc | UnaryOp::PreDec | UnaryOp::PostInc | UnaryOp::PostDec,
+            expr,
+        } => Some(expr),
+        _ => None,
+    }
+}
+
+/// Whether evaluating `expr` twice gives the same value and changes nothing,
+/// as for `a`, `s.items` or `m[i + 1]`
+fn evaluates_alike(expr: &Expression) -> bool {
+    match expr {
+        Expression::Literal(_) | Expression::Ident(_) => true,
+        Expression::FieldAccess { expr, .. }
+        | Expression::Cast { expr, .. }
+        | Expression::Unary {
+            op: UnaryOp::Deref | UnaryOp::Neg | UnaryOp::Not,
+            expr,
+        } => evaluates_alike(expr),
+        Expression::Index { expr, index } => evaluates_alike(expr) && evaluates_alike(index),
+        Expression::Binary { op, left, right } => {
+            !op.is_assignment() && evaluates_alike(left) && evaluates_alike(right)
+        }
+        _ => false,
+    }
+}
+
+/// Name of the variable a place expression (`a`, `a.f`, `a[i]`, `*a`)
+/// belongs to
+fn place_root(expr: &Expression) -> Option<&str> {
+    match expr {
+        Expression::Ident(ident) => Some(&ident.name),
+        Expression::FieldAccess { expr, .. } | Expression::Index { expr, .. } => place_root(expr),
+        Expression::Unary {
+            op: UnaryOp::Deref,
+            expr,
+        } => place_root(expr),
+        _ => None,
+    }
+}
+
+/// Expressions `stmt` holds directly, outside its nested blocks
+pub(crate) fn statement_expressions(stmt: &Statement) -> Vec<&Expression> {
+    match stmt {
+        Statement::Let { init, .. } | Statement::Var { init, .. } => init.iter().collect(),
+        Statement::Const { value, .. } => vec![value],
+        Statement::Expr(expr) => vec![expr],
+        Statement::Return(expr) => expr.iter().collect(),
+        Statement::If { condition, .. }
+        | Statement::While { condition, .. }
+        | Statement::DoWhile { condition, .. } => vec![condition],
+        Statement::For {
+            init,
+            condition,
+            increment,
+            ..
+        } => {
+            let mut exprs = statement_expressions(init);
+            exprs.extend([condition, increment]);
+            exprs
+        }
+        Statement::ForIn { iter, .. } => vec![iter],
+        Statement::Switch { expr, cases, .. } => std::iter::once(expr)
+            .chain(cases.iter().flat_map(|case| &case.values))
+            .collect(),
+        Statement::Break(_)
+        | Statement::Continue(_)
+        | Statement::Unsafe(_)
+        | Statement::Block(_)
+        | Statement::StaticAssert(_)
+        | Statement::NestedFunction { .. } => Vec::new(),
+    }
+}
+
+/// Operands and other expressions nested directly in `expr`
+fn subexpressions(expr: &Expression) -> Vec<&Expression> {
+    match expr {
+        Expression::Binary { left, right, .. } | Expression::Comma { left, right } => {
+            vec![left, right]
+        }
+        Expression::Unary { expr, .. }
+        | Expression::FieldAccess { expr, .. }
+        | Expression::Cast { expr, .. }
+        | Expression::SizeofExpr { expr }
+        | Expression::AlignofExpr { expr }
+        | Expression::TruthTest { expr, .. }
+        | Expression::CheckedCast { expr, .. }
+        | Expression::ErrorProp { expr } => vec![expr],
+        Expression::Index { expr, index } => vec![expr, index],
+        Expression::Call { func, args } => std::iter::once(func.as_ref()).chain(args).collect(),
+        Expression::MethodCall { receiver, args, .. } => {
+            std::iter::once(receiver.as_ref()).chain(args).collect()
+        }
+        Expression::TypeScopedCall { args, .. } | Expression::ExplicitGenericCall { args, .. } => {
+            args.iter().collect()
+        }
+        Expression::Ternary {
+            condition,
+            then_expr,
+            else_expr,
+        } => vec![condition, then_expr, else_expr],
+        Expression::StructInit { fields, .. } => fields.iter().map(|(_, value)| value).collect(),
+        Expression::ArrayLit { elements } | Expression::TupleLit { elements } => {
+            elements.iter().collect()
+        }
+        Expression::Range { start, end, .. } => start
+            .iter()
+            .chain(end)
+            .map(|bound| bound.as_ref())
+            .collect(),
+        Expression::Match { expr, arms } => std::iter::once(expr.as_ref())
+            .chain(arms.iter().map(|arm| &arm.body))
+            .collect(),
+        Expression::Literal(_)
+        | Expression::Ident(_)
+        | Expression::Sizeof { .. }
+        | Expression::Alignof { .. }
+        | Expression::MacroCall { .. }
+        | Expression::RustBlock { .. }
+        | Expression::Lambda { .. } => Vec::new(),
+    }
+}
+
+/// Whether `stmt` is a loop, which bare `break` and `continue` inside it target
+fn is_loop(stmt: &Statement) -> bool {
+    matches!(
+        stmt,
+        Statement::While { .. }
+            | Statement::DoWhile { .. }
+            | Statement::For { .. }
+            | Statement::ForIn { .. }
+    )
+}
+
+/// Whether `block` declares names that would leak if its statements were
+/// hoisted into the enclosing block
+fn declares_locals(block: &Block) -> bool {
+    block.statements.iter().any(|stmt| {
+        matches!(
+            stmt,
+            Statement::Let { .. }
+                | Statement::Var { .. }
+                | Statement::Const { .. }
+                | Statement::NestedFunction { .. }
+        )
+    })
+}
+
+/// Declared name of a top-level item, if it has one
+pub(crate) fn item_name(item: &Item) -> Option<&str> {
+    item_ident(item).map(|name| name.name.as_str())
+}
+
+/// Declared name of a top-level item with its span, if it has one
+pub(crate) fn item_ident(item: &Item) -> Option<&Ident> {
+    match item {
+        Item::Function(func) => Some(&func.name),
+        Item::Struct(struct_def) => Some(&struct_def.name),
+        Item::Enum(enum_def) => Some(&enum_def.name),
+        Item::Trait(trait_def) => Some(&trait_def.name),
+        Item::Typedef(typedef) => Some(&typedef.name),
+        Item::Namespace(namespace) => Some(&namespace.name),
+        Item::Const(const_item) => Some(&const_item.name),
+        Item::Static(static_item) => Some(&static_item.name),
+        Item::MacroDefinition(macro_def) => Some(&macro_def.name),
+        Item::Impl(_)
+        | Item::Import(_)
+        | Item::Export(_)
+        | Item::Extern(_)
+        | Item::StaticAssert(_) => None,
+    }
+}
+
+/// Output section of a top-level item, in emission order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum ItemSection {
+    Imports,
+    Macros,
+    Types,
+    Constants,
+    Foreign,
+    Functions,
+    Modules,
+}
+
+impl ItemSection {
+    fn title(self) -> &'static str {
+        match self {
+            ItemSection::Imports => "Imports",
+            ItemSection::Macros => "Macros",
+            ItemSection::Types => "Types",
+            ItemSection::Constants => "Constants",
+            ItemSection::Foreign => "Foreign items",
+            ItemSection::Functions => "Functions",
+            ItemSection::Modules => "Modules",
+        }
+    }
+}
+
+/// Section an item belongs to when output is grouped
+fn item_section(item: &Item) -> ItemSection {
+    match item {
+        Item::Import(_) | Item::Export(_) => ItemSection::Imports,
+        Item::MacroDefinition(_) => ItemSection::Macros,
+        Item::Struct(_) | Item::Enum(_) | Item::Trait(_) | Item::Impl(_) | Item::Typedef(_) => {
+            ItemSection::Types
+        }
+        Item::Const(_) | Item::Static(_) | Item::StaticAssert(_) => ItemSection::Constants,
+        Item::Extern(_) => ItemSection::Foreign,
+        Item::Function(_) => ItemSection::Functions,
+        Item::Namespace(_) => ItemSection::Modules,
+    }
+}
+
+/// Opening and closing text for a macro delimiter style
+fn macro_delimiter_pair(delimiter: &MacroDelimiter) -> (&'static str, &'static str) {
+    match delimiter {
+        MacroDelimiter::None => ("", ""),
+        MacroDelimiter::Parens => ("(", ")"),
+        MacroDelimiter::Brackets => ("[", "]"),
+        MacroDelimiter::Braces => ("{", "}"),
+    }
+}
+
+/// Join raw token texts back into source, spacing them the way a person would
+/// write them (no space inside brackets, before separators, or around `.`)
+pub(crate) fn join_token_texts<'t>(texts: impl Iterator<Item = &'t str>) -> String {
+    let mut result = String::new();
+    let mut prev: Option<&str> = None;
+    for text in texts {
+        if let Some(prev) = prev {
+            let tight_after = matches!(prev, "(" | "[" | "." | "!" | "::" | "#");
+            let tight_before = matches!(text, ")" | "]" | "," | ";" | "." | "::")
+                || (matches!(text, "(" | "[")
+                    && !is_operator_text(prev)
+                    && !matches!(prev, "if" | "while" | "for" | "switch" | "return"));
+            if !tight_after && !tight_before {
+                result.push(' ');
+            }
+        }
+        result.push_str(text);
+        prev = Some(text);
+    }
+    result
+}
+
+/// Check if token text is a punctuation operator (after which `(` starts a group)
+fn is_operator_text(text: &str) -> bool {
+    !text.is_empty()
+        && text
+            .chars()
+            .all(|c| !c.is_alphanumeric() && c != '_' && c != '"' && c != '\'')
+        && !matches!(text, ")" | "]" | "}")
+}
+
 /// Check if a string is a Rust keyword
 fn is_rust_keyword(s: &str) -> bool {
     matches!(
@@ -1582,6 +4796,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("main"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::empty(),
@@ -1604,6 +4819,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("foo"),
+            generics: vec![],
             params: vec![],
             return_type: Some(Type::Primitive(PrimitiveType::Void)),
             body: Block::empty(),
@@ -1626,14 +4842,17 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("add"),
+            generics: vec![],
             params: vec![
                 Param {
                     name: Ident::new("a"),
                     ty: Type::Primitive(PrimitiveType::I32),
+                    variadic: false,
                 },
                 Param {
                     name: Ident::new("b"),
                     ty: Type::Primitive(PrimitiveType::I32),
+                    variadic: false,
                 },
             ],
             return_type: Some(Type::Primitive(PrimitiveType::I32)),
@@ -1655,6 +4874,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Private,
             name: Ident::new("helper"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::empty(),
@@ -1682,6 +4902,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![stmt]),
@@ -1707,6 +4928,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![stmt]),
@@ -1732,6 +4954,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![stmt]),
@@ -1757,6 +4980,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![stmt]),
@@ -1768,7 +4992,7 @@ mod tests {
             doc_comments: vec![],
         };
         let output = gen.generate(&file);
-        assert!(output.contains("while true"));
+        assert!(output.contains("loop {"));
     }
 
     #[test]
@@ -1782,6 +5006,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![stmt]),
@@ -1793,7 +5018,619 @@ mod tests {
             doc_comments: vec![],
         };
         let output = gen.generate(&file);
-        assert!(output.contains("'outer: while true"));
+        assert!(output.contains("'outer: loop {"));
+    }
+
+    #[test]
+    fn test_generate_do_while() {
+        let source = "int count(int n) {\n    var int i = 0;\n    do {\n        i = i + 1;\n    } while (i < n);\n    do {\n        i = i - 1;\n        if (i == 5) {\n            continue;\n        }\n        if (i < 0) {\n            break;\n        }\n    } while (i > 0);\n    return i;\n}\n";
+        let file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+
+        let rust = CodeGenerator::new(TargetLanguage::Rust).generate(&file);
+        assert!(
+            rust.contains(
+                "    loop {\n        (i = (i + 1));\n        if !((i < n)) { break; }\n    }\n"
+            ),
+            "{}",
+            rust
+        );
+        // `continue` still tests the condition, so it leaves a labeled block
+        assert!(
+            rust.contains("    'do_while: loop {\n        'do_continue: {\n"),
+            "{}",
+            rust
+        );
+        assert!(rust.contains("break 'do_continue;"), "{}", rust);
+        assert!(rust.contains("break 'do_while;"), "{}", rust);
+        assert!(
+            rust.contains("        }\n        if !((i > 0)) { break; }\n    }\n"),
+            "{}",
+            rust
+        );
+
+        let crusty = CodeGenerator::new(TargetLanguage::Crusty).generate(&file);
+        assert!(crusty.contains("} while ((i < n));\n"), "{}", crusty);
+        assert!(crusty.contains("        continue;\n"), "{}", crusty);
+    }
+
+    #[test]
+    fn test_generate_match_expression() {
+        let source = "int f(Color c, Option<int> o, int n) {\n    let a = match (c) { Color.Red => 1, Color::Green => 2, _ => 3 };\n    let b = match (o) { Option.Some(x) => x, Option.None => 0 };\n    return match ((n, a)) { (0, _) => b, (m, _) => m, };\n}\n";
+        let file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+
+        let rust = CodeGenerator::new(TargetLanguage::Rust).generate(&file);
+        assert!(
+            rust.contains("let a = match c { Color::Red => 1, Color::Green => 2, _ => 3 };"),
+            "{}",
+            rust
+        );
+        assert!(
+            rust.contains("let b = match o { Some(x) => x, None => 0 };"),
+            "{}",
+            rust
+        );
+        assert!(
+            rust.contains("return match (n, a) { (0, _) => b, (m, _) => m };"),
+            "{}",
+            rust
+        );
+
+        let crusty = CodeGenerator::new(TargetLanguage::Crusty).generate(&file);
+        assert!(
+            crusty.contains("match (o) { Option.Some(x) => x, Option.None => 0 }"),
+            "{}",
+            crusty
+        );
+        assert!(
+            crusty.contains("match (c) { Color.Red => 1, Color.Green => 2, _ => 3 }"),
+            "{}",
+            crusty
+        );
+    }
+
+    #[test]
+    fn test_generate_for_in_over_struct_array() {
+        let source = "struct Point { int x; int y; }\nint f() {\n    var Point pts[2] = [(Point){ .x = 1, .y = 2 }, (Point){ .x = 3, .y = 4 }];\n    var int total = 0;\n    for (p in pts) {\n        p.x = p.x + 1;\n    }\n    for (p in pts) {\n        total = total + p.x;\n    }\n    return total;\n}\n";
+        let file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+        let mut analyzer = crate::semantic::SemanticAnalyzer::new();
+        analyzer.analyze(&file).unwrap();
+
+        let hir = crate::hir::lower(&file, &analyzer.analysis());
+        let rust = CodeGenerator::new(TargetLanguage::Rust).generate_hir(&hir);
+        assert!(rust.contains("for p in pts.iter_mut() {"), "{}", rust);
+        assert!(rust.contains("for p in pts.iter() {"), "{}", rust);
+
+        let crusty = CodeGenerator::new(TargetLanguage::Crusty).generate(&file);
+        assert!(!crusty.contains(".iter"), "{}", crusty);
+    }
+
+    #[test]
+    fn test_generate_sizeof_of_expressions() {
+        let source = "struct Header { u32 magic; u16 flags; }\nusize f(Header h) {\n    let i64 big = 2;\n    let int a[4] = [1, 2, 3, 4];\n    return sizeof(big) + sizeof(a[0] + 1) + sizeof(h.flags) + alignof(big) + alignof(Header);\n}\n";
+        let file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+        let mut analyzer = crate::semantic::SemanticAnalyzer::new();
+        analyzer.analyze(&file).unwrap();
+
+        let hir = crate::hir::lower(&file, &analyzer.analysis());
+        let rust = CodeGenerator::new(TargetLanguage::Rust).generate_hir(&hir);
+        assert!(
+            rust.contains("return ((((std::mem::size_of::<i64>() + std::mem::size_of::<i32>()) + std::mem::size_of::<u16>()) + std::mem::align_of::<i64>()) + std::mem::align_of::<Header>());"),
+            "{}",
+            rust
+        );
+
+        // Without the types from semantic analysis, Rust measures the values
+        let rust = CodeGenerator::new(TargetLanguage::Rust).generate(&file);
+        assert!(rust.contains("std::mem::size_of_val(&h.flags)"), "{}", rust);
+        let crusty = CodeGenerator::new(TargetLanguage::Crusty).generate(&file);
+        assert!(crusty.contains("sizeof(h.flags)"), "{}", crusty);
+        assert!(crusty.contains("alignof(Header)"), "{}", crusty);
+    }
+
+    #[test]
+    fn test_generate_tagged_union() {
+        let source = "enum Shape { Circle(float radius), Rect(float w, float h), Empty }\nfloat area(Shape s) {\n    let Shape c = Shape.Circle(2.0);\n    return match (s) { Shape.Circle(r) => r, Shape.Rect(w, h) => w * h, Shape.Empty => 0.0 };\n}\n";
+        let file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+
+        let rust = CodeGenerator::new(TargetLanguage::Rust).generate(&file);
+        assert!(
+            rust.contains("pub enum Shape {\n    Circle(f64),\n    Rect(f64, f64),\n    Empty,\n}"),
+            "{}",
+            rust
+        );
+        assert!(
+            rust.contains("let c: Shape = Shape::Circle(2.0);"),
+            "{}",
+            rust
+        );
+        assert!(
+            rust.contains("Shape::Rect(w, h) => (w * h), Shape::Empty => 0.0"),
+            "{}",
+            rust
+        );
+
+        let crusty = CodeGenerator::new(TargetLanguage::Crusty).generate(&file);
+        assert!(
+            crusty.contains("    Circle(float radius),\n    Rect(float w, float h),\n    Empty,\n"),
+            "{}",
+            crusty
+        );
+    }
+
+    #[test]
+    fn test_generate_trait_and_impl() {
+        let source = "trait Shape {\n    float area(&self);\n    void scale(var &self, float k);\n}\nstruct Circle {\n    float r;\n}\nimpl Shape for Circle {\n    float area(&self) {\n        return self.r;\n    }\n    void scale(var &self, float k) {\n        self.r = self.r * k;\n    }\n}\n";
+        let file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+
+        let rust = CodeGenerator::new(TargetLanguage::Rust).generate(&file);
+        assert!(
+            rust.contains("pub trait Shape {\n    fn area(&self) -> f64;\n    fn scale(&mut self, k: f64);\n}"),
+            "{}",
+            rust
+        );
+        assert!(
+            rust.contains("impl Shape for Circle {\n    fn area(&self) -> f64 {\n"),
+            "{}",
+            rust
+        );
+        assert!(
+            rust.contains("    fn scale(&mut self, k: f64) {\n"),
+            "{}",
+            rust
+        );
+
+        let crusty = CodeGenerator::new(TargetLanguage::Crusty).generate(&file);
+        assert!(
+            crusty.contains(
+                "trait Shape {\n    float area(&self);\n    void scale(&mut self, float k);\n}"
+            ),
+            "{}",
+            crusty
+        );
+        assert!(
+            crusty.contains("impl Shape for Circle {\n    float area(&self) {\n"),
+            "{}",
+            crusty
+        );
+    }
+
+    #[test]
+    fn test_generate_generic_trait_impl() {
+        let source = "trait Ordered {\n    bool ordered(&self);\n}\nstruct Pair(T) {\n    T first;\n    T second;\n}\nimpl Ordered for Pair(T) {\n    bool ordered(&self) {\n        return self.first < self.second;\n    }\n}\n";
+        let file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+        let mut analyzer = crate::semantic::SemanticAnalyzer::new();
+        analyzer.analyze(&file).unwrap();
+
+        let mut generator = CodeGenerator::new(TargetLanguage::Rust);
+        generator.set_generic_bounds(analyzer.generic_bounds().clone());
+        let rust = generator.generate(&file);
+        assert!(
+            rust.contains("impl<T: PartialOrd> Ordered for Pair<T> {\n"),
+            "{}",
+            rust
+        );
+
+        let crusty = CodeGenerator::new(TargetLanguage::Crusty).generate(&file);
+        assert!(
+            crusty.contains("impl Ordered for Pair(T) {\n"),
+            "{}",
+            crusty
+        );
+    }
+
+    #[test]
+    fn test_generate_trait_object_types() {
+        let source = "trait Shape {\n    float area(&self);\n}\nfloat show(&Shape s) {\n    return s.area();\n}\nvoid grow(var &Shape s) {\n}\nShape* keep(Shape* s) {\n    return s;\n}\n";
+        let file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+
+        let rust = CodeGenerator::new(TargetLanguage::Rust).generate(&file);
+        assert!(
+            rust.contains("pub fn show(s: &dyn Shape) -> f64 {"),
+            "{}",
+            rust
+        );
+        assert!(
+            rust.contains("pub fn grow(s: &mut dyn Shape) {"),
+            "{}",
+            rust
+        );
+        assert!(
+            rust.contains("pub fn keep(s: Box<dyn Shape>) -> Box<dyn Shape> {"),
+            "{}",
+            rust
+        );
+    }
+
+    #[test]
+    fn test_generate_trait_default_method() {
+        let source = "trait Shape {\n    float area(&self);\n    float twice(&self) {\n        return self.area() * 2.0;\n    }\n}\n";
+        let file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+
+        let rust = CodeGenerator::new(TargetLanguage::Rust).generate(&file);
+        assert!(
+            rust.contains("pub trait Shape {\n    fn area(&self) -> f64;\n    fn twice(&self) -> f64 {\n        return (self.area() * 2.0);\n    }\n}"),
+            "{}",
+            rust
+        );
+
+        let crusty = CodeGenerator::new(TargetLanguage::Crusty).generate(&file);
+        assert!(
+            crusty.contains(
+                "    float twice(&self) {\n        return (self.area() * 2.0);\n    }\n}"
+            ),
+            "{}",
+            crusty
+        );
+    }
+
+    #[test]
+    fn test_generate_generic_function() {
+        let source = "T square(T)(T x) {\n    return x * x;\n}\nK first(K, V)(K key, V value) {\n    return key;\n}\n";
+        let file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+        let mut analyzer = crate::semantic::SemanticAnalyzer::new();
+        analyzer.analyze(&file).unwrap();
+
+        let mut generator = CodeGenerator::new(TargetLanguage::Rust);
+        generator.set_generic_bounds(analyzer.generic_bounds().clone());
+        let rust = generator.generate(&file);
+        assert!(
+            rust.contains("pub fn square<T: Copy + std::ops::Mul<Output = T>>(x: T) -> T {"),
+            "{}",
+            rust
+        );
+        assert!(
+            rust.contains("pub fn first<K, V>(key: K, value: V) -> K {"),
+            "{}",
+            rust
+        );
+
+        let crusty = CodeGenerator::new(TargetLanguage::Crusty).generate(&file);
+        assert!(crusty.contains("T square(T)(T x) {"), "{}", crusty);
+        assert!(
+            crusty.contains("K first(K, V)(K key, V value) {"),
+            "{}",
+            crusty
+        );
+    }
+
+    #[test]
+    fn test_generate_generic_struct() {
+        let source = "struct Pair(T) {\n    T first;\n    T second;\n    T larger(self) {\n        if (self.first > self.second) {\n            return self.first;\n        }\n        return self.second;\n    }\n}\nvoid main() {\n    let p = (Pair<int>){ .first = 3, .second = 7 };\n}\n";
+        let file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+        let mut analyzer = crate::semantic::SemanticAnalyzer::new();
+        analyzer.analyze(&file).unwrap();
+
+        let mut generator = CodeGenerator::new(TargetLanguage::Rust);
+        generator.set_generic_bounds(analyzer.generic_bounds().clone());
+        let rust = generator.generate(&file);
+        assert!(rust.contains("pub struct Pair<T> {"), "{}", rust);
+        assert!(rust.contains("impl<T: PartialOrd> Pair<T> {"), "{}", rust);
+        assert!(
+            rust.contains("let p = Pair::<i32> { first: 3, second: 7 };"),
+            "{}",
+            rust
+        );
+
+        let crusty = CodeGenerator::new(TargetLanguage::Crusty).generate(&file);
+        assert!(crusty.contains("struct Pair(T) {"), "{}", crusty);
+        assert!(
+            crusty.contains("let p = (Pair<int>){ .first = 3, .second = 7 };"),
+            "{}",
+            crusty
+        );
+    }
+
+    #[test]
+    fn test_generate_escapes_rust_keywords() {
+        let source = "struct Token {\n    int type;\n}\nenum Mode { copy, ref }\nint use(Token t, int fn) {\n    var int ref = t.type + fn;\n    for (dyn in [1, 2]) {\n        ref = ref + dyn;\n    }\n    let Token copy = (Token){ .type = ref };\n    let m = Mode.ref;\n    return copy.type;\n}\n";
+        let file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+
+        let rust = CodeGenerator::new(TargetLanguage::Rust).generate(&file);
+        for expected in [
+            "    pub r#type: i32,",
+            "    copy = 0,\n    r#ref = 1,",
+            "pub fn r#use(t: Token, r#fn: i32) -> i32 {",
+            "let mut r#ref: i32 = (t.r#type + r#fn);",
+            "for r#dyn in [1, 2] {",
+            "Token { r#type: r#ref }",
+            "let m = Mode::r#ref;",
+            "return copy.r#type;",
+        ] {
+            assert!(rust.contains(expected), "{}\n{}", expected, rust);
+        }
+
+        let crusty = CodeGenerator::new(TargetLanguage::Crusty).generate(&file);
+        assert!(!crusty.contains("r#"), "{}", crusty);
+        assert!(crusty.contains("int use(Token t, int fn) {"), "{}", crusty);
+    }
+
+    #[test]
+    fn test_generate_qualifies_shadowed_prelude_names() {
+        let source = "struct Option {\n    int tag;\n}\nvoid drop(int x) {}\nvoid main() {\n    drop(1);\n    let p = NULL;\n}\n";
+        let file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+
+        let rust = CodeGenerator::new(TargetLanguage::Rust).generate(&file);
+        assert!(rust.contains("self::drop(1);"), "{}", rust);
+        assert!(
+            rust.contains("let p = std::option::Option::None;"),
+            "{}",
+            rust
+        );
+
+        let crusty = CodeGenerator::new(TargetLanguage::Crusty).generate(&file);
+        assert!(crusty.contains("    drop(1);"), "{}", crusty);
+        assert!(crusty.contains("NULL"), "{}", crusty);
+        assert!(RUST_PRELUDE.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert_eq!(rust_prelude_path("String"), Some("std::string::String"));
+        assert_eq!(rust_prelude_path("Point"), None);
+    }
+
+    #[test]
+    fn test_generate_entry_points() {
+        let source = "u64 start() {\n    return 3;\n}\n";
+        let file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+
+        let mut generator = CodeGenerator::new(TargetLanguage::Rust);
+        generator.set_entry(Some("start".to_string()));
+        let hosted = generator.generate(&file);
+        assert!(
+            hosted.ends_with("fn main() {\n    std::process::exit(start() as i32);\n}\n"),
+            "{}",
+            hosted
+        );
+
+        generator.set_freestanding(true);
+        let freestanding = generator.generate(&file);
+        assert!(
+            freestanding.starts_with("#![no_std]\n#![no_main]\n\n"),
+            "{}",
+            freestanding
+        );
+        assert!(
+            freestanding.contains(
+                "#[export_name = \"main\"]\npub extern \"C\" fn __crusty_entry() -> i32 {\n    start() as i32\n}\n"
+            ),
+            "{}",
+            freestanding
+        );
+        assert!(
+            freestanding.contains("#[panic_handler]"),
+            "{}",
+            freestanding
+        );
+        assert!(!freestanding.contains("fn main"), "{}", freestanding);
+
+        // Without --entry, only a freestanding program gets a generated entry
+        let main = crate::parser::Parser::new("void main() {}\n")
+            .unwrap()
+            .parse_file()
+            .unwrap();
+        let plain = CodeGenerator::new(TargetLanguage::Rust).generate(&main);
+        assert_eq!(plain, "pub fn main() {\n}\n");
+        generator.set_entry(None);
+        assert!(generator
+            .generate(&main)
+            .contains("fn __crusty_entry() -> i32 {\n    main();\n    0\n}"));
+    }
+
+    #[test]
+    fn test_generate_bench_harness() {
+        let source = "#[bench]\nint fib_20() {\n    return 6765;\n}\nvoid helper() {}\n#[bench]\nvoid type() {}\n";
+        let file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+
+        let plain = CodeGenerator::new(TargetLanguage::Rust).generate(&file);
+        assert!(!plain.contains("#[bench]"), "{}", plain);
+        assert!(!plain.contains("fn main"), "{}", plain);
+
+        let mut generator = CodeGenerator::new(TargetLanguage::Rust);
+        generator.set_bench(true);
+        let harness = generator.generate(&file);
+        assert!(!harness.contains("#[bench]"), "{}", harness);
+        assert!(
+            harness.contains("fn main() {\n    __crusty_bench(\"fib_20\", fib_20);\n    __crusty_bench(\"type\", r#type);\n}\n"),
+            "{}",
+            harness
+        );
+        assert!(
+            harness.contains("fn __crusty_bench<T>(name: &str, mut f: impl FnMut() -> T) {"),
+            "{}",
+            harness
+        );
+        assert!(
+            harness.contains("if elapsed.as_millis() >= 100 || iterations == 1 << 30 {"),
+            "{}",
+            harness
+        );
+
+        let crusty = CodeGenerator::new(TargetLanguage::Crusty).generate(&file);
+        assert!(crusty.contains("#[bench]\nint fib_20() {"), "{}", crusty);
+    }
+
+    #[test]
+    fn test_generate_track_caller() {
+        let source = "#[inline]\nint half(int n) {\n    return n / 2;\n}\nint twice(int n) {\n    return n * 2;\n}\n";
+        let file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+
+        let plain = CodeGenerator::new(TargetLanguage::Rust).generate(&file);
+        assert!(!plain.contains("#[track_caller]"), "{}", plain);
+
+        let mut generator = CodeGenerator::new(TargetLanguage::Rust);
+        generator.set_track_caller(true);
+        let rust = generator.generate(&file);
+        assert!(
+            rust.contains("#[inline]\n#[track_caller]\npub fn half(n: i32) -> i32 {"),
+            "{}",
+            rust
+        );
+        assert_eq!(rust.matches("#[track_caller]").count(), 1, "{}", rust);
+    }
+
+    #[test]
+    fn test_generate_result_panic_policy() {
+        let source = "int? pick(int[4] a, usize i) {\n    a[i] = 1;\n    return Ok(a[i]);\n}\nint first(int[4] a) {\n    return a[0];\n}\n";
+        let file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+        let checked = "a[{ let __crusty_index = i; if __crusty_index >= a.len() { return Err(\"index out of bounds\".into()); } __crusty_index }]";
+
+        let plain = CodeGenerator::new(TargetLanguage::Rust).generate(&file);
+        assert!(!plain.contains("__crusty_index"), "{}", plain);
+
+        let mut generator = CodeGenerator::new(TargetLanguage::Rust);
+        generator.set_panic_policy(PanicPolicy::Result);
+        let rust = generator.generate(&file);
+        assert!(rust.contains(&format!("({} = 1);", checked)), "{}", rust);
+        assert!(
+            rust.contains(&format!("return Ok({});", checked)),
+            "{}",
+            rust
+        );
+        // Functions that cannot return an error still panic
+        assert!(rust.contains("return a[0];"), "{}", rust);
+    }
+
+    #[test]
+    fn test_result_panic_policy_checks_every_index() {
+        let source = "struct Buf {\n    int[3] items;\n}\nint[3] make() {\n    return [7, 8, 9];\n}\nint? pick(Buf b, Buf[2] bufs, usize i) {\n    b.items[i] = 5;\n    return Ok(bufs[i].items[1] + make()[i]);\n}\n";
+        let file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+
+        let mut generator = CodeGenerator::new(TargetLanguage::Rust);
+        generator.set_panic_policy(PanicPolicy::Result);
+        let rust = generator.generate(&file);
+        // A field and a nested array are checked in place
+        assert!(
+            rust.contains("(b.items[{ let __crusty_index = i; if __crusty_index >= b.items.len() { return Err(\"index out of bounds\".into()); } __crusty_index }] = 5);"),
+            "{}",
+            rust
+        );
+        let buf = "bufs[{ let __crusty_index = i; if __crusty_index >= bufs.len() { return Err(\"index out of bounds\".into()); } __crusty_index }]";
+        assert!(
+            rust.contains(&format!(
+                "{}.items[{{ let __crusty_index = 1; if __crusty_index >= {}.items.len() {{ return Err(\"index out of bounds\".into()); }} __crusty_index }}]",
+                buf, buf
+            )),
+            "{}",
+            rust
+        );
+        // An array returned by a call is evaluated once
+        assert!(
+            rust.contains("(*make().get(i).ok_or(\"index out of bounds\")?)"),
+            "{}",
+            rust
+        );
+    }
+
+    #[test]
+    fn test_result_panic_policy_checks_narrowing_casts() {
+        let source = "int? shrink(int n) {\n    u8 small = (u8)n;\n    i64 wide = (i64)n;\n    return Ok((int)small + (int)wide);\n}\nint plain(int n) {\n    return (int)(u8)n;\n}\n";
+        let file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+        let mut analyzer = crate::semantic::SemanticAnalyzer::new();
+        analyzer.analyze(&file).unwrap();
+
+        let hir = crate::hir::lower(&file, &analyzer.analysis());
+        let mut generator = CodeGenerator::new(TargetLanguage::Rust);
+        generator.set_panic_policy(PanicPolicy::Result);
+        let rust = generator.generate_hir(&hir);
+        assert!(
+            rust.contains("let small: u8 = (<u8 as std::convert::TryFrom<i32>>::try_from(n).map_err(|_| \"cast out of range\")?);"),
+            "{}",
+            rust
+        );
+        assert!(
+            rust.contains("return Ok(((small as i32) + (<i32 as std::convert::TryFrom<i64>>::try_from(wide).map_err(|_| \"cast out of range\")?)));"),
+            "{}",
+            rust
+        );
+        // Widening casts cannot fail
+        assert!(rust.contains("let wide: i64 = (n as i64);"), "{}", rust);
+        // Functions that cannot return an error cast as before
+        assert!(rust.contains("return ((n as u8) as i32);"), "{}", rust);
+    }
+
+    #[test]
+    fn test_generate_truthiness_tests() {
+        let source = "struct Node { int v; }\nint f(Option<int> p, Node* q, int n) {\n    if (p) {\n        return 1;\n    }\n    while (!q) {\n        return 2;\n    }\n    bool both = p && !n;\n    return n ? 3 : 4;\n}\n";
+        let file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+        let mut analyzer = crate::semantic::SemanticAnalyzer::new();
+        analyzer.set_int_conditions(crate::semantic::IntConditions::Compat);
+        analyzer.analyze(&file).unwrap();
+
+        let hir = crate::hir::lower(&file, &analyzer.analysis());
+        let rust = CodeGenerator::new(TargetLanguage::Rust).generate_hir(&hir);
+        assert!(rust.contains("    if p.is_some() {\n"), "{}", rust);
+        assert!(rust.contains("    while q.is_null() {\n"), "{}", rust);
+        assert!(
+            rust.contains("let both: bool = (p.is_some() && (n == 0));"),
+            "{}",
+            rust
+        );
+        assert!(
+            rust.contains("return if (n != 0) { 3 } else { 4 };"),
+            "{}",
+            rust
+        );
+
+        let crusty = CodeGenerator::new(TargetLanguage::Crusty).generate(&file);
+        assert!(crusty.contains("if (p) {"), "{}", crusty);
+        assert!(crusty.contains("while (!(q)) {"), "{}", crusty);
     }
 
     #[test]
@@ -1803,6 +5640,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![stmt]),
@@ -1824,6 +5662,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![stmt]),
@@ -1861,6 +5700,27 @@ mod tests {
         assert_eq!(result, "(42 as f64)");
     }
 
+    #[test]
+    fn test_generate_struct_reference_cast() {
+        let source = "struct Derived {\n    int kind;\n}\n\nint f(&Base b, &mut Base m, Base* p) {\n    let d = (&Derived)b;\n    let e = (&mut Derived)m;\n    let q = (Derived*)p;\n    return 0;\n}\n";
+        let file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+        let code = CodeGenerator::new(TargetLanguage::Rust).generate(&file);
+        assert!(
+            code.contains("let d = unsafe { &*std::ptr::from_ref(b).cast::<Derived>() };"),
+            "{}",
+            code
+        );
+        assert!(
+            code.contains("let e = unsafe { &mut *std::ptr::from_mut(m).cast::<Derived>() };"),
+            "{}",
+            code
+        );
+        assert!(code.contains("let q = (p as *mut Derived);"), "{}", code);
+    }
+
     #[test]
     fn test_generate_sizeof_expression() {
         let gen = CodeGenerator::new(TargetLanguage::Rust);
@@ -1871,6 +5731,46 @@ mod tests {
         assert_eq!(result, "std::mem::size_of::<i32>()");
     }
 
+    #[test]
+    fn test_generate_radix_literals() {
+        let gen = CodeGenerator::new(TargetLanguage::Rust);
+        let literal = |value, radix| {
+            gen.generate_expression_string(&Expression::Literal(Literal::RadixInt(value, radix)))
+        };
+        assert_eq!(literal(255, Radix::Hex), "0xFF");
+        assert_eq!(literal(63, Radix::Octal), "0o77");
+        assert_eq!(literal(10, Radix::Binary), "0b1010");
+        assert_eq!(literal(-16, Radix::Hex), "-0x10");
+    }
+
+    #[test]
+    fn test_generate_suffixed_literals() {
+        let gen = CodeGenerator::new(TargetLanguage::Rust);
+        let literal = |literal, ty| {
+            gen.generate_expression_string(&Expression::Literal(Literal::Suffixed(
+                Box::new(literal),
+                ty,
+            )))
+        };
+        assert_eq!(literal(Literal::Int(42), PrimitiveType::U32), "42u32");
+        assert_eq!(
+            literal(Literal::RadixInt(255, Radix::Hex), PrimitiveType::U8),
+            "0xFFu8"
+        );
+        assert_eq!(literal(Literal::Float(2.0), PrimitiveType::F32), "2.0f32");
+    }
+
+    #[test]
+    fn test_generate_c_long_for_the_platform() {
+        let mut gen = CodeGenerator::new(TargetLanguage::Rust);
+        gen.set_platform(Platform::from_cfg(&crate::cfg::TargetCfg::parse(
+            "target_family=\"windows\"\ntarget_pointer_width=\"64\"\nwindows\n",
+        )));
+        let ty = |name| gen.generate_type_string(&Type::Ident(Ident::new(name)));
+        assert_eq!(ty("long"), "i32");
+        assert_eq!(ty("unsigned long"), "u32");
+    }
+
     #[test]
     fn test_generate_type_scoped_call() {
         let gen = CodeGenerator::new(TargetLanguage::Rust);
@@ -1902,6 +5802,7 @@ mod tests {
         let struct_def = Struct {
             visibility: Visibility::Public,
             name: Ident::new("Point"),
+            generics: vec![],
             fields: vec![
                 Field {
                     visibility: Visibility::Public,
@@ -1938,6 +5839,7 @@ mod tests {
         let method = Function {
             visibility: Visibility::Public,
             name: Ident::new("new"),
+            generics: vec![],
             params: vec![],
             return_type: Some(Type::Ident(Ident::new("Self"))),
             body: Block::empty(),
@@ -1947,6 +5849,7 @@ mod tests {
         let struct_def = Struct {
             visibility: Visibility::Public,
             name: Ident::new("Point"),
+            generics: vec![],
             fields: vec![],
             methods: vec![method],
             doc_comments: vec![],
@@ -1968,12 +5871,14 @@ mod tests {
         let method = Function {
             visibility: Visibility::Public,
             name: Ident::new("get_x"),
+            generics: vec![],
             params: vec![Param {
                 name: Ident::new("self"),
                 ty: Type::Reference {
                     ty: Box::new(Type::Ident(Ident::new("Self"))),
                     mutable: false,
                 },
+                variadic: false,
             }],
             return_type: Some(Type::Primitive(PrimitiveType::I32)),
             body: Block::empty(),
@@ -1983,6 +5888,7 @@ mod tests {
         let struct_def = Struct {
             visibility: Visibility::Public,
             name: Ident::new("Point"),
+            generics: vec![],
             fields: vec![],
             methods: vec![method],
             doc_comments: vec![],
@@ -2004,6 +5910,7 @@ mod tests {
         let method = Function {
             visibility: Visibility::Public,
             name: Ident::new("set_x"),
+            generics: vec![],
             params: vec![
                 Param {
                     name: Ident::new("self"),
@@ -2011,10 +5918,12 @@ mod tests {
                         ty: Box::new(Type::Ident(Ident::new("Self"))),
                         mutable: true,
                     },
+                    variadic: false,
                 },
                 Param {
                     name: Ident::new("new_x"),
                     ty: Type::Primitive(PrimitiveType::I32),
+                    variadic: false,
                 },
             ],
             return_type: None,
@@ -2025,6 +5934,7 @@ mod tests {
         let struct_def = Struct {
             visibility: Visibility::Public,
             name: Ident::new("Point"),
+            generics: vec![],
             fields: vec![],
             methods: vec![method],
             doc_comments: vec![],
@@ -2046,6 +5956,7 @@ mod tests {
         let method = Function {
             visibility: Visibility::Private,
             name: Ident::new("origin"),
+            generics: vec![],
             params: vec![],
             return_type: Some(Type::Ident(Ident::new("Self"))),
             body: Block::empty(),
@@ -2055,6 +5966,7 @@ mod tests {
         let struct_def = Struct {
             visibility: Visibility::Public,
             name: Ident::new("Point"),
+            generics: vec![],
             fields: vec![],
             methods: vec![method],
             doc_comments: vec![],
@@ -2081,14 +5993,17 @@ mod tests {
                 EnumVariant {
                     name: Ident::new("Red"),
                     value: Some(0),
+                    fields: vec![],
                 },
                 EnumVariant {
                     name: Ident::new("Green"),
                     value: Some(1),
+                    fields: vec![],
                 },
                 EnumVariant {
                     name: Ident::new("Blue"),
                     value: Some(2),
+                    fields: vec![],
                 },
             ],
             doc_comments: vec![],
@@ -2128,6 +6043,30 @@ mod tests {
             gen.generate_type_string(&Type::Primitive(PrimitiveType::U64)),
             "u64"
         );
+        assert_eq!(
+            gen.generate_type_string(&Type::Primitive(PrimitiveType::I8)),
+            "i8"
+        );
+        assert_eq!(
+            gen.generate_type_string(&Type::Primitive(PrimitiveType::I16)),
+            "i16"
+        );
+        assert_eq!(
+            gen.generate_type_string(&Type::Primitive(PrimitiveType::Isize)),
+            "isize"
+        );
+        assert_eq!(
+            gen.generate_type_string(&Type::Primitive(PrimitiveType::U8)),
+            "u8"
+        );
+        assert_eq!(
+            gen.generate_type_string(&Type::Primitive(PrimitiveType::U16)),
+            "u16"
+        );
+        assert_eq!(
+            gen.generate_type_string(&Type::Primitive(PrimitiveType::Usize)),
+            "usize"
+        );
         assert_eq!(
             gen.generate_type_string(&Type::Primitive(PrimitiveType::Float)),
             "f64"
@@ -2265,6 +6204,7 @@ mod tests {
                 "100".to_string(),
             )],
             delimiter: MacroDelimiter::None,
+            variadic: false,
         };
 
         let file = File {
@@ -2326,6 +6266,7 @@ mod tests {
                 ),
             ],
             delimiter: MacroDelimiter::Parens,
+            variadic: false,
         };
 
         let file = File {
@@ -2382,6 +6323,7 @@ mod tests {
                 ),
             ],
             delimiter: MacroDelimiter::Parens,
+            variadic: false,
         };
 
         let file = File {
@@ -2395,6 +6337,34 @@ mod tests {
         assert!(output.contains("$msg"));
     }
 
+    #[test]
+    fn test_generate_variadic_macro() {
+        let source = "#define __LOG__(fmt, ...) __println__(fmt, __VA_ARGS__)\n";
+        let file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+
+        let rust = CodeGenerator::new(TargetLanguage::Rust).generate(&file);
+        assert!(
+            rust.contains("($fmt:expr$(, $va_args:expr)*) => {"),
+            "{}",
+            rust
+        );
+        assert!(
+            rust.contains("println! ( $fmt , $($va_args),* )"),
+            "{}",
+            rust
+        );
+
+        let crusty = CodeGenerator::new(TargetLanguage::Crusty).generate(&file);
+        assert!(
+            crusty.contains("#define __LOG__(fmt, ...) __println__(fmt, __VA_ARGS__)"),
+            "{}",
+            crusty
+        );
+    }
+
     #[test]
     fn test_generate_macro_with_ternary() {
         let mut gen = CodeGenerator::new(TargetLanguage::Rust);
@@ -2460,6 +6430,7 @@ mod tests {
                 ),
             ],
             delimiter: MacroDelimiter::Parens,
+            variadic: false,
         };
 
         let file = File {
@@ -2490,6 +6461,7 @@ mod tests {
                 "42".to_string(),
             )],
             delimiter: MacroDelimiter::None,
+            variadic: false,
         };
 
         let file = File {
@@ -2511,6 +6483,7 @@ mod tests {
             params: vec![],
             body: vec![],
             delimiter: MacroDelimiter::None,
+            variadic: false,
         };
 
         let file = File {
@@ -2762,6 +6735,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![stmt]),
@@ -2823,6 +6797,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![stmt]),
@@ -2856,6 +6831,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![stmt]),
@@ -2891,6 +6867,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![stmt]),
@@ -2924,6 +6901,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![stmt]),
@@ -2970,6 +6948,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("read_config"),
+            generics: vec![],
             params: vec![],
             return_type: Some(Type::Fallible {
                 ty: Box::new(Type::Ident(Ident::new("Config"))),
@@ -3002,6 +6981,830 @@ mod tests {
         let result = gen.generate_expression_string(&expr);
         assert_eq!(result, "parse()??");
     }
+
+    #[test]
+    fn test_generate_sectioned_item_order() {
+        use crate::parser::Parser;
+
+        let source = "int helper() { return 1; }\n\
+                      struct Point { int x; }\n\
+                      #define __ONE__ 1\n\
+                      int main() { return helper(); }\n\
+                      enum Color { Red }\n";
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+
+        let mut gen = CodeGenerator::new(TargetLanguage::Rust);
+        gen.set_item_order(ItemOrder::Sectioned);
+        let output = gen.generate(&file);
+
+        let position = |needle: &str| output.find(needle).expect(needle);
+        assert!(position("// Macros") < position("macro_rules! one"));
+        assert!(position("macro_rules! one") < position("// Types"));
+        assert!(position("// Types") < position("struct Point"));
+        // Source order is kept within a section
+        assert!(position("struct Point") < position("enum Color"));
+        assert!(position("enum Color") < position("// Functions"));
+        assert!(position("fn helper") < position("fn main"));
+    }
+
+    #[test]
+    fn test_generate_source_item_order_is_default() {
+        use crate::parser::Parser;
+
+        let source = "int main() { return 0; }\nstruct Point { int x; }\n";
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+        let output = CodeGenerator::new(TargetLanguage::Rust).generate(&file);
+
+        assert!(output.find("fn main").unwrap() < output.find("struct Point").unwrap());
+        assert!(!output.contains("// Types"));
+    }
+
+    #[test]
+    fn test_generate_header_and_lint_allows() {
+        let mut gen = CodeGenerator::new(TargetLanguage::Rust);
+        gen.set_header(vec!["Generated by crustyc".to_string()]);
+        gen.set_lint_allows(vec!["unused_parens".to_string(), "dead_code".to_string()]);
+        let file = File {
+            items: vec![],
+            doc_comments: vec!["Module docs".to_string()],
+        };
+        let output = gen.generate(&file);
+
+        assert!(output.starts_with(
+            "// Generated by crustyc\n#![allow(unused_parens, dead_code)]\n\n//! Module docs\n"
+        ));
+    }
+
+    #[test]
+    fn test_generate_crusty_ignores_lint_allows() {
+        let mut gen = CodeGenerator::new(TargetLanguage::Crusty);
+        gen.set_lint_allows(vec!["unused_parens".to_string()]);
+        let file = File {
+            items: vec![],
+            doc_comments: vec![],
+        };
+        assert_eq!(gen.generate(&file), "");
+    }
+
+    const HEAP_SOURCE: &str = "\
+struct Big {
+    int[20000] data;
+}
+
+#[heap]
+struct Tagged {
+    int x;
+}
+
+#[stack]
+struct Pinned {
+    int[20000] data;
+}
+
+int f() {
+    let b = (Big){ .data = 0 };
+    var t = (Tagged){ .x = 1 };
+    let p = (Pinned){ .data = 0 };
+    let n = 1;
+    return n;
+}
+";
+
+    #[test]
+    fn test_large_locals_are_boxed() {
+        use crate::parser::Parser;
+
+        let file = Parser::new(HEAP_SOURCE).unwrap().parse_file().unwrap();
+        let mut gen = CodeGenerator::new(TargetLanguage::Rust);
+        gen.set_heap_threshold(Some(DEFAULT_HEAP_THRESHOLD));
+        let output = gen.generate(&file);
+
+        assert!(output.contains("let b: Box<Big> = Box::new(Big { data: 0 });"));
+        assert!(output.contains("let mut t: Box<Tagged> = Box::new(Tagged { x: 1 });"));
+        assert!(output.contains("let p = Pinned { data: 0 };"));
+        assert!(output.contains("let n = 1;"));
+        // Allocation attributes are consumed, not emitted
+        assert!(!output.contains("#[heap]"));
+        assert!(!output.contains("#[stack]"));
+
+        let allocations = gen.heap_allocations();
+        assert_eq!(allocations.len(), 2);
+        assert_eq!(allocations[0].reason, HeapReason::Size(80000));
+        assert_eq!(allocations[1].reason, HeapReason::Attribute);
+        assert_eq!(
+            allocations[0].to_string(),
+            "`b` in `f` (Big, 80000 bytes) is allocated with Box::new to avoid overflowing the stack"
+        );
+    }
+
+    #[test]
+    fn test_boxed_locals_are_used_as_values() {
+        use crate::parser::Parser;
+
+        let source = "struct Big {\n    int[20000] data;\n}\nint first(Big b) {\n    return b.data[0];\n}\nBig f(Big v) {\n    let Big b;\n    b = v;\n    var Big c = v;\n    c.data[1] = first(b);\n    __println__(\"{}\", c.data[1]);\n    return c;\n}\n";
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+        let mut gen = CodeGenerator::new(TargetLanguage::Rust);
+        gen.set_heap_threshold(Some(DEFAULT_HEAP_THRESHOLD));
+        let output = gen.generate(&file);
+
+        for expected in [
+            "let b: Box<Big>;\n",
+            "(b = Box::new(v));",
+            "let mut c: Box<Big> = Box::new(v);",
+            "((*c).data[1] = first((*b)));",
+            "println!(\"{}\", (*c).data[1]);",
+            "return (*c);",
+        ] {
+            assert!(output.contains(expected), "{}\n{}", expected, output);
+        }
+        let positions: Vec<_> = gen
+            .heap_allocations()
+            .iter()
+            .map(|allocation| allocation.position)
+            .collect();
+        assert_eq!(
+            positions,
+            [Some(Position::new(8, 5)), Some(Position::new(10, 5))]
+        );
+    }
+
+    #[test]
+    fn test_boxed_locals_end_with_their_block() {
+        use crate::parser::Parser;
+
+        let source = "struct Big {\n    int[20000] data;\n}\nvoid f(Big v) {\n    var int b = 1;\n    if (b > 0) {\n        var Big b = v;\n        b.data[0] = 2;\n    }\n    b = b + 1;\n}\n";
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+        let mut gen = CodeGenerator::new(TargetLanguage::Rust);
+        gen.set_heap_threshold(Some(DEFAULT_HEAP_THRESHOLD));
+        let output = gen.generate(&file);
+
+        for expected in [
+            "let mut b: Box<Big> = Box::new(v);",
+            "((*b).data[0] = 2);",
+            "(b = (b + 1));",
+        ] {
+            assert!(output.contains(expected), "{}\n{}", expected, output);
+        }
+    }
+
+    #[test]
+    fn test_volatile_locals_end_with_their_block() {
+        let source = "void poll(int* p) {\n    unsafe {\n        if (*p > 0) {\n            volatile int* p = (volatile int*)4096;\n            *p = 1;\n        }\n        *p = 2;\n    }\n}\n";
+        let file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+
+        let rust = CodeGenerator::new(TargetLanguage::Rust).generate(&file);
+        assert!(rust.contains("p.write_volatile(1);"), "{}", rust);
+        assert!(rust.contains("(*(p) = 2);"), "{}", rust);
+    }
+
+    #[test]
+    fn test_array_locals_end_with_their_block() {
+        let source = "void fill(Vec<int> b) {\n    int a[4] = [1, 2, 3, 4];\n    if (a[0] > 0) {\n        var int b[4] = [0, 0, 0, 0];\n        memcpy(b, a, 16);\n    }\n    memcpy(b, a, 16);\n}\n";
+        let file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+
+        let rust = CodeGenerator::new(TargetLanguage::Rust).generate(&file);
+        assert!(rust.contains("b.copy_from_slice(&a);"), "{}", rust);
+        // The parameter's length is not known, so both sides are sliced
+        assert!(
+            rust.contains("b[..4].copy_from_slice(&a[..4]);"),
+            "{}",
+            rust
+        );
+    }
+
+    #[test]
+    fn test_enum_variants_in_macro_arguments() {
+        use crate::parser::Parser;
+
+        let source = "enum Color {\n    Red,\n    Blue,\n}\nstruct P {\n    int Red;\n}\nint code(Color c) {\n    return 1;\n}\nvoid f(P p) {\n    __println__(\"{} {} {}\", code(Color.Blue), p.Red, Color.Green);\n}\n";
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+        let rust = CodeGenerator::new(TargetLanguage::Rust).generate(&file);
+        // Only variants of the file's enums are paths
+        assert!(
+            rust.contains("println!(\"{} {} {}\", code(Color::Blue), p.Red, Color.Green);"),
+            "{}",
+            rust
+        );
+
+        let crusty = CodeGenerator::new(TargetLanguage::Crusty).generate(&file);
+        assert!(crusty.contains("code(Color.Blue)"), "{}", crusty);
+    }
+
+    #[test]
+    fn test_heap_threshold_disabled_keeps_attribute() {
+        use crate::parser::Parser;
+
+        let file = Parser::new(HEAP_SOURCE).unwrap().parse_file().unwrap();
+        let mut gen = CodeGenerator::new(TargetLanguage::Rust);
+        let output = gen.generate(&file);
+
+        assert!(output.contains("let b = Big { data: 0 };"));
+        assert!(output.contains("let mut t: Box<Tagged> = Box::new(Tagged { x: 1 });"));
+        assert_eq!(gen.heap_allocations().len(), 1);
+    }
+
+    #[test]
+    fn test_crusty_target_keeps_allocation_attributes() {
+        use crate::parser::Parser;
+
+        let file = Parser::new(HEAP_SOURCE).unwrap().parse_file().unwrap();
+        let mut gen = CodeGenerator::new(TargetLanguage::Crusty);
+        gen.set_heap_threshold(Some(DEFAULT_HEAP_THRESHOLD));
+        let output = gen.generate(&file);
+
+        assert!(output.contains("#[heap]\nstruct Tagged"));
+        assert!(output.contains("#[stack]\nstruct Pinned"));
+        assert!(!output.contains("Box::new"));
+    }
+
+    #[test]
+    fn test_crusty_lint_attributes_are_not_passed_to_rustc() {
+        use crate::parser::Parser;
+
+        let source =
+            "#[allow(no_effect, unused)]\nvoid poll() {}\n#[deny(shadowing)]\nvoid tick() {}\n";
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+        let output = CodeGenerator::new(TargetLanguage::Rust).generate(&file);
+        assert!(output.contains("#[allow(unused)]\npub fn poll()"));
+        assert!(!output.contains("no_effect"));
+        assert!(!output.contains("#[deny"));
+
+        let crusty = CodeGenerator::new(TargetLanguage::Crusty).generate(&file);
+        assert!(crusty.contains("#[allow(no_effect, unused)]"));
+        assert!(crusty.contains("#[deny(shadowing)]"));
+    }
+
+    const GUARD_SOURCE: &str = "\
+int check(int err, int v) {
+    if (err != 0) {
+        return err;
+    } else if (v < 0) {
+        return 0;
+    } else {
+        let w = v * 2;
+        return w;
+    }
+}
+
+int shadowed(int err) {
+    let w = 1;
+    if (err != 0) {
+        return err;
+    } else {
+        let w = 5;
+    }
+    return w;
+}
+
+int twice(Option<int> p, int q) {
+    if (!p) {
+        return 0;
+    }
+    if (q == NULL) {
+        return 1;
+    }
+    return p * 2;
+}
+";
+
+    #[test]
+    fn test_volatile_accesses_lower_to_volatile_calls() {
+        let source = "void poll(volatile int* status, int* plain) {\n    unsafe {\n        volatile int* reg = (volatile int*)4096;\n        *reg = *status;\n        *status = *status | 4;\n        *plain = 1;\n    }\n}\n";
+        let file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+
+        let rust = CodeGenerator::new(TargetLanguage::Rust).generate(&file);
+        assert!(
+            rust.contains("pub fn poll(status: *mut i32, plain: *mut i32)"),
+            "{}",
+            rust
+        );
+        assert!(rust.contains("    unsafe {\n"), "{}", rust);
+        assert!(
+            rust.contains("let reg: *mut i32 = (4096 as *mut i32);"),
+            "{}",
+            rust
+        );
+        assert!(
+            rust.contains("reg.write_volatile(status.read_volatile());"),
+            "{}",
+            rust
+        );
+        assert!(
+            rust.contains("status.write_volatile((status.read_volatile() | 4));"),
+            "{}",
+            rust
+        );
+        assert!(rust.contains("(*(plain) = 1);"), "{}", rust);
+
+        let crusty = CodeGenerator::new(TargetLanguage::Crusty).generate(&file);
+        assert!(crusty.contains("volatile int"), "{}", crusty);
+        assert!(crusty.contains("unsafe {"), "{}", crusty);
+    }
+
+    #[test]
+    fn test_generate_struct_equality() {
+        let source = "enum Color { Red, Green }\nstruct Pixel {\n    int x;\n    Color color;\n}\nstruct Name {\n    int id;\n    bool eq(&self, &Name other) {\n        return self.id == other.id;\n    }\n}\n";
+        let file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+
+        let mut generator = CodeGenerator::new(TargetLanguage::Rust);
+        generator.set_equality_impls(BTreeMap::from([
+            ("Color".to_string(), EqualityImpl::Derive),
+            ("Pixel".to_string(), EqualityImpl::Derive),
+            ("Name".to_string(), EqualityImpl::Method),
+        ]));
+        let rust = generator.generate(&file);
+        assert!(
+            rust.contains("#[derive(PartialEq)]\npub enum Color {"),
+            "{}",
+            rust
+        );
+        assert!(
+            rust.contains("#[derive(PartialEq)]\npub struct Pixel {"),
+            "{}",
+            rust
+        );
+        assert!(
+            rust.contains(
+                "impl PartialEq for Name {\n    fn eq(&self, other: &Self) -> bool {\n        Name::eq(self, other)\n    }\n}\n"
+            ),
+            "{}",
+            rust
+        );
+        assert!(!rust.contains("pub struct Name {\n#"), "{}", rust);
+
+        // Types never compared get no PartialEq implementation
+        let plain = CodeGenerator::new(TargetLanguage::Rust).generate(&file);
+        assert!(!plain.contains("PartialEq"), "{}", plain);
+    }
+
+    #[test]
+    fn test_generate_imported_modules() {
+        let util = crate::parser::Parser::new("int clamp(int x) {\n    return x;\n}\n")
+            .unwrap()
+            .parse_file()
+            .unwrap();
+        let main = crate::parser::Parser::new(
+            "#import std.io\n#import util\n#import util.clamp\n#export util.clamp\n",
+        )
+        .unwrap()
+        .parse_file()
+        .unwrap();
+
+        let mut generator = CodeGenerator::new(TargetLanguage::Rust);
+        generator.set_local_modules(HashSet::from(["util".to_string()]));
+        let rust = generator.generate(&main);
+        assert!(rust.contains("use std::io;\n"), "{}", rust);
+        // The crate root declares the module itself
+        assert!(!rust.contains("use crate::util;\n"), "{}", rust);
+        assert!(rust.contains("use crate::util::clamp;\n"), "{}", rust);
+        assert!(rust.contains("pub use crate::util::clamp;\n"), "{}", rust);
+        assert_eq!(
+            generator.generate_module("util", &util),
+            "pub mod util {\n    pub fn clamp(x: i32) -> i32 {\n        return x;\n    }\n}\n"
+        );
+        // Another module brings it into scope
+        generator.set_in_module(true);
+        let rust = generator.generate(&main);
+        assert!(rust.contains("use crate::util;\n"), "{}", rust);
+
+        let crusty = CodeGenerator::new(TargetLanguage::Crusty).generate(&main);
+        assert!(crusty.contains("#import util.clamp\n"), "{}", crusty);
+        assert!(crusty.contains("#export util.clamp\n"), "{}", crusty);
+    }
+
+    #[test]
+    fn test_generate_extern_block() {
+        let source = "extern \"C\" {\n    int puts(char* s);\n    int printf(char* fmt, ...);\n    void* malloc(usize n);\n}\n\nvoid main() {\n    unsafe {\n        puts(\"hi\");\n    }\n}\n";
+        let file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+
+        let rust = CodeGenerator::new(TargetLanguage::Rust).generate(&file);
+        assert!(rust.contains("unsafe extern \"C\" {\n"), "{}", rust);
+        assert!(
+            rust.contains("    pub fn puts(s: *mut std::ffi::c_char) -> i32;\n"),
+            "{}",
+            rust
+        );
+        assert!(
+            rust.contains("    pub fn printf(fmt: *mut std::ffi::c_char, ...) -> i32;\n"),
+            "{}",
+            rust
+        );
+        assert!(
+            rust.contains("    pub fn malloc(n: usize) -> *mut std::ffi::c_void;\n"),
+            "{}",
+            rust
+        );
+        assert!(
+            rust.contains("puts((\"hi\\0\".as_ptr() as *mut std::ffi::c_char));"),
+            "{}",
+            rust
+        );
+
+        let crusty = CodeGenerator::new(TargetLanguage::Crusty).generate(&file);
+        assert!(crusty.contains("extern \"C\" {\n"), "{}", crusty);
+        assert!(crusty.contains("    int printf("), "{}", crusty);
+        assert!(crusty.contains(" fmt, ...);\n"), "{}", crusty);
+    }
+
+    #[test]
+    fn test_generate_array_copy() {
+        let source = "void fill(int n) {\n    int a[4] = [1, 2, 3, 4];\n    var int b[4] = [0, 0, 0, 0];\n    var int c[8] = [0, 0, 0, 0, 0, 0, 0, 0];\n    memcpy(b, a, 4 * (int)sizeof(int));\n    memcpy(c, a, 8);\n    memcpy(c, b, n);\n    b = a;\n    bool same = a == b;\n}\n";
+        let file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+
+        let rust = CodeGenerator::new(TargetLanguage::Rust).generate(&file);
+        assert!(rust.contains("b.copy_from_slice(&a);"), "{}", rust);
+        assert!(
+            rust.contains("c[..2].copy_from_slice(&a[..2]);"),
+            "{}",
+            rust
+        );
+        assert!(
+            rust.contains("c[..(n as usize) / std::mem::size_of::<i32>()].copy_from_slice(&b[..(n as usize) / std::mem::size_of::<i32>()]);"),
+            "{}",
+            rust
+        );
+        assert!(rust.contains("(b = a);"), "{}", rust);
+        assert!(rust.contains("let same: bool = (a == b);"), "{}", rust);
+
+        let crusty = CodeGenerator::new(TargetLanguage::Crusty).generate(&file);
+        assert!(crusty.contains("memcpy(c, b, n);"), "{}", crusty);
+    }
+
+    #[test]
+    fn test_generate_time_builtins() {
+        let source = "void wait(int n) {\n    i64 start = time(NULL);\n    i64 ticks = clock();\n    sleep(n);\n    usleep(500);\n    f64 seconds = (f64)(clock() - ticks) / (f64)CLOCKS_PER_SEC;\n    f64 since = difftime(time(), start);\n}\n";
+        let file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+
+        let mut generator = CodeGenerator::new(TargetLanguage::Rust);
+        generator.set_clock_start(true);
+        let rust = generator.generate(&file);
+        assert!(
+            rust.contains("let start: i64 = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(-1, |elapsed| elapsed.as_secs() as i64);"),
+            "{}",
+            rust
+        );
+        assert!(
+            rust.contains("let ticks: i64 = (crate::__CRUSTY_CLOCK_START.get_or_init(std::time::Instant::now).elapsed().as_micros() as i64);"),
+            "{}",
+            rust
+        );
+        assert!(
+            rust.ends_with("\nstatic __CRUSTY_CLOCK_START: std::sync::OnceLock<std::time::Instant> = std::sync::OnceLock::new();\n"),
+            "{}",
+            rust
+        );
+        assert!(
+            rust.contains("std::thread::sleep(std::time::Duration::from_secs(<u64 as std::convert::TryFrom<_>>::try_from(n).unwrap_or(0)));"),
+            "{}",
+            rust
+        );
+        assert!(
+            rust.contains("Duration::from_micros(<u64 as std::convert::TryFrom<_>>::try_from(500)"),
+            "{}",
+            rust
+        );
+        assert!(rust.contains("(1_000_000_i64 as f64)"), "{}", rust);
+        assert!(rust.contains(" - start) as f64)"), "{}", rust);
+
+        let crusty = CodeGenerator::new(TargetLanguage::Crusty).generate(&file);
+        assert!(crusty.contains("sleep(n);"), "{}", crusty);
+
+        // A function of the same name replaces the builtin
+        let source = "int sleep(int n) {\n    return n;\n}\nvoid main() {\n    sleep(1);\n}\n";
+        let file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+        let rust = CodeGenerator::new(TargetLanguage::Rust).generate(&file);
+        assert!(!rust.contains("std::thread::sleep"), "{}", rust);
+        assert!(!rust.contains("__CRUSTY_CLOCK_START"), "{}", rust);
+
+        // A program calling clock() in any of its files starts the clock
+        // in its entry function
+        let mut generator = CodeGenerator::new(TargetLanguage::Rust);
+        generator.set_clock_start(true);
+        let rust = generator.generate(&file);
+        assert!(
+            rust.contains("pub fn main() {\n    crate::__CRUSTY_CLOCK_START.get_or_init(std::time::Instant::now);\n    sleep(1);\n}"),
+            "{}",
+            rust
+        );
+        assert!(rust.contains("\nstatic __CRUSTY_CLOCK_START: "), "{}", rust);
+    }
+
+    #[test]
+    fn test_generate_signal_builtins() {
+        let source = "void on_interrupt(int signum) {\n    __println__(\"{}\", signum);\n}\nvoid main() {\n    signal(SIGINT, on_interrupt);\n    signal(SIGTERM, SIG_IGN);\n    int status = raise(SIGINT);\n}\n";
+        let file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+
+        let rust = CodeGenerator::new(TargetLanguage::Rust).generate(&file);
+        assert!(
+            rust.contains("{ extern \"C\" fn __crusty_on_signal(signum: i32) { on_interrupt(signum) } unsafe extern \"C\" { fn signal(signum: i32, handler: usize) -> usize; } unsafe { signal(2, __crusty_on_signal as usize) }; };"),
+            "{}",
+            rust
+        );
+        assert!(rust.contains("unsafe { signal(15, 1) }"), "{}", rust);
+        assert!(
+            rust.contains("let status: i32 = { unsafe extern \"C\" { fn raise(signum: i32) -> i32; } unsafe { raise(2) } };"),
+            "{}",
+            rust
+        );
+
+        let crusty = CodeGenerator::new(TargetLanguage::Crusty).generate(&file);
+        assert!(
+            crusty.contains("signal(SIGINT, on_interrupt);"),
+            "{}",
+            crusty
+        );
+    }
+
+    #[test]
+    fn test_generate_static_assert() {
+        let source = "static_assert((int)sizeof(Header) == 8, \"Header is {8} bytes\");\nvoid main() {\n    static_assert(1 < 2, \"ordered\");\n}\n";
+        let file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+
+        let rust = CodeGenerator::new(TargetLanguage::Rust).generate(&file);
+        assert!(
+            rust.contains("const _: () = assert!(((std::mem::size_of::<Header>() as i32) == 8), \"{}\", \"Header is {8} bytes\");"),
+            "{}",
+            rust
+        );
+        assert!(
+            rust.contains("    const _: () = assert!((1 < 2), \"ordered\");"),
+            "{}",
+            rust
+        );
+
+        let crusty = CodeGenerator::new(TargetLanguage::Crusty).generate(&file);
+        assert!(
+            crusty.contains("static_assert(((int)sizeof(Header) == 8), \"Header is {8} bytes\");"),
+            "{}",
+            crusty
+        );
+    }
+
+    #[test]
+    fn test_generate_string_initialized_arrays() {
+        let source = "void main() {\n    char name[5] = \"hi\";\n    var u8 magic[6] = b\"\\x7fELF\";\n    let bytes = b\"ok\";\n}\n";
+        let file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+
+        let rust = CodeGenerator::new(TargetLanguage::Rust).generate(&file);
+        assert!(
+            rust.contains("let name: [char; 5] = ['h', 'i', '\\u{0}', '\\u{0}', '\\u{0}'];"),
+            "{}",
+            rust
+        );
+        assert!(
+            rust.contains("let mut magic: [u8; 6] = *b\"\\x7fELF\\x00\\x00\";"),
+            "{}",
+            rust
+        );
+        assert!(rust.contains("let bytes = b\"ok\";"), "{}", rust);
+
+        let crusty = CodeGenerator::new(TargetLanguage::Crusty).generate(&file);
+        assert!(crusty.contains("let char name[5] = \"hi\";"), "{}", crusty);
+        assert!(
+            crusty.contains("var u8 magic[6] = b\"\\x7fELF\";"),
+            "{}",
+            crusty
+        );
+    }
+
+    #[test]
+    fn test_generate_wide_strings() {
+        let source = "void main() {\n    let title = L\"Caf\u{e9}\\n\";\n}\n";
+        let file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+
+        let rust = CodeGenerator::new(TargetLanguage::Rust).generate(&file);
+        assert!(
+            rust.contains("let title = \"Caf\\u{e9}\\n\\0\".encode_utf16().collect::<Vec<u16>>();"),
+            "{}",
+            rust
+        );
+
+        let mut generator = CodeGenerator::new(TargetLanguage::Rust);
+        generator.set_wide_strings(WideStringMapping::WideString);
+        let rust = generator.generate(&file);
+        assert!(
+            rust.contains("let title = widestring::u16cstr!(\"Caf\\u{e9}\\n\");"),
+            "{}",
+            rust
+        );
+
+        let crusty = CodeGenerator::new(TargetLanguage::Crusty).generate(&file);
+        assert!(
+            crusty.contains("let title = L\"Caf\\u{e9}\\n\";"),
+            "{}",
+            crusty
+        );
+    }
+
+    #[test]
+    fn test_generate_raw_strings() {
+        let source = r##"void main() { let path = r"C:\temp"; let quoted = r#"say "hi""#; }"##;
+        let file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+
+        let rust = CodeGenerator::new(TargetLanguage::Rust).generate(&file);
+        assert!(rust.contains("let path = r\"C:\\temp\";"), "{}", rust);
+        assert!(rust.contains("let quoted = r#\"say \"hi\"\"#;"), "{}", rust);
+
+        let crusty = CodeGenerator::new(TargetLanguage::Crusty).generate(&file);
+        assert!(crusty.contains("let path = r\"C:\\temp\";"), "{}", crusty);
+    }
+
+    #[test]
+    fn test_generate_folded_case_labels() {
+        let source = "#define __BASE__ 10\nint classify(int n) {\n    const int STEP = 2;\n    switch (n) {\n        case __BASE__ + 1: { return 1; }\n        case STEP * 3: case -1: { return 2; }\n        case STEP: { return 3; }\n        default: { return 0; }\n    }\n}\n";
+        let mut file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+        crate::macros::expand_macros(&mut file, crate::limits::DEFAULT_MAX_EXPANSION_TOKENS)
+            .unwrap();
+
+        let rust = CodeGenerator::new(TargetLanguage::Rust).generate(&file);
+        assert!(rust.contains("11 => {"), "{}", rust);
+        assert!(rust.contains("6 | -1 => {"), "{}", rust);
+        // A constant on its own is already a pattern
+        assert!(rust.contains("STEP => {"), "{}", rust);
+    }
+
+    #[test]
+    fn test_embedded_source_quotes_each_item() {
+        let source = "#define __LIMIT__ 10\n\nint add(int a, int b) {\n    return a + b;  \n}\n";
+        let file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+        let mut generator = CodeGenerator::new(TargetLanguage::Rust);
+        generator.set_embedded_source(Some(source.to_string()));
+        let output = generator.generate(&file);
+
+        assert!(
+            output.contains(
+                "//    3 | int add(int a, int b) {\n//    4 |     return a + b;\n//    5 | }\npub fn add(a: i32, b: i32) -> i32 {\n"
+            ),
+            "{}",
+            output
+        );
+        assert!(!output.contains("// 1 |"), "{}", output);
+        // The item itself starts after its quoted source
+        let start = output
+            .lines()
+            .position(|l| l.starts_with("pub fn add"))
+            .unwrap();
+        assert_eq!(generator.generated_items()[1].line, start + 1);
+    }
+
+    #[test]
+    fn test_early_return_guards_are_flattened() {
+        use crate::parser::Parser;
+
+        let file = Parser::new(GUARD_SOURCE).unwrap().parse_file().unwrap();
+        let output = CodeGenerator::new(TargetLanguage::Rust).generate(&file);
+
+        assert!(
+            output.contains(
+                "    if (err != 0) {\n        return err;\n    }\n    if (v < 0) {\n        return 0;\n    }\n    let w = (v * 2);\n    return w;\n"
+            ),
+            "{}",
+            output
+        );
+        // Hoisting `let w = 5;` would shadow the `w` returned afterwards
+        assert!(
+            output.contains("    } else {\n        let w = 5;\n    }\n    return w;"),
+            "{}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_null_guard_on_option_becomes_let_else() {
+        use crate::parser::Parser;
+
+        let file = Parser::new(GUARD_SOURCE).unwrap().parse_file().unwrap();
+        let output = CodeGenerator::new(TargetLanguage::Rust).generate(&file);
+
+        assert!(
+            output.contains("    let Some(p) = p else {\n        return 0;\n    };\n"),
+            "{}",
+            output
+        );
+        // `q` is not an Option, so its guard is left alone
+        assert!(
+            output.contains("    if (q == Option::None) {"),
+            "{}",
+            output
+        );
+
+        let crusty = CodeGenerator::new(TargetLanguage::Crusty).generate(&file);
+        assert!(!crusty.contains("let Some"));
+        assert!(crusty.contains("} else {"));
+    }
+
+    #[test]
+    fn test_null_guard_keeps_mutable_locals_mutable() {
+        use crate::parser::Parser;
+
+        let source = "int bump(Option<int> q) {\n    var Option<int> p = q;\n    if (!p) {\n        return 0;\n    }\n    p = p + 1;\n    return p;\n}\nint first(Option<int> q) {\n    var Option<int> r = q;\n    if (r == NULL) {\n        return 1;\n    }\n    return r;\n}\nint last(Option<int> q) {\n    let Option<int> s = q;\n    if (!s) {\n        return 2;\n    }\n    return s;\n}\n";
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+        let output = CodeGenerator::new(TargetLanguage::Rust).generate(&file);
+
+        for expected in [
+            "let Some(mut p) = p else {",
+            "let Some(mut r) = r else {",
+            "let Some(s) = s else {",
+        ] {
+            assert!(output.contains(expected), "{}\n{}", expected, output);
+        }
+    }
+
+    #[test]
+    fn test_null_guard_narrows_only_to_the_end_of_its_block() {
+        use crate::parser::Parser;
+
+        let source = "int f(bool flag, Option<int> p) {\n    if (flag) {\n        if (!p) {\n            return -1;\n        }\n        return p;\n    }\n    if (p) {\n        return 1;\n    }\n    return 0;\n}\n";
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+        let mut analyzer = crate::semantic::SemanticAnalyzer::new();
+        analyzer.analyze(&file).unwrap();
+        let hir = crate::hir::lower(&file, &analyzer.analysis());
+        let output = CodeGenerator::new(TargetLanguage::Rust).generate_hir(&hir);
+
+        assert!(
+            output.contains("        let Some(p) = p else {"),
+            "{}",
+            output
+        );
+        // After the block, `p` is the Option again
+        assert!(output.contains("    if p.is_some() {"), "{}", output);
+    }
+
+    #[test]
+    fn test_generate_is_deterministic_across_runs() {
+        use crate::parser::Parser;
+        use crate::semantic::SemanticAnalyzer;
+
+        let source = "void outer() {\n\
+                      var int alpha = 1;\n\
+                      var int beta = 2;\n\
+                      var int gamma = 3;\n\
+                      void bump() { alpha = beta + gamma; }\n\
+                      bump();\n\
+                      }\n";
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+
+        let generate = || {
+            let mut analyzer = SemanticAnalyzer::new();
+            analyzer.analyze(&file).unwrap();
+            let captures = analyzer.get_all_captures().clone();
+            let names: Vec<String> = captures["bump"].iter().map(|c| c.name.clone()).collect();
+            let mut gen = CodeGenerator::new(TargetLanguage::Rust);
+            gen.set_captures(captures);
+            (names, gen.generate(&file))
+        };
+
+        let (names, first) = generate();
+        assert_eq!(names, vec!["alpha", "beta", "gamma"]);
+        for _ in 0..5 {
+            assert_eq!(generate().1, first);
+        }
+    }
 }
 
 #[cfg(test)]
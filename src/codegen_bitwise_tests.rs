@@ -128,6 +128,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![stmt]),
@@ -154,6 +155,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![stmt]),
@@ -275,6 +277,45 @@ mod tests {
         assert_eq!(result, "if true { 1 } else { 2 }");
     }
 
+    #[test]
+    fn test_generate_nested_ternary_as_else_if_chain() {
+        let expr = Expression::Ternary {
+            condition: Box::new(Expression::Ident(Ident::new("a"))),
+            then_expr: Box::new(Expression::Literal(Literal::Int(1))),
+            else_expr: Box::new(Expression::Ternary {
+                condition: Box::new(Expression::Ident(Ident::new("b"))),
+                then_expr: Box::new(Expression::Literal(Literal::Int(2))),
+                else_expr: Box::new(Expression::Literal(Literal::Int(3))),
+            }),
+        };
+
+        let rust = CodeGenerator::new(TargetLanguage::Rust).generate_expression_string(&expr);
+        assert_eq!(rust, "if a { 1 } else if b { 2 } else { 3 }");
+
+        let crusty = CodeGenerator::new(TargetLanguage::Crusty).generate_expression_string(&expr);
+        assert_eq!(crusty, "if (a) { 1 } else if (b) { 2 } else { 3 }");
+    }
+
+    #[test]
+    fn test_generate_qualified_enum_variants() {
+        let source = "enum Color { Red, Green }\nvoid main() {\n    Color a = Color.Red;\n    let b = @Color.Green;\n    Color c = Green;\n}\n";
+        let file = crate::parser::Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+
+        let mut gen = CodeGenerator::new(TargetLanguage::Rust);
+        gen.set_unqualified_variants([("Green".to_string(), "Color".to_string())].into());
+        let rust = gen.generate(&file);
+        assert!(rust.contains("let a: Color = Color::Red;"), "{}", rust);
+        assert!(rust.contains("let b = Color::Green;"), "{}", rust);
+        assert!(rust.contains("let c: Color = Color::Green;"), "{}", rust);
+
+        let crusty = CodeGenerator::new(TargetLanguage::Crusty).generate(&file);
+        assert!(crusty.contains("Color.Green"), "{}", crusty);
+        assert!(!crusty.contains("Color.Green()"), "{}", crusty);
+    }
+
     #[test]
     fn test_generate_macro_call_expression() {
         let gen = CodeGenerator::new(TargetLanguage::Rust);
@@ -284,6 +325,7 @@ mod tests {
                 kind: TokenKind::Literal,
                 text: "\"hello\"".to_string(),
             }],
+            delimiter: MacroDelimiter::Parens,
         };
         let result = gen.generate_expression_string(&expr);
         assert_eq!(result, "println!(\"hello\")");
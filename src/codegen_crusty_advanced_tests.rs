@@ -24,6 +24,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: Some(Type::Primitive(PrimitiveType::Void)),
             body: Block::new(vec![stmt]),
@@ -50,6 +51,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: Some(Type::Primitive(PrimitiveType::Void)),
             body: Block::new(vec![stmt]),
@@ -84,6 +86,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: Some(Type::Primitive(PrimitiveType::Void)),
             body: Block::new(vec![stmt]),
@@ -116,6 +119,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: Some(Type::Primitive(PrimitiveType::Void)),
             body: Block::new(vec![stmt]),
@@ -150,6 +154,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: Some(Type::Primitive(PrimitiveType::Void)),
             body: Block::new(vec![stmt]),
@@ -172,6 +177,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: Some(Type::Primitive(PrimitiveType::Void)),
             body: Block::new(vec![stmt]),
@@ -183,7 +189,7 @@ mod tests {
             doc_comments: vec![],
         };
         let output = gen.generate(&file);
-        assert!(output.contains("break .outer;"));
+        assert!(output.contains("break outer;"));
     }
 
     #[test]
@@ -193,6 +199,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: Some(Type::Primitive(PrimitiveType::Void)),
             body: Block::new(vec![stmt]),
@@ -204,7 +211,7 @@ mod tests {
             doc_comments: vec![],
         };
         let output = gen.generate(&file);
-        assert!(output.contains("continue .outer;"));
+        assert!(output.contains("continue outer;"));
     }
 
     #[test]
@@ -232,6 +239,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: Some(Type::Primitive(PrimitiveType::Void)),
             body: Block::new(vec![stmt]),
@@ -282,6 +290,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: Some(Type::Primitive(PrimitiveType::Void)),
             body: Block::new(vec![stmt]),
@@ -303,14 +312,17 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("add"),
+            generics: vec![],
             params: vec![
                 Param {
                     name: Ident::new("a"),
                     ty: Type::Primitive(PrimitiveType::Int),
+                    variadic: false,
                 },
                 Param {
                     name: Ident::new("b"),
                     ty: Type::Primitive(PrimitiveType::Int),
+                    variadic: false,
                 },
             ],
             return_type: Some(Type::Primitive(PrimitiveType::Int)),
@@ -333,6 +345,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: Some(Type::Primitive(PrimitiveType::Int)),
             body: Block::new(vec![stmt]),
@@ -359,6 +372,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: Some(Type::Primitive(PrimitiveType::Void)),
             body: Block::new(vec![stmt]),
@@ -384,6 +398,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: Some(Type::Primitive(PrimitiveType::Void)),
             body: Block::new(vec![stmt]),
@@ -178,7 +178,7 @@ void main() {
         let mut codegen = CodeGenerator::new(TargetLanguage::Crusty);
         let crusty_code = codegen.generate(&file);
 
-        assert!(crusty_code.contains("let x"));
+        assert!(crusty_code.contains("let int x;"));
     }
 
     #[test]
@@ -194,7 +194,7 @@ void main() {
         let mut codegen = CodeGenerator::new(TargetLanguage::Crusty);
         let crusty_code = codegen.generate(&file);
 
-        assert!(crusty_code.contains("var x"));
+        assert!(crusty_code.contains("var int x;"));
     }
 
     #[test]
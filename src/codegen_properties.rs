@@ -33,6 +33,7 @@ mod tests {
                         | "type"
                         | "impl"
                         | "trait"
+                        | "interface"
                         | "pub"
                         | "use"
                         | "mod"
@@ -132,6 +133,7 @@ mod tests {
             Function {
                 visibility: vis,
                 name,
+                generics: vec![],
                 params: vec![],
                 return_type: Some(ret_type),
                 body: Block::empty(),
@@ -145,6 +147,7 @@ mod tests {
         (arb_visibility(), arb_ident()).prop_map(|(vis, name)| Struct {
             visibility: vis,
             name,
+            generics: vec![],
             fields: vec![],
             methods: vec![],
             doc_comments: vec![],
@@ -160,10 +163,12 @@ mod tests {
                 EnumVariant {
                     name: Ident::new("Variant1"),
                     value: Some(0),
+                    fields: vec![],
                 },
                 EnumVariant {
                     name: Ident::new("Variant2"),
                     value: Some(1),
+                    fields: vec![],
                 },
             ],
             doc_comments: vec![],
@@ -230,6 +235,7 @@ mod tests {
             let func = Function {
                 visibility: Visibility::Public,
                 name: Ident::new("test"),
+                generics: vec![],
                 params: vec![],
                 return_type: None,
                 body: Block::new(vec![stmt]),
@@ -263,6 +269,7 @@ mod tests {
             let func = Function {
                 visibility: Visibility::Public,
                 name: Ident::new("test"),
+                generics: vec![],
                 params: vec![],
                 return_type: None,
                 body: Block::new(vec![stmt]),
@@ -316,6 +323,7 @@ mod tests {
             let func = Function {
                 visibility: Visibility::Public,
                 name: Ident::new("test"),
+                generics: vec![],
                 params: vec![],
                 return_type: None,
                 body: Block::new(vec![stmt]),
@@ -340,6 +348,7 @@ mod tests {
             let func = Function {
                 visibility: Visibility::Public,
                 name: Ident::new("test"),
+                generics: vec![],
                 params: vec![],
                 return_type: None,
                 body: Block::new(vec![stmt]),
@@ -368,6 +377,7 @@ mod tests {
             let func = Function {
                 visibility: Visibility::Public,
                 name: Ident::new("test"),
+                generics: vec![],
                 params: vec![],
                 return_type: None,
                 body: Block::new(vec![stmt]),
@@ -448,6 +458,7 @@ mod tests {
                 params,
                 body,
                 delimiter,
+                variadic: false,
             }
         })
     }
@@ -677,12 +688,14 @@ mod tests {
             let method = Function {
                 visibility: Visibility::Public,
                 name: method_name.clone(),
+                generics: vec![],
                 params: vec![Param {
                     name: Ident::new("self"),
                     ty: Type::Reference {
                         ty: Box::new(Type::Ident(Ident::new("Self"))),
                         mutable: false,
                     },
+                    variadic: false,
                 }],
                 return_type: None,
                 body: Block::empty(),
@@ -692,6 +705,7 @@ mod tests {
             let struct_def = Struct {
                 visibility: Visibility::Public,
                 name: struct_name.clone(),
+                generics: vec![],
                 fields: vec![],
                 methods: vec![method],
                 doc_comments: vec![],
@@ -733,6 +747,7 @@ mod tests {
             let func = Function {
                 visibility: Visibility::Public,
                 name: Ident::new("test"),
+                generics: vec![],
                 params: vec![],
                 return_type: None,
                 body: Block::new(vec![for_in]),
@@ -783,6 +798,7 @@ mod tests {
             let func = Function {
                 visibility: Visibility::Public,
                 name: Ident::new("test"),
+                generics: vec![],
                 params: vec![],
                 return_type: None,
                 body: Block::new(vec![c_for]),
@@ -825,6 +841,7 @@ mod tests {
             let func = Function {
                 visibility: Visibility::Public,
                 name: Ident::new("test"),
+                generics: vec![],
                 params: vec![],
                 return_type: None,
                 body: Block::new(vec![switch]),
@@ -932,9 +949,11 @@ mod tests {
                 params: vec![Param {
                     name: param_name.clone(),
                     ty: param_type.clone(),
+                    variadic: false,
                 }],
                 return_type: Some(return_type.clone()),
                 body: Block {
+                    positions: Vec::new(),
                     statements: vec![Statement::Return(Some(Expression::Ident(param_name.clone())))],
                 },
             };
@@ -943,9 +962,11 @@ mod tests {
             let outer_func = Function {
                 visibility: Visibility::Public,
                 name: Ident::new("outer"),
+                generics: vec![],
                 params: vec![],
                 return_type: Some(Type::Primitive(PrimitiveType::Void)),
                 body: Block {
+                    positions: Vec::new(),
                     statements: vec![nested_func],
                 },
                 doc_comments: vec![],
@@ -994,6 +1015,7 @@ mod tests {
                 params: vec![],
                 return_type: Some(return_type.clone()),
                 body: Block {
+                    positions: Vec::new(),
                     statements: vec![Statement::Return(Some(Expression::Ident(capture_var.clone())))],
                 },
             };
@@ -1002,9 +1024,11 @@ mod tests {
             let outer_func = Function {
                 visibility: Visibility::Public,
                 name: Ident::new("outer"),
+                generics: vec![],
                 params: vec![],
                 return_type: Some(Type::Primitive(PrimitiveType::Void)),
                 body: Block {
+                    positions: Vec::new(),
                     statements: vec![
                         Statement::Let {
                             name: capture_var.clone(),
@@ -1055,6 +1079,7 @@ mod tests {
                 params: vec![],
                 return_type: Some(Type::Primitive(PrimitiveType::Void)),
                 body: Block {
+                    positions: Vec::new(),
                     statements: vec![
                         Statement::Expr(Expression::Binary {
                             left: Box::new(Expression::Ident(capture_var.clone())),
@@ -1073,9 +1098,11 @@ mod tests {
             let outer_func = Function {
                 visibility: Visibility::Public,
                 name: Ident::new("outer"),
+                generics: vec![],
                 params: vec![],
                 return_type: Some(Type::Primitive(PrimitiveType::Void)),
                 body: Block {
+                    positions: Vec::new(),
                     statements: vec![
                         Statement::Var {
                             name: capture_var.clone(),
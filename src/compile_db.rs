@@ -0,0 +1,146 @@
+// Copyright (c) 2026 Mark Ferrell
+// Licensed under the MIT License. See LICENSE.txt in the project root.
+
+//! Compilation database describing how each Crusty file was compiled.
+//!
+//! The format follows `compile_commands.json`: a JSON array with one entry
+//! per source file, extended with the macros the file defines and the paths
+//! its imports are resolved against.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Conventional file name of the compilation database
+pub const COMPILE_DB_FILE_NAME: &str = "compile_commands.json";
+
+/// How a single source file was compiled
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompileCommand {
+    /// Working directory the compiler ran in
+    pub directory: String,
+    /// Source file, as given to the compiler
+    pub file: String,
+    /// Primary output written for the file
+    pub output: String,
+    /// Command line that reproduces the compilation
+    pub arguments: Vec<String>,
+    /// Source language the file was parsed as
+    pub language: String,
+    /// Macros defined by the file (`#define`)
+    pub defines: Vec<String>,
+    /// Directories imports are resolved against
+    pub include_paths: Vec<String>,
+}
+
+/// A set of compile commands, serialized as a JSON array
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CompilationDatabase {
+    commands: Vec<CompileCommand>,
+}
+
+impl CompilationDatabase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a command, replacing any earlier entry for the same file
+    pub fn push(&mut self, command: CompileCommand) {
+        self.commands
+            .retain(|existing| existing.file != command.file);
+        self.commands.push(command);
+    }
+
+    #[allow(dead_code)]
+    pub fn commands(&self) -> &[CompileCommand] {
+        &self.commands
+    }
+
+    /// Find the entry for a source file
+    #[allow(dead_code)]
+    pub fn find(&self, file: &Path) -> Option<&CompileCommand> {
+        self.commands
+            .iter()
+            .find(|command| Path::new(&command.file) == file)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("compile commands are always serializable")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// Write the database to `path`, creating parent directories as needed
+    pub fn write(&self, path: &Path) -> Result<(), std::io::Error> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, self.to_json() + "\n")
+    }
+
+    /// Read a database previously written with [`CompilationDatabase::write`]
+    pub fn load(path: &Path) -> Result<Self, std::io::Error> {
+        let json = std::fs::read_to_string(path)?;
+        Self::from_json(&json).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(file: &str, output: &str) -> CompileCommand {
+        CompileCommand {
+            directory: "/work".to_string(),
+            file: file.to_string(),
+            output: output.to_string(),
+            arguments: vec!["crustyc".to_string(), file.to_string()],
+            language: "crusty".to_string(),
+            defines: vec!["__MAX__".to_string()],
+            include_paths: vec!["src".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_serializes_as_json_array() {
+        let mut db = CompilationDatabase::new();
+        db.push(command("src/a.crst", "out/a.rs"));
+        let json = db.to_json();
+        assert!(json.starts_with('['));
+        assert!(json.contains("\"file\": \"src/a.crst\""));
+        assert!(json.contains("\"include_paths\""));
+        assert_eq!(CompilationDatabase::from_json(&json).unwrap(), db);
+    }
+
+    #[test]
+    fn test_push_replaces_existing_entry() {
+        let mut db = CompilationDatabase::new();
+        db.push(command("src/a.crst", "out/a.rs"));
+        db.push(command("src/b.crst", "out/b.rs"));
+        db.push(command("src/a.crst", "out/a2.rs"));
+
+        assert_eq!(db.commands().len(), 2);
+        assert_eq!(
+            db.find(Path::new("src/a.crst")).unwrap().output,
+            "out/a2.rs"
+        );
+        assert_eq!(db.find(Path::new("src/c.crst")), None);
+    }
+
+    #[test]
+    fn test_write_and_load() {
+        let path = std::env::temp_dir()
+            .join("crustyc_compile_db_test")
+            .join(COMPILE_DB_FILE_NAME);
+        let mut db = CompilationDatabase::new();
+        db.push(command("src/a.crst", "out/a.rs"));
+
+        db.write(&path).unwrap();
+        let loaded = CompilationDatabase::load(&path);
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+
+        assert_eq!(loaded.unwrap(), db);
+    }
+}
@@ -0,0 +1,399 @@
+// Copyright (c) 2026 Mark Ferrell
+// Licensed under the MIT License. See LICENSE.txt in the project root.
+
+//! Compile-time evaluation of constant expressions.
+//!
+//! Covers what `static_assert` conditions and constant initializers are
+//! typically built from: literals, named constants, arithmetic, comparison,
+//! logical and bitwise operators, casts between numeric types, and the size
+//! of primitive types. The size of a struct or array depends on a layout only
+//! rustc computes, so such expressions report [`ConstEvalError::Layout`] and
+//! are left for the generated Rust to check.
+
+use crate::ast::{BinaryOp, Expression, Literal, PrimitiveType, Type, UnaryOp};
+use crate::platform::Platform;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Value of a constant expression
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConstValue {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+}
+
+impl fmt::Display for ConstValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConstValue::Int(value) => write!(f, "{}", value),
+            ConstValue::Float(value) => write!(f, "{}", value),
+            ConstValue::Bool(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// Why an expression could not be evaluated
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstEvalError {
+    /// The expression uses something that is not known at compile time
+    NotConstant(String),
+    /// The value depends on the memory layout of a type
+    Layout(String),
+    /// Evaluation failed: overflow, division by zero or mismatched operands
+    Invalid(String),
+    /// Evaluation took more steps than the limit given
+    StepLimit(usize),
+}
+
+impl fmt::Display for ConstEvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConstEvalError::NotConstant(what) => write!(f, "{} is not a constant", what),
+            ConstEvalError::Layout(ty) => write!(f, "the layout of '{}' is not known", ty),
+            ConstEvalError::Invalid(msg) => write!(f, "{}", msg),
+            ConstEvalError::StepLimit(limit) => write!(
+                f,
+                "evaluation exceeded {} steps",
+                crate::limits::format_count(*limit)
+            ),
+        }
+    }
+}
+
+/// Evaluate `expr`, resolving identifiers against `consts` and sizes
+/// against `platform`
+pub fn evaluate(
+    expr: &Expression,
+    consts: &HashMap<String, ConstValue>,
+    platform: &Platform,
+) -> Result<ConstValue, ConstEvalError> {
+    evaluate_with_limit(
+        expr,
+        consts,
+        platform,
+        crate::limits::DEFAULT_MAX_CONST_EVAL_STEPS,
+    )
+}
+
+/// Evaluate `expr` as [`evaluate`] does, giving up once it takes more than
+/// `limit` steps (0 for no limit)
+pub fn evaluate_with_limit(
+    expr: &Expression,
+    consts: &HashMap<String, ConstValue>,
+    platform: &Platform,
+    limit: usize,
+) -> Result<ConstValue, ConstEvalError> {
+    Evaluator {
+        consts,
+        platform,
+        steps: 0,
+        limit,
+    }
+    .evaluate(expr)
+}
+
+/// Evaluation of one expression, counting the nodes visited
+struct Evaluator<'a> {
+    consts: &'a HashMap<String, ConstValue>,
+    platform: &'a Platform,
+    steps: usize,
+    limit: usize,
+}
+
+impl Evaluator<'_> {
+    fn evaluate(&mut self, expr: &Expression) -> Result<ConstValue, ConstEvalError> {
+        self.steps += 1;
+        if crate::limits::exceeds(self.steps, self.limit) {
+            return Err(ConstEvalError::StepLimit(self.limit));
+        }
+        match expr {
+            Expression::Literal(Literal::Int(value) | Literal::RadixInt(value, _)) => {
+                Ok(ConstValue::Int(*value))
+            }
+            Expression::Literal(Literal::Float(value)) => Ok(ConstValue::Float(*value)),
+            Expression::Literal(Literal::Suffixed(literal, ty)) => cast(
+                self.evaluate(&Expression::Literal((**literal).clone()))?,
+                &Type::Primitive(ty.clone()),
+                self.platform,
+            ),
+            Expression::Literal(Literal::Bool(value)) => Ok(ConstValue::Bool(*value)),
+            Expression::Literal(Literal::Char(c)) => Ok(ConstValue::Int(*c as i64)),
+            Expression::Ident(ident) => self
+                .consts
+                .get(&ident.name)
+                .copied()
+                .ok_or_else(|| ConstEvalError::NotConstant(format!("'{}'", ident.name))),
+            Expression::Unary { op, expr } => evaluate_unary(op, self.evaluate(expr)?),
+            Expression::Binary { op, left, right } => {
+                // && and || short-circuit, so the right operand may be anything
+                // when the left decides the result
+                let left = self.evaluate(left)?;
+                match (op, left) {
+                    (BinaryOp::And, ConstValue::Bool(false)) => return Ok(left),
+                    (BinaryOp::Or, ConstValue::Bool(true)) => return Ok(left),
+                    _ => {}
+                }
+                evaluate_binary(op, left, self.evaluate(right)?)
+            }
+            Expression::Ternary {
+                condition,
+                then_expr,
+                else_expr,
+            } => match self.evaluate(condition)? {
+                ConstValue::Bool(true) => self.evaluate(then_expr),
+                ConstValue::Bool(false) => self.evaluate(else_expr),
+                other => Err(ConstEvalError::Invalid(format!(
+                    "condition must be a bool, found {}",
+                    other
+                ))),
+            },
+            Expression::Cast { expr, ty } => cast(self.evaluate(expr)?, ty, self.platform),
+            Expression::Sizeof { ty } => {
+                primitive_size(ty, self.platform).map(|size| ConstValue::Int(size as i64))
+            }
+            _ => Err(ConstEvalError::NotConstant("this expression".to_string())),
+        }
+    }
+}
+
+/// Evaluate `expr` before the target is known, as the parser must for
+/// array sizes and enum values: a value that differs between the C data
+/// models, such as `sizeof(long)`, is not constant
+pub fn evaluate_portable(
+    expr: &Expression,
+    consts: &HashMap<String, ConstValue>,
+) -> Result<ConstValue, ConstEvalError> {
+    let [first, rest @ ..] = Platform::data_models();
+    let value = evaluate(expr, consts, &first)?;
+    for platform in &rest {
+        if evaluate(expr, consts, platform)? != value {
+            return Err(ConstEvalError::NotConstant(
+                "a value that differs between targets".to_string(),
+            ));
+        }
+    }
+    Ok(value)
+}
+
+fn evaluate_unary(op: &UnaryOp, value: ConstValue) -> Result<ConstValue, ConstEvalError> {
+    match (op, value) {
+        (UnaryOp::Not, ConstValue::Bool(b)) => Ok(ConstValue::Bool(!b)),
+        (UnaryOp::Neg, ConstValue::Int(i)) => i
+            .checked_neg()
+            .map(ConstValue::Int)
+            .ok_or_else(|| ConstEvalError::Invalid("negation overflows".to_string())),
+        (UnaryOp::Neg, ConstValue::Float(f)) => Ok(ConstValue::Float(-f)),
+        _ => Err(ConstEvalError::Invalid(format!(
+            "operator {:?} cannot be applied to {}",
+            op, value
+        ))),
+    }
+}
+
+fn evaluate_binary(
+    op: &BinaryOp,
+    left: ConstValue,
+    right: ConstValue,
+) -> Result<ConstValue, ConstEvalError> {
+    use ConstValue::{Bool, Float, Int};
+
+    let overflow = || ConstEvalError::Invalid(format!("{:?} overflows", op));
+    match (left, right) {
+        (Int(a), Int(b)) => match op {
+            BinaryOp::Add => a.checked_add(b).map(Int).ok_or_else(overflow),
+            BinaryOp::Sub => a.checked_sub(b).map(Int).ok_or_else(overflow),
+            BinaryOp::Mul => a.checked_mul(b).map(Int).ok_or_else(overflow),
+            BinaryOp::Div | BinaryOp::Mod if b == 0 => {
+                Err(ConstEvalError::Invalid("division by zero".to_string()))
+            }
+            BinaryOp::Div => a.checked_div(b).map(Int).ok_or_else(overflow),
+            BinaryOp::Mod => a.checked_rem(b).map(Int).ok_or_else(overflow),
+            BinaryOp::BitAnd => Ok(Int(a & b)),
+            BinaryOp::BitOr => Ok(Int(a | b)),
+            BinaryOp::BitXor => Ok(Int(a ^ b)),
+            BinaryOp::Shl => u32::try_from(b)
+                .ok()
+                .and_then(|b| a.checked_shl(b))
+                .map(Int)
+                .ok_or_else(overflow),
+            BinaryOp::Shr => u32::try_from(b)
+                .ok()
+                .and_then(|b| a.checked_shr(b))
+                .map(Int)
+                .ok_or_else(overflow),
+            _ => compare(op, a.partial_cmp(&b)),
+        },
+        (Float(a), Float(b)) => match op {
+            BinaryOp::Add => Ok(Float(a + b)),
+            BinaryOp::Sub => Ok(Float(a - b)),
+            BinaryOp::Mul => Ok(Float(a * b)),
+            BinaryOp::Div => Ok(Float(a / b)),
+            _ => compare(op, a.partial_cmp(&b)),
+        },
+        (Bool(a), Bool(b)) => match op {
+            BinaryOp::And => Ok(Bool(a && b)),
+            BinaryOp::Or => Ok(Bool(a || b)),
+            BinaryOp::Eq => Ok(Bool(a == b)),
+            BinaryOp::Ne => Ok(Bool(a != b)),
+            _ => Err(ConstEvalError::Invalid(format!(
+                "operator {:?} cannot be applied to bool",
+                op
+            ))),
+        },
+        _ => Err(ConstEvalError::Invalid(format!(
+            "mismatched operands {} and {}",
+            left, right
+        ))),
+    }
+}
+
+fn compare(
+    op: &BinaryOp,
+    ordering: Option<std::cmp::Ordering>,
+) -> Result<ConstValue, ConstEvalError> {
+    let ordering =
+        ordering.ok_or_else(|| ConstEvalError::Invalid("comparison with NaN".to_string()))?;
+    let result = match op {
+        BinaryOp::Eq => ordering.is_eq(),
+        BinaryOp::Ne => ordering.is_ne(),
+        BinaryOp::Lt => ordering.is_lt(),
+        BinaryOp::Gt => ordering.is_gt(),
+        BinaryOp::Le => ordering.is_le(),
+        BinaryOp::Ge => ordering.is_ge(),
+        _ => {
+            return Err(ConstEvalError::Invalid(format!(
+                "operator {:?} is not allowed in a constant expression",
+                op
+            )))
+        }
+    };
+    Ok(ConstValue::Bool(result))
+}
+
+fn cast(value: ConstValue, ty: &Type, platform: &Platform) -> Result<ConstValue, ConstEvalError> {
+    let primitive = match ty {
+        Type::Primitive(primitive) => primitive.clone(),
+        Type::Ident(ident) => platform.c_type(&ident.name).ok_or_else(|| {
+            ConstEvalError::NotConstant("a cast to a non-primitive type".to_string())
+        })?,
+        _ => {
+            return Err(ConstEvalError::NotConstant(
+                "a cast to a non-primitive type".to_string(),
+            ))
+        }
+    };
+    let narrow_pointers = platform.pointer_width == 4;
+    let as_int = match value {
+        ConstValue::Int(i) => i,
+        ConstValue::Float(f) => f as i64,
+        ConstValue::Bool(b) => b as i64,
+    };
+    Ok(match primitive {
+        PrimitiveType::I8 => ConstValue::Int(as_int as i8 as i64),
+        PrimitiveType::I16 => ConstValue::Int(as_int as i16 as i64),
+        PrimitiveType::Int | PrimitiveType::I32 => ConstValue::Int(as_int as i32 as i64),
+        PrimitiveType::U8 => ConstValue::Int(as_int as u8 as i64),
+        PrimitiveType::U16 => ConstValue::Int(as_int as u16 as i64),
+        PrimitiveType::U32 => ConstValue::Int(as_int as u32 as i64),
+        PrimitiveType::Isize if narrow_pointers => ConstValue::Int(as_int as i32 as i64),
+        PrimitiveType::Usize if narrow_pointers => ConstValue::Int(as_int as u32 as i64),
+        PrimitiveType::I64 | PrimitiveType::U64 | PrimitiveType::Isize | PrimitiveType::Usize => {
+            ConstValue::Int(as_int)
+        }
+        PrimitiveType::Char => ConstValue::Int(as_int as u8 as i64),
+        PrimitiveType::Float | PrimitiveType::F32 | PrimitiveType::F64 => match value {
+            ConstValue::Float(f) => ConstValue::Float(f),
+            _ => ConstValue::Float(as_int as f64),
+        },
+        PrimitiveType::Bool => ConstValue::Bool(as_int != 0),
+        PrimitiveType::Void => {
+            return Err(ConstEvalError::Invalid("cannot cast to void".to_string()))
+        }
+    })
+}
+
+/// Size in bytes of a primitive type, matching the Rust type it becomes
+pub fn primitive_size(ty: &Type, platform: &Platform) -> Result<usize, ConstEvalError> {
+    match ty {
+        Type::Primitive(primitive) => Ok(platform.size_of(primitive)),
+        Type::Ident(ident) => match platform.c_type(&ident.name) {
+            Some(_) => Ok(platform.long_width),
+            None => Err(ConstEvalError::Layout(ident.name.clone())),
+        },
+        _ => Err(ConstEvalError::Layout(format!("{:?}", ty))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::crusty_peg_parser;
+
+    fn eval(source: &str) -> Result<ConstValue, ConstEvalError> {
+        let consts = HashMap::from([("SIZE".to_string(), ConstValue::Int(16))]);
+        evaluate(
+            &crusty_peg_parser::expr(source).unwrap(),
+            &consts,
+            &Platform::host(),
+        )
+    }
+
+    #[test]
+    fn test_evaluates_arithmetic_and_comparisons() {
+        assert_eq!(eval("SIZE * 4 + 1"), Ok(ConstValue::Int(65)));
+        assert_eq!(eval("(SIZE & (SIZE - 1)) == 0"), Ok(ConstValue::Bool(true)));
+        assert_eq!(eval("1 << 4 >= SIZE && !false"), Ok(ConstValue::Bool(true)));
+        assert_eq!(eval("SIZE > 8 ? 1 : 2"), Ok(ConstValue::Int(1)));
+        assert_eq!(eval("sizeof(i64) == 8"), Ok(ConstValue::Bool(true)));
+        assert_eq!(eval("(u32)(0 - 1)"), Ok(ConstValue::Int(u32::MAX as i64)));
+    }
+
+    #[test]
+    fn test_reports_what_cannot_be_evaluated() {
+        assert_eq!(
+            eval("count > 0"),
+            Err(ConstEvalError::NotConstant("'count'".to_string()))
+        );
+        assert_eq!(
+            eval("sizeof(Header) == 16"),
+            Err(ConstEvalError::Layout("Header".to_string()))
+        );
+        assert_eq!(
+            eval("SIZE / 0"),
+            Err(ConstEvalError::Invalid("division by zero".to_string()))
+        );
+        assert!(matches!(
+            eval("9223372036854775807 + 1"),
+            Err(ConstEvalError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_evaluation_stops_at_the_step_limit() {
+        let expr = crusty_peg_parser::expr("1 + 2 * 3 - 4").unwrap();
+        let platform = Platform::host();
+        assert_eq!(
+            evaluate_with_limit(&expr, &HashMap::new(), &platform, 7),
+            Ok(ConstValue::Int(3))
+        );
+        let error = evaluate_with_limit(&expr, &HashMap::new(), &platform, 6).unwrap_err();
+        assert_eq!(error, ConstEvalError::StepLimit(6));
+        assert_eq!(error.to_string(), "evaluation exceeded 6 steps");
+    }
+
+    #[test]
+    fn test_portable_values_hold_for_every_target() {
+        let portable = |source: &str| {
+            evaluate_portable(&crusty_peg_parser::expr(source).unwrap(), &HashMap::new())
+        };
+        assert_eq!(portable("sizeof(i64) * 2"), Ok(ConstValue::Int(16)));
+        assert_eq!(portable("(usize)(255)"), Ok(ConstValue::Int(255)));
+        assert_eq!(
+            portable("sizeof(usize)"),
+            Err(ConstEvalError::NotConstant(
+                "a value that differs between targets".to_string()
+            ))
+        );
+    }
+}
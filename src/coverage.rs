@@ -0,0 +1,262 @@
+// Copyright (c) 2026 Mark Ferrell
+// Licensed under the MIT License. See LICENSE.txt in the project root.
+
+//! Line coverage of the Crusty source from an instrumented test run.
+//!
+//! `--coverage` builds the `#[test]` functions into a test harness with
+//! rustc's `-C instrument-coverage` and runs it. The profile it writes is
+//! merged with `llvm-profdata` and exported as LCOV by `llvm-cov`, which
+//! counts how often each line of the generated Rust ran. Each count is then
+//! carried through the source map to the Crusty line the statement or item
+//! on that line came from, and the source is listed with its counts in the
+//! style of gcov:
+//!
+//! ```text
+//! sign.crst: 3 of 4 lines run (75.0%)
+//!         2:    1:int sign(int x) {
+//!         2:    2:    if (x > 0) {
+//!         2:    3:        return 1;
+//!         -:    4:    }
+//!     #####:    5:    return 0;
+//!         -:    6:}
+//! ```
+//!
+//! The LLVM tools must read the profile format of rustc's LLVM, so the ones
+//! of the `llvm-tools` rustup component are preferred over any on `PATH`.
+
+use crate::source_map::SourceMap;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// How often each line of one Crusty file ran
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileCoverage {
+    /// The Crusty file, as named in the source map
+    pub path: String,
+    /// Run count of each line that starts an item or statement
+    pub lines: BTreeMap<usize, u64>,
+}
+
+impl FileCoverage {
+    /// Number of lines that ran at least once
+    pub fn lines_run(&self) -> usize {
+        self.lines.values().filter(|&&count| count > 0).count()
+    }
+}
+
+/// Run counts of the lines of `rust_file` in an LCOV report
+///
+/// Records of other files, such as modules written by `--split-modules`,
+/// are skipped; they are matched by file name, as llvm-cov reports the path
+/// rustc was given.
+pub fn parse_lcov(lcov: &str, rust_file: &Path) -> BTreeMap<usize, u64> {
+    let mut counts = BTreeMap::new();
+    let mut in_file = false;
+    for line in lcov.lines() {
+        if let Some(path) = line.strip_prefix("SF:") {
+            in_file = Path::new(path).file_name() == rust_file.file_name();
+        } else if line == "end_of_record" {
+            in_file = false;
+        } else if let Some(record) = line.strip_prefix("DA:").filter(|_| in_file) {
+            let mut fields = record.split(',');
+            let line = fields.next().and_then(|line| line.parse::<usize>().ok());
+            let count = fields.next().and_then(|count| count.parse::<u64>().ok());
+            if let (Some(line), Some(count)) = (line, count) {
+                let entry = counts.entry(line).or_insert(0);
+                *entry = (*entry).max(count);
+            }
+        }
+    }
+    counts
+}
+
+/// Carry the run counts of generated lines to the Crusty lines they were
+/// generated from, one entry per file of `map`
+///
+/// Only the first generated line of an item or statement is counted for
+/// it, so the closing braces of a loop do not stand in for its body. A
+/// Crusty line that several statements start on ran as often as the most
+/// frequent of them.
+pub fn map_to_source(map: &SourceMap, counts: &BTreeMap<usize, u64>) -> Vec<FileCoverage> {
+    let mut files: Vec<FileCoverage> = map
+        .sources
+        .iter()
+        .map(|path| FileCoverage {
+            path: path.clone(),
+            lines: BTreeMap::new(),
+        })
+        .collect();
+    for mapping in &map.mappings {
+        let Some(&count) = counts.get(&mapping.generated_line) else {
+            continue;
+        };
+        let entry = files[mapping.source].lines.entry(mapping.line).or_insert(0);
+        *entry = (*entry).max(count);
+    }
+    files.retain(|file| !file.lines.is_empty());
+    files
+}
+
+/// List each file with its run counts: `-` marks lines that start no
+/// statement and `#####` those that never ran
+///
+/// `source_text` reads a Crusty file; files it cannot read are listed by
+/// their summary alone.
+pub fn format_report(
+    files: &[FileCoverage],
+    source_text: impl Fn(&str) -> Option<String>,
+) -> String {
+    let mut report = String::new();
+    for file in files {
+        let total = file.lines.len();
+        report.push_str(&format!(
+            "{}: {} of {} lines run ({:.1}%)\n",
+            file.path,
+            file.lines_run(),
+            total,
+            file.lines_run() as f64 * 100.0 / total.max(1) as f64
+        ));
+        let Some(text) = source_text(&file.path) else {
+            continue;
+        };
+        for (index, line) in text.lines().enumerate() {
+            let count = match file.lines.get(&(index + 1)) {
+                Some(0) => "#####".to_string(),
+                Some(count) => count.to_string(),
+                None => "-".to_string(),
+            };
+            report.push_str(&format!("{:>9}:{:>5}:{}\n", count, index + 1, line));
+        }
+    }
+    report
+}
+
+/// Path of an LLVM tool: the `llvm-tools` component's copy beside rustc's
+/// target libraries when installed, otherwise `name` to search `PATH` for
+fn llvm_tool(name: &str) -> PathBuf {
+    Command::new("rustc")
+        .args(["--print", "target-libdir"])
+        .output()
+        .ok()
+        .and_then(|output| {
+            let libdir = PathBuf::from(String::from_utf8(output.stdout).ok()?.trim());
+            let tool = libdir
+                .parent()?
+                .join("bin")
+                .join(name)
+                .with_extension(std::env::consts::EXE_EXTENSION);
+            tool.is_file().then_some(tool)
+        })
+        .unwrap_or_else(|| PathBuf::from(name))
+}
+
+/// Run an LLVM tool, returning its standard output
+fn run_llvm_tool(name: &str, args: &[&std::ffi::OsStr], verbose: bool) -> Result<String, String> {
+    let tool = llvm_tool(name);
+    if verbose {
+        println!("Invoking {}: {:?}", name, args);
+    }
+    let output = Command::new(&tool).args(args).output().map_err(|e| {
+        format!(
+            "failed to execute {}: {}; install it with `rustup component add llvm-tools`",
+            tool.display(),
+            e
+        )
+    })?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} failed: {}\n(the tool must be from the LLVM version of rustc, as in `rustup component add llvm-tools`)",
+            name,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Run the instrumented test harness built at `harness` and export the
+/// line coverage of its run as LCOV
+///
+/// The raw and merged profiles are written beside the harness
+/// (`main.profraw` and `main.profdata`) and removed once exported.
+/// Returns whether the tests passed along with the report, which covers
+/// failing runs too.
+pub fn run_tests(harness: &Path, verbose: bool) -> Result<(bool, String), String> {
+    let raw = harness.with_extension("profraw");
+    let merged = harness.with_extension("profdata");
+
+    // A bare file name would be searched for on PATH
+    let status = Command::new(Path::new(".").join(harness))
+        .env("LLVM_PROFILE_FILE", &raw)
+        .status()
+        .map_err(|e| format!("failed to run test harness {}: {}", harness.display(), e))?;
+
+    let lcov = run_llvm_tool(
+        "llvm-profdata",
+        &[
+            "merge".as_ref(),
+            "-sparse".as_ref(),
+            raw.as_os_str(),
+            "-o".as_ref(),
+            merged.as_os_str(),
+        ],
+        verbose,
+    )
+    .and_then(|_| {
+        let profile = format!("-instr-profile={}", merged.display());
+        run_llvm_tool(
+            "llvm-cov",
+            &[
+                "export".as_ref(),
+                "-format=lcov".as_ref(),
+                profile.as_ref(),
+                harness.as_os_str(),
+            ],
+            verbose,
+        )
+    });
+    let _ = std::fs::remove_file(&raw);
+    let _ = std::fs::remove_file(&merged);
+    Ok((status.success(), lcov?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source_map::Mapping;
+
+    const LCOV: &str = "SF:/src/other.rs\nDA:3,9\nend_of_record\nSF:sign.rs\nFN:3,_RNvCs_4sign4sign\nDA:3,2\nDA:4,2\nDA:5,2\nDA:6,0\nDA:7,2\nDA:9,0\nend_of_record\n";
+
+    const SOURCE: &str =
+        "int sign(int x) {\n    if (x > 0) {\n        return 1;\n    }\n    return 0;\n}\n";
+
+    fn mapping(generated_line: usize, line: usize) -> Mapping {
+        Mapping {
+            generated_line,
+            source: 0,
+            line,
+            column: 5,
+        }
+    }
+
+    #[test]
+    fn test_maps_line_counts_to_crusty_lines() {
+        let counts = parse_lcov(LCOV, Path::new("sign.rs"));
+        assert_eq!(counts.get(&3), Some(&2));
+        assert_eq!(counts.get(&6), Some(&0));
+        assert_eq!(counts.len(), 6);
+
+        let mut map = SourceMap::new("sign.rs");
+        map.sources = vec!["sign.crst".to_string(), "unused.crst".to_string()];
+        map.mappings = vec![mapping(3, 1), mapping(4, 2), mapping(5, 3), mapping(6, 5)];
+        let files = map_to_source(&map, &counts);
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].lines_run(), 3);
+
+        let report = format_report(&files, |_| Some(SOURCE.to_string()));
+        assert_eq!(
+            report,
+            "sign.crst: 3 of 4 lines run (75.0%)\n        2:    1:int sign(int x) {\n        2:    2:    if (x > 0) {\n        2:    3:        return 1;\n        -:    4:    }\n    #####:    5:    return 0;\n        -:    6:}\n"
+        );
+    }
+}
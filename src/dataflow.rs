@@ -0,0 +1,745 @@
+// Copyright (c) 2026 Mark Ferrell
+// Licensed under the MIT License. See LICENSE.txt in the project root.
+
+//! Definite-initialization and reachability analysis of function bodies.
+//!
+//! C lets a local be declared without a value and read before one is
+//! stored, which is undefined behavior; Rust refuses to compile such a
+//! read. Following every path through a function, the pass finds
+//!
+//! ```text
+//! var int total;
+//! if (n > 0) { total = n; }
+//! return total;        // 'total' may be read before it is assigned
+//!
+//! let int limit;
+//! limit = 10;
+//! limit = 20;          // a `let` may be assigned only once
+//!
+//! return 0;
+//! cleanup();           // unreachable statement after `return`
+//! ```
+//!
+//! A variable is tracked from a declaration without an initializer to the
+//! assignments that reach every path. Loops are followed until the state
+//! at their head stops changing, so an assignment late in the body counts
+//! for the reads of the next iteration. `rust { }` blocks are opaque: a
+//! variable they mention is taken as assigned.
+
+use crate::ast::{BinaryOp, Block, Expression, Literal, Statement, UnaryOp};
+use crate::error::Position;
+use crate::macros::{child_expressions, is_name_token, pattern_bindings};
+use std::collections::{BTreeMap, HashSet};
+
+/// Loops are followed at most this many times to reach a fixed point; the
+/// state only grows, so it settles after a few
+const MAX_LOOP_PASSES: usize = 8;
+
+/// How the findings of the analysis are reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum FlowChecks {
+    /// As warnings
+    #[default]
+    Warn,
+    /// As errors, failing the compilation
+    Deny,
+}
+
+/// A read of an unassigned variable, a repeated assignment of a `let`, or
+/// an unreachable statement
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlowFinding {
+    /// Function the finding is in
+    pub function: String,
+    /// Where the statement starts, when it was parsed from source
+    pub position: Option<Position>,
+    pub message: String,
+}
+
+impl std::fmt::Display for FlowFinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.position {
+            Some(position) => write!(
+                f,
+                "in function '{}', line {}: {}",
+                self.function, position.line, self.message
+            ),
+            None => write!(f, "in function '{}': {}", self.function, self.message),
+        }
+    }
+}
+
+/// Whether a tracked variable holds a value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Init {
+    Unassigned,
+    /// Assigned on some paths only
+    Maybe,
+    Assigned,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Var {
+    init: Init,
+    /// Declared with `let`, so it may be assigned once
+    once: bool,
+}
+
+/// The tracked variables in scope; `None` when the point cannot be reached
+type State = Option<BTreeMap<String, Var>>;
+
+/// Merge the states of two paths that meet
+fn join(a: State, b: State) -> State {
+    let (Some(mut a), Some(b)) = (a.clone(), b.clone()) else {
+        return a.or(b);
+    };
+    for (name, var) in &b {
+        let merged = match a.get(name) {
+            Some(other) if other.init == var.init => *other,
+            Some(other) => Var {
+                init: Init::Maybe,
+                once: other.once || var.once,
+            },
+            // Untracked on the other path: a declaration restored on exit
+            None => Var {
+                init: Init::Maybe,
+                ..*var
+            },
+        };
+        a.insert(name.clone(), merged);
+    }
+    for (name, var) in a.iter_mut() {
+        if !b.contains_key(name) {
+            var.init = Init::Maybe;
+        }
+    }
+    Some(a)
+}
+
+/// Targets of `break` and `continue` in a loop being followed
+struct LoopFrame {
+    label: Option<String>,
+    /// Number of scopes open outside the loop
+    depth: usize,
+    breaks: State,
+    continues: State,
+}
+
+/// Check the body of function `function`, and of the functions nested in it
+pub fn check_function(function: &str, body: &Block) -> Vec<FlowFinding> {
+    let mut checker = Checker {
+        function: function.to_string(),
+        findings: Vec::new(),
+        reported: HashSet::new(),
+        reporting: true,
+        position: None,
+        scopes: Vec::new(),
+        loops: Vec::new(),
+    };
+    checker.block(body, Some(BTreeMap::new()));
+    checker.findings
+}
+
+struct Checker {
+    function: String,
+    findings: Vec<FlowFinding>,
+    /// Findings made so far, each reported once
+    reported: HashSet<(String, Option<(usize, usize)>)>,
+    /// Off while a loop is followed to its fixed point
+    reporting: bool,
+    /// Start of the statement being checked
+    position: Option<Position>,
+    /// For each open block, the variables it declares and what they
+    /// shadowed
+    scopes: Vec<Vec<(String, Option<Var>)>>,
+    loops: Vec<LoopFrame>,
+}
+
+impl Checker {
+    /// Report `message` at the statement being checked, once per function
+    /// unless `each_statement`
+    fn report(&mut self, message: String, each_statement: bool) {
+        if !self.reporting {
+            return;
+        }
+        let key = (
+            message.clone(),
+            self.position
+                .filter(|_| each_statement)
+                .map(|position| (position.line, position.column)),
+        );
+        if self.reported.insert(key) {
+            self.findings.push(FlowFinding {
+                function: self.function.clone(),
+                position: self.position,
+                message,
+            });
+        }
+    }
+
+    fn block(&mut self, block: &Block, mut state: State) -> State {
+        self.scopes.push(Vec::new());
+        let enclosing = self.position;
+        let mut exit: Option<&'static str> = None;
+        let mut unreachable_reported = false;
+        for (i, stmt) in block.statements.iter().enumerate() {
+            self.position = block.positions.get(i).copied().or(enclosing);
+            if let Statement::NestedFunction { name, body, .. } = stmt {
+                if self.reporting {
+                    let nested = check_function(&name.name, body);
+                    self.findings.extend(nested);
+                }
+                continue;
+            }
+            if state.is_none() {
+                if !unreachable_reported {
+                    let message = match exit {
+                        Some(keyword) => {
+                            format!("statement is unreachable after `{}`", keyword)
+                        }
+                        None => {
+                            "statement is unreachable: control never leaves the statement before it"
+                                .to_string()
+                        }
+                    };
+                    self.report(message, true);
+                    unreachable_reported = true;
+                }
+                continue;
+            }
+            state = self.statement(stmt, state);
+            exit = match stmt {
+                Statement::Return(_) => Some("return"),
+                Statement::Break(_) => Some("break"),
+                Statement::Continue(_) => Some("continue"),
+                _ => None,
+            };
+        }
+        self.position = enclosing;
+        let declared = self.scopes.pop().unwrap_or_default();
+        state.map(|mut vars| {
+            restore(&mut vars, &declared);
+            vars
+        })
+    }
+
+    /// The state of `state` once the blocks opened inside the loop at
+    /// `depth` are left, as by `break` or `continue`
+    fn unwind(&self, state: &State, depth: usize) -> State {
+        let mut vars = state.clone()?;
+        for declared in self.scopes[depth..].iter().rev() {
+            restore(&mut vars, declared);
+        }
+        Some(vars)
+    }
+
+    fn declare(&mut self, name: &str, var: Option<Var>, state: &mut State) {
+        let Some(vars) = state else {
+            return;
+        };
+        if let Some(scope) = self.scopes.last_mut() {
+            if !scope.iter().any(|(declared, _)| declared == name) {
+                scope.push((name.to_string(), vars.get(name).copied()));
+            }
+        }
+        match var {
+            Some(var) => vars.insert(name.to_string(), var),
+            None => vars.remove(name),
+        };
+    }
+
+    fn statement(&mut self, stmt: &Statement, mut state: State) -> State {
+        match stmt {
+            Statement::Let {
+                name,
+                init,
+                mutable,
+                ..
+            } => {
+                let var = match init {
+                    Some(init) => {
+                        self.expression(init, &mut state);
+                        None
+                    }
+                    None => Some(Var {
+                        init: Init::Unassigned,
+                        once: !mutable,
+                    }),
+                };
+                self.declare(&name.name, var, &mut state);
+                state
+            }
+            Statement::Var { name, init, .. } => {
+                let var = match init {
+                    Some(init) => {
+                        self.expression(init, &mut state);
+                        None
+                    }
+                    None => Some(Var {
+                        init: Init::Unassigned,
+                        once: false,
+                    }),
+                };
+                self.declare(&name.name, var, &mut state);
+                state
+            }
+            Statement::Const { name, value, .. } => {
+                self.expression(value, &mut state);
+                self.declare(&name.name, None, &mut state);
+                state
+            }
+            Statement::Expr(expr) => {
+                self.expression(expr, &mut state);
+                state
+            }
+            Statement::Return(value) => {
+                if let Some(value) = value {
+                    self.expression(value, &mut state);
+                }
+                None
+            }
+            Statement::If {
+                condition,
+                then_block,
+                else_block,
+            } => {
+                self.expression(condition, &mut state);
+                let then_state = self.block(then_block, state.clone());
+                let else_state = match else_block {
+                    Some(else_block) => self.block(else_block, state),
+                    None => state,
+                };
+                join(then_state, else_state)
+            }
+            Statement::While {
+                label,
+                condition,
+                body,
+            } => {
+                let endless = matches!(condition, Expression::Literal(Literal::Bool(true)));
+                self.follow_loop(label, state, |checker, mut head| {
+                    checker.expression(condition, &mut head);
+                    let exit = if endless { None } else { head.clone() };
+                    let end = checker.block(body, head);
+                    (end, exit)
+                })
+            }
+            Statement::DoWhile { body, condition } => {
+                // The body runs before the condition is first tested
+                self.follow_loop(&None, state, |checker, head| {
+                    let mut end = checker.block(body, head);
+                    let continues = checker
+                        .loops
+                        .last_mut()
+                        .and_then(|frame| frame.continues.take());
+                    end = join(end, continues);
+                    checker.expression(condition, &mut end);
+                    (end.clone(), end)
+                })
+            }
+            Statement::For {
+                label,
+                init,
+                condition,
+                increment,
+                body,
+            } => {
+                // The loop variable is scoped to the loop
+                self.scopes.push(Vec::new());
+                let state = self.statement(init, state);
+                let after = self.follow_loop(label, state, |checker, mut head| {
+                    checker.expression(condition, &mut head);
+                    let exit = head.clone();
+                    let end = checker.block(body, head);
+                    let continues = checker
+                        .loops
+                        .last_mut()
+                        .and_then(|frame| frame.continues.take());
+                    let mut end = join(end, continues);
+                    checker.expression(increment, &mut end);
+                    (end, exit)
+                });
+                let declared = self.scopes.pop().unwrap_or_default();
+                after.map(|mut vars| {
+                    restore(&mut vars, &declared);
+                    vars
+                })
+            }
+            Statement::ForIn {
+                label,
+                var,
+                iter,
+                body,
+            } => {
+                self.expression(iter, &mut state);
+                self.follow_loop(label, state, |checker, head| {
+                    let exit = head.clone();
+                    checker.scopes.push(Vec::new());
+                    let mut entry = head;
+                    checker.declare(&var.name, None, &mut entry);
+                    let end = checker.block(body, entry);
+                    let declared = checker.scopes.pop().unwrap_or_default();
+                    let end = end.map(|mut vars| {
+                        restore(&mut vars, &declared);
+                        vars
+                    });
+                    (end, exit)
+                })
+            }
+            Statement::Switch {
+                expr,
+                cases,
+                default,
+            } => {
+                self.expression(expr, &mut state);
+                let mut after = match default {
+                    Some(default) => self.block(default, state.clone()),
+                    None => state.clone(),
+                };
+                for case in cases {
+                    let case_state = self.block(&case.body, state.clone());
+                    after = join(after, case_state);
+                }
+                after
+            }
+            Statement::Break(label) | Statement::Continue(label) => {
+                let target = self.loops.iter().rposition(|frame| match label {
+                    Some(label) => frame.label.as_deref() == Some(label.name.as_str()),
+                    None => true,
+                });
+                if let Some(target) = target {
+                    let unwound = self.unwind(&state, self.loops[target].depth);
+                    let frame = &mut self.loops[target];
+                    if matches!(stmt, Statement::Break(_)) {
+                        frame.breaks = join(frame.breaks.take(), unwound);
+                    } else {
+                        frame.continues = join(frame.continues.take(), unwound);
+                    }
+                }
+                None
+            }
+            Statement::Unsafe(block) | Statement::Block(block) => self.block(block, state),
+            Statement::StaticAssert(_) | Statement::NestedFunction { .. } => state,
+        }
+    }
+
+    /// Follow a loop entered with `entry` until the state at its head
+    /// settles, then once more reporting what is found
+    ///
+    /// `pass` checks one iteration from the head, returning the state at
+    /// its end, which flows back to the head, and the state in which the
+    /// loop ends without `break`. The state after the loop joins the latter
+    /// with those of its `break`s.
+    fn follow_loop(
+        &mut self,
+        label: &Option<crate::ast::Ident>,
+        entry: State,
+        mut pass: impl FnMut(&mut Self, State) -> (State, State),
+    ) -> State {
+        let reporting = self.reporting;
+        self.reporting = false;
+        let mut head = entry.clone();
+        for _ in 0..MAX_LOOP_PASSES {
+            let (end, _) = self.loop_pass(label, head.clone(), &mut pass);
+            let next = join(entry.clone(), end);
+            if next == head {
+                break;
+            }
+            head = next;
+        }
+        self.reporting = reporting;
+        let (_, after) = self.loop_pass(label, head, &mut pass);
+        after
+    }
+
+    /// One pass over a loop body from `head`: the state flowing back to the
+    /// head, and the state after the loop
+    fn loop_pass(
+        &mut self,
+        label: &Option<crate::ast::Ident>,
+        head: State,
+        pass: &mut impl FnMut(&mut Self, State) -> (State, State),
+    ) -> (State, State) {
+        self.loops.push(LoopFrame {
+            label: label.as_ref().map(|label| label.name.clone()),
+            depth: self.scopes.len(),
+            breaks: None,
+            continues: None,
+        });
+        let (end, exit) = pass(self, head);
+        let frame = self.loops.pop().expect("the frame pushed above");
+        (join(end, frame.continues), join(exit, frame.breaks))
+    }
+
+    fn expression(&mut self, expr: &Expression, state: &mut State) {
+        match expr {
+            Expression::Ident(name) => self.read(&name.name, state),
+            Expression::Binary { op, left, right } => match op {
+                BinaryOp::Assign => {
+                    self.expression(right, state);
+                    match left.as_ref() {
+                        Expression::Ident(name) => self.assign(&name.name, state),
+                        // Storing into a field, element or pointee reads
+                        // the variable holding it
+                        place => self.expression(place, state),
+                    }
+                }
+                BinaryOp::And | BinaryOp::Or => {
+                    self.expression(left, state);
+                    let mut right_state = state.clone();
+                    self.expression(right, &mut right_state);
+                    *state = join(state.take(), right_state);
+                }
+                op if is_compound_assignment(op) => {
+                    self.expression(right, state);
+                    self.expression(left, state);
+                    if let Expression::Ident(name) = left.as_ref() {
+                        self.assign(&name.name, state);
+                    }
+                }
+                _ => {
+                    self.expression(left, state);
+                    self.expression(right, state);
+                }
+            },
+            Expression::Unary { op, expr } => {
+                self.expression(expr, state);
+                if let (
+                    UnaryOp::PreInc | UnaryOp::PreDec | UnaryOp::PostInc | UnaryOp::PostDec,
+                    Expression::Ident(name),
+                ) = (op, expr.as_ref())
+                {
+                    self.assign(&name.name, state);
+                }
+            }
+            Expression::Ternary {
+                condition,
+                then_expr,
+                else_expr,
+            } => {
+                self.expression(condition, state);
+                let mut else_state = state.clone();
+                self.expression(then_expr, state);
+                self.expression(else_expr, &mut else_state);
+                *state = join(state.take(), else_state);
+            }
+            Expression::Match { expr, arms } => {
+                self.expression(expr, state);
+                let mut after: State = None;
+                for arm in arms {
+                    let mut bound = HashSet::new();
+                    pattern_bindings(&arm.pattern, &mut bound);
+                    self.scopes.push(Vec::new());
+                    let mut arm_state = state.clone();
+                    for name in &bound {
+                        self.declare(name, None, &mut arm_state);
+                    }
+                    self.expression(&arm.body, &mut arm_state);
+                    let declared = self.scopes.pop().unwrap_or_default();
+                    if let Some(vars) = arm_state.as_mut() {
+                        restore(vars, &declared);
+                    }
+                    after = join(after, arm_state);
+                }
+                if !arms.is_empty() {
+                    *state = after;
+                }
+            }
+            // The body runs when the lambda is called, but the variables it
+            // captures must be assigned where it is made; what it assigns
+            // may never happen
+            Expression::Lambda { params, body, .. } => {
+                let mut inner = state.clone();
+                self.scopes.push(Vec::new());
+                for param in params {
+                    self.declare(&param.name.name, None, &mut inner);
+                }
+                self.block(body, inner);
+                self.scopes.pop();
+            }
+            // The operand is not evaluated
+            Expression::Sizeof { .. }
+            | Expression::SizeofExpr { .. }
+            | Expression::Alignof { .. }
+            | Expression::AlignofExpr { .. } => {}
+            // Format macros borrow their arguments
+            Expression::MacroCall { args, .. } => {
+                let mut after_dot = false;
+                for token in args {
+                    // A name after a dot is a field, as in `p.x`
+                    if is_name_token(token) && !after_dot {
+                        self.read(&token.text, state);
+                    }
+                    after_dot = token.text == ".";
+                }
+            }
+            Expression::RustBlock { tokens } => {
+                if let Some(vars) = state {
+                    for token in tokens {
+                        if is_name_token(token) {
+                            vars.remove(&token.text);
+                        }
+                    }
+                }
+            }
+            _ => {
+                for child in child_expressions(expr) {
+                    self.expression(child, state);
+                }
+            }
+        }
+    }
+
+    fn read(&mut self, name: &str, state: &State) {
+        let Some(var) = state.as_ref().and_then(|vars| vars.get(name)).copied() else {
+            return;
+        };
+        let message = match var.init {
+            Init::Assigned => return,
+            Init::Unassigned => format!("'{}' is read before it is assigned", name),
+            Init::Maybe => format!(
+                "'{}' may be read before it is assigned; not every path to the read assigns it",
+                name
+            ),
+        };
+        self.report(message, false);
+    }
+
+    fn assign(&mut self, name: &str, state: &mut State) {
+        let Some(vars) = state else {
+            return;
+        };
+        let Some(var) = vars.get(name).copied() else {
+            return;
+        };
+        if var.once && var.init != Init::Unassigned {
+            self.report(format!(
+                "'{}' is declared with `let` and {} assigned here; declare it with `var` to assign it again",
+                name,
+                if var.init == Init::Assigned {
+                    "is already"
+                } else {
+                    "may already be"
+                }
+            ), false);
+        }
+        if var.once {
+            if let Some(vars) = state {
+                vars.insert(
+                    name.to_string(),
+                    Var {
+                        init: Init::Assigned,
+                        once: true,
+                    },
+                );
+            }
+        } else if let Some(vars) = state {
+            vars.remove(name);
+        }
+    }
+}
+
+/// Undo the declarations of a block that is left
+fn restore(vars: &mut BTreeMap<String, Var>, declared: &[(String, Option<Var>)]) {
+    for (name, shadowed) in declared.iter().rev() {
+        match shadowed {
+            Some(var) => vars.insert(name.clone(), *var),
+            None => vars.remove(name),
+        };
+    }
+}
+
+fn is_compound_assignment(op: &BinaryOp) -> bool {
+    op.is_assignment() && *op != BinaryOp::Assign
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Item;
+    use crate::parser::Parser;
+
+    fn check(source: &str) -> Vec<String> {
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+        file.items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Function(func) => Some(check_function(&func.name.name, &func.body)),
+                _ => None,
+            })
+            .flatten()
+            .map(|finding| finding.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_reads_before_assignment() {
+        let found = check(
+            "int f(int n) {\n    var int total;\n    var int count;\n    if (n > 0) {\n        total = n;\n        count = 1;\n    } else {\n        count = 0;\n    }\n    let int limit;\n    return total + count + limit;\n}\n",
+        );
+        assert_eq!(
+            found,
+            [
+                "in function 'f', line 11: 'total' may be read before it is assigned; not every path to the read assigns it",
+                "in function 'f', line 11: 'limit' is read before it is assigned",
+            ]
+        );
+
+        let found = check("void show() {\n    var int n;\n    __println__(\"{}\", n);\n}\n");
+        assert_eq!(
+            found,
+            ["in function 'show', line 3: 'n' is read before it is assigned"]
+        );
+    }
+
+    #[test]
+    fn test_loops_and_scopes() {
+        let found = check(
+            "int f(int n) {\n    var int last;\n    var int i = 0;\n    while (i < n) {\n        if (i > 0) {\n            n = last;\n        }\n        last = i;\n        i++;\n    }\n    var int seen;\n    .outer: loop {\n        seen = 1;\n        break outer;\n    }\n    if (n > 0) {\n        var int seen;\n        seen = 2;\n    }\n    return seen;\n}\n",
+        );
+        assert_eq!(
+            found,
+            ["in function 'f', line 6: 'last' may be read before it is assigned; not every path to the read assigns it"]
+        );
+    }
+
+    #[test]
+    fn test_let_assigned_once() {
+        let found = check(
+            "int f(int n) {\n    let int limit;\n    if (n > 0) {\n        limit = n;\n    } else {\n        limit = 0;\n    }\n    let int step;\n    step = 1;\n    while (n > 0) {\n        step = 2;\n        n = n - 1;\n    }\n    return limit + step;\n}\n",
+        );
+        assert_eq!(
+            found,
+            ["in function 'f', line 11: 'step' is declared with `let` and is already assigned here; declare it with `var` to assign it again"]
+        );
+    }
+
+    #[test]
+    fn test_unreachable_statements() {
+        let found = check(
+            "int f(int n) {\n    while (n > 0) {\n        break;\n        n = 0;\n    }\n    if (n > 0) {\n        return 1;\n    } else {\n        return 2;\n    }\n    return 3;\n}\nvoid g() {\n    return;\n    g();\n    g();\n}\n",
+        );
+        assert_eq!(
+            found,
+            [
+                "in function 'f', line 4: statement is unreachable after `break`",
+                "in function 'f', line 11: statement is unreachable: control never leaves the statement before it",
+                "in function 'g', line 15: statement is unreachable after `return`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reads_in_lambdas() {
+        let found = check(
+            "int f(int n) {\n    let int limit;\n    var int total;\n    let show = fn(int x) -> int {\n        total = x;\n        return x + limit;\n    };\n    return total;\n}\n",
+        );
+        assert_eq!(
+            found,
+            [
+                "in function 'f', line 6: 'limit' is read before it is assigned",
+                "in function 'f', line 8: 'total' is read before it is assigned",
+            ]
+        );
+    }
+}
@@ -0,0 +1,619 @@
+// Copyright (c) 2026 Mark Ferrell
+// Licensed under the MIT License. See LICENSE.txt in the project root.
+
+//! Whole-program dead code report.
+//!
+//! `--dead-code` follows references from the program's entry points through
+//! the root file and every module it imports, and lists the functions,
+//! types, macros and globals nothing reachable refers to. rustc's own
+//! `dead_code` lint cannot do this: it sees one generated crate at a time,
+//! after `#define` macros have been expanded away.
+//!
+//! References are matched by name, so an item is reached when anything
+//! reachable mentions its name, even a local of the same name. The report
+//! can therefore miss dead items but never lists a live one. A struct's
+//! methods, and the trait implementations for a type, are reached with the
+//! type itself. Entry points are `main` (or the `--entry` function),
+//! `#[test]`, `#[bench]` and `#[no_mangle]` functions, static assertions and
+//! `#export`s;
+//! a library (`--lib`) has every public item of the root file as an entry
+//! point instead of `main`.
+
+use crate::ast::{
+    Block, Expression, File, Function, Ident, Item, Param, Pattern, Statement, Type, Visibility,
+};
+use crate::error::Position;
+use crate::macros::is_name_token;
+use crate::utils::locate_item;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+/// Attributes that make a function an entry point of its own
+const ENTRY_ATTRIBUTES: &[&str] = &["test", "bench", "no_mangle"];
+
+/// How `--dead-code` prints its report
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum DeadCodeFormat {
+    /// One `file:line:column: kind 'name' is never used` line per item
+    #[default]
+    Text,
+    /// The report as JSON
+    Json,
+}
+
+/// One file of the program
+pub struct SourceFile<'a> {
+    pub path: &'a str,
+    pub source: &'a str,
+    pub file: &'a File,
+}
+
+/// An item nothing reachable from an entry point refers to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DeadItem {
+    /// `function`, `struct`, `enum`, `trait`, `typedef`, `const`, `static`
+    /// or `macro`
+    pub kind: &'static str,
+    pub name: String,
+    pub file: String,
+    /// Where the item's name is declared, when it can be found
+    pub position: Option<Position>,
+}
+
+/// The result of a dead code search
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DeadCodeReport {
+    /// Names the search started from
+    pub entry_points: Vec<String>,
+    /// Unreachable items, in file and declaration order
+    pub unreachable: Vec<DeadItem>,
+}
+
+impl DeadCodeReport {
+    /// The report as pretty-printed JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    /// The report as one line per unreachable item
+    pub fn to_text(&self) -> String {
+        self.unreachable
+            .iter()
+            .map(|item| {
+                let location = match item.position {
+                    Some(position) => {
+                        format!("{}:{}:{}", item.file, position.line, position.column)
+                    }
+                    None => item.file.clone(),
+                };
+                format!(
+                    "{}: {} '{}' is never used\n",
+                    location, item.kind, item.name
+                )
+            })
+            .collect()
+    }
+}
+
+/// Find the items of `files` that no entry point reaches
+///
+/// The first file is the root. `entry` names the function the program
+/// starts at; `None` searches a library, whose entry points are the public
+/// items of the root file.
+pub fn find_dead_code(files: &[SourceFile], entry: Option<&str>) -> DeadCodeReport {
+    let mut graph = Graph::default();
+    graph.entry_points.extend(entry.map(str::to_string));
+    for (index, source) in files.iter().enumerate() {
+        graph.items(&source.file.items, index, index == 0 && entry.is_none());
+    }
+
+    let reached = graph.reach();
+    let unreachable = graph
+        .declarations
+        .iter()
+        .filter(|declaration| !reached.contains(&declaration.name))
+        .map(|declaration| {
+            let source = &files[declaration.file];
+            DeadItem {
+                kind: declaration.kind,
+                name: declaration.name.clone(),
+                file: source.path.to_string(),
+                position: locate_item(source.source, &declaration.name).map(|span| span.start),
+            }
+        })
+        .collect();
+    DeadCodeReport {
+        entry_points: graph.entry_points,
+        unreachable,
+    }
+}
+
+/// Every name the items of `file` mention, matched as the report matches
+/// references
+pub(crate) fn referenced_names(file: &File) -> HashSet<String> {
+    let mut graph = Graph::default();
+    graph.items(&file.items, 0, false);
+    graph
+        .references
+        .into_values()
+        .flatten()
+        .chain(graph.roots)
+        .collect()
+}
+
+struct Declaration {
+    kind: &'static str,
+    name: String,
+    /// Index of the declaring file
+    file: usize,
+}
+
+#[derive(Default)]
+struct Graph {
+    declarations: Vec<Declaration>,
+    /// Names each declared name refers to; items sharing a name share an
+    /// entry
+    references: HashMap<String, HashSet<String>>,
+    entry_points: Vec<String>,
+    /// Names referred to outside any declaration, as by static assertions
+    roots: HashSet<String>,
+}
+
+impl Graph {
+    fn items(&mut self, items: &[Item], file: usize, library: bool) {
+        for item in items {
+            let mut refs = References::default();
+            let (kind, name, visibility) = match item {
+                Item::Function(func) => {
+                    let entry = func
+                        .attributes
+                        .iter()
+                        .any(|attr| ENTRY_ATTRIBUTES.contains(&attr.name.name.as_str()));
+                    if entry {
+                        self.entry_points.push(func.name.name.clone());
+                    }
+                    refs.function(func);
+                    ("function", &func.name, &func.visibility)
+                }
+                Item::Struct(s) => {
+                    s.fields.iter().for_each(|field| refs.ty(&field.ty));
+                    s.methods.iter().for_each(|method| refs.function(method));
+                    ("struct", &s.name, &s.visibility)
+                }
+                Item::Enum(e) => {
+                    let fields = e.variants.iter().flat_map(|variant| &variant.fields);
+                    fields.for_each(|field| refs.ty(&field.ty));
+                    ("enum", &e.name, &e.visibility)
+                }
+                Item::Trait(t) => {
+                    for method in &t.methods {
+                        match method.body {
+                            Some(_) => refs.function(&method.as_function()),
+                            None => refs.signature(&method.params, &method.return_type),
+                        }
+                    }
+                    ("trait", &t.name, &t.visibility)
+                }
+                Item::Typedef(t) => {
+                    refs.ty(&t.target);
+                    ("typedef", &t.name, &t.visibility)
+                }
+                Item::Const(c) => {
+                    refs.ty(&c.ty);
+                    refs.expression(&c.value);
+                    ("const", &c.name, &c.visibility)
+                }
+                Item::Static(s) => {
+                    refs.ty(&s.ty);
+                    refs.expression(&s.value);
+                    ("static", &s.name, &s.visibility)
+                }
+                Item::MacroDefinition(m) => {
+                    for token in &m.body {
+                        if let crate::lexer::TokenKind::Ident(name) = &token.kind {
+                            refs.names.insert(name.clone());
+                        }
+                    }
+                    ("macro", &m.name, &Visibility::Private)
+                }
+                Item::Impl(i) => {
+                    refs.name(&i.trait_name);
+                    i.methods.iter().for_each(|method| refs.function(method));
+                    // An implementation lives as long as the type it is for
+                    match type_name(&i.ty) {
+                        Some(ty) => self.references.entry(ty).or_default().extend(refs.names),
+                        None => self.roots.extend(refs.names),
+                    }
+                    continue;
+                }
+                Item::StaticAssert(assert) => {
+                    refs.expression(&assert.condition);
+                    self.roots.extend(refs.names);
+                    continue;
+                }
+                Item::Export(export) => {
+                    self.roots
+                        .extend(export.path.iter().map(|ident| ident.name.clone()));
+                    continue;
+                }
+                Item::Namespace(namespace) => {
+                    self.items(&namespace.items, file, library);
+                    continue;
+                }
+                Item::Import(_) | Item::Extern(_) => continue,
+            };
+
+            if library && *visibility == Visibility::Public {
+                self.entry_points.push(name.name.clone());
+            }
+            self.declarations.push(Declaration {
+                kind,
+                name: name.name.clone(),
+                file,
+            });
+            self.references
+                .entry(name.name.clone())
+                .or_default()
+                .extend(refs.names);
+        }
+    }
+
+    /// Every name reachable from the entry points and roots
+    fn reach(&self) -> HashSet<String> {
+        let mut reached = HashSet::new();
+        let mut pending: Vec<&String> = self.entry_points.iter().chain(&self.roots).collect();
+        while let Some(name) = pending.pop() {
+            if !reached.insert(name.clone()) {
+                continue;
+            }
+            if let Some(refs) = self.references.get(name) {
+                pending.extend(refs.iter().filter(|name| !reached.contains(*name)));
+            }
+        }
+        reached
+    }
+}
+
+/// The name of the declared type `ty` refers to, as `Pair` in `Pair<int>`
+fn type_name(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Ident(ident) => Some(ident.name.clone()),
+        Type::Generic { base, .. } => type_name(base),
+        _ => None,
+    }
+}
+
+/// Names mentioned by one declaration
+#[derive(Default)]
+struct References {
+    names: HashSet<String>,
+}
+
+impl References {
+    fn name(&mut self, ident: &Ident) {
+        self.names.insert(ident.name.clone());
+    }
+
+    fn function(&mut self, func: &Function) {
+        self.signature(&func.params, &func.return_type);
+        self.block(&func.body);
+    }
+
+    fn signature(&mut self, params: &[Param], return_type: &Option<Type>) {
+        params.iter().for_each(|param| self.ty(&param.ty));
+        if let Some(ty) = return_type {
+            self.ty(ty);
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        block
+            .statements
+            .iter()
+            .for_each(|stmt| self.statement(stmt));
+    }
+
+    fn statement(&mut self, stmt: &Statement) {
+        match stmt {
+            Statement::Let { ty, init, .. } | Statement::Var { ty, init, .. } => {
+                if let Some(ty) = ty {
+                    self.ty(ty);
+                }
+                if let Some(init) = init {
+                    self.expression(init);
+                }
+            }
+            Statement::Const { ty, value, .. } => {
+                self.ty(ty);
+                self.expression(value);
+            }
+            Statement::Expr(expr) | Statement::Return(Some(expr)) => self.expression(expr),
+            Statement::If {
+                condition,
+                then_block,
+                else_block,
+            } => {
+                self.expression(condition);
+                self.block(then_block);
+                if let Some(block) = else_block {
+                    self.block(block);
+                }
+            }
+            Statement::While {
+                condition, body, ..
+            }
+            | Statement::DoWhile { body, condition } => {
+                self.expression(condition);
+                self.block(body);
+            }
+            Statement::For {
+                init,
+                condition,
+                increment,
+                body,
+                ..
+            } => {
+                self.statement(init);
+                self.expression(condition);
+                self.expression(increment);
+                self.block(body);
+            }
+            Statement::ForIn { iter, body, .. } => {
+                self.expression(iter);
+                self.block(body);
+            }
+            Statement::Switch {
+                expr,
+                cases,
+                default,
+            } => {
+                self.expression(expr);
+                for case in cases {
+                    case.values.iter().for_each(|value| self.expression(value));
+                    self.block(&case.body);
+                }
+                if let Some(block) = default {
+                    self.block(block);
+                }
+            }
+            Statement::Unsafe(body) | Statement::Block(body) => self.block(body),
+            Statement::StaticAssert(assert) => self.expression(&assert.condition),
+            Statement::NestedFunction {
+                params,
+                return_type,
+                body,
+                ..
+            } => {
+                self.signature(params, return_type);
+                self.block(body);
+            }
+            Statement::Return(None) | Statement::Break(_) | Statement::Continue(_) => {}
+        }
+    }
+
+    fn expression(&mut self, expr: &Expression) {
+        match expr {
+            Expression::Ident(ident) => self.name(ident),
+            // `ns.f` and `ns.f()` name items of a namespace
+            Expression::FieldAccess { field: name, .. }
+            | Expression::MethodCall { method: name, .. } => self.name(name),
+            Expression::Cast { ty, .. }
+            | Expression::Sizeof { ty }
+            | Expression::Alignof { ty }
+            | Expression::StructInit { ty, .. } => self.ty(ty),
+            Expression::TypeScopedCall { ty, method, .. } => {
+                self.ty(ty);
+                self.name(method);
+            }
+            Expression::ExplicitGenericCall {
+                ty,
+                generics,
+                method,
+                ..
+            } => {
+                self.ty(ty);
+                generics.iter().for_each(|ty| self.ty(ty));
+                self.name(method);
+            }
+            Expression::MacroCall { name, args, .. } => {
+                self.name(name);
+                self.tokens(args);
+            }
+            Expression::RustBlock { tokens } => self.tokens(tokens),
+            Expression::Match { arms, .. } => {
+                arms.iter().for_each(|arm| self.pattern(&arm.pattern));
+            }
+            Expression::Lambda {
+                params,
+                return_type,
+                body,
+                ..
+            } => {
+                self.signature(params, return_type);
+                self.block(body);
+            }
+            _ => {}
+        }
+        for child in crate::macros::child_expressions(expr) {
+            self.expression(child);
+        }
+    }
+
+    /// Macro arguments are kept as untyped tokens, so any word may be a name
+    fn tokens(&mut self, tokens: &[crate::ast::Token]) {
+        let idents = tokens.iter().filter(|token| is_name_token(token));
+        self.names.extend(idents.map(|token| token.text.clone()));
+    }
+
+    fn ty(&mut self, ty: &Type) {
+        match ty {
+            Type::Ident(ident) => self.name(ident),
+            Type::Pointer { ty, .. }
+            | Type::Reference { ty, .. }
+            | Type::Array { ty, .. }
+            | Type::Slice { ty }
+            | Type::Fallible { ty }
+            | Type::Volatile { ty } => self.ty(ty),
+            Type::Tuple { types } => types.iter().for_each(|ty| self.ty(ty)),
+            Type::Generic { base, args } => {
+                self.ty(base);
+                args.iter().for_each(|ty| self.ty(ty));
+            }
+            Type::Function {
+                params,
+                return_type,
+            } => {
+                params.iter().for_each(|ty| self.ty(ty));
+                self.ty(return_type);
+            }
+            Type::Primitive(_) | Type::Auto => {}
+        }
+    }
+
+    fn pattern(&mut self, pattern: &Pattern) {
+        match pattern {
+            Pattern::Variant {
+                enum_name, fields, ..
+            } => {
+                self.name(enum_name);
+                fields.iter().for_each(|field| self.pattern(field));
+            }
+            Pattern::Tuple(patterns) => patterns.iter().for_each(|p| self.pattern(p)),
+            Pattern::Wildcard | Pattern::Literal(_) | Pattern::Binding(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn parse(source: &str) -> File {
+        Parser::new(source).unwrap().parse_file().unwrap()
+    }
+
+    fn dead(report: &DeadCodeReport) -> Vec<(&str, &str)> {
+        report
+            .unreachable
+            .iter()
+            .map(|item| (item.kind, item.name.as_str()))
+            .collect()
+    }
+
+    const PROGRAM: &str = "\
+#define __SQUARE__(x) ((x) * (x))
+#define __CUBE__(x) ((x) * (x) * (x))
+
+enum Color { Red, Green }
+enum Unused { A, B }
+typedef int Meters;
+typedef int Feet;
+
+struct Point {
+    Meters x;
+    int helper(&self) { return scale(self.x); }
+}
+
+int scale(int n) { return n * 2; }
+static int orphan() { return orphan_helper(); }
+int orphan_helper() { return 0; }
+
+int main() {
+    let p = (Point){ .x = 1 };
+    let c = Color.Red;
+    return __SQUARE__(p.helper());
+}
+";
+
+    #[test]
+    fn test_reports_unreachable_items() {
+        let file = parse(PROGRAM);
+        let files = [SourceFile {
+            path: "main.crst",
+            source: PROGRAM,
+            file: &file,
+        }];
+        let report = find_dead_code(&files, Some("main"));
+        assert_eq!(report.entry_points, vec!["main"]);
+        assert_eq!(
+            dead(&report),
+            vec![
+                ("macro", "__CUBE__"),
+                ("enum", "Unused"),
+                ("typedef", "Feet"),
+                ("function", "orphan"),
+                ("function", "orphan_helper"),
+            ]
+        );
+
+        let text = report.to_text();
+        assert!(
+            text.contains("main.crst:7:13: typedef 'Feet' is never used\n"),
+            "{}",
+            text
+        );
+        let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(json["unreachable"][0]["name"], "__CUBE__");
+        assert_eq!(json["unreachable"][3]["position"]["line"], 15);
+    }
+
+    #[test]
+    fn test_follows_references_across_modules() {
+        let root = "#import shapes.area\nint main() { return area(2); }\n";
+        let module = "int area(int s) { return s * s; }\nint perimeter(int s) { return 4 * s; }\n";
+        let (root_file, module_file) = (parse(root), parse(module));
+        let files = [
+            SourceFile {
+                path: "main.crst",
+                source: root,
+                file: &root_file,
+            },
+            SourceFile {
+                path: "shapes.crst",
+                source: module,
+                file: &module_file,
+            },
+        ];
+        let report = find_dead_code(&files, Some("main"));
+        assert_eq!(dead(&report), vec![("function", "perimeter")]);
+        assert_eq!(report.unreachable[0].file, "shapes.crst");
+    }
+
+    #[test]
+    fn test_library_entry_points_are_public_items() {
+        let source = "\
+int api(int n) { return internal(n); }
+static int internal(int n) { return n; }
+static int forgotten() { return 0; }
+#[test]
+static void check() { let n = sizes(); }
+static int sizes() { return 4; }
+";
+        let file = parse(source);
+        let files = [SourceFile {
+            path: "lib.crst",
+            source,
+            file: &file,
+        }];
+        let report = find_dead_code(&files, None);
+        assert_eq!(report.entry_points, vec!["api", "check"]);
+        assert_eq!(dead(&report), vec![("function", "forgotten")]);
+    }
+
+    #[test]
+    fn test_follows_calls_in_lambdas() {
+        let source = "\
+static int twice(int n) { return n * 2; }
+typedef int Count;
+int main() { let f = fn(Count n) -> int { return twice(n); }; return f(1); }
+";
+        let file = parse(source);
+        let files = [SourceFile {
+            path: "main.crst",
+            source,
+            file: &file,
+        }];
+        assert!(dead(&find_dead_code(&files, Some("main"))).is_empty());
+    }
+}
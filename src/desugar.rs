@@ -0,0 +1,218 @@
+// Copyright (c) 2026 Mark Ferrell
+// Licensed under the MIT License. See LICENSE.txt in the project root.
+
+//! Lowering a program to the smaller Crusty it means, for
+//! `--emit crusty-desugared`.
+//!
+//! The program is printed as Crusty after macros are expanded, with the
+//! shorthand spelled out: compound assignments become plain ones, ternaries
+//! whose value is assigned, returned or discarded become `if`/`else`, and
+//! locals declared without a type get the one semantic analysis inferred.
+//! The printer writes every declaration as `let` or `var` with its type as a
+//! cast, so implicit `int x = 1;` declarations come out as `let x = (int)1;`:
+//!
+//! ```text
+//! var total = 0;              var total = (int)0;
+//! total += n * 2;      =>     total = total + n * 2;
+//! return n > 0 ? n : -n;      if (n > 0) { return n; } else { return -n; }
+//! ```
+
+use crate::ast::{BinaryOp, Block, Expression, File, Statement, Type};
+use crate::codegen::compound_assign_base;
+use crate::hir::{bodies_mut, LocalTypes, Site};
+use crate::tailcall::child_blocks_mut;
+
+/// `file` with its shorthand spelled out and the types in `locals` written
+/// into the declarations without one
+pub fn desugar(file: &File, locals: &LocalTypes) -> File {
+    let mut file = file.clone();
+    for (site, body, _) in bodies_mut(&mut file.items) {
+        desugar_block(body, &site, locals);
+    }
+    file
+}
+
+/// Desugar each statement of `block`, within `site`, and the blocks nested
+/// in it
+fn desugar_block(block: &mut Block, site: &Site, locals: &LocalTypes) {
+    for i in 0..block.statements.len() {
+        let site = site.statement(block, i);
+        let declared = locals.get(&site).map(Vec::as_slice).unwrap_or_default();
+        let stmt = &mut block.statements[i];
+        let desugared = desugar_statement(std::mem::replace(stmt, Statement::Break(None)));
+        *stmt = desugared;
+        annotate(stmt, declared);
+        for child in child_blocks_mut(stmt) {
+            desugar_block(child, &site, locals);
+        }
+        match stmt {
+            Statement::For { init, .. } => annotate(init, declared),
+            Statement::NestedFunction { body, .. } => desugar_block(body, &site, locals),
+            _ => {}
+        }
+    }
+}
+
+/// Give `stmt`, a `let` or `var` declared without a type, the type found
+/// for it among `locals`, those its statement declares. Initializers that
+/// spell their type, casts, struct literals and lambdas, are left as they
+/// are
+fn annotate(stmt: &mut Statement, locals: &[(String, Type)]) {
+    let (name, ty, init) = match stmt {
+        Statement::Let { name, ty, init, .. } | Statement::Var { name, ty, init } => {
+            (name, ty, init)
+        }
+        _ => return,
+    };
+    let Some(init) = init else {
+        return;
+    };
+    if ty.is_some()
+        || matches!(
+            init,
+            Expression::Cast { .. } | Expression::StructInit { .. } | Expression::Lambda { .. }
+        )
+    {
+        return;
+    }
+    *ty = locals
+        .iter()
+        .find(|(local, _)| *local == name.name)
+        .map(|(_, found)| found.clone());
+}
+
+/// `stmt` with a compound assignment or a ternary at its top spelled out
+fn desugar_statement(stmt: Statement) -> Statement {
+    match stmt {
+        Statement::Expr(Expression::Binary { op, left, right })
+            if compound_assign_base(&op).is_some() && is_place(&left) =>
+        {
+            let base = compound_assign_base(&op).unwrap_or(op);
+            assign(
+                *left.clone(),
+                Expression::Binary {
+                    op: base,
+                    left,
+                    right,
+                },
+            )
+        }
+        Statement::Expr(Expression::Binary {
+            op: BinaryOp::Assign,
+            left,
+            right,
+        }) if matches!(*right, Expression::Ternary { .. }) => {
+            branch(*right, &|value| assign(*left.clone(), value))
+        }
+        Statement::Expr(ternary @ Expression::Ternary { .. }) => branch(ternary, &Statement::Expr),
+        Statement::Return(Some(ternary @ Expression::Ternary { .. })) => {
+            branch(ternary, &|value| Statement::Return(Some(value)))
+        }
+        stmt => stmt,
+    }
+}
+
+/// `if (condition) { use(then) } else { use(else) }` for the ternary
+/// `condition ? then : else`, with nested ternaries in the branches spelled
+/// out the same way
+fn branch(ternary: Expression, use_value: &dyn Fn(Expression) -> Statement) -> Statement {
+    let Expression::Ternary {
+        condition,
+        then_expr,
+        else_expr,
+    } = ternary
+    else {
+        return use_value(ternary);
+    };
+    let arm = |value: Expression| {
+        let stmt = match value {
+            Expression::Ternary { .. } => branch(value, use_value),
+            value => use_value(value),
+        };
+        Block::new(vec![stmt])
+    };
+    Statement::If {
+        condition: *condition,
+        then_block: arm(*then_expr),
+        else_block: Some(arm(*else_expr)),
+    }
+}
+
+fn assign(target: Expression, value: Expression) -> Statement {
+    Statement::Expr(Expression::Binary {
+        op: BinaryOp::Assign,
+        left: Box::new(target),
+        right: Box::new(value),
+    })
+}
+
+/// Whether `expr` names a place that reading twice has no side effects
+/// and reads the same both times, so `expr op= v` can be `expr = expr op v`
+fn is_place(expr: &Expression) -> bool {
+    match expr {
+        Expression::Ident(_) | Expression::Literal(_) => true,
+        Expression::FieldAccess { expr, .. } => is_place(expr),
+        Expression::Index { expr, index } => is_place(expr) && is_place(index),
+        Expression::Unary {
+            op: crate::ast::UnaryOp::Deref,
+            expr,
+        } => is_place(expr),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::TargetLanguage;
+    use crate::parser::Parser;
+    use crate::pretty::PrettyPrinter;
+    use crate::semantic::SemanticAnalyzer;
+
+    fn desugared(source: &str) -> String {
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.analyze(&file).unwrap();
+        let file = desugar(&file, analyzer.local_types());
+        PrettyPrinter::new(TargetLanguage::Crusty)
+            .format_ast_as_crusty(&file)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_desugar_spells_out_shorthand() {
+        let crusty = desugared(
+            "int f(int n) {\n    var total = 0;\n    total += n * 2;\n    var int[3] xs;\n    xs[0] <<= 1;\n    total = n > 0 ? n : n < -9 ? 9 : -n;\n    return total > 9 ? 9 : total;\n}\n",
+        );
+        assert!(crusty.contains("var total = (i32)0;"), "{}", crusty);
+        assert!(crusty.contains("total = (total + (n * 2));"), "{}", crusty);
+        assert!(crusty.contains("var int xs[3];"), "{}", crusty);
+        assert!(crusty.contains("xs[0] = (xs[0] << 1);"), "{}", crusty);
+        assert!(
+            crusty.contains(
+                "if ((n > 0)) {\n        total = n;\n    } else {\n        if ((n < -(9))) {"
+            ),
+            "{}",
+            crusty
+        );
+        assert!(
+            crusty.contains("if ((total > 9)) {\n        return 9;"),
+            "{}",
+            crusty
+        );
+        assert!(!crusty.contains(" else { "), "{}", crusty);
+    }
+
+    #[test]
+    fn test_desugar_keeps_side_effects_single() {
+        let crusty = desugared(
+            "int g() {\n    return 0;\n}\nint f() {\n    var int[2] xs;\n    xs[g()] += 1;\n    let y = xs[0] > 0 ? 1 : 2;\n    return y;\n}\n",
+        );
+        assert!(crusty.contains("xs[g()] += 1;"), "{}", crusty);
+        assert!(
+            crusty.contains("let y = (i32)if ((xs[0] > 0)) { 1 } else { 2 };"),
+            "{}",
+            crusty
+        );
+    }
+}
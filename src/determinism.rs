@@ -0,0 +1,213 @@
+// Copyright (c) 2026 Mark Ferrell
+// Licensed under the MIT License. See LICENSE.txt in the project root.
+
+//! Checks that code generation is deterministic, and hashes of the code
+//! generated for each item.
+//!
+//! Reproducible builds need the same Crusty source to generate the same
+//! Rust every time. Iterating a `HashMap`, whose order changes from run to
+//! run, or a counter for generated names that is not reset between runs
+//! breaks this in ways a single build does not show. `--check-determinism`
+//! generates each file twice from the same AST and fails when the outputs
+//! differ, naming the first item that does:
+//!
+//! ```text
+//! code generation is not deterministic: item 'area' differs between two runs, first at line 12
+//!   first:  let (w, h) = (a, b);
+//!   second: let (h, w) = (b, a);
+//! ```
+//!
+//! With `--emit-item-hashes`, a hash of each item's generated code is
+//! written next to the generated file (`main.rs.hashes` for `main.rs`), so a
+//! build cache can tell which items changed without comparing the code:
+//!
+//! ```text
+//! {
+//!   "version": 1,
+//!   "file": "main.rs",
+//!   "items": [
+//!     { "name": "area", "hash": "5d3c0e9d2a4f71b8" },
+//!     { "name": "shapes::Circle", "hash": "c1f2a9e07b3d5564" }
+//!   ]
+//! }
+//! ```
+//!
+//! The hash is 64-bit FNV-1a, which does not depend on the Rust version or
+//! platform the compiler was built with.
+
+use crate::codegen::GeneratedItem;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Version of the JSON item hash format
+pub const ITEM_HASHES_VERSION: u32 = 1;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Hash of the code generated for one item
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemHash {
+    /// The item, prefixed with its module (`shapes::Circle`) when it comes
+    /// from an imported file
+    pub name: String,
+    /// 64-bit FNV-1a hash of the item's lines, in hexadecimal
+    pub hash: String,
+}
+
+/// Hashes of the items of one generated Rust file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemHashes {
+    pub version: u32,
+    /// The generated Rust file
+    pub file: String,
+    /// Items in generated order
+    pub items: Vec<ItemHash>,
+}
+
+impl ItemHashes {
+    /// No hashes yet, for the generated file `file`
+    pub fn new(file: impl Into<String>) -> Self {
+        Self {
+            version: ITEM_HASHES_VERSION,
+            file: file.into(),
+            items: Vec::new(),
+        }
+    }
+
+    /// Hash the items one generator produced as `code`, naming them within
+    /// `module` when it is given
+    pub fn add(&mut self, code: &str, items: &[GeneratedItem], module: Option<&str>) {
+        let lines: Vec<&str> = code.lines().collect();
+        for item in items {
+            let text = lines
+                .get(item.line.saturating_sub(1)..item.end_line.min(lines.len()))
+                .unwrap_or_default()
+                .join("\n");
+            self.items.push(ItemHash {
+                name: match module {
+                    Some(module) => format!("{}::{}", module, item.name),
+                    None => item.name.clone(),
+                },
+                hash: format!("{:016x}", stable_hash(&text)),
+            });
+        }
+    }
+
+    /// The hashes as pretty-printed JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+}
+
+/// 64-bit FNV-1a hash of `text`, the same on every platform and build
+pub fn stable_hash(text: &str) -> u64 {
+    text.bytes().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// The first line at which two generations of the same file differ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// Item the line belongs to in the first output, if any
+    pub item: Option<String>,
+    /// Line of the generated file (1-based)
+    pub line: usize,
+    pub first: String,
+    pub second: String,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "code generation is not deterministic: ")?;
+        match &self.item {
+            Some(item) => write!(f, "item '{}' differs between two runs", item)?,
+            None => write!(f, "the output differs between two runs")?,
+        }
+        write!(
+            f,
+            ", first at line {}\n  first:  {}\n  second: {}",
+            self.line, self.first, self.second
+        )
+    }
+}
+
+/// Compare two generations of the same file, where `items` are the items
+/// of the first
+pub fn compare(first: &str, second: &str, items: &[GeneratedItem]) -> Option<Divergence> {
+    if first == second {
+        return None;
+    }
+    let mut first_lines = first.lines();
+    let mut second_lines = second.lines();
+    let mut line = 0;
+    loop {
+        line += 1;
+        match (first_lines.next(), second_lines.next()) {
+            (Some(a), Some(b)) if a == b => continue,
+            (a, b) => {
+                return Some(Divergence {
+                    item: items
+                        .iter()
+                        .find(|item| (item.line..=item.end_line).contains(&line))
+                        .map(|item| item.name.clone()),
+                    line,
+                    first: a.unwrap_or("<end of output>").to_string(),
+                    second: b.unwrap_or("<end of output>").to_string(),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(name: &str, line: usize, end_line: usize) -> GeneratedItem {
+        GeneratedItem {
+            name: name.to_string(),
+            line,
+            end_line,
+        }
+    }
+
+    #[test]
+    fn test_item_hashes_follow_item_code() {
+        let code = "// Generated\n\npub fn one() -> i32 {\n    return 1;\n}\n\npub fn two() -> i32 {\n    return 2;\n}\n";
+        let moved = "// Generated\n\n\npub fn one() -> i32 {\n    return 1;\n}\n\npub fn two() -> i32 {\n    return 3;\n}\n";
+        let mut hashes = ItemHashes::new("main.rs");
+        hashes.add(code, &[item("one", 3, 5), item("two", 7, 9)], None);
+        let mut changed = ItemHashes::new("main.rs");
+        changed.add(moved, &[item("one", 4, 6), item("two", 8, 10)], Some("m"));
+
+        assert_eq!(hashes.items[0].hash, changed.items[0].hash);
+        assert_ne!(hashes.items[1].hash, changed.items[1].hash);
+        assert_eq!(changed.items[1].name, "m::two");
+        assert_eq!(format!("{:016x}", stable_hash("")), "cbf29ce484222325");
+        assert_eq!(format!("{:016x}", stable_hash("a")), "af63dc4c8601ec8c");
+
+        let json: ItemHashes = serde_json::from_str(&hashes.to_json()).unwrap();
+        assert_eq!(json, hashes);
+    }
+
+    #[test]
+    fn test_compare_names_the_first_differing_item() {
+        let first = "pub fn one() {\n    a();\n    b();\n}\n";
+        let second = "pub fn one() {\n    b();\n    a();\n}\n";
+        assert_eq!(compare(first, first, &[]), None);
+
+        let divergence = compare(first, second, &[item("one", 1, 4)]).unwrap();
+        assert_eq!(divergence.line, 2);
+        assert_eq!(
+            divergence.to_string(),
+            "code generation is not deterministic: item 'one' differs between two runs, first at line 2\n  first:      a();\n  second:     b();"
+        );
+        let shorter = compare(first, "pub fn one() {\n", &[]).unwrap();
+        assert_eq!(
+            (shorter.item, shorter.second.as_str()),
+            (None, "<end of output>")
+        );
+    }
+}
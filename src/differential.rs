@@ -0,0 +1,389 @@
+// Copyright (c) 2026 Mark Ferrell
+// Licensed under the MIT License. See LICENSE.txt in the project root.
+
+//! Differential testing between the recursive-descent and PEG parsers.
+//!
+//! While both parsers exist, every input accepted by one should be accepted by
+//! the other and produce the same AST. This module runs both over a corpus,
+//! reports each divergence, and shrinks the offending input to a minimal
+//! reproduction.
+
+use crate::ast::File;
+use crate::parser::{crusty_peg_parser, Parser};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+
+/// Upper bound on parser invocations spent minimizing one divergence
+const MAX_MINIMIZE_STEPS: usize = 2000;
+
+/// Which parser an outcome refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParserKind {
+    RecursiveDescent,
+    Peg,
+}
+
+impl fmt::Display for ParserKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParserKind::RecursiveDescent => write!(f, "recursive-descent"),
+            ParserKind::Peg => write!(f, "PEG"),
+        }
+    }
+}
+
+/// How the two parsers disagreed on an input
+#[derive(Debug, Clone, PartialEq)]
+pub enum DivergenceKind {
+    /// Both parsers accepted the input but built different ASTs
+    AstMismatch { recursive: File, peg: File },
+    /// Only the named parser accepted the input
+    OnlyAccepted { parser: ParserKind, error: String },
+    /// The named parser panicked
+    Panicked(ParserKind),
+}
+
+impl DivergenceKind {
+    /// Whether two divergences are the same kind of failure, ignoring details
+    fn same_class(&self, other: &DivergenceKind) -> bool {
+        match (self, other) {
+            (DivergenceKind::AstMismatch { .. }, DivergenceKind::AstMismatch { .. }) => true,
+            (
+                DivergenceKind::OnlyAccepted { parser: a, .. },
+                DivergenceKind::OnlyAccepted { parser: b, .. },
+            ) => a == b,
+            (DivergenceKind::Panicked(a), DivergenceKind::Panicked(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for DivergenceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DivergenceKind::AstMismatch { .. } => write!(f, "ASTs differ"),
+            DivergenceKind::OnlyAccepted { parser, error } => {
+                write!(f, "only the {} parser accepts the input: {}", parser, error)
+            }
+            DivergenceKind::Panicked(parser) => write!(f, "the {} parser panicked", parser),
+        }
+    }
+}
+
+/// A divergence found on one corpus entry
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    pub name: String,
+    pub kind: DivergenceKind,
+    pub minimized: String,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}: {}", self.name, self.kind)?;
+        writeln!(f, "  minimized input:")?;
+        for line in self.minimized.lines() {
+            writeln!(f, "    {}", line)?;
+        }
+        if let DivergenceKind::AstMismatch { recursive, peg } = &self.kind {
+            writeln!(f, "  recursive-descent: {:?}", recursive)?;
+            writeln!(f, "  PEG: {:?}", peg)?;
+        }
+        Ok(())
+    }
+}
+
+/// Result of running both parsers over a corpus
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DifferentialReport {
+    pub inputs: usize,
+    pub divergences: Vec<Divergence>,
+}
+
+impl DifferentialReport {
+    pub fn is_clean(&self) -> bool {
+        self.divergences.is_empty()
+    }
+}
+
+impl fmt::Display for DifferentialReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} of {} inputs diverge between parsers",
+            self.divergences.len(),
+            self.inputs
+        )?;
+        for divergence in &self.divergences {
+            write!(f, "\n{}", divergence)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse with the recursive-descent parser, catching panics
+fn parse_recursive(source: &str) -> Result<Result<File, String>, ParserKind> {
+    panic::catch_unwind(AssertUnwindSafe(|| {
+        Parser::new(source)
+            .and_then(|mut parser| parser.parse_file())
+            .map_err(|e| e.to_string())
+    }))
+    .map_err(|_| ParserKind::RecursiveDescent)
+}
+
+/// Parse with the PEG parser, catching panics
+fn parse_peg(source: &str) -> Result<Result<File, String>, ParserKind> {
+    panic::catch_unwind(AssertUnwindSafe(|| {
+        crusty_peg_parser::file(source).map_err(|e| e.to_string())
+    }))
+    .map_err(|_| ParserKind::Peg)
+}
+
+/// Run both parsers over one input and describe how they disagree, if at all
+///
+/// File-level doc comments are ignored: the PEG grammar does not collect them.
+pub fn compare(source: &str) -> Option<DivergenceKind> {
+    let recursive = match parse_recursive(source) {
+        Ok(result) => result,
+        Err(parser) => return Some(DivergenceKind::Panicked(parser)),
+    };
+    let peg = match parse_peg(source) {
+        Ok(result) => result,
+        Err(parser) => return Some(DivergenceKind::Panicked(parser)),
+    };
+
+    match (recursive, peg) {
+        (Ok(mut recursive), Ok(peg)) => {
+            recursive.doc_comments.clear();
+            (recursive != peg).then_some(DivergenceKind::AstMismatch { recursive, peg })
+        }
+        (Ok(_), Err(error)) => Some(DivergenceKind::OnlyAccepted {
+            parser: ParserKind::RecursiveDescent,
+            error,
+        }),
+        (Err(error), Ok(_)) => Some(DivergenceKind::OnlyAccepted {
+            parser: ParserKind::Peg,
+            error,
+        }),
+        (Err(_), Err(_)) => None,
+    }
+}
+
+/// Shrink `source` while it still diverges the same way as `kind`
+///
+/// Removes runs of lines first and then runs of characters, halving the run
+/// length until single units no longer shrink the input.
+pub fn minimize(source: &str, kind: &DivergenceKind) -> String {
+    let mut steps = 0;
+    let mut still_diverges = |candidate: &str| {
+        steps += 1;
+        steps <= MAX_MINIMIZE_STEPS
+            && compare(candidate).is_some_and(|found| found.same_class(kind))
+    };
+
+    let lines: Vec<String> = source.lines().map(|line| format!("{}\n", line)).collect();
+    let lines = shrink(lines, &mut still_diverges);
+    let chars: Vec<String> = lines.concat().chars().map(String::from).collect();
+    shrink(chars, &mut still_diverges).concat()
+}
+
+/// Greedy chunk removal over `units`, keeping removals that preserve the predicate
+fn shrink(mut units: Vec<String>, keep: &mut impl FnMut(&str) -> bool) -> Vec<String> {
+    let mut chunk = units.len() / 2;
+    while chunk > 0 {
+        let mut start = 0;
+        while start < units.len() {
+            let end = (start + chunk).min(units.len());
+            let candidate: String = units[..start].concat() + &units[end..].concat();
+            if keep(&candidate) {
+                units.drain(start..end);
+            } else {
+                start += chunk;
+            }
+        }
+        chunk /= 2;
+    }
+    units
+}
+
+/// Compare both parsers over named inputs, minimizing every divergence
+pub fn run_corpus<I, N, S>(corpus: I) -> DifferentialReport
+where
+    I: IntoIterator<Item = (N, S)>,
+    N: Into<String>,
+    S: AsRef<str>,
+{
+    let mut report = DifferentialReport::default();
+
+    for (name, source) in corpus {
+        report.inputs += 1;
+        let source = source.as_ref();
+        if let Some(kind) = compare(source) {
+            let minimized = minimize(source, &kind);
+            report.divergences.push(Divergence {
+                name: name.into(),
+                kind,
+                minimized,
+            });
+        }
+    }
+
+    report
+}
+
+/// Load every `.crst` file under `dir` (recursively) as a named corpus entry
+pub fn load_corpus(dir: &Path) -> io::Result<Vec<(String, String)>> {
+    let mut corpus = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+
+    while let Some(current) = pending.pop() {
+        for entry in fs::read_dir(&current)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.extension().is_some_and(|ext| ext == "crst") {
+                let source = fs::read_to_string(&path)?;
+                corpus.push((path.display().to_string(), source));
+            }
+        }
+    }
+
+    corpus.sort();
+    Ok(corpus)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare_agreeing_parsers() {
+        assert_eq!(compare("int add(int a, int b) { return a + b; }"), None);
+        assert_eq!(compare("int f( {"), None);
+    }
+
+    #[test]
+    fn test_if_expressions_agree() {
+        assert_eq!(
+            compare("int f(int x) { return if (x > 0) { 1 } else if (x < 0) { 2 } else { 0 }; }"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_match_expressions_agree() {
+        assert_eq!(
+            compare("int f(int x) { return match ((x, true)) { (0, _) => 1, (n, b) => n }; }"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_tagged_unions_agree() {
+        assert_eq!(
+            compare("enum Shape { Circle(float radius), Rect(float w, float h), Empty }\nfloat f(Shape s) { return match (s) { Shape.Circle(r) => r, _ => 0.0 }; }"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_traits_agree() {
+        assert_eq!(compare("trait Shape {\n    float area(&self);\n    void scale(var &self, float k);\n}\nstruct Circle {\n    float r;\n}\nimpl Shape for Circle {\n    float area(&self) {\n        return self.r;\n    }\n    void scale(var &self, float k) {\n        self.r = self.r * k;\n    }\n}\n"), None);
+    }
+
+    #[test]
+    fn test_default_methods_agree() {
+        assert_eq!(compare("trait Shape {\n    float area(&self);\n    float scale(&self) {\n        return 1.0;\n    }\n    void reset(var &self) {\n    }\n}\n"), None);
+        assert_eq!(
+            compare("interface Named {\n    int id(&self) {\n        return 0;\n    }\n}\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_member_visibility_agrees() {
+        assert_eq!(compare("struct Counter {\n    pub int count;\n    static int limit;\n    static void clamp(var &self) {\n        self.count = self.limit;\n    }\n    pub int get(&self) {\n        return self.count;\n    }\n}\n"), None);
+    }
+
+    #[test]
+    fn test_generic_impls_agree() {
+        assert_eq!(compare("struct Pair(T) {\n    T a;\n    T b;\n}\ntrait Sized {\n    int size(&self);\n}\nimpl Sized for Pair(T) {\n    int size(&self) {\n        return 2;\n    }\n}\n"), None);
+    }
+
+    #[test]
+    fn test_generic_functions_agree() {
+        assert_eq!(
+            compare("T max(T)(T a, T b) {\n    if (a > b) {\n        return a;\n    }\n    return b;\n}\nPoint origin() {\n    return p;\n}\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_minimize_drops_unrelated_items() {
+        // Macro body tokens carry spans relative to the macro in the PEG parser
+        let source = "int f() {\n    return 1;\n}\n\n#define __MAX__ 100\n";
+        let kind = compare(source).expect("macro body spans differ");
+        assert!(matches!(kind, DivergenceKind::AstMismatch { .. }));
+
+        let minimized = minimize(source, &kind);
+        assert!(!minimized.contains("return"), "{:?}", minimized);
+        assert!(compare(&minimized).is_some_and(|found| found.same_class(&kind)));
+    }
+
+    #[test]
+    fn test_shrink_removes_irrelevant_units() {
+        let units: Vec<String> = "abXcdYef".chars().map(String::from).collect();
+        let mut keep = |candidate: &str| candidate.contains('X') && candidate.contains('Y');
+        assert_eq!(shrink(units, &mut keep).concat(), "XY");
+    }
+
+    #[test]
+    fn test_run_corpus_counts_inputs() {
+        let report = run_corpus(vec![
+            ("a.crst", "void f() { }"),
+            ("b.crst", "int g() { return 0; }"),
+        ]);
+        assert_eq!(report.inputs, 2);
+        assert!(report.is_clean(), "{}", report);
+    }
+
+    #[test]
+    fn test_report_display_includes_minimized_input() {
+        let report = DifferentialReport {
+            inputs: 1,
+            divergences: vec![Divergence {
+                name: "x.crst".to_string(),
+                kind: DivergenceKind::Panicked(ParserKind::Peg),
+                minimized: "int x;".to_string(),
+            }],
+        };
+        let rendered = report.to_string();
+        assert!(rendered.starts_with("1 of 1 inputs diverge between parsers"));
+        assert!(rendered.contains("x.crst: the PEG parser panicked"));
+        assert!(rendered.contains("    int x;"));
+    }
+
+    #[test]
+    fn test_example_corpus_report() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("example");
+        let corpus = load_corpus(&dir).unwrap();
+        assert!(!corpus.is_empty());
+
+        let report = run_corpus(corpus.iter().map(|(n, s)| (n.as_str(), s.as_str())));
+        assert_eq!(report.inputs, corpus.len());
+        for divergence in &report.divergences {
+            assert!(divergence.minimized.len() <= corpus_source(&corpus, &divergence.name).len());
+        }
+    }
+
+    fn corpus_source<'a>(corpus: &'a [(String, String)], name: &str) -> &'a str {
+        corpus
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, s)| s.as_str())
+            .unwrap()
+    }
+}
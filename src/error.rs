@@ -3,10 +3,13 @@
 
 //! Error handling types and utilities.
 
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
 /// Source code position for error reporting
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
 pub struct Position {
     pub line: usize,
     pub column: usize,
@@ -25,7 +28,7 @@ impl fmt::Display for Position {
 }
 
 /// Source code span for error reporting
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Span {
     pub start: Position,
     pub end: Position,
@@ -74,6 +77,8 @@ pub struct ParseError {
     pub message: String,
     pub expected: Vec<String>,
     pub found: String,
+    /// Offending source line with a caret under the error column
+    pub snippet: Option<Box<str>>,
 }
 
 impl ParseError {
@@ -88,17 +93,90 @@ impl ParseError {
             message: message.into(),
             expected,
             found: found.into(),
+            snippet: None,
+        }
+    }
+
+    /// The label shown under the offending token: what the parser expected
+    /// and what it found instead
+    pub fn label(&self) -> String {
+        match self.expected.as_slice() {
+            [] => format!("unexpected {}", self.found),
+            [expected] => format!("expected {}, found {}", expected, self.found),
+            expected => format!(
+                "expected one of {}, found {}",
+                expected.join(", "),
+                self.found
+            ),
+        }
+    }
+
+    /// Attach the offending source line, rendered under the error message
+    pub fn with_snippet(mut self, source: &str) -> Self {
+        if let Some(snippet) = source_snippet(source, self.span.start) {
+            self.snippet = Some(snippet.into());
         }
+        self
     }
 }
 
+/// The source line at `position` with a caret under its column, as shown
+/// under parse errors and translated rustc diagnostics
+pub fn source_snippet(source: &str, position: Position) -> Option<String> {
+    span_snippet(
+        source,
+        Span::new(position, position),
+        None,
+        Severity::Error,
+        false,
+    )
+}
+
+/// The first source line of `span` with carets under the span and `label`
+/// after them; a span running past its first line is underlined to the end
+/// of that line
+fn span_snippet(
+    source: &str,
+    span: Span,
+    label: Option<&str>,
+    severity: Severity,
+    color: bool,
+) -> Option<String> {
+    let line = span.start.line;
+    let text = source.lines().nth(line.checked_sub(1)?)?;
+    let start = span.start.column.max(1);
+    let width = if span.end.line == line && span.end.column > start {
+        span.end.column - start
+    } else if span.end.line > line {
+        (text.chars().count() + 1).saturating_sub(start).max(1)
+    } else {
+        1
+    };
+    let gutter = " ".repeat(line.to_string().len());
+    let mut marker = "^".repeat(width);
+    if let Some(label) = label {
+        marker = format!("{} {}", marker, label);
+    }
+    Some(format!(
+        "{gutter} {bar}\n{number} {bar} {text}\n{gutter} {bar} {pad}{marker}",
+        bar = paint("|", GUTTER_STYLE, color),
+        number = paint(&line.to_string(), GUTTER_STYLE, color),
+        pad = " ".repeat(start - 1),
+        marker = paint(&marker, severity.style(), color),
+    ))
+}
+
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "Parse error at {}: {}", self.span, self.message)?;
         if !self.expected.is_empty() {
             write!(f, " (expected: {})", self.expected.join(", "))?;
         }
-        write!(f, " (found: {})", self.found)
+        write!(f, " (found: {})", self.found)?;
+        if let Some(snippet) = &self.snippet {
+            write!(f, "\n{}", snippet)?;
+        }
+        Ok(())
     }
 }
 
@@ -115,6 +193,19 @@ pub enum SemanticErrorKind {
     UnsupportedFeature,
 }
 
+impl SemanticErrorKind {
+    /// The error code diagnostics of this kind carry
+    pub fn code(&self) -> &'static str {
+        match self {
+            SemanticErrorKind::UndefinedVariable => "C0201",
+            SemanticErrorKind::TypeMismatch => "C0202",
+            SemanticErrorKind::DuplicateDefinition => "C0203",
+            SemanticErrorKind::InvalidOperation => "C0204",
+            SemanticErrorKind::UnsupportedFeature => "C0205",
+        }
+    }
+}
+
 impl fmt::Display for SemanticErrorKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -133,6 +224,10 @@ pub struct SemanticError {
     pub span: Span,
     pub kind: SemanticErrorKind,
     pub message: String,
+    /// Explanations shown as notes after the message
+    pub notes: Vec<String>,
+    /// How to fix the error, shown after the notes
+    pub help: Option<String>,
 }
 
 impl SemanticError {
@@ -141,8 +236,22 @@ impl SemanticError {
             span,
             kind,
             message: message.into(),
+            notes: Vec::new(),
+            help: None,
         }
     }
+
+    /// Add a note explaining the error, when there is one
+    pub fn with_note(mut self, note: Option<String>) -> Self {
+        self.notes.extend(note);
+        self
+    }
+
+    /// Suggest a fix for the error
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
 }
 
 impl fmt::Display for SemanticError {
@@ -183,18 +292,37 @@ impl std::error::Error for CodeGenError {}
 #[derive(Debug)]
 pub enum CompilerError {
     Lex(LexError),
-    Parse(ParseError),
+    /// Syntax errors, in source order
+    Parse(Vec<ParseError>),
     Semantic(Vec<SemanticError>),
     CodeGen(CodeGenError),
     Io(std::io::Error),
     RustcInvocation(String),
+    /// A command line the compiler cannot act on, such as one naming an
+    /// unknown lint
+    Usage(String),
+    /// Compilation stopped once lints denied this many times were reported
+    DeniedLints(usize),
+    /// An error in an imported module, reported against the module's file
+    Module {
+        path: String,
+        error: Box<CompilerError>,
+    },
 }
 
 impl fmt::Display for CompilerError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             CompilerError::Lex(e) => write!(f, "{}", e),
-            CompilerError::Parse(e) => write!(f, "{}", e),
+            CompilerError::Parse(errors) => {
+                for (i, e) in errors.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{}", e)?;
+                }
+                Ok(())
+            }
             CompilerError::Semantic(errors) => {
                 writeln!(f, "Semantic errors:")?;
                 for error in errors {
@@ -205,6 +333,38 @@ impl fmt::Display for CompilerError {
             CompilerError::CodeGen(e) => write!(f, "{}", e),
             CompilerError::Io(e) => write!(f, "I/O error: {}", e),
             CompilerError::RustcInvocation(msg) => write!(f, "rustc invocation error: {}", msg),
+            CompilerError::Usage(msg) => write!(f, "{}", msg),
+            CompilerError::DeniedLints(count) => write!(
+                f,
+                "aborting due to {} denied lint{}",
+                count,
+                if *count == 1 { "" } else { "s" }
+            ),
+            CompilerError::Module { path, error } => match error.as_ref() {
+                CompilerError::Parse(errors) => {
+                    for (i, e) in errors.iter().enumerate() {
+                        let mut e = e.clone();
+                        e.message = format!("{}: {}", path, e.message);
+                        if i > 0 {
+                            writeln!(f)?;
+                        }
+                        write!(f, "{}", e)?;
+                    }
+                    Ok(())
+                }
+                CompilerError::Semantic(errors) => {
+                    writeln!(f, "Semantic errors:")?;
+                    for e in errors {
+                        writeln!(
+                            f,
+                            "  Semantic error at {} ({}): {}: {}",
+                            e.span, e.kind, path, e.message
+                        )?;
+                    }
+                    Ok(())
+                }
+                error => write!(f, "{}: {}", path, error),
+            },
         }
     }
 }
@@ -213,11 +373,14 @@ impl std::error::Error for CompilerError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
             CompilerError::Lex(e) => Some(e),
-            CompilerError::Parse(e) => Some(e),
+            CompilerError::Parse(errors) => errors.first().map(|e| e as &dyn std::error::Error),
             CompilerError::Semantic(errors) => errors.first().map(|e| e as &dyn std::error::Error),
             CompilerError::CodeGen(e) => Some(e),
             CompilerError::Io(e) => Some(e),
-            CompilerError::RustcInvocation(_) => None,
+            CompilerError::RustcInvocation(_)
+            | CompilerError::Usage(_)
+            | CompilerError::DeniedLints(_) => None,
+            CompilerError::Module { error, .. } => Some(error.as_ref()),
         }
     }
 }
@@ -230,6 +393,12 @@ impl From<LexError> for CompilerError {
 
 impl From<ParseError> for CompilerError {
     fn from(e: ParseError) -> Self {
+        CompilerError::Parse(vec![e])
+    }
+}
+
+impl From<Vec<ParseError>> for CompilerError {
+    fn from(e: Vec<ParseError>) -> Self {
         CompilerError::Parse(e)
     }
 }
@@ -252,6 +421,245 @@ impl From<std::io::Error> for CompilerError {
     }
 }
 
+impl CompilerError {
+    /// `error`, which is in the imported module read from `path`
+    pub fn in_module(path: &std::path::Path, error: impl Into<CompilerError>) -> Self {
+        CompilerError::Module {
+            path: path.display().to_string(),
+            error: Box::new(error.into()),
+        }
+    }
+
+    /// The error as diagnostics, one per syntax or semantic error
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        match self {
+            CompilerError::Lex(e) => vec![Diagnostic::error(&e.message)
+                .with_code(LEX_ERROR)
+                .with_span(e.span)],
+            CompilerError::Parse(errors) => errors
+                .iter()
+                .map(|e| {
+                    Diagnostic::error(&e.message)
+                        .with_code(PARSE_ERROR)
+                        .with_span(e.span)
+                        .with_label(e.label())
+                })
+                .collect(),
+            CompilerError::Semantic(errors) => errors
+                .iter()
+                .map(|e| {
+                    let diagnostic = e.notes.iter().fold(
+                        Diagnostic::error(&e.message)
+                            .with_code(e.kind.code())
+                            .with_span(e.span),
+                        |diagnostic, note| diagnostic.with_child("note", note),
+                    );
+                    match &e.help {
+                        Some(help) => diagnostic.with_child("help", help),
+                        None => diagnostic,
+                    }
+                })
+                .collect(),
+            CompilerError::CodeGen(e) => {
+                vec![Diagnostic::error(&e.message).with_code(CODEGEN_ERROR)]
+            }
+            CompilerError::Io(e) => vec![Diagnostic::error(e.to_string())],
+            CompilerError::RustcInvocation(msg) => vec![Diagnostic::error(msg)],
+            CompilerError::Usage(_) | CompilerError::DeniedLints(_) => {
+                vec![Diagnostic::error(self.to_string())]
+            }
+            CompilerError::Module { path, error } => error
+                .diagnostics()
+                .into_iter()
+                .map(|diagnostic| match diagnostic.file {
+                    Some(_) => diagnostic,
+                    None => diagnostic.in_file(path),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Error code of lexical errors
+pub const LEX_ERROR: &str = "C0001";
+/// Error code of parse errors
+pub const PARSE_ERROR: &str = "C0101";
+/// Error code of code generation errors
+pub const CODEGEN_ERROR: &str = "C0301";
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const GUTTER_STYLE: &str = "\x1b[1;34m";
+
+/// `text` in the ANSI `style` when `color` is set
+fn paint(text: &str, style: &str, color: bool) -> String {
+    if color {
+        format!("{}{}{}", style, text, RESET)
+    } else {
+        text.to_string()
+    }
+}
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl Severity {
+    fn style(self) -> &'static str {
+        match self {
+            Severity::Error => "\x1b[1;31m",
+            Severity::Warning => "\x1b[1;33m",
+            Severity::Note => "\x1b[1;32m",
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Note => write!(f, "note"),
+        }
+    }
+}
+
+/// An error, warning or note from any phase of the compiler, rendered the
+/// way rustc renders its own:
+///
+/// ```text
+/// error[C0101]: Expected expression
+///  --> main.crst:2:13
+///   |
+/// 2 |     let x = ;
+///   |             ^ expected expression, found ';'
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Error code such as `C0101`, listed in the error handling docs, or
+    /// rustc's `E0308`
+    pub code: Option<String>,
+    pub message: String,
+    pub file: Option<String>,
+    /// Where the problem is; `None` when the phase reporting it has no
+    /// position
+    pub span: Option<Span>,
+    /// Shown after the carets under the span
+    pub label: Option<String>,
+    /// Notes and help shown after the snippet, as `(level, message)`
+    pub children: Vec<(String, String)>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            code: None,
+            message: message.into(),
+            file: None,
+            span: None,
+            label: None,
+            children: Vec::new(),
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, message)
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, message)
+    }
+
+    pub fn note(message: impl Into<String>) -> Self {
+        Self::new(Severity::Note, message)
+    }
+
+    pub fn with_code(mut self, code: impl Into<String>) -> Self {
+        self.code = Some(code.into());
+        self
+    }
+
+    /// Point the diagnostic at `span`; spans on line 0, which nodes without
+    /// a recorded position carry, are ignored
+    pub fn with_span(mut self, span: Span) -> Self {
+        if span.start.line > 0 {
+            self.span = Some(span);
+        }
+        self
+    }
+
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// Add a note or help line, such as `("help", "...")`
+    pub fn with_child(mut self, level: impl Into<String>, message: impl Into<String>) -> Self {
+        self.children.push((level.into(), message.into()));
+        self
+    }
+
+    pub fn in_file(mut self, file: impl Into<String>) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+
+    /// Render the diagnostic, with ANSI colors when `color` is set; `source`
+    /// is the text of its file, from which the offending line is shown
+    pub fn render(&self, source: Option<&str>, color: bool) -> String {
+        let style = self.severity.style();
+        let mut rendered = match &self.code {
+            Some(code) => paint(&format!("{}[{}]", self.severity, code), style, color),
+            None => paint(&self.severity.to_string(), style, color),
+        };
+        rendered.push_str(&paint(&format!(": {}", self.message), BOLD, color));
+        rendered.push('\n');
+
+        let width = self
+            .span
+            .map_or(0, |span| span.start.line.to_string().len());
+        let gutter = " ".repeat(width);
+        let arrow = paint("-->", GUTTER_STYLE, color);
+        match (&self.file, self.span) {
+            (Some(file), Some(span)) => {
+                rendered.push_str(&format!("{}{} {}:{}\n", gutter, arrow, file, span.start));
+            }
+            (Some(file), None) => rendered.push_str(&format!(" {} {}\n", arrow, file)),
+            _ => {}
+        }
+
+        let snippet = self.span.zip(source).and_then(|(span, source)| {
+            span_snippet(source, span, self.label.as_deref(), self.severity, color)
+        });
+        if let Some(snippet) = snippet {
+            rendered.push_str(&snippet);
+            rendered.push('\n');
+        } else if let Some(label) = &self.label {
+            push_child(&mut rendered, &gutter, "note", label, color);
+        }
+        for (level, message) in &self.children {
+            push_child(&mut rendered, &gutter, level, message, color);
+        }
+        rendered
+    }
+}
+
+fn push_child(rendered: &mut String, gutter: &str, level: &str, message: &str, color: bool) {
+    rendered.push_str(&format!(
+        "{} {} {}: {}\n",
+        gutter,
+        paint("=", GUTTER_STYLE, color),
+        paint(level, BOLD, color),
+        message
+    ));
+}
+
 /// Result type for compiler operations
 pub type Result<T> = std::result::Result<T, CompilerError>;
 
@@ -315,6 +723,87 @@ mod tests {
         assert!(matches!(compiler_error, CompilerError::Lex(_)));
     }
 
+    #[test]
+    fn test_diagnostic_render() {
+        let source = "void f() {\n    let x = y;\n}\n";
+        let error = CompilerError::in_module(
+            std::path::Path::new("lib.crst"),
+            ParseError::new(
+                Span::new(Position::new(2, 13), Position::new(2, 14)),
+                "expected expression",
+                vec!["literal".to_string()],
+                "'y'",
+            ),
+        );
+        let diagnostic = &error.diagnostics()[0];
+        assert_eq!(
+            diagnostic.render(Some(source), false),
+            "error[C0101]: expected expression\n --> lib.crst:2:13\n  |\n2 |     let x = y;\n  |             ^ expected literal, found 'y'\n"
+        );
+        assert_eq!(
+            diagnostic.render(None, false),
+            "error[C0101]: expected expression\n --> lib.crst:2:13\n  = note: expected literal, found 'y'\n"
+        );
+        let colored = diagnostic.render(Some(source), true);
+        assert!(colored.starts_with("\x1b[1;31merror[C0101]\x1b[0m\x1b[1m: expected expression"));
+        assert!(colored.contains("\x1b[1;31m^ expected literal, found 'y'\x1b[0m"));
+
+        // The span is underlined across its width, or to the end of its
+        // first line
+        let wide = Diagnostic::warning("unused")
+            .in_file("a.crst")
+            .with_span(Span::new(Position::new(2, 9), Position::new(2, 14)))
+            .with_child("help", "remove it");
+        assert_eq!(
+            wide.render(Some(source), false),
+            "warning: unused\n --> a.crst:2:9\n  |\n2 |     let x = y;\n  |         ^^^^^\n  = help: remove it\n"
+        );
+        let multiline = Diagnostic::error("body")
+            .with_span(Span::new(Position::new(1, 10), Position::new(3, 2)));
+        assert!(multiline
+            .render(Some(source), false)
+            .ends_with("1 | void f() {\n  |          ^\n"));
+    }
+
+    #[test]
+    fn test_compiler_error_diagnostics() {
+        let unknown = Span::new(Position::new(0, 0), Position::new(0, 0));
+        let error = CompilerError::in_module(
+            std::path::Path::new("m.crst"),
+            vec![
+                SemanticError::new(unknown, SemanticErrorKind::UndefinedVariable, "no 'x'"),
+                SemanticError::new(unknown, SemanticErrorKind::TypeMismatch, "bad")
+                    .with_note(Some("why".to_string())),
+            ],
+        );
+        let diagnostics = error.diagnostics();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].code.as_deref(), Some("C0201"));
+        assert_eq!(diagnostics[0].span, None);
+        assert_eq!(
+            diagnostics[1].render(None, false),
+            "error[C0202]: bad\n --> m.crst\n = note: why\n"
+        );
+        // The module's path is kept out of the diagnostics' messages, and
+        // shown before them in Display
+        assert!(error
+            .to_string()
+            .contains("Semantic error at 0:0-0:0 (type mismatch): m.crst: bad"));
+
+        let at = |line| Span::new(Position::new(line, 5), Position::new(line, 5));
+        let error = CompilerError::from(vec![
+            ParseError::new(at(2), "expected expression", vec![], "';'"),
+            ParseError::new(at(7), "expected ')'", vec![], "'int'"),
+        ]);
+        let lines: Vec<usize> = error
+            .diagnostics()
+            .iter()
+            .map(|d| d.span.unwrap().start.line)
+            .collect();
+        assert_eq!(lines, [2, 7]);
+        assert_eq!(error.to_string().lines().count(), 2);
+    }
+
     #[test]
     fn test_semantic_error_kinds() {
         assert_eq!(
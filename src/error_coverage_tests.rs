@@ -109,6 +109,22 @@ mod tests {
         assert!(display.contains("symbol"));
     }
 
+    #[test]
+    fn test_parse_error_display_with_snippet() {
+        let span = Span::new(Position::new(2, 13), Position::new(2, 13));
+        let error = ParseError::new(span, "syntax error", vec![], "';'")
+            .with_snippet("void f() {\n    let x = ;\n}\n");
+        let display = format!("{}", error);
+        assert!(display.ends_with("  |\n2 |     let x = ;\n  |             ^"));
+    }
+
+    #[test]
+    fn test_parse_error_snippet_out_of_range() {
+        let span = Span::new(Position::new(9, 1), Position::new(9, 1));
+        let error = ParseError::new(span, "syntax error", vec![], "EOF").with_snippet("x");
+        assert_eq!(error.snippet, None);
+    }
+
     #[test]
     fn test_parse_error_as_std_error() {
         let span = Span::new(Position::new(1, 1), Position::new(1, 1));
@@ -223,7 +239,7 @@ mod tests {
             vec![],
             "test",
         );
-        let compiler_error = CompilerError::Parse(parse_error);
+        let compiler_error = CompilerError::Parse(vec![parse_error]);
         let display = format!("{}", compiler_error);
         assert!(display.contains("Parse error"));
     }
@@ -299,7 +315,7 @@ mod tests {
             vec![],
             "test",
         );
-        let compiler_error = CompilerError::Parse(parse_error);
+        let compiler_error = CompilerError::Parse(vec![parse_error]);
         assert!(compiler_error.source().is_some());
     }
 
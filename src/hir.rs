@@ -0,0 +1,843 @@
+// Copyright (c) 2026 Mark Ferrell
+// Licensed under the MIT License. See LICENSE.txt in the project root.
+
+//! The lowered, type-annotated program semantic analysis hands to code
+//! generation.
+//!
+//! Lowering rewrites the checked program into the smaller language codegen
+//! translates, and carries the types semantic analysis found alongside it so
+//! codegen reads them instead of working them out again:
+//!
+//! - `for (init; condition; step) body` becomes `{ init; while (condition)
+//!   { body step; } }`. Loops whose body continues them keep their `for`,
+//!   since `continue` would skip the step.
+//! - `p->field` is already `(*p).field` once parsed.
+//! - A value stored by a declaration, assignment or return in a place of
+//!   another numeric type gets the cast C would have applied implicitly: an
+//!   integer literal takes the place's type, a narrower value widens, and
+//!   a value stored in a pointer to a trait it implements, or inserted
+//!   into a collection of them, is boxed.
+//! - The trailing arguments of a call to a variadic function are passed as
+//!   an array the function's slice parameter borrows: `sum(1, 2, 3)` calls
+//!   `sum(&[1, 2, 3])`.
+//! - A call of a function of a module imported whole, `util.twice(3)`, is
+//!   made through the module's path, `util::twice(3)`.
+//! - `&n` passed for a `var &` parameter is cast to the parameter's type,
+//!   which codegen writes as a mutable borrow.
+//! - `sizeof` and `alignof` of a value measure the type analysis found for
+//!   it: `sizeof(big)` becomes `sizeof(i64)`.
+//! - A condition, or operand of `!`, `&&` or `||`, that is not a boolean is
+//!   marked with how C tests it for truth: a pointer when it is not null,
+//!   an Option when it holds a value, an integer when it is not zero.
+//! - A for-in loop over an array of structs or trait objects borrows the
+//!   elements instead of moving them out: it iterates over `a.iter()`, or
+//!   `a.iter_mut()` when the body writes through the loop variable.
+//! - A cast to an integer type that cannot hold every value of the type
+//!   cast from, in a function returning `T?`, is marked as one to check.
+//! - Lambdas whose captured variables are all only read and copied capture
+//!   them by value, and the bodies of lambdas are lowered as functions'.
+//! - Every local keeps the type semantic analysis found for it in
+//!   [`Hir::locals`], which codegen reads instead of working it out from
+//!   the declaration.
+//!
+//! What analysis found is recorded by [`Site`]: the function, with methods
+//! named after the type they are of, and the statement it was found in.
+//!
+//! ```text
+//! long total = 0;                    long total = (long)0;
+//! double mean = count;               double mean = (double)count;
+//! for (int i = 0; i < n; i++) {  =>  { int i = 0; while (i < n) {
+//!     total += i;                        total += i; i++; } }
+//! }
+//! ```
+
+use crate::ast::{Block, Expression, File, Ident, Item, Statement, Truthiness, Type, UnaryOp};
+use crate::error::Position;
+use crate::tailcall::child_blocks_mut;
+use std::collections::BTreeMap;
+
+/// A statement semantic analysis recorded something about: the function it
+/// is in, named by [`site_function`], and where the statement starts
+///
+/// Statements of nested functions and lambdas are in the function around
+/// them. A statement the compiler built, which has no position, takes that
+/// of the statement it is part of.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Site {
+    pub function: String,
+    pub position: Position,
+}
+
+impl Site {
+    pub fn new(function: impl Into<String>, position: Position) -> Self {
+        Self {
+            function: function.into(),
+            position,
+        }
+    }
+
+    /// The site of statement `i` of `block`, a block within this site's
+    /// statement
+    pub fn statement(&self, block: &Block, i: usize) -> Site {
+        Site {
+            function: self.function.clone(),
+            position: block.positions.get(i).copied().unwrap_or(self.position),
+        }
+    }
+}
+
+/// How sites name the function `name`: as written, or `Type::name` for a
+/// method of the struct, trait or impl target `owner`, so methods of the
+/// same name on different types are told apart
+pub fn site_function(owner: Option<&Type>, name: &str) -> String {
+    match owner {
+        Some(owner) => format!("{}::{}", owner, name),
+        None => name.to_string(),
+    }
+}
+
+/// Types semantic analysis found for the locals declared at each site:
+/// site -> (name, type)
+pub type LocalTypes = BTreeMap<Site, Vec<(String, Type)>>;
+
+/// Values converted implicitly where another numeric type is expected:
+/// site -> (value, or the assignment storing it, expected type)
+pub type ImplicitConversions = BTreeMap<Site, Vec<(Expression, Type)>>;
+
+/// Arguments converted implicitly to the type of their parameter:
+/// site -> (call, argument index, parameter type)
+pub type ArgumentConversions = BTreeMap<Site, Vec<(Expression, usize, Type)>>;
+
+/// Calls collecting their trailing arguments into a variadic parameter:
+/// site -> (call, number of other parameters)
+pub type VariadicCalls = BTreeMap<Site, Vec<(Expression, usize)>>;
+
+/// Calls of functions of modules imported whole, `util.twice(3)`: site ->
+/// call
+pub type ModuleCalls = BTreeMap<Site, Vec<Expression>>;
+
+/// Lambdas capturing by value: site -> lambda as written
+pub type ValueLambdas = BTreeMap<Site, Vec<Expression>>;
+
+/// Values `sizeof` and `alignof` measure: site -> (the `sizeof` or
+/// `alignof`, type of the value)
+pub type SizeofOperands = BTreeMap<Site, Vec<(Expression, Type)>>;
+
+/// Values tested for truth that are not booleans: site -> (value, how it
+/// is tested)
+pub type TruthinessTests = BTreeMap<Site, Vec<(Expression, Truthiness)>>;
+
+/// Arrays whose elements a for-in loop borrows: site -> (array, whether
+/// the loop writes through its elements)
+pub type BorrowedArrays = BTreeMap<Site, Vec<(Expression, bool)>>;
+
+/// Casts to an integer type that cannot hold every value of the integer
+/// type cast from, in functions returning `T?`: site -> (cast, type cast
+/// from)
+pub type NarrowingCasts = BTreeMap<Site, Vec<(Expression, Type)>>;
+
+/// What semantic analysis found checking a file, by site
+pub struct Analysis<'a> {
+    /// Types of the `let` and `var` locals
+    pub locals: &'a LocalTypes,
+    /// Values stored in a declaration, assignment or return whose type is
+    /// another numeric type, cast explicitly
+    pub conversions: &'a ImplicitConversions,
+    /// Arguments of another numeric type than their parameter, and elements
+    /// boxed as they are inserted into a collection of trait objects,
+    /// converted explicitly
+    pub arguments: &'a ArgumentConversions,
+    /// Calls to variadic functions, whose trailing arguments are passed as
+    /// an array
+    pub variadic_calls: &'a VariadicCalls,
+    /// Calls of the functions of modules imported whole, made through the
+    /// module's path
+    pub module_calls: &'a ModuleCalls,
+    /// Lambdas whose captured variables are all only read and copied,
+    /// marked to capture by value
+    pub value_lambdas: &'a ValueLambdas,
+    /// Values `sizeof` and `alignof` measure, whose types are measured
+    /// instead
+    pub sizeof_operands: &'a SizeofOperands,
+    /// Values tested for truth that are not booleans, marked with how they
+    /// are tested
+    pub truthiness_tests: &'a TruthinessTests,
+    /// Arrays of structs and trait objects for-in loops iterate over,
+    /// borrowed through `iter()` or `iter_mut()`
+    pub borrowed_arrays: &'a BorrowedArrays,
+    /// Casts that may change the value cast, in functions returning `T?`,
+    /// marked as checked
+    pub narrowing_casts: &'a NarrowingCasts,
+}
+
+/// A checked program lowered for code generation
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hir {
+    /// The program with its loops and implicit conversions lowered
+    pub file: File,
+    /// Types semantic analysis found for the locals
+    pub locals: LocalTypes,
+}
+
+/// Lower `file` with what `analysis` found checking it
+pub fn lower(file: &File, analysis: &Analysis) -> Hir {
+    let mut file = file.clone();
+    for (site, body, returns) in bodies_mut(&mut file.items) {
+        // Before anything in the lambdas is lowered, as they were recorded
+        capture_block(body, &site, analysis.value_lambdas);
+        lower_block(body, &site, analysis.conversions, returns.as_ref());
+        // After the conversions of stored values, which wrap the values
+        // but leave what is in them as written, so it is found as it was
+        // recorded
+        expressions_block(body, &site, analysis);
+    }
+    Hir {
+        file,
+        locals: analysis.locals.clone(),
+    }
+}
+
+/// The function bodies among `items`, including methods and default trait
+/// methods, with the site around their statements and their return types
+pub(crate) fn bodies_mut(items: &mut [Item]) -> Vec<(Site, &mut Block, Option<Type>)> {
+    let mut bodies = Vec::new();
+    for item in items {
+        let (owner, functions) = match item {
+            Item::Function(func) => (None, vec![func]),
+            Item::Struct(s) => (
+                Some(Type::Ident(s.name.clone())),
+                s.methods.iter_mut().collect(),
+            ),
+            Item::Impl(i) => (Some(i.ty.clone()), i.methods.iter_mut().collect()),
+            Item::Trait(t) => {
+                let owner = Type::Ident(t.name.clone());
+                for method in &mut t.methods {
+                    if let Some(body) = &mut method.body {
+                        let function = site_function(Some(&owner), &method.name.name);
+                        let site = Site::new(function, Position::new(0, 0));
+                        bodies.push((site, body, method.return_type.clone()));
+                    }
+                }
+                continue;
+            }
+            Item::Namespace(namespace) => {
+                bodies.extend(bodies_mut(&mut namespace.items));
+                continue;
+            }
+            _ => continue,
+        };
+        for func in functions {
+            let site = Site::new(
+                site_function(owner.as_ref(), &func.name.name),
+                Position::new(0, 0),
+            );
+            bodies.push((site, &mut func.body, func.return_type.clone()));
+        }
+    }
+    bodies
+}
+
+/// What `recorded` holds for `site`
+fn at<'a, T>(recorded: &'a BTreeMap<Site, Vec<T>>, site: &Site) -> &'a [T] {
+    recorded.get(site).map(Vec::as_slice).unwrap_or_default()
+}
+
+/// Lower each statement of `block`, within `site` in a function returning
+/// `returns`, and the blocks nested in it
+fn lower_block(
+    block: &mut Block,
+    site: &Site,
+    conversions: &ImplicitConversions,
+    returns: Option<&Type>,
+) {
+    for i in 0..block.statements.len() {
+        let site = site.statement(block, i);
+        let stmt = &mut block.statements[i];
+        convert(stmt, at(conversions, &site), returns);
+        if let Statement::For { init, .. } = stmt {
+            convert(init, at(conversions, &site), returns);
+            for expr in statement_expressions_mut(init) {
+                lower_lambdas(expr, &site, conversions);
+            }
+        }
+        for expr in statement_expressions_mut(stmt) {
+            lower_lambdas(expr, &site, conversions);
+        }
+        for child in child_blocks_mut(stmt) {
+            lower_block(child, &site, conversions, returns);
+        }
+        if let Statement::NestedFunction {
+            body, return_type, ..
+        } = stmt
+        {
+            lower_block(body, &site, conversions, return_type.as_ref());
+        }
+        let lowered = lower_loop(std::mem::replace(stmt, Statement::Break(None)));
+        *stmt = lowered;
+    }
+}
+
+/// Lower the bodies of the lambdas in `expr`, in the statement at `site`
+fn lower_lambdas(expr: &mut Expression, site: &Site, conversions: &ImplicitConversions) {
+    match expr {
+        Expression::Lambda {
+            body, return_type, ..
+        } => lower_block(body, site, conversions, return_type.as_ref()),
+        expr => {
+            for child in crate::macros::child_expressions_mut(expr) {
+                lower_lambdas(child, site, conversions);
+            }
+        }
+    }
+}
+
+/// Cast the value `stmt` stores when `conversions` records it converted to
+/// the type of the place it is stored in, a function returning `returns`
+fn convert(stmt: &mut Statement, conversions: &[(Expression, Type)], returns: Option<&Type>) {
+    let (value, ty) = match stmt {
+        Statement::Let {
+            ty: Some(ty),
+            init: Some(value),
+            ..
+        }
+        | Statement::Var {
+            ty: Some(ty),
+            init: Some(value),
+            ..
+        } => (value, Some(&*ty)),
+        Statement::Return(Some(value)) => (value, returns),
+        Statement::Expr(
+            stored @ Expression::Binary {
+                op: crate::ast::BinaryOp::Assign,
+                ..
+            },
+        ) => {
+            // An assignment is recorded whole, the place naming the type
+            let Some((_, ty)) = conversions.iter().find(|(recorded, _)| recorded == stored) else {
+                return;
+            };
+            if let Expression::Binary { right, .. } = stored {
+                cast(right, ty);
+            }
+            return;
+        }
+        _ => return,
+    };
+    let recorded = |ty: &&Type| {
+        conversions
+            .iter()
+            .any(|(recorded, expected)| recorded == value && expected == *ty)
+    };
+    if let Some(ty) = ty.filter(recorded) {
+        cast(value, ty);
+    }
+}
+
+/// Replace `value` with its conversion to `ty`: a cast, or boxing when
+/// `ty` is a pointer, which only trait objects convert to
+fn cast(value: &mut Expression, ty: &Type) {
+    *value = match ty {
+        Type::Pointer { .. } => Expression::TypeScopedCall {
+            ty: Type::Ident(Ident::new("Box")),
+            method: Ident::new("new"),
+            args: vec![value.clone()],
+        },
+        _ => Expression::Cast {
+            expr: Box::new(value.clone()),
+            ty: ty.clone(),
+        },
+    };
+}
+
+/// Lower the expressions of `block`, within `site`, as `analysis` found
+/// them
+fn expressions_block(block: &mut Block, site: &Site, analysis: &Analysis) {
+    for i in 0..block.statements.len() {
+        let site = site.statement(block, i);
+        let stmt = &mut block.statements[i];
+        // Matched before the array is lowered, as it was recorded
+        let borrowed = match &*stmt {
+            Statement::ForIn { iter, .. } => at(analysis.borrowed_arrays, &site)
+                .iter()
+                .find(|(array, _)| array == iter)
+                .map(|(_, written)| *written),
+            _ => None,
+        };
+        for expr in statement_expressions_mut(stmt) {
+            lower_expression(expr, &site, analysis);
+        }
+        if let (Some(written), Statement::ForIn { iter, .. }) = (borrowed, &mut *stmt) {
+            let array = std::mem::replace(iter, Expression::Ident(Ident::new("_")));
+            *iter = Expression::MethodCall {
+                receiver: Box::new(array),
+                method: Ident::new(if written { "iter_mut" } else { "iter" }),
+                args: Vec::new(),
+            };
+        }
+        if let Statement::For { init, .. } = stmt {
+            for expr in statement_expressions_mut(init) {
+                lower_expression(expr, &site, analysis);
+            }
+        }
+        for child in child_blocks_mut(stmt) {
+            expressions_block(child, &site, analysis);
+        }
+        if let Statement::NestedFunction { body, .. } = stmt {
+            expressions_block(body, &site, analysis);
+        }
+    }
+}
+
+/// `expr`, in the statement at `site`, and the expressions in it with the
+/// arguments of calls converted to their parameters' types, trailing
+/// arguments collected into a borrowed array, functions of modules called
+/// through the module's path, values `sizeof` and `alignof` measure
+/// replaced by their types, casts that may change the value marked as
+/// checked, and tests for truth made explicit
+fn lower_expression(expr: &mut Expression, site: &Site, analysis: &Analysis) {
+    if let Expression::Lambda { body, .. } = expr {
+        expressions_block(body, site, analysis);
+        return;
+    }
+    // Matched before what is in `expr` is rewritten, as it was recorded
+    let converted: BTreeMap<usize, &Type> = at(analysis.arguments, site)
+        .iter()
+        .filter(|(call, _, _)| call == expr)
+        .map(|(_, index, ty)| (*index, ty))
+        .collect();
+    let fixed = at(analysis.variadic_calls, site)
+        .iter()
+        .find(|(call, _)| call == expr)
+        .map(|(_, fixed)| *fixed);
+    let module_call = at(analysis.module_calls, site).contains(expr);
+    let measured = at(analysis.sizeof_operands, site)
+        .iter()
+        .find(|(measure, _)| measure == expr)
+        .map(|(_, ty)| ty.clone());
+    let tested = at(analysis.truthiness_tests, site)
+        .iter()
+        .find(|(value, _)| value == expr)
+        .map(|(_, test)| *test);
+    let checked = at(analysis.narrowing_casts, site)
+        .iter()
+        .find(|(cast, _)| cast == expr)
+        .map(|(_, from)| from.clone());
+    for child in crate::macros::child_expressions_mut(expr) {
+        lower_expression(child, site, analysis);
+    }
+    if let Some(ty) = measured {
+        *expr = match expr {
+            Expression::Sizeof { .. } | Expression::SizeofExpr { .. } => Expression::Sizeof { ty },
+            _ => Expression::Alignof { ty },
+        };
+    }
+    if let Expression::Call { args, .. } | Expression::MethodCall { args, .. } = expr {
+        for (index, ty) in converted {
+            if let Some(arg) = args.get_mut(index) {
+                cast(arg, ty);
+            }
+        }
+    }
+    if let (Some(fixed), Expression::Call { args, .. }) = (fixed, &mut *expr) {
+        let elements = args.split_off(fixed.min(args.len()));
+        args.push(Expression::Unary {
+            op: UnaryOp::Ref,
+            expr: Box::new(Expression::ArrayLit { elements }),
+        });
+    }
+    if let (true, Expression::Call { func, args }) = (module_call, &mut *expr) {
+        if let Expression::FieldAccess {
+            expr: module,
+            field,
+        } = func.as_ref()
+        {
+            if let Expression::Ident(module) = module.as_ref() {
+                *expr = Expression::TypeScopedCall {
+                    ty: Type::Ident(module.clone()),
+                    method: field.clone(),
+                    args: std::mem::take(args),
+                };
+            }
+        }
+    }
+    if let (Some(from), Expression::Cast { expr: value, ty }) = (checked, &mut *expr) {
+        *expr = Expression::CheckedCast {
+            expr: std::mem::replace(value, Box::new(Expression::Ident(Ident::new("_")))),
+            from,
+            ty: ty.clone(),
+        };
+    }
+    // Last, around what the rewrites above made of the value
+    if let Some(test) = tested {
+        *expr = Expression::TruthTest {
+            expr: Box::new(std::mem::replace(expr, Expression::Ident(Ident::new("_")))),
+            test,
+        };
+    }
+}
+
+/// Mark the lambdas in `lambdas` made in `block`, within `site`, to capture
+/// by value
+fn capture_block(block: &mut Block, site: &Site, lambdas: &ValueLambdas) {
+    for i in 0..block.statements.len() {
+        let site = site.statement(block, i);
+        let stmt = &mut block.statements[i];
+        for expr in statement_expressions_mut(stmt) {
+            capture_lambdas(expr, &site, lambdas);
+        }
+        if let Statement::For { init, .. } = stmt {
+            for expr in statement_expressions_mut(init) {
+                capture_lambdas(expr, &site, lambdas);
+            }
+        }
+        for child in child_blocks_mut(stmt) {
+            capture_block(child, &site, lambdas);
+        }
+        if let Statement::NestedFunction { body, .. } = stmt {
+            capture_block(body, &site, lambdas);
+        }
+    }
+}
+
+/// Mark the lambdas in `expr`, in the statement at `site`, that are in
+/// `lambdas` to capture by value
+fn capture_lambdas(expr: &mut Expression, site: &Site, lambdas: &ValueLambdas) {
+    // Matched before the lambdas in its body are marked, as it was recorded
+    let recorded = at(lambdas, site).contains(expr);
+    match expr {
+        Expression::Lambda { body, by_value, .. } => {
+            *by_value = recorded;
+            capture_block(body, site, lambdas);
+        }
+        expr => {
+            for child in crate::macros::child_expressions_mut(expr) {
+                capture_lambdas(child, site, lambdas);
+            }
+        }
+    }
+}
+
+/// Expressions `stmt` holds directly, outside its nested blocks
+fn statement_expressions_mut(stmt: &mut Statement) -> Vec<&mut Expression> {
+    match stmt {
+        Statement::Let { init, .. } | Statement::Var { init, .. } => init.iter_mut().collect(),
+        Statement::Const { value, .. } | Statement::Expr(value) => vec![value],
+        Statement::Return(value) => value.iter_mut().collect(),
+        Statement::If { condition, .. }
+        | Statement::While { condition, .. }
+        | Statement::DoWhile { condition, .. } => vec![condition],
+        Statement::For {
+            condition,
+            increment,
+            ..
+        } => vec![condition, increment],
+        Statement::ForIn { iter, .. } => vec![iter],
+        Statement::Switch { expr, cases, .. } => std::iter::once(expr)
+            .chain(cases.iter_mut().flat_map(|case| &mut case.values))
+            .collect(),
+        Statement::StaticAssert(assertion) => vec![&mut assertion.condition],
+        Statement::Break(_)
+        | Statement::Continue(_)
+        | Statement::Unsafe(_)
+        | Statement::Block(_)
+        | Statement::NestedFunction { .. } => Vec::new(),
+    }
+}
+
+/// `stmt` with a `for` loop that never continues spelled as `while`
+fn lower_loop(stmt: Statement) -> Statement {
+    match stmt {
+        Statement::For {
+            label,
+            init,
+            condition,
+            increment,
+            mut body,
+        } if !continues(&body, label.as_ref(), true) => {
+            body.statements.push(Statement::Expr(increment));
+            Statement::Block(Block::new(vec![
+                *init,
+                Statement::While {
+                    label,
+                    condition,
+                    body,
+                },
+            ]))
+        }
+        stmt => stmt,
+    }
+}
+
+/// Whether `block`, the body of a loop labelled `label`, has a `continue`
+/// for that loop; `unlabelled` when a bare `continue` in it is one
+fn continues(block: &Block, label: Option<&crate::ast::Ident>, unlabelled: bool) -> bool {
+    block.statements.iter().any(|stmt| match stmt {
+        Statement::Continue(None) => unlabelled,
+        Statement::Continue(Some(target)) => Some(target) == label,
+        // A bare `continue` in an inner loop continues that loop
+        Statement::While { body, .. }
+        | Statement::DoWhile { body, .. }
+        | Statement::For { body, .. }
+        | Statement::ForIn { body, .. } => label.is_some() && continues(body, label, false),
+        Statement::NestedFunction { .. } => false,
+        stmt => crate::tailcall::child_blocks(stmt)
+            .into_iter()
+            .any(|block| continues(block, label, unlabelled)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::PrimitiveType;
+    use crate::codegen::{CodeGenerator, TargetLanguage};
+    use crate::parser::Parser;
+    use crate::semantic::SemanticAnalyzer;
+
+    fn lowered(source: &str) -> (Hir, String) {
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.analyze(&file).unwrap();
+        let hir = lower(&file, &analyzer.analysis());
+        let rust = CodeGenerator::new(TargetLanguage::Rust).generate_hir(&hir);
+        (hir, rust)
+    }
+
+    #[test]
+    fn test_lower_for_to_while() {
+        let (hir, rust) = lowered(
+            "int sum(int n) {\n    var int total = 0;\n    for (var int i = 0; i < n; i++) {\n        total = total + i;\n    }\n    return total;\n}\n",
+        );
+        let Item::Function(func) = &hir.file.items[0] else {
+            panic!("Expected function, got {:?}", hir.file.items[0]);
+        };
+        let Statement::Block(block) = &func.body.statements[1] else {
+            panic!("Expected block, got {:?}", func.body.statements[1]);
+        };
+        assert!(matches!(block.statements[0], Statement::Var { .. }));
+        assert!(matches!(block.statements[1], Statement::While { .. }));
+        assert!(
+            rust.contains("    {\n        let mut i: i32 = 0;\n        while (i < n) {\n"),
+            "{}",
+            rust
+        );
+    }
+
+    #[test]
+    fn test_lower_keeps_continued_for() {
+        let (hir, _) = lowered(
+            "int sum(int n) {\n    var int total = 0;\n    for (var int i = 0; i < n; i++) {\n        if (i == 2) {\n            continue;\n        }\n        while (total > 100) {\n            total = total - 1;\n        }\n        total = total + i;\n    }\n    return total;\n}\n",
+        );
+        let Item::Function(func) = &hir.file.items[0] else {
+            panic!("Expected function, got {:?}", hir.file.items[0]);
+        };
+        assert!(matches!(func.body.statements[1], Statement::For { .. }));
+    }
+
+    #[test]
+    fn test_lower_casts_implicit_conversions() {
+        let (hir, rust) = lowered(
+            "long f() {\n    var long total = 0;\n    total = 5;\n    let n = 3;\n    return 1;\n}\n",
+        );
+        assert!(
+            rust.contains("let mut total: i64 = (0 as i64);"),
+            "{}",
+            rust
+        );
+        assert!(rust.contains("(total = (5 as i64));"), "{}", rust);
+        assert!(rust.contains("return (1 as i64);"), "{}", rust);
+        assert!(rust.contains("let n = 3;"), "{}", rust);
+        let site = Site::new("f", Position::new(4, 5));
+        assert_eq!(
+            hir.locals[&site],
+            [("n".to_string(), Type::Primitive(PrimitiveType::I32))]
+        );
+    }
+
+    #[test]
+    fn test_lower_tells_methods_of_one_name_apart() {
+        let (hir, rust) = lowered(
+            "struct Small {\n    long size(int n) {\n        let m = n;\n        return n;\n    }\n}\nstruct Large {\n    long size(long n) {\n        let m = n;\n        return n;\n    }\n}\n",
+        );
+        assert!(
+            rust.contains(
+                "pub fn size(n: i32) -> i64 {\n        let m = n;\n        return (n as i64);"
+            ),
+            "{}",
+            rust
+        );
+        assert!(
+            rust.contains("pub fn size(n: i64) -> i64 {\n        let m = n;\n        return n;"),
+            "{}",
+            rust
+        );
+        let small = Site::new("Small::size", Position::new(3, 9));
+        let large = Site::new("Large::size", Position::new(9, 9));
+        assert_eq!(hir.locals[&small][0].1, Type::Primitive(PrimitiveType::Int));
+        assert_eq!(hir.locals[&large][0].1, Type::Ident(Ident::new("long")));
+    }
+
+    #[test]
+    fn test_lower_casts_widened_arguments() {
+        let (_, rust) = lowered(
+            "struct Acc {\n    long total;\n    void add(var &self, long n) {\n        self.total = self.total + n;\n    }\n}\nlong twice(long n) {\n    return n * 2;\n}\nlong sum(long... values) {\n    return 0;\n}\nvoid main(int i) {\n    var Acc acc = (Acc){ .total = 0 };\n    acc.add(i);\n    let long t = twice(i) + twice(twice(i));\n    let long s = sum(twice(i), twice(i));\n}\n",
+        );
+        assert!(rust.contains("acc.add((i as i64));"), "{}", rust);
+        assert!(
+            rust.contains("let t: i64 = (twice((i as i64)) + twice(twice((i as i64))));"),
+            "{}",
+            rust
+        );
+        assert!(
+            rust.contains("let s: i64 = sum(&([twice((i as i64)), twice((i as i64))]));"),
+            "{}",
+            rust
+        );
+    }
+
+    #[test]
+    fn test_lower_casts_widened_values() {
+        let (_, rust) = lowered(
+            "long widen(u8 b, int i) {\n    var long total = b;\n    var int count = i;\n    total = i;\n    count = i;\n    float mean = i;\n    return b;\n}\n",
+        );
+        assert!(
+            rust.contains("let mut total: i64 = (b as i64);"),
+            "{}",
+            rust
+        );
+        assert!(rust.contains("let mut count: i32 = i;"), "{}", rust);
+        assert!(rust.contains("(total = (i as i64));"), "{}", rust);
+        assert!(rust.contains("(count = i);"), "{}", rust);
+        assert!(rust.contains("let mean: f64 = (i as f64);"), "{}", rust);
+        assert!(rust.contains("return (b as i64);"), "{}", rust);
+    }
+
+    #[test]
+    fn test_lower_collects_variadic_arguments() {
+        let (_, rust) = lowered(
+            "int sum(int... values) {\n    var int total = 0;\n    for (v in values) {\n        total += v;\n    }\n    return total;\n}\nint scaled(int factor, int... values) {\n    return factor * sum(values);\n}\nlong total(int[3] a) {\n    let int none = sum();\n    let int n = scaled(2, sum(1, 2), 3) + sum(&a);\n    return sum(4, 5);\n}\n",
+        );
+        assert!(
+            rust.contains("pub fn sum(values: &[i32]) -> i32 {"),
+            "{}",
+            rust
+        );
+        assert!(rust.contains("return (factor * sum(values));"), "{}", rust);
+        assert!(rust.contains("let none: i32 = sum(&([]));"), "{}", rust);
+        assert!(
+            rust.contains("let n: i32 = (scaled(2, &([sum(&([1, 2])), 3])) + sum(&(a)));"),
+            "{}",
+            rust
+        );
+        assert!(rust.contains("return (sum(&([4, 5])) as i64);"), "{}", rust);
+    }
+
+    #[test]
+    fn test_lower_measures_types_of_values() {
+        let (_, rust) = lowered(
+            "usize f() {\n    let char x = 'a';\n    let usize outer = sizeof(x);\n    if (outer > 0) {\n        let i64 x = 2;\n        return sizeof(x) + alignof(x);\n    }\n    return outer + sizeof(outer + 1);\n}\n",
+        );
+        assert!(
+            rust.contains("let outer: usize = std::mem::size_of::<char>();"),
+            "{}",
+            rust
+        );
+        // The shadowing local is measured, not the one it shadows
+        assert!(
+            rust.contains("return (std::mem::size_of::<i64>() + std::mem::align_of::<i64>());"),
+            "{}",
+            rust
+        );
+        assert!(
+            rust.contains("return (outer + std::mem::size_of::<usize>());"),
+            "{}",
+            rust
+        );
+    }
+
+    #[test]
+    fn test_lower_tests_truth_per_site() {
+        let source = "int f(Option<int> p, int* q) {\n    if (p) {\n        return 1;\n    }\n    if (!q) {\n        let int p = 0;\n        if (p || !p) {\n            return 2;\n        }\n    }\n    return 0;\n}\n";
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.set_int_conditions(crate::semantic::IntConditions::Compat);
+        analyzer.analyze(&file).unwrap();
+        let hir = lower(&file, &analyzer.analysis());
+        let Item::Function(func) = &hir.file.items[0] else {
+            panic!("Expected function, got {:?}", hir.file.items[0]);
+        };
+        let Statement::If { condition, .. } = &func.body.statements[0] else {
+            panic!("Expected if, got {:?}", func.body.statements[0]);
+        };
+        assert_eq!(
+            condition,
+            &Expression::TruthTest {
+                expr: Box::new(Expression::Ident(Ident::new("p"))),
+                test: Truthiness::Option,
+            }
+        );
+
+        let rust = CodeGenerator::new(TargetLanguage::Rust).generate_hir(&hir);
+        assert!(rust.contains("if p.is_some() {"), "{}", rust);
+        assert!(rust.contains("if q.is_null() {"), "{}", rust);
+        // The local shadowing the Option is tested as the integer it is
+        assert!(rust.contains("if ((p != 0) || (p == 0)) {"), "{}", rust);
+    }
+
+    #[test]
+    fn test_lower_lambdas() {
+        let (hir, rust) = lowered(
+            "int apply(fn(int) -> int f, int n) {\n    return f(n);\n}\nlong run() {\n    var int k = 2;\n    var long total = 0;\n    let scale = fn(int x) -> int {\n        return x * k;\n    };\n    k = 3;\n    var add = fn(int x) {\n        total = x;\n    };\n    add(scale(1));\n    return apply(fn(int x) -> int {\n        return x + 1;\n    }, 4);\n}\n",
+        );
+        let Item::Function(func) = &hir.file.items[1] else {
+            panic!("Expected function, got {:?}", hir.file.items[1]);
+        };
+        assert!(matches!(
+            &func.body.statements[2],
+            Statement::Let {
+                init: Some(Expression::Lambda { by_value: true, .. }),
+                ..
+            }
+        ));
+        assert!(
+            rust.contains("let scale = move |x: i32| -> i32 {"),
+            "{}",
+            rust
+        );
+        assert!(rust.contains("let mut add = |x: i32| {"), "{}", rust);
+        assert!(rust.contains("(total = (x as i64));"), "{}", rust);
+        assert!(rust.contains("return (apply(|x: i32| -> i32 {"), "{}", rust);
+    }
+
+    #[test]
+    fn test_lower_boxes_trait_objects() {
+        let (_, rust) = lowered(
+            "trait Shape {\n    float area(&self);\n}\nstruct Circle {\n    float r;\n}\nimpl Shape for Circle {\n    float area(&self) {\n        return self.r;\n    }\n}\nShape* make(float r) {\n    let c = (Circle){ .r = r };\n    let d = (Circle){ .r = r };\n    let e = (Circle){ .r = r };\n    var Shape* s = c;\n    s = d;\n    return e;\n}\n",
+        );
+        assert!(
+            rust.contains("let mut s: Box<dyn Shape> = Box::new(c);"),
+            "{}",
+            rust
+        );
+        assert!(rust.contains("(s = Box::new(d));"), "{}", rust);
+        assert!(rust.contains("return Box::new(e);"), "{}", rust);
+    }
+
+    #[test]
+    fn test_lower_boxes_inserted_trait_objects() {
+        let (_, rust) = lowered(
+            "trait Shape {\n    float area(&self);\n}\nstruct Circle {\n    float r;\n}\nimpl Shape for Circle {\n    float area(&self) {\n        return self.r;\n    }\n}\nusize total() {\n    let c = (Circle){ .r = 1.0 };\n    var Vec<Shape*> shapes = @Vec(Shape*).new();\n    shapes.push(c);\n    shapes.insert(0, (Circle){ .r = 2.0 });\n    return shapes.len();\n}\n",
+        );
+        assert!(
+            rust.contains("let mut shapes: Vec<Box<dyn Shape>> = Vec::<Box<dyn Shape>>::new();"),
+            "{}",
+            rust
+        );
+        assert!(rust.contains("shapes.push(Box::new(c));"), "{}", rust);
+        assert!(
+            rust.contains("shapes.insert(0, Box::new(Circle { r: 2.0 }));"),
+            "{}",
+            rust
+        );
+    }
+}
@@ -0,0 +1,673 @@
+// Copyright (c) 2026 Mark Ferrell
+// Licensed under the MIT License. See LICENSE.txt in the project root.
+
+//! Promotion of function-like macros into inline functions.
+//!
+//! A `#define` with parameters whose body is a single expression is usually
+//! a function C could not inline reliably. This optional pass, run before
+//! macro expansion, turns such a macro into a real `#[inline]` function,
+//! which semantic analysis checks once, as written, and codegen emits as a
+//! readable Rust function instead of text pasted at every use:
+//!
+//! ```text
+//! #define __SQUARE__(x) x * x           #[inline]
+//! int area(int w) {                     fn square(x: i32) -> i32 {
+//!     return __SQUARE__(w + 1);             return (x * x);
+//! }                                     }
+//!                                       pub fn area(w: i32) -> i32 {
+//!                                           return square((w + 1));
+//!                                       }
+//! ```
+//!
+//! The function is named after the macro, lowercased and without its
+//! underscores. Its parameter types come from the arguments of the macro's
+//! uses, which must all be literals, casts, or expressions of primitive
+//! variables and constants whose types agree. Its return type is that of
+//! the body, which may refer only to the parameters, primitive constants
+//! and functions. Macros whose uses or body cannot be typed this way, or
+//! that are used in constants, stay macros.
+//!
+//! The macro itself is kept, forwarding to the function, so uses the pass
+//! does not type, such as those in the arguments of `__println__`, call the
+//! function too. Unlike an expansion, a call evaluates each argument once.
+
+use crate::ast::{
+    Attribute, BinaryOp, Block, Expression, File, Function, Ident, Item, Literal, MacroDefinition,
+    MacroDelimiter, Param, PrimitiveType, Statement, Type, UnaryOp, Visibility,
+};
+use crate::lexer::{Token, TokenKind};
+use crate::macros::{arguments_source, lex, parse_tokens, pattern_bindings, split_arguments};
+use std::collections::{HashMap, HashSet};
+
+/// A macro promoted into a function
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Promotion {
+    pub macro_name: String,
+    pub function: String,
+}
+
+/// The type of a value, as far as the pass can tell
+#[derive(Debug, Clone, PartialEq)]
+enum Inferred {
+    Exact(PrimitiveType),
+    /// An unsuffixed integer literal, which takes the type of its context
+    Integer,
+    /// An unsuffixed floating-point literal
+    Floating,
+}
+
+impl Inferred {
+    /// The type a value of both `self` and `other` has, if they agree
+    fn unify(self, other: Inferred) -> Option<Inferred> {
+        match (self, other) {
+            (Inferred::Exact(a), Inferred::Exact(b)) => {
+                (canonical(&a) == canonical(&b)).then_some(Inferred::Exact(a))
+            }
+            (Inferred::Exact(ty), Inferred::Integer) | (Inferred::Integer, Inferred::Exact(ty)) => {
+                ty.is_integer().then_some(Inferred::Exact(ty))
+            }
+            (Inferred::Exact(ty), Inferred::Floating)
+            | (Inferred::Floating, Inferred::Exact(ty)) => {
+                is_float(&ty).then_some(Inferred::Exact(ty))
+            }
+            (Inferred::Integer, Inferred::Integer) => Some(Inferred::Integer),
+            (Inferred::Floating, Inferred::Floating) => Some(Inferred::Floating),
+            _ => None,
+        }
+    }
+
+    /// The type the value has, literals taking their default types
+    fn resolve(self) -> PrimitiveType {
+        match self {
+            Inferred::Exact(ty) => ty,
+            Inferred::Integer => PrimitiveType::Int,
+            Inferred::Floating => PrimitiveType::Float,
+        }
+    }
+}
+
+/// `int` and `float` are the same types as `i32` and `f64`
+fn canonical(ty: &PrimitiveType) -> PrimitiveType {
+    match ty {
+        PrimitiveType::Int => PrimitiveType::I32,
+        PrimitiveType::Float => PrimitiveType::F64,
+        ty => ty.clone(),
+    }
+}
+
+fn is_float(ty: &PrimitiveType) -> bool {
+    matches!(
+        ty,
+        PrimitiveType::F32 | PrimitiveType::F64 | PrimitiveType::Float
+    )
+}
+
+/// Types of the names in scope; `None` for names of other types, which
+/// hide any outer name they shadow
+type Scope = HashMap<String, Option<PrimitiveType>>;
+
+/// Promote the function-like macros of `file` that can be typed into
+/// inline functions
+///
+/// Returns the macros that were promoted, with the functions they became.
+pub fn promote_macros(file: &mut File) -> Vec<Promotion> {
+    let candidates: HashMap<String, Expression> = file
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            Item::MacroDefinition(macro_def)
+                if macro_def.delimiter == MacroDelimiter::Parens
+                    && !macro_def.params.is_empty()
+                    && !macro_def.variadic =>
+            {
+                let body = parse_tokens(&macro_def.body).ok()?;
+                Some((macro_def.name.name.clone(), body))
+            }
+            _ => None,
+        })
+        .collect();
+    if candidates.is_empty() {
+        return Vec::new();
+    }
+
+    let mut survey = Survey {
+        candidates: &candidates,
+        globals: Scope::new(),
+        returns: HashMap::new(),
+        uses: HashMap::new(),
+        rejected: HashSet::new(),
+        names: HashSet::new(),
+    };
+    survey.declare_items(&file.items);
+    survey.survey_items(&file.items);
+
+    // Each function takes the place of its macro, which expansion removes
+    let mut promotions = Vec::new();
+    for item in std::mem::take(&mut file.items) {
+        let Item::MacroDefinition(mut macro_def) = item else {
+            file.items.push(item);
+            continue;
+        };
+        let function = candidates
+            .get(&macro_def.name.name)
+            .and_then(|body| survey.promote(&macro_def, body));
+        let Some(function) = function else {
+            file.items.push(Item::MacroDefinition(macro_def));
+            continue;
+        };
+        survey.names.insert(function.name.name.clone());
+        macro_def.body = forwarding_body(&function);
+        promotions.push(Promotion {
+            macro_name: macro_def.name.name.clone(),
+            function: function.name.name.clone(),
+        });
+        file.items.push(Item::MacroDefinition(macro_def));
+        file.items.push(Item::Function(function));
+    }
+    promotions
+}
+
+/// Tokens of `name(params)`, the body of a macro forwarding to `function`
+fn forwarding_body(function: &Function) -> Vec<Token> {
+    let params: Vec<&str> = function
+        .params
+        .iter()
+        .map(|param| param.name.name.as_str())
+        .collect();
+    let source = format!("{}({})", function.name.name, params.join(", "));
+    lex(&source).unwrap_or_default()
+}
+
+/// The name a promoted macro's function gets: `__CLAMP_TO__` becomes
+/// `clamp_to`
+fn function_name(macro_name: &str) -> Option<String> {
+    let name = macro_name.trim_matches('_').to_lowercase();
+    let tokens = lex(&name).ok()?;
+    match tokens.as_slice() {
+        [ident] if ident.kind == TokenKind::Ident(name.clone()) => Some(name),
+        _ => None,
+    }
+}
+
+/// What the file says about the types of its macros' uses
+struct Survey<'a> {
+    /// Macros that may be promoted, with their parsed bodies
+    candidates: &'a HashMap<String, Expression>,
+    /// Constants and immutable statics of primitive types
+    globals: Scope,
+    /// Return types of the free functions returning primitive types
+    returns: HashMap<String, PrimitiveType>,
+    /// Argument types of each typed use of each candidate
+    uses: HashMap<String, Vec<Vec<Inferred>>>,
+    /// Candidates with a use that cannot be typed or that must stay constant
+    rejected: HashSet<String>,
+    /// Every name the file declares, which a function name must not reuse
+    names: HashSet<String>,
+}
+
+impl Survey<'_> {
+    fn declare_items(&mut self, items: &[Item]) {
+        for item in items {
+            match item {
+                Item::Function(func) => {
+                    self.names.insert(func.name.name.clone());
+                    if let Some(Type::Primitive(ty)) = &func.return_type {
+                        self.returns.insert(func.name.name.clone(), ty.clone());
+                    }
+                }
+                Item::Const(const_item) => {
+                    self.names.insert(const_item.name.name.clone());
+                    if let Type::Primitive(ty) = &const_item.ty {
+                        self.globals
+                            .insert(const_item.name.name.clone(), Some(ty.clone()));
+                    }
+                }
+                Item::Static(static_item) => {
+                    self.names.insert(static_item.name.name.clone());
+                    // Reading a mutable static needs an unsafe block
+                    if let (Type::Primitive(ty), false) = (&static_item.ty, static_item.mutable) {
+                        self.globals
+                            .insert(static_item.name.name.clone(), Some(ty.clone()));
+                    }
+                }
+                Item::Struct(struct_def) => {
+                    self.names.insert(struct_def.name.name.clone());
+                }
+                Item::Enum(enum_def) => {
+                    self.names.insert(enum_def.name.name.clone());
+                }
+                Item::Trait(trait_def) => {
+                    self.names.insert(trait_def.name.name.clone());
+                }
+                Item::Typedef(typedef) => {
+                    self.names.insert(typedef.name.name.clone());
+                }
+                Item::Extern(extern_block) => {
+                    for func in &extern_block.functions {
+                        self.names.insert(func.name.name.clone());
+                    }
+                }
+                Item::MacroDefinition(macro_def) => {
+                    self.names.insert(macro_def.name.name.clone());
+                }
+                Item::Namespace(namespace) => {
+                    self.names.insert(namespace.name.name.clone());
+                    self.declare_items(&namespace.items);
+                }
+                Item::Impl(_) | Item::Import(_) | Item::Export(_) | Item::StaticAssert(_) => {}
+            }
+        }
+    }
+
+    fn survey_items(&mut self, items: &[Item]) {
+        for item in items {
+            match item {
+                Item::Function(func) => self.survey_function(func),
+                Item::Struct(struct_def) => {
+                    for method in &struct_def.methods {
+                        self.survey_function(method);
+                    }
+                }
+                Item::Impl(impl_block) => {
+                    for method in &impl_block.methods {
+                        self.survey_function(method);
+                    }
+                }
+                Item::Trait(trait_def) => {
+                    for method in trait_def.methods.iter().filter(|m| m.body.is_some()) {
+                        self.survey_function(&method.as_function());
+                    }
+                }
+                // Constants cannot call functions that are not `const`
+                Item::Const(const_item) => self.reject_uses(&const_item.value),
+                Item::Static(static_item) => self.reject_uses(&static_item.value),
+                Item::StaticAssert(assertion) => self.reject_uses(&assertion.condition),
+                Item::Namespace(namespace) => self.survey_items(&namespace.items),
+                _ => {}
+            }
+        }
+    }
+
+    fn survey_function(&mut self, func: &Function) {
+        let mut scope = self.globals.clone();
+        self.declare_params(&func.params, &mut scope);
+        self.survey_block(&func.body, &scope);
+    }
+
+    fn declare_params(&mut self, params: &[Param], scope: &mut Scope) {
+        for param in params {
+            self.declare(&param.name, Some(&param.ty), None, scope);
+        }
+    }
+
+    /// Bring `name` into scope, with its declared type or the type of its
+    /// initializer
+    fn declare(
+        &mut self,
+        name: &Ident,
+        ty: Option<&Type>,
+        init: Option<&Expression>,
+        scope: &mut Scope,
+    ) {
+        let ty = match (ty, init) {
+            (Some(Type::Primitive(ty)), _) => Some(ty.clone()),
+            (None, Some(init)) => infer(init, scope, &self.returns).map(Inferred::resolve),
+            _ => None,
+        };
+        self.names.insert(name.name.clone());
+        scope.insert(name.name.clone(), ty);
+    }
+
+    fn survey_block(&mut self, block: &Block, scope: &Scope) {
+        let mut scope = scope.clone();
+        for stmt in &block.statements {
+            self.survey_statement(stmt, &mut scope);
+        }
+    }
+
+    fn survey_statement(&mut self, stmt: &Statement, scope: &mut Scope) {
+        match stmt {
+            Statement::Let { name, ty, init, .. } | Statement::Var { name, ty, init } => {
+                if let Some(init) = init {
+                    self.survey_expression(init, scope);
+                }
+                self.declare(name, ty.as_ref(), init.as_ref(), scope);
+            }
+            Statement::Const { name, ty, value } => {
+                self.survey_expression(value, scope);
+                self.declare(name, Some(ty), None, scope);
+            }
+            Statement::Expr(expr) | Statement::Return(Some(expr)) => {
+                self.survey_expression(expr, scope)
+            }
+            Statement::If {
+                condition,
+                then_block,
+                else_block,
+            } => {
+                self.survey_expression(condition, scope);
+                self.survey_block(then_block, scope);
+                if let Some(else_block) = else_block {
+                    self.survey_block(else_block, scope);
+                }
+            }
+            Statement::While {
+                condition, body, ..
+            }
+            | Statement::DoWhile { body, condition } => {
+                self.survey_expression(condition, scope);
+                self.survey_block(body, scope);
+            }
+            Statement::For {
+                init,
+                condition,
+                increment,
+                body,
+                ..
+            } => {
+                let mut scope = scope.clone();
+                self.survey_statement(init, &mut scope);
+                self.survey_expression(condition, &scope);
+                self.survey_expression(increment, &scope);
+                self.survey_block(body, &scope);
+            }
+            Statement::ForIn {
+                var, iter, body, ..
+            } => {
+                self.survey_expression(iter, scope);
+                let mut scope = scope.clone();
+                self.declare(var, None, None, &mut scope);
+                self.survey_block(body, &scope);
+            }
+            Statement::Switch {
+                expr,
+                cases,
+                default,
+            } => {
+                self.survey_expression(expr, scope);
+                for case in cases {
+                    for value in &case.values {
+                        self.survey_expression(value, scope);
+                    }
+                    self.survey_block(&case.body, scope);
+                }
+                if let Some(default) = default {
+                    self.survey_block(default, scope);
+                }
+            }
+            Statement::Unsafe(body) | Statement::Block(body) => self.survey_block(body, scope),
+            Statement::NestedFunction {
+                name, params, body, ..
+            } => {
+                self.declare(name, None, None, scope);
+                let mut inner = self.globals.clone();
+                self.declare_params(params, &mut inner);
+                self.survey_block(body, &inner);
+            }
+            Statement::StaticAssert(assertion) => self.reject_uses(&assertion.condition),
+            Statement::Return(None) | Statement::Break(_) | Statement::Continue(_) => {}
+        }
+    }
+
+    fn survey_expression(&mut self, expr: &Expression, scope: &Scope) {
+        match expr {
+            Expression::MacroCall { name, args, .. }
+                if self.candidates.contains_key(&name.name) =>
+            {
+                match argument_types(args, scope, &self.returns) {
+                    Some(types) => self.uses.entry(name.name.clone()).or_default().push(types),
+                    None => {
+                        self.rejected.insert(name.name.clone());
+                    }
+                }
+            }
+            Expression::Match { expr, arms } => {
+                self.survey_expression(expr, scope);
+                for arm in arms {
+                    let mut bound = HashSet::new();
+                    pattern_bindings(&arm.pattern, &mut bound);
+                    let mut scope = scope.clone();
+                    for name in bound {
+                        self.names.insert(name.clone());
+                        scope.insert(name, None);
+                    }
+                    self.survey_expression(&arm.body, &scope);
+                }
+            }
+            _ => {
+                for child in crate::macros::child_expressions(expr) {
+                    self.survey_expression(child, scope);
+                }
+            }
+        }
+    }
+
+    /// Keep the candidates used in `expr` as macros
+    fn reject_uses(&mut self, expr: &Expression) {
+        if let Expression::MacroCall { name, args, .. } = expr {
+            let used = std::iter::once(&name.name).chain(args.iter().map(|arg| &arg.text));
+            for name in used {
+                if self.candidates.contains_key(name) {
+                    self.rejected.insert(name.clone());
+                }
+            }
+        }
+        for child in crate::macros::child_expressions(expr) {
+            self.reject_uses(child);
+        }
+    }
+
+    /// The function `macro_def`, whose body is `body`, becomes, when its
+    /// uses and body can be typed and its name is free
+    fn promote(&self, macro_def: &MacroDefinition, body: &Expression) -> Option<Function> {
+        let name = &macro_def.name.name;
+        if self.rejected.contains(name) {
+            return None;
+        }
+        let function = function_name(name).filter(|function| !self.names.contains(function))?;
+
+        let uses = self.uses.get(name)?;
+        let mut scope = self.globals.clone();
+        let mut params = Vec::new();
+        for (i, param) in macro_def.params.iter().enumerate() {
+            let ty = uses
+                .iter()
+                .map(|types| types[i].clone())
+                .try_fold(None, |unified: Option<Inferred>, ty| match unified {
+                    Some(unified) => unified.unify(ty).map(Some),
+                    None => Some(Some(ty)),
+                })??
+                .resolve();
+            scope.insert(param.name.clone(), Some(ty.clone()));
+            params.push(Param {
+                name: param.clone(),
+                ty: Type::Primitive(ty),
+                variadic: false,
+            });
+        }
+        let return_type = infer(body, &scope, &self.returns)?.resolve();
+        if return_type == PrimitiveType::Void {
+            return None;
+        }
+
+        Some(Function {
+            visibility: Visibility::Private,
+            name: Ident::new(function),
+            generics: Vec::new(),
+            params,
+            return_type: Some(Type::Primitive(return_type)),
+            body: Block::new(vec![Statement::Return(Some(body.clone()))]),
+            doc_comments: Vec::new(),
+            attributes: vec![Attribute {
+                name: Ident::new("inline"),
+                args: Vec::new(),
+            }],
+        })
+    }
+}
+
+/// Types of the arguments of a macro use, if all of them can be typed
+fn argument_types(
+    args: &[crate::ast::Token],
+    scope: &Scope,
+    returns: &HashMap<String, PrimitiveType>,
+) -> Option<Vec<Inferred>> {
+    let tokens = lex(&arguments_source(args)).ok()?;
+    split_arguments(&tokens)
+        .into_iter()
+        .map(|arg| infer(&parse_tokens(arg).ok()?, scope, returns))
+        .collect()
+}
+
+/// The type of `expr`, when it is built from literals, casts, operators,
+/// names in `scope` and calls of functions in `returns`, and has no side
+/// effects of its own
+fn infer(
+    expr: &Expression,
+    scope: &Scope,
+    returns: &HashMap<String, PrimitiveType>,
+) -> Option<Inferred> {
+    match expr {
+        Expression::Literal(literal) => match literal {
+            Literal::Int(_) | Literal::RadixInt(..) => Some(Inferred::Integer),
+            Literal::Float(_) => Some(Inferred::Floating),
+            Literal::Suffixed(_, ty) => Some(Inferred::Exact(ty.clone())),
+            Literal::Char(_) => Some(Inferred::Exact(PrimitiveType::Char)),
+            Literal::Bool(_) => Some(Inferred::Exact(PrimitiveType::Bool)),
+            _ => None,
+        },
+        Expression::Ident(name) => scope.get(&name.name)?.clone().map(Inferred::Exact),
+        Expression::Cast {
+            expr,
+            ty: Type::Primitive(ty),
+        } => {
+            infer(expr, scope, returns)?;
+            Some(Inferred::Exact(ty.clone()))
+        }
+        Expression::Binary { op, left, right } => {
+            let left_type = infer(left, scope, returns)?;
+            let right_type = infer(right, scope, returns)?;
+            match op {
+                BinaryOp::Add
+                | BinaryOp::Sub
+                | BinaryOp::Mul
+                | BinaryOp::Div
+                | BinaryOp::Mod
+                | BinaryOp::BitAnd
+                | BinaryOp::BitOr
+                | BinaryOp::BitXor => left_type.unify(right_type),
+                BinaryOp::Shl | BinaryOp::Shr => Some(left_type),
+                BinaryOp::Eq
+                | BinaryOp::Ne
+                | BinaryOp::Lt
+                | BinaryOp::Gt
+                | BinaryOp::Le
+                | BinaryOp::Ge => left_type
+                    .unify(right_type)
+                    .map(|_| Inferred::Exact(PrimitiveType::Bool)),
+                BinaryOp::And | BinaryOp::Or => Some(Inferred::Exact(PrimitiveType::Bool)),
+                _ => None,
+            }
+        }
+        Expression::Unary {
+            op: UnaryOp::Not | UnaryOp::Neg,
+            expr,
+        } => infer(expr, scope, returns),
+        Expression::Ternary {
+            condition,
+            then_expr,
+            else_expr,
+        } => {
+            infer(condition, scope, returns)?;
+            infer(then_expr, scope, returns)?.unify(infer(else_expr, scope, returns)?)
+        }
+        Expression::Call { func, args } => {
+            let Expression::Ident(name) = func.as_ref() else {
+                return None;
+            };
+            // A local of the same name hides the function
+            if scope.contains_key(&name.name) {
+                return None;
+            }
+            for arg in args {
+                infer(arg, scope, returns)?;
+            }
+            returns.get(&name.name).cloned().map(Inferred::Exact)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::{CodeGenerator, TargetLanguage};
+    use crate::parser::Parser;
+
+    /// Promote and expand the macros of `source`, returning the promotions
+    /// and the Rust generated for the result
+    fn promote(source: &str) -> (Vec<String>, String) {
+        let mut file = Parser::new(source).unwrap().parse_file().unwrap();
+        let promotions = promote_macros(&mut file)
+            .into_iter()
+            .map(|promotion| format!("{} -> {}", promotion.macro_name, promotion.function))
+            .collect();
+        crate::macros::expand_macros(&mut file, crate::limits::DEFAULT_MAX_EXPANSION_TOKENS)
+            .unwrap();
+        let rust = CodeGenerator::new(TargetLanguage::Rust).generate(&file);
+        (promotions, rust)
+    }
+
+    #[test]
+    fn test_promotes_typed_expression_macros() {
+        let (promotions, rust) = promote(
+            "#define __SQUARE__(x) x * x\n#define __MAX__(a, b) a > b ? a : b\nint area(int w, int h) {\n    return __SQUARE__(w + 1) + __MAX__(w, h);\n}\nvoid main() {\n    let n = area(2, 3);\n    __println__(\"{}\", __SQUARE__(n));\n}\n",
+        );
+        assert_eq!(promotions, ["__SQUARE__ -> square", "__MAX__ -> max"]);
+        assert!(
+            rust.contains("#[inline]\nfn square(x: i32) -> i32 {"),
+            "{}",
+            rust
+        );
+        assert!(rust.contains("fn max(a: i32, b: i32) -> i32 {"), "{}", rust);
+        assert!(
+            rust.contains("return (square((w + 1)) + max(w, h));"),
+            "{}",
+            rust
+        );
+        // Uses the pass does not type forward to the function
+        assert!(rust.contains("println!(\"{}\", (square(n)));"), "{}", rust);
+    }
+
+    #[test]
+    fn test_literal_arguments_take_the_type_of_other_uses() {
+        let (promotions, rust) = promote(
+            "#define __HALF__(x) x / 2.0\nf32 f(f32 y) {\n    return __HALF__(y) + __HALF__(1.0);\n}\n",
+        );
+        assert_eq!(promotions, ["__HALF__ -> half"]);
+        assert!(rust.contains("fn half(x: f32) -> f32 {"), "{}", rust);
+    }
+
+    #[test]
+    fn test_untyped_macros_stay_macros() {
+        let sources = [
+            // Argument types disagree
+            "#define __TWICE__(x) x + x\nvoid main() {\n    i64 a = 1;\n    u8 b = 2;\n    let c = __TWICE__(a);\n    let d = __TWICE__(b);\n}\n",
+            // The body uses a name of the caller
+            "#define __SCALE__(x) x * factor\nint f(int factor) {\n    return __SCALE__(3);\n}\n",
+            // An argument that is not a primitive value
+            "#define __FIRST__(s) s.x\nstruct P { int x; }\nint f(P p) {\n    return __FIRST__(p);\n}\n",
+            // Used in a static assertion, which cannot call a function
+            "#define __DOUBLE__(x) x * 2\nstatic_assert(__DOUBLE__(4) == 8, \"doubles\");\n",
+            // The function name is taken
+            "#define __SQUARE__(x) x * x\nint square(int y) {\n    return __SQUARE__(y);\n}\n",
+            // Never used, so nothing gives its parameter a type
+            "#define __INC__(x) x + 1\nvoid main() {}\n",
+        ];
+        for source in sources {
+            let (promotions, rust) = promote(source);
+            assert!(promotions.is_empty(), "{:?} for {}", promotions, source);
+            assert!(!rust.contains("#[inline]"), "{}", rust);
+        }
+    }
+}
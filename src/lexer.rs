@@ -4,9 +4,16 @@
 //! Lexical analysis module for tokenizing Crusty source code.
 
 use crate::error::{LexError, Position, Span};
+use serde::{Deserialize, Serialize};
+
+/// Largest source file the lexer accepts, in bytes
+pub const MAX_SOURCE_BYTES: usize = 16 * 1024 * 1024;
+
+/// Largest string literal the lexer accepts, in bytes after unescaping
+pub const MAX_STRING_LITERAL_BYTES: usize = 64 * 1024;
 
 /// Token kinds in Crusty
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[allow(dead_code)]
 pub enum TokenKind {
     // Keywords
@@ -19,6 +26,7 @@ pub enum TokenKind {
     If,
     Else,
     While,
+    Do,
     For,
     In,
     Return,
@@ -30,6 +38,7 @@ pub enum TokenKind {
     Namespace,
     Extern,
     Unsafe,
+    Volatile,
     Loop,
     Match,
     Switch,
@@ -39,10 +48,20 @@ pub enum TokenKind {
 
     // Types
     Int,
+    I8,
+    I16,
     I32,
     I64,
+    Isize,
+    U8,
+    U16,
     U32,
     U64,
+    Usize,
+    /// C's `long`, as wide as the target platform makes it
+    Long,
+    /// C's `unsigned long`
+    ULong,
     Float,
     F32,
     F64,
@@ -86,6 +105,7 @@ pub enum TokenKind {
     Dec,         // --
     Dot,         // .
     Arrow,       // ->
+    FatArrow,    // =>
     DotDot,      // ..
     DotDotEq,    // ..=
     Question,    // ?
@@ -111,6 +131,10 @@ pub enum TokenKind {
     IntLiteral(String),
     FloatLiteral(String),
     StringLiteral(String),
+    /// `r"..."` or `r#"..."#`, whose text is taken as written
+    RawStringLiteral(String),
+    ByteStringLiteral(Vec<u8>),
+    WideStringLiteral(String),
     CharLiteral(char),
     BoolLiteral(bool),
     Null,
@@ -135,8 +159,218 @@ impl std::fmt::Display for TokenKind {
     }
 }
 
+impl TokenKind {
+    /// The fixed text of a keyword, type or punctuation token, or `None`
+    /// for literals, identifiers and end of file
+    pub fn spelling(&self) -> Option<&'static str> {
+        Some(match self {
+            TokenKind::Let => "let",
+            TokenKind::Var => "var",
+            TokenKind::Const => "const",
+            TokenKind::Static => "static",
+            TokenKind::Mut => "mut",
+            TokenKind::Define => "define",
+            TokenKind::If => "if",
+            TokenKind::Else => "else",
+            TokenKind::While => "while",
+            TokenKind::Do => "do",
+            TokenKind::For => "for",
+            TokenKind::In => "in",
+            TokenKind::Return => "return",
+            TokenKind::Break => "break",
+            TokenKind::Continue => "continue",
+            TokenKind::Struct => "struct",
+            TokenKind::Enum => "enum",
+            TokenKind::Typedef => "typedef",
+            TokenKind::Namespace => "namespace",
+            TokenKind::Extern => "extern",
+            TokenKind::Unsafe => "unsafe",
+            TokenKind::Volatile => "volatile",
+            TokenKind::Loop => "loop",
+            TokenKind::Match => "match",
+            TokenKind::Switch => "switch",
+            TokenKind::Case => "case",
+            TokenKind::Default => "default",
+            TokenKind::Auto => "auto",
+            TokenKind::Int => "int",
+            TokenKind::I8 => "i8",
+            TokenKind::I16 => "i16",
+            TokenKind::I32 => "i32",
+            TokenKind::I64 => "i64",
+            TokenKind::Isize => "isize",
+            TokenKind::U8 => "u8",
+            TokenKind::U16 => "u16",
+            TokenKind::U32 => "u32",
+            TokenKind::U64 => "u64",
+            TokenKind::Usize => "usize",
+            TokenKind::Long => "long",
+            TokenKind::ULong => "unsigned long",
+            TokenKind::Float => "float",
+            TokenKind::F32 => "f32",
+            TokenKind::F64 => "f64",
+            TokenKind::Bool => "bool",
+            TokenKind::Char => "char",
+            TokenKind::Void => "void",
+            TokenKind::Plus => "+",
+            TokenKind::Minus => "-",
+            TokenKind::Star => "*",
+            TokenKind::Slash => "/",
+            TokenKind::Percent => "%",
+            TokenKind::Eq => "==",
+            TokenKind::Ne => "!=",
+            TokenKind::Lt => "<",
+            TokenKind::Gt => ">",
+            TokenKind::Le => "<=",
+            TokenKind::Ge => ">=",
+            TokenKind::And => "&&",
+            TokenKind::Or => "||",
+            TokenKind::Not => "!",
+            TokenKind::BitAnd => "&",
+            TokenKind::BitOr => "|",
+            TokenKind::BitXor => "^",
+            TokenKind::BitNot => "~",
+            TokenKind::Shl => "<<",
+            TokenKind::Shr => ">>",
+            TokenKind::Assign => "=",
+            TokenKind::PlusEq => "+=",
+            TokenKind::MinusEq => "-=",
+            TokenKind::StarEq => "*=",
+            TokenKind::SlashEq => "/=",
+            TokenKind::PercentEq => "%=",
+            TokenKind::AndEq => "&=",
+            TokenKind::OrEq => "|=",
+            TokenKind::XorEq => "^=",
+            TokenKind::ShlEq => "<<=",
+            TokenKind::ShrEq => ">>=",
+            TokenKind::Inc => "++",
+            TokenKind::Dec => "--",
+            TokenKind::Dot => ".",
+            TokenKind::Arrow => "->",
+            TokenKind::FatArrow => "=>",
+            TokenKind::DotDot => "..",
+            TokenKind::DotDotEq => "..=",
+            TokenKind::Question => "?",
+            TokenKind::Colon => ":",
+            TokenKind::DoubleColon => "::",
+            TokenKind::LParen => "(",
+            TokenKind::RParen => ")",
+            TokenKind::LBrace => "{",
+            TokenKind::RBrace => "}",
+            TokenKind::LBracket => "[",
+            TokenKind::RBracket => "]",
+            TokenKind::Comma => ",",
+            TokenKind::Semicolon => ";",
+            TokenKind::Hash => "#",
+            TokenKind::Bang => "!",
+            TokenKind::At => "@",
+            TokenKind::Null => "NULL",
+            _ => return None,
+        })
+    }
+
+    /// How a parse error names this token kind when it was expected:
+    /// `';'` for fixed tokens, and the class such as `identifier` otherwise
+    pub fn description(&self) -> String {
+        match (self, self.spelling()) {
+            (_, Some(text)) => format!("'{}'", text),
+            (TokenKind::Ident(_), _) => "identifier".to_string(),
+            (TokenKind::IntLiteral(_), _) => "integer".to_string(),
+            (TokenKind::Eof, _) => "end of file".to_string(),
+            _ => "literal".to_string(),
+        }
+    }
+}
+
+/// The token a reserved word such as `let`, `int` or `true` is lexed as, or
+/// `None` when `text` is an identifier
+pub fn word_token(text: &str) -> Option<TokenKind> {
+    Some(match text {
+        "let" => TokenKind::Let,
+        "var" => TokenKind::Var,
+        "const" => TokenKind::Const,
+        "static" => TokenKind::Static,
+        "mut" => TokenKind::Mut,
+        "define" => TokenKind::Define,
+        "if" => TokenKind::If,
+        "else" => TokenKind::Else,
+        "while" => TokenKind::While,
+        "do" => TokenKind::Do,
+        "for" => TokenKind::For,
+        "in" => TokenKind::In,
+        "return" => TokenKind::Return,
+        "break" => TokenKind::Break,
+        "continue" => TokenKind::Continue,
+        "struct" => TokenKind::Struct,
+        "enum" => TokenKind::Enum,
+        "typedef" => TokenKind::Typedef,
+        "namespace" => TokenKind::Namespace,
+        "extern" => TokenKind::Extern,
+        "unsafe" => TokenKind::Unsafe,
+        "volatile" => TokenKind::Volatile,
+        "loop" => TokenKind::Loop,
+        "match" => TokenKind::Match,
+        "switch" => TokenKind::Switch,
+        "case" => TokenKind::Case,
+        "default" => TokenKind::Default,
+        "auto" => TokenKind::Auto,
+        "int" => TokenKind::Int,
+        "i8" => TokenKind::I8,
+        "i16" => TokenKind::I16,
+        "i32" => TokenKind::I32,
+        "i64" => TokenKind::I64,
+        "isize" => TokenKind::Isize,
+        "u8" => TokenKind::U8,
+        "u16" => TokenKind::U16,
+        "u32" => TokenKind::U32,
+        "u64" => TokenKind::U64,
+        "usize" => TokenKind::Usize,
+        "float" => TokenKind::Float,
+        "f32" => TokenKind::F32,
+        "f64" => TokenKind::F64,
+        "bool" => TokenKind::Bool,
+        "char" => TokenKind::Char,
+        "void" => TokenKind::Void,
+        "true" => TokenKind::BoolLiteral(true),
+        "false" => TokenKind::BoolLiteral(false),
+        "NULL" => TokenKind::Null,
+        _ => return None,
+    })
+}
+
+/// Words of the C integer type spellings, such as `unsigned long long`
+const C_TYPE_WORDS: &[&str] = &["unsigned", "signed", "short", "long", "int", "char"];
+
+/// The type token a C integer type spelling such as `unsigned int` or
+/// `long long` stands for
+pub fn c_type_token(words: &[&str]) -> Result<TokenKind, String> {
+    let count = |word: &str| words.iter().filter(|&&w| w == word).count();
+    let unsigned = count("unsigned") > 0;
+    let (short, long, char) = (count("short"), count("long"), count("char") > 0);
+    if count("unsigned") + count("signed") > 1
+        || count("int") + count("char") > 1
+        || short > 1
+        || long > 2
+        || (short > 0 && long > 0)
+        || (char && short + long > 0)
+    {
+        return Err(format!("'{}' is not a C integer type", words.join(" ")));
+    }
+    Ok(match (unsigned, short, long) {
+        (false, _, _) if char => TokenKind::I8,
+        (true, _, _) if char => TokenKind::U8,
+        (false, 1, _) => TokenKind::I16,
+        (true, 1, _) => TokenKind::U16,
+        (false, _, 2) => TokenKind::I64,
+        (true, _, 2) => TokenKind::U64,
+        (false, _, 1) => TokenKind::Long,
+        (true, _, 1) => TokenKind::ULong,
+        (false, _, _) => TokenKind::Int,
+        (true, _, _) => TokenKind::U32,
+    })
+}
+
 /// A token with its kind, span, and text
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Token {
     pub kind: TokenKind,
     pub span: Span,
@@ -150,6 +384,7 @@ impl Token {
 }
 
 /// Lexer for tokenizing Crusty source code
+#[derive(Clone)]
 pub struct Lexer<'a> {
     pub(crate) source: &'a str,
     pub(crate) chars: std::iter::Peekable<std::str::CharIndices<'a>>,
@@ -202,6 +437,22 @@ impl<'a> Lexer<'a> {
         }
     }
 
+    /// Skip whitespace and at most one comment, returning whether a comment
+    /// was skipped
+    fn skip_comment(&mut self) -> Result<bool, LexError> {
+        self.skip_whitespace();
+        let rest = &self.source[self.position..];
+        if rest.starts_with("//") {
+            self.skip_line_comment();
+            Ok(true)
+        } else if rest.starts_with("/*") {
+            self.skip_block_comment()?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
     fn skip_line_comment(&mut self) {
         // Skip //
         self.advance();
@@ -256,49 +507,7 @@ impl<'a> Lexer<'a> {
         }
 
         let text = &self.source[start..self.position];
-        let kind = match text {
-            "let" => TokenKind::Let,
-            "var" => TokenKind::Var,
-            "const" => TokenKind::Const,
-            "static" => TokenKind::Static,
-            "mut" => TokenKind::Mut,
-            "define" => TokenKind::Define,
-            "if" => TokenKind::If,
-            "else" => TokenKind::Else,
-            "while" => TokenKind::While,
-            "for" => TokenKind::For,
-            "in" => TokenKind::In,
-            "return" => TokenKind::Return,
-            "break" => TokenKind::Break,
-            "continue" => TokenKind::Continue,
-            "struct" => TokenKind::Struct,
-            "enum" => TokenKind::Enum,
-            "typedef" => TokenKind::Typedef,
-            "namespace" => TokenKind::Namespace,
-            "extern" => TokenKind::Extern,
-            "unsafe" => TokenKind::Unsafe,
-            "loop" => TokenKind::Loop,
-            "match" => TokenKind::Match,
-            "switch" => TokenKind::Switch,
-            "case" => TokenKind::Case,
-            "default" => TokenKind::Default,
-            "auto" => TokenKind::Auto,
-            "int" => TokenKind::Int,
-            "i32" => TokenKind::I32,
-            "i64" => TokenKind::I64,
-            "u32" => TokenKind::U32,
-            "u64" => TokenKind::U64,
-            "float" => TokenKind::Float,
-            "f32" => TokenKind::F32,
-            "f64" => TokenKind::F64,
-            "bool" => TokenKind::Bool,
-            "char" => TokenKind::Char,
-            "void" => TokenKind::Void,
-            "true" => TokenKind::BoolLiteral(true),
-            "false" => TokenKind::BoolLiteral(false),
-            "NULL" => TokenKind::Null,
-            _ => TokenKind::Ident(text.to_string()),
-        };
+        let kind = word_token(text).unwrap_or_else(|| TokenKind::Ident(text.to_string()));
 
         Token::new(
             kind,
@@ -307,12 +516,59 @@ impl<'a> Lexer<'a> {
         )
     }
 
+    /// Read the rest of the C integer type spelling `first` starts, such as
+    /// `unsigned long long`, as one type token
+    fn read_c_type(&mut self, first: Token) -> Result<Token, LexError> {
+        let mut words = vec![first.text.as_str()];
+        let mut end = first.span.end;
+        while !matches!(words.last(), Some(&"int" | &"char")) {
+            let saved = (self.chars.clone(), self.position, self.line, self.column);
+            self.skip_whitespace();
+            let start = self.position;
+            while self
+                .peek()
+                .is_some_and(|ch| ch.is_alphanumeric() || ch == '_')
+            {
+                self.advance();
+            }
+            let word = &self.source[start..self.position];
+            if !C_TYPE_WORDS.contains(&word) {
+                (self.chars, self.position, self.line, self.column) = saved;
+                break;
+            }
+            words.push(word);
+            end = self.current_position();
+        }
+        let span = Span::new(first.span.start, end);
+        let kind = c_type_token(&words).map_err(|message| LexError::new(span, message))?;
+        Ok(Token::new(kind, span, words.join(" ")))
+    }
+
     fn read_number(&mut self, start_pos: Position, first_char: char) -> Result<Token, LexError> {
         let start = self.position - first_char.len_utf8();
         let mut is_float = false;
 
+        // 0x, 0o and 0b literals take every letter and digit that follows,
+        // so the parser can report a digit the radix does not have
+        if first_char == '0' && matches!(self.peek(), Some('x' | 'X' | 'o' | 'O' | 'b' | 'B')) {
+            self.advance();
+            while let Some(ch) = self.peek() {
+                if ch.is_ascii_alphanumeric() || ch == '_' {
+                    self.advance();
+                } else {
+                    break;
+                }
+            }
+            let text = &self.source[start..self.position];
+            return Ok(Token::new(
+                TokenKind::IntLiteral(text.to_string()),
+                Span::new(start_pos, self.current_position()),
+                text.to_string(),
+            ));
+        }
+
         while let Some(ch) = self.peek() {
-            if ch.is_ascii_digit() {
+            if ch.is_ascii_digit() || ch == '_' {
                 self.advance();
             } else if ch == '.' && !is_float {
                 // Peek ahead to see if there's a digit after the dot
@@ -339,6 +595,18 @@ impl<'a> Lexer<'a> {
             }
         }
 
+        // A type suffix such as `u8` or `f32` belongs to the number, and
+        // `f32` or `f64` makes it a float
+        if matches!(self.peek(), Some('i' | 'u' | 'f')) {
+            is_float |= self.peek() == Some('f');
+            while self
+                .peek()
+                .is_some_and(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+            {
+                self.advance();
+            }
+        }
+
         let text = &self.source[start..self.position];
         let kind = if is_float {
             TokenKind::FloatLiteral(text.to_string())
@@ -354,8 +622,7 @@ impl<'a> Lexer<'a> {
     }
 
     fn read_string(&mut self, start_pos: Position) -> Result<Token, LexError> {
-        // Skip opening "
-        self.advance();
+        // Opening " has already been consumed by next_token
         let mut value = String::new();
 
         loop {
@@ -370,28 +637,260 @@ impl<'a> Lexer<'a> {
                     self.advance();
                     break;
                 }
+                Some('\\') => match self.read_escape() {
+                    Ok(ch) => value.push(ch),
+                    Err(error) => {
+                        self.skip_past_quote('"');
+                        return Err(error);
+                    }
+                },
+                Some(ch) => {
+                    value.push(ch);
+                    self.advance();
+                }
+            }
+            if value.len() > MAX_STRING_LITERAL_BYTES {
+                return Err(LexError::new(
+                    Span::new(start_pos, self.current_position()),
+                    format!(
+                        "string literal exceeds the limit of {} bytes",
+                        MAX_STRING_LITERAL_BYTES
+                    ),
+                ));
+            }
+        }
+
+        Ok(Token::new(
+            TokenKind::StringLiteral(value.clone()),
+            Span::new(start_pos, self.current_position()),
+            format!("\"{}\"", value),
+        ))
+    }
+
+    /// Read a raw string literal, `r"..."` or `r#"..."#`: its text runs,
+    /// with no escapes, up to a `"` followed by as many `#` as opened it
+    fn read_raw_string(&mut self, start_pos: Position) -> Result<Token, LexError> {
+        // The r has already been consumed by next_token
+        let start = self.position - 1;
+        let mut hashes = 0;
+        while self.peek() == Some('#') {
+            self.advance();
+            hashes += 1;
+        }
+        self.advance();
+        let closing = format!("\"{}", "#".repeat(hashes));
+
+        let body_start = self.position;
+        let Some(length) = self.source[body_start..].find(&closing) else {
+            while self.advance().is_some() {}
+            return Err(LexError::new(
+                Span::new(start_pos, self.current_position()),
+                format!("unterminated raw string literal: expected '{}'", closing),
+            ));
+        };
+        if length > MAX_STRING_LITERAL_BYTES {
+            return Err(LexError::new(
+                Span::new(start_pos, self.current_position()),
+                format!(
+                    "string literal exceeds the limit of {} bytes",
+                    MAX_STRING_LITERAL_BYTES
+                ),
+            ));
+        }
+        while self.position < body_start + length + closing.len() {
+            self.advance();
+        }
+
+        let value = self.source[body_start..body_start + length].to_string();
+        Ok(Token::new(
+            TokenKind::RawStringLiteral(value),
+            Span::new(start_pos, self.current_position()),
+            self.source[start..self.position].to_string(),
+        ))
+    }
+
+    /// Read the escape sequence at a `\` in a string or character literal:
+    /// `\n`, `\t`, `\r`, `\0`, `\\`, `\"`, `\'`, `\xNN` up to `\x7F`, or
+    /// `\u{XXXX}` with one to six hexadecimal digits
+    fn read_escape(&mut self) -> Result<char, LexError> {
+        let start = self.current_position();
+        self.advance();
+        let escaped = self.advance();
+        let error = |lexer: &Self, message: String| {
+            LexError::new(Span::new(start, lexer.current_position()), message)
+        };
+        match escaped {
+            Some('n') => Ok('\n'),
+            Some('t') => Ok('\t'),
+            Some('r') => Ok('\r'),
+            Some('0') => Ok('\0'),
+            Some('\\') => Ok('\\'),
+            Some('"') => Ok('"'),
+            Some('\'') => Ok('\''),
+            Some('x') => {
+                let mut digits = String::new();
+                while digits.len() < 2 && self.peek().is_some_and(|ch| ch.is_ascii_hexdigit()) {
+                    digits.extend(self.advance());
+                }
+                match u8::from_str_radix(&digits, 16) {
+                    Ok(byte) if digits.len() == 2 && byte.is_ascii() => Ok(char::from(byte)),
+                    Ok(_) if digits.len() == 2 => Err(error(
+                        self,
+                        format!(
+                            "escape '\\x{}' is out of range: \\x escapes go up to \\x7F",
+                            digits
+                        ),
+                    )),
+                    _ => Err(error(
+                        self,
+                        "invalid \\x escape: expected two hexadecimal digits".to_string(),
+                    )),
+                }
+            }
+            Some('u') => {
+                if self.peek() != Some('{') {
+                    return Err(error(
+                        self,
+                        "invalid \\u escape: expected '{' after \\u".to_string(),
+                    ));
+                }
+                self.advance();
+                let mut digits = String::new();
+                while self.peek().is_some_and(|ch| ch.is_ascii_hexdigit()) {
+                    digits.extend(self.advance());
+                }
+                if self.peek() != Some('}') {
+                    return Err(error(
+                        self,
+                        "unterminated \\u escape: expected '}'".to_string(),
+                    ));
+                }
+                self.advance();
+                if digits.is_empty() || digits.len() > 6 {
+                    return Err(error(
+                        self,
+                        "invalid \\u escape: expected 1 to 6 hexadecimal digits".to_string(),
+                    ));
+                }
+                u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| {
+                        error(
+                            self,
+                            format!(
+                                "invalid \\u escape: {} is not a Unicode scalar value",
+                                digits
+                            ),
+                        )
+                    })
+            }
+            Some(ch) if ch != '\n' => {
+                Err(error(self, format!("invalid escape sequence '\\{}'", ch)))
+            }
+            _ => Err(error(self, "invalid escape sequence".to_string())),
+        }
+    }
+
+    /// Skip the rest of a literal with a malformed escape, up to and
+    /// including its closing `quote`, so lexing resumes after it
+    fn skip_past_quote(&mut self, quote: char) {
+        while let Some(ch) = self.peek() {
+            if ch == '\n' {
+                return;
+            }
+            self.advance();
+            if ch == '\\' {
+                self.advance();
+            } else if ch == quote {
+                return;
+            }
+        }
+    }
+
+    /// Read a character literal (`'a'`, `'\n'` or `'\u{e9}'`)
+    fn read_char(&mut self, start_pos: Position) -> Result<Token, LexError> {
+        // Opening ' has already been consumed by next_token
+        let error = |lexer: &Self, message: &str| {
+            LexError::new(Span::new(start_pos, lexer.current_position()), message)
+        };
+        let value = match self.peek() {
+            Some('\\') => self
+                .read_escape()
+                .inspect_err(|_| self.skip_past_quote('\''))?,
+            Some('\'') => {
+                self.advance();
+                return Err(error(self, "empty character literal"));
+            }
+            Some(ch) if ch != '\n' => {
+                self.advance();
+                ch
+            }
+            _ => return Err(error(self, "unterminated character literal")),
+        };
+        match self.peek() {
+            Some('\'') => {
+                self.advance();
+            }
+            Some(_)
+                if self.source[self.position..]
+                    .lines()
+                    .next()
+                    .is_some_and(|line| line.contains('\'')) =>
+            {
+                self.skip_past_quote('\'');
+                return Err(error(
+                    self,
+                    "character literal may only contain one character",
+                ));
+            }
+            _ => return Err(error(self, "unterminated character literal")),
+        }
+        Ok(Token::new(
+            TokenKind::CharLiteral(value),
+            Span::new(start_pos, self.current_position()),
+            format!("'{}'", value.escape_default()),
+        ))
+    }
+
+    /// Read a byte string literal (`b"..."`), which is ASCII-only and may
+    /// spell any byte with a `\xNN` escape
+    fn read_byte_string(&mut self, start_pos: Position) -> Result<Token, LexError> {
+        // Opening b" has already been consumed by next_token
+        let mut value = Vec::new();
+
+        loop {
+            let byte = match self.peek() {
+                None | Some('\n') => {
+                    return Err(LexError::new(
+                        Span::new(start_pos, self.current_position()),
+                        "unterminated byte string literal",
+                    ));
+                }
+                Some('"') => {
+                    self.advance();
+                    break;
+                }
                 Some('\\') => {
                     self.advance();
-                    match self.peek() {
-                        Some('n') => {
-                            value.push('\n');
-                            self.advance();
-                        }
-                        Some('t') => {
-                            value.push('\t');
-                            self.advance();
-                        }
-                        Some('r') => {
-                            value.push('\r');
-                            self.advance();
-                        }
-                        Some('\\') => {
-                            value.push('\\');
-                            self.advance();
-                        }
-                        Some('"') => {
-                            value.push('"');
-                            self.advance();
+                    let escaped = self.peek();
+                    self.advance();
+                    match escaped {
+                        Some('n') => b'\n',
+                        Some('t') => b'\t',
+                        Some('r') => b'\r',
+                        Some('0') => b'\0',
+                        Some('\\') => b'\\',
+                        Some('"') => b'"',
+                        Some('\'') => b'\'',
+                        Some('x') => {
+                            let digits: String = (0..2).filter_map(|_| self.advance()).collect();
+                            u8::from_str_radix(&digits, 16).map_err(|_| {
+                                LexError::new(
+                                    Span::new(start_pos, self.current_position()),
+                                    "invalid \\x escape in byte string literal",
+                                )
+                            })?
                         }
                         _ => {
                             return Err(LexError::new(
@@ -401,47 +900,55 @@ impl<'a> Lexer<'a> {
                         }
                     }
                 }
-                Some(ch) => {
-                    value.push(ch);
+                Some(ch) if ch.is_ascii() => {
                     self.advance();
+                    ch as u8
                 }
+                Some(ch) => {
+                    return Err(LexError::new(
+                        Span::new(start_pos, self.current_position()),
+                        format!("non-ASCII character '{}' in byte string literal", ch),
+                    ));
+                }
+            };
+            value.push(byte);
+            if value.len() > MAX_STRING_LITERAL_BYTES {
+                return Err(LexError::new(
+                    Span::new(start_pos, self.current_position()),
+                    format!(
+                        "string literal exceeds the limit of {} bytes",
+                        MAX_STRING_LITERAL_BYTES
+                    ),
+                ));
             }
         }
 
+        let text = format!("b\"{}\"", value.escape_ascii());
         Ok(Token::new(
-            TokenKind::StringLiteral(value.clone()),
+            TokenKind::ByteStringLiteral(value),
             Span::new(start_pos, self.current_position()),
-            format!("\"{}\"", value),
+            text,
         ))
     }
 
     pub fn next_token(&mut self) -> Result<Token, LexError> {
-        self.skip_whitespace();
+        if self.source.len() > MAX_SOURCE_BYTES {
+            let start = Position::new(1, 1);
+            return Err(LexError::new(
+                Span::new(start, start),
+                format!(
+                    "source file is {} bytes, exceeding the limit of {} bytes",
+                    self.source.len(),
+                    MAX_SOURCE_BYTES
+                ),
+            ));
+        }
 
-        let start_pos = self.current_position();
+        // Comments are skipped in a loop rather than by recursion so long
+        // runs of them cannot exhaust the stack
+        while self.skip_comment()? {}
 
-        // Handle comments
-        if self.peek() == Some('/') {
-            let saved_pos = (self.position, self.line, self.column);
-            self.advance();
-            match self.peek() {
-                Some('/') => {
-                    self.skip_line_comment();
-                    return self.next_token();
-                }
-                Some('*') => {
-                    self.skip_block_comment()?;
-                    return self.next_token();
-                }
-                _ => {
-                    // Restore position, it's a division operator
-                    self.position = saved_pos.0;
-                    self.line = saved_pos.1;
-                    self.column = saved_pos.2;
-                    self.chars = self.source[self.position..].char_indices().peekable();
-                }
-            }
-        }
+        let start_pos = self.current_position();
 
         let ch = match self.advance() {
             Some(ch) => ch,
@@ -522,6 +1029,10 @@ impl<'a> Lexer<'a> {
                     self.advance();
                     (TokenKind::Eq, "==")
                 }
+                Some('>') => {
+                    self.advance();
+                    (TokenKind::FatArrow, "=>")
+                }
                 _ => (TokenKind::Assign, "="),
             },
             '!' => match self.peek() {
@@ -617,9 +1128,47 @@ impl<'a> Lexer<'a> {
                 return self.read_string(start_pos);
             }
 
+            // Character literals
+            '\'' => {
+                return self.read_char(start_pos);
+            }
+
+            // Wide string literals: L"..." (wchar_t) and u"..." (char16_t)
+            'L' | 'u' if self.peek() == Some('"') => {
+                self.advance();
+                let token = self.read_string(start_pos)?;
+                let TokenKind::StringLiteral(value) = token.kind else {
+                    unreachable!("read_string returns a string literal");
+                };
+                return Ok(Token::new(
+                    TokenKind::WideStringLiteral(value),
+                    token.span,
+                    format!("{}{}", ch, token.text),
+                ));
+            }
+
+            // Raw string literals: r"..." and r#"..."#
+            'r' if self.source[self.position..]
+                .trim_start_matches('#')
+                .starts_with('"') =>
+            {
+                return self.read_raw_string(start_pos);
+            }
+
+            // Byte string literals
+            'b' if self.peek() == Some('"') => {
+                self.advance();
+                return self.read_byte_string(start_pos);
+            }
+
             // Identifiers and keywords
             ch if ch.is_alphabetic() || ch == '_' => {
-                return Ok(self.read_identifier(start_pos, ch));
+                let token = self.read_identifier(start_pos, ch);
+                if matches!(&token.kind, TokenKind::Ident(word) if C_TYPE_WORDS.contains(&word.as_str()))
+                {
+                    return self.read_c_type(token);
+                }
+                return Ok(token);
             }
 
             // Numbers
@@ -642,14 +1191,21 @@ impl<'a> Lexer<'a> {
         ))
     }
 
+    /// Every remaining token, up to but not including the end of file
+    pub fn tokenize(&mut self) -> Result<Vec<Token>, LexError> {
+        let mut tokens = Vec::new();
+        loop {
+            let token = self.next_token()?;
+            if token.kind == TokenKind::Eof {
+                return Ok(tokens);
+            }
+            tokens.push(token);
+        }
+    }
+
     #[allow(dead_code)]
     pub fn peek_token(&mut self) -> Result<Token, LexError> {
-        let saved_state = (
-            self.position,
-            self.line,
-            self.column,
-            self.source[self.position..].to_string(),
-        );
+        let saved_state = (self.position, self.line, self.column, self.chars.clone());
 
         let token = self.next_token()?;
 
@@ -657,7 +1213,7 @@ impl<'a> Lexer<'a> {
         self.position = saved_state.0;
         self.line = saved_state.1;
         self.column = saved_state.2;
-        self.chars = self.source[self.position..].char_indices().peekable();
+        self.chars = saved_state.3;
 
         Ok(token)
     }
@@ -735,6 +1291,65 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_radix_numbers() {
+        let mut lexer = Lexer::new("0xFF 0o77 0b1010 1_000_000 0x1G..");
+        let texts: Vec<String> = std::iter::from_fn(|| {
+            let token = lexer.next_token().unwrap();
+            matches!(token.kind, TokenKind::IntLiteral(_)).then_some(token.text)
+        })
+        .collect();
+        assert_eq!(texts, ["0xFF", "0o77", "0b1010", "1_000_000", "0x1G"]);
+    }
+
+    #[test]
+    fn test_number_suffixes() {
+        let mut lexer = Lexer::new("42u32 3.5f32 2f64 0xFFi8 1..n");
+        let tokens: Vec<TokenKind> = std::iter::from_fn(|| {
+            let token = lexer.next_token().unwrap();
+            (token.kind != TokenKind::Eof).then_some(token.kind)
+        })
+        .collect();
+        assert_eq!(
+            tokens,
+            [
+                TokenKind::IntLiteral("42u32".to_string()),
+                TokenKind::FloatLiteral("3.5f32".to_string()),
+                TokenKind::FloatLiteral("2f64".to_string()),
+                TokenKind::IntLiteral("0xFFi8".to_string()),
+                TokenKind::IntLiteral("1".to_string()),
+                TokenKind::DotDot,
+                TokenKind::Ident("n".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_c_type_spellings() {
+        let mut lexer = Lexer::new("unsigned int long long x short; unsigned char signed long");
+        let tokens: Vec<(TokenKind, String)> = std::iter::from_fn(|| {
+            let token = lexer.next_token().unwrap();
+            (token.kind != TokenKind::Eof).then_some((token.kind, token.text))
+        })
+        .collect();
+        assert_eq!(
+            tokens,
+            [
+                (TokenKind::U32, "unsigned int".to_string()),
+                (TokenKind::I64, "long long".to_string()),
+                (TokenKind::Ident("x".to_string()), "x".to_string()),
+                (TokenKind::I16, "short".to_string()),
+                (TokenKind::Semicolon, ";".to_string()),
+                (TokenKind::U8, "unsigned char".to_string()),
+                (TokenKind::Long, "signed long".to_string()),
+            ]
+        );
+        assert_eq!(
+            c_type_token(&["short", "long"]),
+            Err("'short long' is not a C integer type".to_string())
+        );
+    }
+
     #[test]
     fn test_strings() {
         let source = r#""hello" "world\n""#;
@@ -747,6 +1362,182 @@ mod tests {
         assert!(matches!(token2.kind, TokenKind::StringLiteral(_)));
     }
 
+    #[test]
+    fn test_escape_sequences() {
+        let mut lexer = Lexer::new(r#""\x41\u{e9}\u{1F600}" 'a' '\n' '\u{e9}' '\''"#);
+        let kinds: Vec<TokenKind> = std::iter::from_fn(|| {
+            let token = lexer.next_token().unwrap();
+            (token.kind != TokenKind::Eof).then_some(token.kind)
+        })
+        .collect();
+        assert_eq!(
+            kinds,
+            [
+                TokenKind::StringLiteral("A\u{e9}\u{1F600}".to_string()),
+                TokenKind::CharLiteral('a'),
+                TokenKind::CharLiteral('\n'),
+                TokenKind::CharLiteral('\u{e9}'),
+                TokenKind::CharLiteral('\''),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_malformed_escapes() {
+        let error = |source: &str| {
+            let error = Lexer::new(source).next_token().unwrap_err();
+            (
+                error.message,
+                error.span.start.column,
+                error.span.end.column,
+            )
+        };
+        assert_eq!(
+            error(r#""ab\xZZ""#),
+            (
+                "invalid \\x escape: expected two hexadecimal digits".to_string(),
+                4,
+                6
+            )
+        );
+        assert_eq!(
+            error(r#""\x80""#),
+            (
+                "escape '\\x80' is out of range: \\x escapes go up to \\x7F".to_string(),
+                2,
+                6
+            )
+        );
+        assert_eq!(
+            error(r#""\u{110000}""#),
+            (
+                "invalid \\u escape: 110000 is not a Unicode scalar value".to_string(),
+                2,
+                12
+            )
+        );
+        assert_eq!(
+            error(r#""\u41""#).0,
+            "invalid \\u escape: expected '{' after \\u"
+        );
+        assert_eq!(
+            error(r#""\u{41""#).0,
+            "unterminated \\u escape: expected '}'"
+        );
+        assert_eq!(error(r#""\q""#).0, "invalid escape sequence '\\q'");
+        assert_eq!(error("''").0, "empty character literal");
+        assert_eq!(
+            error("'ab'").0,
+            "character literal may only contain one character"
+        );
+
+        // Lexing resumes after the malformed literal
+        let mut lexer = Lexer::new(r#""\xZZ" x"#);
+        assert!(lexer.next_token().is_err());
+        assert_eq!(
+            lexer.next_token().unwrap().kind,
+            TokenKind::Ident("x".to_string())
+        );
+    }
+
+    #[test]
+    fn test_string_value_and_text() {
+        let mut lexer = Lexer::new(r#"("fast", x)"#);
+
+        lexer.next_token().unwrap();
+        let token = lexer.next_token().unwrap();
+        assert_eq!(token.kind, TokenKind::StringLiteral("fast".to_string()));
+        assert_eq!(token.text, "\"fast\"");
+    }
+
+    #[test]
+    fn test_byte_strings() {
+        let mut lexer = Lexer::new(r#"b"ab\x01\0" bytes"#);
+        let token = lexer.next_token().unwrap();
+        assert_eq!(
+            token.kind,
+            TokenKind::ByteStringLiteral(vec![b'a', b'b', 1, 0])
+        );
+        assert_eq!(token.text, r#"b"ab\x01\x00""#);
+        assert_eq!(
+            lexer.next_token().unwrap().kind,
+            TokenKind::Ident("bytes".to_string())
+        );
+
+        let err = Lexer::new("b\"caf\u{e9}\"").next_token().unwrap_err();
+        assert_eq!(
+            err.message,
+            "non-ASCII character '\u{e9}' in byte string literal"
+        );
+        let err = Lexer::new(r#"b"\xZZ""#).next_token().unwrap_err();
+        assert_eq!(err.message, "invalid \\x escape in byte string literal");
+    }
+
+    #[test]
+    fn test_wide_strings() {
+        let mut lexer = Lexer::new(r#"L"wide\n" u"utf16" Lx u"#);
+        let token = lexer.next_token().unwrap();
+        assert_eq!(
+            token.kind,
+            TokenKind::WideStringLiteral("wide\n".to_string())
+        );
+        assert_eq!(token.text, "L\"wide\n\"");
+        assert_eq!(
+            lexer.next_token().unwrap().kind,
+            TokenKind::WideStringLiteral("utf16".to_string())
+        );
+        assert_eq!(
+            lexer.next_token().unwrap().kind,
+            TokenKind::Ident("Lx".to_string())
+        );
+        assert_eq!(
+            lexer.next_token().unwrap().kind,
+            TokenKind::Ident("u".to_string())
+        );
+    }
+
+    #[test]
+    fn test_raw_strings() {
+        let mut lexer = Lexer::new(r###"r"C:\temp\" r#"say "\d+""# r##"a "# b"## r x"###);
+        let token = lexer.next_token().unwrap();
+        assert_eq!(
+            token.kind,
+            TokenKind::RawStringLiteral(r"C:\temp\".to_string())
+        );
+        assert_eq!(token.text, r#"r"C:\temp\""#);
+        assert_eq!(
+            lexer.next_token().unwrap().kind,
+            TokenKind::RawStringLiteral(r#"say "\d+""#.to_string())
+        );
+        assert_eq!(
+            lexer.next_token().unwrap().kind,
+            TokenKind::RawStringLiteral(r##"a "# b"##.to_string())
+        );
+        assert_eq!(
+            lexer.next_token().unwrap().kind,
+            TokenKind::Ident("r".to_string())
+        );
+
+        let err = Lexer::new(r##"r#"open" end"##).next_token().unwrap_err();
+        assert_eq!(
+            err.message,
+            "unterminated raw string literal: expected '\"#'"
+        );
+    }
+
+    #[test]
+    fn test_peek_token_preserves_positions() {
+        let mut lexer = Lexer::new("a / b");
+
+        lexer.next_token().unwrap();
+        lexer.peek_token().unwrap();
+        assert_eq!(lexer.next_token().unwrap().kind, TokenKind::Slash);
+        assert_eq!(
+            lexer.next_token().unwrap().kind,
+            TokenKind::Ident("b".to_string())
+        );
+    }
+
     #[test]
     fn test_comments() {
         let source = "// line comment\nint /* block comment */ main";
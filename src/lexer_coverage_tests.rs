@@ -9,7 +9,7 @@ mod tests {
 
     #[test]
     fn test_all_keywords() {
-        let source = "let var const static mut define if else while for in return break continue struct enum typedef namespace extern unsafe loop match switch case default auto";
+        let source = "let var const static mut define if else while for in return break continue struct enum typedef namespace extern unsafe volatile loop match switch case default auto";
         let mut lexer = Lexer::new(source);
 
         let expected = vec![
@@ -33,6 +33,7 @@ mod tests {
             TokenKind::Namespace,
             TokenKind::Extern,
             TokenKind::Unsafe,
+            TokenKind::Volatile,
             TokenKind::Loop,
             TokenKind::Match,
             TokenKind::Switch,
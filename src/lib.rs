@@ -3,12 +3,16 @@
 
 //! Crusty compiler library
 
+pub mod archive;
 pub mod ast;
+pub mod borrowck;
 #[cfg(test)]
 mod c_style_declaration_tests;
+pub mod cfg;
 pub mod cli;
 #[cfg(test)]
 mod cli_properties;
+pub mod clippy;
 pub mod codegen;
 #[cfg(test)]
 mod codegen_advanced_tests;
@@ -20,14 +24,29 @@ mod codegen_crusty_advanced_tests;
 mod codegen_crusty_tests;
 #[cfg(test)]
 mod codegen_properties;
+pub mod compile_db;
+pub mod consteval;
+pub mod coverage;
+pub mod dataflow;
+pub mod deadcode;
+pub mod desugar;
+pub mod determinism;
+pub mod differential;
 pub mod error;
 #[cfg(test)]
 mod error_coverage_tests;
+pub mod hir;
+pub mod inline_macros;
 pub mod lexer;
 #[cfg(test)]
 mod lexer_coverage_tests;
+pub mod limits;
+pub mod lints;
+pub mod macros;
+pub mod modules;
 #[cfg(test)]
 mod nested_function_tests;
+pub mod no_effect;
 pub mod parser;
 #[cfg(test)]
 mod parser_additional_coverage_tests;
@@ -40,7 +59,11 @@ mod parser_edge_case_tests;
 #[cfg(test)]
 mod parser_error_tests;
 #[cfg(test)]
+mod parser_fuzz_tests;
+#[cfg(test)]
 mod parser_properties;
+pub mod platform;
+pub mod preprocess;
 pub mod pretty;
 #[cfg(test)]
 mod pretty_properties;
@@ -62,6 +85,13 @@ mod semantic_return_tests;
 mod semantic_statement_tests;
 #[cfg(test)]
 mod semantic_type_tests;
+pub mod size_report;
+pub mod source_map;
+pub mod switch_ladders;
+pub mod symbols;
+pub mod tailcall;
+pub mod timings;
 #[cfg(test)]
 mod typedef_integration_tests;
+pub mod unused;
 pub mod utils;
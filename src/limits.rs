@@ -0,0 +1,117 @@
+// Copyright (c) 2026 Mark Ferrell
+// Licensed under the MIT License. See LICENSE.txt in the project root.
+
+//! Limits on how much work one compilation may ask for.
+//!
+//! A few lines of source can ask for more than the machine has: macros
+//! whose bodies use their argument twice double in size with each level,
+//! and `#import` pulls whole files into the program. Rather than grow until
+//! the allocator aborts, each phase that can grow checks a limit and
+//! reports a diagnostic naming it:
+//!
+//! ```text
+//! error: macro expansion exceeded 1M tokens
+//! ```
+//!
+//! The limits are set with `--max-ast-nodes`, `--max-expansion-tokens` and
+//! `--max-const-eval-steps`; 0 disables one.
+
+use crate::ast::{Block, Expression, File, Item, Statement};
+use crate::codegen::statement_expressions;
+use crate::macros::child_expressions;
+use crate::tailcall::child_blocks;
+
+/// Most syntax tree nodes the program may have once macros are expanded
+pub const DEFAULT_MAX_AST_NODES: usize = 5_000_000;
+
+/// Most tokens expanding one use of a macro may produce
+pub const DEFAULT_MAX_EXPANSION_TOKENS: usize = 1_000_000;
+
+/// Most operations evaluating one constant expression may take
+pub const DEFAULT_MAX_CONST_EVAL_STEPS: usize = 10_000;
+
+/// Whether `count` is past `limit`, where a limit of 0 is no limit
+pub fn exceeds(count: usize, limit: usize) -> bool {
+    limit > 0 && count > limit
+}
+
+/// `count` as limits are written in diagnostics: `1M` for a million, `64K`
+/// for 64 thousand, and exact otherwise
+pub fn format_count(count: usize) -> String {
+    match count {
+        0 => "0".to_string(),
+        n if n % 1_000_000 == 0 => format!("{}M", n / 1_000_000),
+        n if n % 1_000 == 0 => format!("{}K", n / 1_000),
+        n => n.to_string(),
+    }
+}
+
+/// Number of items, statements and expressions in `file`
+///
+/// The tree is walked with a work list rather than recursion, since a long
+/// chain such as `1 + 1 + ... + 1` nests as deep as it is long.
+pub fn count_ast_nodes(file: &File) -> usize {
+    let mut count = 0;
+    let mut items: Vec<&Item> = file.items.iter().collect();
+    let mut blocks: Vec<&Block> = Vec::new();
+    let mut expressions: Vec<&Expression> = Vec::new();
+    while let Some(item) = items.pop() {
+        count += 1;
+        match item {
+            Item::Function(func) => blocks.push(&func.body),
+            Item::Struct(s) => blocks.extend(s.methods.iter().map(|m| &m.body)),
+            Item::Impl(i) => blocks.extend(i.methods.iter().map(|m| &m.body)),
+            Item::Trait(t) => blocks.extend(t.methods.iter().filter_map(|m| m.body.as_ref())),
+            Item::Namespace(namespace) => items.extend(&namespace.items),
+            Item::Const(c) => expressions.push(&c.value),
+            Item::Static(s) => expressions.push(&s.value),
+            Item::StaticAssert(assertion) => expressions.push(&assertion.condition),
+            _ => {}
+        }
+    }
+    while let Some(block) = blocks.pop() {
+        for stmt in &block.statements {
+            count += 1;
+            expressions.extend(statement_expressions(stmt));
+            blocks.extend(child_blocks(stmt));
+            match stmt {
+                Statement::NestedFunction { body, .. } => blocks.push(body),
+                Statement::StaticAssert(assertion) => expressions.push(&assertion.condition),
+                _ => {}
+            }
+        }
+    }
+    while let Some(expr) = expressions.pop() {
+        count += 1;
+        expressions.extend(child_expressions(expr));
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    #[test]
+    fn test_count_ast_nodes() {
+        let file = Parser::new(
+            "static_assert(1 + 2 == 3, \"sum\");\nint main() {\n    int n = 3;\n    if (n > 2) {\n        return n;\n    }\n    return 0;\n}\n",
+        )
+        .unwrap()
+        .parse_file()
+        .unwrap();
+        // 2 items, 4 statements, the 5 nodes of `1 + 2 == 3`, the 3 of
+        // `n > 2`, and `3`, `n` and `0`
+        assert_eq!(count_ast_nodes(&file), 17);
+    }
+
+    #[test]
+    fn test_format_count() {
+        assert_eq!(format_count(1_000_000), "1M");
+        assert_eq!(format_count(64_000), "64K");
+        assert_eq!(format_count(1_500), "1500");
+        assert!(!exceeds(10, 0));
+        assert!(exceeds(11, 10));
+    }
+}
@@ -0,0 +1,362 @@
+// Copyright (c) 2026 Mark Ferrell
+// Licensed under the MIT License. See LICENSE.txt in the project root.
+
+//! Named lints and the levels they are reported at.
+//!
+//! Every warning the compiler gives belongs to a lint with a name, so it can
+//! be silenced or made an error the way rustc's are:
+//!
+//! ```text
+//! crustyc main.crst -A switch_ladders -W unused -E shadowing
+//!
+//! #[allow(no_effect)]
+//! void poll() { ... }
+//! ```
+//!
+//! A lint is reported at its default level unless the command line sets
+//! another, and an `#[allow(...)]`, `#[warn(...)]` or `#[deny(...)]` on the
+//! function, method or type the warning is about overrides both. A denied
+//! lint is reported as an error and fails the compilation once its phase
+//! is done.
+
+use crate::ast::{Attribute, AttributeArg, Item};
+use crate::error::Position;
+use std::collections::BTreeMap;
+
+/// How a lint is reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    /// Not at all
+    Allow,
+    /// As a warning
+    Warn,
+    /// As an error, failing the compilation
+    Deny,
+}
+
+impl Level {
+    /// The level an attribute named `name` sets, as `deny` in `#[deny(x)]`
+    pub fn from_attribute(name: &str) -> Option<Level> {
+        match name {
+            "allow" => Some(Level::Allow),
+            "warn" => Some(Level::Warn),
+            "deny" => Some(Level::Deny),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Level {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Level::Allow => write!(f, "allow"),
+            Level::Warn => write!(f, "warn"),
+            Level::Deny => write!(f, "deny"),
+        }
+    }
+}
+
+/// A kind of warning, with the name the command line and attributes use
+#[derive(Debug, PartialEq, Eq)]
+pub struct Lint {
+    pub name: &'static str,
+    pub default: Level,
+    /// Whether rustc knows the name too, so attributes naming it are passed
+    /// on to the generated Rust
+    pub rustc: bool,
+    pub description: &'static str,
+}
+
+/// Macro uses evaluating an argument with side effects more than once
+pub const MACRO_SIDE_EFFECTS: Lint = Lint {
+    name: "macro_side_effects",
+    default: Level::Warn,
+    rustc: false,
+    description: "macro arguments with side effects that are evaluated more than once",
+};
+
+/// Items named like an item of the Rust prelude
+pub const SHADOWING: Lint = Lint {
+    name: "shadowing",
+    default: Level::Warn,
+    rustc: false,
+    description: "items named like an item of the Rust prelude, which they shadow",
+};
+
+/// String initializers leaving no room for a NUL terminator
+pub const UNTERMINATED_STRINGS: Lint = Lint {
+    name: "unterminated_strings",
+    default: Level::Warn,
+    rustc: false,
+    description: "string initializers filling their array, leaving no room for a NUL",
+};
+
+/// Bare enum variants accepted with `--unqualified-variants`
+pub const UNQUALIFIED_VARIANTS: Lint = Lint {
+    name: "unqualified_variants",
+    default: Level::Warn,
+    rustc: false,
+    description: "enum variants written without their enum",
+};
+
+/// Implicit integer-to-float conversions that may round the value
+pub const LOSSY_CONVERSIONS: Lint = Lint {
+    name: "lossy_conversions",
+    default: Level::Warn,
+    rustc: false,
+    description: "implicit conversions of integers to floats that cannot hold every value",
+};
+
+/// Findings of the definite-initialization and reachability analysis
+pub const FLOW: Lint = Lint {
+    name: "flow",
+    default: Level::Warn,
+    rustc: false,
+    description: "reads of unassigned variables, reassigned `let`s and unreachable statements",
+};
+
+/// Expression statements whose value is discarded
+pub const NO_EFFECT: Lint = Lint {
+    name: "no_effect",
+    default: Level::Warn,
+    rustc: false,
+    description: "expression statements that have no effect",
+};
+
+/// Unused variables, parameters, private functions and fields
+pub const UNUSED: Lint = Lint {
+    name: "unused",
+    default: Level::Warn,
+    rustc: true,
+    description: "variables, parameters, private functions and fields nothing uses",
+};
+
+/// If/else chains that could be switches, with `--switch-ladders`
+pub const SWITCH_LADDERS: Lint = Lint {
+    name: "switch_ladders",
+    default: Level::Warn,
+    rustc: false,
+    description: "if/else chains comparing one variable against constants",
+};
+
+/// Clippy's suggestions for the generated Rust, with `--clippy`
+pub const CLIPPY: Lint = Lint {
+    name: "clippy",
+    default: Level::Warn,
+    rustc: false,
+    description: "clippy's suggestions for the generated Rust",
+};
+
+/// Every lint, in the order the phases reporting them run
+pub const LINTS: &[&Lint] = &[
+    &MACRO_SIDE_EFFECTS,
+    &SHADOWING,
+    &UNTERMINATED_STRINGS,
+    &UNQUALIFIED_VARIANTS,
+    &LOSSY_CONVERSIONS,
+    &FLOW,
+    &NO_EFFECT,
+    &UNUSED,
+    &SWITCH_LADDERS,
+    &CLIPPY,
+];
+
+/// The lint named `name`
+pub fn find(name: &str) -> Option<&'static Lint> {
+    LINTS.iter().copied().find(|lint| lint.name == name)
+}
+
+/// Whether `name` is a lint of this compiler that rustc does not know
+pub fn is_crusty_only(name: &str) -> bool {
+    find(name).is_some_and(|lint| !lint.rustc)
+}
+
+/// A warning of a lint, about the function or item named `item`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    pub lint: &'static Lint,
+    /// Function, method or type the warning is about, whose attributes
+    /// may change its level
+    pub item: Option<String>,
+    pub message: String,
+    /// Where in the source the warning points, when known
+    pub position: Option<Position>,
+}
+
+impl Warning {
+    pub fn new(lint: &'static Lint, message: impl Into<String>) -> Self {
+        Self {
+            lint,
+            item: None,
+            message: message.into(),
+            position: None,
+        }
+    }
+
+    pub fn in_item(mut self, item: impl Into<String>) -> Self {
+        self.item = Some(item.into());
+        self
+    }
+
+    pub fn at(mut self, position: Option<Position>) -> Self {
+        self.position = position;
+        self
+    }
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl PartialEq<&str> for Warning {
+    fn eq(&self, other: &&str) -> bool {
+        self.message == *other
+    }
+}
+
+/// Where the level a lint is reported at comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LevelSource {
+    Default,
+    CommandLine,
+    /// An attribute on the item named
+    Attribute(String),
+}
+
+/// The level of each lint the command line sets
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LintLevels {
+    levels: BTreeMap<&'static str, Level>,
+}
+
+impl LintLevels {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Report the lint named `name` at `level`; fails for unknown names
+    pub fn set(&mut self, name: &str, level: Level) -> Result<(), String> {
+        match find(name) {
+            Some(lint) => {
+                self.levels.insert(lint.name, level);
+                Ok(())
+            }
+            None => Err(format!(
+                "unknown lint '{}'; the lints are {}",
+                name,
+                LINTS
+                    .iter()
+                    .map(|lint| lint.name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
+    }
+
+    /// The level of `lint` outside any item setting one
+    pub fn level(&self, lint: &Lint) -> (Level, LevelSource) {
+        match self.levels.get(lint.name) {
+            Some(&level) => (level, LevelSource::CommandLine),
+            None => (lint.default, LevelSource::Default),
+        }
+    }
+
+    /// The level of `warning`, given the items of the file it is in
+    pub fn level_of(&self, warning: &Warning, items: &[Item]) -> (Level, LevelSource) {
+        let attributes = warning
+            .item
+            .as_deref()
+            .and_then(|name| item_attributes(items, name));
+        match attributes.and_then(|attributes| attribute_level(attributes, warning.lint)) {
+            Some(level) => (
+                level,
+                LevelSource::Attribute(warning.item.clone().unwrap_or_default()),
+            ),
+            None => self.level(warning.lint),
+        }
+    }
+}
+
+/// The level the last `#[allow]`, `#[warn]` or `#[deny]` of `attributes`
+/// naming `lint` sets
+pub fn attribute_level(attributes: &[Attribute], lint: &Lint) -> Option<Level> {
+    attributes
+        .iter()
+        .rev()
+        .filter(|attr| {
+            attr.args
+                .iter()
+                .any(|arg| matches!(arg, AttributeArg::Ident(name) if name.name == lint.name))
+        })
+        .find_map(|attr| Level::from_attribute(&attr.name.name))
+}
+
+/// Attributes of the function, method or type named `name` among `items`
+pub fn item_attributes<'a>(items: &'a [Item], name: &str) -> Option<&'a [Attribute]> {
+    items.iter().find_map(|item| match item {
+        Item::Function(func) if func.name.name == name => Some(func.attributes.as_slice()),
+        Item::Struct(s) if s.name.name == name => Some(s.attributes.as_slice()),
+        Item::Struct(s) => s
+            .methods
+            .iter()
+            .find(|m| m.name.name == name)
+            .map(|m| m.attributes.as_slice()),
+        Item::Impl(i) => i
+            .methods
+            .iter()
+            .find(|m| m.name.name == name)
+            .map(|m| m.attributes.as_slice()),
+        Item::Enum(e) if e.name.name == name => Some(e.attributes.as_slice()),
+        Item::Trait(t) if t.name.name == name => Some(t.attributes.as_slice()),
+        Item::Namespace(namespace) => item_attributes(&namespace.items, name),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    #[test]
+    fn test_command_line_levels() {
+        let mut levels = LintLevels::new();
+        assert_eq!(levels.level(&UNUSED), (Level::Warn, LevelSource::Default));
+        levels.set("unused", Level::Deny).unwrap();
+        assert_eq!(
+            levels.level(&UNUSED),
+            (Level::Deny, LevelSource::CommandLine)
+        );
+        let error = levels.set("unusd", Level::Allow).unwrap_err();
+        assert!(error.starts_with("unknown lint 'unusd'; the lints are macro_side_effects"));
+    }
+
+    #[test]
+    fn test_attributes_override_the_command_line() {
+        let file = Parser::new(
+            "#[allow(no_effect)]\nvoid poll() {}\nstruct Meter {\n    #[deny(no_effect)]\n    void read(&self) {}\n}\nvoid main() {}\n",
+        )
+        .unwrap()
+        .parse_file()
+        .unwrap();
+        let mut levels = LintLevels::new();
+        levels.set("no_effect", Level::Warn).unwrap();
+
+        let warning = Warning::new(&NO_EFFECT, "x").in_item("poll");
+        assert_eq!(
+            levels.level_of(&warning, &file.items),
+            (Level::Allow, LevelSource::Attribute("poll".to_string()))
+        );
+        let warning = Warning::new(&NO_EFFECT, "x").in_item("read");
+        assert_eq!(levels.level_of(&warning, &file.items).0, Level::Deny);
+        let warning = Warning::new(&NO_EFFECT, "x").in_item("main");
+        assert_eq!(
+            levels.level_of(&warning, &file.items),
+            (Level::Warn, LevelSource::CommandLine)
+        );
+        let warning = Warning::new(&UNUSED, "x").in_item("poll");
+        assert_eq!(levels.level_of(&warning, &file.items).0, Level::Warn);
+    }
+}
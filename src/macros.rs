@@ -0,0 +1,1191 @@
+// Copyright (c) 2026 Mark Ferrell
+// Licensed under the MIT License. See LICENSE.txt in the project root.
+
+//! Expansion of `#define` macros.
+//!
+//! The parser keeps macro bodies and the arguments of macro uses as raw
+//! tokens. This pass, run between parsing and semantic analysis, replaces
+//! every use of a macro defined in the file with its body, then parses the
+//! result into an ordinary expression that the rest of the compiler checks
+//! and translates like any other:
+//!
+//! ```text
+//! #define __SQUARE__(x) x * x          int area(int w) {
+//! int area(int w) {                        return ((w + 1) * (w + 1));
+//!     return __SQUARE__(w + 1);        }
+//! }
+//! ```
+//!
+//! Expansion is token-level, as in C, with three safeguards: arguments are
+//! expanded before they are substituted, each substituted argument and each
+//! expansion is parenthesized so operator precedence cannot regroup it, and
+//! names a body binds itself (the bindings of its `match` arms) are renamed
+//! per use so they cannot capture a name passed in by the caller. Macros may
+//! use other macros, in their bodies or their arguments, but not themselves.
+//! Parentheses a body writes around a parameter, as in `((x) * (x))`, are
+//! dropped when the argument is a single identifier or literal, which the
+//! parser would otherwise read as a cast.
+//!
+//! A macro whose parameters end in `...` takes any number of further
+//! arguments, which its body refers to as `__VA_ARGS__`. They are spliced in
+//! separated by commas; when there are none, a comma written just before
+//! `__VA_ARGS__` is dropped with them. When the body forwards them to a
+//! format macro such as `__println__`, each use is checked for a format
+//! string whose placeholders match the arguments passed:
+//!
+//! ```text
+//! #define __LOG__(fmt, ...) __println__(fmt, __VA_ARGS__)
+//! __LOG__("{} of {}", done, total);   // println!("{} of {}", done, total)
+//! __LOG__("{} of {}", done);          // error: 2 placeholders, 1 argument
+//! ```
+//!
+//! An argument substituted for a parameter the body uses more than once is
+//! evaluated once per use, as in C. When such an argument has side effects,
+//! incrementing, decrementing or calling a function, the use is warned
+//! about:
+//!
+//! ```text
+//! #define __MAX__(a, b) a > b ? a : b
+//! __MAX__(i++, j);   // warning: argument 'i++' is evaluated 2 times
+//! ```
+//!
+//! Uses of macros the file does not define, such as `__println__`, are left
+//! for Rust, with any defined macros in their arguments expanded.
+
+use crate::ast::{
+    Block, Expression, File, Function, Item, MacroDefinition, MacroDelimiter, Pattern, Statement,
+    UnaryOp,
+};
+use crate::codegen::join_token_texts;
+use crate::error::{Position, SemanticError, SemanticErrorKind, Span};
+use crate::lexer::{Lexer, Token, TokenKind};
+use crate::lints::{self, Warning};
+use crate::parser::Parser;
+use std::collections::{HashMap, HashSet};
+
+/// The name a variadic macro's body gives its further arguments
+const VA_ARGS: &str = "__VA_ARGS__";
+
+/// Macros whose first argument is a format string for the arguments after it
+const FORMAT_MACROS: &[&str] = &[
+    "__eprint__",
+    "__eprintln__",
+    "__format__",
+    "__panic__",
+    "__print__",
+    "__println__",
+];
+
+/// Expand every use of the macros `file` defines
+///
+/// The definitions are removed from the file, since nothing refers to them
+/// once their uses are expanded. Errors name the function or item the
+/// offending use is in and, when the use has arguments, point at them.
+/// Returns warnings about uses that evaluate an argument with side effects
+/// more than once. A use whose expansion produces more than `max_tokens`
+/// tokens fails (0 for no limit).
+pub fn expand_macros(
+    file: &mut File,
+    max_tokens: usize,
+) -> Result<Vec<Warning>, Vec<SemanticError>> {
+    let mut definitions = HashMap::new();
+    take_definitions(&mut file.items, &mut definitions);
+    if definitions.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let macros = definitions
+        .into_iter()
+        .map(|(name, definition)| {
+            let bound = body_bindings(&definition);
+            (name, Macro { definition, bound })
+        })
+        .collect();
+    let mut expander = Expander {
+        macros,
+        uses: 0,
+        tokens: 0,
+        max_tokens,
+        repeated: Vec::new(),
+        item: None,
+        position: None,
+        warnings: Vec::new(),
+        errors: Vec::new(),
+    };
+    expander.expand_items(&mut file.items);
+    if expander.errors.is_empty() {
+        Ok(expander.warnings)
+    } else {
+        Err(expander.errors)
+    }
+}
+
+/// Names of the macros `file` defines
+pub fn macro_names(file: &File) -> Vec<String> {
+    file.items
+        .iter()
+        .filter_map(|item| match item {
+            Item::MacroDefinition(macro_def) => Some(macro_def.name.name.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Move the macro definitions among `items`, including those in
+/// namespaces, into `definitions`
+fn take_definitions(items: &mut Vec<Item>, definitions: &mut HashMap<String, MacroDefinition>) {
+    for item in std::mem::take(items) {
+        match item {
+            Item::MacroDefinition(macro_def) => {
+                definitions.insert(macro_def.name.name.clone(), macro_def);
+            }
+            Item::Namespace(mut namespace) => {
+                take_definitions(&mut namespace.items, definitions);
+                items.push(Item::Namespace(namespace));
+            }
+            item => items.push(item),
+        }
+    }
+}
+
+/// Names bound by the `match` arms of a macro body, which are local to each
+/// expansion
+fn body_bindings(definition: &MacroDefinition) -> HashSet<String> {
+    let mut bound = HashSet::new();
+    if let Ok(body) = parse_tokens(&definition.body) {
+        collect_bindings(&body, &mut bound);
+    }
+    for param in &definition.params {
+        bound.remove(&param.name);
+    }
+    bound
+}
+
+fn collect_bindings(expr: &Expression, bound: &mut HashSet<String>) {
+    if let Expression::Match { arms, .. } = expr {
+        for arm in arms {
+            pattern_bindings(&arm.pattern, bound);
+        }
+    }
+    for child in child_expressions(expr) {
+        collect_bindings(child, bound);
+    }
+}
+
+pub(crate) fn pattern_bindings(pattern: &Pattern, bound: &mut HashSet<String>) {
+    match pattern {
+        Pattern::Binding(name) => {
+            bound.insert(name.name.clone());
+        }
+        Pattern::Variant { fields, .. } | Pattern::Tuple(fields) => {
+            for field in fields {
+                pattern_bindings(field, bound);
+            }
+        }
+        Pattern::Wildcard | Pattern::Literal(_) => {}
+    }
+}
+
+/// A macro defined in the file being expanded
+struct Macro {
+    definition: MacroDefinition,
+    /// Names the body binds, renamed in each expansion
+    bound: HashSet<String>,
+}
+
+struct Expander {
+    macros: HashMap<String, Macro>,
+    /// Macro uses expanded so far, numbering the renamed bindings of each
+    uses: usize,
+    /// Tokens substituted so far for the use being expanded
+    tokens: usize,
+    /// Most tokens one use may substitute; 0 for no limit
+    max_tokens: usize,
+    /// Arguments with side effects the use being expanded evaluates more
+    /// than once: (macro, argument, times)
+    repeated: Vec<(String, String, usize)>,
+    /// Function or global being expanded, which the warnings are about
+    item: Option<String>,
+    /// Start of the statement being expanded, when it was parsed
+    position: Option<Position>,
+    warnings: Vec<Warning>,
+    errors: Vec<SemanticError>,
+}
+
+impl Expander {
+    fn expand_items(&mut self, items: &mut [Item]) {
+        for item in items {
+            match item {
+                Item::Function(func) => self.expand_function(func),
+                Item::Impl(impl_block) => {
+                    for method in &mut impl_block.methods {
+                        self.expand_function(method);
+                    }
+                }
+                Item::Trait(trait_def) => {
+                    for method in &mut trait_def.methods {
+                        if let Some(body) = &mut method.body {
+                            self.item = Some(method.name.name.clone());
+                            self.expand_block(body);
+                        }
+                    }
+                }
+                Item::Const(const_item) => {
+                    self.item = Some(const_item.name.name.clone());
+                    self.expand_expression(&mut const_item.value);
+                }
+                Item::Static(static_item) => {
+                    self.item = Some(static_item.name.name.clone());
+                    self.expand_expression(&mut static_item.value);
+                }
+                Item::StaticAssert(assertion) => {
+                    self.item = None;
+                    self.expand_expression(&mut assertion.condition);
+                }
+                Item::Namespace(namespace) => self.expand_items(&mut namespace.items),
+                _ => {}
+            }
+        }
+    }
+
+    fn expand_function(&mut self, func: &mut Function) {
+        self.item = Some(func.name.name.clone());
+        self.expand_block(&mut func.body);
+    }
+
+    fn expand_block(&mut self, block: &mut Block) {
+        let enclosing = self.position;
+        for (i, stmt) in block.statements.iter_mut().enumerate() {
+            self.position = block.positions.get(i).copied().or(enclosing);
+            self.expand_statement(stmt);
+        }
+        self.position = enclosing;
+    }
+
+    fn expand_statement(&mut self, stmt: &mut Statement) {
+        match stmt {
+            Statement::Let { init, .. } | Statement::Var { init, .. } => {
+                if let Some(init) = init {
+                    self.expand_expression(init);
+                }
+            }
+            Statement::Const { value, .. } | Statement::Expr(value) => {
+                self.expand_expression(value)
+            }
+            Statement::Return(value) => {
+                if let Some(value) = value {
+                    self.expand_expression(value);
+                }
+            }
+            Statement::If {
+                condition,
+                then_block,
+                else_block,
+            } => {
+                self.expand_expression(condition);
+                self.expand_block(then_block);
+                if let Some(else_block) = else_block {
+                    self.expand_block(else_block);
+                }
+            }
+            Statement::While {
+                condition, body, ..
+            }
+            | Statement::DoWhile { body, condition } => {
+                self.expand_expression(condition);
+                self.expand_block(body);
+            }
+            Statement::For {
+                init,
+                condition,
+                increment,
+                body,
+                ..
+            } => {
+                self.expand_statement(init);
+                self.expand_expression(condition);
+                self.expand_expression(increment);
+                self.expand_block(body);
+            }
+            Statement::ForIn { iter, body, .. } => {
+                self.expand_expression(iter);
+                self.expand_block(body);
+            }
+            Statement::Switch {
+                expr,
+                cases,
+                default,
+            } => {
+                self.expand_expression(expr);
+                for case in cases {
+                    for value in &mut case.values {
+                        self.expand_expression(value);
+                    }
+                    self.expand_block(&mut case.body);
+                }
+                if let Some(default) = default {
+                    self.expand_block(default);
+                }
+            }
+            Statement::Unsafe(body)
+            | Statement::Block(body)
+            | Statement::NestedFunction { body, .. } => self.expand_block(body),
+            Statement::StaticAssert(assertion) => self.expand_expression(&mut assertion.condition),
+            Statement::Break(_) | Statement::Continue(_) => {}
+        }
+    }
+
+    fn expand_expression(&mut self, expr: &mut Expression) {
+        let (source, span) = match expr {
+            Expression::MacroCall {
+                name,
+                args,
+                delimiter,
+            } if self.macros.contains_key(&name.name) => {
+                let (open, close) = macro_delimiter_text(delimiter);
+                let source = format!("{}{}{}{}", name.name, open, arguments_source(args), close);
+                (source, name.span)
+            }
+            Expression::MacroCall { name, args, .. } => {
+                // A Rust macro: its arguments stay tokens, with the uses of
+                // defined macros among them expanded
+                if args.iter().any(|arg| self.macros.contains_key(&arg.text)) {
+                    let source = arguments_source(args);
+                    let position = name.position().or(self.position);
+                    self.tokens = 0;
+                    let expanded = lex(&source)
+                        .and_then(|tokens| self.expand_tokens(&tokens, &mut Vec::new()));
+                    self.warn_repeated(position);
+                    match expanded {
+                        Ok(expanded) => {
+                            *args = expanded
+                                .into_iter()
+                                .map(|token| crate::ast::Token {
+                                    kind: crate::ast::TokenKind::Other,
+                                    text: token.text,
+                                })
+                                .collect()
+                        }
+                        Err(message) => self.error(position, message),
+                    }
+                }
+                return;
+            }
+            Expression::Ident(name) if self.macros.contains_key(&name.name) => {
+                (name.name.clone(), name.span)
+            }
+            Expression::Lambda { body, .. } => {
+                self.expand_block(body);
+                return;
+            }
+            _ => {
+                for child in child_expressions_mut(expr) {
+                    self.expand_expression(child);
+                }
+                return;
+            }
+        };
+
+        self.tokens = 0;
+        let expanded = lex(&source)
+            .and_then(|tokens| self.expand_tokens(&tokens, &mut Vec::new()))
+            .and_then(|expanded| {
+                parse_tokens(&expanded)
+                    .map_err(|e| format!("expansion is not an expression: {}", e))
+            });
+        // What the use expands to is written where the use is
+        let position = (span.start.line != 0)
+            .then_some(span.start)
+            .or(self.position);
+        self.warn_repeated(position);
+        match expanded {
+            Ok(mut expanded) => {
+                respan(&mut expanded, span);
+                *expr = expanded;
+            }
+            Err(message) => self.error(position, message),
+        }
+    }
+
+    /// Expand the uses of defined macros in `tokens`; `active` holds the
+    /// macros whose expansion is in progress
+    fn expand_tokens(
+        &mut self,
+        tokens: &[Token],
+        active: &mut Vec<String>,
+    ) -> Result<Vec<Token>, String> {
+        let mut expanded = Vec::new();
+        let mut i = 0;
+        while i < tokens.len() {
+            let token = &tokens[i];
+            i += 1;
+            let Some(mac) = ident_name(token).and_then(|name| self.macros.get(name)) else {
+                expanded.push(token.clone());
+                continue;
+            };
+            let name = mac.definition.name.name.clone();
+            if active.contains(&name) {
+                return Err(format!("macro '{}' expands to itself", name));
+            }
+
+            let args = match delimiter_tokens(&mac.definition.delimiter) {
+                (Some(open), Some(_)) => {
+                    if tokens.get(i).map(|t| &t.kind) != Some(&open) {
+                        return Err(format!("macro '{}' must be invoked with arguments", name));
+                    }
+                    let end = matching_close(tokens, i)
+                        .ok_or_else(|| format!("unterminated arguments to macro '{}'", name))?;
+                    let inner = &tokens[i + 1..end];
+                    i = end + 1;
+                    split_arguments(inner)
+                }
+                _ => Vec::new(),
+            };
+            let params = &mac.definition.params;
+            let variadic = mac.definition.variadic;
+            if args.len() < params.len() || (!variadic && args.len() > params.len()) {
+                return Err(format!(
+                    "macro '{}' expects {}{} argument{}, found {}",
+                    name,
+                    if variadic { "at least " } else { "" },
+                    params.len(),
+                    if params.len() == 1 { "" } else { "s" },
+                    args.len()
+                ));
+            }
+            if !variadic && mac.definition.body.iter().any(is_va_args) {
+                return Err(format!(
+                    "macro '{}' uses {} but does not end its parameters in '...'",
+                    name, VA_ARGS
+                ));
+            }
+
+            let mut values = Vec::new();
+            for arg in args {
+                values.push(self.expand_tokens(arg, active)?);
+            }
+            if variadic {
+                check_format_arguments(&self.macros[&name].definition, &values)?;
+            }
+            let substituted = self.substitute(&name, &values);
+            self.tokens += substituted.len();
+            if crate::limits::exceeds(self.tokens, self.max_tokens) {
+                return Err(format!(
+                    "macro expansion exceeded {} tokens",
+                    crate::limits::format_count(self.max_tokens)
+                ));
+            }
+            active.push(name);
+            let body = self.expand_tokens(&substituted, active)?;
+            active.pop();
+            expanded.extend(parenthesize(body, token.span));
+        }
+        Ok(expanded)
+    }
+
+    /// The body of macro `name` with `values` in place of its parameters,
+    /// the values past them in place of `__VA_ARGS__`, and its own bindings
+    /// renamed apart from the caller's names
+    fn substitute(&mut self, name: &str, values: &[Vec<Token>]) -> Vec<Token> {
+        self.uses += 1;
+        let mac = &self.macros[name];
+        let extra = &values[mac.definition.params.len()..];
+        let mut body = Vec::new();
+        let mut substitutions = vec![0; mac.definition.params.len()];
+        let mut skip_close = false;
+        for (at, token) in mac.definition.body.iter().enumerate() {
+            if std::mem::take(&mut skip_close) {
+                continue;
+            }
+            if is_va_args(token) {
+                if extra.is_empty()
+                    && body.last().map(|t: &Token| &t.kind) == Some(&TokenKind::Comma)
+                {
+                    body.pop();
+                }
+                for (i, value) in extra.iter().enumerate() {
+                    if i > 0 {
+                        body.push(punct_token(TokenKind::Comma, token.span));
+                    }
+                    body.extend(parenthesize(value.clone(), token.span));
+                }
+                continue;
+            }
+            let ident = ident_name(token);
+            let param = ident.and_then(|ident| {
+                mac.definition
+                    .params
+                    .iter()
+                    .position(|param| param.name == ident)
+            });
+            match (ident, param) {
+                // The parser reads `(n) * (n)` as a cast of `*(n)` to `n`,
+                // so parentheses the body puts around an identifier or
+                // literal argument are dropped unless a cast could follow
+                (_, Some(index))
+                    if is_atomic(&values[index]) && grouped_alone(&mac.definition.body, at) =>
+                {
+                    substitutions[index] += 1;
+                    body.pop();
+                    body.extend(values[index].clone());
+                    skip_close = true;
+                }
+                (_, Some(index)) => {
+                    substitutions[index] += 1;
+                    body.extend(parenthesize(values[index].clone(), token.span))
+                }
+                (Some(ident), None) if mac.bound.contains(ident) => body.push(ident_token(
+                    &format!("{}__{}", ident, self.uses),
+                    token.span,
+                )),
+                _ => body.push(token.clone()),
+            }
+        }
+        for (value, times) in values.iter().zip(substitutions) {
+            if times < 2 || !has_side_effects(value) {
+                continue;
+            }
+            let argument = join_token_texts(
+                unparenthesize(value)
+                    .iter()
+                    .map(|token| token.text.as_str()),
+            );
+            // An argument already repeated by a nested macro is repeated
+            // again by this one, as in __DOUBLE__(__DOUBLE__(i++))
+            let mut nested = false;
+            for (_, earlier, earlier_times) in &mut self.repeated {
+                if argument.contains(earlier.as_str()) {
+                    *earlier_times = argument.matches(earlier.as_str()).count() * times;
+                    nested = true;
+                }
+            }
+            if !nested {
+                self.repeated.push((name.to_string(), argument, times));
+            }
+        }
+        body
+    }
+
+    /// Warn about the arguments with side effects the macro use at
+    /// `position` evaluates more than once
+    fn warn_repeated(&mut self, position: Option<Position>) {
+        for (name, argument, times) in std::mem::take(&mut self.repeated) {
+            let mut warning = Warning::new(
+                &lints::MACRO_SIDE_EFFECTS,
+                format!(
+                    "argument '{}' of macro '{}' is evaluated {} times, so its side effects happen {} times; assign it to a local first",
+                    argument, name, times, times
+                ),
+            );
+            warning.item = self.item.clone();
+            self.warnings.push(warning.at(position));
+        }
+    }
+
+    /// Report an error expanding the macro use at `position`
+    fn error(&mut self, position: Option<Position>, message: String) {
+        let position = position.unwrap_or(Position::new(0, 0));
+        self.errors.push(SemanticError::new(
+            Span::new(position, position),
+            SemanticErrorKind::InvalidOperation,
+            message,
+        ));
+    }
+}
+
+/// Check the format macros a variadic macro forwards its further arguments
+/// to, as in `__println__(fmt, __VA_ARGS__)`: when the format string is a
+/// literal, in the body or passed for a parameter, it must have a
+/// placeholder for each argument the use passes on
+fn check_format_arguments(
+    definition: &MacroDefinition,
+    values: &[Vec<Token>],
+) -> Result<(), String> {
+    let body = &definition.body;
+    let extra = values.len() - definition.params.len();
+    for (i, token) in body.iter().enumerate() {
+        let Some(name) = ident_name(token).filter(|name| FORMAT_MACROS.contains(name)) else {
+            continue;
+        };
+        if body.get(i + 1).map(|t| &t.kind) != Some(&TokenKind::LParen) {
+            continue;
+        }
+        let Some(end) = matching_close(body, i + 1) else {
+            continue;
+        };
+        let args = split_arguments(&body[i + 2..end]);
+        if !args
+            .iter()
+            .any(|arg| matches!(arg, [token] if is_va_args(token)))
+        {
+            continue;
+        }
+
+        let format = match args[0] {
+            [token] => match ident_name(token)
+                .and_then(|ident| definition.params.iter().position(|p| p.name == ident))
+            {
+                Some(index) => match values[index].as_slice() {
+                    [value] => value,
+                    _ => continue,
+                },
+                None => token,
+            },
+            _ => continue,
+        };
+        let TokenKind::StringLiteral(format) = &format.kind else {
+            continue;
+        };
+        let Some(placeholders) = count_placeholders(format) else {
+            continue;
+        };
+        let passed: usize = args[1..]
+            .iter()
+            .map(|arg| match arg {
+                [token] if is_va_args(token) => extra,
+                _ => 1,
+            })
+            .sum();
+        if placeholders != passed {
+            return Err(format!(
+                "format string \"{}\" of '{}' has {} placeholder{}, but {} argument{} passed",
+                escape(format, '"'),
+                name,
+                placeholders,
+                if placeholders == 1 { "" } else { "s" },
+                passed,
+                if passed == 1 { " is" } else { "s are" }
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The number of `{}` placeholders in a format string, or `None` when one
+/// names its argument (`{0}`, `{name}`) so the count says nothing
+fn count_placeholders(format: &str) -> Option<usize> {
+    let mut count = 0;
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+            }
+            '{' => {
+                let spec: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                let argument = spec.split(':').next().unwrap_or_default();
+                if !argument.is_empty() {
+                    return None;
+                }
+                count += 1;
+            }
+            _ => {}
+        }
+    }
+    Some(count)
+}
+
+/// Whether the expression `tokens` spell increments, decrements or calls
+/// a function
+fn has_side_effects(tokens: &[Token]) -> bool {
+    fn effects(expr: &Expression) -> bool {
+        let effect = match expr {
+            Expression::Unary { op, .. } => matches!(
+                op,
+                UnaryOp::PreInc | UnaryOp::PreDec | UnaryOp::PostInc | UnaryOp::PostDec
+            ),
+            Expression::Call { .. } | Expression::MethodCall { .. } => true,
+            _ => false,
+        };
+        effect || child_expressions(expr).into_iter().any(effects)
+    }
+    parse_tokens(tokens).is_ok_and(|expr| effects(&expr))
+}
+
+fn is_va_args(token: &Token) -> bool {
+    ident_name(token) == Some(VA_ARGS)
+}
+
+/// Tokenize the source of a macro use
+pub(crate) fn lex(source: &str) -> Result<Vec<Token>, String> {
+    Lexer::new(source).tokenize().map_err(|e| e.message)
+}
+
+/// Source text of the arguments of a macro use, which the parser keeps as
+/// token texts with the escapes of literals dropped
+pub(crate) fn arguments_source(args: &[crate::ast::Token]) -> String {
+    let texts: Vec<String> = args
+        .iter()
+        .map(|arg| {
+            let text = arg.text.as_str();
+            let quoted = |prefix: &str, quote: char| {
+                text.strip_prefix(prefix)
+                    .and_then(|rest| rest.strip_suffix(quote))
+                    .map(|value| format!("{}{}{}", prefix, escape(value, quote), quote))
+            };
+            quoted("\"", '"')
+                .or_else(|| quoted("L\"", '"'))
+                .or_else(|| quoted("'", '\''))
+                .unwrap_or_else(|| text.to_string())
+        })
+        .collect();
+    join_token_texts(texts.iter().map(String::as_str))
+}
+
+/// Whether a token of a macro use or `rust { }` block may be a name; the
+/// parser keeps their tokens untyped, so any word may
+pub(crate) fn is_name_token(token: &crate::ast::Token) -> bool {
+    token.kind == crate::ast::TokenKind::Ident
+        || (token.kind == crate::ast::TokenKind::Other
+            && token
+                .text
+                .starts_with(|c: char| c.is_alphabetic() || c == '_'))
+}
+
+/// Parse expanded tokens back into an expression
+pub(crate) fn parse_tokens(tokens: &[Token]) -> Result<Expression, String> {
+    let source = tokens
+        .iter()
+        .map(token_source)
+        .collect::<Vec<_>>()
+        .join(" ");
+    Parser::new(&source)
+        .and_then(|mut parser| parser.parse_standalone_expression())
+        .map_err(|e| e.message)
+}
+
+/// Source text of a token, with the escapes literal tokens drop restored
+fn token_source(token: &Token) -> String {
+    match &token.kind {
+        TokenKind::StringLiteral(value) => format!("\"{}\"", escape(value, '"')),
+        TokenKind::WideStringLiteral(value) => format!("L\"{}\"", escape(value, '"')),
+        TokenKind::CharLiteral(c) => format!("'{}'", escape(&c.to_string(), '\'')),
+        _ => token.text.clone(),
+    }
+}
+
+fn escape(value: &str, quote: char) -> String {
+    let mut escaped = String::new();
+    for c in value.chars() {
+        match c {
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '\\' => escaped.push_str("\\\\"),
+            c if c == quote => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// `tokens` without the parentheses around all of them
+fn unparenthesize(mut tokens: &[Token]) -> &[Token] {
+    while tokens.first().map(|t| &t.kind) == Some(&TokenKind::LParen)
+        && matching_close(tokens, 0) == Some(tokens.len() - 1)
+    {
+        tokens = &tokens[1..tokens.len() - 1];
+    }
+    tokens
+}
+
+/// Whether `tokens` is a single identifier or literal
+fn is_atomic(tokens: &[Token]) -> bool {
+    matches!(tokens, [token] if is_operand(&token.kind))
+}
+
+/// Whether `kind` is an identifier or a literal
+fn is_operand(kind: &TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::Ident(_)
+            | TokenKind::IntLiteral(_)
+            | TokenKind::FloatLiteral(_)
+            | TokenKind::StringLiteral(_)
+            | TokenKind::RawStringLiteral(_)
+            | TokenKind::ByteStringLiteral(_)
+            | TokenKind::WideStringLiteral(_)
+            | TokenKind::CharLiteral(_)
+            | TokenKind::BoolLiteral(_)
+    )
+}
+
+/// Whether the token at `at` in `body` is alone in grouping parentheses,
+/// as in `(x) * 2`, rather than the parentheses of a call or a `match`,
+/// and what follows them cannot be an operand they cast
+fn grouped_alone(body: &[Token], at: usize) -> bool {
+    let kind = |i: usize| body.get(i).map(|token| &token.kind);
+    let follows_word_or_group = at >= 2
+        && (matches!(kind(at - 2), Some(TokenKind::RParen | TokenKind::RBracket))
+            || body[at - 2]
+                .text
+                .starts_with(|c: char| c.is_alphanumeric() || c == '_'));
+    at > 0
+        && kind(at - 1) == Some(&TokenKind::LParen)
+        && kind(at + 1) == Some(&TokenKind::RParen)
+        && !follows_word_or_group
+        && !kind(at + 2).is_some_and(|next| is_operand(next) || *next == TokenKind::LParen)
+}
+
+/// `tokens` in parentheses, unless it is a single token
+fn parenthesize(tokens: Vec<Token>, span: Span) -> Vec<Token> {
+    if tokens.len() <= 1 {
+        return tokens;
+    }
+    let mut grouped = vec![punct_token(TokenKind::LParen, span)];
+    grouped.extend(tokens);
+    grouped.push(punct_token(TokenKind::RParen, span));
+    grouped
+}
+
+/// Index of the token closing the group opened at `open`
+fn matching_close(tokens: &[Token], open: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        match token.kind {
+            TokenKind::LParen | TokenKind::LBracket | TokenKind::LBrace => depth += 1,
+            TokenKind::RParen | TokenKind::RBracket | TokenKind::RBrace => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Split macro arguments at the commas outside any brackets
+pub(crate) fn split_arguments(tokens: &[Token]) -> Vec<&[Token]> {
+    if tokens.is_empty() {
+        return Vec::new();
+    }
+    let mut args = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        match token.kind {
+            TokenKind::LParen | TokenKind::LBracket | TokenKind::LBrace => depth += 1,
+            TokenKind::RParen | TokenKind::RBracket | TokenKind::RBrace => {
+                depth = depth.saturating_sub(1)
+            }
+            TokenKind::Comma if depth == 0 => {
+                args.push(&tokens[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    args.push(&tokens[start..]);
+    args
+}
+
+fn macro_delimiter_text(delimiter: &MacroDelimiter) -> (&'static str, &'static str) {
+    match delimiter {
+        MacroDelimiter::None => ("", ""),
+        MacroDelimiter::Parens => ("(", ")"),
+        MacroDelimiter::Brackets => ("[", "]"),
+        MacroDelimiter::Braces => ("{", "}"),
+    }
+}
+
+fn delimiter_tokens(delimiter: &MacroDelimiter) -> (Option<TokenKind>, Option<TokenKind>) {
+    match delimiter {
+        MacroDelimiter::None => (None, None),
+        MacroDelimiter::Parens => (Some(TokenKind::LParen), Some(TokenKind::RParen)),
+        MacroDelimiter::Brackets => (Some(TokenKind::LBracket), Some(TokenKind::RBracket)),
+        MacroDelimiter::Braces => (Some(TokenKind::LBrace), Some(TokenKind::RBrace)),
+    }
+}
+
+fn ident_name(token: &Token) -> Option<&str> {
+    match &token.kind {
+        TokenKind::Ident(name) => Some(name),
+        _ => None,
+    }
+}
+
+fn ident_token(name: &str, span: Span) -> Token {
+    Token::new(TokenKind::Ident(name.to_string()), span, name.to_string())
+}
+
+fn punct_token(kind: TokenKind, span: Span) -> Token {
+    let text = match kind {
+        TokenKind::LParen => "(",
+        TokenKind::RParen => ")",
+        TokenKind::LBracket => "[",
+        TokenKind::RBracket => "]",
+        TokenKind::LBrace => "{",
+        TokenKind::RBrace => "}",
+        TokenKind::Comma => ",",
+        _ => unreachable!("only grouping punctuation and commas are synthesized"),
+    };
+    Token::new(kind, span, text.to_string())
+}
+
+pub(crate) fn child_expressions(expr: &Expression) -> Vec<&Expression> {
+    match expr {
+        Expression::Binary { left, right, .. } | Expression::Comma { left, right } => {
+            vec![left, right]
+        }
+        Expression::Unary { expr, .. }
+        | Expression::FieldAccess { expr, .. }
+        | Expression::Cast { expr, .. }
+        | Expression::SizeofExpr { expr }
+        | Expression::AlignofExpr { expr }
+        | Expression::TruthTest { expr, .. }
+        | Expression::CheckedCast { expr, .. }
+        | Expression::ErrorProp { expr } => vec![expr],
+        Expression::Index { expr, index } => vec![expr, index],
+        Expression::Call { func, args } => std::iter::once(func.as_ref()).chain(args).collect(),
+        Expression::Ternary {
+            condition,
+            then_expr,
+            else_expr,
+        } => vec![condition, then_expr, else_expr],
+        Expression::StructInit { fields, .. } => fields.iter().map(|(_, value)| value).collect(),
+        Expression::ArrayLit { elements } | Expression::TupleLit { elements } => {
+            elements.iter().collect()
+        }
+        Expression::Range { start, end, .. } => start
+            .iter()
+            .chain(end)
+            .map(|bound| bound.as_ref())
+            .collect(),
+        Expression::MethodCall { receiver, args, .. } => {
+            std::iter::once(receiver.as_ref()).chain(args).collect()
+        }
+        Expression::TypeScopedCall { args, .. } | Expression::ExplicitGenericCall { args, .. } => {
+            args.iter().collect()
+        }
+        Expression::Match { expr, arms } => std::iter::once(expr.as_ref())
+            .chain(arms.iter().map(|arm| &arm.body))
+            .collect(),
+        Expression::Literal(_)
+        | Expression::Ident(_)
+        | Expression::Sizeof { .. }
+        | Expression::Alignof { .. }
+        | Expression::MacroCall { .. }
+        | Expression::RustBlock { .. }
+        | Expression::Lambda { .. } => Vec::new(),
+    }
+}
+
+pub(crate) fn child_expressions_mut(expr: &mut Expression) -> Vec<&mut Expression> {
+    match expr {
+        Expression::Binary { left, right, .. } | Expression::Comma { left, right } => {
+            vec![left, right]
+        }
+        Expression::Unary { expr, .. }
+        | Expression::FieldAccess { expr, .. }
+        | Expression::Cast { expr, .. }
+        | Expression::SizeofExpr { expr }
+        | Expression::AlignofExpr { expr }
+        | Expression::TruthTest { expr, .. }
+        | Expression::CheckedCast { expr, .. }
+        | Expression::ErrorProp { expr } => vec![expr],
+        Expression::Index { expr, index } => vec![expr, index],
+        Expression::Call { func, args } => std::iter::once(func.as_mut()).chain(args).collect(),
+        Expression::Ternary {
+            condition,
+            then_expr,
+            else_expr,
+        } => vec![condition, then_expr, else_expr],
+        Expression::StructInit { fields, .. } => {
+            fields.iter_mut().map(|(_, value)| value).collect()
+        }
+        Expression::ArrayLit { elements } | Expression::TupleLit { elements } => {
+            elements.iter_mut().collect()
+        }
+        Expression::Range { start, end, .. } => start
+            .iter_mut()
+            .chain(end)
+            .map(|bound| bound.as_mut())
+            .collect(),
+        Expression::MethodCall { receiver, args, .. } => {
+            std::iter::once(receiver.as_mut()).chain(args).collect()
+        }
+        Expression::TypeScopedCall { args, .. } | Expression::ExplicitGenericCall { args, .. } => {
+            args.iter_mut().collect()
+        }
+        Expression::Match { expr, arms } => std::iter::once(expr.as_mut())
+            .chain(arms.iter_mut().map(|arm| &mut arm.body))
+            .collect(),
+        Expression::Literal(_)
+        | Expression::Ident(_)
+        | Expression::Sizeof { .. }
+        | Expression::Alignof { .. }
+        | Expression::MacroCall { .. }
+        | Expression::RustBlock { .. }
+        | Expression::Lambda { .. } => Vec::new(),
+    }
+}
+
+/// Give the names in the expansion `expr` the `span` of the macro use, since
+/// they were lexed from the expanded text rather than the source
+fn respan(expr: &mut Expression, span: Span) {
+    match expr {
+        Expression::Ident(name) => name.span = span,
+        Expression::FieldAccess { field, .. } => field.span = span,
+        Expression::MethodCall { method, .. } => method.span = span,
+        _ => {}
+    }
+    for child in child_expressions_mut(expr) {
+        respan(child, span);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::{CodeGenerator, TargetLanguage};
+
+    /// Expand the macros of `source` and generate Rust for the result
+    fn expand(source: &str) -> Result<String, Vec<SemanticError>> {
+        let mut file = Parser::new(source).unwrap().parse_file().unwrap();
+        expand_macros(&mut file, crate::limits::DEFAULT_MAX_EXPANSION_TOKENS)?;
+        Ok(CodeGenerator::new(TargetLanguage::Rust).generate(&file))
+    }
+
+    #[test]
+    fn test_expands_constant_and_parameterized_macros() {
+        let rust = expand(
+            "#define __PI__ 3.14159\n#define __SQUARE__(x) x * x\nfloat area(float r) {\n    return __PI__ * __SQUARE__(r + 1.0);\n}\n",
+        )
+        .unwrap();
+        assert!(!rust.contains("macro_rules!"), "{}", rust);
+        assert!(
+            rust.contains("return (3.14159 * ((r + 1.0) * (r + 1.0)));"),
+            "{}",
+            rust
+        );
+    }
+
+    #[test]
+    fn test_parenthesized_parameters_are_not_casts() {
+        let rust = expand(
+            "#define __SQ__(x) ((x) * (x))\n#define __AS__(T, v) ((T)v)\nint f(int n) {\n    return __SQ__(n) + __SQ__(2) + __SQ__(n + 1);\n}\nu8 g(int n) {\n    return __AS__(u8, n);\n}\n",
+        )
+        .unwrap();
+        assert!(
+            rust.contains("return (((n * n) + (2 * 2)) + ((n + 1) * (n + 1)));"),
+            "{}",
+            rust
+        );
+        // Parentheses followed by an operand still cast it
+        assert!(rust.contains("return (n as u8);"), "{}", rust);
+    }
+
+    #[test]
+    fn test_expands_nested_macro_uses() {
+        let rust = expand(
+            "#define __DOUBLE__(x) x + x\n#define __QUAD__(x) __DOUBLE__(__DOUBLE__(x))\nvoid main() {\n    let n = __QUAD__(1);\n    __println__(\"{}\", __DOUBLE__(n));\n}\n",
+        )
+        .unwrap();
+        assert!(rust.contains("let n = ((1 + 1) + (1 + 1));"), "{}", rust);
+        assert!(rust.contains("println!(\"{}\", (n + n))"), "{}", rust);
+    }
+
+    #[test]
+    fn test_body_bindings_do_not_capture_arguments() {
+        let rust = expand(
+            "#define __OR__(o, d) match (o) { Option.Some(v) => v, _ => d }\nint f(Option<int> o, int v) {\n    return __OR__(o, v);\n}\n",
+        )
+        .unwrap();
+        assert!(rust.contains("Some(v__1) => v__1"), "{}", rust);
+        assert!(rust.contains("_ => v"), "{}", rust);
+    }
+
+    #[test]
+    fn test_expands_variadic_macros() {
+        let rust = expand(
+            "#define __LOG__(fmt, ...) __println__(fmt, __VA_ARGS__)\n#define __SUM__(...) add3(__VA_ARGS__)\nint add3(int a, int b, int c) {\n    return a + b + c;\n}\nvoid main() {\n    int done = 3;\n    __LOG__(\"{} of {}\", done, done + 2);\n    __LOG__(\"starting\");\n    __LOG__(\"{{sum}} {:>4}\", __SUM__(1, 2, done));\n    __LOG__(\"{0} {0}\", done);\n}\n",
+        )
+        .unwrap();
+        assert!(
+            rust.contains("println!(\"{} of {}\", done, (done + 2));"),
+            "{}",
+            rust
+        );
+        assert!(rust.contains("println!(\"starting\");"), "{}", rust);
+        assert!(
+            rust.contains("println!(\"{{sum}} {:>4}\", ((add3(1, 2, done))));"),
+            "{}",
+            rust
+        );
+
+        let errors = expand(
+            "#define __LOG__(fmt, ...) __println__(fmt, __VA_ARGS__)\n#define __ADD__(a) a + __VA_ARGS__\nvoid main() {\n    __LOG__(\"{} of {}\", 1);\n    __LOG__(\"done\", 1, 2);\n    __LOG__();\n    let n = __ADD__(1);\n}\n",
+        )
+        .unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "format string \"{} of {}\" of '__println__' has 2 placeholders, but 1 argument is passed",
+                "format string \"done\" of '__println__' has 0 placeholders, but 2 arguments are passed",
+                "macro '__LOG__' expects at least 1 argument, found 0",
+                "macro '__ADD__' uses __VA_ARGS__ but does not end its parameters in '...'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_warns_about_repeated_arguments_with_side_effects() {
+        let mut file = Parser::new(
+            "#define __MAX__(a, b) a > b ? a : b\n#define __NEG__(x) -x\n#define __DOUBLE__(x) x + x\n#define __QUAD__(x) __DOUBLE__(__DOUBLE__(x))\nint next(int n) {\n    return n + 1;\n}\nvoid main() {\n    var int i = 1;\n    int j = 2;\n    int m = __MAX__(i++, j);\n    int q = __QUAD__(next(j));\n    int once = __NEG__(next(i)) + __DOUBLE__(j + 1);\n}\n",
+        )
+        .unwrap()
+        .parse_file()
+        .unwrap();
+        let warnings =
+            expand_macros(&mut file, crate::limits::DEFAULT_MAX_EXPANSION_TOKENS).unwrap();
+        assert_eq!(
+            warnings,
+            [
+                "argument 'i ++' of macro '__MAX__' is evaluated 2 times, so its side effects happen 2 times; assign it to a local first",
+                "argument 'next(j)' of macro '__DOUBLE__' is evaluated 4 times, so its side effects happen 4 times; assign it to a local first",
+            ]
+        );
+        let positions: Vec<Option<Position>> = warnings.iter().map(|w| w.position).collect();
+        assert_eq!(
+            positions,
+            [Some(Position::new(11, 13)), Some(Position::new(12, 13))]
+        );
+    }
+
+    #[test]
+    fn test_expansion_errors_point_at_the_use() {
+        let errors = expand(
+            "#define __MAX__(a, b) a > b ? a : b\n#define __LOOP__ __LOOP__ + 1\nint f(int x) {\n    return __MAX__(x, 1, 2);\n}\nint g() {\n    return __LOOP__;\n}\n",
+        )
+        .unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "macro '__MAX__' expects 2 arguments, found 3",
+                "macro '__LOOP__' expands to itself",
+            ]
+        );
+        let starts: Vec<Position> = errors.iter().map(|e| e.span.start).collect();
+        assert_eq!(starts, [Position::new(4, 12), Position::new(7, 12)]);
+    }
+
+    #[test]
+    fn test_expansion_stops_at_the_token_limit() {
+        let mut file = Parser::new(
+            "#define __TWICE__(x) x + x\n#define __FOUR__(x) __TWICE__(__TWICE__(x))\n#define __HUGE__(x) __FOUR__(__FOUR__(__FOUR__(__FOUR__(x))))\nint f() {\n    return __FOUR__(1);\n}\nint g() {\n    return __HUGE__(1);\n}\n",
+        )
+        .unwrap()
+        .parse_file()
+        .unwrap();
+        let errors = expand_macros(&mut file, 1_000).unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(messages, ["macro expansion exceeded 1K tokens"]);
+    }
+}
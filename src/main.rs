@@ -1,18 +1,45 @@
 // Copyright (c) 2026 Mark Ferrell
 // Licensed under the MIT License. See LICENSE.txt in the project root.
 
+mod archive;
 mod ast;
+mod borrowck;
+mod cfg;
 mod cli;
+mod clippy;
 mod codegen;
+mod compile_db;
+mod consteval;
+mod coverage;
+mod dataflow;
+mod deadcode;
+mod desugar;
+mod determinism;
 mod error;
+mod hir;
+mod inline_macros;
 mod lexer;
+mod limits;
+mod lints;
+mod macros;
+mod modules;
+mod no_effect;
 mod parser;
+mod platform;
+mod preprocess;
 mod pretty;
 mod rustc;
 mod semantic;
+mod size_report;
+mod source_map;
+mod switch_ladders;
+mod symbols;
+mod tailcall;
+mod timings;
+mod unused;
 mod utils;
 
-use cli::{run_compiler, CompilerOptions};
+use cli::{report_error, run_compiler, CompilerOptions};
 use std::process;
 
 fn main() {
@@ -27,7 +54,7 @@ fn main() {
             }
         }
         Err(e) => {
-            eprintln!("Error: {}", e);
+            report_error(&options, &e);
             process::exit(1);
         }
     }
@@ -0,0 +1,460 @@
+// Copyright (c) 2026 Mark Ferrell
+// Licensed under the MIT License. See LICENSE.txt in the project root.
+
+//! Multi-file compilation: loading the Crusty files named by `#import`.
+//!
+//! `#import util.clamp` in `main.crst` refers to the module `util` when a
+//! `util.crst` sits next to `main.crst`. Such modules are parsed here,
+//! checked before the file importing them, and emitted as inline `pub mod`
+//! blocks of the generated crate. Paths that name no sibling file, such as
+//! `#import std.io`, are left for rustc to resolve.
+//!
+//! Files given on the command line after the root file are compiled into
+//! the same crate as modules too. These share one symbol table: each of
+//! them, and the root file, can use the public items of the others without
+//! an `#import`.
+
+use crate::ast::{File, Ident, Import, Item, Visibility};
+use crate::error::{CompilerError, Position, SemanticError, SemanticErrorKind, Span};
+use crate::parser::Parser;
+use crate::timings::Timings;
+use rayon::prelude::*;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Extension of the Crusty files `#import` resolves to
+const MODULE_EXTENSION: &str = "crst";
+
+/// A Crusty file compiled as a module of another
+#[derive(Debug, Clone)]
+pub struct Module {
+    /// Module name: the file stem, as written in `#import`
+    pub name: String,
+    /// Path the module was loaded from
+    pub path: PathBuf,
+    /// Parsed contents of the module
+    pub file: File,
+    /// Whether the module was given on the command line, sharing its public
+    /// items with the other inputs
+    pub input: bool,
+}
+
+/// Load every Crusty module `file`, read from `path`, imports directly or
+/// through other modules
+///
+/// Modules are returned after the modules they import, so they can be
+/// analyzed in order. An import cycle is reported as an error naming the
+/// modules along it. Each module's conditional blocks are resolved with the
+/// names in `defines`, as the root file's are.
+pub fn load_modules(
+    path: &Path,
+    file: &File,
+    defines: &[String],
+) -> Result<Vec<Module>, CompilerError> {
+    let mut loader = Loader {
+        dir: path.parent().unwrap_or(Path::new(".")).to_path_buf(),
+        modules: Vec::new(),
+        stack: vec![module_name(path)],
+        defines: defines.to_vec(),
+    };
+    loader.visit(file)?;
+    Ok(loader.modules)
+}
+
+/// Depth-first walk over the `#import` graph
+struct Loader {
+    /// Directory imported modules are looked up in
+    dir: PathBuf,
+    /// Modules loaded so far, dependencies first
+    modules: Vec<Module>,
+    /// Modules being loaded, from the root file to the current one
+    stack: Vec<String>,
+    /// Names defined for `#ifdef` on the command line
+    defines: Vec<String>,
+}
+
+impl Loader {
+    fn visit(&mut self, file: &File) -> Result<(), CompilerError> {
+        for item in &file.items {
+            let path = match item {
+                Item::Import(import) => &import.path,
+                Item::Export(export) => &export.path,
+                _ => continue,
+            };
+            let Some(name) = path.first().map(|ident| ident.name.as_str()) else {
+                continue;
+            };
+            if self.modules.iter().any(|module| module.name == name) {
+                continue;
+            }
+            let module_path = self.dir.join(format!("{}.{}", name, MODULE_EXTENSION));
+            if !module_path.is_file() {
+                continue;
+            }
+            if let Some(start) = self.stack.iter().position(|open| open == name) {
+                let mut cycle = self.stack[start..].to_vec();
+                cycle.push(name.to_string());
+                return Err(CompilerError::Semantic(vec![SemanticError::new(
+                    Span::new(Position::new(0, 0), Position::new(0, 0)),
+                    SemanticErrorKind::InvalidOperation,
+                    format!("import cycle: {}", cycle.join(" -> ")),
+                )]));
+            }
+
+            let module_file = parse_module(&module_path, &self.defines)?;
+
+            self.stack.push(name.to_string());
+            self.visit(&module_file)?;
+            self.stack.pop();
+            self.modules.push(Module {
+                name: name.to_string(),
+                path: module_path,
+                file: module_file,
+                input: false,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Read, preprocess and parse the Crusty file at `path`
+fn parse_module(path: &Path, defines: &[String]) -> Result<File, CompilerError> {
+    let source = std::fs::read_to_string(path)?;
+    crate::preprocess::preprocess(&source, defines)
+        .map_err(CompilerError::from)
+        .and_then(|source| Ok(Parser::new(&source)?.parse_file_partial().into_file()?))
+        .map_err(|e| CompilerError::in_module(path, e))
+}
+
+/// The module name of the Crusty file at `path`: its file stem
+fn module_name(path: &Path) -> String {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn input_error(message: String) -> CompilerError {
+    CompilerError::Semantic(vec![SemanticError::new(
+        Span::new(Position::new(0, 0), Position::new(0, 0)),
+        SemanticErrorKind::InvalidOperation,
+        message,
+    )])
+}
+
+/// Add the Crusty files given after the root file at `root` to `modules`,
+/// the modules the root file imports, along with the modules they import
+///
+/// The inputs are parsed in parallel, each parse recorded in `timings`. Each
+/// becomes the module named by its
+/// file stem, so two inputs with the same stem, or one named like the root
+/// file, are an error. An input the root file already imports is marked as
+/// an input rather than loaded twice.
+pub fn load_inputs(
+    root: &Path,
+    inputs: &[PathBuf],
+    modules: &mut Vec<Module>,
+    defines: &[String],
+    timings: &Timings,
+) -> Result<(), CompilerError> {
+    let parsed: Vec<Result<File, CompilerError>> = inputs
+        .par_iter()
+        .map(|path| {
+            timings.time("parse", &path.display().to_string(), || {
+                parse_module(path, defines)
+            })
+        })
+        .collect();
+
+    let root_name = module_name(root);
+    let mut seen: BTreeMap<String, &Path> = BTreeMap::new();
+    for (path, file) in inputs.iter().zip(parsed) {
+        let file = file?;
+        let name = module_name(path);
+        if name == root_name {
+            return Err(input_error(format!(
+                "input '{}' would be module '{}', which names the root file '{}'",
+                path.display(),
+                name,
+                root.display()
+            )));
+        }
+        if let Some(other) = seen.insert(name.clone(), path) {
+            return Err(input_error(format!(
+                "inputs '{}' and '{}' would both be module '{}'",
+                other.display(),
+                path.display(),
+                name
+            )));
+        }
+
+        for dependency in load_modules(path, &file, defines)? {
+            if !modules.iter().any(|module| module.name == dependency.name) {
+                modules.push(dependency);
+            }
+        }
+        match modules.iter_mut().find(|module| module.name == name) {
+            Some(module) if same_file(&module.path, path) => module.input = true,
+            Some(module) => {
+                return Err(input_error(format!(
+                    "input '{}' would be module '{}', which is already imported from '{}'",
+                    path.display(),
+                    name,
+                    module.path.display()
+                )));
+            }
+            None => modules.push(Module {
+                name,
+                path: path.clone(),
+                file,
+                input: true,
+            }),
+        }
+    }
+    Ok(())
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// The name of the item `item` declares, if other files can import it
+fn item_name(item: &Item) -> Option<(&str, &Visibility)> {
+    match item {
+        Item::Function(func) => Some((&func.name.name, &func.visibility)),
+        Item::Struct(struct_def) => Some((&struct_def.name.name, &struct_def.visibility)),
+        Item::Enum(enum_def) => Some((&enum_def.name.name, &enum_def.visibility)),
+        Item::Typedef(typedef) => Some((&typedef.name.name, &typedef.visibility)),
+        Item::Const(const_def) => Some((&const_def.name.name, &const_def.visibility)),
+        Item::Static(static_def) => Some((&static_def.name.name, &static_def.visibility)),
+        _ => None,
+    }
+}
+
+/// Let the root file and every input module use the public items of the
+/// input modules without an `#import`
+///
+/// Each name a file uses but neither declares nor imports, and that an input
+/// module other than its own declares publicly, gets an implicit `#import`
+/// of that item, so analysis and code generation treat it as written. A
+/// public name declared by two inputs is an error.
+pub fn share_input_items(root: &mut File, modules: &mut [Module]) -> Result<(), CompilerError> {
+    let mut public: BTreeMap<String, String> = BTreeMap::new();
+    for module in modules.iter().filter(|module| module.input) {
+        for item in &module.file.items {
+            let Some((name, Visibility::Public)) = item_name(item) else {
+                continue;
+            };
+            if let Some(other) = public.insert(name.to_string(), module.name.clone()) {
+                return Err(input_error(format!(
+                    "'{}' is declared by both module '{}' and module '{}'",
+                    name, other, module.name
+                )));
+            }
+        }
+    }
+
+    import_shared_items(root, None, &public);
+    for module in modules.iter_mut().filter(|module| module.input) {
+        import_shared_items(&mut module.file, Some(&module.name), &public);
+    }
+    Ok(())
+}
+
+/// Prepend to `file`, the module `own` or the root file, an `#import` for
+/// each item of `public` it uses from another module
+fn import_shared_items(file: &mut File, own: Option<&str>, public: &BTreeMap<String, String>) {
+    let mut known: HashSet<&str> = HashSet::new();
+    for item in &file.items {
+        match item {
+            Item::Import(import) => {
+                let name = import.alias.as_ref().or(import.path.last());
+                known.extend(name.map(|ident| ident.name.as_str()));
+            }
+            _ => known.extend(item_name(item).map(|(name, _)| name)),
+        }
+    }
+
+    let used = crate::deadcode::referenced_names(file);
+    let imports: Vec<Item> = public
+        .iter()
+        .filter(|(name, module)| {
+            Some(module.as_str()) != own
+                && used.contains(name.as_str())
+                && !known.contains(name.as_str())
+        })
+        .map(|(name, module)| {
+            Item::Import(Import {
+                path: vec![Ident::new(module.as_str()), Ident::new(name.as_str())],
+                alias: None,
+            })
+        })
+        .collect();
+    file.items.splice(0..0, imports);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write `files` into a fresh directory and load the imports of the first
+    fn load(test: &str, files: &[(&str, &str)]) -> Result<Vec<Module>, CompilerError> {
+        let dir = std::env::temp_dir().join(format!("crusty_modules_{}", test));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, source) in files {
+            std::fs::write(dir.join(name), source).unwrap();
+        }
+        let root = dir.join(files[0].0);
+        let file = Parser::new(files[0].1).unwrap().parse_file().unwrap();
+        let result = load_modules(&root, &file, &[]);
+        std::fs::remove_dir_all(&dir).unwrap();
+        result
+    }
+
+    #[test]
+    fn test_imports_load_dependencies_first() {
+        let modules = load(
+            "order",
+            &[
+                (
+                    "main.crst",
+                    "#import std.io\n#import shapes.area\n#import util.clamp\nvoid main() {}\n",
+                ),
+                (
+                    "shapes.crst",
+                    "#import util.clamp\nint area(int w, int h) { return clamp(w) * h; }\n",
+                ),
+                ("util.crst", "int clamp(int x) { return x; }\n"),
+            ],
+        )
+        .unwrap();
+        let names: Vec<&str> = modules.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["util", "shapes"]);
+    }
+
+    #[test]
+    fn test_import_cycle_is_reported() {
+        let err = load(
+            "cycle",
+            &[
+                ("main.crst", "#import a.f\nvoid main() {}\n"),
+                ("a.crst", "#import b.g\nint f() { return 1; }\n"),
+                ("b.crst", "#import a.f\nint g() { return 2; }\n"),
+            ],
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Semantic errors:\n  Semantic error at 0:0-0:0 (invalid operation): import cycle: a -> b -> a\n"
+        );
+    }
+
+    /// Write `files` into a fresh directory and load the rest as inputs of
+    /// the first, sharing their public items
+    fn load_inputs_of(
+        test: &str,
+        files: &[(&str, &str)],
+    ) -> Result<(File, Vec<Module>), CompilerError> {
+        let dir = std::env::temp_dir().join(format!("crusty_inputs_{}", test));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, source) in files {
+            std::fs::write(dir.join(name), source).unwrap();
+        }
+        let inputs: Vec<PathBuf> = files[1..].iter().map(|(name, _)| dir.join(name)).collect();
+        let mut root = Parser::new(files[0].1).unwrap().parse_file().unwrap();
+        let result = load_modules(&dir.join(files[0].0), &root, &[]).and_then(|mut modules| {
+            let timings = Timings::new();
+            load_inputs(&dir.join(files[0].0), &inputs, &mut modules, &[], &timings)?;
+            share_input_items(&mut root, &mut modules)?;
+            Ok(modules)
+        });
+        std::fs::remove_dir_all(&dir).unwrap();
+        result.map(|modules| (root, modules))
+    }
+
+    fn imports(file: &File) -> Vec<String> {
+        file.items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Import(import) => Some(
+                    import
+                        .path
+                        .iter()
+                        .map(|ident| ident.name.as_str())
+                        .collect::<Vec<_>>()
+                        .join("."),
+                ),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_inputs_share_public_items() {
+        let (root, modules) = load_inputs_of(
+            "share",
+            &[
+                ("main.crst", "void main() { let Size s = grow(1); }\n"),
+                (
+                    "grow.crst",
+                    "#import size.Size\nSize grow(Size s) { return s + step(); }\n",
+                ),
+                (
+                    "size.crst",
+                    "typedef int Size;\nint step() { return 1; }\nstatic int hidden() { return 0; }\n",
+                ),
+            ],
+        )
+        .unwrap();
+        assert_eq!(imports(&root), ["size.Size", "grow.grow"]);
+        let names: Vec<&str> = modules.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["size", "grow"]);
+        assert!(modules.iter().all(|module| module.input));
+        // The explicit import is kept and not repeated
+        assert_eq!(imports(&modules[1].file), ["size.step", "size.Size"]);
+    }
+
+    #[test]
+    fn test_conflicting_inputs_are_reported() {
+        let err = load_inputs_of(
+            "duplicate",
+            &[
+                ("main.crst", "void main() {}\n"),
+                ("a.crst", "int f() { return 1; }\n"),
+                ("b.crst", "int f() { return 2; }\n"),
+            ],
+        )
+        .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("'f' is declared by both module 'a' and module 'b'"),
+            "{}",
+            err
+        );
+
+        let err = load_inputs_of(
+            "root_name",
+            &[("main.crst", "void main() {}\n"), ("main.crst", "")],
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("names the root file"), "{}", err);
+    }
+
+    #[test]
+    fn test_module_parse_errors_name_the_file() {
+        let err = load(
+            "parse_error",
+            &[
+                ("main.crst", "#import broken.f\nvoid main() {}\n"),
+                ("broken.crst", "int f( {\n"),
+            ],
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("broken.crst: "), "{}", err);
+    }
+}
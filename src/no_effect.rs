@@ -0,0 +1,162 @@
+// Copyright (c) 2026 Mark Ferrell
+// Licensed under the MIT License. See LICENSE.txt in the project root.
+
+//! Detection of expression statements that have no effect.
+//!
+//! A statement that computes a value and discards it does nothing. In code
+//! ported from C it is usually a typo for an assignment, or a leftover from
+//! an edit:
+//!
+//! ```text
+//! count + 1;        // meant count = count + 1;
+//! done == true;     // meant done = true;
+//! ```
+//!
+//! An expression has an effect when it calls a function, method or macro,
+//! assigns, increments or decrements, dereferences (the read may be of a
+//! volatile register), propagates an error with `?`, or runs Rust or a
+//! `match`. Casting to `void`, as in `(void)unused;`, discards a value on
+//! purpose and is not reported.
+
+use crate::ast::{
+    Block, Expression, File, Function, Item, PrimitiveType, Statement, Type, UnaryOp,
+};
+use crate::codegen::statement_expressions;
+use crate::error::Position;
+use crate::macros::child_expressions;
+use crate::tailcall::child_blocks;
+
+/// An expression statement whose value is discarded and that does nothing
+/// else
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NoEffect {
+    /// Function the statement is in
+    pub function: String,
+    /// Where the statement starts, when it was parsed from source
+    pub position: Option<Position>,
+}
+
+/// Find the statements without effect in every function and method of
+/// `file`
+pub fn find_no_effect_statements(file: &File) -> Vec<NoEffect> {
+    let mut found = Vec::new();
+    items(&file.items, &mut found);
+    found
+}
+
+fn items(items: &[Item], found: &mut Vec<NoEffect>) {
+    for item in items {
+        match item {
+            Item::Function(func) => function(func, found),
+            Item::Struct(s) => s.methods.iter().for_each(|m| function(m, found)),
+            Item::Impl(i) => i.methods.iter().for_each(|m| function(m, found)),
+            Item::Trait(t) => t
+                .methods
+                .iter()
+                .filter_map(|m| Some((&m.name.name, m.body.as_ref()?)))
+                .for_each(|(name, body)| block(name, body, found)),
+            Item::Namespace(namespace) => self::items(&namespace.items, found),
+            _ => {}
+        }
+    }
+}
+
+fn function(func: &Function, found: &mut Vec<NoEffect>) {
+    block(&func.name.name, &func.body, found);
+}
+
+fn block(function: &str, block: &Block, found: &mut Vec<NoEffect>) {
+    for (i, stmt) in block.statements.iter().enumerate() {
+        match stmt {
+            Statement::Expr(expr) if !has_effect(expr) => found.push(NoEffect {
+                function: function.to_string(),
+                position: block.positions.get(i).copied(),
+            }),
+            Statement::NestedFunction { name, body, .. } => self::block(&name.name, body, found),
+            _ => {}
+        }
+        for expr in statement_expressions(stmt) {
+            lambdas(function, expr, found);
+        }
+        for child in child_blocks(stmt) {
+            self::block(function, child, found);
+        }
+    }
+}
+
+/// Check the bodies of the lambdas in `expr`, which are part of `function`
+fn lambdas(function: &str, expr: &Expression, found: &mut Vec<NoEffect>) {
+    match expr {
+        Expression::Lambda { body, .. } => self::block(function, body, found),
+        expr => {
+            for child in child_expressions(expr) {
+                lambdas(function, child, found);
+            }
+        }
+    }
+}
+
+/// Whether evaluating `expr` can do anything besides produce its value
+fn has_effect(expr: &Expression) -> bool {
+    // The operand of `sizeof` and `alignof` is not evaluated
+    if matches!(
+        expr,
+        Expression::SizeofExpr { .. } | Expression::AlignofExpr { .. }
+    ) {
+        return false;
+    }
+    let effect = match expr {
+        Expression::Binary { op, .. } => op.is_assignment(),
+        Expression::Unary { op, .. } => !matches!(op, UnaryOp::Not | UnaryOp::Neg | UnaryOp::Ref),
+        Expression::Cast {
+            ty: Type::Primitive(PrimitiveType::Void),
+            ..
+        } => true,
+        Expression::Call { .. }
+        | Expression::MethodCall { .. }
+        | Expression::TypeScopedCall { .. }
+        | Expression::ExplicitGenericCall { .. }
+        | Expression::MacroCall { .. }
+        | Expression::RustBlock { .. }
+        | Expression::ErrorProp { .. }
+        | Expression::Match { .. } => true,
+        _ => false,
+    };
+    effect || child_expressions(expr).into_iter().any(has_effect)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn find(source: &str) -> Vec<NoEffect> {
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+        find_no_effect_statements(&file)
+    }
+
+    #[test]
+    fn test_finds_statements_without_effect() {
+        let found = find(
+            "int next(int n) {\n    return n + 1;\n}\nvoid main() {\n    var int count = 0;\n    count + 1;\n    count == 2;\n    if (count > 0) {\n        count;\n    }\n    count = count + 1;\n    count++;\n    next(count);\n    (void)count;\n    -next(count);\n}\n",
+        );
+        let lines: Vec<usize> = found
+            .iter()
+            .map(|statement| statement.position.unwrap().line)
+            .collect();
+        assert_eq!(lines, [6, 7, 9]);
+        assert!(found.iter().all(|statement| statement.function == "main"));
+    }
+
+    #[test]
+    fn test_finds_statements_in_lambdas() {
+        let found = find(
+            "void main() {\n    var int count = 0;\n    let bump = fn(int n) {\n        count + n;\n        count = count + n;\n    };\n    bump(1);\n}\n",
+        );
+        let lines: Vec<usize> = found
+            .iter()
+            .map(|statement| statement.position.unwrap().line)
+            .collect();
+        assert_eq!(lines, [4]);
+    }
+}
@@ -13,18 +13,234 @@
 //! - Direct AST construction within grammar rules
 
 use crate::ast::*;
-use crate::error::ParseError;
+use crate::consteval::{self, ConstValue};
+use crate::error::{ParseError, Position};
 use crate::lexer::{Lexer, Token, TokenKind};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+
+/// Deepest nesting of blocks, expressions and types the parser accepts
+///
+/// Each level is a recursive call, so this bounds stack use on pathological
+/// input such as thousands of nested parentheses.
+pub const MAX_NESTING_DEPTH: usize = 128;
+
+/// Most binary operators the parser chains into one expression
+///
+/// A chain such as `1 + 1 + … + 1` nests one level per operator in the tree
+/// every later pass recurses through, rustc's included, so this bounds their
+/// stack use.
+pub const MAX_OPERATOR_DEPTH: usize = 1024;
+
+/// Upper bound on the syntax errors one recovering parse reports
+const MAX_PARSE_ERRORS: usize = 32;
+
+/// Name of the static assertion form, which is not a reserved keyword
+const STATIC_ASSERT: &str = "static_assert";
+
+/// Spellings of C's `restrict` pointer qualifier, which are not reserved
+/// keywords either
+const RESTRICT: &[&str] = &["restrict", "__restrict", "__restrict__"];
+
+/// Words starting trait declarations and implementations, which are not
+/// reserved keywords: a variable may still be named `impl`
+const ITEM_KEYWORDS: &[&str] = &["trait", "interface", "impl"];
+
+/// `text` without the type suffix of a number literal, and the type the
+/// suffix gives. `f32` and `f64` are only suffixes when `floats` is set,
+/// since a hexadecimal literal may end in those digits.
+fn split_suffix(text: &str, floats: bool) -> (&str, Option<PrimitiveType>) {
+    [5, 3, 2]
+        .into_iter()
+        .filter(|&len| text.len() > len && text.is_char_boundary(text.len() - len))
+        .find_map(|len| {
+            let (number, suffix) = text.split_at(text.len() - len);
+            PrimitiveType::from_suffix(suffix)
+                .filter(|ty| floats || ty.is_integer())
+                .map(|ty| (number, Some(ty)))
+        })
+        .unwrap_or((text, None))
+}
+
+/// The integer literal `text`: decimal, or hexadecimal, octal or binary
+/// after a `0x`, `0o` or `0b` prefix, with `_` allowed between digits and
+/// an optional type suffix such as `u8` or `i64`
+pub fn parse_int_literal(text: &str) -> Result<Literal, String> {
+    let (number, suffix) = split_suffix(text, false);
+    let radix = number.get(..2).and_then(Radix::from_prefix);
+    let digits: String = match radix {
+        Some(_) => &number[2..],
+        None => number,
+    }
+    .chars()
+    .filter(|&ch| ch != '_')
+    .collect();
+    let base = radix.map_or(10, Radix::base);
+    if digits.is_empty() {
+        return Err(format!("integer literal '{}' has no digits", text));
+    }
+    if let Some(digit) = digits.chars().find(|ch| !ch.is_digit(base)) {
+        return Err(format!(
+            "invalid digit '{}' in {} literal '{}'",
+            digit,
+            radix.map_or("decimal", Radix::name),
+            text
+        ));
+    }
+    let value = i64::from_str_radix(&digits, base).map_err(|_| {
+        format!(
+            "integer literal '{}' is too large for a 64-bit integer",
+            text
+        )
+    })?;
+    let literal = match radix {
+        Some(radix) => Literal::RadixInt(value, radix),
+        None => Literal::Int(value),
+    };
+    Ok(match suffix {
+        Some(ty) => Literal::Suffixed(Box::new(literal), ty),
+        None => literal,
+    })
+}
+
+/// The float literal `text`, with `_` allowed between digits and an
+/// optional `f32` or `f64` suffix
+pub fn parse_float_literal(text: &str) -> Result<Literal, String> {
+    let (number, suffix) = split_suffix(text, true);
+    let value = number
+        .replace('_', "")
+        .parse::<f64>()
+        .ok()
+        .filter(|_| suffix.as_ref().is_none_or(|ty| !ty.is_integer()))
+        .ok_or_else(|| format!("invalid float literal '{}'", text))?;
+    Ok(match suffix {
+        Some(ty) => Literal::Suffixed(Box::new(Literal::Float(value)), ty),
+        None => Literal::Float(value),
+    })
+}
+
+/// The array size the integer literal `text` gives
+fn array_size(text: &str) -> Result<usize, String> {
+    let value = parse_int_literal(text)?.int_value().unwrap_or_default();
+    usize::try_from(value).map_err(|_| format!("invalid array size '{}'", text))
+}
+
+/// The type a C integer type spelling stands for. `long` is named rather
+/// than resolved, for semantic analysis and codegen to size for the target
+/// platform
+fn c_integer_type(kind: &TokenKind) -> Type {
+    match kind {
+        TokenKind::Long => Type::Ident(Ident::new("long")),
+        TokenKind::ULong => Type::Ident(Ident::new("unsigned long")),
+        TokenKind::I8 => Type::Primitive(PrimitiveType::I8),
+        TokenKind::U8 => Type::Primitive(PrimitiveType::U8),
+        TokenKind::I16 => Type::Primitive(PrimitiveType::I16),
+        TokenKind::U16 => Type::Primitive(PrimitiveType::U16),
+        TokenKind::I64 => Type::Primitive(PrimitiveType::I64),
+        TokenKind::U64 => Type::Primitive(PrimitiveType::U64),
+        TokenKind::U32 => Type::Primitive(PrimitiveType::U32),
+        _ => Type::Primitive(PrimitiveType::Int),
+    }
+}
+
+/// The operator a compound assignment token stands for (`AddAssign` for `+=`)
+fn compound_assign_op(kind: &TokenKind) -> Option<BinaryOp> {
+    Some(match kind {
+        TokenKind::PlusEq => BinaryOp::AddAssign,
+        TokenKind::MinusEq => BinaryOp::SubAssign,
+        TokenKind::StarEq => BinaryOp::MulAssign,
+        TokenKind::SlashEq => BinaryOp::DivAssign,
+        TokenKind::PercentEq => BinaryOp::ModAssign,
+        TokenKind::AndEq => BinaryOp::BitAndAssign,
+        TokenKind::OrEq => BinaryOp::BitOrAssign,
+        TokenKind::XorEq => BinaryOp::BitXorAssign,
+        TokenKind::ShlEq => BinaryOp::ShlAssign,
+        TokenKind::ShrEq => BinaryOp::ShrAssign,
+        _ => return None,
+    })
+}
+
+/// Wrap `ty` in the dimensions of a C-style array declarator, outermost
+/// first, so `int grid[2][3]` is two arrays of three ints
+fn array_of(ty: Type, sizes: Vec<usize>) -> Type {
+    sizes.into_iter().rev().fold(ty, |ty, size| Type::Array {
+        ty: Box::new(ty),
+        size: Some(size),
+    })
+}
+
+/// Concatenate two adjacent string literals; None when a byte string meets
+/// a text string
+fn concat_string_literals(left: Literal, right: Literal) -> Option<Literal> {
+    match (left, right) {
+        (Literal::RawString(a), Literal::RawString(b)) => Some(Literal::RawString(a + &b)),
+        (
+            Literal::String(a) | Literal::RawString(a),
+            Literal::String(b) | Literal::RawString(b),
+        ) => Some(Literal::String(a + &b)),
+        (
+            Literal::String(a) | Literal::RawString(a) | Literal::WideString(a),
+            Literal::String(b) | Literal::RawString(b) | Literal::WideString(b),
+        ) => Some(Literal::WideString(a + &b)),
+        (Literal::ByteString(mut a), Literal::ByteString(b)) => {
+            a.extend(b);
+            Some(Literal::ByteString(a))
+        }
+        _ => None,
+    }
+}
+
+/// The part of a file that parsed, with the syntax errors that cut it short
+///
+/// Editors and other tools keep working from `file` while the source is
+/// mid-edit: its items still provide symbols and completion even though the
+/// file as a whole does not parse.
+#[derive(Debug, Clone)]
+pub struct PartialFile {
+    /// Items parsed despite the errors
+    pub file: File,
+    /// Syntax errors, empty when the whole file parsed
+    pub errors: Vec<ParseError>,
+}
+
+impl PartialFile {
+    /// The complete file, or the first error when parsing failed
+    pub fn into_result(self) -> Result<File, ParseError> {
+        match self.errors.into_iter().next() {
+            Some(error) => Err(error),
+            None => Ok(self.file),
+        }
+    }
+
+    /// The complete file, or every error when parsing failed
+    pub fn into_file(self) -> Result<File, Vec<ParseError>> {
+        if self.errors.is_empty() {
+            Ok(self.file)
+        } else {
+            Err(self.errors)
+        }
+    }
+}
 
 /// Parser for Crusty source code
 pub struct Parser<'a> {
     lexer: Lexer<'a>,
     current_token: Token,
     /// Token buffer for lookahead (stores peeked tokens)
-    token_buffer: Vec<Token>,
+    token_buffer: VecDeque<Token>,
+    /// Current nesting depth of blocks, expressions and types
+    depth: usize,
+    /// Binary operators chained above the operand being parsed
+    operators: usize,
     /// Registry of macro names to their delimiter types
     macro_registry: HashMap<String, MacroDelimiter>,
+    /// Values of the `#define` constants and `const` locals in scope, for
+    /// the constant expressions of array sizes and enum values
+    constants: HashMap<String, ConstValue>,
+    /// Syntax errors recovered from so far, in source order
+    errors: Vec<ParseError>,
+    /// Set once recovery gives up, after a lexical error or once
+    /// `MAX_PARSE_ERRORS` errors are recorded
+    halted: bool,
 }
 
 impl<'a> Parser<'a> {
@@ -38,16 +254,21 @@ impl<'a> Parser<'a> {
         Ok(Self {
             lexer,
             current_token,
-            token_buffer: Vec::new(),
+            token_buffer: VecDeque::new(),
+            depth: 0,
+            operators: 0,
             macro_registry: HashMap::new(),
+            constants: HashMap::new(),
+            errors: Vec::new(),
+            halted: false,
         })
     }
 
     /// Advance to the next token
     fn advance(&mut self) -> Result<(), ParseError> {
         // If we have buffered tokens, use them first
-        if !self.token_buffer.is_empty() {
-            self.current_token = self.token_buffer.remove(0);
+        if let Some(token) = self.token_buffer.pop_front() {
+            self.current_token = token;
         } else {
             self.current_token = self
                 .lexer
@@ -66,7 +287,7 @@ impl<'a> Parser<'a> {
                 .lexer
                 .next_token()
                 .map_err(|e| ParseError::new(e.span, e.message, vec![], "lexical error"))?;
-            self.token_buffer.push(token);
+            self.token_buffer.push_back(token);
         }
 
         if n == 0 {
@@ -78,6 +299,57 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Run `parse` one nesting level deeper, failing with a diagnostic
+    /// instead of recursing past `MAX_NESTING_DEPTH`
+    fn nested<T>(
+        &mut self,
+        parse: fn(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<T, ParseError> {
+        if self.depth >= MAX_NESTING_DEPTH {
+            return Err(self.nesting_error());
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// Count one more operator in the chain being parsed, failing with a
+    /// diagnostic past `MAX_OPERATOR_DEPTH`
+    fn chain_operator(&mut self) -> Result<(), ParseError> {
+        if self.operators >= MAX_OPERATOR_DEPTH {
+            return Err(ParseError::new(
+                self.current_token.span,
+                format!(
+                    "expression chains more than {} operators; split it into smaller expressions",
+                    MAX_OPERATOR_DEPTH
+                ),
+                vec![],
+                self.found(),
+            ));
+        }
+        self.operators += 1;
+        Ok(())
+    }
+
+    /// The current token as errors name what was found: its source text in
+    /// quotes, as rustc shows it
+    fn found(&self) -> String {
+        match self.current_token.kind {
+            TokenKind::Eof => "end of file".to_string(),
+            _ => format!("'{}'", self.current_token.text),
+        }
+    }
+
+    fn nesting_error(&self) -> ParseError {
+        ParseError::new(
+            self.current_token.span,
+            format!("nesting exceeds the limit of {} levels", MAX_NESTING_DEPTH),
+            vec![],
+            self.found(),
+        )
+    }
+
     /// Expect a specific token kind and consume it
     fn expect(&mut self, expected: TokenKind) -> Result<Token, ParseError> {
         if std::mem::discriminant(&self.current_token.kind) == std::mem::discriminant(&expected) {
@@ -88,11 +360,12 @@ impl<'a> Parser<'a> {
             Err(ParseError::new(
                 self.current_token.span,
                 format!(
-                    "expected {:?}, found {:?}",
-                    expected, self.current_token.kind
+                    "expected {}, found {}",
+                    expected.description(),
+                    self.found()
                 ),
-                vec![format!("{:?}", expected)],
-                format!("{:?}", self.current_token.kind),
+                vec![expected.description()],
+                self.found(),
             ))
         }
     }
@@ -117,6 +390,14 @@ impl<'a> Parser<'a> {
                 | TokenKind::I64
                 | TokenKind::U32
                 | TokenKind::U64
+                | TokenKind::I8
+                | TokenKind::I16
+                | TokenKind::Isize
+                | TokenKind::U8
+                | TokenKind::U16
+                | TokenKind::Usize
+                | TokenKind::Long
+                | TokenKind::ULong
                 | TokenKind::Float
                 | TokenKind::F32
                 | TokenKind::F64
@@ -127,9 +408,40 @@ impl<'a> Parser<'a> {
                 | TokenKind::Star // For pointer types like *int
                 | TokenKind::BitAnd // For reference types like &int
                 | TokenKind::Var // For mutable references like var &int
+                | TokenKind::Volatile // For volatile pointees like volatile u32*
+                | TokenKind::Const // For const pointees like const char*
         )
     }
 
+    /// Whether the token `n` tokens ahead is a `restrict` qualifier: one of
+    /// its spellings followed by the name it qualifies
+    fn restrict_at(&mut self, n: usize) -> Result<bool, ParseError> {
+        let qualifier = matches!(
+            self.peek_ahead(n)?,
+            Some(Token { kind: TokenKind::Ident(word), .. }) if RESTRICT.contains(&word.as_str())
+        );
+        Ok(qualifier
+            && matches!(
+                self.peek_ahead(n + 1)?,
+                Some(Token {
+                    kind: TokenKind::Ident(_),
+                    ..
+                })
+            ))
+    }
+
+    /// Whether a `const` starts a pointer-to-const declaration such as
+    /// `const char* name = ...;` rather than a constant
+    fn at_pointer_to_const(&mut self) -> Result<bool, ParseError> {
+        Ok(matches!(
+            self.peek_ahead(2)?,
+            Some(Token {
+                kind: TokenKind::Star,
+                ..
+            })
+        ))
+    }
+
     /// Check if current position looks like a variable declaration (Type name = value;)
     /// Uses lookahead to distinguish from expressions like int(x) or int + 5
     /// Returns true if pattern matches: Type Identifier '='
@@ -147,6 +459,14 @@ impl<'a> Parser<'a> {
                 | TokenKind::I64
                 | TokenKind::U32
                 | TokenKind::U64
+                | TokenKind::I8
+                | TokenKind::I16
+                | TokenKind::Isize
+                | TokenKind::U8
+                | TokenKind::U16
+                | TokenKind::Usize
+                | TokenKind::Long
+                | TokenKind::ULong
                 | TokenKind::Float
                 | TokenKind::F32
                 | TokenKind::F64
@@ -169,14 +489,18 @@ impl<'a> Parser<'a> {
 
         // Look ahead to see what comes after the type
         // Pattern: Type Identifier '='
-        // Need to handle pointer types: int* ptr = ...
-        let mut lookahead_offset = 1;
+        // Need to handle array and pointer types: int[N] arr = ..., int* ptr = ...
+        let Some(mut lookahead_offset) = self.skip_array_suffix(1)? else {
+            return Ok(false);
+        };
 
         // Skip pointer/reference modifiers
         loop {
             let next_token = self.peek_ahead(lookahead_offset)?;
             if let Some(token) = next_token {
-                if matches!(token.kind, TokenKind::Star | TokenKind::BitAnd) {
+                if matches!(token.kind, TokenKind::Star | TokenKind::BitAnd)
+                    || self.restrict_at(lookahead_offset)?
+                {
                     lookahead_offset += 1;
                     continue;
                 }
@@ -195,8 +519,14 @@ impl<'a> Parser<'a> {
                 return Ok(false);
             }
 
+            // Skip a C-style array declarator: char buf[8] = ...
+            let Some(offset) = self.skip_array_suffix(lookahead_offset + 1)? else {
+                return Ok(false);
+            };
+            lookahead_offset = offset;
+
             // Check if there's an '=' after the identifier
-            let token_after_ident = self.peek_ahead(lookahead_offset + 1)?;
+            let token_after_ident = self.peek_ahead(lookahead_offset)?;
             if let Some(token) = token_after_ident {
                 return Ok(matches!(token.kind, TokenKind::Assign));
             }
@@ -205,24 +535,220 @@ impl<'a> Parser<'a> {
         Ok(false)
     }
 
+    /// Offset of the token after the array sizes starting `offset` tokens
+    /// ahead, as `[N * 2][4]`, or None when a bracket is never closed
+    fn skip_array_suffix(&mut self, mut offset: usize) -> Result<Option<usize>, ParseError> {
+        while self.peek_ahead(offset)?.map(|token| token.kind) == Some(TokenKind::LBracket) {
+            // The size may be a constant expression: char buf[N * 2] = ...
+            let mut depth = 0;
+            loop {
+                match self.peek_ahead(offset)?.map(|token| token.kind) {
+                    Some(TokenKind::LBracket) => depth += 1,
+                    Some(TokenKind::RBracket) => depth -= 1,
+                    Some(TokenKind::Eof | TokenKind::Semicolon) | None => return Ok(None),
+                    _ => {}
+                }
+                offset += 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+        }
+        Ok(Some(offset))
+    }
+
     /// Check if we're at end of file
     fn is_at_end(&self) -> bool {
         matches!(self.current_token.kind, TokenKind::Eof)
     }
 
-    /// Parse a complete source file into a File AST
+    /// Parse a complete source file into a File AST, or return its first
+    /// syntax error
     pub fn parse_file(&mut self) -> Result<File, ParseError> {
+        self.parse_file_partial().into_result()
+    }
+
+    /// Parse a source file, recovering from syntax errors to report them all
+    ///
+    /// After an error inside a block the parser skips to the end of the
+    /// statement, past its `;` or its own block, or to the `}` closing the
+    /// block, and goes on with the next statement. After an error elsewhere
+    /// in an item it skips to the end of the item, or to the next token that
+    /// starts one. The items that parsed are returned alongside the errors;
+    /// an item an error falls inside is dropped. Recovery stops at a
+    /// lexical error or after `MAX_PARSE_ERRORS` errors.
+    pub fn parse_file_partial(&mut self) -> PartialFile {
+        // A nesting level costs a dozen or more stack frames
+        crate::utils::on_deep_stack("crusty-parser", || self.parse_items())
+    }
+
+    /// Parse the source as a single expression, such as the expansion of a
+    /// macro use
+    pub fn parse_standalone_expression(&mut self) -> Result<Expression, ParseError> {
+        let expr = self.parse_expression()?;
+        if let Some(error) = self.errors.first() {
+            return Err(error.clone());
+        }
+        if !self.is_at_end() {
+            return Err(ParseError::new(
+                self.current_token.span,
+                "unexpected tokens after expression",
+                vec!["end of expression".to_string()],
+                self.found(),
+            ));
+        }
+        Ok(expr)
+    }
+
+    fn parse_items(&mut self) -> PartialFile {
         let mut items = Vec::new();
-        let doc_comments = Vec::new();
 
         while !self.is_at_end() {
-            items.push(self.parse_item()?);
+            let start = self.current_token.span.start;
+            let recorded = self.errors.len();
+            match self.parse_item() {
+                Ok(item) if self.errors.len() == recorded => items.push(item),
+                Ok(_) => {}
+                Err(error) => {
+                    if self
+                        .recover(error, |parser| parser.skip_item(start))
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            }
+            if self.halted {
+                break;
+            }
         }
 
-        Ok(File {
-            items,
-            doc_comments,
-        })
+        PartialFile {
+            file: File {
+                items,
+                doc_comments: Vec::new(),
+            },
+            errors: std::mem::take(&mut self.errors),
+        }
+    }
+
+    /// Record `error` and skip past the construct it is in with `skip`
+    ///
+    /// Returns an error instead when recovery has given up, which the
+    /// callers pass on until `parse_items` stops; that error is already
+    /// recorded.
+    fn recover(
+        &mut self,
+        error: ParseError,
+        skip: impl FnOnce(&mut Self) -> Result<(), ParseError>,
+    ) -> Result<(), ParseError> {
+        if !self.halted {
+            self.errors.push(error);
+            if self.errors.len() >= MAX_PARSE_ERRORS {
+                self.halted = true;
+            } else if let Err(error) = skip(self) {
+                self.errors.push(error);
+                self.halted = true;
+            }
+        }
+        match self.errors.last() {
+            Some(error) if self.halted => Err(error.clone()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Skip the rest of a statement that failed to parse: through its `;`
+    /// or the block it opened, or up to the `}` closing the enclosing block
+    fn skip_statement(&mut self) -> Result<(), ParseError> {
+        let mut depth = 0;
+        loop {
+            match self.current_token.kind {
+                TokenKind::Eof => return Ok(()),
+                TokenKind::Semicolon if depth == 0 => return self.advance(),
+                TokenKind::LBrace => depth += 1,
+                TokenKind::RBrace if depth == 0 => return Ok(()),
+                TokenKind::RBrace if depth == 1 => return self.advance(),
+                TokenKind::RBrace => depth -= 1,
+                _ => {}
+            }
+            self.advance()?;
+        }
+    }
+
+    /// Skip the rest of an item that failed to parse, which began at
+    /// `start`: through its `;` or its closing `}`, or up to the next token
+    /// that starts an item
+    fn skip_item(&mut self, start: Position) -> Result<(), ParseError> {
+        let mut depth = 0;
+        loop {
+            if depth == 0 && self.current_token.span.start != start && self.at_item_start()? {
+                return Ok(());
+            }
+            match self.current_token.kind {
+                TokenKind::Eof => return Ok(()),
+                TokenKind::Semicolon if depth == 0 => return self.advance(),
+                TokenKind::LBrace => depth += 1,
+                TokenKind::RBrace if depth <= 1 => return self.advance(),
+                TokenKind::RBrace => depth -= 1,
+                _ => {}
+            }
+            self.advance()?;
+        }
+    }
+
+    /// Whether the current token starts an item: a keyword only items start
+    /// with, a directive or attribute, or the return type of a function
+    /// followed by its name and `(`
+    fn at_item_start(&mut self) -> Result<bool, ParseError> {
+        if self.at_item_keyword(ITEM_KEYWORDS)? {
+            return Ok(true);
+        }
+        match self.current_token.kind {
+            TokenKind::Struct
+            | TokenKind::Enum
+            | TokenKind::Typedef
+            | TokenKind::Extern
+            | TokenKind::Static
+            | TokenKind::Hash => Ok(true),
+            TokenKind::Star | TokenKind::BitAnd | TokenKind::Var | TokenKind::Volatile => Ok(false),
+            _ if self.is_type_token() => Ok(matches!(
+                self.peek_ahead(1)?,
+                Some(Token {
+                    kind: TokenKind::Ident(_),
+                    ..
+                })
+            ) && matches!(
+                self.peek_ahead(2)?,
+                Some(Token {
+                    kind: TokenKind::LParen,
+                    ..
+                })
+            )),
+            _ => Ok(false),
+        }
+    }
+
+    /// Whether the current token is one of `words`, contextual keywords
+    /// that start an item only where a name follows them, so they remain
+    /// free to name variables and functions
+    fn at_item_keyword(&mut self, words: &[&str]) -> Result<bool, ParseError> {
+        if !matches!(&self.current_token.kind, TokenKind::Ident(word) if words.contains(&word.as_str()))
+        {
+            return Ok(false);
+        }
+        Ok(matches!(
+            self.peek_ahead(1)?,
+            Some(Token {
+                kind: TokenKind::Ident(_),
+                ..
+            })
+        ) && !matches!(
+            self.peek_ahead(2)?,
+            Some(Token {
+                kind: TokenKind::LParen,
+                ..
+            })
+        ))
     }
 
     /// Parse a top-level item
@@ -230,8 +756,15 @@ impl<'a> Parser<'a> {
         // Parse attributes first (they start with #[)
         let attributes = self.parse_attributes()?;
 
-        // Check for #define directive (starts with # but not #[)
+        // Check for #import/#export or #define directives (start with # but not #[)
         if self.check(&TokenKind::Hash) {
+            if let Some(Token {
+                kind: TokenKind::Ident(directive),
+                ..
+            }) = self.peek_ahead(1)?
+            {
+                return self.parse_module_directive(&directive);
+            }
             return self.parse_define();
         }
 
@@ -243,6 +776,19 @@ impl<'a> Parser<'a> {
             false
         };
 
+        // Functions returning a struct, enum or type parameter start with
+        // an identifier
+        if self.looks_like_named_return_function()? {
+            return self.parse_function(is_static, attributes);
+        }
+
+        if self.at_item_keyword(&["trait", "interface"])? {
+            return self.parse_trait(attributes);
+        }
+        if self.at_item_keyword(&["impl"])? {
+            return self.parse_impl();
+        }
+
         // Check for type keywords that indicate function declarations
         match &self.current_token.kind {
             TokenKind::Int
@@ -250,6 +796,14 @@ impl<'a> Parser<'a> {
             | TokenKind::I64
             | TokenKind::U32
             | TokenKind::U64
+            | TokenKind::I8
+            | TokenKind::I16
+            | TokenKind::Isize
+            | TokenKind::U8
+            | TokenKind::U16
+            | TokenKind::Usize
+            | TokenKind::Long
+            | TokenKind::ULong
             | TokenKind::Float
             | TokenKind::F32
             | TokenKind::F64
@@ -259,6 +813,10 @@ impl<'a> Parser<'a> {
             TokenKind::Struct => self.parse_struct_with_attributes(attributes),
             TokenKind::Enum => self.parse_enum_with_attributes(attributes),
             TokenKind::Typedef => self.parse_typedef(is_static),
+            TokenKind::Extern => self.parse_extern(),
+            TokenKind::Ident(name) if name == STATIC_ASSERT => {
+                Ok(Item::StaticAssert(self.parse_static_assert()?))
+            }
             _ => Err(ParseError::new(
                 self.current_token.span,
                 "expected item declaration",
@@ -266,14 +824,130 @@ impl<'a> Parser<'a> {
                     "function".to_string(),
                     "struct".to_string(),
                     "enum".to_string(),
+                    "trait".to_string(),
+                    "impl".to_string(),
                     "typedef".to_string(),
+                    "extern".to_string(),
                     "#define".to_string(),
                 ],
-                format!("{:?}", self.current_token.kind),
+                self.found(),
             )),
         }
     }
 
+    /// Parse an extern block of foreign function declarations:
+    /// `extern "C" { int puts(char* s); }`
+    fn parse_extern(&mut self) -> Result<Item, ParseError> {
+        self.expect(TokenKind::Extern)?;
+
+        // The ABI string is optional; Rust defaults it to "C"
+        let abi = match &self.current_token.kind {
+            TokenKind::StringLiteral(abi) => {
+                let abi = abi.clone();
+                self.advance()?;
+                Some(abi)
+            }
+            _ => None,
+        };
+
+        self.expect(TokenKind::LBrace)?;
+        let mut functions = Vec::new();
+        while !self.check(&TokenKind::RBrace) && !self.is_at_end() {
+            functions.push(self.parse_foreign_function()?);
+        }
+        self.expect(TokenKind::RBrace)?;
+
+        Ok(Item::Extern(Extern { abi, functions }))
+    }
+
+    /// Parse the `...` after the element type of a variadic parameter, as in
+    /// `int... values`, if present: the parameter's type, and whether it is
+    /// variadic
+    fn parse_variadic_suffix(&mut self, element: Type) -> Result<(Type, bool), ParseError> {
+        // `...` lexes as `..` followed by `.`
+        if !self.check(&TokenKind::DotDot) {
+            return Ok((element, false));
+        }
+        self.advance()?;
+        self.expect(TokenKind::Dot)?;
+        Ok((variadic_type(element), true))
+    }
+
+    /// Parse a function signature ending in `;`, with an optional trailing
+    /// `...` for C variadic functions
+    fn parse_foreign_function(&mut self) -> Result<ForeignFunction, ParseError> {
+        let return_type = match self.parse_type()? {
+            Type::Primitive(PrimitiveType::Void) => None,
+            ty => Some(ty),
+        };
+
+        let name = match &self.current_token.kind {
+            TokenKind::Ident(name) => {
+                let ident = Ident::spanned(name.clone(), self.current_token.span);
+                self.advance()?;
+                ident
+            }
+            _ => {
+                return Err(ParseError::new(
+                    self.current_token.span,
+                    "expected function name",
+                    vec!["identifier".to_string()],
+                    self.found(),
+                ));
+            }
+        };
+
+        self.expect(TokenKind::LParen)?;
+        let mut params = Vec::new();
+        let mut variadic = false;
+        while !self.check(&TokenKind::RParen) {
+            // `...` lexes as `..` followed by `.`
+            if self.check(&TokenKind::DotDot) {
+                self.advance()?;
+                self.expect(TokenKind::Dot)?;
+                variadic = true;
+                break;
+            }
+
+            let ty = self.parse_type()?;
+            let name = match &self.current_token.kind {
+                TokenKind::Ident(name) => {
+                    let ident = Ident::spanned(name.clone(), self.current_token.span);
+                    self.advance()?;
+                    ident
+                }
+                _ => {
+                    return Err(ParseError::new(
+                        self.current_token.span,
+                        "expected parameter name",
+                        vec!["identifier".to_string()],
+                        self.found(),
+                    ));
+                }
+            };
+            params.push(Param {
+                name,
+                ty,
+                variadic: false,
+            });
+
+            if self.check(&TokenKind::Comma) {
+                self.advance()?;
+            } else {
+                break;
+            }
+        }
+        self.expect(TokenKind::RParen)?;
+        self.expect(TokenKind::Semicolon)?;
+
+        Ok(ForeignFunction {
+            name,
+            params,
+            return_type,
+            variadic,
+        })
+    }
+
     /// Parse attributes (#[...])
     fn parse_attributes(&mut self) -> Result<Vec<Attribute>, ParseError> {
         let mut attributes = Vec::new();
@@ -282,15 +956,7 @@ impl<'a> Parser<'a> {
             // Peek ahead to check if this is an attribute (#[) or a #define
             // We need to check the next token without consuming the #
             let is_attribute = {
-                let mut temp_lexer = Lexer {
-                    source: self.lexer.source,
-                    chars: self.lexer.source[self.lexer.position..]
-                        .char_indices()
-                        .peekable(),
-                    position: self.lexer.position,
-                    line: self.lexer.line,
-                    column: self.lexer.column,
-                };
+                let mut temp_lexer = self.lexer.clone();
 
                 // Try to read the next token
                 if let Ok(token) = temp_lexer.next_token() {
@@ -311,7 +977,7 @@ impl<'a> Parser<'a> {
             // Parse attribute name
             let name = match &self.current_token.kind {
                 TokenKind::Ident(n) => {
-                    let ident = Ident::new(n.clone());
+                    let ident = Ident::spanned(n.clone(), self.current_token.span);
                     self.advance()?;
                     ident
                 }
@@ -320,7 +986,7 @@ impl<'a> Parser<'a> {
                         self.current_token.span,
                         "expected attribute name",
                         vec!["identifier".to_string()],
-                        format!("{:?}", self.current_token.kind),
+                        self.found(),
                     ));
                 }
             };
@@ -356,7 +1022,7 @@ impl<'a> Parser<'a> {
     fn parse_attribute_arg(&mut self) -> Result<AttributeArg, ParseError> {
         match &self.current_token.kind {
             TokenKind::Ident(n) => {
-                let ident = Ident::new(n.clone());
+                let ident = Ident::spanned(n.clone(), self.current_token.span);
                 self.advance()?;
 
                 // Check for name = value syntax
@@ -364,6 +1030,20 @@ impl<'a> Parser<'a> {
                     self.advance()?;
                     let value = self.parse_attribute_literal()?;
                     Ok(AttributeArg::NameValue { name: ident, value })
+                } else if self.check(&TokenKind::LParen) {
+                    // Nested list: all(unix, target_arch = "x86_64")
+                    self.advance()?;
+                    let mut args = Vec::new();
+                    while !self.check(&TokenKind::RParen) {
+                        args.push(self.parse_attribute_arg()?);
+                        if self.check(&TokenKind::Comma) {
+                            self.advance()?;
+                        } else {
+                            break;
+                        }
+                    }
+                    self.expect(TokenKind::RParen)?;
+                    Ok(AttributeArg::List { name: ident, args })
                 } else {
                     Ok(AttributeArg::Ident(ident))
                 }
@@ -379,16 +1059,11 @@ impl<'a> Parser<'a> {
     fn parse_attribute_literal(&mut self) -> Result<Literal, ParseError> {
         match &self.current_token.kind {
             TokenKind::IntLiteral(s) => {
-                let val = s.parse::<i64>().map_err(|_| {
-                    ParseError::new(
-                        self.current_token.span,
-                        "invalid integer literal",
-                        vec![],
-                        s.clone(),
-                    )
+                let literal = parse_int_literal(s).map_err(|message| {
+                    ParseError::new(self.current_token.span, message, vec![], s.clone())
                 })?;
                 self.advance()?;
-                Ok(Literal::Int(val))
+                Ok(literal)
             }
             TokenKind::StringLiteral(s) => {
                 let val = s.clone();
@@ -408,7 +1083,7 @@ impl<'a> Parser<'a> {
                     "string".to_string(),
                     "bool".to_string(),
                 ],
-                format!("{:?}", self.current_token.kind),
+                self.found(),
             )),
         }
     }
@@ -423,7 +1098,7 @@ impl<'a> Parser<'a> {
         // Parse struct name
         let name = match &self.current_token.kind {
             TokenKind::Ident(name) => {
-                let ident = Ident::new(name.clone());
+                let ident = Ident::spanned(name.clone(), self.current_token.span);
                 self.advance()?;
                 ident
             }
@@ -432,11 +1107,18 @@ impl<'a> Parser<'a> {
                     self.current_token.span,
                     "expected struct name",
                     vec!["identifier".to_string()],
-                    format!("{:?}", self.current_token.kind),
+                    self.found(),
                 ));
             }
         };
 
+        // Type parameters of a generic struct: struct Pair(T) { ... }
+        let generics = if self.check(&TokenKind::LParen) {
+            self.parse_type_param_list()?
+        } else {
+            Vec::new()
+        };
+
         self.expect(TokenKind::LBrace)?;
 
         let mut fields = Vec::new();
@@ -446,10 +1128,15 @@ impl<'a> Parser<'a> {
             // Parse field/method attributes
             let item_attributes = self.parse_attributes()?;
 
+            let visibility = self.parse_member_visibility()?;
+
             // Check if this is a method (has parentheses after identifier) or a field
             if self.is_method_definition()? {
                 let mut method = self.parse_struct_method()?;
                 method.attributes = item_attributes;
+                if let Some(visibility) = visibility {
+                    method.visibility = visibility;
+                }
                 methods.push(method);
             } else {
                 // Parse as field
@@ -457,7 +1144,7 @@ impl<'a> Parser<'a> {
 
                 let field_name = match &self.current_token.kind {
                     TokenKind::Ident(name) => {
-                        let ident = Ident::new(name.clone());
+                        let ident = Ident::spanned(name.clone(), self.current_token.span);
                         self.advance()?;
                         ident
                     }
@@ -466,7 +1153,7 @@ impl<'a> Parser<'a> {
                             self.current_token.span,
                             "expected field name",
                             vec!["identifier".to_string()],
-                            format!("{:?}", self.current_token.kind),
+                            self.found(),
                         ));
                     }
                 };
@@ -474,7 +1161,7 @@ impl<'a> Parser<'a> {
                 self.expect(TokenKind::Semicolon)?;
 
                 fields.push(Field {
-                    visibility: Visibility::Public,
+                    visibility: visibility.unwrap_or(Visibility::Public),
                     name: field_name,
                     ty: field_type,
                     doc_comments: Vec::new(),
@@ -488,6 +1175,7 @@ impl<'a> Parser<'a> {
         Ok(Item::Struct(Struct {
             visibility: Visibility::Public,
             name,
+            generics,
             fields,
             methods,
             doc_comments: Vec::new(),
@@ -505,7 +1193,7 @@ impl<'a> Parser<'a> {
         // Parse enum name
         let name = match &self.current_token.kind {
             TokenKind::Ident(name) => {
-                let ident = Ident::new(name.clone());
+                let ident = Ident::spanned(name.clone(), self.current_token.span);
                 self.advance()?;
                 ident
             }
@@ -514,7 +1202,7 @@ impl<'a> Parser<'a> {
                     self.current_token.span,
                     "expected enum name",
                     vec!["identifier".to_string()],
-                    format!("{:?}", self.current_token.kind),
+                    self.found(),
                 ));
             }
         };
@@ -528,7 +1216,7 @@ impl<'a> Parser<'a> {
             // Parse variant name
             let variant_name = match &self.current_token.kind {
                 TokenKind::Ident(name) => {
-                    let ident = Ident::new(name.clone());
+                    let ident = Ident::spanned(name.clone(), self.current_token.span);
                     self.advance()?;
                     ident
                 }
@@ -537,37 +1225,19 @@ impl<'a> Parser<'a> {
                         self.current_token.span,
                         "expected enum variant name",
                         vec!["identifier".to_string()],
-                        format!("{:?}", self.current_token.kind),
+                        self.found(),
                     ));
                 }
             };
 
+            let fields = self.parse_variant_fields()?;
+
             // Check for explicit value
-            let value = if self.check(&TokenKind::Assign) {
+            let value = if self.check(&TokenKind::Assign) && fields.is_empty() {
                 self.advance()?;
-                match &self.current_token.kind {
-                    TokenKind::IntLiteral(s) => {
-                        let val = s.parse::<i64>().map_err(|_| {
-                            ParseError::new(
-                                self.current_token.span,
-                                "invalid integer literal",
-                                vec![],
-                                s.clone(),
-                            )
-                        })?;
-                        self.advance()?;
-                        next_value = val + 1;
-                        Some(val)
-                    }
-                    _ => {
-                        return Err(ParseError::new(
-                            self.current_token.span,
-                            "expected integer literal",
-                            vec!["integer".to_string()],
-                            format!("{:?}", self.current_token.kind),
-                        ));
-                    }
-                }
+                let val = self.parse_enum_value(&variants)?;
+                next_value = val + 1;
+                Some(val)
             } else {
                 let val = next_value;
                 next_value += 1;
@@ -577,6 +1247,7 @@ impl<'a> Parser<'a> {
             variants.push(EnumVariant {
                 name: variant_name,
                 value,
+                fields,
             });
 
             if self.check(&TokenKind::Comma) {
@@ -597,24 +1268,178 @@ impl<'a> Parser<'a> {
         }))
     }
 
+    /// Parse the payload of a tagged-union variant, `(Type name, ...)`, or
+    /// nothing for a plain variant
+    fn parse_variant_fields(&mut self) -> Result<Vec<Param>, ParseError> {
+        let mut fields = Vec::new();
+        if !self.check(&TokenKind::LParen) {
+            return Ok(fields);
+        }
+        self.advance()?;
+        while !self.check(&TokenKind::RParen) {
+            let ty = self.parse_type()?;
+            let name = match &self.current_token.kind {
+                TokenKind::Ident(name) => {
+                    let ident = Ident::spanned(name.clone(), self.current_token.span);
+                    self.advance()?;
+                    ident
+                }
+                _ => {
+                    return Err(ParseError::new(
+                        self.current_token.span,
+                        "expected variant field name",
+                        vec!["identifier".to_string()],
+                        self.found(),
+                    ));
+                }
+            };
+            fields.push(Param {
+                name,
+                ty,
+                variadic: false,
+            });
+
+            if self.check(&TokenKind::Comma) {
+                self.advance()?;
+            } else {
+                break;
+            }
+        }
+        self.expect(TokenKind::RParen)?;
+        Ok(fields)
+    }
+
     /// Parse a function declaration
+    /// Parse the type parameter list of a generic function, `(T, U)` in
+    /// `T pick(T, U)(T a, U b)`; empty when the name is followed directly by
+    /// the parameter list
+    fn parse_generic_params(&mut self) -> Result<Vec<Ident>, ParseError> {
+        if !self.looks_like_generic_params()? {
+            return Ok(Vec::new());
+        }
+        self.parse_type_param_list()
+    }
+
+    /// Parse a parenthesized list of type parameter names: `(T, U)`
+    fn parse_type_param_list(&mut self) -> Result<Vec<Ident>, ParseError> {
+        self.expect(TokenKind::LParen)?;
+        let mut generics = Vec::new();
+        loop {
+            if let TokenKind::Ident(name) = &self.current_token.kind {
+                generics.push(Ident::spanned(name.clone(), self.current_token.span));
+                self.advance()?;
+            }
+            if self.check(&TokenKind::Comma) {
+                self.advance()?;
+            } else {
+                break;
+            }
+        }
+        self.expect(TokenKind::RParen)?;
+        Ok(generics)
+    }
+
+    /// Check for `(Ident, ...)` followed by a second `(`, which only a type
+    /// parameter list can be: a parameter list names each parameter after
+    /// its type
+    fn looks_like_generic_params(&mut self) -> Result<bool, ParseError> {
+        if !self.check(&TokenKind::LParen) {
+            return Ok(false);
+        }
+        let mut offset = 1;
+        loop {
+            match self.peek_ahead(offset)?.map(|token| token.kind) {
+                Some(TokenKind::Ident(_)) => offset += 1,
+                _ => return Ok(false),
+            }
+            match self.peek_ahead(offset)?.map(|token| token.kind) {
+                Some(TokenKind::Comma) => offset += 1,
+                Some(TokenKind::RParen) => break,
+                _ => return Ok(false),
+            }
+        }
+        Ok(self.peek_ahead(offset + 1)?.map(|token| token.kind) == Some(TokenKind::LParen))
+    }
+
+    /// Check for a function returning a named type, `Point origin(...)` or
+    /// `T max(T)(...)`: a type name, pointer or reference markers and an
+    /// optional `?`, then the function name and its opening parenthesis
+    fn looks_like_named_return_function(&mut self) -> Result<bool, ParseError> {
+        if !matches!(self.current_token.kind, TokenKind::Ident(_)) {
+            return Ok(false);
+        }
+        let Some(mut offset) = self.skip_type_arguments_ahead(1)? else {
+            return Ok(false);
+        };
+        while matches!(
+            self.peek_ahead(offset)?.map(|token| token.kind),
+            Some(TokenKind::Star | TokenKind::BitAnd)
+        ) {
+            offset += 1;
+        }
+        if self.peek_ahead(offset)?.map(|token| token.kind) == Some(TokenKind::Question) {
+            offset += 1;
+        }
+        Ok(matches!(
+            self.peek_ahead(offset)?.map(|token| token.kind),
+            Some(TokenKind::Ident(_))
+        ) && self.peek_ahead(offset + 1)?.map(|token| token.kind) == Some(TokenKind::LParen))
+    }
+
+    /// The offset just past the type arguments `<...>` starting `offset`
+    /// tokens ahead, `offset` itself when there are none, or `None` when
+    /// the `<` is never closed
+    fn skip_type_arguments_ahead(&mut self, offset: usize) -> Result<Option<usize>, ParseError> {
+        if self.peek_ahead(offset)?.map(|token| token.kind) != Some(TokenKind::Lt) {
+            return Ok(Some(offset));
+        }
+        let mut depth = 0;
+        let mut offset = offset;
+        loop {
+            match self.peek_ahead(offset)?.map(|token| token.kind) {
+                Some(TokenKind::Lt) => depth += 1,
+                Some(TokenKind::Gt) => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(Some(offset + 1));
+                    }
+                }
+                Some(
+                    TokenKind::Semicolon | TokenKind::LBrace | TokenKind::RBrace | TokenKind::Eof,
+                )
+                | None => return Ok(None),
+                _ => {}
+            }
+            offset += 1;
+        }
+    }
+
+    /// Parse the return type of a function or method: `None` for `void`,
+    /// and `Type::Fallible` for `T?`, which returns a `Result`
+    fn parse_return_type(&mut self) -> Result<Option<Type>, ParseError> {
+        if self.check(&TokenKind::Void) {
+            self.advance()?;
+            return Ok(None);
+        }
+        let ty = self.parse_type()?;
+        if self.check(&TokenKind::Question) {
+            self.advance()?;
+            return Ok(Some(Type::Fallible { ty: Box::new(ty) }));
+        }
+        Ok(Some(ty))
+    }
+
     fn parse_function(
         &mut self,
         is_static: bool,
         attributes: Vec<Attribute>,
     ) -> Result<Item, ParseError> {
-        // Parse return type
-        let return_type = if self.check(&TokenKind::Void) {
-            self.advance()?;
-            None
-        } else {
-            Some(self.parse_type()?)
-        };
+        let return_type = self.parse_return_type()?;
 
         // Parse function name
         let name = match &self.current_token.kind {
             TokenKind::Ident(name) => {
-                let ident = Ident::new(name.clone());
+                let ident = Ident::spanned(name.clone(), self.current_token.span);
                 self.advance()?;
                 ident
             }
@@ -623,11 +1448,13 @@ impl<'a> Parser<'a> {
                     self.current_token.span,
                     "expected function name",
                     vec!["identifier".to_string()],
-                    format!("{:?}", self.current_token.kind),
+                    self.found(),
                 ));
             }
         };
 
+        let generics = self.parse_generic_params()?;
+
         // Parse parameter list
         self.expect(TokenKind::LParen)?;
         let mut params = Vec::new();
@@ -636,11 +1463,12 @@ impl<'a> Parser<'a> {
             loop {
                 // Parse parameter type
                 let param_type = self.parse_type()?;
+                let (param_type, variadic) = self.parse_variadic_suffix(param_type)?;
 
                 // Parse parameter name
                 let param_name = match &self.current_token.kind {
                     TokenKind::Ident(name) => {
-                        let ident = Ident::new(name.clone());
+                        let ident = Ident::spanned(name.clone(), self.current_token.span);
                         self.advance()?;
                         ident
                     }
@@ -649,7 +1477,7 @@ impl<'a> Parser<'a> {
                             self.current_token.span,
                             "expected parameter name",
                             vec!["identifier".to_string()],
-                            format!("{:?}", self.current_token.kind),
+                            self.found(),
                         ));
                     }
                 };
@@ -657,6 +1485,7 @@ impl<'a> Parser<'a> {
                 params.push(Param {
                     name: param_name,
                     ty: param_type,
+                    variadic,
                 });
 
                 if self.check(&TokenKind::Comma) {
@@ -679,6 +1508,7 @@ impl<'a> Parser<'a> {
                 Visibility::Public
             },
             name,
+            generics,
             params,
             return_type,
             body,
@@ -695,7 +1525,7 @@ impl<'a> Parser<'a> {
         // Parse struct name
         let name = match &self.current_token.kind {
             TokenKind::Ident(name) => {
-                let ident = Ident::new(name.clone());
+                let ident = Ident::spanned(name.clone(), self.current_token.span);
                 self.advance()?;
                 ident
             }
@@ -704,11 +1534,18 @@ impl<'a> Parser<'a> {
                     self.current_token.span,
                     "expected struct name",
                     vec!["identifier".to_string()],
-                    format!("{:?}", self.current_token.kind),
+                    self.found(),
                 ));
             }
         };
 
+        // Type parameters of a generic struct: struct Pair(T) { ... }
+        let generics = if self.check(&TokenKind::LParen) {
+            self.parse_type_param_list()?
+        } else {
+            Vec::new()
+        };
+
         self.expect(TokenKind::LBrace)?;
 
         let mut fields = Vec::new();
@@ -732,7 +1569,7 @@ impl<'a> Parser<'a> {
                 // Parse field name
                 let field_name = match &self.current_token.kind {
                     TokenKind::Ident(name) => {
-                        let ident = Ident::new(name.clone());
+                        let ident = Ident::spanned(name.clone(), self.current_token.span);
                         self.advance()?;
                         ident
                     }
@@ -741,7 +1578,7 @@ impl<'a> Parser<'a> {
                             self.current_token.span,
                             "expected field name",
                             vec!["identifier".to_string()],
-                            format!("{:?}", self.current_token.kind),
+                            self.found(),
                         ));
                     }
                 };
@@ -763,6 +1600,7 @@ impl<'a> Parser<'a> {
         Ok(Item::Struct(Struct {
             visibility: Visibility::Public,
             name,
+            generics,
             fields,
             methods,
             doc_comments: Vec::new(),
@@ -770,8 +1608,21 @@ impl<'a> Parser<'a> {
         }))
     }
 
+    /// Parse the `static` or `pub` before a struct field or method, which
+    /// makes it private to the struct's module or, as it is by default,
+    /// public
+    fn parse_member_visibility(&mut self) -> Result<Option<Visibility>, ParseError> {
+        let visibility = match &self.current_token.kind {
+            TokenKind::Static => Visibility::Private,
+            TokenKind::Ident(name) if name == "pub" => Visibility::Public,
+            _ => return Ok(None),
+        };
+        self.advance()?;
+        Ok(Some(visibility))
+    }
+
     /// Check if the current position is a method definition
-    fn is_method_definition(&self) -> Result<bool, ParseError> {
+    fn is_method_definition(&mut self) -> Result<bool, ParseError> {
         // A method definition looks like:
         // - return_type method_name(params) { body }
         // - void method_name(params) { body }
@@ -791,6 +1642,14 @@ impl<'a> Parser<'a> {
                 | TokenKind::I64
                 | TokenKind::U32
                 | TokenKind::U64
+                | TokenKind::I8
+                | TokenKind::I16
+                | TokenKind::Isize
+                | TokenKind::U8
+                | TokenKind::U16
+                | TokenKind::Usize
+                | TokenKind::Long
+                | TokenKind::ULong
                 | TokenKind::Float
                 | TokenKind::F32
                 | TokenKind::F64
@@ -804,36 +1663,45 @@ impl<'a> Parser<'a> {
             return Ok(false);
         }
 
-        // Create a temporary lexer for lookahead starting from current lexer position
-        let mut temp_lexer = Lexer {
-            source: self.lexer.source,
-            chars: self.lexer.source[self.lexer.position..]
-                .char_indices()
-                .peekable(),
-            position: self.lexer.position,
-            line: self.lexer.line,
-            column: self.lexer.column,
+        // The method name, after any type arguments of the return type, and
+        // then its opening parenthesis
+        let Some(offset) = self.skip_type_arguments_ahead(1)? else {
+            return Ok(false);
         };
+        Ok(matches!(
+            self.peek_ahead(offset)?.map(|token| token.kind),
+            Some(TokenKind::Ident(_))
+        ) && self.peek_ahead(offset + 1)?.map(|token| token.kind) == Some(TokenKind::LParen))
+    }
 
-        // Read the next token (should be identifier for method name)
-        if let Ok(token) = temp_lexer.next_token() {
-            if !matches!(token.kind, TokenKind::Ident(_)) {
-                return Ok(false);
-            }
-        } else {
-            return Ok(false);
-        }
+    /// Parse a method definition within a struct or trait impl
+    fn parse_struct_method(&mut self) -> Result<Function, ParseError> {
+        let (is_static, generics, signature) = self.parse_method_signature()?;
 
-        // Check for (
-        if let Ok(token) = temp_lexer.next_token() {
-            Ok(matches!(token.kind, TokenKind::LParen))
-        } else {
-            Ok(false)
-        }
+        // Parse method body
+        let body = self.parse_block()?;
+
+        Ok(Function {
+            visibility: if is_static {
+                Visibility::Private
+            } else {
+                Visibility::Public
+            },
+            name: signature.name,
+            generics,
+            params: signature.params,
+            return_type: signature.return_type,
+            body,
+            doc_comments: Vec::new(),
+            attributes: Vec::new(),
+        })
     }
 
-    /// Parse a method definition within a struct
-    fn parse_struct_method(&mut self) -> Result<Function, ParseError> {
+    /// Parse a method's signature up to its closing parenthesis, whether it
+    /// is declared `static`, and its type parameters
+    fn parse_method_signature(
+        &mut self,
+    ) -> Result<(bool, Vec<Ident>, MethodSignature), ParseError> {
         // Check for static keyword
         let is_static = if self.check(&TokenKind::Static) {
             self.advance()?;
@@ -842,18 +1710,12 @@ impl<'a> Parser<'a> {
             false
         };
 
-        // Parse return type
-        let return_type = if self.check(&TokenKind::Void) {
-            self.advance()?;
-            None
-        } else {
-            Some(self.parse_type()?)
-        };
+        let return_type = self.parse_return_type()?;
 
         // Parse method name
         let name = match &self.current_token.kind {
             TokenKind::Ident(name) => {
-                let ident = Ident::new(name.clone());
+                let ident = Ident::spanned(name.clone(), self.current_token.span);
                 self.advance()?;
                 ident
             }
@@ -862,11 +1724,13 @@ impl<'a> Parser<'a> {
                     self.current_token.span,
                     "expected method name",
                     vec!["identifier".to_string()],
-                    format!("{:?}", self.current_token.kind),
+                    self.found(),
                 ));
             }
         };
 
+        let generics = self.parse_generic_params()?;
+
         // Parse parameter list
         self.expect(TokenKind::LParen)?;
         let mut params = Vec::new();
@@ -874,14 +1738,21 @@ impl<'a> Parser<'a> {
         if !self.check(&TokenKind::RParen) {
             loop {
                 // Check for self parameter
-                if self.check(&TokenKind::Ident("self".to_string())) {
-                    let self_ident = Ident::new("self");
+                let self_reference = self.check(&TokenKind::BitAnd)
+                    && match self.peek_ahead(1)?.map(|token| token.kind) {
+                        Some(TokenKind::Mut) => true,
+                        Some(TokenKind::Ident(n)) => n == "self",
+                        _ => false,
+                    };
+                if matches!(&self.current_token.kind, TokenKind::Ident(n) if n == "self") {
+                    let self_ident = Ident::spanned("self", self.current_token.span);
                     self.advance()?;
 
                     // self parameter (immutable reference)
                     params.push(Param {
                         name: self_ident,
                         ty: Type::Ident(Ident::new("Self")),
+                        variadic: false,
                     });
                 } else if self.check(&TokenKind::Var) {
                     // var &self (mutable reference to self)
@@ -900,13 +1771,14 @@ impl<'a> Parser<'a> {
                                     ty: Box::new(Type::Ident(Ident::new("Self"))),
                                     mutable: true,
                                 },
+                                variadic: false,
                             });
                         } else {
                             return Err(ParseError::new(
                                 self.current_token.span,
                                 "expected 'self' after var &",
                                 vec!["self".to_string()],
-                                format!("{:?}", self.current_token.kind),
+                                self.found(),
                             ));
                         }
                     } else {
@@ -914,10 +1786,10 @@ impl<'a> Parser<'a> {
                             self.current_token.span,
                             "expected 'self' after var &",
                             vec!["self".to_string()],
-                            format!("{:?}", self.current_token.kind),
+                            self.found(),
                         ));
                     }
-                } else if self.check(&TokenKind::BitAnd) {
+                } else if self_reference {
                     // &self or &mut self
                     self.advance()?;
 
@@ -938,13 +1810,14 @@ impl<'a> Parser<'a> {
                                     ty: Box::new(Type::Ident(Ident::new("Self"))),
                                     mutable,
                                 },
+                                variadic: false,
                             });
                         } else {
                             return Err(ParseError::new(
                                 self.current_token.span,
                                 "expected 'self' after &",
                                 vec!["self".to_string()],
-                                format!("{:?}", self.current_token.kind),
+                                self.found(),
                             ));
                         }
                     } else {
@@ -952,16 +1825,17 @@ impl<'a> Parser<'a> {
                             self.current_token.span,
                             "expected 'self' after &",
                             vec!["self".to_string()],
-                            format!("{:?}", self.current_token.kind),
+                            self.found(),
                         ));
                     }
                 } else {
                     // Regular parameter
                     let param_type = self.parse_type()?;
+                    let (param_type, variadic) = self.parse_variadic_suffix(param_type)?;
 
                     let param_name = match &self.current_token.kind {
                         TokenKind::Ident(name) => {
-                            let ident = Ident::new(name.clone());
+                            let ident = Ident::spanned(name.clone(), self.current_token.span);
                             self.advance()?;
                             ident
                         }
@@ -970,7 +1844,7 @@ impl<'a> Parser<'a> {
                                 self.current_token.span,
                                 "expected parameter name",
                                 vec!["identifier".to_string()],
-                                format!("{:?}", self.current_token.kind),
+                                self.found(),
                             ));
                         }
                     };
@@ -978,6 +1852,7 @@ impl<'a> Parser<'a> {
                     params.push(Param {
                         name: param_name,
                         ty: param_type,
+                        variadic,
                     });
                 }
 
@@ -991,22 +1866,118 @@ impl<'a> Parser<'a> {
 
         self.expect(TokenKind::RParen)?;
 
-        // Parse method body
-        let body = self.parse_block()?;
+        Ok((
+            is_static,
+            generics,
+            MethodSignature {
+                name,
+                params,
+                return_type,
+                body: None,
+            },
+        ))
+    }
 
-        Ok(Function {
-            visibility: if is_static {
-                Visibility::Private
+    /// Parse a trait declaration: `trait Name { signature; ... }`, also
+    /// written with `interface`. A signature followed by a block instead of
+    /// `;` is a default method
+    fn parse_trait(&mut self, attributes: Vec<Attribute>) -> Result<Item, ParseError> {
+        self.advance()?;
+
+        let name = match &self.current_token.kind {
+            TokenKind::Ident(name) => {
+                let ident = Ident::spanned(name.clone(), self.current_token.span);
+                self.advance()?;
+                ident
+            }
+            _ => {
+                return Err(ParseError::new(
+                    self.current_token.span,
+                    "expected trait name",
+                    vec!["identifier".to_string()],
+                    self.found(),
+                ));
+            }
+        };
+
+        self.expect(TokenKind::LBrace)?;
+        let mut methods = Vec::new();
+        while !self.check(&TokenKind::RBrace) {
+            let start = self.current_token.span;
+            let (_, generics, mut signature) = self.parse_method_signature()?;
+            if !generics.is_empty() {
+                return Err(ParseError::new(
+                    start,
+                    format!("trait method '{}' cannot be generic", signature.name.name),
+                    Vec::new(),
+                    format!("'{}'", generics[0].name),
+                ));
+            }
+            if self.check(&TokenKind::LBrace) {
+                signature.body = Some(self.parse_block()?);
             } else {
-                Visibility::Public
-            },
+                self.expect(TokenKind::Semicolon)?;
+            }
+            methods.push(signature);
+        }
+        self.expect(TokenKind::RBrace)?;
+
+        Ok(Item::Trait(Trait {
+            visibility: Visibility::Public,
             name,
-            params,
-            return_type,
-            body,
+            methods,
             doc_comments: Vec::new(),
-            attributes: Vec::new(),
-        })
+            attributes,
+        }))
+    }
+
+    /// Parse a trait implementation: `impl Trait for Type { method ... }`,
+    /// with `Type` written `Name(T, ...)` to implement it for a generic struct
+    fn parse_impl(&mut self) -> Result<Item, ParseError> {
+        let position = self.current_token.span.start;
+        self.advance()?;
+
+        let trait_name = match &self.current_token.kind {
+            TokenKind::Ident(name) => {
+                let ident = Ident::spanned(name.clone(), self.current_token.span);
+                self.advance()?;
+                ident
+            }
+            _ => {
+                return Err(ParseError::new(
+                    self.current_token.span,
+                    "expected trait name",
+                    vec!["identifier".to_string()],
+                    self.found(),
+                ));
+            }
+        };
+        self.expect(TokenKind::For)?;
+        let mut ty = self.parse_type()?;
+        // `Pair(T)` implements the trait for every `Pair<T>`
+        let mut generics = Vec::new();
+        if matches!(ty, Type::Ident(_)) && self.check(&TokenKind::LParen) {
+            generics = self.parse_type_param_list()?;
+            ty = Type::Generic {
+                base: Box::new(ty),
+                args: generics.iter().cloned().map(Type::Ident).collect(),
+            };
+        }
+
+        self.expect(TokenKind::LBrace)?;
+        let mut methods = Vec::new();
+        while !self.check(&TokenKind::RBrace) {
+            methods.push(self.parse_struct_method()?);
+        }
+        self.expect(TokenKind::RBrace)?;
+
+        Ok(Item::Impl(Impl {
+            trait_name,
+            generics,
+            ty,
+            methods,
+            position,
+        }))
     }
 
     /// Parse an enum definition
@@ -1017,7 +1988,7 @@ impl<'a> Parser<'a> {
         // Parse enum name
         let name = match &self.current_token.kind {
             TokenKind::Ident(name) => {
-                let ident = Ident::new(name.clone());
+                let ident = Ident::spanned(name.clone(), self.current_token.span);
                 self.advance()?;
                 ident
             }
@@ -1026,7 +1997,7 @@ impl<'a> Parser<'a> {
                     self.current_token.span,
                     "expected enum name",
                     vec!["identifier".to_string()],
-                    format!("{:?}", self.current_token.kind),
+                    self.found(),
                 ));
             }
         };
@@ -1040,7 +2011,7 @@ impl<'a> Parser<'a> {
             // Parse variant name
             let variant_name = match &self.current_token.kind {
                 TokenKind::Ident(name) => {
-                    let ident = Ident::new(name.clone());
+                    let ident = Ident::spanned(name.clone(), self.current_token.span);
                     self.advance()?;
                     ident
                 }
@@ -1049,37 +2020,19 @@ impl<'a> Parser<'a> {
                         self.current_token.span,
                         "expected enum variant name",
                         vec!["identifier".to_string()],
-                        format!("{:?}", self.current_token.kind),
+                        self.found(),
                     ));
                 }
             };
 
+            let fields = self.parse_variant_fields()?;
+
             // Check for explicit value
-            let value = if self.check(&TokenKind::Assign) {
+            let value = if self.check(&TokenKind::Assign) && fields.is_empty() {
                 self.advance()?;
-                match &self.current_token.kind {
-                    TokenKind::IntLiteral(s) => {
-                        let val = s.parse::<i64>().map_err(|_| {
-                            ParseError::new(
-                                self.current_token.span,
-                                "invalid integer literal",
-                                vec![],
-                                s.clone(),
-                            )
-                        })?;
-                        self.advance()?;
-                        next_value = val + 1;
-                        Some(val)
-                    }
-                    _ => {
-                        return Err(ParseError::new(
-                            self.current_token.span,
-                            "expected integer literal",
-                            vec!["integer".to_string()],
-                            format!("{:?}", self.current_token.kind),
-                        ));
-                    }
-                }
+                let val = self.parse_enum_value(&variants)?;
+                next_value = val + 1;
+                Some(val)
             } else {
                 let val = next_value;
                 next_value += 1;
@@ -1089,6 +2042,7 @@ impl<'a> Parser<'a> {
             variants.push(EnumVariant {
                 name: variant_name,
                 value,
+                fields,
             });
 
             if self.check(&TokenKind::Comma) {
@@ -1119,7 +2073,7 @@ impl<'a> Parser<'a> {
         // Parse alias name
         let name = match &self.current_token.kind {
             TokenKind::Ident(name) => {
-                let ident = Ident::new(name.clone());
+                let ident = Ident::spanned(name.clone(), self.current_token.span);
                 self.advance()?;
                 ident
             }
@@ -1128,23 +2082,80 @@ impl<'a> Parser<'a> {
                     self.current_token.span,
                     "expected typedef name",
                     vec!["identifier".to_string()],
-                    format!("{:?}", self.current_token.kind),
+                    self.found(),
                 ));
             }
         };
 
-        self.expect(TokenKind::Semicolon)?;
+        self.expect(TokenKind::Semicolon)?;
+
+        Ok(Item::Typedef(Typedef {
+            visibility: if is_static {
+                Visibility::Private
+            } else {
+                Visibility::Public
+            },
+            name,
+            target,
+            doc_comments: Vec::new(),
+        }))
+    }
+
+    /// Parse an `#import` or `#export` directive
+    ///
+    /// The directive names a dotted module path (`#import std.collections.HashMap`)
+    /// and ends at the last path segment, without a semicolon.
+    fn parse_module_directive(&mut self, directive: &str) -> Result<Item, ParseError> {
+        self.expect(TokenKind::Hash)?;
+        let span = self.current_token.span;
+        let rejection = match directive {
+            "import" | "export" => None,
+            "include" => Some("#include is not supported; use #import instead"),
+            "use" => Some("#use is not supported; use #import or #export instead"),
+            _ => Some("unknown directive"),
+        };
+        if let Some(message) = rejection {
+            return Err(ParseError::new(
+                span,
+                message,
+                vec![
+                    "import".to_string(),
+                    "export".to_string(),
+                    "define".to_string(),
+                ],
+                format!("#{}", directive),
+            ));
+        }
+        self.advance()?;
+
+        let mut path = vec![self.parse_path_segment()?];
+        while self.check(&TokenKind::Dot) {
+            self.advance()?;
+            path.push(self.parse_path_segment()?);
+        }
+
+        Ok(if directive == "import" {
+            Item::Import(Import { path, alias: None })
+        } else {
+            Item::Export(Export { path, alias: None })
+        })
+    }
 
-        Ok(Item::Typedef(Typedef {
-            visibility: if is_static {
-                Visibility::Private
-            } else {
-                Visibility::Public
-            },
-            name,
-            target,
-            doc_comments: Vec::new(),
-        }))
+    /// Parse one identifier of a dotted module path
+    fn parse_path_segment(&mut self) -> Result<Ident, ParseError> {
+        match &self.current_token.kind {
+            TokenKind::Ident(name) => {
+                let ident = Ident::spanned(name.clone(), self.current_token.span);
+                self.advance()?;
+                Ok(ident)
+            }
+            _ => Err(ParseError::new(
+                self.current_token.span,
+                "expected module path",
+                vec!["identifier".to_string()],
+                self.found(),
+            )),
+        }
     }
 
     /// Parse a #define macro definition
@@ -1170,7 +2181,7 @@ impl<'a> Parser<'a> {
                         n.clone(),
                     ));
                 }
-                let ident = Ident::new(n.clone());
+                let ident = Ident::spanned(n.clone(), self.current_token.span);
                 self.advance()?;
                 ident
             }
@@ -1179,105 +2190,25 @@ impl<'a> Parser<'a> {
                     self.current_token.span,
                     "expected macro name",
                     vec!["__MACRO_NAME__".to_string()],
-                    format!("{:?}", self.current_token.kind),
+                    self.found(),
                 ));
             }
         };
 
         // Detect delimiter type and parse parameters
         let mut params = Vec::new();
+        let mut variadic = false;
         let delimiter = if self.check(&TokenKind::LParen) {
             self.advance()?;
-
-            // Parse parameters
-            if !self.check(&TokenKind::RParen) {
-                loop {
-                    match &self.current_token.kind {
-                        TokenKind::Ident(param_name) => {
-                            params.push(Ident::new(param_name.clone()));
-                            self.advance()?;
-                        }
-                        _ => {
-                            return Err(ParseError::new(
-                                self.current_token.span,
-                                "expected parameter name",
-                                vec!["identifier".to_string()],
-                                format!("{:?}", self.current_token.kind),
-                            ));
-                        }
-                    }
-
-                    if self.check(&TokenKind::Comma) {
-                        self.advance()?;
-                    } else {
-                        break;
-                    }
-                }
-            }
-
-            self.expect(TokenKind::RParen)?;
+            (params, variadic) = self.parse_macro_params(TokenKind::RParen)?;
             MacroDelimiter::Parens
         } else if self.check(&TokenKind::LBracket) {
             self.advance()?;
-
-            // Parse parameters
-            if !self.check(&TokenKind::RBracket) {
-                loop {
-                    match &self.current_token.kind {
-                        TokenKind::Ident(param_name) => {
-                            params.push(Ident::new(param_name.clone()));
-                            self.advance()?;
-                        }
-                        _ => {
-                            return Err(ParseError::new(
-                                self.current_token.span,
-                                "expected parameter name",
-                                vec!["identifier".to_string()],
-                                format!("{:?}", self.current_token.kind),
-                            ));
-                        }
-                    }
-
-                    if self.check(&TokenKind::Comma) {
-                        self.advance()?;
-                    } else {
-                        break;
-                    }
-                }
-            }
-
-            self.expect(TokenKind::RBracket)?;
+            (params, variadic) = self.parse_macro_params(TokenKind::RBracket)?;
             MacroDelimiter::Brackets
         } else if self.check(&TokenKind::LBrace) {
             self.advance()?;
-
-            // Parse parameters
-            if !self.check(&TokenKind::RBrace) {
-                loop {
-                    match &self.current_token.kind {
-                        TokenKind::Ident(param_name) => {
-                            params.push(Ident::new(param_name.clone()));
-                            self.advance()?;
-                        }
-                        _ => {
-                            return Err(ParseError::new(
-                                self.current_token.span,
-                                "expected parameter name",
-                                vec!["identifier".to_string()],
-                                format!("{:?}", self.current_token.kind),
-                            ));
-                        }
-                    }
-
-                    if self.check(&TokenKind::Comma) {
-                        self.advance()?;
-                    } else {
-                        break;
-                    }
-                }
-            }
-
-            self.expect(TokenKind::RBrace)?;
+            (params, variadic) = self.parse_macro_params(TokenKind::RBrace)?;
             MacroDelimiter::Braces
         } else {
             // No delimiter - constant macro
@@ -1304,28 +2235,88 @@ impl<'a> Parser<'a> {
         // Register macro in the registry
         self.macro_registry
             .insert(name.name.clone(), delimiter.clone());
+        if delimiter == MacroDelimiter::None {
+            if let Ok(value) = crate::macros::parse_tokens(&body) {
+                self.record_constant(&name.name, &value);
+            }
+        }
 
         Ok(Item::MacroDefinition(MacroDefinition {
             name,
             params,
             body,
             delimiter,
+            variadic,
         }))
     }
 
+    /// Parse the parameter names of a #define up to `close`, with an
+    /// optional trailing `...` for variadic macros
+    fn parse_macro_params(&mut self, close: TokenKind) -> Result<(Vec<Ident>, bool), ParseError> {
+        let mut params = Vec::new();
+        let mut variadic = false;
+        while !self.check(&close) {
+            // `...` lexes as `..` followed by `.`
+            if self.check(&TokenKind::DotDot) {
+                self.advance()?;
+                self.expect(TokenKind::Dot)?;
+                variadic = true;
+                break;
+            }
+
+            match &self.current_token.kind {
+                TokenKind::Ident(param_name) => {
+                    params.push(Ident::spanned(param_name.clone(), self.current_token.span));
+                    self.advance()?;
+                }
+                _ => {
+                    return Err(ParseError::new(
+                        self.current_token.span,
+                        "expected parameter name",
+                        vec!["identifier".to_string()],
+                        self.found(),
+                    ));
+                }
+            }
+
+            if self.check(&TokenKind::Comma) {
+                self.advance()?;
+            } else {
+                break;
+            }
+        }
+        self.expect(close)?;
+        Ok((params, variadic))
+    }
+
     /// Parse a block of statements
     fn parse_block(&mut self) -> Result<Block, ParseError> {
+        self.nested(Self::parse_block_contents)
+    }
+
+    fn parse_block_contents(&mut self) -> Result<Block, ParseError> {
         self.expect(TokenKind::LBrace)?;
+        // Constants declared in the block go out of scope with it
+        let constants = self.constants.clone();
 
         let mut statements = Vec::new();
+        let mut positions = Vec::new();
 
         while !self.check(&TokenKind::RBrace) && !self.is_at_end() {
-            statements.push(self.parse_statement()?);
+            let position = self.current_token.span.start;
+            match self.parse_statement() {
+                Ok(statement) => {
+                    positions.push(position);
+                    statements.push(statement);
+                }
+                Err(error) => self.recover(error, Self::skip_statement)?,
+            }
         }
 
         self.expect(TokenKind::RBrace)?;
+        self.constants = constants;
 
-        Ok(Block::new(statements))
+        Ok(Block::with_positions(statements, positions))
     }
 
     /// Parse a statement
@@ -1333,13 +2324,28 @@ impl<'a> Parser<'a> {
         match &self.current_token.kind {
             TokenKind::Let => self.parse_let_statement(),
             TokenKind::Var => self.parse_var_statement(),
-            TokenKind::Const => self.parse_const_statement(),
+            // const char* name = ...; declares a pointer to const
+            TokenKind::Const => {
+                if self.at_pointer_to_const()? {
+                    self.parse_implicit_let_statement()
+                } else {
+                    self.parse_const_statement()
+                }
+            }
             TokenKind::If => self.parse_if_statement(),
             TokenKind::While => self.parse_while_statement(),
+            TokenKind::Do => self.parse_do_while_statement(),
             TokenKind::For => self.parse_for_statement(),
+            TokenKind::Switch => self.parse_switch_statement(),
             TokenKind::Return => self.parse_return_statement(),
             TokenKind::Break => self.parse_break_statement(),
             TokenKind::Continue => self.parse_continue_statement(),
+            TokenKind::Unsafe => self.parse_unsafe_statement(),
+            // volatile u32* reg = ...;
+            TokenKind::Volatile => self.parse_implicit_let_statement(),
+            TokenKind::Ident(name) if name == STATIC_ASSERT => {
+                Ok(Statement::StaticAssert(self.parse_static_assert()?))
+            }
             TokenKind::Dot => {
                 // Check for labeled loop (.label: loop { ... })
                 self.parse_labeled_loop()
@@ -1351,6 +2357,14 @@ impl<'a> Parser<'a> {
             | TokenKind::I64
             | TokenKind::U32
             | TokenKind::U64
+            | TokenKind::I8
+            | TokenKind::I16
+            | TokenKind::Isize
+            | TokenKind::U8
+            | TokenKind::U16
+            | TokenKind::Usize
+            | TokenKind::Long
+            | TokenKind::ULong
             | TokenKind::Float
             | TokenKind::F32
             | TokenKind::F64
@@ -1391,12 +2405,69 @@ impl<'a> Parser<'a> {
                 left: Box::new(expr),
                 right: Box::new(value),
             }))
+        } else if let Some(op) = compound_assign_op(&self.current_token.kind) {
+            // Compound assignment: x += y
+            self.advance()?;
+            let value = self.parse_expression_stub()?;
+            self.expect(TokenKind::Semicolon)?;
+            Ok(Statement::Expr(Expression::Binary {
+                op,
+                left: Box::new(expr),
+                right: Box::new(value),
+            }))
         } else {
             self.expect(TokenKind::Semicolon)?;
             Ok(Statement::Expr(expr))
         }
     }
 
+    /// Whether the type starting at the current token goes on past its
+    /// name, as after `let` or `var`: to type arguments, `Vec<Shape*> v`, or
+    /// to a pointer, `Shape* s`
+    fn is_compound_declaration(&mut self) -> Result<bool, ParseError> {
+        Ok(match self.peek_ahead(1)?.map(|token| token.kind) {
+            Some(TokenKind::Lt) => true,
+            Some(TokenKind::Star) => matches!(
+                self.peek_ahead(2)?.map(|token| token.kind),
+                Some(TokenKind::Ident(_))
+            ),
+            // `fn(int) -> int f` and `int(*)(int) f`
+            Some(TokenKind::LParen) => {
+                matches!(&self.current_token.kind, TokenKind::Ident(name) if name == "fn")
+                    || self.function_pointer_at(2)?
+            }
+            _ => false,
+        })
+    }
+
+    /// Whether the tokens from `offset` on are the `*)` of a C function
+    /// pointer type, as in `int(*)(int)`
+    fn function_pointer_at(&mut self, offset: usize) -> Result<bool, ParseError> {
+        Ok(matches!(
+            self.peek_ahead(offset)?.map(|token| token.kind),
+            Some(TokenKind::Star)
+        ) && matches!(
+            self.peek_ahead(offset + 1)?.map(|token| token.kind),
+            Some(TokenKind::RParen)
+        ))
+    }
+
+    /// Parse the parenthesized parameter types of a function type
+    fn parse_function_type_params(&mut self) -> Result<Vec<Type>, ParseError> {
+        self.expect(TokenKind::LParen)?;
+        let mut params = Vec::new();
+        while !self.check(&TokenKind::RParen) {
+            params.push(self.parse_type()?);
+            if self.check(&TokenKind::Comma) {
+                self.advance()?;
+            } else {
+                break;
+            }
+        }
+        self.expect(TokenKind::RParen)?;
+        Ok(params)
+    }
+
     /// Parse a let statement
     fn parse_let_statement(&mut self) -> Result<Statement, ParseError> {
         self.expect(TokenKind::Let)?;
@@ -1408,12 +2479,11 @@ impl<'a> Parser<'a> {
         let (name, ty) = if self.is_type_token() {
             // Check if this looks like a type followed by identifier
             // Pattern: let Type Identifier = ...
-            let next_token = self.peek_ahead(1)?;
-            let is_type_declaration = if let Some(token) = next_token {
-                matches!(token.kind, TokenKind::Ident(_))
-            } else {
-                false
-            };
+            let is_type_declaration = self.is_compound_declaration()?
+                || matches!(
+                    self.peek_ahead(1)?.map(|token| token.kind),
+                    Some(TokenKind::Ident(_) | TokenKind::LBracket)
+                );
 
             if is_type_declaration {
                 // Parse type first
@@ -1422,7 +2492,7 @@ impl<'a> Parser<'a> {
                 // Then parse variable name
                 let name = match &self.current_token.kind {
                     TokenKind::Ident(n) => {
-                        let ident = Ident::new(n.clone());
+                        let ident = Ident::spanned(n.clone(), self.current_token.span);
                         self.advance()?;
                         ident
                     }
@@ -1431,17 +2501,17 @@ impl<'a> Parser<'a> {
                             self.current_token.span,
                             "expected variable name after type",
                             vec!["identifier".to_string()],
-                            format!("{:?}", self.current_token.kind),
+                            self.found(),
                         ));
                     }
                 };
 
-                (name, Some(ty))
+                (name, Some(self.parse_array_declarator(ty)?))
             } else {
                 // Type inference (let x = 42;)
                 let name = match &self.current_token.kind {
                     TokenKind::Ident(n) => {
-                        let ident = Ident::new(n.clone());
+                        let ident = Ident::spanned(n.clone(), self.current_token.span);
                         self.advance()?;
                         ident
                     }
@@ -1450,7 +2520,7 @@ impl<'a> Parser<'a> {
                             self.current_token.span,
                             "expected variable name",
                             vec!["identifier".to_string()],
-                            format!("{:?}", self.current_token.kind),
+                            self.found(),
                         ));
                     }
                 };
@@ -1461,7 +2531,7 @@ impl<'a> Parser<'a> {
             // Type inference (let x = 42;)
             let name = match &self.current_token.kind {
                 TokenKind::Ident(n) => {
-                    let ident = Ident::new(n.clone());
+                    let ident = Ident::spanned(n.clone(), self.current_token.span);
                     self.advance()?;
                     ident
                 }
@@ -1470,7 +2540,7 @@ impl<'a> Parser<'a> {
                         self.current_token.span,
                         "expected variable name",
                         vec!["identifier".to_string()],
-                        format!("{:?}", self.current_token.kind),
+                        self.found(),
                     ));
                 }
             };
@@ -1507,12 +2577,11 @@ impl<'a> Parser<'a> {
         let (name, ty) = if self.is_type_token() {
             // Check if this looks like a type followed by identifier
             // Pattern: var Type Identifier = ...
-            let next_token = self.peek_ahead(1)?;
-            let is_type_declaration = if let Some(token) = next_token {
-                matches!(token.kind, TokenKind::Ident(_))
-            } else {
-                false
-            };
+            let is_type_declaration = self.is_compound_declaration()?
+                || matches!(
+                    self.peek_ahead(1)?.map(|token| token.kind),
+                    Some(TokenKind::Ident(_) | TokenKind::LBracket)
+                );
 
             if is_type_declaration {
                 // Parse type first
@@ -1521,7 +2590,7 @@ impl<'a> Parser<'a> {
                 // Then parse variable name
                 let name = match &self.current_token.kind {
                     TokenKind::Ident(n) => {
-                        let ident = Ident::new(n.clone());
+                        let ident = Ident::spanned(n.clone(), self.current_token.span);
                         self.advance()?;
                         ident
                     }
@@ -1530,17 +2599,17 @@ impl<'a> Parser<'a> {
                             self.current_token.span,
                             "expected variable name after type",
                             vec!["identifier".to_string()],
-                            format!("{:?}", self.current_token.kind),
+                            self.found(),
                         ));
                     }
                 };
 
-                (name, Some(ty))
+                (name, Some(self.parse_array_declarator(ty)?))
             } else {
                 // Type inference (var x = 42;)
                 let name = match &self.current_token.kind {
                     TokenKind::Ident(n) => {
-                        let ident = Ident::new(n.clone());
+                        let ident = Ident::spanned(n.clone(), self.current_token.span);
                         self.advance()?;
                         ident
                     }
@@ -1549,7 +2618,7 @@ impl<'a> Parser<'a> {
                             self.current_token.span,
                             "expected variable name",
                             vec!["identifier".to_string()],
-                            format!("{:?}", self.current_token.kind),
+                            self.found(),
                         ));
                     }
                 };
@@ -1560,7 +2629,7 @@ impl<'a> Parser<'a> {
             // Type inference (var x = 42;)
             let name = match &self.current_token.kind {
                 TokenKind::Ident(n) => {
-                    let ident = Ident::new(n.clone());
+                    let ident = Ident::spanned(n.clone(), self.current_token.span);
                     self.advance()?;
                     ident
                 }
@@ -1569,7 +2638,7 @@ impl<'a> Parser<'a> {
                         self.current_token.span,
                         "expected variable name",
                         vec!["identifier".to_string()],
-                        format!("{:?}", self.current_token.kind),
+                        self.found(),
                     ));
                 }
             };
@@ -1622,7 +2691,7 @@ impl<'a> Parser<'a> {
         // Parse constant name
         let name = match &self.current_token.kind {
             TokenKind::Ident(n) => {
-                let ident = Ident::new(n.clone());
+                let ident = Ident::spanned(n.clone(), self.current_token.span);
                 self.advance()?;
                 ident
             }
@@ -1631,7 +2700,7 @@ impl<'a> Parser<'a> {
                     self.current_token.span,
                     "expected constant name",
                     vec!["identifier".to_string()],
-                    format!("{:?}", self.current_token.kind),
+                    self.found(),
                 ));
             }
         };
@@ -1653,6 +2722,7 @@ impl<'a> Parser<'a> {
             Type::Primitive(PrimitiveType::Int)
         };
 
+        self.record_constant(&name.name, &value);
         Ok(Statement::Const { name, ty, value })
     }
 
@@ -1666,7 +2736,7 @@ impl<'a> Parser<'a> {
         // Parse variable name
         let name = match &self.current_token.kind {
             TokenKind::Ident(n) => {
-                let ident = Ident::new(n.clone());
+                let ident = Ident::spanned(n.clone(), self.current_token.span);
                 self.advance()?;
                 ident
             }
@@ -1675,11 +2745,13 @@ impl<'a> Parser<'a> {
                     self.current_token.span,
                     "expected variable name after type",
                     vec!["identifier".to_string()],
-                    format!("{:?}", self.current_token.kind),
+                    self.found(),
                 ));
             }
         };
 
+        let ty = self.parse_array_declarator(ty)?;
+
         // Expect assignment
         self.expect(TokenKind::Assign)?;
 
@@ -1697,6 +2769,117 @@ impl<'a> Parser<'a> {
         })
     }
 
+    /// Parse one or more adjacent string literals, concatenated as in C:
+    /// `"a" "b"` is `"ab"`, and `"a" L"b"` is the wide string `L"ab"`
+    fn parse_string_literals(&mut self) -> Result<Literal, ParseError> {
+        let mut result: Option<Literal> = None;
+        loop {
+            let literal = match &self.current_token.kind {
+                TokenKind::StringLiteral(s) => Literal::String(s.clone()),
+                TokenKind::RawStringLiteral(s) => Literal::RawString(s.clone()),
+                TokenKind::ByteStringLiteral(bytes) => Literal::ByteString(bytes.clone()),
+                TokenKind::WideStringLiteral(s) => Literal::WideString(s.clone()),
+                _ => break,
+            };
+            result = Some(match result {
+                None => literal,
+                Some(previous) => concat_string_literals(previous, literal).ok_or_else(|| {
+                    ParseError::new(
+                        self.current_token.span,
+                        "cannot concatenate a byte string with a text string",
+                        vec![],
+                        self.current_token.text.clone(),
+                    )
+                })?,
+            });
+            self.advance()?;
+        }
+        Ok(result.expect("called on a string literal token"))
+    }
+
+    /// Remember the value of constant `name` when it can be evaluated here
+    fn record_constant(&mut self, name: &str, value: &Expression) {
+        if let Ok(value) = consteval::evaluate_portable(value, &self.constants) {
+            self.constants.insert(name.to_string(), value);
+        }
+    }
+
+    /// Parse a constant expression, as `N * 2` in `char buf[N * 2]`, to the
+    /// value it has with `constants` in scope
+    fn parse_constant(
+        &mut self,
+        what: &str,
+        constants: &HashMap<String, ConstValue>,
+    ) -> Result<i64, ParseError> {
+        let span = self.current_token.span;
+        let found = self.found();
+        let expr = self.parse_expression()?;
+        match consteval::evaluate_portable(&expr, constants) {
+            Ok(ConstValue::Int(value)) => Ok(value),
+            Ok(value) => Err(ParseError::new(
+                span,
+                format!("{} must be an integer, found {}", what, value),
+                vec!["integer".to_string()],
+                found,
+            )),
+            Err(error) => Err(ParseError::new(
+                span,
+                format!("{} must be a constant expression: {}", what, error),
+                vec!["constant expression".to_string()],
+                found,
+            )),
+        }
+    }
+
+    /// Parse the size between the brackets of an array type
+    fn parse_array_size(&mut self) -> Result<usize, ParseError> {
+        if let TokenKind::IntLiteral(s) = self.current_token.kind.clone() {
+            if self.peek_ahead(1)?.map(|token| token.kind) == Some(TokenKind::RBracket) {
+                let size = array_size(&s).map_err(|message| {
+                    ParseError::new(self.current_token.span, message, vec![], s.clone())
+                })?;
+                self.advance()?;
+                return Ok(size);
+            }
+        }
+        let span = self.current_token.span;
+        let constants = self.constants.clone();
+        let size = self.parse_constant("array size", &constants)?;
+        usize::try_from(size).map_err(|_| {
+            ParseError::new(
+                span,
+                format!("invalid array size '{}'", size),
+                vec![],
+                size.to_string(),
+            )
+        })
+    }
+
+    /// Parse the value given to an enum variant, where the variants before
+    /// it are constants too
+    fn parse_enum_value(&mut self, variants: &[EnumVariant]) -> Result<i64, ParseError> {
+        let mut constants = self.constants.clone();
+        for variant in variants {
+            if let Some(value) = variant.value {
+                constants.insert(variant.name.name.clone(), ConstValue::Int(value));
+            }
+        }
+        self.parse_constant("enum value", &constants)
+    }
+
+    /// Parse the C-style array suffix of a declarator (`char buf[8]`)
+    fn parse_array_declarator(&mut self, ty: Type) -> Result<Type, ParseError> {
+        let mut sizes = Vec::new();
+        while self.check(&TokenKind::LBracket) {
+            self.advance()?;
+            let size = self.parse_array_size()?;
+            self.expect(TokenKind::RBracket)?;
+            sizes.push(size);
+        }
+
+        Ok(array_of(ty, sizes))
+    }
+
     /// Parse an if statement
     fn parse_if_statement(&mut self) -> Result<Statement, ParseError> {
         self.expect(TokenKind::If)?;
@@ -1747,6 +2930,21 @@ impl<'a> Parser<'a> {
         })
     }
 
+    /// Parse a do-while statement (`do { ... } while (cond);`)
+    fn parse_do_while_statement(&mut self) -> Result<Statement, ParseError> {
+        self.expect(TokenKind::Do)?;
+
+        let body = self.parse_block()?;
+
+        self.expect(TokenKind::While)?;
+        self.expect(TokenKind::LParen)?;
+        let condition = self.parse_expression_stub()?;
+        self.expect(TokenKind::RParen)?;
+        self.expect(TokenKind::Semicolon)?;
+
+        Ok(Statement::DoWhile { body, condition })
+    }
+
     /// Parse a for statement (C-style or for-in)
     fn parse_for_statement(&mut self) -> Result<Statement, ParseError> {
         self.expect(TokenKind::For)?;
@@ -1764,7 +2962,7 @@ impl<'a> Parser<'a> {
         if matches!(first_token.kind, TokenKind::Ident(_)) {
             let var_name = match &self.current_token.kind {
                 TokenKind::Ident(n) => {
-                    let ident = Ident::new(n.clone());
+                    let ident = Ident::spanned(n.clone(), self.current_token.span);
                     self.advance()?;
                     ident
                 }
@@ -1835,7 +3033,7 @@ impl<'a> Parser<'a> {
 
         // Check for label (just identifier, no dot)
         let label = if let TokenKind::Ident(n) = &self.current_token.kind {
-            let ident = Ident::new(n.clone());
+            let ident = Ident::spanned(n.clone(), self.current_token.span);
             self.advance()?;
             Some(ident)
         } else {
@@ -1853,7 +3051,7 @@ impl<'a> Parser<'a> {
 
         // Check for label (just identifier, no dot)
         let label = if let TokenKind::Ident(n) = &self.current_token.kind {
-            let ident = Ident::new(n.clone());
+            let ident = Ident::spanned(n.clone(), self.current_token.span);
             self.advance()?;
             Some(ident)
         } else {
@@ -1865,6 +3063,78 @@ impl<'a> Parser<'a> {
         Ok(Statement::Continue(label))
     }
 
+    /// Parse a switch statement
+    /// (`switch (x) { case 1: case 2: { ... } default: { ... } }`)
+    fn parse_switch_statement(&mut self) -> Result<Statement, ParseError> {
+        self.expect(TokenKind::Switch)?;
+        self.expect(TokenKind::LParen)?;
+        let expr = self.parse_expression_stub()?;
+        self.expect(TokenKind::RParen)?;
+        self.expect(TokenKind::LBrace)?;
+
+        let mut cases = Vec::new();
+        while self.check(&TokenKind::Case) {
+            let mut values = Vec::new();
+            while self.check(&TokenKind::Case) {
+                self.advance()?;
+                values.push(self.parse_expression_stub()?);
+                self.expect(TokenKind::Colon)?;
+            }
+            let body = self.parse_block()?;
+            cases.push(SwitchCase { values, body });
+        }
+
+        let default = if self.check(&TokenKind::Default) {
+            self.advance()?;
+            self.expect(TokenKind::Colon)?;
+            Some(self.parse_block()?)
+        } else {
+            None
+        };
+
+        self.expect(TokenKind::RBrace)?;
+        Ok(Statement::Switch {
+            expr,
+            cases,
+            default,
+        })
+    }
+
+    /// Parse an unsafe block statement (unsafe { ... })
+    fn parse_unsafe_statement(&mut self) -> Result<Statement, ParseError> {
+        self.expect(TokenKind::Unsafe)?;
+        let body = self.parse_block()?;
+        Ok(Statement::Unsafe(body))
+    }
+
+    /// Parse `static_assert(condition, "message");`
+    fn parse_static_assert(&mut self) -> Result<StaticAssert, ParseError> {
+        let position = self.current_token.span.start;
+        self.advance()?;
+        self.expect(TokenKind::LParen)?;
+        let condition = self.parse_expression_stub()?;
+        self.expect(TokenKind::Comma)?;
+        let message = match &self.current_token.kind {
+            TokenKind::StringLiteral(s) => s.clone(),
+            _ => {
+                return Err(ParseError::new(
+                    self.current_token.span,
+                    "expected static assertion message",
+                    vec!["string literal".to_string()],
+                    self.found(),
+                ))
+            }
+        };
+        self.advance()?;
+        self.expect(TokenKind::RParen)?;
+        self.expect(TokenKind::Semicolon)?;
+        Ok(StaticAssert {
+            condition,
+            message,
+            position,
+        })
+    }
+
     /// Parse a labeled loop (.label: loop { ... })
     fn parse_labeled_loop(&mut self) -> Result<Statement, ParseError> {
         self.expect(TokenKind::Dot)?;
@@ -1872,7 +3142,7 @@ impl<'a> Parser<'a> {
         // Parse label name
         let label = match &self.current_token.kind {
             TokenKind::Ident(n) => {
-                let ident = Ident::new(n.clone());
+                let ident = Ident::spanned(n.clone(), self.current_token.span);
                 self.advance()?;
                 ident
             }
@@ -1881,7 +3151,7 @@ impl<'a> Parser<'a> {
                     self.current_token.span,
                     "expected label name",
                     vec!["identifier".to_string()],
-                    format!("{:?}", self.current_token.kind),
+                    self.found(),
                 ));
             }
         };
@@ -1916,7 +3186,7 @@ impl<'a> Parser<'a> {
                 self.current_token.span,
                 "expected 'while' or 'loop' after label",
                 vec!["while".to_string(), "loop".to_string()],
-                format!("{:?}", self.current_token.kind),
+                self.found(),
             ))
         }
     }
@@ -1934,6 +3204,14 @@ impl<'a> Parser<'a> {
                 | TokenKind::I64
                 | TokenKind::U32
                 | TokenKind::U64
+                | TokenKind::I8
+                | TokenKind::I16
+                | TokenKind::Isize
+                | TokenKind::U8
+                | TokenKind::U16
+                | TokenKind::Usize
+                | TokenKind::Long
+                | TokenKind::ULong
                 | TokenKind::Float
                 | TokenKind::F32
                 | TokenKind::F64
@@ -1988,7 +3266,7 @@ impl<'a> Parser<'a> {
         // Parse function name
         let name = match &self.current_token.kind {
             TokenKind::Ident(n) => {
-                let ident = Ident::new(n.clone());
+                let ident = Ident::spanned(n.clone(), self.current_token.span);
                 self.advance()?;
                 ident
             }
@@ -1997,12 +3275,27 @@ impl<'a> Parser<'a> {
                     self.current_token.span,
                     "expected function name",
                     vec!["identifier".to_string()],
-                    format!("{:?}", self.current_token.kind),
+                    self.found(),
                 ));
             }
         };
 
-        // Parse parameter list
+        let params = self.parse_nested_params()?;
+
+        // Parse function body
+        let body = self.parse_block()?;
+
+        Ok(Statement::NestedFunction {
+            name,
+            params,
+            return_type,
+            body,
+        })
+    }
+
+    /// Parse the parameter list of a nested function or lambda, with its
+    /// parentheses
+    fn parse_nested_params(&mut self) -> Result<Vec<Param>, ParseError> {
         self.expect(TokenKind::LParen)?;
         let mut params = Vec::new();
 
@@ -2014,7 +3307,7 @@ impl<'a> Parser<'a> {
                 // Parse parameter name
                 let param_name = match &self.current_token.kind {
                     TokenKind::Ident(n) => {
-                        let ident = Ident::new(n.clone());
+                        let ident = Ident::spanned(n.clone(), self.current_token.span);
                         self.advance()?;
                         ident
                     }
@@ -2023,7 +3316,7 @@ impl<'a> Parser<'a> {
                             self.current_token.span,
                             "expected parameter name",
                             vec!["identifier".to_string()],
-                            format!("{:?}", self.current_token.kind),
+                            self.found(),
                         ));
                     }
                 };
@@ -2031,6 +3324,7 @@ impl<'a> Parser<'a> {
                 params.push(Param {
                     name: param_name,
                     ty: param_type,
+                    variadic: false,
                 });
 
                 if self.check(&TokenKind::Comma) {
@@ -2043,14 +3337,26 @@ impl<'a> Parser<'a> {
 
         self.expect(TokenKind::RParen)?;
 
-        // Parse function body
-        let body = self.parse_block()?;
+        Ok(params)
+    }
 
-        Ok(Statement::NestedFunction {
-            name,
+    /// Parse a lambda, `fn(int x) -> int { ... }`, returning void without
+    /// `->`
+    fn parse_lambda(&mut self) -> Result<Expression, ParseError> {
+        self.advance()?;
+        let params = self.parse_nested_params()?;
+        let return_type = if self.check(&TokenKind::Arrow) {
+            self.advance()?;
+            Some(self.parse_type()?)
+        } else {
+            None
+        };
+        let body = self.parse_block()?;
+        Ok(Expression::Lambda {
             params,
             return_type,
             body,
+            by_value: false,
         })
     }
 
@@ -2061,7 +3367,11 @@ impl<'a> Parser<'a> {
 
     /// Parse an expression with operator precedence
     fn parse_expression(&mut self) -> Result<Expression, ParseError> {
-        self.parse_ternary()
+        // An operator chain an error cut short is not counted against the next
+        let outer = self.operators;
+        let expr = self.parse_ternary();
+        self.operators = outer;
+        expr
     }
 
     /// Parse ternary conditional operator (? :)
@@ -2084,12 +3394,179 @@ impl<'a> Parser<'a> {
         Ok(expr)
     }
 
+    /// Parse an if/else in expression position: `if (cond) { a } else { b }`
+    ///
+    /// Each branch holds a single expression, and `else if` continues the
+    /// chain. The result is the same conditional expression as `cond ? a : b`.
+    fn parse_if_expression(&mut self) -> Result<Expression, ParseError> {
+        self.expect(TokenKind::If)?;
+        self.expect(TokenKind::LParen)?;
+        let condition = Box::new(self.parse_expression()?);
+        self.expect(TokenKind::RParen)?;
+        let then_expr = Box::new(self.parse_if_expression_branch()?);
+
+        if !self.check(&TokenKind::Else) {
+            return Err(ParseError::new(
+                self.current_token.span,
+                "if expression requires an else branch",
+                vec!["else".to_string()],
+                self.found(),
+            ));
+        }
+        self.advance()?;
+
+        let else_expr = Box::new(if self.check(&TokenKind::If) {
+            self.parse_if_expression()?
+        } else {
+            self.parse_if_expression_branch()?
+        });
+
+        Ok(Expression::Ternary {
+            condition,
+            then_expr,
+            else_expr,
+        })
+    }
+
+    /// Parse one `{ expr }` branch of an if expression
+    fn parse_if_expression_branch(&mut self) -> Result<Expression, ParseError> {
+        self.expect(TokenKind::LBrace)?;
+        let expr = self.parse_expression()?;
+        self.expect(TokenKind::RBrace)?;
+        Ok(expr)
+    }
+
+    /// Parse a match expression: `match (expr) { pattern => value, ... }`
+    fn parse_match_expression(&mut self) -> Result<Expression, ParseError> {
+        self.expect(TokenKind::Match)?;
+        self.expect(TokenKind::LParen)?;
+        let expr = Box::new(self.parse_expression()?);
+        self.expect(TokenKind::RParen)?;
+        self.expect(TokenKind::LBrace)?;
+
+        let mut arms = Vec::new();
+        while !self.check(&TokenKind::RBrace) {
+            let pattern = self.parse_pattern()?;
+            self.expect(TokenKind::FatArrow)?;
+            let body = self.parse_expression()?;
+            arms.push(MatchArm { pattern, body });
+
+            if self.check(&TokenKind::Comma) {
+                self.advance()?;
+            } else {
+                break;
+            }
+        }
+        self.expect(TokenKind::RBrace)?;
+
+        Ok(Expression::Match { expr, arms })
+    }
+
+    /// Parse a match arm pattern
+    fn parse_pattern(&mut self) -> Result<Pattern, ParseError> {
+        match self.current_token.kind.clone() {
+            TokenKind::Ident(name) if name == "_" => {
+                self.advance()?;
+                Ok(Pattern::Wildcard)
+            }
+            TokenKind::Ident(name) => {
+                let span = self.current_token.span;
+                self.advance()?;
+                if !self.check(&TokenKind::Dot) && !self.check(&TokenKind::DoubleColon) {
+                    return Ok(Pattern::Binding(Ident::spanned(name, span)));
+                }
+                self.advance()?;
+                let variant = match &self.current_token.kind {
+                    TokenKind::Ident(variant) => {
+                        Ident::spanned(variant.clone(), self.current_token.span)
+                    }
+                    _ => {
+                        return Err(ParseError::new(
+                            self.current_token.span,
+                            "expected variant name in pattern",
+                            vec!["identifier".to_string()],
+                            self.found(),
+                        ));
+                    }
+                };
+                self.advance()?;
+
+                let mut fields = Vec::new();
+                if self.check(&TokenKind::LParen) {
+                    self.advance()?;
+                    fields = self.parse_pattern_list()?;
+                }
+                Ok(Pattern::Variant {
+                    enum_name: Ident::spanned(name, span),
+                    variant,
+                    fields,
+                })
+            }
+            TokenKind::LParen => {
+                self.advance()?;
+                let patterns = self.parse_pattern_list()?;
+                Ok(Pattern::Tuple(patterns))
+            }
+            TokenKind::Minus => {
+                self.advance()?;
+                let negated = match self.parse_primary()? {
+                    Expression::Literal(literal) => literal.negated(),
+                    _ => None,
+                };
+                negated.map(Pattern::Literal).ok_or_else(|| {
+                    ParseError::new(
+                        self.current_token.span,
+                        "expected number after '-' in pattern",
+                        vec!["number".to_string()],
+                        self.found(),
+                    )
+                })
+            }
+            TokenKind::IntLiteral(_)
+            | TokenKind::FloatLiteral(_)
+            | TokenKind::StringLiteral(_)
+            | TokenKind::RawStringLiteral(_)
+            | TokenKind::CharLiteral(_)
+            | TokenKind::BoolLiteral(_) => match self.parse_primary()? {
+                Expression::Literal(literal) => Ok(Pattern::Literal(literal)),
+                _ => unreachable!("literal tokens parse to literals"),
+            },
+            _ => Err(ParseError::new(
+                self.current_token.span,
+                "expected pattern",
+                vec![
+                    "literal".to_string(),
+                    "identifier".to_string(),
+                    "_".to_string(),
+                ],
+                self.found(),
+            )),
+        }
+    }
+
+    /// Parse comma-separated patterns up to and including the closing `)`
+    fn parse_pattern_list(&mut self) -> Result<Vec<Pattern>, ParseError> {
+        let mut patterns = Vec::new();
+        while !self.check(&TokenKind::RParen) {
+            patterns.push(self.parse_pattern()?);
+            if self.check(&TokenKind::Comma) {
+                self.advance()?;
+            } else {
+                break;
+            }
+        }
+        self.expect(TokenKind::RParen)?;
+        Ok(patterns)
+    }
+
     /// Parse logical OR (||)
     fn parse_logical_or(&mut self) -> Result<Expression, ParseError> {
         let mut left = self.parse_logical_and()?;
+        let outer = self.operators;
 
         while self.check(&TokenKind::Or) {
             self.advance()?;
+            self.chain_operator()?;
             let right = self.parse_logical_and()?;
             left = Expression::Binary {
                 op: BinaryOp::Or,
@@ -2097,6 +3574,7 @@ impl<'a> Parser<'a> {
                 right: Box::new(right),
             };
         }
+        self.operators = outer;
 
         Ok(left)
     }
@@ -2104,9 +3582,11 @@ impl<'a> Parser<'a> {
     /// Parse logical AND (&&)
     fn parse_logical_and(&mut self) -> Result<Expression, ParseError> {
         let mut left = self.parse_bitwise_or()?;
+        let outer = self.operators;
 
         while self.check(&TokenKind::And) {
             self.advance()?;
+            self.chain_operator()?;
             let right = self.parse_bitwise_or()?;
             left = Expression::Binary {
                 op: BinaryOp::And,
@@ -2114,6 +3594,7 @@ impl<'a> Parser<'a> {
                 right: Box::new(right),
             };
         }
+        self.operators = outer;
 
         Ok(left)
     }
@@ -2121,9 +3602,11 @@ impl<'a> Parser<'a> {
     /// Parse bitwise OR (|)
     fn parse_bitwise_or(&mut self) -> Result<Expression, ParseError> {
         let mut left = self.parse_bitwise_xor()?;
+        let outer = self.operators;
 
         while self.check(&TokenKind::BitOr) {
             self.advance()?;
+            self.chain_operator()?;
             let right = self.parse_bitwise_xor()?;
             left = Expression::Binary {
                 op: BinaryOp::BitOr,
@@ -2131,6 +3614,7 @@ impl<'a> Parser<'a> {
                 right: Box::new(right),
             };
         }
+        self.operators = outer;
 
         Ok(left)
     }
@@ -2138,9 +3622,11 @@ impl<'a> Parser<'a> {
     /// Parse bitwise XOR (^)
     fn parse_bitwise_xor(&mut self) -> Result<Expression, ParseError> {
         let mut left = self.parse_bitwise_and()?;
+        let outer = self.operators;
 
         while self.check(&TokenKind::BitXor) {
             self.advance()?;
+            self.chain_operator()?;
             let right = self.parse_bitwise_and()?;
             left = Expression::Binary {
                 op: BinaryOp::BitXor,
@@ -2148,6 +3634,7 @@ impl<'a> Parser<'a> {
                 right: Box::new(right),
             };
         }
+        self.operators = outer;
 
         Ok(left)
     }
@@ -2155,9 +3642,11 @@ impl<'a> Parser<'a> {
     /// Parse bitwise AND (&)
     fn parse_bitwise_and(&mut self) -> Result<Expression, ParseError> {
         let mut left = self.parse_equality()?;
+        let outer = self.operators;
 
         while self.check(&TokenKind::BitAnd) {
             self.advance()?;
+            self.chain_operator()?;
             let right = self.parse_equality()?;
             left = Expression::Binary {
                 op: BinaryOp::BitAnd,
@@ -2165,6 +3654,7 @@ impl<'a> Parser<'a> {
                 right: Box::new(right),
             };
         }
+        self.operators = outer;
 
         Ok(left)
     }
@@ -2172,6 +3662,7 @@ impl<'a> Parser<'a> {
     /// Parse equality operators (==, !=)
     fn parse_equality(&mut self) -> Result<Expression, ParseError> {
         let mut left = self.parse_comparison()?;
+        let outer = self.operators;
 
         while self.check(&TokenKind::Eq) || self.check(&TokenKind::Ne) {
             let op = if self.check(&TokenKind::Eq) {
@@ -2180,6 +3671,7 @@ impl<'a> Parser<'a> {
                 BinaryOp::Ne
             };
             self.advance()?;
+            self.chain_operator()?;
             let right = self.parse_comparison()?;
             left = Expression::Binary {
                 op,
@@ -2187,6 +3679,7 @@ impl<'a> Parser<'a> {
                 right: Box::new(right),
             };
         }
+        self.operators = outer;
 
         Ok(left)
     }
@@ -2194,6 +3687,7 @@ impl<'a> Parser<'a> {
     /// Parse comparison operators (<, >, <=, >=)
     fn parse_comparison(&mut self) -> Result<Expression, ParseError> {
         let mut left = self.parse_shift()?;
+        let outer = self.operators;
 
         while self.check(&TokenKind::Lt)
             || self.check(&TokenKind::Gt)
@@ -2208,6 +3702,7 @@ impl<'a> Parser<'a> {
                 _ => unreachable!(),
             };
             self.advance()?;
+            self.chain_operator()?;
             let right = self.parse_shift()?;
             left = Expression::Binary {
                 op,
@@ -2215,6 +3710,7 @@ impl<'a> Parser<'a> {
                 right: Box::new(right),
             };
         }
+        self.operators = outer;
 
         Ok(left)
     }
@@ -2222,6 +3718,7 @@ impl<'a> Parser<'a> {
     /// Parse shift operators (<<, >>)
     fn parse_shift(&mut self) -> Result<Expression, ParseError> {
         let mut left = self.parse_additive()?;
+        let outer = self.operators;
 
         while self.check(&TokenKind::Shl) || self.check(&TokenKind::Shr) {
             let op = if self.check(&TokenKind::Shl) {
@@ -2230,6 +3727,7 @@ impl<'a> Parser<'a> {
                 BinaryOp::Shr
             };
             self.advance()?;
+            self.chain_operator()?;
             let right = self.parse_additive()?;
             left = Expression::Binary {
                 op,
@@ -2237,6 +3735,7 @@ impl<'a> Parser<'a> {
                 right: Box::new(right),
             };
         }
+        self.operators = outer;
 
         Ok(left)
     }
@@ -2244,6 +3743,7 @@ impl<'a> Parser<'a> {
     /// Parse additive operators (+, -)
     fn parse_additive(&mut self) -> Result<Expression, ParseError> {
         let mut left = self.parse_multiplicative()?;
+        let outer = self.operators;
 
         while self.check(&TokenKind::Plus) || self.check(&TokenKind::Minus) {
             let op = if self.check(&TokenKind::Plus) {
@@ -2252,6 +3752,7 @@ impl<'a> Parser<'a> {
                 BinaryOp::Sub
             };
             self.advance()?;
+            self.chain_operator()?;
             let right = self.parse_multiplicative()?;
             left = Expression::Binary {
                 op,
@@ -2259,6 +3760,7 @@ impl<'a> Parser<'a> {
                 right: Box::new(right),
             };
         }
+        self.operators = outer;
 
         Ok(left)
     }
@@ -2266,6 +3768,7 @@ impl<'a> Parser<'a> {
     /// Parse multiplicative operators (*, /, %)
     fn parse_multiplicative(&mut self) -> Result<Expression, ParseError> {
         let mut left = self.parse_unary()?;
+        let outer = self.operators;
 
         while self.check(&TokenKind::Star)
             || self.check(&TokenKind::Slash)
@@ -2278,6 +3781,7 @@ impl<'a> Parser<'a> {
                 _ => unreachable!(),
             };
             self.advance()?;
+            self.chain_operator()?;
             let right = self.parse_unary()?;
             left = Expression::Binary {
                 op,
@@ -2285,14 +3789,20 @@ impl<'a> Parser<'a> {
                 right: Box::new(right),
             };
         }
+        self.operators = outer;
 
         Ok(left)
     }
 
     /// Parse unary operators (!, -, &, *, ++, --)
     fn parse_unary(&mut self) -> Result<Expression, ParseError> {
+        self.nested(Self::parse_unary_operator)
+    }
+
+    fn parse_unary_operator(&mut self) -> Result<Expression, ParseError> {
         match &self.current_token.kind {
-            TokenKind::Not => {
+            // The lexer emits `!` as Bang; in prefix position it is logical not
+            TokenKind::Not | TokenKind::Bang => {
                 self.advance()?;
                 let expr = self.parse_unary()?;
                 Ok(Expression::Unary {
@@ -2344,11 +3854,38 @@ impl<'a> Parser<'a> {
         }
     }
 
-    /// Parse postfix operators (++, --, function calls, field access, array indexing, macro calls)
+    /// Check for `expr?`, which propagates an error: a `?` followed by
+    /// an operand starts a conditional instead
+    fn at_error_propagation(&mut self) -> Result<bool, ParseError> {
+        if !self.check(&TokenKind::Question) {
+            return Ok(false);
+        }
+        Ok(matches!(
+            self.peek_ahead(1)?.map(|token| token.kind),
+            Some(
+                TokenKind::Semicolon
+                    | TokenKind::RParen
+                    | TokenKind::RBracket
+                    | TokenKind::RBrace
+                    | TokenKind::Comma
+                    | TokenKind::Dot
+                    | TokenKind::Question
+            )
+        ))
+    }
+
+    /// Parse postfix operators (++, --, ?, function calls, field access, array indexing, macro calls)
     fn parse_postfix(&mut self) -> Result<Expression, ParseError> {
         let mut expr = self.parse_primary()?;
 
         loop {
+            if self.at_error_propagation()? {
+                self.advance()?;
+                expr = Expression::ErrorProp {
+                    expr: Box::new(expr),
+                };
+                continue;
+            }
             match &self.current_token.kind {
                 TokenKind::Inc => {
                     self.advance()?;
@@ -2371,22 +3908,35 @@ impl<'a> Parser<'a> {
                         self.advance()?;
 
                         // Parse macro arguments based on delimiter
-                        let args = if self.check(&TokenKind::LParen) {
-                            self.parse_macro_args(TokenKind::LParen, TokenKind::RParen)?
+                        let (args, delimiter) = if self.check(&TokenKind::LParen) {
+                            (
+                                self.parse_macro_args(TokenKind::LParen, TokenKind::RParen)?,
+                                MacroDelimiter::Parens,
+                            )
                         } else if self.check(&TokenKind::LBracket) {
-                            self.parse_macro_args(TokenKind::LBracket, TokenKind::RBracket)?
+                            (
+                                self.parse_macro_args(TokenKind::LBracket, TokenKind::RBracket)?,
+                                MacroDelimiter::Brackets,
+                            )
                         } else if self.check(&TokenKind::LBrace) {
-                            self.parse_macro_args(TokenKind::LBrace, TokenKind::RBrace)?
+                            (
+                                self.parse_macro_args(TokenKind::LBrace, TokenKind::RBrace)?,
+                                MacroDelimiter::Braces,
+                            )
                         } else {
                             return Err(ParseError::new(
                                 self.current_token.span,
                                 "expected (, [, or { after macro name",
                                 vec!["(".to_string(), "[".to_string(), "{".to_string()],
-                                format!("{:?}", self.current_token.kind),
+                                self.found(),
                             ));
                         };
 
-                        expr = Expression::MacroCall { name, args };
+                        expr = Expression::MacroCall {
+                            name,
+                            args,
+                            delimiter,
+                        };
                     } else {
                         // ! is error propagation operator, not a macro call
                         expr = Expression::ErrorProp {
@@ -2408,6 +3958,7 @@ impl<'a> Parser<'a> {
                             expr = Expression::MacroCall {
                                 name: macro_name,
                                 args,
+                                delimiter: MacroDelimiter::Parens,
                             };
                             continue;
                         }
@@ -2448,6 +3999,7 @@ impl<'a> Parser<'a> {
                             expr = Expression::MacroCall {
                                 name: macro_name,
                                 args,
+                                delimiter: MacroDelimiter::Brackets,
                             };
                             continue;
                         }
@@ -2530,6 +4082,7 @@ impl<'a> Parser<'a> {
                             expr = Expression::MacroCall {
                                 name: macro_name,
                                 args,
+                                delimiter: MacroDelimiter::Braces,
                             };
                             continue;
                         }
@@ -2563,7 +4116,7 @@ impl<'a> Parser<'a> {
                         // Regular field access
                         let field = match &self.current_token.kind {
                             TokenKind::Ident(n) => {
-                                let ident = Ident::new(n.clone());
+                                let ident = Ident::spanned(n.clone(), self.current_token.span);
                                 self.advance()?;
                                 ident
                             }
@@ -2572,7 +4125,7 @@ impl<'a> Parser<'a> {
                                     self.current_token.span,
                                     "expected field name or tuple index",
                                     vec!["identifier".to_string(), "integer".to_string()],
-                                    format!("{:?}", self.current_token.kind),
+                                    self.found(),
                                 ));
                             }
                         };
@@ -2587,7 +4140,7 @@ impl<'a> Parser<'a> {
                     self.advance()?;
                     let field = match &self.current_token.kind {
                         TokenKind::Ident(n) => {
-                            let ident = Ident::new(n.clone());
+                            let ident = Ident::spanned(n.clone(), self.current_token.span);
                             self.advance()?;
                             ident
                         }
@@ -2596,7 +4149,7 @@ impl<'a> Parser<'a> {
                                 self.current_token.span,
                                 "expected field name",
                                 vec!["identifier".to_string()],
-                                format!("{:?}", self.current_token.kind),
+                                self.found(),
                             ));
                         }
                     };
@@ -2687,15 +4240,7 @@ impl<'a> Parser<'a> {
     /// Assumes current token is LBrace
     fn is_struct_initializer(&self) -> Result<bool, ParseError> {
         // Create a temporary lexer for lookahead
-        let mut temp_lexer = Lexer {
-            source: self.lexer.source,
-            chars: self.lexer.source[self.lexer.position..]
-                .char_indices()
-                .peekable(),
-            position: self.lexer.position,
-            line: self.lexer.line,
-            column: self.lexer.column,
-        };
+        let mut temp_lexer = self.lexer.clone();
 
         // Check if next token is a dot (designated initializer syntax)
         if let Ok(token) = temp_lexer.next_token() {
@@ -2719,7 +4264,7 @@ impl<'a> Parser<'a> {
             // Parse field name
             let field_name = match &self.current_token.kind {
                 TokenKind::Ident(name) => {
-                    let ident = Ident::new(name.clone());
+                    let ident = Ident::spanned(name.clone(), self.current_token.span);
                     self.advance()?;
                     ident
                 }
@@ -2728,7 +4273,7 @@ impl<'a> Parser<'a> {
                         self.current_token.span,
                         "expected field name after '.'",
                         vec!["identifier".to_string()],
-                        format!("{:?}", self.current_token.kind),
+                        self.found(),
                     ));
                 }
             };
@@ -2760,35 +4305,32 @@ impl<'a> Parser<'a> {
 
     /// Parse primary expressions (literals, identifiers, parenthesized expressions, type-scoped calls)
     fn parse_primary(&mut self) -> Result<Expression, ParseError> {
+        // `fn(` starts a lambda; `fn` alone is an ordinary name
+        let lambda = matches!(&self.current_token.kind, TokenKind::Ident(name) if name == "fn")
+            && matches!(
+                self.peek_ahead(1)?.map(|token| token.kind),
+                Some(TokenKind::LParen)
+            );
         match &self.current_token.kind {
             TokenKind::IntLiteral(s) => {
-                let val = s.parse::<i64>().map_err(|_| {
-                    ParseError::new(
-                        self.current_token.span,
-                        "invalid integer literal",
-                        vec![],
-                        s.clone(),
-                    )
+                let literal = parse_int_literal(s).map_err(|message| {
+                    ParseError::new(self.current_token.span, message, vec![], s.clone())
                 })?;
                 self.advance()?;
-                Ok(Expression::Literal(Literal::Int(val)))
+                Ok(Expression::Literal(literal))
             }
             TokenKind::FloatLiteral(s) => {
-                let val = s.parse::<f64>().map_err(|_| {
-                    ParseError::new(
-                        self.current_token.span,
-                        "invalid float literal",
-                        vec![],
-                        s.clone(),
-                    )
+                let literal = parse_float_literal(s).map_err(|message| {
+                    ParseError::new(self.current_token.span, message, vec![], s.clone())
                 })?;
                 self.advance()?;
-                Ok(Expression::Literal(Literal::Float(val)))
+                Ok(Expression::Literal(literal))
             }
-            TokenKind::StringLiteral(s) => {
-                let val = s.clone();
-                self.advance()?;
-                Ok(Expression::Literal(Literal::String(val)))
+            TokenKind::StringLiteral(_)
+            | TokenKind::RawStringLiteral(_)
+            | TokenKind::ByteStringLiteral(_)
+            | TokenKind::WideStringLiteral(_) => {
+                Ok(Expression::Literal(self.parse_string_literals()?))
             }
             TokenKind::CharLiteral(c) => {
                 let val = *c;
@@ -2804,6 +4346,43 @@ impl<'a> Parser<'a> {
                 self.advance()?;
                 Ok(Expression::Literal(Literal::Null))
             }
+            TokenKind::If => self.parse_if_expression(),
+            TokenKind::Match => self.parse_match_expression(),
+            TokenKind::Ident(_) if lambda => self.parse_lambda(),
+            TokenKind::Ident(name) if name == "sizeof" || name == "alignof" => {
+                let sizeof = name == "sizeof";
+                self.advance()?;
+                self.expect(TokenKind::LParen)?;
+                // The operand is a type when one spans the parentheses, and
+                // an expression otherwise
+                if self.is_type_token() {
+                    let saved_lexer = self.lexer.clone();
+                    let saved_token = self.current_token.clone();
+                    let saved_buffer = self.token_buffer.clone();
+                    match self.parse_type() {
+                        Ok(ty) if self.check(&TokenKind::RParen) => {
+                            self.advance()?;
+                            return Ok(if sizeof {
+                                Expression::Sizeof { ty }
+                            } else {
+                                Expression::Alignof { ty }
+                            });
+                        }
+                        _ => {
+                            self.lexer = saved_lexer;
+                            self.current_token = saved_token;
+                            self.token_buffer = saved_buffer;
+                        }
+                    }
+                }
+                let expr = Box::new(self.parse_expression()?);
+                self.expect(TokenKind::RParen)?;
+                Ok(if sizeof {
+                    Expression::SizeofExpr { expr }
+                } else {
+                    Expression::AlignofExpr { expr }
+                })
+            }
             TokenKind::LParen => {
                 // Could be:
                 // 1. Cast expression: (Type)expr
@@ -2826,10 +4405,9 @@ impl<'a> Parser<'a> {
                 if is_cast {
                     // Try to parse as cast: (Type)expr
                     // Save position in case we need to backtrack
-                    let saved_position = self.lexer.position;
-                    let saved_line = self.lexer.line;
-                    let saved_column = self.lexer.column;
+                    let saved_lexer = self.lexer.clone();
                     let saved_token = self.current_token.clone();
+                    let saved_buffer = self.token_buffer.clone();
 
                     // Try to parse type
                     match self.parse_type() {
@@ -2845,20 +4423,16 @@ impl<'a> Parser<'a> {
                                 });
                             } else {
                                 // Not a cast, restore position and parse as expression
-                                self.lexer.position = saved_position;
-                                self.lexer.line = saved_line;
-                                self.lexer.column = saved_column;
+                                self.lexer = saved_lexer;
                                 self.current_token = saved_token;
-                                self.advance()?;
+                                self.token_buffer = saved_buffer;
                             }
                         }
                         Err(_) => {
                             // Failed to parse type, restore position and parse as expression
-                            self.lexer.position = saved_position;
-                            self.lexer.line = saved_line;
-                            self.lexer.column = saved_column;
+                            self.lexer = saved_lexer;
                             self.current_token = saved_token;
-                            self.advance()?;
+                            self.token_buffer = saved_buffer;
                         }
                     }
                 }
@@ -2965,7 +4539,7 @@ impl<'a> Parser<'a> {
                 self.expect(TokenKind::Dot)?;
                 let method = match &self.current_token.kind {
                     TokenKind::Ident(n) => {
-                        let ident = Ident::new(n.clone());
+                        let ident = Ident::spanned(n.clone(), self.current_token.span);
                         self.advance()?;
                         ident
                     }
@@ -2974,7 +4548,7 @@ impl<'a> Parser<'a> {
                             self.current_token.span,
                             "expected method name",
                             vec!["identifier".to_string()],
-                            format!("{:?}", self.current_token.kind),
+                            self.found(),
                         ));
                     }
                 };
@@ -3015,8 +4589,11 @@ impl<'a> Parser<'a> {
                 }
             }
             TokenKind::Ident(n) => {
-                let ident = Ident::new(n.clone());
+                let ident = Ident::spanned(n.clone(), self.current_token.span);
                 self.advance()?;
+                if self.check(&TokenKind::DoubleColon) {
+                    return self.parse_path_expression(ident);
+                }
                 Ok(Expression::Ident(ident))
             }
             TokenKind::LBrace => {
@@ -3035,7 +4612,7 @@ impl<'a> Parser<'a> {
                             "identifier".to_string(),
                             "(".to_string(),
                         ],
-                        format!("{:?}", self.current_token.kind),
+                        self.found(),
                     ))
                 }
             }
@@ -3047,16 +4624,67 @@ impl<'a> Parser<'a> {
                     "identifier".to_string(),
                     "(".to_string(),
                 ],
-                format!("{:?}", self.current_token.kind),
+                self.found(),
             )),
         }
     }
 
+    /// Parse the rest of a Rust-style path after its first segment
+    ///
+    /// `Type::method(args)` is a type-scoped call, like `@Type.method(args)`;
+    /// `Enum::Variant` is the same qualified access as `Enum.Variant`.
+    fn parse_path_expression(&mut self, first: Ident) -> Result<Expression, ParseError> {
+        self.expect(TokenKind::DoubleColon)?;
+        let name = match &self.current_token.kind {
+            TokenKind::Ident(n) => Ident::spanned(n.clone(), self.current_token.span),
+            _ => {
+                return Err(ParseError::new(
+                    self.current_token.span,
+                    "expected identifier after '::'",
+                    vec!["identifier".to_string()],
+                    self.found(),
+                ));
+            }
+        };
+        self.advance()?;
+
+        if !self.check(&TokenKind::LParen) {
+            return Ok(Expression::FieldAccess {
+                expr: Box::new(Expression::Ident(first)),
+                field: name,
+            });
+        }
+
+        self.advance()?;
+        let mut args = Vec::new();
+        if !self.check(&TokenKind::RParen) {
+            loop {
+                args.push(self.parse_expression()?);
+                if self.check(&TokenKind::Comma) {
+                    self.advance()?;
+                } else {
+                    break;
+                }
+            }
+        }
+        self.expect(TokenKind::RParen)?;
+
+        Ok(Expression::TypeScopedCall {
+            ty: Type::Ident(first),
+            method: name,
+            args,
+        })
+    }
+
     /// Parse a generic type parameter with alternating parentheses and brackets
     /// Supports: T, Inner[T], Inner[Type(T)], etc.
     fn parse_generic_type_param(&mut self) -> Result<Type, ParseError> {
+        self.nested(Self::parse_generic_type_param_contents)
+    }
+
+    fn parse_generic_type_param_contents(&mut self) -> Result<Type, ParseError> {
         // Parse base type
-        let mut base_type = self.parse_base_type_for_generic()?;
+        let mut base_type = self.parse_pointer_suffix()?;
 
         // Check for nested generics with brackets
         if self.check(&TokenKind::LBracket) {
@@ -3085,9 +4713,23 @@ impl<'a> Parser<'a> {
         Ok(base_type)
     }
 
+    /// Parse the base type of a type argument and the pointers to it, as
+    /// `Shape*` in `@Vec(Shape*)`
+    fn parse_pointer_suffix(&mut self) -> Result<Type, ParseError> {
+        let mut ty = self.parse_base_type_for_generic()?;
+        while self.check(&TokenKind::Star) {
+            self.advance()?;
+            ty = Type::Pointer {
+                ty: Box::new(ty),
+                mutable: true,
+            };
+        }
+        Ok(ty)
+    }
+
     /// Parse nested generic parameter (alternates back to parentheses)
     fn parse_nested_generic_param(&mut self) -> Result<Type, ParseError> {
-        let mut base_type = self.parse_base_type_for_generic()?;
+        let mut base_type = self.parse_pointer_suffix()?;
 
         // Check for nested generics with parentheses (alternating)
         if self.check(&TokenKind::LParen) {
@@ -3139,6 +4781,35 @@ impl<'a> Parser<'a> {
                 self.advance()?;
                 Ok(Type::Primitive(PrimitiveType::U64))
             }
+            TokenKind::I8 => {
+                self.advance()?;
+                Ok(Type::Primitive(PrimitiveType::I8))
+            }
+            TokenKind::I16 => {
+                self.advance()?;
+                Ok(Type::Primitive(PrimitiveType::I16))
+            }
+            TokenKind::Isize => {
+                self.advance()?;
+                Ok(Type::Primitive(PrimitiveType::Isize))
+            }
+            TokenKind::U8 => {
+                self.advance()?;
+                Ok(Type::Primitive(PrimitiveType::U8))
+            }
+            TokenKind::U16 => {
+                self.advance()?;
+                Ok(Type::Primitive(PrimitiveType::U16))
+            }
+            TokenKind::Usize => {
+                self.advance()?;
+                Ok(Type::Primitive(PrimitiveType::Usize))
+            }
+            TokenKind::Long | TokenKind::ULong => {
+                let ty = c_integer_type(&self.current_token.kind);
+                self.advance()?;
+                Ok(ty)
+            }
             TokenKind::Float => {
                 self.advance()?;
                 Ok(Type::Primitive(PrimitiveType::Float))
@@ -3164,7 +4835,7 @@ impl<'a> Parser<'a> {
                 Ok(Type::Primitive(PrimitiveType::Void))
             }
             TokenKind::Ident(name) => {
-                let ident = Ident::new(name.clone());
+                let ident = Ident::spanned(name.clone(), self.current_token.span);
                 self.advance()?;
                 Ok(Type::Ident(ident))
             }
@@ -3172,13 +4843,17 @@ impl<'a> Parser<'a> {
                 self.current_token.span,
                 "expected type in generic parameter",
                 vec!["type".to_string()],
-                format!("{:?}", self.current_token.kind),
+                self.found(),
             )),
         }
     }
 
     /// Parse a type expression
     fn parse_type(&mut self) -> Result<Type, ParseError> {
+        self.nested(Self::parse_type_contents)
+    }
+
+    fn parse_type_contents(&mut self) -> Result<Type, ParseError> {
         // Check for mutable reference types (var & or &mut)
         if self.check(&TokenKind::Var) {
             self.advance()?;
@@ -3222,6 +4897,26 @@ impl<'a> Parser<'a> {
             });
         }
 
+        // Check for the const qualifier: const char* points to chars that
+        // cannot be written through it, and a const parameter is as
+        // immutable as any other
+        let constant = self.check(&TokenKind::Const);
+        if constant {
+            self.advance()?;
+        }
+
+        // Check for the volatile qualifier (volatile u32* points to a volatile u32)
+        let volatile = self.check(&TokenKind::Volatile);
+        if volatile {
+            self.advance()?;
+        }
+
+        let function_type = matches!(&self.current_token.kind, TokenKind::Ident(name) if name == "fn")
+            && matches!(
+                self.peek_ahead(1)?.map(|token| token.kind),
+                Some(TokenKind::LParen)
+            );
+
         // Parse base type
         let mut base_type = match &self.current_token.kind {
             TokenKind::Int => {
@@ -3244,6 +4939,35 @@ impl<'a> Parser<'a> {
                 self.advance()?;
                 Type::Primitive(PrimitiveType::U64)
             }
+            TokenKind::I8 => {
+                self.advance()?;
+                Type::Primitive(PrimitiveType::I8)
+            }
+            TokenKind::I16 => {
+                self.advance()?;
+                Type::Primitive(PrimitiveType::I16)
+            }
+            TokenKind::Isize => {
+                self.advance()?;
+                Type::Primitive(PrimitiveType::Isize)
+            }
+            TokenKind::U8 => {
+                self.advance()?;
+                Type::Primitive(PrimitiveType::U8)
+            }
+            TokenKind::U16 => {
+                self.advance()?;
+                Type::Primitive(PrimitiveType::U16)
+            }
+            TokenKind::Usize => {
+                self.advance()?;
+                Type::Primitive(PrimitiveType::Usize)
+            }
+            TokenKind::Long | TokenKind::ULong => {
+                let ty = c_integer_type(&self.current_token.kind);
+                self.advance()?;
+                ty
+            }
             TokenKind::Float => {
                 self.advance()?;
                 Type::Primitive(PrimitiveType::Float)
@@ -3287,8 +5011,24 @@ impl<'a> Parser<'a> {
                 self.expect(TokenKind::RParen)?;
                 Type::Tuple { types }
             }
+            // fn(int, int) -> int, a function pointer; without `->` it
+            // returns void
+            TokenKind::Ident(_) if function_type => {
+                self.advance()?;
+                let params = self.parse_function_type_params()?;
+                let return_type = if self.check(&TokenKind::Arrow) {
+                    self.advance()?;
+                    self.parse_type()?
+                } else {
+                    Type::Primitive(PrimitiveType::Void)
+                };
+                Type::Function {
+                    params,
+                    return_type: Box::new(return_type),
+                }
+            }
             TokenKind::Ident(name) => {
-                let ident = Ident::new(name.clone());
+                let ident = Ident::spanned(name.clone(), self.current_token.span);
                 self.advance()?;
                 Type::Ident(ident)
             }
@@ -3297,7 +5037,7 @@ impl<'a> Parser<'a> {
                     self.current_token.span,
                     "expected type",
                     vec!["type".to_string()],
-                    format!("{:?}", self.current_token.kind),
+                    self.found(),
                 ));
             }
         };
@@ -3335,45 +5075,42 @@ impl<'a> Parser<'a> {
                 base_type = Type::Slice {
                     ty: Box::new(base_type),
                 };
-            } else {
-                // Array type with size
-                match &self.current_token.kind {
-                    TokenKind::IntLiteral(s) => {
-                        let size = s.parse::<usize>().map_err(|_| {
-                            ParseError::new(
-                                self.current_token.span,
-                                "invalid array size",
-                                vec![],
-                                s.clone(),
-                            )
-                        })?;
-                        self.advance()?;
-                        self.expect(TokenKind::RBracket)?;
-                        base_type = Type::Array {
-                            ty: Box::new(base_type),
-                            size: Some(size),
-                        };
-                    }
-                    _ => {
-                        return Err(ParseError::new(
-                            self.current_token.span,
-                            "expected array size",
-                            vec!["integer".to_string()],
-                            format!("{:?}", self.current_token.kind),
-                        ));
-                    }
-                }
+            } else {
+                // Array type with size
+                let size = self.parse_array_size()?;
+                self.expect(TokenKind::RBracket)?;
+                base_type = Type::Array {
+                    ty: Box::new(base_type),
+                    size: Some(size),
+                };
             }
         }
 
-        // Check for postfix pointer/reference syntax (C-style: int* or int&)
+        if volatile {
+            base_type = Type::Volatile {
+                ty: Box::new(base_type),
+            };
+        }
+
+        // Check for postfix pointer/reference syntax (C-style: int* or int&).
+        // Each level wraps the type once more, so it counts as nesting too.
+        let mut levels = 0;
         while self.check(&TokenKind::Star) || self.check(&TokenKind::BitAnd) {
+            levels += 1;
+            if self.depth + levels > MAX_NESTING_DEPTH {
+                return Err(self.nesting_error());
+            }
             if self.check(&TokenKind::Star) {
                 self.advance()?;
                 base_type = Type::Pointer {
                     ty: Box::new(base_type),
-                    mutable: true,
+                    mutable: !(constant && levels == 1),
                 };
+                // int* restrict p promises p is the only way to reach what it
+                // points to, which a raw pointer neither needs nor checks
+                if self.restrict_at(0)? {
+                    self.advance()?;
+                }
             } else if self.check(&TokenKind::BitAnd) {
                 self.advance()?;
                 // Check for mutable reference (&mut)
@@ -3390,6 +5127,18 @@ impl<'a> Parser<'a> {
             }
         }
 
+        // C function pointer: int(*)(int, int) points to a function taking
+        // two ints and returning one
+        if self.check(&TokenKind::LParen) && self.function_pointer_at(1)? {
+            self.advance()?;
+            self.expect(TokenKind::Star)?;
+            self.expect(TokenKind::RParen)?;
+            base_type = Type::Function {
+                params: self.parse_function_type_params()?,
+                return_type: Box::new(base_type),
+            };
+        }
+
         Ok(base_type)
     }
 }
@@ -3584,6 +5333,45 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_struct_member_visibility() {
+        let source = "struct Counter {\n    pub int count;\n    static int limit;\n    static void clamp(var &self) {}\n    pub int get(&self) { return self.count; }\n    void reset(var &self) {}\n}\n";
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+        let Item::Struct(s) = &file.items[0] else {
+            panic!("Expected struct item");
+        };
+        let fields: Vec<(&str, &Visibility)> = s
+            .fields
+            .iter()
+            .map(|f| (f.name.name.as_str(), &f.visibility))
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                ("count", &Visibility::Public),
+                ("limit", &Visibility::Private)
+            ]
+        );
+        let methods: Vec<(&str, &Visibility)> = s
+            .methods
+            .iter()
+            .map(|m| (m.name.name.as_str(), &m.visibility))
+            .collect();
+        assert_eq!(
+            methods,
+            [
+                ("clamp", &Visibility::Private),
+                ("get", &Visibility::Public),
+                ("reset", &Visibility::Public)
+            ]
+        );
+
+        let crusty = crate::codegen::CodeGenerator::new(crate::codegen::TargetLanguage::Crusty)
+            .generate(&file);
+        assert!(crusty.contains("    static int limit;\n"), "{}", crusty);
+        assert!(crusty.contains("    static void clamp("), "{}", crusty);
+    }
+
     #[test]
     fn test_parse_enum() {
         let source = "enum Color { Red, Green, Blue }";
@@ -3633,6 +5421,212 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_tagged_union() {
+        let source = "enum Shape { Circle(float radius), Rect(float w, float h), Empty }";
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+
+        match &file.items[0] {
+            Item::Enum(e) => {
+                assert_eq!(e.variants.len(), 3);
+                assert_eq!(
+                    e.variants[0].fields,
+                    vec![Param {
+                        name: Ident::new("radius"),
+                        ty: Type::Primitive(PrimitiveType::Float),
+                        variadic: false,
+                    }]
+                );
+                let names: Vec<&str> = e.variants[1]
+                    .fields
+                    .iter()
+                    .map(|field| field.name.name.as_str())
+                    .collect();
+                assert_eq!(names, ["w", "h"]);
+                assert!(e.variants[2].fields.is_empty());
+            }
+            _ => panic!("Expected enum item"),
+        }
+    }
+
+    #[test]
+    fn test_parse_trait_and_impl() {
+        let source = "trait Shape {\n    float area(&self);\n    void scale(var &self, float k);\n}\nstruct Circle {\n    float r;\n}\nimpl Shape for Circle {\n    float area(&self) {\n        return self.r;\n    }\n    void scale(var &self, float k) {\n        self.r = self.r * k;\n    }\n}\n";
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+
+        let Item::Trait(trait_def) = &file.items[0] else {
+            panic!("Expected trait item, got {:?}", file.items[0]);
+        };
+        assert_eq!(trait_def.name.name, "Shape");
+        assert_eq!(trait_def.methods.len(), 2);
+        assert_eq!(
+            trait_def.methods[0].return_type,
+            Some(Type::Primitive(PrimitiveType::Float))
+        );
+        assert_eq!(trait_def.methods[1].return_type, None);
+        assert_eq!(trait_def.methods[1].params.len(), 2);
+
+        let Item::Impl(impl_block) = &file.items[2] else {
+            panic!("Expected impl item, got {:?}", file.items[2]);
+        };
+        assert_eq!(impl_block.trait_name.name, "Shape");
+        assert_eq!(impl_block.ty, Type::Ident(Ident::new("Circle")));
+        let names: Vec<&str> = impl_block
+            .methods
+            .iter()
+            .map(|method| method.name.name.as_str())
+            .collect();
+        assert_eq!(names, ["area", "scale"]);
+
+        let generic = "impl Shape for Pair(T, U) { float area(&self) { return 0.0; } }";
+        let file = Parser::new(generic).unwrap().parse_file().unwrap();
+        let Item::Impl(impl_block) = &file.items[0] else {
+            panic!("Expected impl item, got {:?}", file.items[0]);
+        };
+        assert_eq!(impl_block.generics, [Ident::new("T"), Ident::new("U")]);
+        assert_eq!(
+            impl_block.ty,
+            Type::Generic {
+                base: Box::new(Type::Ident(Ident::new("Pair"))),
+                args: vec![Type::Ident(Ident::new("T")), Type::Ident(Ident::new("U"))],
+            }
+        );
+
+        let objects = "void f() { let Shape* s = make(); var Shape* t = s; }";
+        let file = Parser::new(objects).unwrap().parse_file().unwrap();
+        let Item::Function(func) = &file.items[0] else {
+            panic!("Expected function, got {:?}", file.items[0]);
+        };
+        let pointer = Type::Pointer {
+            ty: Box::new(Type::Ident(Ident::new("Shape"))),
+            mutable: true,
+        };
+        assert!(
+            matches!(&func.body.statements[0], Statement::Let { ty: Some(ty), .. } if *ty == pointer)
+        );
+        assert!(
+            matches!(&func.body.statements[1], Statement::Var { ty: Some(ty), .. } if *ty == pointer)
+        );
+
+        let collection = "void f() { var Vec<Shape*> v = @Vec(Shape*).new(); }";
+        let file = Parser::new(collection).unwrap().parse_file().unwrap();
+        let Item::Function(func) = &file.items[0] else {
+            panic!("Expected function, got {:?}", file.items[0]);
+        };
+        let Statement::Var {
+            ty: Some(ty),
+            init: Some(Expression::ExplicitGenericCall { generics, .. }),
+            ..
+        } = &func.body.statements[0]
+        else {
+            panic!("Expected typed var, got {:?}", func.body.statements[0]);
+        };
+        let vec = Type::Generic {
+            base: Box::new(Type::Ident(Ident::new("Vec"))),
+            args: vec![pointer.clone()],
+        };
+        assert_eq!(*ty, vec);
+        assert_eq!(*generics, [pointer]);
+
+        let interface = "interface Shape { float area(&self); }";
+        let file = Parser::new(interface).unwrap().parse_file().unwrap();
+        assert!(matches!(&file.items[0], Item::Trait(t) if t.name.name == "Shape"));
+
+        let defaults =
+            "trait Shape { float area(&self); float twice(&self) { return self.area() * 2.0; } }";
+        let file = Parser::new(defaults).unwrap().parse_file().unwrap();
+        let Item::Trait(trait_def) = &file.items[0] else {
+            panic!("Expected trait item, got {:?}", file.items[0]);
+        };
+        assert!(trait_def.methods[0].body.is_none());
+        assert_eq!(
+            trait_def.methods[1]
+                .body
+                .as_ref()
+                .map(|body| body.statements.len()),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn test_trait_words_still_name_variables_and_functions() {
+        let source = "int impl(int trait) {\n    let int interface = trait + 1;\n    return interface;\n}\ninterface Named {\n    int name(&self);\n}\nvoid main() {\n    var int impl = 2;\n    impl = impl + 1;\n}\n";
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+        assert!(matches!(&file.items[0], Item::Function(f) if f.name.name == "impl"));
+        assert!(matches!(&file.items[1], Item::Trait(t) if t.name.name == "Named"));
+        let Item::Function(main) = &file.items[2] else {
+            panic!("Expected function, got {:?}", file.items[2]);
+        };
+        assert!(
+            matches!(&main.body.statements[0], Statement::Var { name, .. } if name.name == "impl")
+        );
+    }
+
+    #[test]
+    fn test_parse_file_partial_keeps_items_before_error() {
+        let source =
+            "struct Point { int x; }\nint f() { return 1; }\nint g( {\nint h() { return 2; }\n";
+        let partial = Parser::new(source).unwrap().parse_file_partial();
+        assert_eq!(partial.file.items.len(), 2);
+        assert!(matches!(&partial.file.items[0], Item::Struct(s) if s.name.name == "Point"));
+        assert!(matches!(&partial.file.items[1], Item::Function(f) if f.name.name == "f"));
+        assert_eq!(partial.errors.len(), 1);
+        assert_eq!(partial.errors[0].span.start.line, 3);
+
+        let complete = Parser::new("int f() { return 1; }")
+            .unwrap()
+            .parse_file_partial();
+        assert!(complete.errors.is_empty());
+        assert_eq!(complete.file.items.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_file_partial_recovers_from_each_error() {
+        let source = "int f(int a) {\n    int b = a + ;\n    if (a > 0) {\n        return a * ;\n    }\n    return b;\n}\nint g(int a int b) {\n    return a;\n}\nvoid h() {\n    foo(1, 2;\n    bar();\n}\nstruct Point { int x; }\nint ok() { return 1; }\n";
+        let partial = Parser::new(source).unwrap().parse_file_partial();
+        let lines: Vec<usize> = partial
+            .errors
+            .iter()
+            .map(|error| error.span.start.line)
+            .collect();
+        assert_eq!(lines, [2, 4, 8, 12]);
+
+        // Items an error falls inside are dropped
+        let names: Vec<&str> = partial
+            .file
+            .items
+            .iter()
+            .map(|item| match item {
+                Item::Function(func) => func.name.name.as_str(),
+                Item::Struct(s) => s.name.name.as_str(),
+                item => panic!("unexpected item {:?}", item),
+            })
+            .collect();
+        assert_eq!(names, ["Point", "ok"]);
+
+        let many = format!("void f() {{\n{}}}\n", "    x = ;\n".repeat(40));
+        let partial = Parser::new(&many).unwrap().parse_file_partial();
+        assert_eq!(partial.errors.len(), MAX_PARSE_ERRORS);
+    }
+
+    #[test]
+    fn test_parse_generic_function() {
+        let source = "T max(T)(T a, T b) { return a; }\nPoint origin() { return p; }\n";
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+        let Item::Function(max) = &file.items[0] else {
+            panic!("Expected Item::Function, got {:?}", file.items[0]);
+        };
+        assert_eq!(max.generics, vec![Ident::new("T")]);
+        assert_eq!(max.params.len(), 2);
+        assert_eq!(max.return_type, Some(Type::Ident(Ident::new("T"))));
+
+        let Item::Function(origin) = &file.items[1] else {
+            panic!("Expected Item::Function, got {:?}", file.items[1]);
+        };
+        assert!(origin.generics.is_empty());
+        assert_eq!(origin.return_type, Some(Type::Ident(Ident::new("Point"))));
+    }
+
     #[test]
     fn test_parse_typedef() {
         let source = "typedef int MyInt;";
@@ -3831,6 +5825,67 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_do_while_statement() {
+        let source = "int main() { do { x = x - 1; } while (x > 0); return x; }";
+        let mut parser = Parser::new(source).unwrap();
+
+        let file = parser.parse_file().unwrap();
+        match &file.items[0] {
+            Item::Function(func) => {
+                assert_eq!(func.body.statements.len(), 2);
+                match &func.body.statements[0] {
+                    Statement::DoWhile { body, condition } => {
+                        assert_eq!(body.statements.len(), 1);
+                        assert!(matches!(
+                            condition,
+                            Expression::Binary {
+                                op: BinaryOp::Gt,
+                                ..
+                            }
+                        ));
+                    }
+                    other => panic!("Expected do-while, got {:?}", other),
+                }
+            }
+            _ => panic!("Expected function"),
+        }
+
+        let missing_semicolon = "int main() { do { } while (true) }";
+        assert!(Parser::new(missing_semicolon)
+            .unwrap()
+            .parse_file()
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse_switch_statement() {
+        let source = "int main() { switch (x) { case 1: case -2: { return 1; } case Color.Red: { } default: { return 0; } } }";
+        let mut parser = Parser::new(source).unwrap();
+
+        let file = parser.parse_file().unwrap();
+        match &file.items[0] {
+            Item::Function(func) => match &func.body.statements[0] {
+                Statement::Switch {
+                    expr,
+                    cases,
+                    default,
+                } => {
+                    assert!(matches!(expr, Expression::Ident(_)));
+                    assert_eq!(cases.len(), 2);
+                    assert_eq!(cases[0].values.len(), 2);
+                    assert!(matches!(cases[1].values[0], Expression::FieldAccess { .. }));
+                    assert_eq!(default.as_ref().unwrap().statements.len(), 1);
+                }
+                other => panic!("Expected switch, got {:?}", other),
+            },
+            _ => panic!("Expected function"),
+        }
+
+        let missing_colon = "int main() { switch (x) { case 1 { } } }";
+        assert!(Parser::new(missing_colon).unwrap().parse_file().is_err());
+    }
+
     #[test]
     fn test_parse_return_statement() {
         let source = "int main() { return 42; }";
@@ -4052,74 +6107,664 @@ fn test_parse_binary_expression() {
 }
 
 #[test]
-fn test_parse_function_call() {
-    let source = "int main() { return foo(1, 2); }";
-    let mut parser = Parser::new(source).unwrap();
+fn test_parse_function_call() {
+    let source = "int main() { return foo(1, 2); }";
+    let mut parser = Parser::new(source).unwrap();
+
+    let file = parser.parse_file().unwrap();
+    match &file.items[0] {
+        Item::Function(func) => match &func.body.statements[0] {
+            Statement::Return(Some(expr)) => match expr {
+                Expression::Call { args, .. } => {
+                    assert_eq!(args.len(), 2);
+                }
+                _ => panic!("Expected call expression"),
+            },
+            _ => panic!("Expected return statement"),
+        },
+        _ => panic!("Expected function"),
+    }
+}
+
+#[test]
+fn test_parse_field_access() {
+    let source = "int main() { return obj.field; }";
+    let mut parser = Parser::new(source).unwrap();
+
+    let file = parser.parse_file().unwrap();
+    match &file.items[0] {
+        Item::Function(func) => match &func.body.statements[0] {
+            Statement::Return(Some(expr)) => {
+                assert!(matches!(expr, Expression::FieldAccess { .. }));
+            }
+            _ => panic!("Expected return statement"),
+        },
+        _ => panic!("Expected function"),
+    }
+}
+
+#[test]
+fn test_parse_array_indexing() {
+    let source = "int main() { return arr[0]; }";
+    let mut parser = Parser::new(source).unwrap();
+
+    let file = parser.parse_file().unwrap();
+    match &file.items[0] {
+        Item::Function(func) => match &func.body.statements[0] {
+            Statement::Return(Some(expr)) => {
+                assert!(matches!(expr, Expression::Index { .. }));
+            }
+            _ => panic!("Expected return statement"),
+        },
+        _ => panic!("Expected function"),
+    }
+}
+
+#[test]
+fn test_parse_ternary_operator() {
+    let source = "int main() { return x ? 1 : 2; }";
+    let mut parser = Parser::new(source).unwrap();
+
+    let file = parser.parse_file().unwrap();
+    match &file.items[0] {
+        Item::Function(func) => match &func.body.statements[0] {
+            Statement::Return(Some(expr)) => {
+                assert!(matches!(expr, Expression::Ternary { .. }));
+            }
+            _ => panic!("Expected return statement"),
+        },
+        _ => panic!("Expected function"),
+    }
+}
+
+#[test]
+fn test_parse_fallible_return_and_error_propagation() {
+    let source = "int? parse(char* s) {\n    let int n = s.parse()?;\n    return Ok(n > 0 ? n : 0);\n}\nPoint? origin() {\n    return Ok(make()?.at(0)?);\n}\n";
+    let file = Parser::new(source).unwrap().parse_file().unwrap();
+    let Item::Function(parse) = &file.items[0] else {
+        panic!("Expected function");
+    };
+    assert_eq!(
+        parse.return_type,
+        Some(Type::Fallible {
+            ty: Box::new(Type::Primitive(PrimitiveType::Int))
+        })
+    );
+    assert!(matches!(
+        &parse.body.statements[0],
+        Statement::Let {
+            init: Some(Expression::ErrorProp { .. }),
+            ..
+        }
+    ));
+    // A `?` followed by an operand is still a conditional
+    let Statement::Return(Some(Expression::Call { args, .. })) = &parse.body.statements[1] else {
+        panic!("Expected return statement");
+    };
+    assert!(matches!(args[0], Expression::Ternary { .. }));
+
+    let Item::Function(origin) = &file.items[1] else {
+        panic!("Expected function");
+    };
+    assert_eq!(
+        origin.return_type,
+        Some(Type::Fallible {
+            ty: Box::new(Type::Ident(Ident::new("Point")))
+        })
+    );
+}
+
+#[test]
+fn test_parse_compound_assignment() {
+    let source = "void f() { var int x = 1; x <<= 2; x += x; }";
+    let file = Parser::new(source).unwrap().parse_file().unwrap();
+    let Item::Function(func) = &file.items[0] else {
+        panic!("Expected function");
+    };
+    let ops: Vec<&BinaryOp> = func.body.statements[1..]
+        .iter()
+        .map(|stmt| match stmt {
+            Statement::Expr(Expression::Binary { op, .. }) => op,
+            _ => panic!("Expected assignment, found {:?}", stmt),
+        })
+        .collect();
+    assert_eq!(ops, [&BinaryOp::ShlAssign, &BinaryOp::AddAssign]);
+}
+
+#[test]
+fn test_parse_logical_not() {
+    let source = "bool f(bool b) { return !b; }";
+    let file = Parser::new(source).unwrap().parse_file().unwrap();
+    match &file.items[0] {
+        Item::Function(func) => assert!(matches!(
+            &func.body.statements[0],
+            Statement::Return(Some(Expression::Unary {
+                op: UnaryOp::Not,
+                ..
+            }))
+        )),
+        _ => panic!("Expected function"),
+    }
+}
+
+#[test]
+fn test_parse_if_expression() {
+    let source = "int main() { return if (x) { 1 } else { 2 }; }";
+    let mut parser = Parser::new(source).unwrap();
+
+    let file = parser.parse_file().unwrap();
+    let ternary = Parser::new("int main() { return x ? 1 : 2; }")
+        .unwrap()
+        .parse_file()
+        .unwrap();
+    assert_eq!(file, ternary);
+}
+
+#[test]
+fn test_parse_if_expression_else_if_chain() {
+    let source =
+        "int main() { let s = if (x > 0) { 1 } else if (x < 0) { 2 } else { 0 }; return s; }";
+    let mut parser = Parser::new(source).unwrap();
+
+    let file = parser.parse_file().unwrap();
+    match &file.items[0] {
+        Item::Function(func) => match &func.body.statements[0] {
+            Statement::Let {
+                init: Some(Expression::Ternary { else_expr, .. }),
+                ..
+            } => {
+                assert!(matches!(else_expr.as_ref(), Expression::Ternary { .. }));
+            }
+            _ => panic!("Expected let with if expression"),
+        },
+        _ => panic!("Expected function"),
+    }
+}
+
+#[test]
+fn test_parse_if_expression_requires_else() {
+    let source = "int main() { return if (x) { 1 }; }";
+    let err = Parser::new(source).unwrap().parse_file().unwrap_err();
+    assert_eq!(err.message, "if expression requires an else branch");
+    assert_eq!(err.expected, vec!["else".to_string()]);
+}
+
+#[test]
+fn test_parse_match_expression() {
+    let source = "int f(Shape s) { return match (s) { Shape.Dot => 0, Option::Some((x, _)) => x, -1 => 1, n => n, }; }";
+    let file = Parser::new(source).unwrap().parse_file().unwrap();
+    let Item::Function(func) = &file.items[0] else {
+        panic!("Expected function");
+    };
+    let Statement::Return(Some(Expression::Match { expr, arms })) = &func.body.statements[0] else {
+        panic!("Expected return of a match expression");
+    };
+    assert_eq!(expr.as_ref(), &Expression::Ident(Ident::new("s")));
+    let patterns: Vec<&Pattern> = arms.iter().map(|arm| &arm.pattern).collect();
+    assert_eq!(
+        patterns,
+        [
+            &Pattern::Variant {
+                enum_name: Ident::new("Shape"),
+                variant: Ident::new("Dot"),
+                fields: vec![],
+            },
+            &Pattern::Variant {
+                enum_name: Ident::new("Option"),
+                variant: Ident::new("Some"),
+                fields: vec![Pattern::Tuple(vec![
+                    Pattern::Binding(Ident::new("x")),
+                    Pattern::Wildcard,
+                ])],
+            },
+            &Pattern::Literal(Literal::Int(-1)),
+            &Pattern::Binding(Ident::new("n")),
+        ]
+    );
+    assert_eq!(arms[1].body, Expression::Ident(Ident::new("x")));
+}
+
+#[test]
+fn test_parse_volatile_pointer_in_unsafe_block() {
+    let source = "void f(volatile u32* status) { unsafe { volatile u32* reg = (volatile u32*)4096; *reg = *status; } }";
+    let file = Parser::new(source).unwrap().parse_file().unwrap();
+    let Item::Function(func) = &file.items[0] else {
+        panic!("Expected function");
+    };
+    let volatile_u32_ptr = Type::Pointer {
+        ty: Box::new(Type::Volatile {
+            ty: Box::new(Type::Primitive(PrimitiveType::U32)),
+        }),
+        mutable: true,
+    };
+    assert_eq!(func.params[0].ty, volatile_u32_ptr);
+    let Statement::Unsafe(body) = &func.body.statements[0] else {
+        panic!("Expected unsafe block");
+    };
+    assert!(matches!(
+        &body.statements[0],
+        Statement::Let { ty: Some(ty), .. } if *ty == volatile_u32_ptr
+    ));
+}
+
+#[test]
+fn test_parse_method_reference_and_named_type_params() {
+    let source = "struct Name {\n    int id;\n    bool eq(&self, &Name other) { return true; }\n    int pick(&self, Name n) { return 0; }\n}\n";
+    let file = Parser::new(source).unwrap().parse_file().unwrap();
+    let Item::Struct(struct_def) = &file.items[0] else {
+        panic!("Expected struct");
+    };
+    let eq = &struct_def.methods[0];
+    assert_eq!(eq.params[0].name.name, "self");
+    assert_eq!(eq.params[1].name.name, "other");
+    assert_eq!(
+        eq.params[1].ty,
+        Type::Reference {
+            ty: Box::new(Type::Ident(Ident::new("Name"))),
+            mutable: false
+        }
+    );
+    let pick = &struct_def.methods[1];
+    assert_eq!(pick.params[1].name.name, "n");
+    assert_eq!(pick.params[1].ty, Type::Ident(Ident::new("Name")));
+}
+
+#[test]
+fn test_parse_module_directives() {
+    let source = "#import std.collections.HashMap\n#export util.clamp\n#[derive(Debug)]\nstruct P { int x; }\n#define __ONE__ 1\n";
+    let file = Parser::new(source).unwrap().parse_file().unwrap();
+    let Item::Import(import) = &file.items[0] else {
+        panic!("Expected import");
+    };
+    let path: Vec<&str> = import.path.iter().map(|i| i.name.as_str()).collect();
+    assert_eq!(path, ["std", "collections", "HashMap"]);
+    let Item::Export(export) = &file.items[1] else {
+        panic!("Expected export");
+    };
+    let path: Vec<&str> = export.path.iter().map(|i| i.name.as_str()).collect();
+    assert_eq!(path, ["util", "clamp"]);
+    assert!(matches!(&file.items[2], Item::Struct(_)));
+    assert!(matches!(&file.items[3], Item::MacroDefinition(_)));
+
+    let err = Parser::new("#include \"util.crst\"\n")
+        .unwrap()
+        .parse_file()
+        .unwrap_err();
+    assert_eq!(
+        err.message,
+        "#include is not supported; use #import instead"
+    );
+    let err = Parser::new("#use std.io\n")
+        .unwrap()
+        .parse_file()
+        .unwrap_err();
+    assert_eq!(
+        err.message,
+        "#use is not supported; use #import or #export instead"
+    );
+}
+
+#[test]
+fn test_parse_variadic_parameters() {
+    let source = "int scaled(int factor, int... values) {\n    return factor;\n}\n";
+    let file = Parser::new(source).unwrap().parse_file().unwrap();
+    let Item::Function(func) = &file.items[0] else {
+        panic!("Expected function");
+    };
+    assert!(!func.params[0].variadic);
+    assert!(func.params[1].variadic);
+    assert_eq!(
+        func.params[1].ty,
+        Type::Reference {
+            ty: Box::new(Type::Slice {
+                ty: Box::new(Type::Primitive(PrimitiveType::Int)),
+            }),
+            mutable: false,
+        }
+    );
+    assert_eq!(crusty_peg_parser::file(source).unwrap(), file);
+
+    let crusty =
+        crate::codegen::CodeGenerator::new(crate::codegen::TargetLanguage::Crusty).generate(&file);
+    assert!(
+        crusty.contains("int scaled(int factor, int... values) {"),
+        "{}",
+        crusty
+    );
+}
+
+#[test]
+fn test_parse_function_types() {
+    let source = "int apply(int(*)(int, int) op, fn(int) visit) {\n    let fn(int, int) -> int f = op;\n    var int(*)() g = next;\n    return f(1, 2);\n}\n";
+    let file = Parser::new(source).unwrap().parse_file().unwrap();
+    let Item::Function(func) = &file.items[0] else {
+        panic!("Expected function");
+    };
+    let binary = Type::Function {
+        params: vec![
+            Type::Primitive(PrimitiveType::Int),
+            Type::Primitive(PrimitiveType::Int),
+        ],
+        return_type: Box::new(Type::Primitive(PrimitiveType::Int)),
+    };
+    assert_eq!(func.params[0].ty, binary);
+    assert_eq!(
+        func.params[1].ty,
+        Type::Function {
+            params: vec![Type::Primitive(PrimitiveType::Int)],
+            return_type: Box::new(Type::Primitive(PrimitiveType::Void)),
+        }
+    );
+    let Statement::Let { ty, .. } = &func.body.statements[0] else {
+        panic!("Expected let");
+    };
+    assert_eq!(ty.as_ref(), Some(&binary));
+    let Statement::Var { ty, .. } = &func.body.statements[1] else {
+        panic!("Expected var");
+    };
+    assert_eq!(
+        ty.as_ref(),
+        Some(&Type::Function {
+            params: vec![],
+            return_type: Box::new(Type::Primitive(PrimitiveType::Int)),
+        })
+    );
+    assert_eq!(crusty_peg_parser::file(source).unwrap(), file);
+
+    let rust =
+        crate::codegen::CodeGenerator::new(crate::codegen::TargetLanguage::Rust).generate(&file);
+    assert!(
+        rust.contains("op: fn(i32, i32) -> i32, visit: fn(i32)"),
+        "{}",
+        rust
+    );
+}
+
+#[test]
+fn test_parse_lambda() {
+    let source = "void main() {\n    let int k = 2;\n    let scale = fn(int x) -> int {\n        return x * k;\n    };\n    each(fn() {\n        k;\n    });\n}\n";
+    let file = Parser::new(source).unwrap().parse_file().unwrap();
+    let Item::Function(func) = &file.items[0] else {
+        panic!("Expected function");
+    };
+    let Statement::Let {
+        init:
+            Some(Expression::Lambda {
+                params,
+                return_type,
+                body,
+                by_value,
+            }),
+        ..
+    } = &func.body.statements[1]
+    else {
+        panic!("Expected lambda");
+    };
+    assert_eq!(params.len(), 1);
+    assert_eq!(params[0].name.name, "x");
+    assert_eq!(return_type, &Some(Type::Primitive(PrimitiveType::Int)));
+    assert_eq!(body.statements.len(), 1);
+    assert!(!by_value);
+    let Statement::Expr(Expression::Call { args, .. }) = &func.body.statements[2] else {
+        panic!("Expected call");
+    };
+    assert!(matches!(
+        &args[0],
+        Expression::Lambda {
+            return_type: None,
+            ..
+        }
+    ));
+    assert_eq!(crusty_peg_parser::file(source).unwrap(), file);
+
+    let crusty =
+        crate::codegen::CodeGenerator::new(crate::codegen::TargetLanguage::Crusty).generate(&file);
+    assert!(
+        crusty.contains("let scale = fn(int x) -> int {"),
+        "{}",
+        crusty
+    );
+}
 
-    let file = parser.parse_file().unwrap();
-    match &file.items[0] {
-        Item::Function(func) => match &func.body.statements[0] {
-            Statement::Return(Some(expr)) => match expr {
-                Expression::Call { args, .. } => {
-                    assert_eq!(args.len(), 2);
-                }
-                _ => panic!("Expected call expression"),
-            },
-            _ => panic!("Expected return statement"),
-        },
-        _ => panic!("Expected function"),
-    }
+#[test]
+fn test_parse_extern_block() {
+    let source = "extern \"C\" {\n    int puts(char* s);\n    int printf(char* fmt, ...);\n    void abort();\n}\n";
+    let file = Parser::new(source).unwrap().parse_file().unwrap();
+    let Item::Extern(extern_block) = &file.items[0] else {
+        panic!("Expected extern block");
+    };
+    assert_eq!(extern_block.abi.as_deref(), Some("C"));
+    let names: Vec<&str> = extern_block
+        .functions
+        .iter()
+        .map(|f| f.name.name.as_str())
+        .collect();
+    assert_eq!(names, ["puts", "printf", "abort"]);
+    assert!(!extern_block.functions[0].variadic);
+    assert!(extern_block.functions[1].variadic);
+    assert_eq!(extern_block.functions[1].params.len(), 1);
+    assert_eq!(
+        extern_block.functions[0].return_type,
+        Some(Type::Primitive(PrimitiveType::Int))
+    );
+    assert_eq!(extern_block.functions[2].return_type, None);
+
+    let err = Parser::new("extern \"C\" { int puts(char* s) { return 0; } }")
+        .unwrap()
+        .parse_file()
+        .unwrap_err();
+    assert_eq!(err.message, "expected ';', found '{'");
 }
 
 #[test]
-fn test_parse_field_access() {
-    let source = "int main() { return obj.field; }";
-    let mut parser = Parser::new(source).unwrap();
+fn test_parse_radix_literals() {
+    let source = "void main() {\n    u32 mask = 0xFF_FF;\n    int arr[0b100] = [1, 2, 3, 4];\n}";
+    let file = Parser::new(source).unwrap().parse_file().unwrap();
+    let Item::Function(func) = &file.items[0] else {
+        panic!("Expected function");
+    };
+    assert!(matches!(
+        &func.body.statements[0],
+        Statement::Let {
+            init: Some(Expression::Literal(Literal::RadixInt(0xFFFF, Radix::Hex))),
+            ..
+        }
+    ));
+    assert!(matches!(
+        &func.body.statements[1],
+        Statement::Let {
+            ty: Some(Type::Array { size: Some(4), .. }),
+            ..
+        }
+    ));
 
-    let file = parser.parse_file().unwrap();
-    match &file.items[0] {
-        Item::Function(func) => match &func.body.statements[0] {
-            Statement::Return(Some(expr)) => {
-                assert!(matches!(expr, Expression::FieldAccess { .. }));
-            }
-            _ => panic!("Expected return statement"),
-        },
-        _ => panic!("Expected function"),
-    }
+    let error = |source: &str| {
+        Parser::new(source)
+            .unwrap()
+            .parse_file()
+            .unwrap_err()
+            .message
+    };
+    assert_eq!(
+        error("void main() { int a = 0b102; }"),
+        "invalid digit '2' in binary literal '0b102'"
+    );
+    assert_eq!(
+        error("void main() { int a = 0x; }"),
+        "integer literal '0x' has no digits"
+    );
+    assert_eq!(
+        error("void main() { i64 a = 9_223_372_036_854_775_808; }"),
+        "integer literal '9_223_372_036_854_775_808' is too large for a 64-bit integer"
+    );
 }
 
 #[test]
-fn test_parse_array_indexing() {
-    let source = "int main() { return arr[0]; }";
-    let mut parser = Parser::new(source).unwrap();
+fn test_parse_suffixed_literals() {
+    let literal = |text: &str| match Parser::new(text).unwrap().parse_expression() {
+        Ok(Expression::Literal(literal)) => literal,
+        other => panic!("Expected literal, got {:?}", other),
+    };
+    assert_eq!(
+        literal("100i64"),
+        Literal::Suffixed(Box::new(Literal::Int(100)), PrimitiveType::I64)
+    );
+    assert_eq!(
+        literal("0x1f32"),
+        Literal::RadixInt(0x1f32, Radix::Hex),
+        "f32 is hexadecimal digits, not a suffix"
+    );
+    assert_eq!(
+        literal("3.5f32"),
+        Literal::Suffixed(Box::new(Literal::Float(3.5)), PrimitiveType::F32)
+    );
+    assert_eq!(
+        literal("1_000_usize"),
+        Literal::Suffixed(Box::new(Literal::Int(1000)), PrimitiveType::Usize)
+    );
+    assert_eq!(
+        parse_float_literal("3.5i32"),
+        Err("invalid float literal '3.5i32'".to_string())
+    );
+    assert_eq!(
+        parse_int_literal("42ux"),
+        Err("invalid digit 'u' in decimal literal '42ux'".to_string())
+    );
+}
 
-    let file = parser.parse_file().unwrap();
-    match &file.items[0] {
-        Item::Function(func) => match &func.body.statements[0] {
-            Statement::Return(Some(expr)) => {
-                assert!(matches!(expr, Expression::Index { .. }));
-            }
-            _ => panic!("Expected return statement"),
-        },
-        _ => panic!("Expected function"),
-    }
+#[test]
+fn test_parse_pointer_to_const() {
+    let source = "void f(const int* p, const int n, const char** argv) {\n    const int* q = p;\n    const int MAX = 5;\n}";
+    let file = Parser::new(source).unwrap().parse_file().unwrap();
+    let Item::Function(func) = &file.items[0] else {
+        panic!("Expected function");
+    };
+    let const_int = Type::Pointer {
+        ty: Box::new(Type::Primitive(PrimitiveType::Int)),
+        mutable: false,
+    };
+    assert_eq!(func.params[0].ty, const_int);
+    assert_eq!(func.params[1].ty, Type::Primitive(PrimitiveType::Int));
+    assert_eq!(
+        func.params[2].ty,
+        Type::Pointer {
+            ty: Box::new(Type::Pointer {
+                ty: Box::new(Type::Primitive(PrimitiveType::Char)),
+                mutable: false,
+            }),
+            mutable: true,
+        }
+    );
+    assert!(matches!(
+        &func.body.statements[0],
+        Statement::Let { ty: Some(ty), .. } if *ty == const_int
+    ));
+    assert!(matches!(&func.body.statements[1], Statement::Const { .. }));
 }
 
 #[test]
-fn test_parse_ternary_operator() {
-    let source = "int main() { return x ? 1 : 2; }";
-    let mut parser = Parser::new(source).unwrap();
+fn test_parse_restrict_pointers() {
+    let source = "void axpy(const int* restrict x, int* __restrict y) {\n    int* restrict p = y;\n    let restrict = 2;\n}";
+    let file = Parser::new(source).unwrap().parse_file().unwrap();
+    let Item::Function(func) = &file.items[0] else {
+        panic!("Expected function");
+    };
+    let pointer = |mutable| Type::Pointer {
+        ty: Box::new(Type::Primitive(PrimitiveType::Int)),
+        mutable,
+    };
+    assert_eq!(func.params[0].ty, pointer(false));
+    assert_eq!(func.params[1].ty, pointer(true));
+    assert!(matches!(
+        &func.body.statements[0],
+        Statement::Let { name, ty: Some(ty), .. } if name.name == "p" && *ty == pointer(true)
+    ));
+    // restrict is only a qualifier before a name
+    assert!(matches!(
+        &func.body.statements[1],
+        Statement::Let { name, .. } if name.name == "restrict"
+    ));
+
+    let peg = parse_peg_file("void f(int* restrict p) {}").unwrap();
+    let Item::Function(func) = &peg.items[0] else {
+        panic!("Expected function");
+    };
+    assert_eq!(func.params[0].name.name, "p");
+    assert!(matches!(func.params[0].ty, Type::Pointer { .. }));
+}
 
-    let file = parser.parse_file().unwrap();
-    match &file.items[0] {
-        Item::Function(func) => match &func.body.statements[0] {
-            Statement::Return(Some(expr)) => {
-                assert!(matches!(expr, Expression::Ternary { .. }));
-            }
-            _ => panic!("Expected return statement"),
-        },
-        _ => panic!("Expected function"),
-    }
+#[test]
+fn test_parse_c_type_spellings() {
+    let source = "unsigned long count(unsigned int n, long long total) { short s = 1; }";
+    let file = Parser::new(source).unwrap().parse_file().unwrap();
+    let Item::Function(func) = &file.items[0] else {
+        panic!("Expected function");
+    };
+    assert_eq!(
+        func.return_type,
+        Some(Type::Ident(Ident::new("unsigned long")))
+    );
+    assert_eq!(func.params[0].ty, Type::Primitive(PrimitiveType::U32));
+    assert_eq!(func.params[1].ty, Type::Primitive(PrimitiveType::I64));
+    assert!(matches!(
+        &func.body.statements[0],
+        Statement::Let {
+            ty: Some(Type::Primitive(PrimitiveType::I16)),
+            ..
+        }
+    ));
+}
+
+#[test]
+fn test_parse_static_assert() {
+    let source = "static_assert(sizeof(int) == 4, \"int is 4 bytes\");\nvoid main() { static_assert(true, \"ok\"); }";
+    let file = Parser::new(source).unwrap().parse_file().unwrap();
+    let Item::StaticAssert(assertion) = &file.items[0] else {
+        panic!("Expected static assertion");
+    };
+    assert_eq!(assertion.message, "int is 4 bytes");
+    assert!(matches!(
+        &assertion.condition,
+        Expression::Binary { op: BinaryOp::Eq, left, .. }
+            if matches!(left.as_ref(), Expression::Sizeof { .. })
+    ));
+    let Item::Function(func) = &file.items[1] else {
+        panic!("Expected function");
+    };
+    assert!(matches!(
+        &func.body.statements[0],
+        Statement::StaticAssert(StaticAssert { message, .. }) if message == "ok"
+    ));
+
+    let err = Parser::new("static_assert(true, 1);")
+        .unwrap()
+        .parse_file()
+        .unwrap_err();
+    assert_eq!(err.message, "expected static assertion message");
+}
+
+#[test]
+fn test_parse_path_expressions() {
+    let source = "void main() { let c = Color::Red; let v = Vec::new(1); }";
+    let file = Parser::new(source).unwrap().parse_file().unwrap();
+    let Item::Function(func) = &file.items[0] else {
+        panic!("Expected function");
+    };
+    let dotted = Parser::new("void main() { let c = Color.Red; }")
+        .unwrap()
+        .parse_file()
+        .unwrap();
+    let Item::Function(dotted) = &dotted.items[0] else {
+        panic!("Expected function");
+    };
+    assert_eq!(func.body.statements[0], dotted.body.statements[0]);
+    assert!(matches!(
+        &func.body.statements[1],
+        Statement::Let {
+            init: Some(Expression::TypeScopedCall { method, args, .. }),
+            ..
+        } if method.name == "new" && args.len() == 1
+    ));
 }
 
 #[test]
@@ -4273,6 +6918,255 @@ fn test_parse_array_type() {
     }
 }
 
+#[test]
+fn test_parse_c_style_array_declarators() {
+    let source = "void main() {\n    char buf[8] = \"abc\";\n    var u8[4] raw = b\"ab\";\n    int grid[2][3] = g;\n}\n";
+    let file = Parser::new(source).unwrap().parse_file().unwrap();
+    let Item::Function(func) = &file.items[0] else {
+        panic!("Expected function");
+    };
+    let array = |ty: Type, size| Type::Array {
+        ty: Box::new(ty),
+        size: Some(size),
+    };
+
+    match &func.body.statements[0] {
+        Statement::Let { name, ty, init, .. } => {
+            assert_eq!(name.name, "buf");
+            assert_eq!(ty, &Some(array(Type::Primitive(PrimitiveType::Char), 8)));
+            assert_eq!(
+                init,
+                &Some(Expression::Literal(Literal::String("abc".to_string())))
+            );
+        }
+        other => panic!("Expected let, got {:?}", other),
+    }
+    match &func.body.statements[1] {
+        Statement::Var { ty, init, .. } => {
+            assert_eq!(ty, &Some(array(Type::Primitive(PrimitiveType::U8), 4)));
+            assert_eq!(
+                init,
+                &Some(Expression::Literal(Literal::ByteString(b"ab".to_vec())))
+            );
+        }
+        other => panic!("Expected var, got {:?}", other),
+    }
+    match &func.body.statements[2] {
+        Statement::Let { ty, .. } => assert_eq!(
+            ty,
+            &Some(array(array(Type::Primitive(PrimitiveType::Int), 3), 2))
+        ),
+        other => panic!("Expected let, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_parse_adjacent_string_literals() {
+    let source = "void main() {\n    let a = \"con\" \"cat\";\n    let w = L\"wide \" \"and narrow\";\n    let b = b\"ab\" b\"\\x01\";\n}\n";
+    let file = Parser::new(source).unwrap().parse_file().unwrap();
+    let Item::Function(func) = &file.items[0] else {
+        panic!("Expected function");
+    };
+    let inits: Vec<&Expression> = func
+        .body
+        .statements
+        .iter()
+        .map(|stmt| match stmt {
+            Statement::Let {
+                init: Some(init), ..
+            } => init,
+            other => panic!("Expected let, got {:?}", other),
+        })
+        .collect();
+    assert_eq!(
+        inits,
+        [
+            &Expression::Literal(Literal::String("concat".to_string())),
+            &Expression::Literal(Literal::WideString("wide and narrow".to_string())),
+            &Expression::Literal(Literal::ByteString(vec![b'a', b'b', 1])),
+        ]
+    );
+
+    let mixed = "void main() {\n    let m = \"text\" b\"bytes\";\n}\n";
+    let err = Parser::new(mixed).unwrap().parse_file().unwrap_err();
+    assert_eq!(
+        err.message,
+        "cannot concatenate a byte string with a text string"
+    );
+}
+
+#[test]
+fn test_parse_raw_string_literals() {
+    let source = "void main() {\n    let path = r\"C:\\temp\";\n    let re = r#\"\"\\d+\"\"#;\n    let both = r\"\\n\" \"\\n\";\n}\n";
+    let file = Parser::new(source).unwrap().parse_file().unwrap();
+    let Item::Function(func) = &file.items[0] else {
+        panic!("Expected function");
+    };
+    let inits: Vec<&Expression> = func
+        .body
+        .statements
+        .iter()
+        .map(|stmt| match stmt {
+            Statement::Let {
+                init: Some(init), ..
+            } => init,
+            other => panic!("Expected let, got {:?}", other),
+        })
+        .collect();
+    assert_eq!(
+        inits,
+        [
+            &Expression::Literal(Literal::RawString("C:\\temp".to_string())),
+            &Expression::Literal(Literal::RawString("\"\\d+\"".to_string())),
+            // Next to an ordinary string, the text is concatenated as usual
+            &Expression::Literal(Literal::String("\\n\n".to_string())),
+        ]
+    );
+}
+
+#[test]
+fn test_parse_constant_array_sizes_and_enum_values() {
+    let source = "#define __N__ 4\nenum Flag { A = 1, B = A << 1, C = __N__ * 2, D = -1 }\nvoid main() {\n    const int M = 3;\n    var int buf[__N__ * M] = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];\n    int bytes[(int)sizeof(u32) + 1] = [0, 0, 0, 0, 0];\n}\n";
+    let file = Parser::new(source).unwrap().parse_file().unwrap();
+    let variants = file
+        .items
+        .iter()
+        .find_map(|item| match item {
+            Item::Enum(e) => Some(&e.variants),
+            _ => None,
+        })
+        .expect("Expected enum");
+    let values: Vec<Option<i64>> = variants.iter().map(|variant| variant.value).collect();
+    assert_eq!(values, [Some(1), Some(2), Some(8), Some(-1)]);
+
+    let func = file
+        .items
+        .iter()
+        .find_map(|item| match item {
+            Item::Function(func) => Some(func),
+            _ => None,
+        })
+        .expect("Expected function");
+    let sizes: Vec<Option<usize>> = func
+        .body
+        .statements
+        .iter()
+        .filter_map(|stmt| match stmt {
+            Statement::Let {
+                ty: Some(Type::Array { size, .. }),
+                ..
+            }
+            | Statement::Var {
+                ty: Some(Type::Array { size, .. }),
+                ..
+            } => Some(*size),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(sizes, [Some(12), Some(5)]);
+
+    let cases = [
+        (
+            "void main() { int a[1.5] = [0]; }",
+            "array size must be an integer, found 1.5",
+        ),
+        (
+            "void main() { int a[n] = [0]; }",
+            "array size must be a constant expression: 'n' is not a constant",
+        ),
+        (
+            "void main() { int a[(int)sizeof(long)] = [0]; }",
+            "array size must be a constant expression: a value that differs between targets is not a constant",
+        ),
+        (
+            "enum E { A = 1.5 }",
+            "enum value must be an integer, found 1.5",
+        ),
+    ];
+    for (source, message) in cases {
+        let err = Parser::new(source).unwrap().parse_file().unwrap_err();
+        assert_eq!(err.message, message, "{}", source);
+    }
+}
+
+#[test]
+fn test_parse_constant_array_types() {
+    let source = "#define __LEN__ 4\nstruct Buf {\n    u8[__LEN__ * 2] data;\n}\nint first(int[__LEN__] xs) {\n    return xs[0];\n}\nvoid main() {\n    int[2 * 8] buf = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];\n    buf[1] = 2;\n}\n";
+    let file = Parser::new(source).unwrap().parse_file().unwrap();
+    let array = |ty: PrimitiveType, size| Type::Array {
+        ty: Box::new(Type::Primitive(ty)),
+        size: Some(size),
+    };
+    let mut types = Vec::new();
+    for item in &file.items {
+        match item {
+            Item::Struct(s) => types.push(s.fields[0].ty.clone()),
+            Item::Function(func) if func.name.name == "first" => {
+                types.push(func.params[0].ty.clone())
+            }
+            Item::Function(func) => match &func.body.statements[..] {
+                [Statement::Let { ty: Some(ty), .. }, Statement::Expr(_)] => types.push(ty.clone()),
+                other => panic!("Expected declaration and assignment, got {:?}", other),
+            },
+            _ => {}
+        }
+    }
+    assert_eq!(
+        types,
+        [
+            array(PrimitiveType::U8, 8),
+            array(PrimitiveType::Int, 4),
+            array(PrimitiveType::Int, 16),
+        ]
+    );
+
+    let err = Parser::new("void f(int n) {\n    int[n + 1] xs = [0];\n}\n")
+        .unwrap()
+        .parse_file()
+        .unwrap_err();
+    assert_eq!(
+        err.message,
+        "array size must be a constant expression: 'n' is not a constant"
+    );
+}
+
+#[test]
+fn test_parse_sizeof_and_alignof_operands() {
+    let source = "void f(Point p) {\n    let usize a = sizeof(p);\n    let usize b = sizeof(p.x + 1);\n    let usize c = alignof(int*);\n    let usize d = alignof(p.x);\n}\n";
+    let file = Parser::new(source).unwrap().parse_file().unwrap();
+    let Item::Function(func) = &file.items[0] else {
+        panic!("Expected function");
+    };
+    let inits: Vec<&Expression> = func
+        .body
+        .statements
+        .iter()
+        .map(|stmt| match stmt {
+            Statement::Let {
+                init: Some(init), ..
+            } => init,
+            other => panic!("Expected declaration, got {:?}", other),
+        })
+        .collect();
+    // A name parses as a type; semantic analysis decides whether it is one
+    assert_eq!(
+        inits[0],
+        &Expression::Sizeof {
+            ty: Type::Ident(Ident::new("p"))
+        }
+    );
+    assert!(matches!(inits[1], Expression::SizeofExpr { expr }
+        if matches!(expr.as_ref(), Expression::Binary { op: BinaryOp::Add, .. })));
+    assert!(matches!(
+        inits[2],
+        Expression::Alignof {
+            ty: Type::Pointer { .. }
+        }
+    ));
+    assert!(matches!(inits[3], Expression::AlignofExpr { expr }
+        if matches!(expr.as_ref(), Expression::FieldAccess { .. })));
+}
+
 #[test]
 fn test_parse_tuple_type() {
     let source = "int foo((int, bool) x) {}";
@@ -4514,6 +7408,28 @@ fn test_parse_define_empty_body() {
     }
 }
 
+#[test]
+fn test_parse_define_variadic() {
+    let source = "#define __LOG__(fmt, ...) __println__(fmt, __VA_ARGS__)\n#define __ALL__[...] __VA_ARGS__\n#define __ONE__(a) a\n";
+    let file = Parser::new(source).unwrap().parse_file().unwrap();
+
+    let defs: Vec<(usize, bool)> = file
+        .items
+        .iter()
+        .map(|item| match item {
+            Item::MacroDefinition(mac) => (mac.params.len(), mac.variadic),
+            _ => panic!("Expected MacroDefinition"),
+        })
+        .collect();
+    assert_eq!(defs, [(1, true), (0, true), (1, false)]);
+
+    let error = Parser::new("#define __BAD__(..., a) a")
+        .unwrap()
+        .parse_file()
+        .unwrap_err();
+    assert_eq!(error.expected, ["')'"]);
+}
+
 #[cfg(test)]
 mod property_tests {
     use super::*;
@@ -5100,6 +8016,16 @@ enum StructMember {
     Method(Function),
 }
 
+/// The type of a variadic parameter taking `element`s: a slice of them
+fn variadic_type(element: Type) -> Type {
+    Type::Reference {
+        ty: Box::new(Type::Slice {
+            ty: Box::new(element),
+        }),
+        mutable: false,
+    }
+}
+
 peg::parser! {
     pub grammar crusty_peg_parser() for str {
         // ====================================================================
@@ -5160,6 +8086,9 @@ peg::parser! {
         /// Keyword: static
         rule kw_static() = "static" !ident_char()
 
+        /// Member modifier: pub
+        rule kw_pub() = "pub" !ident_char()
+
         /// Keyword: mut
         rule kw_mut() = "mut" !ident_char()
 
@@ -5175,6 +8104,9 @@ peg::parser! {
         /// Keyword: while
         rule kw_while() = "while" !ident_char()
 
+        /// Keyword: do
+        rule kw_do() = "do" !ident_char()
+
         /// Keyword: for
         rule kw_for() = "for" !ident_char()
 
@@ -5196,6 +8128,12 @@ peg::parser! {
         /// Keyword: enum
         rule kw_enum() = "enum" !ident_char()
 
+        /// Keyword: trait, also spelled interface
+        rule kw_trait() = ("trait" / "interface") !ident_char()
+
+        /// Keyword: impl
+        rule kw_impl() = "impl" !ident_char()
+
         /// Keyword: typedef
         rule kw_typedef() = "typedef" !ident_char()
 
@@ -5208,6 +8146,9 @@ peg::parser! {
         /// Keyword: unsafe
         rule kw_unsafe() = "unsafe" !ident_char()
 
+        /// Keyword: volatile
+        rule kw_volatile() = "volatile" !ident_char()
+
         /// Keyword: loop
         rule kw_loop() = "loop" !ident_char()
 
@@ -5241,6 +8182,36 @@ peg::parser! {
         /// Keyword: u64
         rule kw_u64() = "u64" !ident_char()
 
+        /// Keyword: i8
+        rule kw_i8() = "i8" !ident_char()
+
+        /// Keyword: i16
+        rule kw_i16() = "i16" !ident_char()
+
+        /// Keyword: isize
+        rule kw_isize() = "isize" !ident_char()
+
+        /// Keyword: u8
+        rule kw_u8() = "u8" !ident_char()
+
+        /// Keyword: u16
+        rule kw_u16() = "u16" !ident_char()
+
+        /// Keyword: usize
+        rule kw_usize() = "usize" !ident_char()
+
+        /// Keyword: unsigned
+        rule kw_unsigned() = "unsigned" !ident_char()
+
+        /// Keyword: signed
+        rule kw_signed() = "signed" !ident_char()
+
+        /// Keyword: short
+        rule kw_short() = "short" !ident_char()
+
+        /// Keyword: long
+        rule kw_long() = "long" !ident_char()
+
         /// Keyword: float
         rule kw_float() = "float" !ident_char()
 
@@ -5271,17 +8242,22 @@ peg::parser! {
         /// Keyword: sizeof
         rule kw_sizeof() = "sizeof" !ident_char()
 
+        /// Keyword: alignof
+        rule kw_alignof() = "alignof" !ident_char()
+
         /// Helper: matches any keyword (used to prevent keywords from being parsed as identifiers)
         rule keyword() = kw_let() / kw_var() / kw_const() / kw_static() / kw_mut() / kw_define()
-            / kw_if() / kw_else() / kw_while() / kw_for() / kw_in()
+            / kw_if() / kw_else() / kw_while() / kw_do() / kw_for() / kw_in()
             / kw_return() / kw_break() / kw_continue()
             / kw_struct() / kw_enum() / kw_typedef()
-            / kw_namespace() / kw_extern() / kw_unsafe()
+            / kw_namespace() / kw_extern() / kw_unsafe() / kw_volatile()
             / kw_loop() / kw_match() / kw_switch() / kw_case() / kw_default() / kw_auto()
             / kw_int() / kw_i32() / kw_i64() / kw_u32() / kw_u64()
+            / kw_i8() / kw_i16() / kw_isize() / kw_u8() / kw_u16() / kw_usize()
+            / kw_unsigned() / kw_signed() / kw_short() / kw_long()
             / kw_float() / kw_f32() / kw_f64()
             / kw_bool() / kw_char() / kw_void()
-            / kw_true() / kw_false() / kw_null() / kw_sizeof()
+            / kw_true() / kw_false() / kw_null() / kw_sizeof() / kw_alignof()
 
         // ====================================================================
         // LITERALS
@@ -5295,21 +8271,35 @@ peg::parser! {
         // Boolean literals: true, false
         // Null literal: NULL
 
-        /// Integer literal: one or more decimal digits
-        /// Returns Literal::Int
+        /// Integer literal: decimal digits, or hexadecimal, octal or binary
+        /// digits after 0x, 0o or 0b, with _ between digits and an optional
+        /// type suffix (42u8, 0xFFi64)
+        /// Returns Literal::Int, or Literal::RadixInt for another radix,
+        /// wrapped in Literal::Suffixed when suffixed
         pub rule int_literal() -> Literal
-            = n:$(['0'..='9']+) {
-                Literal::Int(n.parse().unwrap())
+            = n:$("0" ['x' | 'X' | 'o' | 'O' | 'b' | 'B'] ['0'..='9' | 'a'..='z' | 'A'..='Z' | '_']+
+                  / ['0'..='9'] ['0'..='9' | '_']* int_suffix()?) {?
+                parse_int_literal(n).map_err(|_| "an integer literal that fits in 64 bits")
             }
 
-        /// Float literal: decimal digits with decimal point
-        /// Returns Literal::Float
+        /// Integer type suffix of a number literal
+        rule int_suffix()
+            = ("i8" / "i16" / "i32" / "i64" / "isize" / "u8" / "u16" / "u32" / "u64" / "usize")
+              !ident_char()
+
+        /// Float type suffix of a number literal
+        rule float_suffix() = ("f32" / "f64") !ident_char()
+
+        /// Float literal: decimal digits with decimal point, or any decimal
+        /// number with an f32 or f64 suffix
+        /// Returns Literal::Float, wrapped in Literal::Suffixed when suffixed
         pub rule float_literal() -> Literal
             = n:$(
-                (['0'..='9']+ "." ['0'..='9']+ (['e' | 'E'] ['+' | '-']? ['0'..='9']+)?)
-                / (['0'..='9']+ ['e' | 'E'] ['+' | '-']? ['0'..='9']+)
-            ) {
-                Literal::Float(n.parse().unwrap())
+                (['0'..='9']+ "." ['0'..='9']+ (['e' | 'E'] ['+' | '-']? ['0'..='9']+)? float_suffix()?)
+                / (['0'..='9']+ ['e' | 'E'] ['+' | '-']? ['0'..='9']+ float_suffix()?)
+                / (['0'..='9'] ['0'..='9' | '_']* float_suffix())
+            ) {?
+                parse_float_literal(n).map_err(|_| "a float literal")
             }
 
         /// String literal: double-quoted with escape sequences
@@ -5324,7 +8314,8 @@ peg::parser! {
             = escape_sequence()
             / c:$([^ '"' | '\\']) { c.chars().next().unwrap() }
 
-        /// Escape sequences in strings and characters
+        /// Escape sequences in strings and characters, including \xNN up to
+        /// \x7F and \u{...} with 1 to 6 hexadecimal digits
         rule escape_sequence() -> char
             = "\\\\" { '\\' }
             / "\\n" { '\n' }
@@ -5333,6 +8324,63 @@ peg::parser! {
             / "\\\"" { '"' }
             / "\\'" { '\'' }
             / "\\0" { '\0' }
+            / "\\x" h:$(['0'..='7'] ['0'..='9' | 'a'..='f' | 'A'..='F']) {
+                char::from(u8::from_str_radix(h, 16).unwrap_or_default())
+            }
+            / "\\u{" h:$(['0'..='9' | 'a'..='f' | 'A'..='F']*<1,6>) "}" {?
+                u32::from_str_radix(h, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or("a Unicode scalar value")
+            }
+
+        /// Wide string literal: L"..." or u"...", UTF-16 text
+        /// Returns Literal::WideString
+        pub rule wide_string_literal() -> Literal
+            = ['L' | 'u'] "\"" s:string_content()* "\"" {
+                Literal::WideString(s.into_iter().collect())
+            }
+
+        /// Adjacent string literals, concatenated as in C: "a" "b" is "ab"
+        rule concatenated_string() -> Literal
+            = first:string_piece() rest:(_ l:string_piece() { l })* {?
+                rest.into_iter()
+                    .try_fold(first, concat_string_literals)
+                    .ok_or("string literals of compatible kinds")
+            }
+
+        rule string_piece() -> Literal
+            = string_literal()
+            / raw_string_literal()
+            / wide_string_literal()
+            / byte_string_literal()
+
+        /// Raw string literal: r"..." or r#"..."#, without escapes, ending at
+        /// the first quote followed by as many # as opened it
+        /// Returns Literal::RawString
+        pub rule raw_string_literal() -> Literal
+            = "r" h:$("#"*) "\"" s:$((!raw_string_close(h.len()) [_])*)
+              raw_string_close(h.len()) {
+                Literal::RawString(s.to_string())
+            }
+
+        rule raw_string_close(hashes: usize)
+            = "\"" "#"*<{hashes}>
+
+        /// Byte string literal: b"..." holding ASCII characters and \xNN escapes
+        /// Returns Literal::ByteString
+        pub rule byte_string_literal() -> Literal
+            = "b\"" b:byte_content()* "\"" {
+                Literal::ByteString(b)
+            }
+
+        /// Byte string content: an escape sequence or a regular ASCII character
+        rule byte_content() -> u8
+            = "\\x" h:$(['0'..='9' | 'a'..='f' | 'A'..='F']*<2>) {
+                u8::from_str_radix(h, 16).unwrap()
+            }
+            / c:escape_sequence() { c as u8 }
+            / c:[c if c.is_ascii() && c != '"' && c != '\\'] { c as u8 }
 
         /// Character literal: single-quoted
         /// Returns Literal::Char
@@ -5389,7 +8437,8 @@ peg::parser! {
         // ====================================================================
         // Type expressions represent the types of values in Crusty.
         //
-        // Primitive types: int, i32, i64, u32, u64, float, f32, f64, bool, char, void
+        // Primitive types: int, i8, i16, i32, i64, isize, u8, u16, u32, u64, usize,
+        //                  float, f32, f64, bool, char, void
         // Complex types: pointers (*T), references (&T, &mut T), arrays (T[N]),
         //                tuples ((T1, T2)), generics (T<A, B>), slices (T[])
         //
@@ -5421,6 +8470,36 @@ peg::parser! {
         pub rule primitive_u64() -> Type
             = kw_u64() { Type::Primitive(PrimitiveType::U64) }
 
+        /// Primitive type: i8
+        /// Returns Type::Primitive(PrimitiveType::I8)
+        pub rule primitive_i8() -> Type
+            = kw_i8() { Type::Primitive(PrimitiveType::I8) }
+
+        /// Primitive type: i16
+        /// Returns Type::Primitive(PrimitiveType::I16)
+        pub rule primitive_i16() -> Type
+            = kw_i16() { Type::Primitive(PrimitiveType::I16) }
+
+        /// Primitive type: isize
+        /// Returns Type::Primitive(PrimitiveType::Isize)
+        pub rule primitive_isize() -> Type
+            = kw_isize() { Type::Primitive(PrimitiveType::Isize) }
+
+        /// Primitive type: u8
+        /// Returns Type::Primitive(PrimitiveType::U8)
+        pub rule primitive_u8() -> Type
+            = kw_u8() { Type::Primitive(PrimitiveType::U8) }
+
+        /// Primitive type: u16
+        /// Returns Type::Primitive(PrimitiveType::U16)
+        pub rule primitive_u16() -> Type
+            = kw_u16() { Type::Primitive(PrimitiveType::U16) }
+
+        /// Primitive type: usize
+        /// Returns Type::Primitive(PrimitiveType::Usize)
+        pub rule primitive_usize() -> Type
+            = kw_usize() { Type::Primitive(PrimitiveType::Usize) }
+
         /// Primitive type: float
         /// Returns Type::Primitive(PrimitiveType::Float)
         pub rule primitive_float() -> Type
@@ -5454,11 +8533,35 @@ peg::parser! {
         /// Any primitive type
         /// Returns Type::Primitive variant
         /// Note: Order matters for PEG - longer matches first (i32 before int, f32 before float)
+        /// A word of a C integer type spelling
+        rule c_type_word() -> &'input str
+            = w:$("unsigned" / "signed" / "short" / "long" / "int" / "char") !ident_char() { w }
+
+        /// C integer type spelling: unsigned int, long long, short, signed char, ...
+        /// Returns the type the spelling stands for
+        pub rule primitive_c_type() -> Type
+            = first:$(("unsigned" / "signed" / "short" / "long") !ident_char())
+              rest:(_ w:c_type_word() { w })*
+            {?
+                let mut words = vec![first];
+                words.extend(rest);
+                crate::lexer::c_type_token(&words)
+                    .map(|kind| c_integer_type(&kind))
+                    .or(Err("C integer type"))
+            }
+
         pub rule primitive_type() -> Type
-            = primitive_i32()
+            = primitive_c_type()
+            / primitive_i32()
             / primitive_i64()
             / primitive_u32()
             / primitive_u64()
+            / primitive_i8()
+            / primitive_i16()
+            / primitive_isize()
+            / primitive_u8()
+            / primitive_u16()
+            / primitive_usize()
             / primitive_int()
             / primitive_f32()
             / primitive_f64()
@@ -5556,10 +8659,22 @@ peg::parser! {
         /// Precedence levels (from lowest to highest):
         /// 1. Reference prefix: &T, &mut T
         /// 2. Postfix operators: T*, T[N], T[]
-        /// 3. Primary types: primitives, identifiers, tuples, generics, auto, parenthesized
+        /// 3. Qualifiers: volatile T, const T
+        /// 4. Primary types: primitives, identifiers, tuples, generics, auto, parenthesized
         ///
         /// Note: The precedence! macro handles left-to-right associativity for postfix
         /// operators, so int** parses as (int*)* (pointer to pointer).
+        /// Array size written as a constant expression, as `2 * 8`
+        rule constant_array_size() -> usize
+            = e:expr() {?
+                match consteval::evaluate_portable(&e, &HashMap::new()) {
+                    Ok(ConstValue::Int(size)) => {
+                        usize::try_from(size).or(Err("non-negative array size"))
+                    }
+                    _ => Err("constant array size"),
+                }
+            }
+
         pub rule type_expr() -> Type = precedence!{
             // Level 1: Reference prefix (lowest precedence)
             // &mut T - mutable reference
@@ -5573,23 +8688,40 @@ peg::parser! {
             --
             // Level 2: Postfix operators (higher precedence)
             // T* - pointer type
+            // T* restrict - the qualifier is accepted and ignored
+            t:(@) _ "*" _ ("restrict" / "__restrict__" / "__restrict") !ident_char() &(_ ident()) {
+                Type::Pointer { ty: Box::new(t), mutable: false }
+            }
             t:(@) _ "*" {
                 Type::Pointer { ty: Box::new(t), mutable: false }
             }
             // T[N] - array type with size
             t:(@) _ "[" _ n:int_literal() _ "]" {
-                if let Literal::Int(size) = n {
-                    Type::Array { ty: Box::new(t), size: Some(size as usize) }
-                } else {
-                    unreachable!()
-                }
+                let size = n.int_value().unwrap_or_default();
+                Type::Array { ty: Box::new(t), size: Some(size as usize) }
+            }
+            // T[N * 2] - array type whose size is a constant expression
+            t:(@) _ "[" _ size:constant_array_size() _ "]" {
+                Type::Array { ty: Box::new(t), size: Some(size) }
             }
             // T[] - slice type (array without size)
             t:(@) _ "[" _ "]" {
                 Type::Slice { ty: Box::new(t) }
             }
+            // T(*)(P1, P2) - C function pointer returning T
+            t:(@) _ "(" _ "*" _ ")" _ "(" _ params:(type_expr() ** (_ "," _)) _ ")" {
+                Type::Function { params, return_type: Box::new(t) }
+            }
             --
-            // Level 3: Primary types (highest precedence)
+            // Level 3: Volatile qualifier, binding tighter than the postfix
+            // operators so that volatile int* points to a volatile int
+            kw_volatile() __ t:@ {
+                Type::Volatile { ty: Box::new(t) }
+            }
+            // const T, whose pointers are already *const T here
+            kw_const() __ t:@ { t }
+            --
+            // Level 4: Primary types (highest precedence)
             // Generic type: Base<T1, T2, ...>
             // Must come before ident_type to avoid consuming the base identifier
             base:type_base() _ "<" _ args:type_list() _ ">" {
@@ -5611,6 +8743,14 @@ peg::parser! {
             }
             // Parenthesized type for grouping (single type without comma)
             "(" _ t:type_expr() _ ")" { t }
+            // Function pointer: fn(P1, P2) -> T, returning void without `->`
+            "fn" !ident_char() _ "(" _ params:(type_expr() ** (_ "," _)) _ ")"
+                ret:(_ "->" _ t:type_expr() { t })? {
+                Type::Function {
+                    params,
+                    return_type: Box::new(ret.unwrap_or(Type::Primitive(PrimitiveType::Void))),
+                }
+            }
             // Auto type
             kw_auto() { Type::Auto }
             // Primitive types
@@ -5643,7 +8783,7 @@ peg::parser! {
         rule literal() -> Literal
             = float_literal()  // Must come before int_literal (longer match)
             / int_literal()
-            / string_literal()
+            / concatenated_string()
             / char_literal()
             / bool_literal()
             / null_literal()
@@ -5881,25 +9021,43 @@ peg::parser! {
         // SPECIAL EXPRESSION RULES (Task 4.6)
         // ====================================================================
         // These rules handle special expression forms:
-        // - sizeof(Type): Returns the size of a type in bytes
+        // - sizeof(Type) and sizeof(expr): Return the size of a type in bytes
+        // - alignof(Type) and alignof(expr): Return the alignment of a type
         // - range expressions: start..end or start..=end
         // - macro calls: name!(args) or name![args] or name!{args}
         //
         // Requirements validated: 1.2, 6.8, 6.15
 
-        /// Sizeof expression: sizeof(Type)
-        /// Returns Expression::Sizeof
+        /// Sizeof expression: sizeof(Type) or sizeof(expr)
+        /// Returns Expression::Sizeof or Expression::SizeofExpr
         ///
         /// The sizeof operator returns the size of a type in bytes.
-        /// Syntax: sizeof(Type)
+        /// Syntax: sizeof(Type) or sizeof(expr)
         /// Examples:
         /// - sizeof(int) -> size of int
         /// - sizeof(MyStruct) -> size of MyStruct
         /// - sizeof(int*) -> size of pointer to int
+        /// - sizeof(p.x) -> size of the type of p.x
         pub rule sizeof_expr() -> Expression
             = _ kw_sizeof() _ "(" _ ty:type_expr() _ ")" _ {
                 Expression::Sizeof { ty }
             }
+            / _ kw_sizeof() _ "(" _ e:expr() _ ")" _ {
+                Expression::SizeofExpr { expr: Box::new(e) }
+            }
+
+        /// Alignof expression: alignof(Type) or alignof(expr)
+        /// Returns Expression::Alignof or Expression::AlignofExpr
+        ///
+        /// The alignof operator returns the alignment of a type in bytes.
+        /// Syntax: alignof(Type) or alignof(expr)
+        pub rule alignof_expr() -> Expression
+            = _ kw_alignof() _ "(" _ ty:type_expr() _ ")" _ {
+                Expression::Alignof { ty }
+            }
+            / _ kw_alignof() _ "(" _ e:expr() _ ")" _ {
+                Expression::AlignofExpr { expr: Box::new(e) }
+            }
 
         /// Range expression: start..end or start..=end
         /// Returns Expression::Range
@@ -5950,15 +9108,16 @@ peg::parser! {
         /// - format!("{}", value)
         pub rule macro_call() -> Expression
             = _ name:ident() _ "!" _ args:macro_args() _ {
-                Expression::MacroCall { name, args }
+                let (delimiter, args) = args;
+                Expression::MacroCall { name, args, delimiter }
             }
 
         /// Helper: macro arguments with different delimiters
-        /// Returns Vec<Token> containing the raw tokens
-        rule macro_args() -> Vec<crate::ast::Token>
-            = "(" tokens:macro_token_stream() ")" { tokens }
-            / "[" tokens:macro_token_stream() "]" { tokens }
-            / "{" tokens:macro_token_stream() "}" { tokens }
+        /// Returns the delimiter used and the raw tokens
+        rule macro_args() -> (MacroDelimiter, Vec<crate::ast::Token>)
+            = "(" tokens:macro_token_stream() ")" { (MacroDelimiter::Parens, tokens) }
+            / "[" tokens:macro_token_stream() "]" { (MacroDelimiter::Brackets, tokens) }
+            / "{" tokens:macro_token_stream() "}" { (MacroDelimiter::Braces, tokens) }
 
         /// Helper: stream of tokens inside macro arguments
         /// Captures everything between delimiters as raw tokens
@@ -6000,7 +9159,12 @@ peg::parser! {
         /// Atom: the most basic expression forms (no postfix operations)
         /// These are the building blocks that postfix operations attach to
         rule atom() -> Expression
-            = sizeof_expr()
+            = if_expr()
+            / match_expr()
+            / lambda_expr()
+            / enum_variant_path()
+            / sizeof_expr()
+            / alignof_expr()
             / macro_call()
             / cast_expr()
             / tuple_lit()
@@ -6010,6 +9174,79 @@ peg::parser! {
             / literal_expr()
             / ident_expr()
 
+        /// Lambda: an anonymous function, returning void without `->`
+        /// Syntax: fn(params) -> Type { body }
+        rule lambda_expr() -> Expression
+            = "fn" !ident_char() _ "(" _ params:param_list()? _ ")"
+              return_type:(_ "->" _ t:type_expr() { t })? _ body:block() {
+                Expression::Lambda {
+                    params: params.unwrap_or_default(),
+                    return_type,
+                    body,
+                    by_value: false,
+                }
+            }
+
+        /// If expression: if/else in expression position, producing the same
+        /// conditional as `cond ? a : b`
+        /// Syntax: if (cond) { expr } else { expr }, with else-if chains
+        rule if_expr() -> Expression
+            = kw_if() _ "(" _ condition:expr() _ ")" _ then_expr:if_expr_branch() _ kw_else() _
+              else_expr:(if_expr() / if_expr_branch()) {
+                Expression::Ternary {
+                    condition: Box::new(condition),
+                    then_expr: Box::new(then_expr),
+                    else_expr: Box::new(else_expr),
+                }
+            }
+
+        rule if_expr_branch() -> Expression
+            = "{" _ e:expr() _ "}" { e }
+
+        /// Match expression: the value of the first arm whose pattern matches
+        /// Syntax: match (expr) { pattern => value, ... }
+        rule match_expr() -> Expression
+            = kw_match() _ "(" _ e:expr() _ ")" _ "{" _
+              arms:(match_arm() ** (_ "," _)) _ ","? _ "}" {
+                Expression::Match {
+                    expr: Box::new(e),
+                    arms,
+                }
+            }
+
+        rule match_arm() -> MatchArm
+            = pattern:pattern() _ "=>" _ body:assignment_expr() { MatchArm { pattern, body } }
+
+        /// Match arm pattern: _, a literal, Enum.Variant(patterns), a tuple of
+        /// patterns, or a name bound to the matched value
+        rule pattern() -> Pattern
+            = "_" !ident_char() { Pattern::Wildcard }
+            / enum_name:ident() _ ("." / "::") _ variant:ident()
+              fields:(_ "(" _ p:(pattern() ** (_ "," _)) _ ")" { p })? {
+                Pattern::Variant {
+                    enum_name,
+                    variant,
+                    fields: fields.unwrap_or_default(),
+                }
+            }
+            / "(" _ p:(pattern() ** (_ "," _)) _ ")" { Pattern::Tuple(p) }
+            / "-" _ l:(float_literal() / int_literal()) {
+                Pattern::Literal(l.negated().unwrap_or(l))
+            }
+            / l:(float_literal() / int_literal() / concatenated_string() / char_literal()
+                 / bool_literal()) { Pattern::Literal(l) }
+            / i:ident() { Pattern::Binding(i) }
+
+        /// Qualified enum variant: Enum::Variant, the same as Enum.Variant
+        /// (Type::method(args) is a type-scoped call, matched before atoms)
+        rule enum_variant_path() -> Expression
+            = e:ident() _ "::" _ v:ident() !(_ "(") {
+                Expression::FieldAccess {
+                    expr: Box::new(Expression::Ident(e)),
+                    field: v,
+                }
+            }
+
         /// Primary expression: the atomic building blocks of expressions
         /// Order matters for PEG ordered choice:
         /// 1. Type-scoped call (Type::method() - must come before postfix to avoid ambiguity)
@@ -6699,10 +9936,10 @@ peg::parser! {
         /// This handles the ambiguity where an identifier could be either a type or a name.
         pub rule let_stmt() -> Statement
             // With explicit type: let Type name [= expr];
-            = _ kw_let() __ ty:type_expr() __ name:ident() _ init:(_ "=" _ e:expr() { e })? _ ";" _ {
+            = _ kw_let() __ ty:type_expr() __ name:ident() dims:array_declarator() _ init:(_ "=" _ e:expr() { e })? _ ";" _ {
                 Statement::Let {
                     name,
-                    ty: Some(ty),
+                    ty: Some(array_of(ty, dims)),
                     init,
                     mutable: false,
                 }
@@ -6717,6 +9954,12 @@ peg::parser! {
                 }
             }
 
+        /// C-style array declarator after a variable name: buf[8], grid[2][3]
+        rule array_declarator() -> Vec<usize>
+            = (_ "[" _ n:int_literal() _ "]" {
+                n.int_value().unwrap_or_default() as usize
+            })*
+
         /// Var statement: mutable variable declaration
         /// Syntax: var [Type] name [= expr];
         /// Returns Statement::Var
@@ -6730,10 +9973,10 @@ peg::parser! {
         /// The grammar uses ordered choice to try type+name first, then name only.
         pub rule var_stmt() -> Statement
             // With explicit type: var Type name [= expr];
-            = _ kw_var() __ ty:type_expr() __ name:ident() _ init:(_ "=" _ e:expr() { e })? _ ";" _ {
+            = _ kw_var() __ ty:type_expr() __ name:ident() dims:array_declarator() _ init:(_ "=" _ e:expr() { e })? _ ";" _ {
                 Statement::Var {
                     name,
-                    ty: Some(ty),
+                    ty: Some(array_of(ty, dims)),
                     init,
                 }
             }
@@ -6837,6 +10080,35 @@ peg::parser! {
                 }
             }
 
+        /// Do-while statement: loop whose body runs before the condition
+        /// Syntax: do { body } while (condition);
+        /// Returns Statement::DoWhile
+        ///
+        /// Examples:
+        /// - do { x = x - 1; } while (x > 0);
+        pub rule do_while_stmt() -> Statement
+            = _ kw_do() _ body:block() _ kw_while() _ "(" _ condition:expr() _ ")" _ ";" _ {
+                Statement::DoWhile { body, condition }
+            }
+
+        /// Unsafe statement: a block whose body may perform unsafe operations
+        /// Syntax: unsafe { body }
+        /// Returns Statement::Unsafe
+        pub rule unsafe_stmt() -> Statement
+            = _ kw_unsafe() _ body:block() _ {
+                Statement::Unsafe(body)
+            }
+
+        /// Static assertion, checked at compile time
+        /// Syntax: static_assert(condition, "message");
+        /// Returns StaticAssert (used both as an item and as a statement)
+        pub rule static_assert() -> StaticAssert
+            = _ "static_assert" !ident_char() _ "(" _ condition:assignment_expr() _ "," _
+              message:string_literal() _ ")" _ ";" _ {
+                let Literal::String(message) = message else { unreachable!() };
+                StaticAssert { condition, message, position: Position::default() }
+            }
+
         /// For statement: C-style for loop
         /// Syntax: for (init; condition; increment) { body }
         /// Returns Statement::For
@@ -7043,14 +10315,14 @@ peg::parser! {
         /// Syntax: Type name
         rule param() -> Param
             = ty:type_expr() __ name:ident() {
-                Param { name, ty }
+                Param { name, ty, variadic: false }
             }
 
         /// Statement: any executable statement
         /// Returns Statement
         ///
         /// Order matters for PEG ordered choice:
-        /// 1. Control flow statements (if, while, for, switch) - must come before expression
+        /// 1. Control flow statements (if, while, do, for, switch) - must come before expression
         /// 2. Jump statements (return, break, continue)
         /// 3. Variable declarations (let, var, const)
         /// 4. Nested function declarations (must come before expr_stmt)
@@ -7061,12 +10333,15 @@ peg::parser! {
         pub rule statement() -> Statement
             = if_stmt()
             / while_stmt()
+            / do_while_stmt()
             / for_in_stmt()  // Must come before for_stmt
             / for_stmt()
             / switch_stmt()
             / return_stmt()
             / break_stmt()
             / continue_stmt()
+            / unsafe_stmt()
+            / a:static_assert() { Statement::StaticAssert(a) }
             / let_stmt()
             / var_stmt()
             / const_stmt()
@@ -7199,6 +10474,10 @@ peg::parser! {
             = name:ident() _ "=" _ value:attribute_literal() {
                 AttributeArg::NameValue { name, value }
             }
+            // Nested list: name(args)
+            / name:ident() _ args:attribute_args() {
+                AttributeArg::List { name, args }
+            }
             // Identifier
             / i:ident() {
                 AttributeArg::Ident(i)
@@ -7225,12 +10504,13 @@ peg::parser! {
         // ====================================================================
         // Function declarations are top-level items that define callable code.
         //
-        // Syntax: [static] [return_type | void] name(params) { body }
+        // Syntax: [static] [return_type | void] name[(type_params)](params) { body }
         //
         // Components:
         // - Visibility: static keyword makes function private, otherwise public
         // - Return type: void for no return, or any type expression
         // - Name: function identifier
+        // - Type parameters: optional list of names making the function generic
         // - Parameters: comma-separated list of Type name pairs
         // - Body: block of statements
         //
@@ -7241,7 +10521,7 @@ peg::parser! {
         // Requirements validated: 1.2, 6.1
 
         /// Function item: top-level function declaration
-        /// Syntax: [attributes] [static] [return_type | void] name(params) { body }
+        /// Syntax: [attributes] [static] [return_type | void] name[(type_params)](params) { body }
         /// Returns Item::Function
         ///
         /// Examples:
@@ -7251,10 +10531,11 @@ peg::parser! {
         /// - #[test] void test_something() { }
         pub rule function() -> Item
             // With attributes, static, and void return type
-            = _ attrs:attributes() _ kw_static() __ kw_void() __ name:ident() _ "(" _ params:function_params()? _ ")" _ body:block() _ {
+            = _ attrs:attributes() _ kw_static() __ kw_void() __ name:ident() _ generics:generic_params()? _ "(" _ params:function_params()? _ ")" _ body:block() _ {
                 Item::Function(Function {
                     visibility: Visibility::Private,
                     name,
+                    generics: generics.unwrap_or_default(),
                     params: params.unwrap_or_default(),
                     return_type: None,
                     body,
@@ -7263,10 +10544,11 @@ peg::parser! {
                 })
             }
             // With attributes, static, and explicit return type
-            / _ attrs:attributes() _ kw_static() __ return_type:type_expr() __ name:ident() _ "(" _ params:function_params()? _ ")" _ body:block() _ {
+            / _ attrs:attributes() _ kw_static() __ return_type:type_expr() __ name:ident() _ generics:generic_params()? _ "(" _ params:function_params()? _ ")" _ body:block() _ {
                 Item::Function(Function {
                     visibility: Visibility::Private,
                     name,
+                    generics: generics.unwrap_or_default(),
                     params: params.unwrap_or_default(),
                     return_type: Some(return_type),
                     body,
@@ -7275,10 +10557,11 @@ peg::parser! {
                 })
             }
             // With attributes and void return type (public)
-            / _ attrs:attributes() _ kw_void() __ name:ident() _ "(" _ params:function_params()? _ ")" _ body:block() _ {
+            / _ attrs:attributes() _ kw_void() __ name:ident() _ generics:generic_params()? _ "(" _ params:function_params()? _ ")" _ body:block() _ {
                 Item::Function(Function {
                     visibility: Visibility::Public,
                     name,
+                    generics: generics.unwrap_or_default(),
                     params: params.unwrap_or_default(),
                     return_type: None,
                     body,
@@ -7287,10 +10570,11 @@ peg::parser! {
                 })
             }
             // With attributes and explicit return type (public)
-            / _ attrs:attributes() _ return_type:type_expr() __ name:ident() _ "(" _ params:function_params()? _ ")" _ body:block() _ {
+            / _ attrs:attributes() _ return_type:type_expr() __ name:ident() _ generics:generic_params()? _ "(" _ params:function_params()? _ ")" _ body:block() _ {
                 Item::Function(Function {
                     visibility: Visibility::Public,
                     name,
+                    generics: generics.unwrap_or_default(),
                     params: params.unwrap_or_default(),
                     return_type: Some(return_type),
                     body,
@@ -7299,6 +10583,16 @@ peg::parser! {
                 })
             }
 
+        /// Type parameters of a generic function: `(T, U)` in
+        /// `T pick(T, U)(T a, U b)`, told apart from the parameter list by
+        /// the `(` that must follow
+        rule generic_params() -> Vec<Ident>
+            = generics:type_params() &(_ "(") { generics }
+
+        /// Parenthesized type parameter names: (T, U)
+        rule type_params() -> Vec<Ident>
+            = "(" _ generics:(ident() ++ (_ "," _)) _ ")" { generics }
+
         /// Function parameters: comma-separated list of parameters
         /// Supports regular parameters and self parameters for methods
         /// Returns Vec<Param>
@@ -7318,6 +10612,7 @@ peg::parser! {
         /// Single function parameter
         /// Supports:
         /// - Regular parameter: Type name
+        /// - Variadic parameter: Type... name
         /// - Self parameter: self (immutable reference)
         /// - Mutable self parameter: var &self
         /// - Immutable reference self: &self
@@ -7330,6 +10625,7 @@ peg::parser! {
                         ty: Box::new(Type::Ident(Ident::new("Self"))),
                         mutable: true,
                     },
+                    variadic: false,
                 }
             }
             // Immutable reference self parameter: &self
@@ -7340,6 +10636,7 @@ peg::parser! {
                         ty: Box::new(Type::Ident(Ident::new("Self"))),
                         mutable: false,
                     },
+                    variadic: false,
                 }
             }
             // Immutable self parameter: self
@@ -7347,11 +10644,16 @@ peg::parser! {
                 Param {
                     name: Ident::new("self"),
                     ty: Type::Ident(Ident::new("Self")),
+                    variadic: false,
                 }
             }
+            // Variadic parameter: Type... name
+            / ty:type_expr() _ "..." _ name:ident() {
+                Param { name, ty: variadic_type(ty), variadic: true }
+            }
             // Regular parameter: Type name
             / ty:type_expr() __ name:ident() {
-                Param { name, ty }
+                Param { name, ty, variadic: false }
             }
 
         // ====================================================================
@@ -7383,7 +10685,7 @@ peg::parser! {
         /// - struct Counter { int value; void increment(var &self) { self.value += 1; } }
         /// - #[derive(Debug)] struct Data { int id; }
         pub rule struct_def() -> Item
-            = _ attrs:attributes() _ kw_struct() __ name:ident() _ "{" _ members:struct_member()* _ "}" _ {
+            = _ attrs:attributes() _ kw_struct() __ name:ident() _ generics:type_params()? _ "{" _ members:struct_member()* _ "}" _ {
                 let mut fields = Vec::new();
                 let mut methods = Vec::new();
 
@@ -7397,6 +10699,7 @@ peg::parser! {
                 Item::Struct(Struct {
                     visibility: Visibility::Public,
                     name,
+                    generics: generics.unwrap_or_default(),
                     fields,
                     methods,
                     doc_comments: Vec::new(),
@@ -7407,9 +10710,22 @@ peg::parser! {
         /// Struct member: either a field or a method
         /// Used internally to parse struct contents
         rule struct_member() -> StructMember
-            = m:struct_method() { StructMember::Method(m) }
+            = _ attrs:attributes() _ kw_pub() __ m:struct_method() {
+                StructMember::Method(Function {
+                    visibility: Visibility::Public,
+                    attributes: attrs,
+                    ..m
+                })
+            }
+            / m:struct_method() { StructMember::Method(m) }
             / f:struct_field() { StructMember::Field(f) }
 
+        /// Struct member modifier: static makes a member private to the
+        /// struct's module, pub spells out the public default
+        rule member_visibility() -> Visibility
+            = kw_static() { Visibility::Private }
+            / kw_pub() { Visibility::Public }
+
         /// Struct field: Type name;
         /// Returns Field
         ///
@@ -7417,10 +10733,11 @@ peg::parser! {
         /// - int x;
         /// - bool active;
         /// - #[serde(skip)] int internal;
+        /// - static int limit;
         rule struct_field() -> Field
-            = _ attrs:attributes() _ ty:type_expr() __ name:ident() _ ";" _ {
+            = _ attrs:attributes() _ visibility:(v:member_visibility() __ { v })? ty:type_expr() __ name:ident() _ ";" _ {
                 Field {
-                    visibility: Visibility::Public,
+                    visibility: visibility.unwrap_or(Visibility::Public),
                     name,
                     ty,
                     doc_comments: Vec::new(),
@@ -7442,10 +10759,11 @@ peg::parser! {
         /// - static Point new(int x, int y) { ... }
         rule struct_method() -> Function
             // Static method with void return type
-            = _ attrs:attributes() _ kw_static() __ kw_void() __ name:ident() _ "(" _ params:function_params()? _ ")" _ body:block() _ {
+            = _ attrs:attributes() _ kw_static() __ kw_void() __ name:ident() _ generics:generic_params()? _ "(" _ params:function_params()? _ ")" _ body:block() _ {
                 Function {
                     visibility: Visibility::Private,
                     name,
+                    generics: generics.unwrap_or_default(),
                     params: params.unwrap_or_default(),
                     return_type: None,
                     body,
@@ -7454,10 +10772,11 @@ peg::parser! {
                 }
             }
             // Static method with explicit return type
-            / _ attrs:attributes() _ kw_static() __ return_type:type_expr() __ name:ident() _ "(" _ params:function_params()? _ ")" _ body:block() _ {
+            / _ attrs:attributes() _ kw_static() __ return_type:type_expr() __ name:ident() _ generics:generic_params()? _ "(" _ params:function_params()? _ ")" _ body:block() _ {
                 Function {
                     visibility: Visibility::Private,
                     name,
+                    generics: generics.unwrap_or_default(),
                     params: params.unwrap_or_default(),
                     return_type: Some(return_type),
                     body,
@@ -7466,10 +10785,11 @@ peg::parser! {
                 }
             }
             // Instance method with void return type
-            / _ attrs:attributes() _ kw_void() __ name:ident() _ "(" _ params:function_params()? _ ")" _ body:block() _ {
+            / _ attrs:attributes() _ kw_void() __ name:ident() _ generics:generic_params()? _ "(" _ params:function_params()? _ ")" _ body:block() _ {
                 Function {
                     visibility: Visibility::Public,
                     name,
+                    generics: generics.unwrap_or_default(),
                     params: params.unwrap_or_default(),
                     return_type: None,
                     body,
@@ -7478,10 +10798,11 @@ peg::parser! {
                 }
             }
             // Instance method with explicit return type
-            / _ attrs:attributes() _ return_type:type_expr() __ name:ident() _ "(" _ params:function_params()? _ ")" _ body:block() _ {
+            / _ attrs:attributes() _ return_type:type_expr() __ name:ident() _ generics:generic_params()? _ "(" _ params:function_params()? _ ")" _ body:block() _ {
                 Function {
                     visibility: Visibility::Public,
                     name,
+                    generics: generics.unwrap_or_default(),
                     params: params.unwrap_or_default(),
                     return_type: Some(return_type),
                     body,
@@ -7527,6 +10848,69 @@ peg::parser! {
                 })
             }
 
+        /// Trait declaration: trait Name { signature; ... }, also written
+        /// with interface
+        /// Returns Item::Trait
+        pub rule trait_def() -> Item
+            = _ attrs:attributes() _ kw_trait() __ name:ident() _ "{" _ methods:trait_method()* _ "}" _ {
+                Item::Trait(Trait {
+                    visibility: Visibility::Public,
+                    name,
+                    methods,
+                    doc_comments: Vec::new(),
+                    attributes: attrs,
+                })
+            }
+
+        /// Trait method signature: [return_type | void] name(params); or,
+        /// with a default implementation, followed by a block
+        /// Returns MethodSignature
+        rule trait_method() -> MethodSignature
+            = _ kw_void() __ name:ident() _ "(" _ params:function_params()? _ ")" _ body:trait_method_body() _ {
+                MethodSignature {
+                    name,
+                    params: params.unwrap_or_default(),
+                    return_type: None,
+                    body,
+                }
+            }
+            / _ return_type:type_expr() __ name:ident() _ "(" _ params:function_params()? _ ")" _ body:trait_method_body() _ {
+                MethodSignature {
+                    name,
+                    params: params.unwrap_or_default(),
+                    return_type: Some(return_type),
+                    body,
+                }
+            }
+
+        /// Trait method terminator: `;` for a bare signature or the default body
+        rule trait_method_body() -> Option<Block>
+            = ";" { None }
+            / body:block() { Some(body) }
+
+        /// Trait implementation: impl Trait for Type { method ... }, where
+        /// `Pair(T)` implements the trait for every `Pair<T>`
+        /// Returns Item::Impl
+        pub rule impl_block() -> Item
+            = _ kw_impl() __ trait_name:ident() __ kw_for() __ ty:impl_target() _ "{" _ methods:struct_method()* _ "}" _ {
+                let (generics, ty) = ty;
+                Item::Impl(Impl {
+                    trait_name,
+                    generics,
+                    ty,
+                    methods,
+                    position: Position::default(),
+                })
+            }
+
+        /// Implementing type with the type parameters it is generic over
+        rule impl_target() -> (Vec<Ident>, Type)
+            = base:ident() _ generics:type_params() {
+                let args = generics.iter().cloned().map(Type::Ident).collect();
+                (generics, Type::Generic { base: Box::new(Type::Ident(base)), args })
+            }
+            / ty:type_expr() { (Vec::new(), ty) }
+
         /// Enum variants: comma-separated list of variants with auto-numbering
         /// Returns Vec<EnumVariant> with values assigned
         rule enum_variants() -> Vec<EnumVariant>
@@ -7534,7 +10918,7 @@ peg::parser! {
                 let mut result = Vec::new();
                 let mut next_value: i64 = 0;
 
-                for (name, explicit_value) in variants.unwrap_or_default() {
+                for (name, fields, explicit_value) in variants.unwrap_or_default() {
                     let value = match explicit_value {
                         Some(v) => {
                             next_value = v + 1;
@@ -7546,31 +10930,33 @@ peg::parser! {
                             Some(v)
                         }
                     };
-                    result.push(EnumVariant { name, value });
+                    result.push(EnumVariant { name, value, fields });
                 }
 
                 result
             }
 
         /// Enum variant list: comma-separated variants
-        /// Returns Vec<(Ident, Option<i64>)> - name and optional explicit value
-        rule enum_variant_list() -> Vec<(Ident, Option<i64>)>
+        /// Returns Vec<(Ident, Vec<Param>, Option<i64>)> - name, payload fields
+        /// and optional explicit value
+        rule enum_variant_list() -> Vec<(Ident, Vec<Param>, Option<i64>)>
             = first:enum_variant_item() rest:(_ "," _ v:enum_variant_item() { v })* (_ ",")? {
                 let mut variants = vec![first];
                 variants.extend(rest);
                 variants
             }
 
-        /// Single enum variant: Name or Name = value
-        /// Returns (Ident, Option<i64>)
-        rule enum_variant_item() -> (Ident, Option<i64>)
-            = name:ident() _ "=" _ value:int_literal_value() { (name, Some(value)) }
-            / name:ident() { (name, None) }
+        /// Single enum variant: Name, Name = value or Name(Type field, ...)
+        /// Returns (Ident, Vec<Param>, Option<i64>)
+        rule enum_variant_item() -> (Ident, Vec<Param>, Option<i64>)
+            = name:ident() _ "=" _ value:int_literal_value() { (name, Vec::new(), Some(value)) }
+            / name:ident() _ "(" _ fields:param_list()? _ ")" { (name, fields.unwrap_or_default(), None) }
+            / name:ident() { (name, Vec::new(), None) }
 
         /// Integer literal value for enum variants
         /// Returns i64
         rule int_literal_value() -> i64
-            = n:$(['0'..='9']+) { n.parse().unwrap() }
+            = n:int_literal() { n.int_value().unwrap_or_default() }
 
         // ====================================================================
         // TYPEDEF ITEM (Task 6.5)
@@ -7663,12 +11049,14 @@ peg::parser! {
         /// - #define __EMPTY__
         pub rule macro_def() -> Item
             // Macro with parentheses delimiter: #define __NAME__(params) body
-            = _ "#" _ kw_define() __ name:macro_name() _ "(" _ params:macro_params()? _ ")" _ body:macro_body() _ {
+            = _ "#" _ kw_define() __ name:macro_name() _ "(" _ params:macro_params() _ ")" _ body:macro_body() _ {
+                let (params, variadic) = params;
                 Item::MacroDefinition(MacroDefinition {
                     name,
-                    params: params.unwrap_or_default(),
+                    params,
                     body,
                     delimiter: MacroDelimiter::Parens,
+                    variadic,
                 })
             }
             // Constant macro (no delimiter): #define __NAME__ body
@@ -7678,6 +11066,7 @@ peg::parser! {
                     params: vec![],
                     body,
                     delimiter: MacroDelimiter::None,
+                    variadic: false,
                 })
             }
 
@@ -7693,13 +11082,13 @@ peg::parser! {
                 Ident::new(format!("__{middle}__"))
             }
 
-        /// Macro parameters: comma-separated list of identifiers
-        /// Returns Vec<Ident>
-        rule macro_params() -> Vec<Ident>
-            = first:ident() rest:(_ "," _ i:ident() { i })* {
-                let mut params = vec![first];
-                params.extend(rest);
-                params
+        /// Macro parameters: comma-separated list of identifiers, optionally
+        /// ending in `...` for a variadic macro
+        /// Returns (Vec<Ident>, bool)
+        rule macro_params() -> (Vec<Ident>, bool)
+            = params:(ident() ** (_ "," _))
+              variadic:((_ "," _)? "..." { true })? {
+                (params, variadic.unwrap_or(false))
             }
 
         /// Macro body: token sequence until end of line or semicolon
@@ -7751,12 +11140,54 @@ peg::parser! {
         /// - typedef int MyInt;
         /// - #define __PI__ 3.14159
         pub rule item() -> Item
-            = macro_def()
+            = module_directive()
+            / macro_def()
+            / a:static_assert() { Item::StaticAssert(a) }
+            / extern_block()
             / struct_def()
             / enum_def()
+            / trait_def()
+            / impl_block()
             / typedef_def()
             / function()
 
+        /// Module directive: #import or #export of a dotted module path
+        /// Syntax: #import a.b.c | #export a.b.c
+        /// Returns Item::Import or Item::Export
+        pub rule module_directive() -> Item
+            = _ "#" _ "import" !ident_char() __ path:(ident() ++ (_ "." _)) _ {
+                Item::Import(Import { path, alias: None })
+            }
+            / _ "#" _ "export" !ident_char() __ path:(ident() ++ (_ "." _)) _ {
+                Item::Export(Export { path, alias: None })
+            }
+
+        /// Extern block: foreign function declarations with an optional ABI
+        /// Syntax: extern ["abi"] { signature* }
+        /// Returns Item::Extern
+        pub rule extern_block() -> Item
+            = _ kw_extern() _ abi:(s:string_literal() {
+                let Literal::String(abi) = s else { unreachable!() };
+                abi
+            })? _ "{" _ functions:foreign_function()* _ "}" _ {
+                Item::Extern(Extern { abi, functions })
+            }
+
+        /// Foreign function signature: Type name(params [, ...]);
+        rule foreign_function() -> ForeignFunction
+            = _ ty:type_expr() __ name:ident() _ "(" _ params:(param() ** (_ "," _)) _
+              variadic:((_ "," _)? "..." { true })? _ ")" _ ";" _ {
+                ForeignFunction {
+                    name,
+                    params,
+                    return_type: match ty {
+                        Type::Primitive(PrimitiveType::Void) => None,
+                        ty => Some(ty),
+                    },
+                    variadic: variadic.unwrap_or(false),
+                }
+            }
+
         /// File: complete Crusty source file
         /// Syntax: item*
         /// Returns File AST node
@@ -7800,6 +11231,135 @@ peg::parser! {
     }
 }
 
+/// Parse a complete file with the PEG grammar, recovering at statement boundaries
+///
+/// On failure the offending statement (from the previous `;`, `{` or `}` up to
+/// and including the next `;` at the same nesting depth) is blanked out and the
+/// file is parsed again, so every independent syntax error is reported in one
+/// pass. Blanking keeps newlines, so later errors still carry their original
+/// line and column.
+#[allow(dead_code)]
+pub fn parse_peg_file(source: &str) -> Result<File, Vec<ParseError>> {
+    let partial = parse_peg_file_partial(source);
+    if partial.errors.is_empty() {
+        Ok(partial.file)
+    } else {
+        Err(partial.errors)
+    }
+}
+
+/// Parse a file with the PEG grammar, keeping what parses around the errors
+///
+/// Recovers as `parse_peg_file` does and returns the file with the failing
+/// statements blanked out. When blanking statements is not enough for the
+/// file to parse, the file is empty.
+#[allow(dead_code)]
+pub fn parse_peg_file_partial(source: &str) -> PartialFile {
+    let mut text = source.to_string();
+    let mut errors = Vec::new();
+
+    loop {
+        let error = match crusty_peg_parser::file(&text) {
+            Ok(file) => return PartialFile { file, errors },
+            Err(error) => error,
+        };
+        let offset = error.location.offset;
+        errors.push(convert_peg_error(&error, source));
+
+        if errors.len() >= MAX_PARSE_ERRORS {
+            break;
+        }
+        let (start, end) = recovery_range(&text, offset);
+        if text[start..end].trim().is_empty() {
+            // Nothing left to skip: further attempts would fail at the same spot
+            break;
+        }
+        text = blank_range(&text, start, end);
+    }
+    PartialFile {
+        file: File {
+            items: Vec::new(),
+            doc_comments: Vec::new(),
+        },
+        errors,
+    }
+}
+
+/// Convert a rust-peg failure into the crate's ParseError
+pub fn convert_peg_error(
+    error: &peg::error::ParseError<peg::str::LineCol>,
+    source: &str,
+) -> ParseError {
+    use crate::error::{Position, Span};
+
+    let position = Position::new(error.location.line, error.location.column);
+    let found = match source[error.location.offset..].chars().next() {
+        Some(ch) => format!("'{}'", ch),
+        None => "end of file".to_string(),
+    };
+    let mut expected: Vec<String> = error.expected.tokens().map(str::to_string).collect();
+    expected.sort();
+    expected.dedup();
+
+    ParseError::new(
+        Span::new(position, position),
+        "unexpected input",
+        expected,
+        found,
+    )
+    .with_snippet(source)
+}
+
+/// Byte range of the statement surrounding `offset`, used for error recovery
+///
+/// The range starts after the closest preceding `;`, `{` or `}` and ends after
+/// the next `;` outside of any nested delimiters, or before a `}` closing the
+/// enclosing block.
+fn recovery_range(text: &str, offset: usize) -> (usize, usize) {
+    let start = text[..offset]
+        .rfind([';', '{', '}'])
+        .map_or(0, |index| index + 1);
+
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (index, ch) in text[offset..].char_indices() {
+        if in_string {
+            match ch {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match ch {
+            '"' => in_string = true,
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' if depth > 0 => depth -= 1,
+            '}' if depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    return (start, offset + index + 1);
+                }
+            }
+            '}' => return (start, offset + index),
+            ';' if depth == 0 => return (start, offset + index + 1),
+            _ => {}
+        }
+    }
+    (start, text.len())
+}
+
+/// Replace everything in `start..end` except newlines with spaces
+fn blank_range(text: &str, start: usize, end: usize) -> String {
+    let blanked: String = text[start..end]
+        .chars()
+        .map(|ch| if ch == '\n' { ch } else { ' ' })
+        .collect();
+    format!("{}{}{}", &text[..start], blanked, &text[end..])
+}
+
 /// Helper function to parse macro body content into tokens
 /// This is a simplified implementation that creates tokens from the body text.
 /// A full implementation would properly tokenize the body using the lexer.
@@ -8373,6 +11933,7 @@ mod peg_tests {
             "if",
             "else",
             "while",
+            "do",
             "for",
             "in",
             "return",
@@ -8384,6 +11945,7 @@ mod peg_tests {
             "namespace",
             "extern",
             "unsafe",
+            "volatile",
             "loop",
             "match",
             "switch",
@@ -8571,6 +12133,41 @@ mod peg_tests {
             crusty_peg_parser::int_literal("123456789"),
             Ok(Literal::Int(123456789))
         );
+        assert_eq!(
+            crusty_peg_parser::int_literal("1_000_000"),
+            Ok(Literal::Int(1_000_000))
+        );
+        assert_eq!(
+            crusty_peg_parser::int_literal("0xFF"),
+            Ok(Literal::RadixInt(255, Radix::Hex))
+        );
+        assert_eq!(
+            crusty_peg_parser::int_literal("0o7_7"),
+            Ok(Literal::RadixInt(63, Radix::Octal))
+        );
+        assert_eq!(
+            crusty_peg_parser::int_literal("0b1010"),
+            Ok(Literal::RadixInt(10, Radix::Binary))
+        );
+        assert!(crusty_peg_parser::int_literal("0b102").is_err());
+        assert!(crusty_peg_parser::int_literal("0x1_0000_0000_0000_0000").is_err());
+
+        // Type suffixes
+        assert_eq!(
+            crusty_peg_parser::int_literal("42u32"),
+            Ok(Literal::Suffixed(
+                Box::new(Literal::Int(42)),
+                PrimitiveType::U32
+            ))
+        );
+        assert_eq!(
+            crusty_peg_parser::int_literal("0xFF_i64"),
+            Ok(Literal::Suffixed(
+                Box::new(Literal::RadixInt(255, Radix::Hex)),
+                PrimitiveType::I64
+            ))
+        );
+        assert!(crusty_peg_parser::int_literal("42u128").is_err());
     }
 
     #[test]
@@ -8602,6 +12199,103 @@ mod peg_tests {
             crusty_peg_parser::float_literal("3e8"),
             Ok(Literal::Float(3e8))
         );
+
+        // Type suffixes
+        assert_eq!(
+            crusty_peg_parser::float_literal("3.5f32"),
+            Ok(Literal::Suffixed(
+                Box::new(Literal::Float(3.5)),
+                PrimitiveType::F32
+            ))
+        );
+        assert_eq!(
+            crusty_peg_parser::float_literal("2f64"),
+            Ok(Literal::Suffixed(
+                Box::new(Literal::Float(2.0)),
+                PrimitiveType::F64
+            ))
+        );
+    }
+
+    #[test]
+    fn test_peg_byte_string_literal() {
+        assert_eq!(
+            crusty_peg_parser::byte_string_literal(r#"b"ab\x7f\n""#),
+            Ok(Literal::ByteString(vec![b'a', b'b', 0x7f, b'\n']))
+        );
+        assert_eq!(
+            crusty_peg_parser::expr(r#"b"""#),
+            Ok(Expression::Literal(Literal::ByteString(Vec::new())))
+        );
+        assert!(crusty_peg_parser::byte_string_literal("b\"caf\u{e9}\"").is_err());
+    }
+
+    #[test]
+    fn test_peg_raw_string_literal() {
+        assert_eq!(
+            crusty_peg_parser::raw_string_literal(r#"r"C:\temp\""#),
+            Ok(Literal::RawString(r"C:\temp\".to_string()))
+        );
+        assert_eq!(
+            crusty_peg_parser::expr(r##"r#"say "hi""#"##),
+            Ok(Expression::Literal(Literal::RawString(
+                r#"say "hi""#.to_string()
+            )))
+        );
+        assert!(crusty_peg_parser::raw_string_literal(r##"r#"open""##).is_err());
+    }
+
+    #[test]
+    fn test_peg_constant_array_size() {
+        assert_eq!(
+            crusty_peg_parser::type_expr("int[2 * 8]"),
+            Ok(Type::Array {
+                ty: Box::new(Type::Primitive(PrimitiveType::Int)),
+                size: Some(16),
+            })
+        );
+        assert_eq!(
+            crusty_peg_parser::type_expr("u8[(int)(sizeof(u32)) + 1]"),
+            Ok(Type::Array {
+                ty: Box::new(Type::Primitive(PrimitiveType::U8)),
+                size: Some(5),
+            })
+        );
+        assert!(crusty_peg_parser::type_expr("int[n]").is_err());
+        assert!(crusty_peg_parser::type_expr("int[1 - 2]").is_err());
+    }
+
+    #[test]
+    fn test_peg_array_declarator() {
+        assert_eq!(
+            crusty_peg_parser::let_stmt("let char buf[8] = \"abc\";"),
+            crusty_peg_parser::let_stmt("let char[8] buf = \"abc\";")
+        );
+        assert_eq!(
+            crusty_peg_parser::var_stmt("var int grid[2][3];"),
+            Ok(Statement::Var {
+                name: Ident::new("grid"),
+                ty: Some(array_of(Type::Primitive(PrimitiveType::Int), vec![2, 3])),
+                init: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_peg_wide_and_concatenated_strings() {
+        assert_eq!(
+            crusty_peg_parser::wide_string_literal("L\"caf\u{e9}\""),
+            Ok(Literal::WideString("caf\u{e9}".to_string()))
+        );
+        assert_eq!(
+            crusty_peg_parser::expr(r#"u"a" "b"  L"c""#),
+            Ok(Expression::Literal(Literal::WideString("abc".to_string())))
+        );
+        assert_eq!(
+            crusty_peg_parser::expr(r#""a" /* split */ "b""#),
+            Ok(Expression::Literal(Literal::String("ab".to_string())))
+        );
+        assert!(crusty_peg_parser::expr(r#"b"a" "b""#).is_err());
     }
 
     #[test]
@@ -8637,6 +12331,13 @@ mod peg_tests {
             crusty_peg_parser::string_literal("\"backslash\\\\here\""),
             Ok(Literal::String("backslash\\here".to_string()))
         );
+        assert_eq!(
+            crusty_peg_parser::string_literal(r#""\x41\u{e9}\u{1F600}""#),
+            Ok(Literal::String("A\u{e9}\u{1F600}".to_string()))
+        );
+        assert!(crusty_peg_parser::string_literal(r#""\x80""#).is_err());
+        assert!(crusty_peg_parser::string_literal(r#""\u{110000}""#).is_err());
+        assert!(crusty_peg_parser::string_literal(r#""\u{}""#).is_err());
     }
 
     #[test]
@@ -8672,6 +12373,14 @@ mod peg_tests {
             crusty_peg_parser::char_literal("'\\\\'"),
             Ok(Literal::Char('\\'))
         );
+        assert_eq!(
+            crusty_peg_parser::char_literal(r"'\u{e9}'"),
+            Ok(Literal::Char('\u{e9}'))
+        );
+        assert_eq!(
+            crusty_peg_parser::char_literal(r"'\x7f'"),
+            Ok(Literal::Char('\x7f'))
+        );
     }
 
     #[test]
@@ -9033,6 +12742,58 @@ mod enum_tests {
         }
     }
 
+    #[test]
+    fn test_peg_enum_with_payloads() {
+        let result = crusty_peg_parser::enum_def(
+            "enum Shape { Circle(float radius), Rect(float w, float h), Empty }",
+        );
+        let Ok(Item::Enum(e)) = result else {
+            panic!("Expected Item::Enum, got {:?}", result);
+        };
+        assert_eq!(e.variants.len(), 3);
+        assert_eq!(e.variants[0].fields.len(), 1);
+        assert_eq!(e.variants[0].fields[0].name.name, "radius");
+        assert_eq!(e.variants[1].fields.len(), 2);
+        assert!(e.variants[2].fields.is_empty());
+    }
+
+    #[test]
+    fn test_peg_trait_and_impl() {
+        let result = crusty_peg_parser::trait_def(
+            "interface Shape { float area(&self); void reset(var &self); }",
+        );
+        let Ok(Item::Trait(trait_def)) = result else {
+            panic!("Expected Item::Trait, got {:?}", result);
+        };
+        assert_eq!(trait_def.methods.len(), 2);
+        assert_eq!(trait_def.methods[1].return_type, None);
+
+        let result = crusty_peg_parser::impl_block(
+            "impl Shape for Circle { float area(&self) { return self.r; } }",
+        );
+        let Ok(Item::Impl(impl_block)) = result else {
+            panic!("Expected Item::Impl, got {:?}", result);
+        };
+        assert_eq!(impl_block.trait_name.name, "Shape");
+        assert_eq!(impl_block.methods.len(), 1);
+    }
+
+    #[test]
+    fn test_peg_generic_function() {
+        let result = crusty_peg_parser::function("K first(K, V)(K key, V value) { return key; }");
+        let Ok(Item::Function(func)) = result else {
+            panic!("Expected Item::Function, got {:?}", result);
+        };
+        assert_eq!(func.generics, vec![Ident::new("K"), Ident::new("V")]);
+        assert_eq!(func.params.len(), 2);
+
+        let result = crusty_peg_parser::function("int f(T a) { return 1; }");
+        let Ok(Item::Function(func)) = result else {
+            panic!("Expected Item::Function, got {:?}", result);
+        };
+        assert!(func.generics.is_empty());
+    }
+
     #[test]
     fn test_peg_enum_with_attributes() {
         // Test enum with attributes
@@ -9344,6 +13105,24 @@ mod macro_def_tests {
         }
     }
 
+    #[test]
+    fn test_peg_macro_def_variadic() {
+        let Ok(Item::MacroDefinition(m)) =
+            crusty_peg_parser::macro_def("#define __LOG__(fmt, ...) __println__(fmt, __VA_ARGS__)")
+        else {
+            panic!("Expected Item::MacroDefinition");
+        };
+        assert_eq!(m.params.len(), 1);
+        assert!(m.variadic);
+
+        let Ok(Item::MacroDefinition(m)) = crusty_peg_parser::macro_def("#define __ALL__(...) 0")
+        else {
+            panic!("Expected Item::MacroDefinition");
+        };
+        assert!(m.params.is_empty());
+        assert!(m.variadic);
+    }
+
     #[test]
     fn test_peg_macro_def_brackets_not_supported() {
         // Bracket delimiter is NOT supported for #define (only for macro usage)
@@ -10566,8 +14345,22 @@ mod primitive_type_tests {
         // Test that invalid type names are rejected
         assert!(crusty_peg_parser::primitive_type("string").is_err());
         assert!(crusty_peg_parser::primitive_type("double").is_err());
-        assert!(crusty_peg_parser::primitive_type("long").is_err());
-        assert!(crusty_peg_parser::primitive_type("short").is_err());
+        assert!(crusty_peg_parser::primitive_type("u128").is_err());
+        assert!(crusty_peg_parser::primitive_type("short long").is_err());
+    }
+
+    #[test]
+    fn test_peg_primitive_c_type() {
+        let parse = |text| crusty_peg_parser::primitive_type(text).unwrap();
+        assert_eq!(parse("unsigned int"), Type::Primitive(PrimitiveType::U32));
+        assert_eq!(parse("long long"), Type::Primitive(PrimitiveType::I64));
+        assert_eq!(parse("unsigned char"), Type::Primitive(PrimitiveType::U8));
+        assert_eq!(parse("short"), Type::Primitive(PrimitiveType::I16));
+        assert_eq!(
+            parse("unsigned long"),
+            Type::Ident(Ident::new("unsigned long"))
+        );
+        assert_eq!(parse("long"), Type::Ident(Ident::new("long")));
     }
 }
 
@@ -10609,6 +14402,71 @@ mod complex_type_tests {
         );
     }
 
+    #[test]
+    fn test_peg_volatile_pointer_type() {
+        assert_eq!(
+            crusty_peg_parser::type_expr("volatile u32*"),
+            Ok(Type::Pointer {
+                ty: Box::new(Type::Volatile {
+                    ty: Box::new(Type::Primitive(PrimitiveType::U32))
+                }),
+                mutable: false
+            })
+        );
+        assert!(matches!(
+            crusty_peg_parser::statement("unsafe { x = 1; }"),
+            Ok(Statement::Unsafe(body)) if body.statements.len() == 1
+        ));
+    }
+
+    #[test]
+    fn test_peg_module_directives() {
+        let file = crusty_peg_parser::file(
+            "#import std.collections.HashMap\n#export util.clamp\nvoid main() {}\n",
+        )
+        .unwrap();
+        assert!(matches!(&file.items[0], Item::Import(import) if import.path.len() == 3));
+        assert!(matches!(
+            &file.items[1],
+            Item::Export(export) if export.path[1].name == "clamp"
+        ));
+        assert!(crusty_peg_parser::file("#include \"util.crst\"\n").is_err());
+    }
+
+    #[test]
+    fn test_peg_extern_block() {
+        let file = crusty_peg_parser::file(
+            "extern \"C\" {\n    int puts(char* s);\n    int printf(char* fmt, ...);\n    void abort();\n}\n",
+        )
+        .unwrap();
+        let Item::Extern(extern_block) = &file.items[0] else {
+            panic!("Expected extern block");
+        };
+        assert_eq!(extern_block.abi.as_deref(), Some("C"));
+        assert_eq!(extern_block.functions.len(), 3);
+        assert!(extern_block.functions[1].variadic);
+        assert_eq!(extern_block.functions[2].return_type, None);
+    }
+
+    #[test]
+    fn test_peg_static_assert() {
+        let file = crusty_peg_parser::file(
+            "static_assert(sizeof(int) == 4, \"int is 4 bytes\");\nvoid main() { static_assert(true, \"ok\"); }",
+        )
+        .unwrap();
+        assert!(matches!(
+            &file.items[0],
+            Item::StaticAssert(StaticAssert { message, .. }) if message == "int is 4 bytes"
+        ));
+        let Item::Function(func) = &file.items[1] else {
+            panic!("Expected function");
+        };
+        assert!(matches!(
+            &func.body.statements[0],
+            Statement::StaticAssert(StaticAssert { message, .. }) if message == "ok"
+        ));
+    }
+
     #[test]
     fn test_peg_pointer_type_double() {
         // Test double pointer: T**
@@ -11742,6 +15600,7 @@ mod keyword_properties {
         "if",
         "else",
         "while",
+        "do",
         "for",
         "in",
         "return",
@@ -11753,6 +15612,7 @@ mod keyword_properties {
         "namespace",
         "extern",
         "unsafe",
+        "volatile",
         "loop",
         "match",
         "switch",
@@ -11764,6 +15624,16 @@ mod keyword_properties {
         "i64",
         "u32",
         "u64",
+        "i8",
+        "i16",
+        "isize",
+        "u8",
+        "u16",
+        "usize",
+        "unsigned",
+        "signed",
+        "short",
+        "long",
         "float",
         "f32",
         "f64",
@@ -11773,6 +15643,8 @@ mod keyword_properties {
         "true",
         "false",
         "NULL",
+        "sizeof",
+        "alignof",
     ];
 
     // Strategy: Generate a random keyword from the list
@@ -12632,6 +16504,12 @@ mod primary_expression_tests {
             ("i64", Type::Primitive(PrimitiveType::I64)),
             ("u32", Type::Primitive(PrimitiveType::U32)),
             ("u64", Type::Primitive(PrimitiveType::U64)),
+            ("i8", Type::Primitive(PrimitiveType::I8)),
+            ("i16", Type::Primitive(PrimitiveType::I16)),
+            ("isize", Type::Primitive(PrimitiveType::Isize)),
+            ("u8", Type::Primitive(PrimitiveType::U8)),
+            ("u16", Type::Primitive(PrimitiveType::U16)),
+            ("usize", Type::Primitive(PrimitiveType::Usize)),
             ("float", Type::Primitive(PrimitiveType::Float)),
             ("f32", Type::Primitive(PrimitiveType::F32)),
             ("f64", Type::Primitive(PrimitiveType::F64)),
@@ -13958,6 +17836,26 @@ mod call_access_expression_tests {
         );
     }
 
+    #[test]
+    fn test_expr_if_expression() {
+        assert_eq!(
+            crusty_peg_parser::expr("if (a) { b } else if (c) { d } else { e }"),
+            crusty_peg_parser::expr("a ? b : c ? d : e")
+        );
+        assert!(crusty_peg_parser::expr("if (a) { b }").is_err());
+    }
+
+    #[test]
+    fn test_expr_match_expression() {
+        let source = "match (s) { Shape.Dot => 0, Option::Some((x, _)) => x, -1 => 1, n => n, }";
+        let mut parser = Parser::new(source).unwrap();
+        assert_eq!(
+            crusty_peg_parser::expr(source),
+            Ok(parser.parse_expression().unwrap())
+        );
+        assert!(crusty_peg_parser::expr("match (s) { 1 }").is_err());
+    }
+
     #[test]
     fn test_expr_ternary_with_expressions() {
         let result = crusty_peg_parser::expr("x > 0 ? 1 : 0");
@@ -14249,6 +18147,32 @@ mod special_expression_tests {
         );
     }
 
+    #[test]
+    fn test_sizeof_and_alignof_expression_operands() {
+        let field = Expression::FieldAccess {
+            expr: Box::new(Expression::Ident(Ident::new("p"))),
+            field: Ident::new("x"),
+        };
+        assert_eq!(
+            crusty_peg_parser::sizeof_expr("sizeof(p.x)"),
+            Ok(Expression::SizeofExpr {
+                expr: Box::new(field.clone())
+            })
+        );
+        assert_eq!(
+            crusty_peg_parser::alignof_expr("alignof(p.x)"),
+            Ok(Expression::AlignofExpr {
+                expr: Box::new(field)
+            })
+        );
+        assert_eq!(
+            crusty_peg_parser::expr("alignof(u64)"),
+            Ok(Expression::Alignof {
+                ty: Type::Primitive(PrimitiveType::U64)
+            })
+        );
+    }
+
     #[test]
     fn test_sizeof_in_expression() {
         // Test sizeof as part of a larger expression
@@ -14396,7 +18320,7 @@ mod special_expression_tests {
         // Test macro call with parentheses
         let result = crusty_peg_parser::macro_call("println!(\"hello\")");
         assert!(result.is_ok());
-        if let Ok(Expression::MacroCall { name, args }) = result {
+        if let Ok(Expression::MacroCall { name, args, .. }) = result {
             assert_eq!(name, Ident::new("println"));
             assert_eq!(args.len(), 1);
         } else {
@@ -14409,7 +18333,7 @@ mod special_expression_tests {
         // Test macro call with brackets
         let result = crusty_peg_parser::macro_call("vec![1, 2, 3]");
         assert!(result.is_ok());
-        if let Ok(Expression::MacroCall { name, args }) = result {
+        if let Ok(Expression::MacroCall { name, args, .. }) = result {
             assert_eq!(name, Ident::new("vec"));
             assert_eq!(args.len(), 1);
         } else {
@@ -14422,7 +18346,7 @@ mod special_expression_tests {
         // Test macro call with braces
         let result = crusty_peg_parser::macro_call("format!{\"value: {}\", x}");
         assert!(result.is_ok());
-        if let Ok(Expression::MacroCall { name, args }) = result {
+        if let Ok(Expression::MacroCall { name, args, .. }) = result {
             assert_eq!(name, Ident::new("format"));
             assert_eq!(args.len(), 1);
         } else {
@@ -14435,7 +18359,7 @@ mod special_expression_tests {
         // Test macro call with empty arguments
         let result = crusty_peg_parser::macro_call("empty!()");
         assert!(result.is_ok());
-        if let Ok(Expression::MacroCall { name, args }) = result {
+        if let Ok(Expression::MacroCall { name, args, .. }) = result {
             assert_eq!(name, Ident::new("empty"));
             assert!(args.is_empty());
         } else {
@@ -15115,11 +19039,11 @@ mod statement_tests {
     #[test]
     fn test_let_stmt_with_u32_type() {
         // Test let statement with u32 type
-        let result = crusty_peg_parser::let_stmt("let u32 unsigned = 42;");
+        let result = crusty_peg_parser::let_stmt("let u32 count = 42;");
         assert_eq!(
             result,
             Ok(Statement::Let {
-                name: Ident::new("unsigned"),
+                name: Ident::new("count"),
                 ty: Some(Type::Primitive(PrimitiveType::U32)),
                 init: Some(Expression::Literal(Literal::Int(42))),
                 mutable: false,
@@ -15609,6 +19533,31 @@ mod control_flow_tests {
         }
     }
 
+    #[test]
+    fn test_do_while_stmt() {
+        let result = crusty_peg_parser::statement("do { x = x - 1; } while (x > 0);");
+        match result.unwrap() {
+            Statement::DoWhile { body, condition } => {
+                assert_eq!(body.statements.len(), 1);
+                assert!(matches!(
+                    condition,
+                    Expression::Binary {
+                        op: BinaryOp::Gt,
+                        ..
+                    }
+                ));
+            }
+            other => panic!("Expected DoWhile statement, got {:?}", other),
+        }
+
+        assert!(crusty_peg_parser::statement("do { } while (true)").is_err());
+        // Identifiers merely starting with `do` are not the keyword
+        assert!(matches!(
+            crusty_peg_parser::statement("done = 1;"),
+            Ok(Statement::Expr(_))
+        ));
+    }
+
     // ========================================================================
     // FOR STATEMENT TESTS
     // ========================================================================
@@ -16287,3 +20236,89 @@ mod function_item_tests {
         }
     }
 }
+
+#[cfg(test)]
+mod peg_error_recovery_tests {
+    use super::*;
+    use crate::error::Position;
+
+    #[test]
+    fn test_parse_peg_file_success() {
+        let file = parse_peg_file("int add(int a, int b) { return a + b; }").unwrap();
+        assert_eq!(file.items.len(), 1);
+    }
+
+    #[test]
+    fn test_peg_error_has_position_expected_and_snippet() {
+        let source = "void f() {\n    let x = ;\n}\n";
+        let errors = parse_peg_file(source).unwrap_err();
+        assert_eq!(errors.len(), 1);
+
+        let error = &errors[0];
+        assert_eq!(error.span.start, Position::new(2, 13));
+        assert_eq!(error.found, "';'");
+        assert!(!error.expected.is_empty());
+        assert!(error.expected.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(
+            error.snippet.as_deref(),
+            Some("  |\n2 |     let x = ;\n  |             ^")
+        );
+    }
+
+    #[test]
+    fn test_peg_error_at_end_of_file() {
+        let errors = parse_peg_file("void f() {").unwrap_err();
+        assert_eq!(errors[0].found, "end of file");
+        assert!(errors[0].expected.contains(&"\"}\"".to_string()));
+    }
+
+    #[test]
+    fn test_peg_recovers_at_statement_boundaries() {
+        let source = "\
+void f() {
+    let x = ;
+    let y = 2;
+    return 1 +;
+}
+
+void g() {
+    let z = ( ;
+}
+";
+        let errors = parse_peg_file(source).unwrap_err();
+        let lines: Vec<usize> = errors.iter().map(|e| e.span.start.line).collect();
+        assert_eq!(lines, vec![2, 4, 8]);
+    }
+
+    #[test]
+    fn test_peg_recovery_skips_nested_blocks() {
+        let source = "void f() {\n    if (x ==) { let a = 1; }\n    let b = ;\n}\n";
+        let errors = parse_peg_file(source).unwrap_err();
+        let lines: Vec<usize> = errors.iter().map(|e| e.span.start.line).collect();
+        assert_eq!(lines, vec![2, 3]);
+    }
+
+    #[test]
+    fn test_recovery_range_stops_at_enclosing_brace() {
+        let text = "{ let x = 1 }";
+        let offset = text.find('1').unwrap();
+        assert_eq!(recovery_range(text, offset), (1, text.len() - 1));
+    }
+
+    #[test]
+    fn test_blank_range_preserves_newlines() {
+        assert_eq!(blank_range("a;b\nc;d", 2, 6), "a; \n  d");
+    }
+
+    #[test]
+    fn test_peg_partial_file_keeps_recovered_items() {
+        let source = "void f() {\n    let x = ;\n}\n\nint g() { return 1; }\n";
+        let partial = parse_peg_file_partial(source);
+        assert_eq!(partial.errors.len(), 1);
+        assert_eq!(partial.file.items.len(), 2);
+        match &partial.file.items[0] {
+            Item::Function(func) => assert!(func.body.statements.is_empty()),
+            other => panic!("Expected Item::Function, got {:?}", other),
+        }
+    }
+}
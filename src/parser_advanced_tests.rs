@@ -56,6 +56,45 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_generic_method() {
+        let source = "struct Box { T pick(T)(&self, T a, T b) { return a; } }";
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+
+        match &file.items[0] {
+            Item::Struct(s) => {
+                assert_eq!(s.methods[0].name.name, "pick");
+                assert_eq!(s.methods[0].generics.len(), 1);
+                assert_eq!(s.methods[0].params.len(), 3);
+            }
+            _ => panic!("Expected struct"),
+        }
+
+        let source = "trait Pick { T pick(T)(&self, T a); }";
+        let error = Parser::new(source).unwrap().parse_file().unwrap_err();
+        assert_eq!(error.message, "trait method 'pick' cannot be generic");
+    }
+
+    #[test]
+    fn test_parse_generic_struct() {
+        let source = "struct Pair(K, V) { K key; V value; Pair<V, K> swapped(self) { return (Pair<V, K>){ .key = self.value, .value = self.key }; } }";
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+
+        match &file.items[0] {
+            Item::Struct(s) => {
+                let generics: Vec<&str> = s.generics.iter().map(|g| g.name.as_str()).collect();
+                assert_eq!(generics, ["K", "V"]);
+                assert_eq!(s.fields.len(), 2);
+                assert_eq!(s.methods[0].name.name, "swapped");
+                assert!(matches!(
+                    s.methods[0].return_type,
+                    Some(Type::Generic { .. })
+                ));
+            }
+            _ => panic!("Expected struct"),
+        }
+    }
+
     #[test]
     fn test_parse_type_scoped_call() {
         let source = "int main() { return @Vec.new(); }";
@@ -0,0 +1,231 @@
+// Copyright (c) 2026 Mark Ferrell
+// Licensed under the MIT License. See LICENSE.txt in the project root.
+
+//! Fuzz tests for the lexer and parser on pathological input.
+//!
+//! Every input must produce a result, never a panic or stack overflow, and
+//! must do so in bounded time.
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ParseError;
+    use crate::lexer::{MAX_SOURCE_BYTES, MAX_STRING_LITERAL_BYTES};
+    use crate::parser::{Parser, MAX_NESTING_DEPTH, MAX_OPERATOR_DEPTH};
+    use proptest::prelude::*;
+    use std::time::{Duration, Instant};
+
+    /// Generous bound on parse time; pathological input used to take minutes
+    const TIME_LIMIT: Duration = Duration::from_secs(10);
+
+    fn parse(source: &str) -> Result<crate::ast::File, ParseError> {
+        let start = Instant::now();
+        let result = Parser::new(source).and_then(|mut parser| parser.parse_file());
+        assert!(
+            start.elapsed() < TIME_LIMIT,
+            "parsing took {:?}",
+            start.elapsed()
+        );
+        result
+    }
+
+    fn nesting_error() -> String {
+        format!("nesting exceeds the limit of {} levels", MAX_NESTING_DEPTH)
+    }
+
+    #[test]
+    fn test_deeply_nested_expressions_are_rejected() {
+        let depth = 100_000;
+        let parens = format!(
+            "int main() {{ return {}1{}; }}",
+            "(".repeat(depth),
+            ")".repeat(depth)
+        );
+        assert_eq!(parse(&parens).unwrap_err().message, nesting_error());
+
+        let negations = format!("bool f() {{ return {}true; }}", "!".repeat(depth));
+        assert_eq!(parse(&negations).unwrap_err().message, nesting_error());
+    }
+
+    #[test]
+    fn test_nesting_up_to_the_limit_is_accepted() {
+        let depth = MAX_NESTING_DEPTH / 2;
+        let parens = format!(
+            "int main() {{ return {}1{}; }}",
+            "(".repeat(depth),
+            ")".repeat(depth)
+        );
+        assert!(parse(&parens).is_ok());
+
+        let blocks = format!(
+            "void main() {{ {}{} }}",
+            "if (true) { ".repeat(depth),
+            "}".repeat(depth)
+        );
+        assert!(parse(&blocks).is_ok());
+    }
+
+    #[test]
+    fn test_deeply_nested_blocks_and_types_are_rejected() {
+        let depth = 50_000;
+        let blocks = format!(
+            "void main() {{ {}{} }}",
+            "if (true) { ".repeat(depth),
+            "}".repeat(depth)
+        );
+        assert_eq!(parse(&blocks).unwrap_err().message, nesting_error());
+
+        // Generic arguments alternate between parentheses and brackets
+        let generics = format!(
+            "void main() {{ let v = @Vec({}int{}.new(); }}",
+            "Vec[Vec(".repeat(depth),
+            ")]".repeat(depth)
+        );
+        assert_eq!(parse(&generics).unwrap_err().message, nesting_error());
+
+        let references = format!("void f({}int x) {{ }}", "& ".repeat(depth));
+        assert_eq!(parse(&references).unwrap_err().message, nesting_error());
+    }
+
+    #[test]
+    fn test_long_operator_chains_are_rejected() {
+        let chain =
+            |terms: usize| format!("int main() {{ return {}; }}", vec!["1"; terms].join(" + "));
+        assert!(parse(&chain(MAX_OPERATOR_DEPTH + 1)).is_ok());
+
+        let err = parse(&chain(100_000)).unwrap_err();
+        assert_eq!(
+            err.message,
+            format!(
+                "expression chains more than {} operators; split it into smaller expressions",
+                MAX_OPERATOR_DEPTH
+            )
+        );
+
+        // A parenthesized chain counts toward the chain it is an operand of
+        let half = vec!["1"; MAX_OPERATOR_DEPTH / 2 + 1].join(" + ");
+        let nested = format!("int main() {{ return {} + ({}); }}", half, half);
+        assert!(parse(&nested).is_err());
+        // Chains beside each other do not add up
+        let sums = format!("int main() {{ return ({}) + ({}); }}", half, half);
+        assert!(parse(&sums).is_ok());
+    }
+
+    #[test]
+    fn test_long_lookahead_is_linear() {
+        // A declaration's type is scanned ahead before it is parsed, buffering
+        // every `*`; the buffer must not be shifted once per token
+        let stars = format!("void main() {{ int{} x = NULL; }}", "*".repeat(200_000));
+        assert_eq!(parse(&stars).unwrap_err().message, nesting_error());
+
+        let pointers = format!(
+            "void main() {{ int{} x = NULL; }}",
+            "*".repeat(MAX_NESTING_DEPTH / 2)
+        );
+        assert!(parse(&pointers).is_ok());
+    }
+
+    #[test]
+    fn test_comment_and_literal_limits() {
+        let comments = format!("{}int main() {{ return 0; }}", "// note\n".repeat(200_000));
+        assert!(parse(&comments).is_ok());
+
+        let empty = "/**/ int main() { return 0; //\n}";
+        assert!(parse(empty).is_ok());
+
+        let unterminated = format!("int main() {{ }} /* {}", "x".repeat(1 << 20));
+        let err = parse(&unterminated).unwrap_err();
+        assert_eq!(err.message, "unterminated block comment");
+        assert_eq!(err.span.start.column, 16);
+
+        let long_string = format!(
+            "void main() {{ let s = \"{}\"; }}",
+            "x".repeat(MAX_STRING_LITERAL_BYTES + 1)
+        );
+        assert_eq!(
+            parse(&long_string).unwrap_err().message,
+            format!(
+                "string literal exceeds the limit of {} bytes",
+                MAX_STRING_LITERAL_BYTES
+            )
+        );
+
+        let oversized = " ".repeat(MAX_SOURCE_BYTES + 1);
+        assert!(parse(&oversized).unwrap_err().message.ends_with(&format!(
+            "exceeding the limit of {} bytes",
+            MAX_SOURCE_BYTES
+        )));
+    }
+
+    /// Fragments that exercise most of the grammar when shuffled together
+    const FRAGMENTS: &[&str] = &[
+        "int",
+        "void",
+        "struct",
+        "enum",
+        "typedef",
+        "if",
+        "else",
+        "while",
+        "for",
+        "return",
+        "break",
+        "unsafe",
+        "volatile",
+        "static_assert",
+        "sizeof",
+        "#define",
+        "#[",
+        "x",
+        "Foo",
+        "__M__",
+        "1",
+        "2.5",
+        "\"s\"",
+        "'c'",
+        "true",
+        "NULL",
+        "(",
+        ")",
+        "{",
+        "}",
+        "[",
+        "]",
+        "<",
+        ">",
+        ",",
+        ";",
+        ":",
+        "::",
+        ".",
+        "..",
+        "=",
+        "==",
+        "+",
+        "-",
+        "*",
+        "&",
+        "!",
+        "?",
+        "->",
+        "/*",
+        "*/",
+        "//",
+        "\n",
+    ];
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(256))]
+
+        #[test]
+        fn fuzz_token_soup_never_panics(
+            fragments in prop::collection::vec(prop::sample::select(FRAGMENTS), 0..200)
+        ) {
+            let _ = parse(&fragments.join(" "));
+        }
+
+        #[test]
+        fn fuzz_arbitrary_text_never_panics(source in "\\PC{0,400}") {
+            let _ = parse(&source);
+        }
+    }
+}
@@ -0,0 +1,216 @@
+// Copyright (c) 2026 Mark Ferrell
+// Licensed under the MIT License. See LICENSE.txt in the project root.
+
+//! What the platform the generated code runs on decides about a program:
+//! the size of pointers and of C's `long`, and which C library signals
+//! exist with which numbers.
+//!
+//! Without `--target` the platform is the host. With it, the description is
+//! built from the target's `rustc --print cfg`, the same configuration
+//! `#[cfg]` attributes are evaluated against, so semantic analysis and
+//! codegen agree with the items the target keeps:
+//!
+//! ```text
+//! long ticks = 0;     // i64 on x86_64-unknown-linux-gnu, i32 on x86_64-pc-windows-msvc
+//! raise(SIGABRT);     // raise(6) on Linux, raise(22) on Windows
+//! ```
+
+use crate::ast::PrimitiveType;
+use crate::cfg::TargetCfg;
+
+/// C signal numbers, with the values POSIX gives them
+const UNIX_SIGNALS: &[(&str, i32)] = &[
+    ("SIGHUP", 1),
+    ("SIGINT", 2),
+    ("SIGQUIT", 3),
+    ("SIGABRT", 6),
+    ("SIGKILL", 9),
+    ("SIGALRM", 14),
+    ("SIGTERM", 15),
+];
+
+/// C signal numbers of the Windows C runtime, which has no SIGHUP, SIGQUIT,
+/// SIGKILL or SIGALRM
+const WINDOWS_SIGNALS: &[(&str, i32)] = &[("SIGINT", 2), ("SIGTERM", 15), ("SIGABRT", 22)];
+
+/// C data models: the target family and pointer size (in bytes) each
+/// applies to, `None` matching any, and the size of a `long`. The first
+/// matching row wins.
+const DATA_MODELS: &[(Option<&str>, Option<usize>, usize)] = &[
+    // LLP64, and Windows' ILP32
+    (Some("windows"), None, 4),
+    // LP64
+    (None, Some(8), 8),
+    // ILP32, and 16-bit targets whose long is still 32 bits
+    (None, None, 4),
+];
+
+/// Description of the platform a program is compiled for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Platform {
+    /// Bytes in a pointer, an `isize` and a `usize`
+    pub pointer_width: usize,
+    /// Bytes in a C `long`
+    pub long_width: usize,
+    /// The C library's signals, empty for targets without an operating
+    /// system
+    signals: &'static [(&'static str, i32)],
+}
+
+impl Platform {
+    /// The platform the compiler itself runs on
+    pub fn host() -> Self {
+        let family = if cfg!(windows) {
+            Some("windows")
+        } else if cfg!(unix) {
+            Some("unix")
+        } else {
+            None
+        };
+        Self::describe(family, std::mem::size_of::<usize>())
+    }
+
+    /// The platform of a target, from its `rustc --print cfg`
+    pub fn from_cfg(cfg: &TargetCfg) -> Self {
+        let family = ["windows", "unix"]
+            .into_iter()
+            .find(|family| cfg.is_set(family));
+        let pointer_width = cfg
+            .value("target_pointer_width")
+            .and_then(|bits| bits.parse::<usize>().ok())
+            .map_or(8, |bits| bits / 8);
+        Self::describe(family, pointer_width)
+    }
+
+    /// One platform of each C data model: ILP32, LP64 and LLP64
+    pub fn data_models() -> [Self; 3] {
+        [
+            Self::describe(Some("unix"), 4),
+            Self::describe(Some("unix"), 8),
+            Self::describe(Some("windows"), 8),
+        ]
+    }
+
+    fn describe(family: Option<&str>, pointer_width: usize) -> Self {
+        let long_width = DATA_MODELS
+            .iter()
+            .find(|(model_family, model_width, _)| {
+                model_family.is_none_or(|model_family| family == Some(model_family))
+                    && model_width.is_none_or(|model_width| model_width == pointer_width)
+            })
+            .map_or(4, |&(_, _, long_width)| long_width);
+        let signals = match family {
+            Some("windows") => WINDOWS_SIGNALS,
+            Some(_) => UNIX_SIGNALS,
+            None => &[],
+        };
+        Self {
+            pointer_width,
+            long_width,
+            signals,
+        }
+    }
+
+    /// The fixed-width type C's `long` is on this platform
+    pub fn long_type(&self) -> PrimitiveType {
+        if self.long_width == 8 {
+            PrimitiveType::I64
+        } else {
+            PrimitiveType::I32
+        }
+    }
+
+    /// The fixed-width type the C type `name` (`long` or `unsigned long`)
+    /// is on this platform
+    pub fn c_type(&self, name: &str) -> Option<PrimitiveType> {
+        match (name, self.long_width) {
+            ("long", _) => Some(self.long_type()),
+            ("unsigned long", 8) => Some(PrimitiveType::U64),
+            ("unsigned long", _) => Some(PrimitiveType::U32),
+            _ => None,
+        }
+    }
+
+    /// Number of the C signal constant `name`, if this platform has it
+    pub fn signal_number(&self, name: &str) -> Option<i32> {
+        self.signals
+            .iter()
+            .find(|(signal, _)| *signal == name)
+            .map(|&(_, number)| number)
+    }
+
+    /// Whether the platform has a C library with `signal()` and `raise()`
+    pub fn has_signals(&self) -> bool {
+        !self.signals.is_empty()
+    }
+
+    /// Size of a value of `primitive` in bytes
+    pub fn size_of(&self, primitive: &PrimitiveType) -> usize {
+        match primitive {
+            PrimitiveType::Void => 0,
+            PrimitiveType::Bool | PrimitiveType::I8 | PrimitiveType::U8 => 1,
+            PrimitiveType::I16 | PrimitiveType::U16 => 2,
+            PrimitiveType::Int
+            | PrimitiveType::I32
+            | PrimitiveType::U32
+            | PrimitiveType::F32
+            | PrimitiveType::Char => 4,
+            PrimitiveType::I64 | PrimitiveType::U64 | PrimitiveType::F64 | PrimitiveType::Float => {
+                8
+            }
+            PrimitiveType::Isize | PrimitiveType::Usize => self.pointer_width,
+        }
+    }
+}
+
+impl Default for Platform {
+    fn default() -> Self {
+        Self::host()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINUX: &str =
+        "target_family=\"unix\"\ntarget_os=\"linux\"\ntarget_pointer_width=\"64\"\nunix\n";
+    const WINDOWS: &str =
+        "target_family=\"windows\"\ntarget_os=\"windows\"\ntarget_pointer_width=\"64\"\nwindows\n";
+    const ARM32: &str =
+        "target_family=\"unix\"\ntarget_os=\"linux\"\ntarget_pointer_width=\"32\"\nunix\n";
+    const BARE_METAL: &str = "target_os=\"none\"\ntarget_pointer_width=\"32\"\n";
+
+    #[test]
+    fn test_data_models_follow_the_target() {
+        let linux = Platform::from_cfg(&TargetCfg::parse(LINUX));
+        assert_eq!((linux.pointer_width, linux.long_width), (8, 8));
+        assert_eq!(linux.long_type(), PrimitiveType::I64);
+
+        let windows = Platform::from_cfg(&TargetCfg::parse(WINDOWS));
+        assert_eq!((windows.pointer_width, windows.long_width), (8, 4));
+        assert_eq!(windows.long_type(), PrimitiveType::I32);
+        assert_eq!(linux.c_type("unsigned long"), Some(PrimitiveType::U64));
+        assert_eq!(windows.c_type("unsigned long"), Some(PrimitiveType::U32));
+        assert_eq!(windows.c_type("short"), None);
+
+        let arm32 = Platform::from_cfg(&TargetCfg::parse(ARM32));
+        assert_eq!((arm32.pointer_width, arm32.long_width), (4, 4));
+        assert_eq!(arm32.size_of(&PrimitiveType::Usize), 4);
+        assert_eq!(linux.size_of(&PrimitiveType::Usize), 8);
+    }
+
+    #[test]
+    fn test_signal_numbers_follow_the_target() {
+        let linux = Platform::from_cfg(&TargetCfg::parse(LINUX));
+        let windows = Platform::from_cfg(&TargetCfg::parse(WINDOWS));
+        let bare_metal = Platform::from_cfg(&TargetCfg::parse(BARE_METAL));
+
+        assert_eq!(linux.signal_number("SIGABRT"), Some(6));
+        assert_eq!(windows.signal_number("SIGABRT"), Some(22));
+        assert_eq!(linux.signal_number("SIGHUP"), Some(1));
+        assert_eq!(windows.signal_number("SIGHUP"), None);
+        assert!(!bare_metal.has_signals());
+        assert_eq!(bare_metal.signal_number("SIGINT"), None);
+    }
+}
@@ -0,0 +1,219 @@
+// Copyright (c) 2026 Mark Ferrell
+// Licensed under the MIT License. See LICENSE.txt in the project root.
+
+//! Conditional compilation: `#ifdef`, `#ifndef`, `#else` and `#endif`.
+//!
+//! This pass runs on the source text before it is parsed, so a block that
+//! is compiled out may contain anything, including code that would not
+//! parse:
+//!
+//! ```text
+//! #ifdef DEBUG
+//! void trace(int n) { __println__("{}", n); }
+//! #else
+//! void trace(int n) {}
+//! #endif
+//! ```
+//!
+//! A name is defined when it is passed with `--define NAME` or when a
+//! `#define` of it appears earlier in a block that is compiled in. Directive
+//! lines and the lines of skipped blocks are replaced with empty lines, so
+//! the positions of errors in the rest of the file are unchanged.
+
+use crate::error::{ParseError, Position, Span};
+use std::collections::HashSet;
+
+/// A conditional block whose `#endif` has not been reached yet
+struct Conditional {
+    /// Line of the `#ifdef` or `#ifndef` opening the block
+    line: usize,
+    /// Whether the enclosing blocks are compiled in
+    enclosing: bool,
+    /// Whether the current branch is compiled in
+    taken: bool,
+    /// Whether the `#else` branch has started
+    in_else: bool,
+}
+
+/// Resolve the conditional blocks of `source`, given the names defined on
+/// the command line
+pub fn preprocess(source: &str, defines: &[String]) -> Result<String, ParseError> {
+    let mut defined: HashSet<&str> = defines.iter().map(String::as_str).collect();
+    let mut open: Vec<Conditional> = Vec::new();
+    let mut output = String::with_capacity(source.len());
+
+    for (index, line) in source.split_inclusive('\n').enumerate() {
+        let number = index + 1;
+        let active = open
+            .last()
+            .is_none_or(|block| block.enclosing && block.taken);
+        let newline = &line[line.trim_end_matches(['\r', '\n']).len()..];
+
+        match directive(line) {
+            Some((kind @ ("ifdef" | "ifndef"), rest)) => {
+                let name = macro_name(rest, number, line)?;
+                let is_defined = defined.contains(name);
+                open.push(Conditional {
+                    line: number,
+                    enclosing: active,
+                    taken: if kind == "ifdef" {
+                        is_defined
+                    } else {
+                        !is_defined
+                    },
+                    in_else: false,
+                });
+            }
+            Some(("else", rest)) => {
+                expect_end(rest, "else", number, line)?;
+                let Some(block) = open.last_mut() else {
+                    return Err(error(number, line, "#else without #ifdef or #ifndef"));
+                };
+                if block.in_else {
+                    return Err(error(
+                        number,
+                        line,
+                        format!("second #else for the #ifdef on line {}", block.line),
+                    ));
+                }
+                block.in_else = true;
+                block.taken = !block.taken;
+            }
+            Some(("endif", rest)) => {
+                expect_end(rest, "endif", number, line)?;
+                if open.pop().is_none() {
+                    return Err(error(number, line, "#endif without #ifdef or #ifndef"));
+                }
+            }
+            Some(("define", rest)) if active => {
+                if let Some(name) = rest.trim_start().split(|c: char| !is_name_char(c)).next() {
+                    defined.insert(name);
+                }
+                output.push_str(line);
+                continue;
+            }
+            _ if active => {
+                output.push_str(line);
+                continue;
+            }
+            _ => {}
+        }
+        output.push_str(newline);
+    }
+
+    match open.last() {
+        Some(block) => Err(error(
+            block.line,
+            source.lines().nth(block.line - 1).unwrap_or_default(),
+            "#ifdef without #endif",
+        )),
+        None => Ok(output),
+    }
+}
+
+/// The directive a line starts with and the text after it
+fn directive(line: &str) -> Option<(&str, &str)> {
+    let rest = line.trim_start().strip_prefix('#')?.trim_start();
+    let end = rest
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(rest.len());
+    Some((&rest[..end], &rest[end..]))
+}
+
+/// The name tested by an `#ifdef` or `#ifndef`
+fn macro_name<'a>(rest: &'a str, number: usize, line: &str) -> Result<&'a str, ParseError> {
+    let rest = strip_comment(rest).trim();
+    if rest.is_empty() || !rest.chars().all(is_name_char) {
+        return Err(error(
+            number,
+            line,
+            "expected a single name after #ifdef or #ifndef",
+        ));
+    }
+    Ok(rest)
+}
+
+fn expect_end(rest: &str, directive: &str, number: usize, line: &str) -> Result<(), ParseError> {
+    if strip_comment(rest).trim().is_empty() {
+        Ok(())
+    } else {
+        Err(error(
+            number,
+            line,
+            format!("unexpected text after #{}", directive),
+        ))
+    }
+}
+
+fn strip_comment(text: &str) -> &str {
+    text.find("//").map_or(text, |start| &text[..start])
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn error(number: usize, line: &str, message: impl Into<String>) -> ParseError {
+    let column = line.len() - line.trim_start().len() + 1;
+    let position = Position::new(number, column);
+    ParseError::new(
+        Span::new(position, position),
+        message,
+        Vec::new(),
+        line.trim().to_string(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keeps_the_branch_for_defined_names() {
+        let source =
+            "#ifdef DEBUG\nint level() { return 2; }\n#else\nint level() { return 0; }\n#endif\n";
+        assert_eq!(
+            preprocess(source, &["DEBUG".to_string()]).unwrap(),
+            "\nint level() { return 2; }\n\n\n\n"
+        );
+        assert_eq!(
+            preprocess(source, &[]).unwrap(),
+            "\n\n\nint level() { return 0; }\n\n"
+        );
+    }
+
+    #[test]
+    fn test_defines_and_nesting() {
+        let source = "#define __FAST__ 1\n#ifndef __FAST__\nthis does not parse (\n#else\n#ifdef SLOW\nint a;\n#endif\nint b;\n#endif\n";
+        assert_eq!(
+            preprocess(source, &[]).unwrap(),
+            "#define __FAST__ 1\n\n\n\n\n\n\nint b;\n\n"
+        );
+
+        // A #define in a skipped block defines nothing
+        let skipped = "#ifdef A\n#define __B__ 1\n#endif\n#ifdef __B__\nint b;\n#endif\n";
+        assert_eq!(preprocess(skipped, &[]).unwrap(), "\n\n\n\n\n\n");
+    }
+
+    #[test]
+    fn test_unbalanced_directives_are_errors() {
+        let unterminated = preprocess("int a;\n  #ifdef A\nint b;\n", &[]).unwrap_err();
+        assert_eq!(unterminated.message, "#ifdef without #endif");
+        assert_eq!(
+            (unterminated.span.start.line, unterminated.span.start.column),
+            (2, 3)
+        );
+
+        let stray = preprocess("#endif\n", &[]).unwrap_err();
+        assert_eq!(stray.message, "#endif without #ifdef or #ifndef");
+
+        let twice = preprocess("#ifdef A\n#else\n#else\n#endif\n", &[]).unwrap_err();
+        assert_eq!(twice.message, "second #else for the #ifdef on line 1");
+
+        let unnamed = preprocess("#ifdef\n#endif\n", &[]).unwrap_err();
+        assert_eq!(
+            unnamed.message,
+            "expected a single name after #ifdef or #ifndef"
+        );
+    }
+}
@@ -6,17 +6,158 @@
 use crate::ast::*;
 use crate::codegen::{CodeGenerator, TargetLanguage};
 
+/// Default maximum line width, matching rustfmt's `max_width`
+pub const DEFAULT_WIDTH: usize = 100;
+
+/// Layout document for Wadler-style pretty printing
+///
+/// A document describes text together with the places where it may be broken.
+/// Each `Group` is laid out flat if its contents fit in the remaining width,
+/// otherwise its `Line`s and `SoftLine`s become newlines at the current nesting.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Doc {
+    /// Literal text (must not contain newlines)
+    Text(String),
+    /// A space when flat, a newline when broken
+    Line,
+    /// Nothing when flat, a newline when broken
+    SoftLine,
+    /// Documents laid out one after another
+    Concat(Vec<Doc>),
+    /// Increase the indentation of broken lines inside the document
+    Nest(usize, Box<Doc>),
+    /// Lay out the document flat if it fits, otherwise break it
+    Group(Box<Doc>),
+}
+
+impl Doc {
+    pub fn text(text: impl Into<String>) -> Self {
+        Doc::Text(text.into())
+    }
+
+    pub fn concat(docs: Vec<Doc>) -> Self {
+        Doc::Concat(docs)
+    }
+
+    pub fn nest(indent: usize, doc: Doc) -> Self {
+        Doc::Nest(indent, Box::new(doc))
+    }
+
+    pub fn group(doc: Doc) -> Self {
+        Doc::Group(Box::new(doc))
+    }
+
+    /// Join documents with `separator` followed by a `Line`
+    pub fn join(docs: Vec<Doc>, separator: &str) -> Self {
+        let mut parts = Vec::new();
+        for (i, doc) in docs.into_iter().enumerate() {
+            if i > 0 {
+                parts.push(Doc::text(separator));
+                parts.push(Doc::Line);
+            }
+            parts.push(doc);
+        }
+        Doc::Concat(parts)
+    }
+}
+
+/// Render a document into text
+///
+/// `indent` is the indentation used after a broken line and `column` is the
+/// column the first character will be written at.
+pub fn render(doc: &Doc, width: usize, indent: usize, column: usize) -> String {
+    let mut output = String::new();
+    let mut column = column;
+    // (indentation, flat mode, document)
+    let mut stack: Vec<(usize, bool, &Doc)> = vec![(indent, false, doc)];
+
+    while let Some((indent, flat, doc)) = stack.pop() {
+        match doc {
+            Doc::Text(text) => {
+                output.push_str(text);
+                column += text.chars().count();
+            }
+            Doc::Line | Doc::SoftLine if flat => {
+                if matches!(doc, Doc::Line) {
+                    output.push(' ');
+                    column += 1;
+                }
+            }
+            Doc::Line | Doc::SoftLine => {
+                output.push('\n');
+                output.push_str(&" ".repeat(indent));
+                column = indent;
+            }
+            Doc::Concat(docs) => {
+                for doc in docs.iter().rev() {
+                    stack.push((indent, flat, doc));
+                }
+            }
+            Doc::Nest(extra, doc) => stack.push((indent + extra, flat, doc)),
+            Doc::Group(doc) => {
+                let flat = flat || fits(width.saturating_sub(column), doc, &stack);
+                stack.push((indent, flat, doc));
+            }
+        }
+    }
+
+    output
+}
+
+/// Check whether `doc` laid out flat, followed by the rest of the line, fits
+fn fits(width: usize, doc: &Doc, rest: &[(usize, bool, &Doc)]) -> bool {
+    let mut remaining = width as isize;
+    let mut stack: Vec<(bool, &Doc)> = vec![(true, doc)];
+    let mut rest = rest.iter().rev();
+
+    loop {
+        if remaining < 0 {
+            return false;
+        }
+        let (flat, doc) = match stack.pop() {
+            Some(entry) => entry,
+            None => match rest.next() {
+                Some((_, flat, doc)) => (*flat, *doc),
+                None => return true,
+            },
+        };
+        match doc {
+            Doc::Text(text) => remaining -= text.chars().count() as isize,
+            Doc::Line if flat => remaining -= 1,
+            Doc::SoftLine if flat => {}
+            // The rest of the line ends at the next break
+            Doc::Line | Doc::SoftLine => return true,
+            Doc::Concat(docs) => {
+                for doc in docs.iter().rev() {
+                    stack.push((flat, doc));
+                }
+            }
+            Doc::Nest(_, doc) | Doc::Group(doc) => stack.push((flat, doc)),
+        }
+    }
+}
+
 /// Pretty printer for formatting source code
 #[allow(dead_code)]
 pub struct PrettyPrinter {
     target: TargetLanguage,
+    width: usize,
 }
 
 #[allow(dead_code)]
 impl PrettyPrinter {
     /// Create a new pretty printer for the specified target language
     pub fn new(target: TargetLanguage) -> Self {
-        Self { target }
+        Self {
+            target,
+            width: DEFAULT_WIDTH,
+        }
+    }
+
+    /// Set the maximum line width used when wrapping Crusty output
+    pub fn with_width(mut self, width: usize) -> Self {
+        self.width = width;
+        self
     }
 
     /// Format source code according to language conventions
@@ -52,6 +193,7 @@ impl PrettyPrinter {
 
         // Regenerate Crusty code from AST with proper formatting
         let mut generator = CodeGenerator::new(TargetLanguage::Crusty);
+        generator.set_max_width(Some(self.width));
         let formatted = generator.generate(&file);
 
         Ok(formatted)
@@ -71,9 +213,9 @@ impl PrettyPrinter {
     pub fn format_ast_as_crusty(&self, file: &File) -> Result<String, String> {
         // Generate Crusty code from AST
         let mut generator = CodeGenerator::new(TargetLanguage::Crusty);
+        generator.set_max_width(Some(self.width));
         let code = generator.generate(file);
 
-        // Format the generated code (currently just returns as-is)
         Ok(code)
     }
 }
@@ -132,6 +274,7 @@ return a+b;
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::empty(),
@@ -161,4 +304,181 @@ return a+b;
         // In Crusty, int is the return type, main is the function name
         assert!(formatted.contains("main()"));
     }
+
+    /// Format Crusty code twice and check the output is stable
+    fn assert_crusty_round_trip(code: &str) -> String {
+        let printer = PrettyPrinter::new(TargetLanguage::Crusty);
+        let first = printer.format(code).expect("first format failed");
+        let second = printer.format(&first).expect("re-format failed");
+        assert_eq!(first, second, "formatting is not idempotent");
+        first
+    }
+
+    #[test]
+    fn test_format_crusty_define_with_delimiters() {
+        let formatted = assert_crusty_round_trip(
+            "#define __MAX__(a, b) ((a) > (b) ? (a) : (b))\n#define __VEC__[x] x\n#define __PI__ 3.14\n",
+        );
+        assert!(formatted.contains("#define __MAX__(a, b) ((a) > (b) ? (a) : (b))"));
+        assert!(formatted.contains("#define __VEC__[x] x"));
+        assert!(formatted.contains("#define __PI__ 3.14"));
+    }
+
+    #[test]
+    fn test_format_crusty_attributes_with_arguments() {
+        let formatted = assert_crusty_round_trip(
+            "#[derive(Debug, Clone)]\nstruct Point { int x; int y; }\n#[cfg(feature = \"fast\")]\nvoid f() { }\n",
+        );
+        assert!(formatted.contains("#[derive(Debug, Clone)]"));
+        assert!(formatted.contains("#[cfg(feature = \"fast\")]"));
+        assert!(formatted.contains("    int x;"));
+    }
+
+    #[test]
+    fn test_format_crusty_macro_invocations() {
+        let formatted = assert_crusty_round_trip(
+            "void f() { __println__(\"x = {}\", 1); let v = __vec__[1, 2, 3]; }",
+        );
+        assert!(formatted.contains("__println__(\"x = {}\", 1);"));
+        assert!(formatted.contains("__vec__[1, 2, 3]"));
+    }
+
+    #[test]
+    fn test_format_crusty_labeled_loops() {
+        let formatted = assert_crusty_round_trip(
+            "void f() { .outer: while (true) { .inner: loop { break outer; } continue outer; } }",
+        );
+        assert!(formatted.contains(".outer: while (true)"));
+        assert!(formatted.contains("break outer;"));
+        assert!(formatted.contains("continue outer;"));
+    }
+
+    #[test]
+    fn test_format_crusty_struct_initializer() {
+        let formatted = assert_crusty_round_trip("void f() { let p = (Point){ .x = 1, .y = 2 }; }");
+        assert!(formatted.contains("let p = (Point){ .x = 1, .y = 2 };"));
+    }
+
+    #[test]
+    fn test_format_ast_as_rust_struct_initializer_and_macros() {
+        use crate::parser::Parser;
+
+        let code = "void f() { let p = (Point){ .x = 1 }; __println__(\"{}\", p.x); }";
+        let file = Parser::new(code).unwrap().parse_file().unwrap();
+        let printer = PrettyPrinter::new(TargetLanguage::Rust);
+        let formatted = printer.format_ast_as_rust(&file).unwrap();
+        assert!(formatted.contains("let p = Point { x: 1 };"));
+        assert!(formatted.contains("println!(\"{}\", p.x);"));
+    }
+
+    #[test]
+    fn test_render_group_fits_flat() {
+        let doc = Doc::group(Doc::concat(vec![
+            Doc::text("f("),
+            Doc::nest(
+                4,
+                Doc::concat(vec![
+                    Doc::SoftLine,
+                    Doc::join(vec![Doc::text("a"), Doc::text("b")], ","),
+                ]),
+            ),
+            Doc::SoftLine,
+            Doc::text(")"),
+        ]));
+        assert_eq!(render(&doc, 80, 0, 0), "f(a, b)");
+    }
+
+    #[test]
+    fn test_render_group_breaks_when_too_wide() {
+        let doc = Doc::group(Doc::concat(vec![
+            Doc::text("f("),
+            Doc::nest(
+                4,
+                Doc::concat(vec![
+                    Doc::SoftLine,
+                    Doc::join(vec![Doc::text("alpha"), Doc::text("beta")], ","),
+                ]),
+            ),
+            Doc::SoftLine,
+            Doc::text(")"),
+        ]));
+        assert_eq!(render(&doc, 8, 0, 0), "f(\n    alpha,\n    beta\n)");
+    }
+
+    #[test]
+    fn test_render_inner_group_stays_flat() {
+        let inner = Doc::group(Doc::concat(vec![Doc::text("g(x)")]));
+        let doc = Doc::group(Doc::concat(vec![
+            Doc::text("f("),
+            Doc::nest(
+                4,
+                Doc::concat(vec![
+                    Doc::SoftLine,
+                    Doc::join(vec![inner, Doc::text("long_argument")], ","),
+                ]),
+            ),
+            Doc::SoftLine,
+            Doc::text(")"),
+        ]));
+        assert_eq!(
+            render(&doc, 12, 4, 4),
+            "f(\n        g(x),\n        long_argument\n    )"
+        );
+    }
+
+    #[test]
+    fn test_format_crusty_wraps_long_call_arguments() {
+        let code = "void f() { compute(first_argument_value, second_argument_value, third_argument_value); }";
+        let printer = PrettyPrinter::new(TargetLanguage::Crusty).with_width(40);
+        let formatted = printer.format(code).unwrap();
+        assert!(formatted.contains(
+            "    compute(\n        first_argument_value,\n        second_argument_value,\n        third_argument_value\n    );"
+        ));
+        // Wrapped output is still valid and stable
+        assert_eq!(printer.format(&formatted).unwrap(), formatted);
+    }
+
+    #[test]
+    fn test_format_crusty_wraps_struct_initializer() {
+        let code = "void f() { let p = (Point){ .x = first_value, .y = second_value, .z = third_value }; }";
+        let printer = PrettyPrinter::new(TargetLanguage::Crusty).with_width(40);
+        let formatted = printer.format(code).unwrap();
+        assert!(formatted.contains(
+            "    let p = (Point){\n        .x = first_value,\n        .y = second_value,\n        .z = third_value\n    };"
+        ));
+        assert_eq!(printer.format(&formatted).unwrap(), formatted);
+    }
+
+    #[test]
+    fn test_format_crusty_wraps_binary_chain() {
+        let code = "int f() { return alpha_value + beta_value + gamma_value + delta_value; }";
+        let printer = PrettyPrinter::new(TargetLanguage::Crusty).with_width(40);
+        let formatted = printer.format(code).unwrap();
+        for line in formatted.lines() {
+            assert!(line.len() <= 40, "line too long: {:?}", line);
+        }
+        assert!(formatted.contains("alpha_value +\n"));
+        assert_eq!(printer.format(&formatted).unwrap(), formatted);
+    }
+
+    #[test]
+    fn test_format_crusty_short_lines_unchanged_by_width() {
+        let code = "int f() { return add(1, 2); }";
+        let wide = PrettyPrinter::new(TargetLanguage::Crusty)
+            .format(code)
+            .unwrap();
+        let narrow = PrettyPrinter::new(TargetLanguage::Crusty)
+            .with_width(30)
+            .format(code)
+            .unwrap();
+        assert_eq!(wide, narrow);
+        assert!(narrow.contains("return add(1, 2);"));
+    }
+
+    #[test]
+    fn test_format_crusty_parenthesized_identifier_expression() {
+        // `(a` looks like the start of a cast; the parser must backtrack cleanly
+        let formatted = assert_crusty_round_trip("int f() { return (a + b) * c; }");
+        assert!(formatted.contains("return ((a + b) * c);"));
+    }
 }
@@ -34,6 +34,7 @@ fn arb_simple_function() -> impl Strategy<Value = Function> {
                 | "type"
                 | "impl"
                 | "trait"
+                | "interface"
                 | "pub"
                 | "use"
                 | "mod"
@@ -112,6 +113,7 @@ fn arb_simple_function() -> impl Strategy<Value = Function> {
                 Visibility::Private
             },
             name: Ident::new(&name),
+            generics: vec![],
             params: vec![],
             return_type: Some(Type::Primitive(PrimitiveType::Int)),
             body: Block::new(statements),
@@ -145,6 +147,7 @@ fn arb_simple_statement() -> impl Strategy<Value = Statement> {
                 | "type"
                 | "impl"
                 | "trait"
+                | "interface"
                 | "pub"
                 | "use"
                 | "mod"
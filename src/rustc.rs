@@ -3,9 +3,15 @@
 
 //! Rustc invocation module for compiling generated Rust code.
 
+use crate::codegen::PanicPolicy;
+use crate::error::{Severity, Span};
+use crate::source_map::SourceMap;
 use std::path::Path;
 use std::process::{Command, Output};
 
+/// Makes rustc report diagnostics as JSON, one object per line on stderr
+const JSON_ERRORS: &str = "--error-format=json";
+
 /// Result of rustc invocation
 #[derive(Debug)]
 pub struct RustcResult {
@@ -33,6 +39,31 @@ impl RustcResult {
 
     /// Get formatted error message if compilation failed
     pub fn error_message(&self) -> Option<String> {
+        self.failure_message(self.rendered_stderr())
+    }
+
+    /// `error_message` with each diagnostic on a generated line pointing at
+    /// the Crusty code it came from, through `source_map`
+    ///
+    /// `source_text` returns the text of a Crusty file named in the map.
+    pub fn translated_error_message(
+        &self,
+        source_map: &SourceMap,
+        source_text: impl Fn(&str) -> Option<String>,
+    ) -> Option<String> {
+        let diagnostics = self.diagnostics();
+        if diagnostics.is_empty() {
+            return self.error_message();
+        }
+        self.failure_message(
+            diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.render(source_map, &source_text))
+                .collect(),
+        )
+    }
+
+    fn failure_message(&self, diagnostics: String) -> Option<String> {
         if self.success {
             None
         } else {
@@ -41,11 +72,27 @@ impl RustcResult {
                 self.exit_code
                     .map(|c| c.to_string())
                     .unwrap_or_else(|| "unknown".to_string()),
-                self.stderr
+                diagnostics
             ))
         }
     }
 
+    /// Diagnostics reported as JSON on stderr; empty when rustc wrote plain
+    /// text, as it does for errors that stop it before compiling
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.stderr.lines().filter_map(Diagnostic::parse).collect()
+    }
+
+    /// stderr as rustc would have printed it without `--error-format=json`
+    fn rendered_stderr(&self) -> String {
+        let diagnostics = self.diagnostics();
+        if diagnostics.is_empty() {
+            self.stderr.clone()
+        } else {
+            diagnostics.iter().map(|d| d.rendered.as_str()).collect()
+        }
+    }
+
     /// Parse rustc error messages and extract structured error information
     #[allow(dead_code)]
     pub fn parse_errors(&self) -> Vec<RustcError> {
@@ -53,6 +100,19 @@ impl RustcResult {
             return Vec::new();
         }
 
+        let diagnostics = self.diagnostics();
+        if !diagnostics.is_empty() {
+            return diagnostics
+                .into_iter()
+                .filter(|d| d.level == "error")
+                .map(|d| RustcError {
+                    error_code: d.code,
+                    message: d.message,
+                    location: d.location,
+                })
+                .collect();
+        }
+
         let mut errors = Vec::new();
         let lines: Vec<&str> = self.stderr.lines().collect();
 
@@ -78,6 +138,108 @@ impl RustcResult {
     }
 }
 
+/// A diagnostic rustc reported with `--error-format=json`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// `error`, `warning`, ...
+    pub level: String,
+    pub code: Option<String>,
+    pub message: String,
+    /// Start of the primary span
+    pub location: Option<ErrorLocation>,
+    /// The notes and help attached to the diagnostic, as `(level, message)`
+    pub children: Vec<(String, String)>,
+    /// rustc's own rendering of the diagnostic
+    pub rendered: String,
+}
+
+impl Diagnostic {
+    /// Parse one line of `--error-format=json` output
+    pub fn parse(line: &str) -> Option<Self> {
+        let json = serde_json::from_str::<serde_json::Value>(line).ok()?;
+        let text = |value: &serde_json::Value| value.as_str().map(str::to_string);
+        let location = json["spans"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|span| span["is_primary"] == true)
+            .map(|span| ErrorLocation {
+                file: text(&span["file_name"]).unwrap_or_default(),
+                line: span["line_start"].as_u64().unwrap_or(0) as usize,
+                column: span["column_start"].as_u64().unwrap_or(0) as usize,
+            });
+        let children = json["children"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|child| Some((text(&child["level"])?, text(&child["message"])?)))
+            .collect();
+        Some(Self {
+            level: text(&json["level"])?,
+            code: text(&json["code"]["code"]),
+            message: text(&json["message"])?,
+            location,
+            children,
+            rendered: text(&json["rendered"]).unwrap_or_default(),
+        })
+    }
+
+    /// The diagnostic pointing at the Crusty code its generated line came
+    /// from, when `source_map` maps it, in the style of parse errors; rustc's
+    /// rendering otherwise
+    ///
+    /// `source_text` returns the text of a Crusty file named in the map.
+    pub fn render(
+        &self,
+        source_map: &SourceMap,
+        source_text: impl Fn(&str) -> Option<String>,
+    ) -> String {
+        let Some(location) = &self.location else {
+            return self.rendered.clone();
+        };
+        let Some((file, position)) = (location.file == source_map.file)
+            .then(|| source_map.lookup(location.line))
+            .flatten()
+        else {
+            return self.rendered.clone();
+        };
+
+        let severity = if self.level.starts_with("error") {
+            Severity::Error
+        } else if self.level == "warning" {
+            Severity::Warning
+        } else {
+            Severity::Note
+        };
+        let mut diagnostic = crate::error::Diagnostic::new(severity, &self.message)
+            .in_file(file)
+            .with_span(Span::new(position, position));
+        // As rustc does, only error codes are shown, not lint names
+        if let Some(code) = self.code.as_ref().filter(|code| is_error_code(code)) {
+            diagnostic = diagnostic.with_code(code);
+        }
+        for (level, message) in &self.children {
+            diagnostic = diagnostic.with_child(level, message);
+        }
+        diagnostic = diagnostic.with_child(
+            "note",
+            format!(
+                "in the generated Rust at {}:{}:{}",
+                location.file, location.line, location.column
+            ),
+        );
+        let mut rendered = diagnostic.render(source_text(file).as_deref(), false);
+        rendered.push('\n');
+        rendered
+    }
+}
+
+/// Whether `code` is a rustc error code such as `E0308`
+fn is_error_code(code: &str) -> bool {
+    code.strip_prefix('E')
+        .is_some_and(|digits| !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()))
+}
+
 /// Structured rustc error information
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[allow(dead_code)]
@@ -166,7 +328,10 @@ pub fn invoke_rustc(
     }
 
     let mut cmd = Command::new("rustc");
-    cmd.arg(rust_file).arg("-o").arg(output_binary);
+    cmd.arg(rust_file)
+        .arg("-o")
+        .arg(output_binary)
+        .arg(JSON_ERRORS);
 
     // Execute rustc and capture output
     let output = cmd
@@ -180,13 +345,154 @@ pub fn invoke_rustc(
             println!("rustc stdout:\n{}", result.stdout);
         }
         if !result.stderr.is_empty() {
-            println!("rustc stderr:\n{}", result.stderr);
+            println!("rustc stderr:\n{}", result.rendered_stderr());
         }
     }
 
     Ok(result)
 }
 
+/// Kind of library built by `--lib`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum LibraryKind {
+    /// A Rust library (`.rlib`) for other Rust crates to link against
+    #[default]
+    Rlib,
+    /// A dynamic library with a C ABI (`.so`, `.dylib` or `.dll`)
+    Cdylib,
+}
+
+impl LibraryKind {
+    /// Name rustc gives the library file of crate `name`
+    pub fn file_name(self, name: &str) -> String {
+        match self {
+            LibraryKind::Rlib => format!("lib{}.rlib", name),
+            LibraryKind::Cdylib => format!(
+                "{}{}{}",
+                std::env::consts::DLL_PREFIX,
+                name,
+                std::env::consts::DLL_SUFFIX
+            ),
+        }
+    }
+
+    fn crate_type(self) -> &'static str {
+        match self {
+            LibraryKind::Rlib => "rlib",
+            LibraryKind::Cdylib => "cdylib",
+        }
+    }
+}
+
+/// Crate name for a source file stem: rustc accepts only letters, digits
+/// and underscores
+pub fn crate_name(stem: &str) -> String {
+    let name: String = stem
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", name)
+    } else {
+        name
+    }
+}
+
+/// Invoke rustc to build a library instead of an executable
+///
+/// # Arguments
+/// * `rust_file` - Path to the Rust source file to compile
+/// * `output_library` - Path where the compiled library should be written
+/// * `kind` - Kind of library to build
+/// * `name` - Crate name of the library
+/// * `target` - Target triple to build for (None = the host)
+/// * `debug` - Whether to keep debug info in the library
+/// * `verbose` - Whether to print verbose output
+///
+/// # Returns
+/// * `Ok(RustcResult)` - Compilation result with stdout/stderr
+/// * `Err(String)` - Error message if rustc could not be executed
+pub fn invoke_rustc_library(
+    rust_file: &Path,
+    output_library: &Path,
+    kind: LibraryKind,
+    name: &str,
+    target: Option<&str>,
+    debug: bool,
+    verbose: bool,
+) -> Result<RustcResult, String> {
+    let mut flags = vec![
+        "--crate-type".to_string(),
+        kind.crate_type().to_string(),
+        "--crate-name".to_string(),
+        name.to_string(),
+    ];
+    flags.extend(target_flags(target));
+    flags.extend(debug_flags(debug));
+    invoke_rustc_with_flags(rust_file, output_library, &flags, verbose)
+}
+
+/// Invoke rustc to build a freestanding (`#![no_std]`, `#![no_main]`)
+/// program into an object file, which the target's linker combines with
+/// its startup code
+///
+/// Panics abort: there is no unwinding runtime without the standard library.
+/// `target` is the target triple to build for (None = the host), and
+/// `debug` keeps debug info in the object file.
+///
+/// # Returns
+/// * `Ok(RustcResult)` - Compilation result with stdout/stderr
+/// * `Err(String)` - Error message if rustc could not be executed
+pub fn invoke_rustc_freestanding(
+    rust_file: &Path,
+    output_object: &Path,
+    target: Option<&str>,
+    debug: bool,
+    verbose: bool,
+) -> Result<RustcResult, String> {
+    let mut flags = vec![
+        "-C".to_string(),
+        "panic=abort".to_string(),
+        "--emit=obj".to_string(),
+    ];
+    flags.extend(target_flags(target));
+    flags.extend(debug_flags(debug));
+    invoke_rustc_with_flags(rust_file, output_object, &flags, verbose)
+}
+
+/// rustc flags selecting the target triple to build for (none for the host)
+pub fn target_flags(target: Option<&str>) -> Vec<String> {
+    target
+        .map(|target| vec!["--target".to_string(), target.to_string()])
+        .unwrap_or_default()
+}
+
+/// rustc flags keeping full debug info, so gdb and lldb can step through
+/// the generated Rust (none without `debug`)
+pub fn debug_flags(debug: bool) -> Vec<String> {
+    if debug {
+        vec!["-C".to_string(), "debuginfo=2".to_string()]
+    } else {
+        Vec::new()
+    }
+}
+
+/// rustc flags making panics abort the process instead of unwinding
+/// under the `abort` policy (none otherwise)
+pub fn panic_flags(policy: PanicPolicy) -> Vec<String> {
+    if policy == PanicPolicy::Abort {
+        vec!["-C".to_string(), "panic=abort".to_string()]
+    } else {
+        Vec::new()
+    }
+}
+
 /// Invoke rustc with additional compiler flags
 ///
 /// # Arguments
@@ -198,7 +504,6 @@ pub fn invoke_rustc(
 /// # Returns
 /// * `Ok(RustcResult)` - Compilation result with stdout/stderr
 /// * `Err(String)` - Error message if rustc could not be executed
-#[allow(dead_code)]
 pub fn invoke_rustc_with_flags(
     rust_file: &Path,
     output_binary: &Path,
@@ -215,7 +520,11 @@ pub fn invoke_rustc_with_flags(
     }
 
     let mut cmd = Command::new("rustc");
-    cmd.arg(rust_file).arg("-o").arg(output_binary).args(flags);
+    cmd.arg(rust_file)
+        .arg("-o")
+        .arg(output_binary)
+        .arg(JSON_ERRORS)
+        .args(flags);
 
     // Execute rustc and capture output
     let output = cmd
@@ -229,13 +538,49 @@ pub fn invoke_rustc_with_flags(
             println!("rustc stdout:\n{}", result.stdout);
         }
         if !result.stderr.is_empty() {
-            println!("rustc stderr:\n{}", result.stderr);
+            println!("rustc stderr:\n{}", result.rendered_stderr());
         }
     }
 
     Ok(result)
 }
 
+/// Run clippy over a generated Rust file without producing a binary
+///
+/// The file is checked as a library so that generated code without `main`
+/// is accepted. Diagnostics are reported as JSON (one object per line) on
+/// stderr, ready for `clippy::parse_suggestions`.
+///
+/// # Returns
+/// * `Ok(RustcResult)` - Clippy result with the JSON diagnostics in stderr
+/// * `Err(String)` - Error message if clippy-driver could not be executed
+pub fn invoke_clippy(rust_file: &Path, verbose: bool) -> Result<RustcResult, String> {
+    if verbose {
+        println!("Invoking clippy-driver: {:?}", rust_file);
+    }
+
+    let out_dir = std::env::temp_dir().join("crustyc-clippy");
+    let mut cmd = Command::new("clippy-driver");
+    cmd.arg(rust_file)
+        .args([
+            "--edition",
+            "2021",
+            "--crate-type",
+            "lib",
+            "--emit=metadata",
+        ])
+        .arg("--error-format=json")
+        .arg("--out-dir")
+        .arg(&out_dir)
+        .args(["-W", "clippy::all"]);
+
+    let output = cmd
+        .output()
+        .map_err(|e| format!("Failed to execute clippy-driver: {}", e))?;
+
+    Ok(RustcResult::from_output(output))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -454,6 +799,116 @@ fn main() {
         }
     }
 
+    #[test]
+    #[cfg(unix)]
+    fn test_translate_json_diagnostics() {
+        use crate::source_map::Mapping;
+
+        let stderr = concat!(
+            r#"{"$message_type":"diagnostic","message":"mismatched types","code":{"code":"E0308","explanation":"..."},"level":"error","spans":[{"file_name":"main.rs","line_start":4,"column_start":17,"is_primary":true}],"children":[{"message":"expected `u8`, found `i32`","code":null,"level":"note","spans":[],"children":[],"rendered":null}],"rendered":"error[E0308]: mismatched types\n --> main.rs:4:17\n\n"}"#,
+            "\n",
+            r#"{"$message_type":"diagnostic","message":"aborting due to 1 previous error","code":null,"level":"error","spans":[],"children":[],"rendered":"error: aborting due to 1 previous error\n\n"}"#,
+            "\n",
+        );
+        let result = RustcResult::from_output(Output {
+            status: create_exit_status(256),
+            stdout: Vec::new(),
+            stderr: stderr.as_bytes().to_vec(),
+        });
+
+        let diagnostics = result.diagnostics();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].code.as_deref(), Some("E0308"));
+        assert_eq!(
+            diagnostics[0].location,
+            Some(ErrorLocation {
+                file: "main.rs".to_string(),
+                line: 4,
+                column: 17,
+            })
+        );
+        assert_eq!(result.parse_errors().len(), 2);
+        assert!(result
+            .error_message()
+            .unwrap()
+            .ends_with(":\nerror[E0308]: mismatched types\n --> main.rs:4:17\n\nerror: aborting due to 1 previous error\n\n"));
+
+        let mut source_map = SourceMap::new("main.rs");
+        source_map.sources.push("main.crst".to_string());
+        source_map.mappings.push(Mapping {
+            generated_line: 3,
+            source: 0,
+            line: 2,
+            column: 5,
+        });
+        let source = "void main() {\n    u8 small = big;\n}\n".to_string();
+        let translated = result
+            .translated_error_message(&source_map, |file| {
+                (file == "main.crst").then(|| source.clone())
+            })
+            .unwrap();
+        assert!(
+            translated.ends_with(
+                ":\nerror[E0308]: mismatched types\n --> main.crst:2:5\n  |\n2 |     u8 small = big;\n  |     ^\n  = note: expected `u8`, found `i32`\n  = note: in the generated Rust at main.rs:4:17\n\nerror: aborting due to 1 previous error\n\n"
+            ),
+            "{}",
+            translated
+        );
+        assert!(is_error_code("E0308"));
+        assert!(!is_error_code("unused_variables"));
+    }
+
+    #[test]
+    fn test_crate_names_and_library_file_names() {
+        assert_eq!(crate_name("math-utils"), "math_utils");
+        assert_eq!(crate_name("2d"), "_2d");
+        assert_eq!(LibraryKind::Rlib.file_name("shapes"), "libshapes.rlib");
+        assert!(LibraryKind::Cdylib.file_name("shapes").contains("shapes"));
+    }
+
+    #[test]
+    fn test_invoke_rustc_library() {
+        let test_source = "pub fn area(w: i32, h: i32) -> i32 {\n    w * h\n}\n";
+        let input_path = PathBuf::from("test_rustc_library_12345.rs");
+        let output_path = PathBuf::from(LibraryKind::Rlib.file_name("shapes_12345"));
+
+        fs::write(&input_path, test_source).unwrap();
+
+        let result = invoke_rustc_library(
+            &input_path,
+            &output_path,
+            LibraryKind::Rlib,
+            "shapes_12345",
+            None,
+            false,
+            false,
+        );
+        let built = output_path.exists();
+
+        // Clean up
+        let _ = fs::remove_file(&input_path);
+        let _ = fs::remove_file(&output_path);
+
+        // This test will only pass if rustc is installed
+        if let Ok(rustc_result) = result {
+            assert!(rustc_result.is_success(), "{}", rustc_result.stderr);
+            assert!(built);
+        }
+    }
+
+    #[test]
+    fn test_debug_flags() {
+        assert!(debug_flags(false).is_empty());
+        assert_eq!(debug_flags(true), vec!["-C", "debuginfo=2"]);
+    }
+
+    #[test]
+    fn test_panic_flags() {
+        assert!(panic_flags(PanicPolicy::Panic).is_empty());
+        assert!(panic_flags(PanicPolicy::Result).is_empty());
+        assert_eq!(panic_flags(PanicPolicy::Abort), vec!["-C", "panic=abort"]);
+    }
+
     #[test]
     fn test_invoke_rustc_with_flags() {
         // Create a simple valid Rust source file
@@ -6,9 +6,32 @@
 #[cfg(test)]
 mod tests {
     use crate::cli::{run_compiler, CompilerOptions, EmitMode};
+    use clap::Parser;
     use std::fs;
     use std::path::PathBuf;
 
+    /// Compile `source` with `flags` under the file stem `name` and run it,
+    /// returning what it prints; `None` when rustc is not installed
+    fn build_and_run(name: &str, source: &str, flags: &[&str]) -> Option<String> {
+        std::process::Command::new("rustc")
+            .arg("--version")
+            .output()
+            .ok()?;
+        let input_path = format!("{}.crst", name);
+        fs::write(&input_path, source).unwrap();
+        let mut args = vec!["crustyc", input_path.as_str(), "-o", name];
+        args.extend_from_slice(flags);
+        let result = run_compiler(&CompilerOptions::try_parse_from(args).unwrap());
+        let output = std::process::Command::new(format!("./{}", name)).output();
+        let _ = fs::remove_file(&input_path);
+        let _ = fs::remove_file(name);
+        let _ = fs::remove_file(format!("{}.rs", name));
+        if let Err(err) = result {
+            panic!("{}", err);
+        }
+        Some(String::from_utf8_lossy(&output.unwrap().stdout).into_owned())
+    }
+
     #[test]
     fn test_compile_valid_crusty_to_binary() {
         // Create a simple valid Crusty source file
@@ -24,12 +47,53 @@ int main() {
 
         let options = CompilerOptions {
             input_file: input_path.clone(),
+            inputs: Vec::new(),
             output_file: Some(output_path.clone()),
             out_dir: None,
             emit: EmitMode::Binary,
             absorb: None,
             verbose: false,
             no_compile: false,
+            check_only: false,
+            split_modules: false,
+            dead_code: None,
+            group_items: false,
+            no_header: false,
+            allow_lints: vec![],
+            no_default_allows: false,
+            clippy: false,
+            compile_db: None,
+            heap_threshold: crate::codegen::DEFAULT_HEAP_THRESHOLD,
+            error_limit: crate::semantic::DEFAULT_ERROR_LIMIT,
+            tail_calls: false,
+            inline_macros: false,
+            switch_ladders: crate::switch_ladders::SwitchLadders::Off,
+            unqualified_variants: false,
+            int_conditions: crate::semantic::IntConditions::Strict,
+            flow_checks: crate::dataflow::FlowChecks::Warn,
+            allow: vec![],
+            warn: vec![],
+            deny: vec![],
+            max_ast_nodes: crate::limits::DEFAULT_MAX_AST_NODES,
+            max_expansion_tokens: crate::limits::DEFAULT_MAX_EXPANSION_TOKENS,
+            max_const_eval_steps: crate::limits::DEFAULT_MAX_CONST_EVAL_STEPS,
+            self_profile: None,
+            report_size: None,
+            source_comments: false,
+            wide_strings: crate::codegen::WideStringMapping::Utf16,
+            defines: vec![],
+            lib: None,
+            entry: None,
+            freestanding: false,
+            bench: false,
+            coverage: false,
+            emit_sourcemap: false,
+            debug: false,
+            panic: crate::codegen::PanicPolicy::Panic,
+            check_determinism: false,
+            emit_item_hashes: false,
+            target: None,
+            color: crate::cli::ColorChoice::Never,
         };
 
         let result = run_compiler(&options);
@@ -68,12 +132,53 @@ main() {
 
         let options = CompilerOptions {
             input_file: input_path.clone(),
+            inputs: Vec::new(),
             output_file: Some(output_path.clone()),
             out_dir: None,
             emit: EmitMode::Binary,
             absorb: None,
             verbose: false,
             no_compile: false,
+            check_only: false,
+            split_modules: false,
+            dead_code: None,
+            group_items: false,
+            no_header: false,
+            allow_lints: vec![],
+            no_default_allows: false,
+            clippy: false,
+            compile_db: None,
+            heap_threshold: crate::codegen::DEFAULT_HEAP_THRESHOLD,
+            error_limit: crate::semantic::DEFAULT_ERROR_LIMIT,
+            tail_calls: false,
+            inline_macros: false,
+            switch_ladders: crate::switch_ladders::SwitchLadders::Off,
+            unqualified_variants: false,
+            int_conditions: crate::semantic::IntConditions::Strict,
+            flow_checks: crate::dataflow::FlowChecks::Warn,
+            allow: vec![],
+            warn: vec![],
+            deny: vec![],
+            max_ast_nodes: crate::limits::DEFAULT_MAX_AST_NODES,
+            max_expansion_tokens: crate::limits::DEFAULT_MAX_EXPANSION_TOKENS,
+            max_const_eval_steps: crate::limits::DEFAULT_MAX_CONST_EVAL_STEPS,
+            self_profile: None,
+            report_size: None,
+            source_comments: false,
+            wide_strings: crate::codegen::WideStringMapping::Utf16,
+            defines: vec![],
+            lib: None,
+            entry: None,
+            freestanding: false,
+            bench: false,
+            coverage: false,
+            emit_sourcemap: false,
+            debug: false,
+            panic: crate::codegen::PanicPolicy::Panic,
+            check_determinism: false,
+            emit_item_hashes: false,
+            target: None,
+            color: crate::cli::ColorChoice::Never,
         };
 
         let result = run_compiler(&options);
@@ -102,12 +207,53 @@ int add(int a, int b) {
 
         let options = CompilerOptions {
             input_file: input_path.clone(),
+            inputs: Vec::new(),
             output_file: Some(output_path.clone()),
             out_dir: None,
             emit: EmitMode::Binary,
             absorb: None,
             verbose: false,
             no_compile: true, // Skip rustc invocation
+            check_only: false,
+            split_modules: false,
+            dead_code: None,
+            group_items: false,
+            no_header: false,
+            allow_lints: vec![],
+            no_default_allows: false,
+            clippy: false,
+            compile_db: None,
+            heap_threshold: crate::codegen::DEFAULT_HEAP_THRESHOLD,
+            error_limit: crate::semantic::DEFAULT_ERROR_LIMIT,
+            tail_calls: false,
+            inline_macros: false,
+            switch_ladders: crate::switch_ladders::SwitchLadders::Off,
+            unqualified_variants: false,
+            int_conditions: crate::semantic::IntConditions::Strict,
+            flow_checks: crate::dataflow::FlowChecks::Warn,
+            allow: vec![],
+            warn: vec![],
+            deny: vec![],
+            max_ast_nodes: crate::limits::DEFAULT_MAX_AST_NODES,
+            max_expansion_tokens: crate::limits::DEFAULT_MAX_EXPANSION_TOKENS,
+            max_const_eval_steps: crate::limits::DEFAULT_MAX_CONST_EVAL_STEPS,
+            self_profile: None,
+            report_size: None,
+            source_comments: false,
+            wide_strings: crate::codegen::WideStringMapping::Utf16,
+            defines: vec![],
+            lib: None,
+            entry: None,
+            freestanding: false,
+            bench: false,
+            coverage: false,
+            emit_sourcemap: false,
+            debug: false,
+            panic: crate::codegen::PanicPolicy::Panic,
+            check_determinism: false,
+            emit_item_hashes: false,
+            target: None,
+            color: crate::cli::ColorChoice::Never,
         };
 
         let result = run_compiler(&options);
@@ -135,12 +281,53 @@ int multiply(int x, int y) {
 
         let options = CompilerOptions {
             input_file: input_path.clone(),
+            inputs: Vec::new(),
             output_file: Some(output_path.clone()),
             out_dir: None,
             emit: EmitMode::Rust, // Only generate Rust, don't compile
             absorb: None,
             verbose: false,
             no_compile: false,
+            check_only: false,
+            split_modules: false,
+            dead_code: None,
+            group_items: false,
+            no_header: false,
+            allow_lints: vec![],
+            no_default_allows: false,
+            clippy: false,
+            compile_db: None,
+            heap_threshold: crate::codegen::DEFAULT_HEAP_THRESHOLD,
+            error_limit: crate::semantic::DEFAULT_ERROR_LIMIT,
+            tail_calls: false,
+            inline_macros: false,
+            switch_ladders: crate::switch_ladders::SwitchLadders::Off,
+            unqualified_variants: false,
+            int_conditions: crate::semantic::IntConditions::Strict,
+            flow_checks: crate::dataflow::FlowChecks::Warn,
+            allow: vec![],
+            warn: vec![],
+            deny: vec![],
+            max_ast_nodes: crate::limits::DEFAULT_MAX_AST_NODES,
+            max_expansion_tokens: crate::limits::DEFAULT_MAX_EXPANSION_TOKENS,
+            max_const_eval_steps: crate::limits::DEFAULT_MAX_CONST_EVAL_STEPS,
+            self_profile: None,
+            report_size: None,
+            source_comments: false,
+            wide_strings: crate::codegen::WideStringMapping::Utf16,
+            defines: vec![],
+            lib: None,
+            entry: None,
+            freestanding: false,
+            bench: false,
+            coverage: false,
+            emit_sourcemap: false,
+            debug: false,
+            panic: crate::codegen::PanicPolicy::Panic,
+            check_determinism: false,
+            emit_item_hashes: false,
+            target: None,
+            color: crate::cli::ColorChoice::Never,
         };
 
         let result = run_compiler(&options);
@@ -168,12 +355,53 @@ int square(int n) {
 
         let options = CompilerOptions {
             input_file: input_path.clone(),
+            inputs: Vec::new(),
             output_file: Some(output_path.clone()),
             out_dir: None,
             emit: EmitMode::Rust,
             absorb: None,
             verbose: true, // Enable verbose output
             no_compile: true,
+            check_only: false,
+            split_modules: false,
+            dead_code: None,
+            group_items: false,
+            no_header: false,
+            allow_lints: vec![],
+            no_default_allows: false,
+            clippy: false,
+            compile_db: None,
+            heap_threshold: crate::codegen::DEFAULT_HEAP_THRESHOLD,
+            error_limit: crate::semantic::DEFAULT_ERROR_LIMIT,
+            tail_calls: false,
+            inline_macros: false,
+            switch_ladders: crate::switch_ladders::SwitchLadders::Off,
+            unqualified_variants: false,
+            int_conditions: crate::semantic::IntConditions::Strict,
+            flow_checks: crate::dataflow::FlowChecks::Warn,
+            allow: vec![],
+            warn: vec![],
+            deny: vec![],
+            max_ast_nodes: crate::limits::DEFAULT_MAX_AST_NODES,
+            max_expansion_tokens: crate::limits::DEFAULT_MAX_EXPANSION_TOKENS,
+            max_const_eval_steps: crate::limits::DEFAULT_MAX_CONST_EVAL_STEPS,
+            self_profile: None,
+            report_size: None,
+            source_comments: false,
+            wide_strings: crate::codegen::WideStringMapping::Utf16,
+            defines: vec![],
+            lib: None,
+            entry: None,
+            freestanding: false,
+            bench: false,
+            coverage: false,
+            emit_sourcemap: false,
+            debug: false,
+            panic: crate::codegen::PanicPolicy::Panic,
+            check_determinism: false,
+            emit_item_hashes: false,
+            target: None,
+            color: crate::cli::ColorChoice::Never,
         };
 
         let result = run_compiler(&options);
@@ -206,14 +434,62 @@ int test_func() {
 
         let options = CompilerOptions {
             input_file: input_path.clone(),
+            inputs: Vec::new(),
             output_file: Some(output_path.clone()),
             out_dir: None,
             emit: EmitMode::Binary,
             absorb: None,
             verbose: false,
             no_compile: false,
+            check_only: false,
+            split_modules: false,
+            dead_code: None,
+            group_items: false,
+            no_header: false,
+            allow_lints: vec![],
+            no_default_allows: false,
+            clippy: false,
+            compile_db: None,
+            heap_threshold: crate::codegen::DEFAULT_HEAP_THRESHOLD,
+            error_limit: crate::semantic::DEFAULT_ERROR_LIMIT,
+            tail_calls: false,
+            inline_macros: false,
+            switch_ladders: crate::switch_ladders::SwitchLadders::Off,
+            unqualified_variants: false,
+            int_conditions: crate::semantic::IntConditions::Strict,
+            flow_checks: crate::dataflow::FlowChecks::Warn,
+            allow: vec![],
+            warn: vec![],
+            deny: vec![],
+            max_ast_nodes: crate::limits::DEFAULT_MAX_AST_NODES,
+            max_expansion_tokens: crate::limits::DEFAULT_MAX_EXPANSION_TOKENS,
+            max_const_eval_steps: crate::limits::DEFAULT_MAX_CONST_EVAL_STEPS,
+            self_profile: None,
+            report_size: None,
+            source_comments: false,
+            wide_strings: crate::codegen::WideStringMapping::Utf16,
+            defines: vec![],
+            lib: None,
+            entry: None,
+            freestanding: false,
+            bench: false,
+            coverage: false,
+            emit_sourcemap: false,
+            debug: false,
+            panic: crate::codegen::PanicPolicy::Panic,
+            check_determinism: false,
+            emit_item_hashes: false,
+            target: None,
+            color: crate::cli::ColorChoice::Never,
         };
 
+        // Without main, an executable is rejected before rustc runs
+        let missing_main = run_compiler(&options).unwrap_err();
+
+        let options = CompilerOptions {
+            lib: Some(crate::rustc::LibraryKind::Rlib),
+            ..options
+        };
         let result = run_compiler(&options);
 
         // Clean up
@@ -221,6 +497,12 @@ int test_func() {
         let _ = fs::remove_file(&output_path);
         let _ = fs::remove_file(format!("{}.rs", output_path.display()));
 
+        assert!(
+            missing_main.to_string().contains("pass --lib"),
+            "{}",
+            missing_main
+        );
+
         // If rustc is available, this should succeed
         // If not, we should get a RustcInvocation error
         if let Err(err) = result {
@@ -231,4 +513,169 @@ int test_func() {
             );
         }
     }
+
+    #[test]
+    fn test_clippy_suggestions_map_back_to_crusty_item() {
+        use crate::clippy;
+        use crate::codegen::{CodeGenerator, TargetLanguage};
+        use crate::parser::Parser;
+        use crate::rustc::invoke_clippy;
+
+        let source = "bool in_range(int x) {\n    return x >= 1 && x <= 9;\n}\n";
+        let ast = Parser::new(source).unwrap().parse_file().unwrap();
+        let mut generator = CodeGenerator::new(TargetLanguage::Rust);
+        let code = generator.generate(&ast);
+
+        let rust_path = PathBuf::from("test_clippy_mapping_12345.rs");
+        fs::write(&rust_path, &code).unwrap();
+        let result = invoke_clippy(&rust_path, false);
+        let _ = fs::remove_file(&rust_path);
+
+        // Only meaningful where clippy-driver is installed
+        let Ok(result) = result else {
+            return;
+        };
+        let suggestions = clippy::parse_suggestions(&result.stderr);
+        let warnings = clippy::map_to_crusty(
+            &suggestions,
+            generator.generated_items(),
+            "range.crst",
+            source,
+        );
+
+        let warning = warnings
+            .iter()
+            .find(|w| w.lint == "clippy::manual_range_contains")
+            .expect("expected manual_range_contains suggestion");
+        assert_eq!(warning.item.as_deref(), Some("in_range"));
+        assert_eq!(warning.location, Some(crate::error::Position::new(1, 6)));
+    }
+
+    #[test]
+    fn test_boxed_locals_compile_and_run() {
+        let source = "struct Big {\n    int[4] data;\n}\nint first(Big b) {\n    return b.data[0];\n}\nBig make(int v) {\n    var Big b = (Big){ .data = [v, v, v, v] };\n    b.data[1] = v + 1;\n    return b;\n}\nvoid main() {\n    let Big b = make(3);\n    let Big d;\n    d = make(9);\n    var Big c = make(1);\n    c = make(5);\n    __println__(\"{} {} {}\", d.data[0], c.data[1], first(b));\n}\n";
+        let Some(output) = build_and_run(
+            "test_boxed_locals_12345",
+            source,
+            &["--heap-threshold", "8"],
+        ) else {
+            return;
+        };
+        assert_eq!(output, "9 6 3\n");
+    }
+
+    #[test]
+    fn test_null_guarded_locals_stay_assignable() {
+        let source = "int bump(Option<int> q) {\n    var Option<int> p = q;\n    if (!p) {\n        return 0;\n    }\n    p = p + 1;\n    return p;\n}\nint first(Option<int> q) {\n    var Option<int> r = q;\n    if (r == NULL) {\n        return -1;\n    }\n    r += 10;\n    return r;\n}\nvoid main() {\n    __println__(\"{} {} {}\", bump(Some(4)), bump(None), first(Some(1)));\n}\n";
+        let Some(output) = build_and_run("test_null_guards_12345", source, &[]) else {
+            return;
+        };
+        assert_eq!(output, "5 0 11\n");
+    }
+
+    #[test]
+    fn test_rust_keywords_in_macro_arguments_compile() {
+        let source = "struct Token {\n    int type;\n}\nint yield(int n) {\n    return n * 2;\n}\nvoid main() {\n    let Token s = (Token){ .type = 7 };\n    __println__(\"{} {}\", s.type, yield(3));\n}\n";
+        let Some(output) = build_and_run("test_macro_keywords_12345", source, &[]) else {
+            return;
+        };
+        assert_eq!(output, "7 6\n");
+    }
+
+    #[test]
+    fn test_borrows_for_var_reference_parameters_are_mutable() {
+        let source = "struct Point {\n    int x;\n}\nvoid bump(var &int n) {\n    *n = *n + 1;\n}\nvoid shift(var &Point p) {\n    p.x = p.x + 10;\n}\nvoid main() {\n    var int k = 1;\n    bump(&k);\n    var Point p = (Point){ .x = 2 };\n    shift(&p);\n    __println__(\"{} {}\", k, p.x);\n}\n";
+        let Some(output) = build_and_run("test_var_borrows_12345", source, &[]) else {
+            return;
+        };
+        assert_eq!(output, "2 12\n");
+    }
+
+    #[test]
+    fn test_modules_imported_whole_compile_and_run() {
+        let util = "test_whole_util_12345.crst";
+        fs::write(util, "int twice(int n) {\n    return n * 2;\n}\n").unwrap();
+        let source = "#import test_whole_util_12345\nvoid main() {\n    let int n = test_whole_util_12345.twice(4);\n    __println__(\"{} {}\", n, test_whole_util_12345.twice(1));\n}\n";
+        let output = build_and_run("test_whole_main_12345", source, &[]);
+        let _ = fs::remove_file(util);
+        let Some(output) = output else {
+            return;
+        };
+        assert_eq!(output, "8 2\n");
+    }
+
+    #[test]
+    fn test_long_operator_chains_compile_and_run() {
+        // Analysis and generation recurse once per operator; they used to
+        // overflow the stack of the thread calling the compiler
+        let source = format!(
+            "void main() {{\n    let int n = {};\n    __println__(\"{{}}\", n);\n}}\n",
+            vec!["1"; 1000].join(" + ")
+        );
+        let Some(output) = build_and_run("test_long_chain_12345", &source, &[]) else {
+            return;
+        };
+        assert_eq!(output, "1000\n");
+    }
+
+    #[test]
+    fn test_for_in_borrows_struct_elements_and_runs() {
+        // The first loop modifies the elements through the loop variable,
+        // the second only reads them
+        let source = r#"
+struct Point { int x; int y; }
+void main() {
+    var Point pts[2] = [(Point){ .x = 1, .y = 2 }, (Point){ .x = 3, .y = 4 }];
+    var int total = 0;
+    for (p in pts) {
+        p.x = p.x * 10;
+        p.y++;
+    }
+    for (p in pts) {
+        total = total + p.x + p.y;
+    }
+    __println__("{}", total);
+}
+"#;
+        let Some(output) = build_and_run("test_for_in_borrow_12345", source, &[]) else {
+            return;
+        };
+        assert_eq!(output, "48\n");
+    }
+
+    #[test]
+    fn test_panic_result_returns_errors_from_fallible_functions() {
+        // An index out of bounds and a cast that changes its value become
+        // errors the `?` in `total` passes on
+        let source = r#"
+int? pick(int[3] values, usize i) {
+    return Ok(values[i]);
+}
+u8? shrink(int n) {
+    return Ok((u8)n);
+}
+int? total(int[3] values, usize i) {
+    let int first = pick(values, i)?;
+    let u8 small = shrink(first * 100)?;
+    return Ok(first + (int)small);
+}
+void main() {
+    let int[3] values = [1, 2, 3];
+    __println__("{}", pick(values, 1).unwrap());
+    __println__("{}", pick(values, 5).unwrap_err());
+    __println__("{}", shrink(300).unwrap_err());
+    __println__("{}", total(values, 1).unwrap());
+    __println__("{}", total(values, 2).unwrap_err());
+    __println__("{}", total(values, 3).unwrap_err());
+}
+"#;
+        let Some(output) = build_and_run("test_panic_result_12345", source, &["--panic=result"])
+        else {
+            return;
+        };
+        assert_eq!(
+            output,
+            "2\nindex out of bounds\ncast out of range\n202\ncast out of range\nindex out of bounds\n"
+        );
+    }
 }
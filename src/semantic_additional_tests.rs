@@ -22,6 +22,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::Let {
@@ -46,6 +47,7 @@ mod tests {
         let point_struct = Struct {
             visibility: Visibility::Public,
             name: Ident::new("Point"),
+            generics: vec![],
             fields: vec![
                 Field {
                     visibility: Visibility::Public,
@@ -70,6 +72,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::Let {
@@ -100,6 +103,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::Let {
@@ -128,6 +132,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::Let {
@@ -156,6 +161,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::Let {
@@ -184,11 +190,13 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::Expr(Expression::MacroCall {
                 name: Ident::new("println"),
                 args: vec![],
+                delimiter: MacroDelimiter::Parens,
             })]),
             doc_comments: vec![],
             attributes: vec![],
@@ -206,6 +214,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::Let {
@@ -230,6 +239,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::Let {
@@ -258,6 +268,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::Let {
@@ -286,6 +297,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::Let {
@@ -314,6 +326,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::Let {
@@ -342,6 +355,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::Let {
@@ -370,6 +384,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![
@@ -440,6 +455,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![
@@ -480,6 +496,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![
@@ -509,6 +526,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![
@@ -538,6 +556,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![
@@ -567,6 +586,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![
@@ -617,6 +637,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![
@@ -180,7 +180,38 @@ mod tests {
         };
 
         let result_type = analyzer.analyze_expression_test(&generic_call);
-        assert_eq!(result_type, Type::Ident(Ident::new("Vec")));
+        assert_eq!(
+            result_type,
+            Type::Generic {
+                base: Box::new(Type::Ident(Ident::new("Vec"))),
+                args: vec![Type::Primitive(PrimitiveType::I32)],
+            }
+        );
+        assert!(analyzer.errors().is_empty());
+
+        // Nested type arguments are checked too
+        let generic_call = Expression::ExplicitGenericCall {
+            ty: Type::Ident(Ident::new("HashMap")),
+            generics: vec![Type::Generic {
+                base: Box::new(Type::Ident(Ident::new("Option"))),
+                args: vec![],
+            }],
+            method: Ident::new("new"),
+            args: vec![],
+        };
+        analyzer.analyze_expression_test(&generic_call);
+        let messages: Vec<&str> = analyzer
+            .errors()
+            .iter()
+            .map(|e| e.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "'HashMap' takes 2 type arguments, found 1",
+                "'Option' takes 1 type argument, found 0",
+            ]
+        );
     }
 
     #[test]
@@ -203,6 +234,7 @@ mod tests {
         let macro_call = Expression::MacroCall {
             name: Ident::new("__my_macro__"),
             args: vec![], // Empty args for test
+            delimiter: MacroDelimiter::Parens,
         };
 
         let result_type = analyzer.analyze_expression_test(&macro_call);
@@ -334,6 +366,7 @@ mod tests {
                 Item::Struct(Struct {
                     visibility: Visibility::Public,
                     name: Ident::new("MyStruct"),
+                    generics: vec![],
                     fields: vec![Field {
                         visibility: Visibility::Public,
                         name: Ident::new("field1"),
@@ -348,9 +381,11 @@ mod tests {
                 Item::Function(Function {
                     visibility: Visibility::Public,
                     name: Ident::new("test_func"),
+                    generics: vec![],
                     params: vec![],
                     return_type: Some(Type::Primitive(PrimitiveType::Void)),
                     body: Block {
+                        positions: Vec::new(),
                         statements: vec![Statement::Let {
                             name: Ident::new("obj"),
                             ty: Some(Type::Ident(Ident::new("MyStruct"))),
@@ -109,6 +109,7 @@ mod tests {
         let func = Item::Function(Function {
             visibility: Visibility::Public,
             name: Ident::new("test_func"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::For {
@@ -149,6 +150,7 @@ mod tests {
         let func = Item::Function(Function {
             visibility: Visibility::Public,
             name: Ident::new("test_func"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::While {
@@ -174,6 +176,7 @@ mod tests {
         let func = Item::Function(Function {
             visibility: Visibility::Public,
             name: Ident::new("test_func"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::While {
@@ -199,6 +202,7 @@ mod tests {
         let func = Item::Function(Function {
             visibility: Visibility::Public,
             name: Ident::new("test_func"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::Switch {
@@ -227,6 +231,7 @@ mod tests {
         let func = Item::Function(Function {
             visibility: Visibility::Public,
             name: Ident::new("test_func"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![
@@ -269,6 +274,7 @@ mod tests {
         let func = Item::Function(Function {
             visibility: Visibility::Public,
             name: Ident::new("test_func"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![
@@ -316,6 +322,7 @@ mod tests {
         let func = Item::Function(Function {
             visibility: Visibility::Public,
             name: Ident::new("test_func"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![
@@ -348,6 +355,7 @@ mod tests {
         let func = Item::Function(Function {
             visibility: Visibility::Public,
             name: Ident::new("test_func"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![
@@ -395,6 +403,7 @@ mod tests {
         let func = Item::Function(Function {
             visibility: Visibility::Public,
             name: Ident::new("test_func"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![
@@ -425,6 +434,7 @@ mod tests {
         let func = Item::Function(Function {
             visibility: Visibility::Public,
             name: Ident::new("test_func"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::Expr(Expression::Cast {
@@ -449,6 +459,7 @@ mod tests {
         let func = Item::Function(Function {
             visibility: Visibility::Public,
             name: Ident::new("test_func"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::Expr(Expression::Sizeof {
@@ -472,6 +483,7 @@ mod tests {
         let func = Item::Function(Function {
             visibility: Visibility::Public,
             name: Ident::new("test_func"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::Expr(Expression::Ternary {
@@ -497,6 +509,7 @@ mod tests {
         let func = Item::Function(Function {
             visibility: Visibility::Public,
             name: Ident::new("test_func"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::Expr(Expression::Range {
@@ -522,6 +535,7 @@ mod tests {
         let func = Item::Function(Function {
             visibility: Visibility::Public,
             name: Ident::new("test_func"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![
@@ -548,6 +562,7 @@ mod tests {
         let struct_item = Item::Struct(Struct {
             visibility: Visibility::Public,
             name: Ident::new("Point"),
+            generics: vec![],
             fields: vec![Field {
                 visibility: Visibility::Public,
                 name: Ident::new("x"),
@@ -558,6 +573,7 @@ mod tests {
             methods: vec![Function {
                 visibility: Visibility::Public,
                 name: Ident::new("new"),
+                generics: vec![],
                 params: vec![],
                 return_type: Some(Type::Ident(Ident::new("Point"))),
                 body: Block::new(vec![]),
@@ -586,10 +602,12 @@ mod tests {
                 EnumVariant {
                     name: Ident::new("Success"),
                     value: Some(0),
+                    fields: vec![],
                 },
                 EnumVariant {
                     name: Ident::new("Error"),
                     value: Some(1),
+                    fields: vec![],
                 },
             ],
             doc_comments: vec![],
@@ -20,6 +20,7 @@ mod tests {
         Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(body),
@@ -240,14 +241,17 @@ mod tests {
         let add_func = Function {
             visibility: Visibility::Public,
             name: Ident::new("add"),
+            generics: vec![],
             params: vec![
                 Param {
                     name: Ident::new("a"),
                     ty: Type::Primitive(PrimitiveType::Int),
+                    variadic: false,
                 },
                 Param {
                     name: Ident::new("b"),
                     ty: Type::Primitive(PrimitiveType::Int),
+                    variadic: false,
                 },
             ],
             return_type: Some(Type::Primitive(PrimitiveType::Int)),
@@ -282,14 +286,17 @@ mod tests {
         let add_func = Function {
             visibility: Visibility::Public,
             name: Ident::new("add"),
+            generics: vec![],
             params: vec![
                 Param {
                     name: Ident::new("a"),
                     ty: Type::Primitive(PrimitiveType::Int),
+                    variadic: false,
                 },
                 Param {
                     name: Ident::new("b"),
                     ty: Type::Primitive(PrimitiveType::Int),
+                    variadic: false,
                 },
             ],
             return_type: Some(Type::Primitive(PrimitiveType::Int)),
@@ -318,14 +325,17 @@ mod tests {
         let add_func = Function {
             visibility: Visibility::Public,
             name: Ident::new("add"),
+            generics: vec![],
             params: vec![
                 Param {
                     name: Ident::new("a"),
                     ty: Type::Primitive(PrimitiveType::Int),
+                    variadic: false,
                 },
                 Param {
                     name: Ident::new("b"),
                     ty: Type::Primitive(PrimitiveType::Int),
+                    variadic: false,
                 },
             ],
             return_type: Some(Type::Primitive(PrimitiveType::Int)),
@@ -381,6 +391,7 @@ mod tests {
         let point_struct = Struct {
             visibility: Visibility::Public,
             name: Ident::new("Point"),
+            generics: vec![],
             fields: vec![
                 Field {
                     visibility: Visibility::Public,
@@ -439,6 +450,7 @@ mod tests {
         let point_struct = Struct {
             visibility: Visibility::Public,
             name: Ident::new("Point"),
+            generics: vec![],
             fields: vec![Field {
                 visibility: Visibility::Public,
                 name: Ident::new("x"),
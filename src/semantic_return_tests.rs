@@ -23,6 +23,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: Some(Type::Primitive(PrimitiveType::Int)),
             body: Block::new(vec![Statement::Return(Some(Expression::Literal(
@@ -44,6 +45,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::Return(None)]),
@@ -63,6 +65,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: Some(Type::Primitive(PrimitiveType::Int)),
             body: Block::new(vec![Statement::Return(Some(Expression::Literal(
@@ -86,6 +89,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: Some(Type::Primitive(PrimitiveType::Int)),
             body: Block::new(vec![Statement::Return(None)]),
@@ -109,6 +113,7 @@ mod tests {
             params: vec![Ident::new("x")],
             body: vec![],
             delimiter: MacroDelimiter::Parens,
+            variadic: false,
         });
 
         let file = create_file_with_items(vec![macro_def]);
@@ -125,6 +130,7 @@ mod tests {
             params: vec![],
             body: vec![],
             delimiter: MacroDelimiter::None,
+            variadic: false,
         });
 
         let file = create_file_with_items(vec![macro_def]);
@@ -141,6 +147,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("__test__"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::empty(),
@@ -162,6 +169,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::Expr(Expression::Ident(Ident::new(
@@ -185,6 +193,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::Let {
@@ -215,6 +224,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::Let {
@@ -239,6 +249,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::Var {
@@ -262,6 +273,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![
@@ -294,6 +306,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![
@@ -328,6 +341,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::Let {
@@ -352,6 +366,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::Var {
@@ -375,6 +390,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::Let {
@@ -399,6 +415,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::NestedFunction {
@@ -6,7 +6,8 @@
 #[cfg(test)]
 mod tests {
     use crate::ast::*;
-    use crate::error::SemanticErrorKind;
+    use crate::error::{Position, SemanticErrorKind};
+    use crate::hir::Site;
     use crate::semantic::SemanticAnalyzer;
 
     fn create_file_with_items(items: Vec<Item>) -> File {
@@ -16,6 +17,1236 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_null_guard_narrows_option() {
+        use crate::parser::Parser;
+
+        let guarded = "int twice(Option<int> p) {\n    if (!p) {\n        return 0;\n    }\n    return p * 2;\n}\n";
+        let file = Parser::new(guarded).unwrap().parse_file().unwrap();
+        assert!(SemanticAnalyzer::new().analyze(&file).is_ok());
+
+        let unguarded = "int twice(Option<int> p) {\n    return p * 2;\n}\n";
+        let file = Parser::new(unguarded).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        assert!(errors
+            .iter()
+            .any(|e| e.kind == SemanticErrorKind::TypeMismatch));
+    }
+
+    #[test]
+    fn test_errors_point_at_what_they_are_about() {
+        use crate::error::Position;
+        use crate::parser::Parser;
+
+        let source = "int add(int a, int b) {\n    return a + b;\n}\nvoid main() {\n    int x = 1;\n    bool b = x;\n    if (x > 0) {\n        int y = missing + 1;\n    }\n    int z = add(1);\n    z = 2 + 3;\n}\n";
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        let found: Vec<(&SemanticErrorKind, Position, Position)> = errors
+            .iter()
+            .map(|e| (&e.kind, e.span.start, e.span.end))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (
+                    &SemanticErrorKind::TypeMismatch,
+                    Position::new(6, 14),
+                    Position::new(6, 15)
+                ),
+                (
+                    &SemanticErrorKind::UndefinedVariable,
+                    Position::new(8, 17),
+                    Position::new(8, 24)
+                ),
+                (
+                    &SemanticErrorKind::TypeMismatch,
+                    Position::new(10, 13),
+                    Position::new(10, 16)
+                ),
+                (
+                    &SemanticErrorKind::InvalidOperation,
+                    Position::new(11, 5),
+                    Position::new(11, 6)
+                ),
+            ]
+        );
+
+        // Errors about an item as a whole point at its name
+        let source = "void main() {\n}\nint f(int a, int a) {\n    return a;\n}\n";
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        assert_eq!(errors[0].span.start, Position::new(3, 5));
+    }
+
+    #[test]
+    fn test_loop_labels_are_scoped() {
+        use crate::parser::Parser;
+
+        let valid = "void main() {\n    .outer: while (true) {\n        .inner: loop {\n            if (false) {\n                continue outer;\n            }\n            break inner;\n        }\n    }\n    .outer: loop {\n        break outer;\n    }\n}\n";
+        let file = Parser::new(valid).unwrap().parse_file().unwrap();
+        assert!(SemanticAnalyzer::new().analyze(&file).is_ok());
+
+        let invalid = "void main() {\n    .outer: loop {\n        .outer: loop {\n            break outer;\n        }\n    }\n    .done: loop {\n        break outr;\n    }\n}\n";
+        let file = Parser::new(invalid).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "label 'outer' is already used by an enclosing loop",
+                "undefined label 'outr': no enclosing loop is labeled '.outr:'",
+            ]
+        );
+        assert_eq!(errors[0].kind, SemanticErrorKind::DuplicateDefinition);
+        assert_eq!(errors[1].kind, SemanticErrorKind::UndefinedVariable);
+    }
+
+    #[test]
+    fn test_flow_checks_warn_or_deny() {
+        use crate::dataflow::FlowChecks;
+        use crate::parser::Parser;
+
+        let source = "int f(int n) {\n    var int total;\n    if (n > 0) {\n        total = n;\n    }\n    return total;\n}\n";
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+        let message =
+            "'total' may be read before it is assigned; not every path to the read assigns it";
+
+        let mut analyzer = SemanticAnalyzer::new();
+        assert!(analyzer.analyze(&file).is_ok());
+        assert_eq!(analyzer.warnings(), [message]);
+
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.set_flow_checks(FlowChecks::Deny);
+        let errors = analyzer.analyze(&file).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, message);
+        assert!(analyzer.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_volatile_access_requires_unsafe() {
+        use crate::parser::Parser;
+
+        let inside = "int peek(volatile int* reg) {\n    unsafe {\n        *reg = 1;\n        return *reg;\n    }\n}\n";
+        let file = Parser::new(inside).unwrap().parse_file().unwrap();
+        assert!(SemanticAnalyzer::new().analyze(&file).is_ok());
+
+        let outside = "int peek(volatile int* reg) {\n    unsafe {\n        *reg = 1;\n    }\n    return *reg;\n}\n";
+        let file = Parser::new(outside).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].message,
+            "volatile access requires an unsafe block"
+        );
+    }
+
+    #[test]
+    fn test_pointer_to_const_is_read_only() {
+        use crate::parser::Parser;
+
+        let valid = "struct Point { int x; }\nint first(const int* values, const Point* p) {\n    unsafe {\n        return *values + p->x;\n    }\n}\nvoid main() {\n    let value = 41;\n    let p = (Point){ .x = 1 };\n    const int* view = (const int*)&value;\n    let n = first(view, (const Point*)&p);\n}\n";
+        let file = Parser::new(valid).unwrap().parse_file().unwrap();
+        assert!(SemanticAnalyzer::new().analyze(&file).is_ok());
+
+        let invalid = "struct Point { int x; }\nvoid clear(const int* values, const Point* p, int* out) {\n    unsafe {\n        *values = 0;\n        p->x = 0;\n        out = values;\n    }\n}\n";
+        let file = Parser::new(invalid).unwrap().parse_file().unwrap();
+        let messages: Vec<String> = SemanticAnalyzer::new()
+            .analyze(&file)
+            .unwrap_err()
+            .into_iter()
+            .map(|error| error.message)
+            .collect();
+        assert_eq!(messages.len(), 4, "{:?}", messages);
+        assert_eq!(
+            messages[0],
+            "cannot modify '*(values)': 'values' points to const"
+        );
+        assert_eq!(messages[1], "cannot modify '*(p).x': 'p' points to const");
+        assert_eq!(messages[2], "cannot modify 'out': 'out' is a parameter");
+        assert!(messages[3].contains("type mismatch"), "{:?}", messages);
+    }
+
+    #[test]
+    fn test_only_var_bindings_are_modified() {
+        use crate::parser::Parser;
+
+        let valid = "struct Point { int x; }\nvoid grow(var &int n, var &Point p) {\n    *n = *n + 1;\n    p.x = 2;\n}\nvoid main() {\n    var int count = 0;\n    count++;\n    let int sign;\n    sign = 1;\n    var int[3] xs = [1, 2, 3];\n    xs[0] = count;\n    var Point pt = (Point){ .x = 1 };\n    grow(&count, &pt);\n}\n";
+        let file = Parser::new(valid).unwrap().parse_file().unwrap();
+        let result = SemanticAnalyzer::new().analyze(&file);
+        assert!(result.is_ok(), "{:?}", result);
+
+        let invalid = "struct Point { int x; }\nvoid bump(&int n, &Point p, int k) {\n    *n = 1;\n    p.x = 2;\n    k = 3;\n}\nvoid grow(var &int n) {\n    *n = *n + 1;\n}\nvoid main() {\n    let int count = 0;\n    count++;\n    let int[3] xs = [1, 2, 3];\n    xs[0] = 4;\n    grow(&count);\n}\n";
+        let file = Parser::new(invalid).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "cannot modify '*(n)': 'n' is a `&` reference, which only reads",
+                "cannot modify 'p.x': 'p' is a `&` reference, which only reads",
+                "cannot modify 'k': 'k' is a parameter",
+                "cannot modify 'count': 'count' is declared with `let`",
+                "cannot modify 'xs[0]': 'xs' is declared with `let`",
+                "cannot modify 'count': 'count' is declared with `let`",
+            ]
+        );
+        assert_eq!(
+            errors[0].help.as_deref(),
+            Some("declare 'n' as a `var &` parameter to modify what it refers to")
+        );
+        assert_eq!(
+            errors[3].help.as_deref(),
+            Some("declare 'count' with `var` instead of `let` to modify it")
+        );
+    }
+
+    #[test]
+    fn test_borrow_precheck() {
+        use crate::parser::Parser;
+
+        let source = "typedef const int* View;\nstruct List {\n    int len;\n    void append(var &self, &List other) {\n        self.len = self.len + other.len;\n    }\n}\nint length(List list) {\n    return list.len;\n}\nView largest() {\n    int best = 0;\n    return (View)&best;\n}\nvoid main() {\n    var List a = (List){ .len = 1 };\n    let List b = (List){ .len = 2 };\n    a.append(&b);\n    a.append(&a);\n    let List c = b;\n    int n = length(b);\n}\n";
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "returns a pointer to local variable 'best', which is dropped when the function returns",
+                "'&(a)' cannot be passed to 'append', which borrows 'a' mutably as `var &self`",
+                "'b' is used after it was moved on line 20",
+            ]
+        );
+        assert_eq!(
+            errors[2].help.as_deref(),
+            Some("pass a reference, `&b`, instead of moving it, or give List `#[derive(Clone, Copy)]` if it is plain data")
+        );
+
+        // A copyable struct is not moved
+        let copied = source.replace("struct List {", "#[derive(Clone, Copy)]\nstruct List {");
+        let file = Parser::new(&copied).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+    }
+
+    #[test]
+    fn test_struct_equality() {
+        use crate::parser::Parser;
+        use crate::semantic::EqualityImpl;
+
+        let source = "enum Color { Red, Green }\nstruct Pixel {\n    int x;\n    Color color;\n}\nstruct Name {\n    int id;\n    bool eq(&self, &Name other) { return self.id == other.id; }\n}\n#[derive(PartialEq)]\nstruct Tagged { int tag; }\nstruct Unused { int x; }\nvoid main() {\n    let a = (Pixel){ .x = 1, .color = Color.Red };\n    let n = (Name){ .id = 1 };\n    let t = (Tagged){ .tag = 1 };\n    bool same = a == a && n != n && t == t;\n}\n";
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        assert!(analyzer.analyze(&file).is_ok());
+        let impls: Vec<(&str, EqualityImpl)> = analyzer
+            .equality_impls()
+            .iter()
+            .map(|(name, how)| (name.as_str(), *how))
+            .collect();
+        assert_eq!(
+            impls,
+            [
+                ("Color", EqualityImpl::Derive),
+                ("Name", EqualityImpl::Method),
+                ("Pixel", EqualityImpl::Derive),
+            ]
+        );
+
+        let bad_eq = "struct Name {\n    int id;\n    int eq(&self, Name other) { return 0; }\n}\nvoid main() {\n    let n = (Name){ .id = 1 };\n    bool same = n == n;\n}\n";
+        let file = Parser::new(bad_eq).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "cannot compare 'Name' values with ==: method 'eq' must be declared as 'bool eq(&self, &Name other)'"
+        );
+    }
+
+    #[test]
+    fn test_method_chain_inference() {
+        use crate::parser::Parser;
+
+        let counter = "struct Counter {\n    int n;\n    Counter bump(&self) { return (Counter){ .n = self.n + 1 }; }\n    int get(&self) { return self.n; }\n    T pick(T)(&self, T a, T b) { return a; }\n}\n";
+        let analyze = |body: &str| {
+            let source = format!(
+                "{}void main() {{\n    let c = (Counter){{ .n = 0 }};\n{}}}\n",
+                counter, body
+            );
+            let file = Parser::new(&source).unwrap().parse_file().unwrap();
+            SemanticAnalyzer::new().analyze(&file)
+        };
+
+        assert!(analyze(
+            "    int n = c.bump().bump().get();\n    float f = c.bump().pick(1.5, 2.5);\n"
+        )
+        .is_ok());
+
+        let errors = analyze("    bool b = c.bump().pick(1, 2);\n").unwrap_err();
+        assert_eq!(errors[0].kind, SemanticErrorKind::TypeMismatch);
+
+        // A broken link is reported once, with the chain it belongs to
+        let errors = analyze("    int n = c.bump().nope().get();\n").unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].message,
+            "'c.bump().nope()' in 'c.bump().nope().get()': no method 'nope' on 'Counter'"
+        );
+
+        let errors = analyze("    int n = c.get(1);\n").unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "'c.get(...)': 'Counter.get' takes 0 arguments, found 1"
+        );
+    }
+
+    #[test]
+    fn test_struct_equality_names_incomparable_field() {
+        let field = |name: &str, ty: Type| Field {
+            visibility: Visibility::Public,
+            name: Ident::new(name),
+            ty,
+            doc_comments: vec![],
+            attributes: vec![],
+        };
+        let structure = |name: &str, fields: Vec<Field>| {
+            Item::Struct(Struct {
+                visibility: Visibility::Public,
+                name: Ident::new(name),
+                generics: vec![],
+                fields,
+                methods: vec![],
+                doc_comments: vec![],
+                attributes: vec![],
+            })
+        };
+        let callback = Type::Function {
+            params: vec![Type::Primitive(PrimitiveType::Int)],
+            return_type: Box::new(Type::Primitive(PrimitiveType::Int)),
+        };
+        let outer = Type::Ident(Ident::new("Outer"));
+        let compare = Item::Function(Function {
+            visibility: Visibility::Public,
+            name: Ident::new("same"),
+            generics: vec![],
+            params: vec![
+                Param {
+                    name: Ident::new("a"),
+                    ty: outer.clone(),
+                    variadic: false,
+                },
+                Param {
+                    name: Ident::new("b"),
+                    ty: Type::Reference {
+                        ty: Box::new(outer),
+                        mutable: false,
+                    },
+                    variadic: false,
+                },
+            ],
+            return_type: Some(Type::Primitive(PrimitiveType::Bool)),
+            body: Block::new(vec![Statement::Return(Some(Expression::Binary {
+                op: BinaryOp::Eq,
+                left: Box::new(Expression::Ident(Ident::new("a"))),
+                right: Box::new(Expression::Unary {
+                    op: UnaryOp::Deref,
+                    expr: Box::new(Expression::Ident(Ident::new("b"))),
+                }),
+            }))]),
+            doc_comments: vec![],
+            attributes: vec![],
+        });
+        let file = create_file_with_items(vec![
+            structure("Inner", vec![field("on_event", callback)]),
+            structure(
+                "Outer",
+                vec![
+                    field("id", Type::Primitive(PrimitiveType::Int)),
+                    field("inner", Type::Ident(Ident::new("Inner"))),
+                ],
+            ),
+            compare,
+        ]);
+
+        let mut analyzer = SemanticAnalyzer::new();
+        let errors = analyzer.analyze(&file).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, SemanticErrorKind::InvalidOperation);
+        assert_eq!(
+            errors[0].message,
+            "cannot compare 'Outer' values with ==: field 'inner' of type 'Inner': field 'on_event' is not comparable"
+        );
+        assert!(analyzer.equality_impls().is_empty());
+    }
+
+    #[test]
+    fn test_imported_module_items() {
+        use crate::parser::Parser;
+
+        let util = Parser::new(
+            "struct Point { int x; int y; }\nint clamp(int x) { return x; }\nstatic int helper() { return 1; }\n",
+        )
+        .unwrap()
+        .parse_file()
+        .unwrap();
+
+        let main = "#import std.io\n#import util.clamp\n#import util.Point\nint main() {\n    Point p = (Point){ .x = 1, .y = 2 };\n    return clamp(p.x);\n}\n";
+        let file = Parser::new(main).unwrap().parse_file().unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.add_module("util", &util);
+        assert!(analyzer.analyze(&file).is_ok());
+
+        // Without the import, clamp is not in scope
+        let unimported = main.replace("#import util.clamp\n", "");
+        let file = Parser::new(&unimported).unwrap().parse_file().unwrap();
+        assert!(analyzer.analyze(&file).is_err());
+
+        let bad = "#import util.helper\n#import util.missing\n#import util.Point.x\n";
+        let file = Parser::new(bad).unwrap().parse_file().unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.add_module("util", &util);
+        let errors = analyzer.analyze(&file).unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "'helper' is private to module 'util'",
+                "module 'util' has no item named 'missing'",
+                "cannot import 'util.Point.x': module 'util' has no nested modules",
+            ]
+        );
+
+        // A module imported whole has its functions called through it
+        let whole = "#import util\nint main() {\n    int n = util.clamp(3);\n    int a = util.helper();\n    int b = util.missing();\n    return util.clamp(true);\n}\n";
+        let file = Parser::new(whole).unwrap().parse_file().unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.add_module("util", &util);
+        let errors = analyzer.analyze(&file).unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "'helper' is private to module 'util'",
+                "module 'util' has no function named 'missing'",
+                "function call argument 1 type mismatch: expected int, found bool",
+            ]
+        );
+        let calls = &analyzer.analysis().module_calls[&Site::new("main", Position::new(3, 5))];
+        assert_eq!(calls.len(), 1);
+    }
+
+    #[test]
+    fn test_private_struct_members() {
+        use crate::parser::Parser;
+
+        let util = Parser::new(
+            "struct Counter {\n    int count;\n    static int limit;\n    pub void bump(var &self) { self.count = self.count + 1; self.clamp(); }\n    static void clamp(var &self) { if (self.count > self.limit) { self.count = self.limit; } }\n    static Counter fresh() { return (Counter){ .count = 0, .limit = 9 }; }\n}\n",
+        )
+        .unwrap()
+        .parse_file()
+        .unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        assert!(analyzer.analyze(&util).is_ok());
+
+        let main = "#import util.Counter\nint main() {\n    var Counter c = (Counter){ .count = 0, .limit = 3 };\n    c.bump();\n    c.clamp();\n    Counter d = @Counter.fresh();\n    return c.count + d.limit;\n}\n";
+        let file = Parser::new(main).unwrap().parse_file().unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.add_module("util", &util);
+        let errors = analyzer.analyze(&file).unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "field 'limit' of 'Counter' is private to module 'util'",
+                "'c.clamp()': method 'clamp' of 'Counter' is private to module 'util'",
+                "method 'fresh' of 'Counter' is private to module 'util'",
+                "field 'limit' of 'Counter' is private to module 'util'",
+            ]
+        );
+        assert_eq!(
+            errors[0].help.as_deref(),
+            Some("remove `static` from 'limit' in the declaration of 'Counter' to make it public")
+        );
+        assert_eq!(
+            errors[1].help.as_deref(),
+            Some("remove `static` from 'clamp' in the declaration of 'Counter' to make it public")
+        );
+    }
+
+    #[test]
+    fn test_private_fields_in_macro_arguments() {
+        use crate::parser::Parser;
+
+        let util = Parser::new("struct Counter {\n    int count;\n    static int limit;\n}\n")
+            .unwrap()
+            .parse_file()
+            .unwrap();
+        let main = "#import util.Counter\nint main() {\n    Counter c = (Counter){ .count = 0 };\n    __println__(\"{} {}\", c.count, c.limit);\n    return 0;\n}\n";
+        let file = Parser::new(main).unwrap().parse_file().unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.add_module("util", &util);
+        let errors = analyzer.analyze(&file).unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            ["field 'limit' of 'Counter' is private to module 'util'"]
+        );
+        assert_eq!(
+            errors[0].help.as_deref(),
+            Some("remove `static` from 'limit' in the declaration of 'Counter' to make it public")
+        );
+    }
+
+    #[test]
+    fn test_extern_function_calls() {
+        use crate::parser::Parser;
+
+        let decls = "extern \"C\" {\n    int puts(char* s);\n    int printf(char* fmt, ...);\n}\n";
+        let inside = format!(
+            "{}void main() {{\n    unsafe {{\n        puts(\"hi\");\n        printf(\"%d %d\\n\", 1, 2);\n    }}\n}}\n",
+            decls
+        );
+        let file = Parser::new(&inside).unwrap().parse_file().unwrap();
+        assert!(SemanticAnalyzer::new().analyze(&file).is_ok());
+
+        let outside = format!("{}void main() {{\n    puts(\"hi\");\n}}\n", decls);
+        let file = Parser::new(&outside).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].message,
+            "call to extern function 'puts' requires an unsafe block"
+        );
+
+        let arity = format!(
+            "{}void main() {{\n    unsafe {{\n        puts(\"a\", \"b\");\n        printf();\n    }}\n}}\n",
+            decls
+        );
+        let file = Parser::new(&arity).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "function call argument count mismatch: expected 1, found 2",
+                "function call argument count mismatch: expected at least 1, found 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_array_copy_and_comparison() {
+        use crate::parser::Parser;
+
+        let valid = "void fill(int n) {\n    int a[4] = [1, 2, 3, 4];\n    var int b[4] = [0, 0, 0, 0];\n    var int c[8] = [0, 0, 0, 0, 0, 0, 0, 0];\n    memcpy(b, a, 4 * (int)sizeof(int));\n    memcpy(c, a, 8);\n    memcpy(c, b, n);\n    b = a;\n    bool same = a == b;\n}\n";
+        let file = Parser::new(valid).unwrap().parse_file().unwrap();
+        assert!(SemanticAnalyzer::new().analyze(&file).is_ok());
+
+        let invalid = "void f(i64[4] w) {\n    var int a[4] = [1, 2, 3, 4];\n    var int c[8] = [0, 0, 0, 0, 0, 0, 0, 0];\n    int x = 0;\n    a = c;\n    bool same = a == c;\n    memcpy(a, c);\n    memcpy(a, x, 4);\n    memcpy(a, w, 8);\n    memcpy(a, c, 6);\n    memcpy(a, c, 32);\n}\n";
+        let file = Parser::new(invalid).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "cannot assign an array of 8 elements to an array of 4",
+                "cannot compare arrays of different sizes: 4 and 8",
+                "memcpy expects a destination, a source and a byte count, found 2 arguments",
+                "memcpy copies between arrays or slices",
+                "memcpy between arrays of different element types: Primitive(Int) and Primitive(I64)",
+                "memcpy of 6 bytes is not a whole number of 4-byte elements",
+                "memcpy of 32 bytes overflows its destination of 16 bytes",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_time_builtins() {
+        use crate::parser::Parser;
+
+        let valid = "void wait(int n) {\n    i64 start = time(NULL);\n    i64 ticks = clock();\n    sleep(n);\n    usleep(500);\n    f64 seconds = (f64)(clock() - ticks) / (f64)CLOCKS_PER_SEC;\n    f64 since = difftime(time(), start);\n}\n";
+        let file = Parser::new(valid).unwrap().parse_file().unwrap();
+        assert!(SemanticAnalyzer::new().analyze(&file).is_ok());
+
+        let invalid = "void f() {\n    i64 now = time(1);\n    sleep(1.5);\n    usleep();\n    int ticks = clock();\n}\n";
+        let file = Parser::new(invalid).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "time expects 0 arguments, found 1",
+                "sleep expects integer arguments, found Primitive(F64)",
+                "usleep expects 1 argument, found 0",
+                "variable 'ticks' type mismatch: expected Primitive(Int), found Primitive(I64)",
+            ]
+        );
+
+        // Without the standard library only difftime() is available
+        let file = Parser::new(valid).unwrap().parse_file().unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.set_freestanding(true);
+        let errors = analyzer.analyze(&file).unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "time() needs the standard library, which --freestanding programs are built without",
+                "clock() needs the standard library, which --freestanding programs are built without",
+                "sleep() needs the standard library, which --freestanding programs are built without",
+                "usleep() needs the standard library, which --freestanding programs are built without",
+                "clock() needs the standard library, which --freestanding programs are built without",
+                "time() needs the standard library, which --freestanding programs are built without",
+            ]
+        );
+
+        // A function of the same name replaces the builtin
+        let shadowed = "int sleep(int n, int m) {\n    return n + m;\n}\nvoid main() {\n    int r = sleep(1, 2);\n}\n";
+        let file = Parser::new(shadowed).unwrap().parse_file().unwrap();
+        assert!(SemanticAnalyzer::new().analyze(&file).is_ok());
+    }
+
+    #[test]
+    fn test_signal_builtins() {
+        use crate::parser::Parser;
+
+        let valid = "void on_interrupt(int signum) {}\nvoid main() {\n    signal(SIGINT, on_interrupt);\n    signal(SIGTERM, SIG_IGN);\n    signal(SIGHUP, SIG_DFL);\n    int status = raise(SIGINT);\n}\n";
+        let file = Parser::new(valid).unwrap().parse_file().unwrap();
+        assert!(SemanticAnalyzer::new().analyze(&file).is_ok());
+
+        let invalid = "void twice(int a, int b) {}\nvoid main() {\n    var int count = 0;\n    void bump(int signum) { count = count + 1; }\n    signal(SIGINT, bump);\n    signal(SIGINT, twice);\n    signal(1.5, SIG_IGN);\n    raise();\n}\n";
+        let file = Parser::new(invalid).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "signal handler 'bump' is a nested function, which may capture local variables; handlers must be top-level functions",
+                "signal handler 'twice' must take the signal number as an int and return void",
+                "signal expects a signal number (int), found Primitive(F64)",
+                "raise expects 1 argument, found 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_signal_errors_point_at_the_call() {
+        use crate::error::Position;
+        use crate::parser::Parser;
+
+        let source = "void main() {\n    int x = 1;\n    int status = x + raise();\n}\n";
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        assert_eq!(errors[0].message, "raise expects 1 argument, found 0");
+        assert_eq!(errors[0].span.start, Position::new(3, 22));
+        assert_eq!(errors[0].span.end, Position::new(3, 27));
+    }
+
+    #[test]
+    fn test_for_in_borrows_struct_elements() {
+        use crate::parser::Parser;
+
+        let valid = "struct Point { int x; int y; }\nint scale(int k) {\n    var Point pts[2] = [(Point){ .x = 1, .y = 2 }, (Point){ .x = 3, .y = 4 }];\n    var int total = 0;\n    for (p in pts) {\n        p.x = p.x * k;\n        p.y++;\n    }\n    for (p in pts) {\n        total = total + p.x;\n    }\n    return total;\n}\n";
+        let file = Parser::new(valid).unwrap().parse_file().unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        assert!(analyzer.analyze(&file).is_ok());
+        // The first loop writes through its elements, the second only reads
+        let pts = Expression::Ident(Ident::new("pts"));
+        let arrays = analyzer.analysis().borrowed_arrays;
+        assert_eq!(
+            arrays[&Site::new("scale", Position::new(5, 5))],
+            [(pts.clone(), true)]
+        );
+        assert_eq!(
+            arrays[&Site::new("scale", Position::new(9, 5))],
+            [(pts, false)]
+        );
+
+        let invalid = "struct Point { int x; int y; }\nvoid f(Point[2] pts) {\n    var Point own[2] = [(Point){ .x = 1, .y = 2 }, (Point){ .x = 3, .y = 4 }];\n    for (p in pts) {\n        p.x = 0;\n    }\n    for (p in own) {\n        own = pts;\n    }\n}\n";
+        let file = Parser::new(invalid).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "cannot modify 'p.x': the elements of 'pts' can only be modified when it is declared 'var'",
+                "cannot modify 'own' while a for-in loop iterates over it",
+                "'pts' is used after it was moved on line 8, in an earlier iteration of the loop",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tagged_union_construction_and_matching() {
+        use crate::parser::Parser;
+
+        let shape =
+            "enum Shape {\n    Circle(float radius),\n    Rect(float w, float h),\n    Empty,\n}\n";
+        let valid = format!("{}float area(Shape s) {{\n    return match (s) {{\n        Shape.Circle(r) => 3.0 * r * r,\n        Shape.Rect(w, h) => w * h,\n        Shape.Empty => 0.0,\n    }};\n}}\nbool same(Shape a) {{\n    let Shape b = Shape.Rect(1.0, 2.0);\n    return a == b;\n}}\n", shape);
+        let file = Parser::new(&valid).unwrap().parse_file().unwrap();
+        assert!(SemanticAnalyzer::new().analyze(&file).is_ok());
+
+        let invalid = format!("{}float area(Shape s) {{\n    return match (s) {{\n        Shape.Circle(r) => r,\n        Shape.Rect(w) => w,\n    }};\n}}\nvoid main() {{\n    let Shape c = Shape.Circle(2.0, 1.0);\n    let Shape d = Shape.Circle(true);\n}}\n", shape);
+        let file = Parser::new(&invalid).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "variant 'Shape.Rect' has 2 field(s), but the pattern has 1",
+                "function call argument count mismatch: expected 1, found 2",
+                "function call argument 1 type mismatch: expected Primitive(Float), found Primitive(Bool)",
+            ]
+        );
+
+        let missing = format!("{}float area(Shape s) {{\n    return match (s) {{\n        Shape.Circle(r) => r,\n        Shape.Empty => 0.0,\n    }};\n}}\n", shape);
+        let file = Parser::new(&missing).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "non-exhaustive match: 'Shape.Rect(_, _)' is not covered; add an arm for it or a '_' arm"
+        );
+    }
+
+    #[test]
+    fn test_trait_impls_are_checked() {
+        use crate::parser::Parser;
+
+        let shape = "trait Shape {\n    float area(&self);\n    void scale(var &self, float k);\n}\nstruct Circle {\n    float r;\n}\nstruct Rect {\n    float w;\n    float h;\n}\n";
+        let valid = format!("{}impl Shape for Circle {{\n    float area(&self) {{\n        return self.r * self.r;\n    }}\n    void scale(var &self, float k) {{\n        self.r = self.r * k;\n    }}\n}}\nimpl Shape for Rect {{\n    float area(&self) {{\n        return self.w * self.h;\n    }}\n    void scale(var &self, float k) {{\n        self.w = self.w * k;\n    }}\n}}\nfloat total() {{\n    var Circle c = (Circle){{ .r = 1.0 }};\n    c.scale(2.0);\n    return c.area();\n}}\n", shape);
+        let file = Parser::new(&valid).unwrap().parse_file().unwrap();
+        assert!(SemanticAnalyzer::new().analyze(&file).is_ok());
+
+        let invalid = format!("{}impl Shape for Circle {{\n    int area(&self) {{\n        return 3;\n    }}\n    void grow(var &self) {{\n        self.r = self.r * 2.0;\n    }}\n}}\nimpl Shape for Circle {{\n    float area(&self) {{\n        return self.r;\n    }}\n    void scale(var &self, float k) {{\n        self.r = self.r * k;\n    }}\n}}\nimpl Drawable for Square {{\n}}\n", shape);
+        let file = Parser::new(&invalid).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "method 'area' does not match its declaration in trait 'Shape': expected 'float area(&self)', found 'int area(&self)'",
+                "impl of trait 'Shape' for 'Circle' is missing method 'scale'",
+                "method 'grow' is not a member of trait 'Shape'",
+                "conflicting implementations of trait 'Shape' for 'Circle'",
+                "undefined trait 'Drawable'",
+                "undefined type 'Square' in impl of 'Drawable'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_trait_default_methods() {
+        use crate::parser::Parser;
+
+        let shape = "trait Shape {\n    float area(&self);\n    float twice(&self) {\n        return self.area() * 2.0;\n    }\n}\nstruct Circle {\n    float r;\n}\nstruct Square {\n    float w;\n}\n";
+        let valid = format!("{}impl Shape for Circle {{\n    float area(&self) {{\n        return self.r * self.r;\n    }}\n}}\nimpl Shape for Square {{\n    float area(&self) {{\n        return self.w * self.w;\n    }}\n    float twice(&self) {{\n        return self.w * self.w * 2.0;\n    }}\n}}\nfloat total() {{\n    let c = (Circle){{ .r = 1.0 }};\n    let s = (Square){{ .w = 2.0 }};\n    return c.twice() + s.twice();\n}}\n", shape);
+        let file = Parser::new(&valid).unwrap().parse_file().unwrap();
+        assert!(SemanticAnalyzer::new().analyze(&file).is_ok());
+
+        let invalid = "trait Shape {\n    float area(&self);\n    float side(&self) {\n        return self.w;\n    }\n    float twice(&self) {\n        return self.area() * 2.0;\n    }\n}\nstruct Square {\n    float w;\n}\nimpl Shape for Square {\n    int twice(&self) {\n        return 2;\n    }\n}\n";
+        let file = Parser::new(invalid).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "default method of trait 'Shape' cannot access field 'w' of self",
+                "impl of trait 'Shape' for 'Square' is missing method 'area'",
+                "method 'twice' does not match its declaration in trait 'Shape': expected 'float twice(&self)', found 'int twice(&self)'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_generic_trait_impls() {
+        use crate::parser::Parser;
+
+        let pair = "trait Growing {\n    bool grows(&self);\n}\nstruct Pair(T) {\n    T first;\n    T second;\n}\nimpl Growing for Pair(T) {\n    bool grows(&self) {\n        return self.first + self.second > self.first;\n    }\n}\n";
+        let valid = format!("{}bool check() {{\n    let p = (Pair<int>){{ .first = 1, .second = 2 }};\n    return p.grows();\n}}\n", pair);
+        let file = Parser::new(&valid).unwrap().parse_file().unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        assert!(analyzer.analyze(&file).is_ok());
+        let bounds: Vec<&str> = analyzer.generic_bounds()["impl Growing for Pair(T)"]["T"]
+            .iter()
+            .map(String::as_str)
+            .collect();
+        assert_eq!(bounds, ["Add", "Copy", "PartialOrd"]);
+
+        let invalid = format!("{}impl Growing for Pair<int> {{\n    bool grows(&self) {{\n        return true;\n    }}\n}}\nstruct Point {{\n    int x;\n}}\nimpl Growing for Point(T) {{\n    bool grows(&self) {{\n        return false;\n    }}\n}}\nbool check() {{\n    let p = (Pair<bool>){{ .first = true, .second = false }};\n    return p.grows();\n}}\n", pair);
+        let file = Parser::new(&invalid).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "conflicting implementations of trait 'Growing': 'impl Growing for Pair<int>' overlaps 'impl Growing for Pair(T)'",
+                "'Point' takes no type arguments, found 1",
+                "'p.grows()': method 'grows' of 'Pair<bool>' is unavailable: 'impl Growing for Pair(T)' needs 'T: Add', and 'bool' does not implement 'Add'",
+            ]
+        );
+        // The overlap is reported at the impl that makes it
+        assert_eq!(errors[0].span.start.line, 13);
+
+        // `Pair<T>` names an instance, not the struct's parameter
+        let angled = "trait Growing {\n    bool grows(&self);\n}\nstruct Pair(T) {\n    T first;\n}\nimpl Growing for Pair<T> {\n    bool grows(&self) {\n        return true;\n    }\n}\n";
+        let file = Parser::new(angled).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            ["undefined type 'T' in impl of 'Growing'; write 'impl Growing for Pair(T)' to implement it for every 'Pair'"]
+        );
+        assert_eq!(errors[0].span.start.line, 7);
+    }
+
+    #[test]
+    fn test_trait_objects() {
+        use crate::parser::Parser;
+
+        let shape = "trait Shape {\n    float area(&self);\n}\nstruct Circle {\n    float r;\n}\nstruct Square {\n    float w;\n}\nimpl Shape for Circle {\n    float area(&self) {\n        return self.r * self.r;\n    }\n}\n";
+        let valid = format!("{}float show(&Shape s) {{\n    return s.area();\n}}\nShape* make(float r) {{\n    return (Circle){{ .r = r }};\n}}\nfloat total() {{\n    let c = (Circle){{ .r = 1.0 }};\n    var Shape* s = make(2.0);\n    float shown = show(&c);\n    s = c;\n    return shown + s.area();\n}}\n", shape);
+        let file = Parser::new(&valid).unwrap().parse_file().unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        assert!(analyzer.analyze(&file).is_ok());
+        let boxed: Vec<&Type> = analyzer
+            .analysis()
+            .conversions
+            .iter()
+            .filter(|(site, _)| site.function == "total")
+            .flat_map(|(_, conversions)| conversions.iter().map(|(_, ty)| ty))
+            .collect();
+        assert_eq!(
+            boxed,
+            [&Type::Pointer {
+                ty: Box::new(Type::Ident(Ident::new("Shape"))),
+                mutable: true,
+            }]
+        );
+
+        let invalid = format!("{}trait Cloned {{\n    Self copy(&self);\n}}\nfloat f(Shape s, Cloned* c) {{\n    let sq = (Square){{ .w = 1.0 }};\n    Shape* p = sq;\n    return p.area(1);\n}}\n", shape);
+        let file = Parser::new(&invalid).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "trait 'Shape' is not a type of values; store one through 'Shape*' or '&Shape'",
+                "trait 'Cloned' cannot be used through a pointer: method 'copy' uses Self other than as its receiver",
+                "variable 'p' type mismatch: expected Pointer { ty: Ident(Ident { name: \"Shape\" }), mutable: true }, found Ident(Ident { name: \"Square\" })",
+                "'p.area(...)': 'Shape.area' takes 0 arguments, found 1",
+            ]
+        );
+        assert_eq!(errors[2].notes, ["'Square' does not implement 'Shape'"]);
+    }
+
+    #[test]
+    fn test_collections_of_trait_objects() {
+        use crate::parser::Parser;
+
+        let shape = "trait Shape {\n    float area(&self);\n}\nstruct Circle {\n    float r;\n}\nstruct Square {\n    float w;\n}\nimpl Shape for Circle {\n    float area(&self) {\n        return self.r * self.r;\n    }\n}\n";
+        let valid = format!("{}float total() {{\n    var Vec<Shape*> shapes = @Vec(Shape*).new();\n    shapes.push((Circle){{ .r = 1.0 }});\n    shapes.insert(0, (Circle){{ .r = 2.0 }});\n    var float sum = 0.0;\n    for (s in shapes) {{\n        sum = sum + s.area();\n    }}\n    return sum;\n}}\n", shape);
+        let file = Parser::new(&valid).unwrap().parse_file().unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        assert!(analyzer.analyze(&file).is_ok());
+        // The inserted elements are boxed
+        let boxed: Vec<usize> = analyzer
+            .analysis()
+            .arguments
+            .iter()
+            .filter(|(site, _)| site.function == "total")
+            .flat_map(|(_, found)| found.iter().map(|(_, index, _)| *index))
+            .collect();
+        assert_eq!(boxed, [0, 1]);
+        let shapes = Expression::Ident(Ident::new("shapes"));
+        assert_eq!(
+            analyzer.analysis().borrowed_arrays[&Site::new("total", Position::new(20, 5))],
+            [(shapes, false)]
+        );
+
+        let invalid = format!("{}void fill(var &Vec<Shape*> shapes) {{\n    shapes.push((Square){{ .w = 1.0 }});\n    for (s in shapes) {{\n        s.area(2);\n    }}\n}}\n", shape);
+        let file = Parser::new(&invalid).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "'shapes.push(...)': 'Square' does not implement 'Shape'",
+                "'s.area(...)': 'Shape.area' takes 0 arguments, found 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_variadic_functions() {
+        use crate::parser::Parser;
+
+        let valid = "int sum(int... values) {\n    var int total = 0;\n    for (v in values) {\n        total += v;\n    }\n    return total;\n}\nint scaled(int factor, int... values) {\n    return factor * sum(values);\n}\nvoid main() {\n    let int[2] a = [1, 2];\n    let int n = scaled(2, 1, 2, 3) + sum() + sum(&a);\n}\n";
+        let file = Parser::new(valid).unwrap().parse_file().unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        assert!(analyzer.analyze(&file).is_ok());
+        // Slices and arrays are passed as they are
+        let fixed: Vec<usize> = analyzer.analysis().variadic_calls
+            [&Site::new("main", Position::new(13, 5))]
+            .iter()
+            .map(|(_, fixed)| *fixed)
+            .collect();
+        assert_eq!(fixed, [1, 0]);
+        assert!(!analyzer
+            .analysis()
+            .variadic_calls
+            .keys()
+            .any(|site| site.function == "scaled"));
+
+        let invalid = "int first(int... values, int n) {\n    return n;\n}\nint pick(int n, int... values) {\n    return n;\n}\nstruct P {\n    int x;\n    int add(&self, int... values) {\n        return self.x;\n    }\n}\nvoid main() {\n    let int a = pick();\n    let int b = pick(1, 2, true);\n}\n";
+        let file = Parser::new(invalid).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "variadic parameter 'values' must be the last parameter of 'first'",
+                "method 'add' cannot take a variadic parameter; take a slice, '&int[] values', instead",
+                "function call argument count mismatch: expected at least 1, found 0",
+                "function call argument 3 type mismatch: expected Primitive(Int), found Primitive(Bool)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_macro_arguments_reject_variadic_calls_and_lambdas() {
+        use crate::parser::Parser;
+
+        let source = "int sum(int... values) {\n    return 0;\n}\nvoid main() {\n    let int k = 2;\n    let int n = sum(1, 2);\n    __println__(\"{} {}\", n, p.sum(1, 2));\n    __println__(\"{}\", sum(1, 2, 3));\n    __println__(\"{}\", fn(int x) -> int { return x + k; }(3));\n}\n";
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "variadic function 'sum' cannot be called in the arguments of '__println__'; store the result in a local first",
+                "a lambda cannot be written in the arguments of '__println__'; bind it to a variable first",
+            ]
+        );
+        assert_eq!(errors[0].span.start, Position::new(8, 5));
+    }
+
+    #[test]
+    fn test_function_pointers() {
+        use crate::parser::Parser;
+
+        let valid = "int add(int a, int b) {\n    return a + b;\n}\nvoid show(int n) {\n}\nint apply(int(*)(int, int) op, int a, int b) {\n    return op(a, b);\n}\nvoid main() {\n    var fn(int, int) -> int f = add;\n    let fn(int) g = show;\n    g(apply(f, 1, 2) + apply(add, 3, 4));\n}\n";
+        let file = Parser::new(valid).unwrap().parse_file().unwrap();
+        assert!(SemanticAnalyzer::new().analyze(&file).is_ok());
+
+        let invalid = "int add(int a, int b) {\n    return a + b;\n}\nint apply(fn(int, int) -> int op) {\n    return op(1, 2);\n}\nvoid main() {\n    let fn(int) -> int f = add;\n    let int n = apply(main);\n}\n";
+        let file = Parser::new(invalid).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert!(errors[1]
+            .message
+            .starts_with("function call argument 1 type mismatch"));
+    }
+
+    #[test]
+    fn test_lambdas() {
+        use crate::ast::{Expression, Statement};
+        use crate::parser::Parser;
+
+        let valid = "int apply(fn(int) -> int f, int n) {\n    return f(n);\n}\nvoid main() {\n    var int k = 2;\n    var String name = String::new();\n    let scale = fn(int x) -> int {\n        return x * k;\n    };\n    let show = fn() {\n        name.len();\n    };\n    var bump = fn() {\n        k = k + 1;\n    };\n    let int n = apply(fn(int x) -> int {\n        return x + 1;\n    }, scale(2));\n}\n";
+        let file = Parser::new(valid).unwrap().parse_file().unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        assert!(analyzer.analyze(&file).is_ok());
+        let crate::ast::Item::Function(main) = &file.items[1] else {
+            panic!("Expected function");
+        };
+        let Statement::Let {
+            init: Some(scale), ..
+        } = &main.body.statements[2]
+        else {
+            panic!("Expected let");
+        };
+        // Only `scale` reads nothing but copied values
+        assert_eq!(
+            analyzer.analysis().value_lambdas[&Site::new("main", Position::new(7, 5))],
+            std::slice::from_ref(scale)
+        );
+        assert!(matches!(scale, Expression::Lambda { .. }));
+
+        let invalid = "int apply(fn(int) -> int f, int n) {\n    return f(n);\n}\nvoid main() {\n    let int k = 2;\n    let scale = fn(int x) -> int {\n        return x * k;\n    };\n    let int a = apply(scale, 1);\n    let int b = apply(fn(int x) -> int {\n        return x + k;\n    }, 1);\n    let fn() -> int f = fn() -> int {\n        return true;\n    };\n}\n";
+        let file = Parser::new(invalid).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "'scale' holds a lambda that captures 'k' and cannot be used as a function pointer",
+                "lambda captures 'k' and cannot be used as a function pointer",
+                "return type mismatch: expected Primitive(Int), found Primitive(Bool)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_generic_functions_infer_types_and_bounds() {
+        use crate::parser::Parser;
+
+        let valid = "T max(T)(T a, T b) {\n    if (a > b) {\n        return a;\n    }\n    return b;\n}\nT sum3(T)(T a, T b, T c) {\n    return max(a, b) + c;\n}\nvoid main() {\n    int i = max(3, 7);\n    float f = sum3(1.5, 2.5, 0.5);\n}\n";
+        let file = Parser::new(valid).unwrap().parse_file().unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        assert!(analyzer.analyze(&file).is_ok());
+        let bounds: Vec<(&str, Vec<&str>)> = analyzer
+            .generic_bounds()
+            .iter()
+            .map(|(function, params)| {
+                (
+                    function.as_str(),
+                    params["T"].iter().map(String::as_str).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            bounds,
+            [
+                ("max", vec!["PartialOrd"]),
+                ("sum3", vec!["Add", "Copy", "PartialOrd"]),
+            ]
+        );
+
+        let invalid = "T max(T)(T a, T b) {\n    return a;\n}\nT zero(T)() {\n    return 0;\n}\nT same(T, T)(T a) {\n    return a;\n}\nvoid main() {\n    let m = max(3, 2.5);\n    int n = max(1.5, 2.5);\n}\n";
+        let file = Parser::new(invalid).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "type parameter 'T' of function 'zero' is not used by any parameter, so calls cannot infer it",
+                "return type mismatch: expected Ident(Ident { name: \"T\" }), found Primitive(I32)",
+                "type parameter 'T' is declared more than once in function 'same'",
+                "function call argument 2 type mismatch: expected Primitive(I32), found Primitive(F64)",
+                "variable 'n' type mismatch: expected Primitive(Int), found Primitive(F64)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_generic_structs() {
+        use crate::parser::Parser;
+
+        let valid = "struct Pair(T) {\n    T first;\n    T second;\n    T larger(self) {\n        if (self.first > self.second) {\n            return self.first;\n        }\n        return self.second;\n    }\n}\nint sum(Pair<int> p) {\n    return p.first + p.second;\n}\nvoid main() {\n    let p = (Pair<int>){ .first = 3, .second = 7 };\n    int big = p.larger();\n    let q = (Pair<float>){ .first = 1.5, .second = 0.5 };\n    float f = q.first;\n}\n";
+        let file = Parser::new(valid).unwrap().parse_file().unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        assert!(analyzer.analyze(&file).is_ok());
+        let bounds: Vec<&str> = analyzer.generic_bounds()["Pair"]["T"]
+            .iter()
+            .map(String::as_str)
+            .collect();
+        assert_eq!(bounds, ["PartialOrd"]);
+
+        let invalid = "struct Pair(T) {\n    T first;\n    T pick(T)(T other) {\n        return other;\n    }\n}\nstruct Entry(K, V) {\n    K key;\n}\nint f(Pair<int, int> p) {\n    return 0;\n}\nvoid main() {\n    let p = (Pair<int>){ .first = 1 };\n    bool b = p.first;\n}\n";
+        let file = Parser::new(invalid).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "type parameter 'T' of method 'pick' is already a type parameter of struct 'Pair'",
+                "type parameter 'V' of struct 'Entry' is not used by any field",
+                "'Pair' takes 1 type argument, found 2",
+                "variable 'b' type mismatch: expected Primitive(Bool), found Primitive(Int)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reports_errors_up_to_limit() {
+        use crate::parser::Parser;
+
+        let source = "int f() {\n    int a = true;\n    int b = missing;\n    return a;\n}\nvoid g() {\n    bool c = 1;\n}\nvoid h() {\n    float d = false;\n}\n";
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        assert_eq!(errors.len(), 4);
+
+        // Analysis stops before the next item once the limit is reached
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.set_error_limit(1);
+        let errors = analyzer.analyze(&file).unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            ["variable 'a' type mismatch: expected Primitive(Int), found Primitive(Bool)"]
+        );
+
+        analyzer.set_error_limit(3);
+        assert_eq!(analyzer.analyze(&file).unwrap_err().len(), 3);
+
+        let source = (0..40)
+            .map(|i| format!("void check{}() {{\n    int x = true;\n}}\n", i))
+            .collect::<String>();
+        let file = Parser::new(&source).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        assert_eq!(errors.len(), crate::semantic::DEFAULT_ERROR_LIMIT);
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.set_error_limit(0);
+        assert_eq!(analyzer.analyze(&file).unwrap_err().len(), 40);
+    }
+
+    #[test]
+    fn test_static_assert_is_evaluated() {
+        use crate::parser::Parser;
+
+        let passing = "static_assert((int)sizeof(i64) == 8, \"i64 is 8 bytes\");\nstruct Header { int a; }\nstatic_assert((int)sizeof(Header) == 4, \"checked by rustc\");\nvoid main() {\n    const int SIZE = 16;\n    static_assert((SIZE & (SIZE - 1)) == 0, \"SIZE must be a power of two\");\n}\n";
+        let file = Parser::new(passing).unwrap().parse_file().unwrap();
+        assert!(SemanticAnalyzer::new().analyze(&file).is_ok());
+
+        let failing = passing.replace("SIZE = 16", "SIZE = 12");
+        let file = Parser::new(&failing).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].message,
+            "static assertion failed: SIZE must be a power of two"
+        );
+
+        let dynamic = "void check(int n) {\n    static_assert(n > 0, \"n is positive\");\n}\n";
+        let file = Parser::new(dynamic).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "static assertion condition is not constant: 'n' is not a constant"
+        );
+
+        // A mistake in the condition is reported once, and an assertion
+        // among the items points at itself
+        let mistaken =
+            "static_assert(1 + true == 2, \"never\");\nstatic_assert(1 == 2, \"one is two\");\n";
+        let file = Parser::new(mistaken).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        let found: Vec<_> = errors
+            .iter()
+            .map(|e| (e.message.as_str(), e.span.start))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (
+                    "binary operation type mismatch: i32 and bool",
+                    Position::new(1, 1)
+                ),
+                ("static assertion failed: one is two", Position::new(2, 1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_string_initialized_arrays() {
+        use crate::parser::Parser;
+
+        let source = "void main() {\n    char name[8] = \"crusty\";\n    u8 magic[4] = b\"\\x7fELF\";\n    var u8 raw[3] = \"abc\";\n}\n";
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        assert!(analyzer.analyze(&file).is_ok());
+        assert_eq!(
+            analyzer.warnings(),
+            [
+                "initializer string for 'magic' fills all 4 elements of its array, leaving no room for a NUL terminator",
+                "initializer string for 'raw' fills all 3 elements of its array, leaving no room for a NUL terminator",
+            ]
+        );
+
+        let too_long =
+            "void main() {\n    char tag[2] = \"abc\";\n    char bytes[4] = b\"abc\";\n}\n";
+        let file = Parser::new(too_long).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(
+            errors[0].message,
+            "initializer string for 'tag' is 3 elements long, too long for an array of 2"
+        );
+        assert!(errors[1]
+            .message
+            .starts_with("variable 'bytes' type mismatch"));
+    }
+
+    #[test]
+    fn test_names_shadowing_the_rust_prelude() {
+        use crate::parser::Parser;
+
+        let source =
+            "struct String { int len; }\nvoid drop(int x) {}\nvoid main() {\n    drop(1);\n}\n";
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        assert!(analyzer.analyze(&file).is_ok());
+        assert_eq!(
+            analyzer.warnings(),
+            [
+                "'String' shadows the Rust prelude's 'std::string::String'; generated code names the prelude item by its full path",
+                "'drop' shadows the Rust prelude's 'std::mem::drop'; generated code names the prelude item by its full path",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cfg_and_target_feature_attributes() {
+        use crate::parser::Parser;
+
+        let valid = "#[cfg(all(unix, not(target_os = \"macos\")))]\n#[target_feature(enable = \"avx2\")]\nint fast() { return 1; }\n";
+        let file = Parser::new(valid).unwrap().parse_file().unwrap();
+        assert!(SemanticAnalyzer::new().analyze(&file).is_ok());
+
+        let invalid = "#[target_feature(enable = \"avx2\")]\nstruct Lanes { int n; }\n#[cfg(unix, windows)]\nint f() { return 0; }\n";
+        let file = Parser::new(invalid).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "#[target_feature] applies only to functions",
+                "#[cfg] takes a single predicate, as in #[cfg(target_os = \"linux\")]",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_qualified_enum_variants() {
+        use crate::parser::Parser;
+
+        let qualified = "enum Color { Red, Green }\nvoid main() {\n    Color a = Color.Red;\n    Color b = Color::Green;\n    let c = @Color.Red;\n}\n";
+        let file = Parser::new(qualified).unwrap().parse_file().unwrap();
+        assert!(SemanticAnalyzer::new().analyze(&file).is_ok());
+
+        let unknown = "enum Color { Red, Green }\nvoid main() {\n    Color a = Color.Blue;\n}\n";
+        let file = Parser::new(unknown).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        assert_eq!(errors[0].message, "enum 'Color' has no variant 'Blue'");
+    }
+
+    #[test]
+    fn test_unqualified_enum_variants() {
+        use crate::parser::Parser;
+
+        let source = "enum Color { Red, Green }\nenum Light { Red, Amber }\nvoid main() {\n    Color a = Green;\n    Color b = Green;\n}\n";
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "enum variant 'Green' must be qualified as 'Color.Green'"
+        );
+
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.set_allow_unqualified_variants(true);
+        assert!(analyzer.analyze(&file).is_ok());
+        assert_eq!(analyzer.warnings().len(), 1);
+        assert_eq!(analyzer.unqualified_variants()["Green"], "Color");
+
+        let ambiguous = "enum Color { Red, Green }\nenum Light { Red, Amber }\nvoid main() {\n    Color a = Red;\n}\n";
+        let file = Parser::new(ambiguous).unwrap().parse_file().unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.set_allow_unqualified_variants(true);
+        let errors = analyzer.analyze(&file).unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "ambiguous enum variant 'Red': qualify it as one of 'Color.Red', 'Light.Red'"
+        );
+    }
+
     #[test]
     fn test_const_statement_in_function() {
         let mut analyzer = SemanticAnalyzer::new();
@@ -23,6 +1254,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::Const {
@@ -46,6 +1278,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::Const {
@@ -71,6 +1304,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![
@@ -103,6 +1337,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::If {
@@ -136,6 +1371,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::If {
@@ -161,6 +1397,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::While {
@@ -179,6 +1416,172 @@ mod tests {
         assert_eq!(errors[0].kind, SemanticErrorKind::TypeMismatch);
     }
 
+    #[test]
+    fn test_do_while_loop() {
+        use crate::parser::Parser;
+
+        let valid = "int count(int n) {\n    var int left = n;\n    do {\n        left = left - 1;\n    } while (left > 0);\n    return left;\n}\n";
+        let file = Parser::new(valid).unwrap().parse_file().unwrap();
+        assert!(SemanticAnalyzer::new().analyze(&file).is_ok());
+
+        let not_boolean = "void spin() {\n    do {\n    } while (1);\n}\n";
+        let file = Parser::new(not_boolean).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].message,
+            "do-while condition must be boolean, found i32; write '1 != 0'"
+        );
+
+        // Locals of the body are out of scope in the condition
+        let body_local =
+            "void spin() {\n    do {\n        bool again = false;\n    } while (again);\n}\n";
+        let file = Parser::new(body_local).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        assert_eq!(errors[0].kind, SemanticErrorKind::UndefinedVariable);
+    }
+
+    #[test]
+    fn test_match_exhaustiveness() {
+        use crate::parser::Parser;
+
+        let messages = |body: &str| {
+            let source = format!(
+                "enum Color {{ Red, Green, Blue }}\nint f(Color c, int n, bool b, Option<int> o) {{\n    return {};\n}}\n",
+                body
+            );
+            let file = Parser::new(&source).unwrap().parse_file().unwrap();
+            match SemanticAnalyzer::new().analyze(&file) {
+                Ok(()) => vec![],
+                Err(errors) => errors.into_iter().map(|e| e.message).collect::<Vec<_>>(),
+            }
+        };
+
+        // Every variant, a catch-all, or every combination of a tuple covers
+        assert!(
+            messages("match (c) { Color.Red => 1, Color.Green => 2, Color::Blue => 3 }").is_empty()
+        );
+        assert!(messages("match (n) { 0 => 1, other => other }").is_empty());
+        assert!(messages(
+            "match ((b, c)) { (true, _) => 1, (false, Color.Red) => 2, (false, x) => 3 }"
+        )
+        .is_empty());
+        assert!(messages("match (o) { Option.Some(x) => x, Option.None => 0 }").is_empty());
+
+        assert_eq!(
+            messages("match (c) { Color.Red => 1, Color.Green => 2 }"),
+            ["non-exhaustive match: 'Color.Blue' is not covered; add an arm for it or a '_' arm"]
+        );
+        assert_eq!(
+            messages("match (n) { 0 => 1, 1 => 2 }"),
+            ["non-exhaustive match: '_' is not covered; add an arm for it or a '_' arm"]
+        );
+        assert_eq!(
+            messages("match ((b, c)) { (true, _) => 1, (_, Color.Red) => 2 }"),
+            ["non-exhaustive match: '(false, Color.Green)' is not covered; add an arm for it or a '_' arm"]
+        );
+        assert_eq!(
+            messages("match (o) { Option.Some(1) => 1, Option.None => 0 }"),
+            ["non-exhaustive match: 'Option.Some(_)' is not covered; add an arm for it or a '_' arm"]
+        );
+    }
+
+    #[test]
+    fn test_match_patterns_are_checked() {
+        use crate::parser::Parser;
+
+        let messages = |body: &str| {
+            let source = format!(
+                "enum Color {{ Red, Green, Blue }}\nint f(Color c, int n) {{\n    return {};\n}}\n",
+                body
+            );
+            let file = Parser::new(&source).unwrap().parse_file().unwrap();
+            match SemanticAnalyzer::new().analyze(&file) {
+                Ok(()) => vec![],
+                Err(errors) => errors.into_iter().map(|e| e.message).collect::<Vec<_>>(),
+            }
+        };
+
+        // A bare variant name would bind the value instead of matching it
+        assert_eq!(
+            messages("match (c) { Red => 1, _ => 2 }"),
+            ["enum variant 'Red' must be qualified as 'Color.Red' in a pattern"]
+        );
+        assert_eq!(
+            messages("match (c) { Color.Purple => 1, _ => 2 }"),
+            ["enum 'Color' has no variant 'Purple'"]
+        );
+        assert_eq!(
+            messages("match (n) { \"a\" => 1, _ => 2 }"),
+            ["pattern type mismatch: expected int, found `\"a\"`"]
+        );
+        assert_eq!(
+            messages("match (n) { (a, b) => a, _ => 2 }"),
+            ["pattern type mismatch: expected int, found a tuple of 2 elements"]
+        );
+        assert_eq!(
+            messages("match (n) { 0 => 1, _ => 2.5 }"),
+            ["match arms have incompatible types: Primitive(I32) and Primitive(F64)"]
+        );
+        // Bindings are scoped to their arm
+        assert_eq!(
+            messages("match (n) { m => m, _ => m }"),
+            ["undefined variable 'm'"]
+        );
+    }
+
+    #[test]
+    fn test_pointer_and_option_conditions() {
+        use crate::parser::Parser;
+        use crate::semantic::IntConditions;
+
+        let source = "struct Node { int v; }\nint f(Option<int> p, Node* q, int n) {\n    if (p) {\n        return 1;\n    }\n    while (!q) {\n        return 2;\n    }\n    bool both = p && q;\n    return n;\n}\n";
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        assert!(analyzer.analyze(&file).is_ok());
+        let tests = analyzer.analysis().truthiness_tests.clone();
+        let p = Expression::Ident(Ident::new("p"));
+        let q = Expression::Ident(Ident::new("q"));
+        assert_eq!(
+            tests[&Site::new("f", Position::new(3, 5))],
+            [(p.clone(), Truthiness::Option)]
+        );
+        assert_eq!(
+            tests[&Site::new("f", Position::new(6, 5))],
+            [(q.clone(), Truthiness::Pointer)]
+        );
+        assert_eq!(
+            tests[&Site::new("f", Position::new(9, 5))],
+            [(p, Truthiness::Option), (q, Truthiness::Pointer)]
+        );
+
+        // Strict mode rejects integers, suggesting the comparison to write
+        let ints =
+            "int f(int n) {\n    if (n) {\n        return 1;\n    }\n    return !n ? 2 : 3;\n}\n";
+        let file = Parser::new(ints).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "if condition must be boolean, found int; write 'n != 0'",
+                "operand of '!' must be boolean, found int; write 'n == 0'",
+            ]
+        );
+
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.set_int_conditions(IntConditions::Compat);
+        assert!(analyzer.analyze(&file).is_ok());
+        let tests = analyzer.analysis().truthiness_tests.clone();
+        assert_eq!(
+            tests.values().flatten().collect::<Vec<_>>(),
+            [
+                &(Expression::Ident(Ident::new("n")), Truthiness::Integer),
+                &(Expression::Ident(Ident::new("n")), Truthiness::Integer)
+            ]
+        );
+    }
+
     #[test]
     fn test_for_loop_condition_not_boolean() {
         let mut analyzer = SemanticAnalyzer::new();
@@ -186,6 +1589,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::For {
@@ -222,6 +1626,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![
@@ -259,6 +1664,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::Switch {
@@ -285,6 +1691,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::Switch {
@@ -306,6 +1713,45 @@ mod tests {
         assert_eq!(errors[0].kind, SemanticErrorKind::TypeMismatch);
     }
 
+    #[test]
+    fn test_switch_duplicate_case_value() {
+        let mut analyzer = SemanticAnalyzer::new();
+
+        let func = Function {
+            visibility: Visibility::Public,
+            name: Ident::new("test"),
+            generics: vec![],
+            params: vec![],
+            return_type: None,
+            body: Block::new(vec![Statement::Switch {
+                expr: Expression::Literal(Literal::Int(1)),
+                cases: vec![
+                    SwitchCase {
+                        values: vec![Expression::Literal(Literal::Int(4))],
+                        body: Block::empty(),
+                    },
+                    SwitchCase {
+                        values: vec![Expression::Binary {
+                            op: BinaryOp::Mul,
+                            left: Box::new(Expression::Literal(Literal::Int(2))),
+                            right: Box::new(Expression::Literal(Literal::Int(2))),
+                        }],
+                        body: Block::empty(),
+                    },
+                ],
+                default: None,
+            }]),
+            doc_comments: vec![],
+            attributes: vec![],
+        };
+
+        let file = create_file_with_items(vec![Item::Function(func)]);
+        let errors = analyzer.analyze(&file).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, SemanticErrorKind::InvalidOperation);
+        assert_eq!(errors[0].message, "duplicate case value 4 in switch");
+    }
+
     #[test]
     fn test_break_statement() {
         let mut analyzer = SemanticAnalyzer::new();
@@ -313,6 +1759,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::While {
@@ -336,6 +1783,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::While {
@@ -362,6 +1810,7 @@ mod tests {
             variants: vec![EnumVariant {
                 name: Ident::new("Ok"),
                 value: None,
+                fields: vec![],
             }],
             doc_comments: vec![],
             attributes: vec![],
@@ -373,6 +1822,7 @@ mod tests {
             variants: vec![EnumVariant {
                 name: Ident::new("Error"),
                 value: None,
+                fields: vec![],
             }],
             doc_comments: vec![],
             attributes: vec![],
@@ -392,6 +1842,7 @@ mod tests {
         let struct1 = Item::Struct(Struct {
             visibility: Visibility::Public,
             name: Ident::new("Point"),
+            generics: vec![],
             fields: vec![],
             methods: vec![],
             doc_comments: vec![],
@@ -401,6 +1852,7 @@ mod tests {
         let struct2 = Item::Struct(Struct {
             visibility: Visibility::Public,
             name: Ident::new("Point"),
+            generics: vec![],
             fields: vec![],
             methods: vec![],
             doc_comments: vec![],
@@ -502,6 +1954,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::While {
@@ -525,6 +1978,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::For {
@@ -563,6 +2017,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::ForIn {
@@ -591,6 +2046,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::Switch {
@@ -627,6 +2083,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::Var {
@@ -650,6 +2107,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::Var {
@@ -675,6 +2133,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::For {
@@ -712,6 +2171,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::ForIn {
@@ -740,6 +2200,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::Switch {
@@ -23,6 +23,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![
@@ -64,6 +65,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![
@@ -102,6 +104,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![
@@ -140,6 +143,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![
@@ -170,6 +174,76 @@ mod tests {
         assert_eq!(errors[0].kind, SemanticErrorKind::InvalidOperation);
     }
 
+    const LAYOUT_STRUCTS: &str = "\
+#[repr(C)]
+struct Base {
+    int kind;
+}
+
+#[repr(C)]
+struct Derived {
+    int kind;
+    int extra;
+}
+
+#[repr(C)]
+struct Other {
+    float kind;
+}
+
+struct Loose {
+    int kind;
+}
+";
+
+    fn analyze_cast(body: &str) -> Result<(), Vec<crate::error::SemanticError>> {
+        let source = format!("{}\n{}", LAYOUT_STRUCTS, body);
+        let file = crate::parser::Parser::new(&source)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+        SemanticAnalyzer::new().analyze(&file)
+    }
+
+    #[test]
+    fn test_cast_between_layout_compatible_structs() {
+        assert!(analyze_cast(
+            "int f(Base* b) {\n    Derived* d = (Derived*)b;\n    return 0;\n}\n"
+        )
+        .is_ok());
+        assert!(analyze_cast(
+            "int f(&Derived d) {\n    let b = (&Base)d;\n    return b.kind;\n}\n"
+        )
+        .is_ok());
+        assert!(analyze_cast(
+            "int f(&mut Base b) {\n    let d = (&mut Derived)b;\n    return d.extra;\n}\n"
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_cast_between_incompatible_structs() {
+        let cases = [
+            (
+                "int f(Base* b) {\n    Other* o = (Other*)b;\n    return 0;\n}\n",
+                "cannot cast 'Base' pointer to 'Other' pointer: field 'kind' of 'Other' does not match field 'kind' of 'Base'",
+            ),
+            (
+                "int f(Base* b) {\n    Loose* l = (Loose*)b;\n    return 0;\n}\n",
+                "cannot cast 'Base' pointer to 'Loose' pointer: 'Loose' must be #[repr(C)] for its layout to be relied on",
+            ),
+            (
+                "int f(&Base b) {\n    let d = (&mut Derived)b;\n    return 0;\n}\n",
+                "cannot cast 'Base' pointer to 'Derived' pointer: an immutable reference cannot be cast to a mutable one",
+            ),
+        ];
+        for (body, message) in cases {
+            let errors = analyze_cast(body).unwrap_err();
+            assert_eq!(errors[0].kind, SemanticErrorKind::InvalidOperation);
+            assert_eq!(errors[0].message, message);
+        }
+    }
+
     #[test]
     fn test_field_access_on_reference() {
         let mut analyzer = SemanticAnalyzer::new();
@@ -177,6 +251,7 @@ mod tests {
         let point_struct = Struct {
             visibility: Visibility::Public,
             name: Ident::new("Point"),
+            generics: vec![],
             fields: vec![Field {
                 visibility: Visibility::Public,
                 name: Ident::new("x"),
@@ -192,6 +267,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![
@@ -230,6 +306,7 @@ mod tests {
         let point_struct = Struct {
             visibility: Visibility::Public,
             name: Ident::new("Point"),
+            generics: vec![],
             fields: vec![Field {
                 visibility: Visibility::Public,
                 name: Ident::new("x"),
@@ -245,6 +322,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![
@@ -283,6 +361,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::Let {
@@ -311,6 +390,7 @@ mod tests {
         let func = Function {
             visibility: Visibility::Public,
             name: Ident::new("test"),
+            generics: vec![],
             params: vec![],
             return_type: None,
             body: Block::new(vec![Statement::Let {
@@ -331,4 +411,127 @@ mod tests {
         let result = analyzer.analyze(&file);
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_integer_literals_adopt_sized_types() {
+        use crate::parser::Parser;
+
+        let valid = "u8 low(u8 b) {\n    return b & 15;\n}\nvoid main() {\n    u8 b = 255;\n    i8 s = -128;\n    u16 w = 65535;\n    i16 h = -1;\n    usize n = 0;\n    isize d = -4;\n    u8 l = low(b + 1);\n}\n";
+        let file = Parser::new(valid).unwrap().parse_file().unwrap();
+        assert!(SemanticAnalyzer::new().analyze(&file).is_ok());
+
+        let invalid = "void main() {\n    u8 b = 256;\n    u16 w = -1;\n    i8 s = 128;\n}\n";
+        let file = Parser::new(invalid).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+    }
+
+    #[test]
+    fn test_suffixed_literals_keep_their_type() {
+        use crate::parser::Parser;
+
+        let valid = "void main() {\n    i64 big = 5_000_000_000i64;\n    i8 low = -128i8;\n    f32 f = 3.5f32;\n    u8 b = 255u8;\n}\n";
+        let file = Parser::new(valid).unwrap().parse_file().unwrap();
+        assert!(SemanticAnalyzer::new().analyze(&file).is_ok());
+
+        let invalid =
+            "void main() {\n    u8 b = 5i32;\n    let c = 300u8;\n    let d = -1u32;\n}\n";
+        let file = Parser::new(invalid).unwrap().parse_file().unwrap();
+        let messages: Vec<String> = SemanticAnalyzer::new()
+            .analyze(&file)
+            .unwrap_err()
+            .into_iter()
+            .map(|error| error.message)
+            .collect();
+        assert_eq!(messages.len(), 3, "{:?}", messages);
+        assert!(messages[0].contains("type mismatch"), "{:?}", messages);
+        assert_eq!(messages[1], "integer literal 300 is out of range for u8");
+        assert_eq!(messages[2], "integer literal -1 is out of range for u32");
+    }
+
+    #[test]
+    fn test_mixed_numeric_types_note_c_conversions() {
+        use crate::parser::Parser;
+        use crate::platform::Platform;
+
+        let source = "void main() {\n    var int x = 1;\n    u32 y = 2;\n    i64 big = 3;\n    long l = 4;\n    u8 b = 5;\n    float f = 1.5;\n    bool below = x < y;\n    bool equal = y == big;\n    let sum = y + l;\n    let scaled = x * f;\n    x = y;\n    u8 small = x;\n    bool flag = true;\n    bool mixed = flag == x;\n}\n";
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        let [_, lp64, _] = Platform::data_models();
+        analyzer.set_platform(lp64);
+        let notes: Vec<Vec<String>> = analyzer
+            .analyze(&file)
+            .unwrap_err()
+            .into_iter()
+            .map(|error| error.notes)
+            .collect();
+        assert_eq!(
+            notes,
+            [
+                vec!["C would convert int and u32 operands to u32 implicitly; Crusty needs the cast: `(u32)x < y`".to_string()],
+                vec!["C would convert u32 and i64 operands to i64 implicitly; Crusty needs the cast: `(i64)y == big`".to_string()],
+                vec!["C would convert u32 and long (i64 on this target) operands to long implicitly; Crusty needs the cast: `(long)y + l`".to_string()],
+                vec!["C would convert int and float operands to float implicitly; Crusty needs the cast: `(float)x * f`".to_string()],
+                vec!["C would convert the u32 value to int implicitly; Crusty needs the cast: `x = (int)y`".to_string()],
+                vec!["C would convert the int value to u8 implicitly; Crusty needs the cast: `(u8)x`".to_string()],
+                // bool is not converted by the usual arithmetic conversions
+                vec![],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_out_of_range_literals_are_not_given_casts() {
+        use crate::parser::Parser;
+
+        let source = "void main() {\n    u8 fits = 200;\n    var u8 a = 300;\n    a = 256;\n    i8 s = -200;\n    int x = 5;\n    u8 b = x;\n}\n";
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+        let errors: Vec<(String, Vec<String>)> = SemanticAnalyzer::new()
+            .analyze(&file)
+            .unwrap_err()
+            .into_iter()
+            .map(|error| (error.message, error.notes))
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                ("integer literal 300 is out of range for u8".to_string(), vec![]),
+                ("integer literal 256 is out of range for u8".to_string(), vec![]),
+                ("integer literal -200 is out of range for i8".to_string(), vec![]),
+                (
+                    "variable 'b' type mismatch: expected Primitive(U8), found Primitive(Int)"
+                        .to_string(),
+                    vec!["C would convert the int value to u8 implicitly; Crusty needs the cast: `(u8)x`".to_string()],
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_widening_conversions_are_implicit() {
+        use crate::parser::Parser;
+
+        let source = "long widen(u8 b, int i) {\n    var long total = b;\n    total = i;\n    float mean = i;\n    f32 scale = i;\n    f64 precise = scale;\n    return b;\n}\n";
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        assert!(analyzer.analyze(&file).is_ok());
+        let warnings: Vec<String> = analyzer
+            .warnings()
+            .iter()
+            .filter(|warning| warning.lint.name == "lossy_conversions")
+            .map(|warning| warning.message.clone())
+            .collect();
+        assert_eq!(
+            warnings,
+            ["implicit conversion from int to f32 may lose precision; cast to keep it: `(f32)i`"]
+        );
+
+        let narrowing = "void main() {\n    long big = 5;\n    int small = big;\n    u32 count = small;\n    f32 single = 1.5f32;\n    i64 whole = single;\n}\n";
+        let file = Parser::new(narrowing).unwrap().parse_file().unwrap();
+        let errors = SemanticAnalyzer::new().analyze(&file).unwrap_err();
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+        assert!(errors
+            .iter()
+            .all(|error| error.message.contains("type mismatch")));
+    }
 }
@@ -0,0 +1,180 @@
+// Copyright (c) 2026 Mark Ferrell
+// Licensed under the MIT License. See LICENSE.txt in the project root.
+
+//! Per-function size report for generated code.
+//!
+//! Lowering can turn a few lines of Crusty into many lines of Rust: volatile
+//! accesses, tail-call loops, heap-allocated locals and macro-heavy bodies
+//! all grow. The report pairs each function's source line count with the
+//! number of lines generated for it and flags the functions whose expansion
+//! ratio exceeds a budget, so hot-spots can be found before the generated
+//! code becomes hard to read.
+
+use crate::ast::{File, Item};
+use crate::codegen::GeneratedItem;
+use crate::utils::locate_item_extent;
+
+/// Default expansion budget: generated lines per source line (the value of a
+/// bare `--report-size`)
+#[allow(dead_code)]
+pub const DEFAULT_EXPANSION_BUDGET: f64 = 3.0;
+
+/// Functions must grow by at least this many lines to be flagged, so one-line
+/// functions that become a three-line Rust `fn` are not reported
+const MIN_FLAGGED_GROWTH: usize = 10;
+
+/// Source and generated size of one function
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionSize {
+    pub name: String,
+    pub source_lines: usize,
+    pub generated_lines: usize,
+}
+
+impl FunctionSize {
+    /// Generated lines per source line
+    pub fn ratio(&self) -> f64 {
+        self.generated_lines as f64 / self.source_lines.max(1) as f64
+    }
+
+    /// Whether the function grew beyond `budget` times its source size
+    pub fn exceeds(&self, budget: f64) -> bool {
+        self.ratio() > budget && self.generated_lines >= self.source_lines + MIN_FLAGGED_GROWTH
+    }
+}
+
+/// Measure every top-level function of `file` that appears in `items`
+///
+/// Functions are reported in source order; ones that cannot be found in the
+/// source or the output are skipped.
+pub fn measure(file: &File, source: &str, items: &[GeneratedItem]) -> Vec<FunctionSize> {
+    file.items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Function(func) => Some(&func.name.name),
+            _ => None,
+        })
+        .filter_map(|name| {
+            let generated = items.iter().find(|item| &item.name == name)?;
+            let span = locate_item_extent(source, name)?;
+            Some(FunctionSize {
+                name: name.clone(),
+                source_lines: span.end.line + 1 - span.start.line,
+                generated_lines: generated.line_count(),
+            })
+        })
+        .collect()
+}
+
+/// Format the report as a table, marking functions over `budget`
+pub fn format_report(sizes: &[FunctionSize], budget: f64) -> String {
+    let width = sizes
+        .iter()
+        .map(|size| size.name.len())
+        .chain(std::iter::once("function".len()))
+        .max()
+        .unwrap_or_default();
+
+    let mut report = format!(
+        "{:<width$}  {:>6}  {:>6}  {:>6}\n",
+        "function", "crusty", "rust", "ratio"
+    );
+    for size in sizes {
+        report.push_str(&format!(
+            "{:<width$}  {:>6}  {:>6}  {:>5.1}x",
+            size.name,
+            size.source_lines,
+            size.generated_lines,
+            size.ratio()
+        ));
+        if size.exceeds(budget) {
+            report.push_str("  <- expansion hot-spot");
+        }
+        report.push('\n');
+    }
+
+    let flagged = sizes.iter().filter(|size| size.exceeds(budget)).count();
+    let source: usize = sizes.iter().map(|size| size.source_lines).sum();
+    let generated: usize = sizes.iter().map(|size| size.generated_lines).sum();
+    report.push_str(&format!(
+        "{} function(s), {} -> {} lines; {} over the {:.1}x budget\n",
+        sizes.len(),
+        source,
+        generated,
+        flagged,
+        budget
+    ));
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::{CodeGenerator, TargetLanguage};
+    use crate::parser::Parser;
+
+    const SOURCE: &str = "\
+int add(int a, int b) {
+    return a + b;
+}
+
+void poll(volatile int* status) {
+    unsafe {
+        *status = *status | 1;
+    }
+}
+";
+
+    #[test]
+    fn test_measure_pairs_source_and_generated_lines() {
+        let file = Parser::new(SOURCE).unwrap().parse_file().unwrap();
+        let mut generator = CodeGenerator::new(TargetLanguage::Rust);
+        generator.generate(&file);
+
+        let sizes = measure(&file, SOURCE, generator.generated_items());
+        assert_eq!(
+            sizes,
+            vec![
+                FunctionSize {
+                    name: "add".to_string(),
+                    source_lines: 3,
+                    generated_lines: 3,
+                },
+                FunctionSize {
+                    name: "poll".to_string(),
+                    source_lines: 5,
+                    generated_lines: 5,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_report_flags_functions_over_budget() {
+        let sizes = vec![
+            FunctionSize {
+                name: "small".to_string(),
+                source_lines: 1,
+                generated_lines: 4,
+            },
+            FunctionSize {
+                name: "exploded".to_string(),
+                source_lines: 5,
+                generated_lines: 40,
+            },
+        ];
+        let report = format_report(&sizes, DEFAULT_EXPANSION_BUDGET);
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[0], "function  crusty    rust   ratio");
+        assert_eq!(lines[1], "small          1       4    4.0x");
+        assert_eq!(
+            lines[2],
+            "exploded       5      40    8.0x  <- expansion hot-spot"
+        );
+        assert_eq!(
+            lines[3],
+            "2 function(s), 6 -> 44 lines; 1 over the 3.0x budget"
+        );
+        assert!(!format_report(&sizes, 10.0).contains("hot-spot"));
+    }
+}
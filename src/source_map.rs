@@ -0,0 +1,170 @@
+// Copyright (c) 2026 Mark Ferrell
+// Licensed under the MIT License. See LICENSE.txt in the project root.
+
+//! Source maps from generated Rust back to the Crusty it was generated from.
+//!
+//! Each mapping pairs a line of the generated file with the Crusty file,
+//! line and column of the item or statement that starts on it. A line
+//! without a mapping of its own belongs to the closest mapped line above
+//! it, so the lines of a statement that grew into several lines of Rust all
+//! map to the start of that statement. With `--emit-sourcemap` or `-g` the
+//! map is written next to the generated file as JSON:
+//!
+//! ```text
+//! {
+//!   "version": 1,
+//!   "file": "main.rs",
+//!   "sources": ["main.crst", "shapes.crst"],
+//!   "mappings": [
+//!     { "generated_line": 3, "source": 0, "line": 1, "column": 5 },
+//!     { "generated_line": 4, "source": 0, "line": 2, "column": 5 }
+//!   ]
+//! }
+//! ```
+//!
+//! The format is stable: lines and columns are 1-based, and
+//! `SOURCE_MAP_VERSION` changes only when a field is removed or changes
+//! meaning, so debugger scripts and editors can rely on it.
+
+use crate::codegen::{GeneratedItem, GeneratedStatement};
+use crate::error::Position;
+use crate::utils::locate_item;
+use serde::{Deserialize, Serialize};
+
+/// Version of the JSON source map format
+pub const SOURCE_MAP_VERSION: u32 = 1;
+
+/// A generated line and the Crusty location it came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Mapping {
+    pub generated_line: usize,
+    /// Index of the Crusty file in `sources`
+    pub source: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Mappings from one generated Rust file to its Crusty sources
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceMap {
+    pub version: u32,
+    /// The generated Rust file
+    pub file: String,
+    /// The Crusty files mapped to, the root file first
+    pub sources: Vec<String>,
+    /// Mappings in generated line order
+    pub mappings: Vec<Mapping>,
+}
+
+impl SourceMap {
+    /// An empty map for the generated file `file`
+    pub fn new(file: impl Into<String>) -> Self {
+        Self {
+            version: SOURCE_MAP_VERSION,
+            file: file.into(),
+            sources: Vec::new(),
+            mappings: Vec::new(),
+        }
+    }
+
+    /// Map the items and statements one generator produced from the Crusty
+    /// file `path`, whose text is `source`; `offset` lines of the generated
+    /// file precede the generator's output
+    pub fn add_source(
+        &mut self,
+        path: &str,
+        source: &str,
+        offset: usize,
+        items: &[GeneratedItem],
+        statements: &[GeneratedStatement],
+    ) {
+        let index = self.sources.len();
+        self.sources.push(path.to_string());
+
+        let items = items.iter().filter_map(|item| {
+            let span = locate_item(source, &item.name)?;
+            Some((item.line, span.start))
+        });
+        let statements = statements
+            .iter()
+            .map(|statement| (statement.line, statement.source));
+        for (line, position) in items.chain(statements) {
+            self.mappings.push(Mapping {
+                generated_line: offset + line,
+                source: index,
+                line: position.line,
+                column: position.column,
+            });
+        }
+        // An item and its first statement may start on the same line, as in
+        // `fn f() { g(); }`; the statement is the closer match
+        self.mappings.sort_by_key(|mapping| mapping.generated_line);
+    }
+
+    /// The Crusty file and position `generated_line` was generated from
+    pub fn lookup(&self, generated_line: usize) -> Option<(&str, Position)> {
+        let mapping = self
+            .mappings
+            .iter()
+            .rev()
+            .find(|mapping| mapping.generated_line <= generated_line)?;
+        Some((
+            &self.sources[mapping.source],
+            Position::new(mapping.line, mapping.column),
+        ))
+    }
+
+    /// The map as pretty-printed JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::{CodeGenerator, TargetLanguage};
+
+    const SOURCE: &str = "int twice(int n) {\n    let m = n * 2;\n    return m;\n}\n";
+
+    fn source_map() -> SourceMap {
+        let file = crate::parser::Parser::new(SOURCE)
+            .unwrap()
+            .parse_file()
+            .unwrap();
+        let mut generator = CodeGenerator::new(TargetLanguage::Rust);
+        generator.set_header(vec!["Generated".to_string()]);
+        let rust = generator.generate(&file);
+        assert_eq!(
+            rust,
+            "// Generated\n\npub fn twice(n: i32) -> i32 {\n    let m = (n * 2);\n    return m;\n}\n"
+        );
+
+        let mut map = SourceMap::new("twice.rs");
+        map.add_source(
+            "twice.crst",
+            SOURCE,
+            0,
+            generator.generated_items(),
+            generator.generated_statements(),
+        );
+        map
+    }
+
+    #[test]
+    fn test_maps_items_and_statements() {
+        let map = source_map();
+        let lines: Vec<(usize, usize, usize)> = map
+            .mappings
+            .iter()
+            .map(|m| (m.generated_line, m.line, m.column))
+            .collect();
+        assert_eq!(lines, vec![(3, 1, 5), (4, 2, 5), (5, 3, 5)]);
+
+        assert_eq!(map.lookup(2), None);
+        assert_eq!(map.lookup(6), Some(("twice.crst", Position::new(3, 5))));
+
+        let json: SourceMap = serde_json::from_str(&map.to_json()).unwrap();
+        assert_eq!(json, map);
+    }
+}
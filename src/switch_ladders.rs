@@ -0,0 +1,481 @@
+// Copyright (c) 2026 Mark Ferrell
+// Licensed under the MIT License. See LICENSE.txt in the project root.
+
+//! Detection of if/else ladders that test one value against constants.
+//!
+//! Code translated from C, or from languages without pattern matching, often
+//! dispatches on an enum or integer with a chain of comparisons. A switch
+//! says the same thing directly and becomes a `match` in Rust, where the
+//! compiler checks enum coverage:
+//!
+//! ```text
+//! if (c == Color.Red) {               switch (c) {
+//!     r = 1;                              case Color.Red: { r = 1; }
+//! } else if (c == Color.Green         case Color.Green:
+//!            || c == Color.Blue) {        case Color.Blue: { r = 2; }
+//!     r = 2;                              default: { r = 0; }
+//! } else {                            }
+//!     r = 0;
+//! }
+//! ```
+//!
+//! A chain qualifies when at least [`MIN_ARMS`] leading conditions compare
+//! the same variable or field path against integer, character or enum
+//! variant constants with `==`, alone or joined by `||`. The first condition
+//! that does not keeps the rest of the chain, unchanged, as the default.
+//! Chains that repeat a value, or whose bodies `break` out of an enclosing
+//! loop (which a switch case would capture), are left alone.
+
+use crate::ast::{
+    BinaryOp, Block, Expression, File, Function, Item, Literal, Statement, SwitchCase, UnaryOp,
+};
+use crate::tailcall::{child_blocks, child_blocks_mut};
+
+/// Fewest compared arms worth turning into a switch
+pub const MIN_ARMS: usize = 3;
+
+/// What to do with if/else ladders that could be switches
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum SwitchLadders {
+    /// Leave them alone
+    #[default]
+    Off,
+    /// Warn about each one, suggesting a switch
+    Warn,
+    /// Rewrite each one into a switch
+    Rewrite,
+}
+
+/// An if/else ladder that tests one value against constants
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SwitchLadder {
+    /// Function the ladder is in
+    pub function: String,
+    /// The variable or field tested, as written
+    pub subject: String,
+    /// Number of constants it is compared against
+    pub values: usize,
+}
+
+/// Find the switch ladders in every function and method of `file`,
+/// rewriting them into switches when `mode` is [`SwitchLadders::Rewrite`]
+pub fn convert_switch_ladders(file: &mut File, mode: SwitchLadders) -> Vec<SwitchLadder> {
+    if mode == SwitchLadders::Off {
+        return Vec::new();
+    }
+    let mut pass = LadderPass {
+        enums: Vec::new(),
+        rewrite: mode == SwitchLadders::Rewrite,
+        function: String::new(),
+        found: Vec::new(),
+    };
+    pass.collect_enums(&file.items);
+    pass.items(&mut file.items);
+    pass.found
+}
+
+struct LadderPass {
+    /// Unit variants of each enum in the file: (enum, variants)
+    enums: Vec<(String, Vec<String>)>,
+    rewrite: bool,
+    /// Function being walked
+    function: String,
+    found: Vec<SwitchLadder>,
+}
+
+/// The arms of a ladder before it becomes a switch
+struct Ladder {
+    subject: Expression,
+    cases: Vec<SwitchCase>,
+    default: Option<Block>,
+}
+
+impl LadderPass {
+    fn collect_enums(&mut self, items: &[Item]) {
+        for item in items {
+            match item {
+                Item::Enum(e) => {
+                    let variants = e
+                        .variants
+                        .iter()
+                        .filter(|variant| variant.fields.is_empty())
+                        .map(|variant| variant.name.name.clone())
+                        .collect();
+                    self.enums.push((e.name.name.clone(), variants));
+                }
+                Item::Namespace(namespace) => self.collect_enums(&namespace.items),
+                _ => {}
+            }
+        }
+    }
+
+    fn items(&mut self, items: &mut [Item]) {
+        for item in items {
+            match item {
+                Item::Function(func) => self.function(func),
+                Item::Struct(s) => s.methods.iter_mut().for_each(|m| self.function(m)),
+                Item::Impl(i) => i.methods.iter_mut().for_each(|m| self.function(m)),
+                Item::Trait(t) => {
+                    for method in &mut t.methods {
+                        if let Some(body) = &mut method.body {
+                            self.function = method.name.name.clone();
+                            self.block(body);
+                        }
+                    }
+                }
+                Item::Namespace(namespace) => self.items(&mut namespace.items),
+                _ => {}
+            }
+        }
+    }
+
+    fn function(&mut self, func: &mut Function) {
+        self.function = func.name.name.clone();
+        self.block(&mut func.body);
+    }
+
+    fn block(&mut self, block: &mut Block) {
+        for stmt in &mut block.statements {
+            self.statement(stmt);
+        }
+    }
+
+    fn statement(&mut self, stmt: &mut Statement) {
+        if let Some(ladder) = self.ladder(stmt) {
+            self.found.push(SwitchLadder {
+                function: self.function.clone(),
+                subject: path_text(&ladder.subject),
+                values: ladder.cases.iter().map(|case| case.values.len()).sum(),
+            });
+            let mut switch = Statement::Switch {
+                expr: ladder.subject,
+                cases: ladder.cases,
+                default: ladder.default,
+            };
+            // Without rewriting, the switch is only walked for nested ladders
+            let target = if self.rewrite {
+                *stmt = switch;
+                stmt
+            } else {
+                &mut switch
+            };
+            for child in child_blocks_mut(target) {
+                self.block(child);
+            }
+            return;
+        }
+
+        if let Statement::NestedFunction { name, body, .. } = stmt {
+            let outer = std::mem::replace(&mut self.function, name.name.clone());
+            self.block(body);
+            self.function = outer;
+            return;
+        }
+        for child in child_blocks_mut(stmt) {
+            self.block(child);
+        }
+    }
+
+    /// The switch `stmt` amounts to, when it is a qualifying if/else ladder
+    fn ladder(&self, stmt: &Statement) -> Option<Ladder> {
+        let mut subject: Option<&Expression> = None;
+        let mut cases = Vec::new();
+        let mut current = stmt;
+        let default = loop {
+            let Statement::If {
+                condition,
+                then_block,
+                else_block,
+            } = current
+            else {
+                break Some(Block::new(vec![current.clone()]));
+            };
+            let Some((tested, values)) = self.case_values(condition) else {
+                break Some(Block::new(vec![current.clone()]));
+            };
+            if subject.is_some_and(|subject| subject != tested) {
+                break Some(Block::new(vec![current.clone()]));
+            }
+            subject = Some(tested);
+            cases.push(SwitchCase {
+                values: values.into_iter().cloned().collect(),
+                body: then_block.clone(),
+            });
+            match else_block {
+                // `else if` parses as an else block holding just the if
+                Some(block) if matches!(block.statements[..], [Statement::If { .. }]) => {
+                    current = &block.statements[0]
+                }
+                Some(block) => break Some(block.clone()),
+                None => break None,
+            }
+        };
+
+        if cases.len() < MIN_ARMS {
+            return None;
+        }
+        let values: Vec<&Expression> = cases.iter().flat_map(|case| &case.values).collect();
+        if values
+            .iter()
+            .enumerate()
+            .any(|(i, value)| values[..i].contains(value))
+        {
+            return None;
+        }
+        if cases
+            .iter()
+            .map(|case| &case.body)
+            .chain(&default)
+            .any(breaks_out)
+        {
+            return None;
+        }
+
+        // A switch over an integer needs a default to be exhaustive; one
+        // over an enum does too unless every variant has a case
+        let default =
+            default.or_else(|| (!self.covers_enum(&values)).then(|| Block::new(Vec::new())));
+        Some(Ladder {
+            subject: subject?.clone(),
+            cases,
+            default,
+        })
+    }
+
+    /// The tested path and the constants of `condition` when it is
+    /// `x == A`, `A == x`, or several of those joined by `||`
+    fn case_values<'a>(
+        &self,
+        condition: &'a Expression,
+    ) -> Option<(&'a Expression, Vec<&'a Expression>)> {
+        let Expression::Binary { op, left, right } = condition else {
+            return None;
+        };
+        match op {
+            BinaryOp::Or => {
+                let (subject, mut values) = self.case_values(left)?;
+                let (other, more) = self.case_values(right)?;
+                (subject == other).then(|| {
+                    values.extend(more);
+                    (subject, values)
+                })
+            }
+            BinaryOp::Eq if self.is_subject(left) && self.is_constant(right) => {
+                Some((left.as_ref(), vec![right.as_ref()]))
+            }
+            BinaryOp::Eq if self.is_subject(right) && self.is_constant(left) => {
+                Some((right.as_ref(), vec![left.as_ref()]))
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether `expr` is a variable or field path other than an enum variant
+    fn is_subject(&self, expr: &Expression) -> bool {
+        match expr {
+            Expression::Ident(_) => true,
+            Expression::FieldAccess { expr: base, .. } => {
+                self.is_subject(base) && self.variant(expr).is_none()
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether `expr` is an integer, character or enum variant constant
+    fn is_constant(&self, expr: &Expression) -> bool {
+        match expr {
+            Expression::Literal(Literal::Char(_)) => true,
+            Expression::Literal(literal) => literal.int_value().is_some(),
+            Expression::Unary {
+                op: UnaryOp::Neg,
+                expr,
+            } => {
+                matches!(expr.as_ref(), Expression::Literal(literal) if literal.int_value().is_some())
+            }
+            _ => self.variant(expr).is_some(),
+        }
+    }
+
+    /// The enum and variant `expr` names, as in `Color.Red`
+    fn variant(&self, expr: &Expression) -> Option<(&str, &str)> {
+        let Expression::FieldAccess { expr, field } = expr else {
+            return None;
+        };
+        let Expression::Ident(name) = expr.as_ref() else {
+            return None;
+        };
+        let (enum_name, variants) = self.enums.iter().find(|(e, _)| *e == name.name)?;
+        variants
+            .iter()
+            .find(|variant| **variant == field.name)
+            .map(|variant| (enum_name.as_str(), variant.as_str()))
+    }
+
+    /// Whether `values` name every variant of one enum
+    fn covers_enum(&self, values: &[&Expression]) -> bool {
+        let Some((enum_name, _)) = values.first().and_then(|value| self.variant(value)) else {
+            return false;
+        };
+        let Some((_, variants)) = self.enums.iter().find(|(e, _)| e == enum_name) else {
+            return false;
+        };
+        variants.iter().all(|variant| {
+            values
+                .iter()
+                .any(|value| self.variant(value) == Some((enum_name, variant.as_str())))
+        })
+    }
+}
+
+/// Whether `block` has a `break` that would leave an enclosing loop
+fn breaks_out(block: &Block) -> bool {
+    block.statements.iter().any(|stmt| match stmt {
+        Statement::Break(None) => true,
+        Statement::While { .. }
+        | Statement::DoWhile { .. }
+        | Statement::For { .. }
+        | Statement::ForIn { .. } => false,
+        _ => child_blocks(stmt).into_iter().any(breaks_out),
+    })
+}
+
+/// A variable or field path as written in Crusty
+fn path_text(expr: &Expression) -> String {
+    match expr {
+        Expression::Ident(ident) => ident.name.clone(),
+        Expression::FieldAccess { expr, field } => format!("{}.{}", path_text(expr), field.name),
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::{CodeGenerator, TargetLanguage};
+    use crate::parser::Parser;
+
+    fn convert(source: &str, mode: SwitchLadders) -> (File, Vec<SwitchLadder>) {
+        let mut file = Parser::new(source).unwrap().parse_file().unwrap();
+        let found = convert_switch_ladders(&mut file, mode);
+        (file, found)
+    }
+
+    fn rust(file: &File) -> String {
+        CodeGenerator::new(TargetLanguage::Rust).generate(file)
+    }
+
+    const COLORS: &str = "\
+enum Color { Red, Green, Blue }
+
+int code(Color c) {
+    int r = 0;
+    if (c == Color.Red) {
+        r = 1;
+    } else if (c == Color.Green || Color.Blue == c) {
+        r = 2;
+    } else if (c == Color.Blue) {
+        r = 3;
+    }
+    return r;
+}
+
+int digit(int n) {
+    if (n == 0) { return 10; }
+    else if (n == 1) { return 11; }
+    else if (n == -1 || n == 2) { return 12; }
+    else { return 0; }
+}
+";
+
+    #[test]
+    fn test_rewrites_integer_ladder() {
+        let (file, found) = convert(COLORS, SwitchLadders::Rewrite);
+        assert_eq!(
+            found,
+            vec![SwitchLadder {
+                function: "digit".to_string(),
+                subject: "n".to_string(),
+                values: 4,
+            }]
+        );
+        let code = rust(&file);
+        assert!(code.contains("match n {"), "{}", code);
+        assert!(code.contains("0 => {"), "{}", code);
+        assert!(code.contains("-1 | 2 => {"), "{}", code);
+        assert!(code.contains("_ => {"), "{}", code);
+    }
+
+    #[test]
+    fn test_duplicate_values_are_left_alone() {
+        // Color.Blue is tested twice, so `code` is not converted
+        let (file, found) = convert(COLORS, SwitchLadders::Rewrite);
+        assert!(found.iter().all(|ladder| ladder.function != "code"));
+        assert!(rust(&file).contains("if (c == Color::Red)"));
+    }
+
+    #[test]
+    fn test_enum_ladder_covering_every_variant_has_no_default() {
+        let source = "\
+enum Color { Red, Green, Blue }
+
+int code(Color c) {
+    if (c == Color.Red) { return 1; }
+    else if (c == Color.Green) { return 2; }
+    else if (c == Color.Blue) { return 3; }
+    return 0;
+}
+";
+        let (file, found) = convert(source, SwitchLadders::Rewrite);
+        assert_eq!(found.len(), 1);
+        let Item::Function(func) = &file.items[1] else {
+            panic!("expected function");
+        };
+        let Statement::Switch { cases, default, .. } = &func.body.statements[0] else {
+            panic!("expected switch");
+        };
+        assert_eq!(cases.len(), 3);
+        assert!(default.is_none());
+        assert!(rust(&file).contains("Color::Blue => {"));
+    }
+
+    #[test]
+    fn test_warn_mode_leaves_the_ladder() {
+        let (file, found) = convert(COLORS, SwitchLadders::Warn);
+        assert_eq!(found.len(), 1);
+        assert_eq!(file, Parser::new(COLORS).unwrap().parse_file().unwrap());
+    }
+
+    #[test]
+    fn test_unrelated_tail_becomes_the_default() {
+        let source = "\
+int classify(int n, int m) {
+    if (n == 1) { return 1; }
+    else if (n == 2) { return 2; }
+    else if (n == 3) { return 3; }
+    else if (m == 4) { return 4; }
+    return 0;
+}
+";
+        let (file, found) = convert(source, SwitchLadders::Rewrite);
+        assert_eq!(found[0].values, 3);
+        let code = rust(&file);
+        assert!(code.contains("_ => {"), "{}", code);
+        assert!(code.contains("if (m == 4)"), "{}", code);
+    }
+
+    #[test]
+    fn test_short_or_breaking_ladders_are_left_alone() {
+        let source = "\
+void run(int n) {
+    if (n == 1) { return; } else if (n == 2) { return; }
+    while (true) {
+        if (n == 1) { break; }
+        else if (n == 2) { n = 3; }
+        else if (n == 3) { n = 1; }
+    }
+}
+";
+        let (_, found) = convert(source, SwitchLadders::Rewrite);
+        assert!(found.is_empty());
+    }
+}
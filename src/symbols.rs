@@ -0,0 +1,511 @@
+// Copyright (c) 2026 Mark Ferrell
+// Licensed under the MIT License. See LICENSE.txt in the project root.
+
+//! Lexically scoped symbol tables.
+//!
+//! Semantic analysis keeps a stack of scopes: the file's items at the
+//! bottom, then a scope for each function body, block, loop and match arm
+//! being analyzed. A name resolves to its innermost binding, so a local may
+//! shadow an item or a local of an enclosing block, but binding a name twice
+//! in one scope is a duplicate definition:
+//!
+//! ```text
+//! void f(int x) {
+//!     if (x > 0) {
+//!         let int x = 2;  // shadows the parameter
+//!     }
+//!     let int x = 3;      // error: Symbol 'x' already defined in this scope
+//! }
+//! ```
+//!
+//! Loop labels are scoped the same way, except that a function body is a
+//! boundary: `break outer;` in a nested function cannot leave a loop of the
+//! function around it.
+
+use crate::ast::Type;
+
+use std::collections::{BTreeMap, HashMap};
+
+/// Symbol kind classification
+#[derive(Debug, Clone, PartialEq)]
+pub enum SymbolKind {
+    Variable,
+    Function,
+    Type,
+    Const,
+}
+
+/// How a variable was bound, which decides what assigning it may do
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Binding {
+    /// A loop variable, pattern binding or item
+    #[default]
+    Other,
+    /// A `let` or `var` with an initializer
+    Initialized,
+    /// A `let` or `var` declared without a value, assigned later
+    Deferred,
+    /// A function parameter
+    Parameter,
+}
+
+/// Symbol information stored in the symbol table
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub ty: Type,
+    pub kind: SymbolKind,
+    pub mutable: bool,
+    pub binding: Binding,
+}
+
+impl Symbol {
+    pub fn new(name: String, ty: Type, kind: SymbolKind, mutable: bool) -> Self {
+        Self {
+            name,
+            ty,
+            kind,
+            mutable,
+            binding: Binding::Other,
+        }
+    }
+
+    pub fn with_binding(mut self, binding: Binding) -> Self {
+        self.binding = binding;
+        self
+    }
+}
+
+/// A single scope containing symbols
+#[derive(Debug, Clone)]
+pub struct Scope {
+    symbols: HashMap<String, Symbol>,
+    /// Label of the loop whose body this scope is
+    label: Option<String>,
+    /// Whether this is the outermost scope of a function body, which loop
+    /// labels outside it do not reach
+    function: bool,
+}
+
+impl Default for Scope {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scope {
+    pub fn new() -> Self {
+        Self {
+            symbols: HashMap::new(),
+            label: None,
+            function: false,
+        }
+    }
+
+    pub fn insert(&mut self, name: String, symbol: Symbol) -> Result<(), String> {
+        if self.symbols.contains_key(&name) {
+            return Err(format!("Symbol '{}' already defined in this scope", name));
+        }
+        self.symbols.insert(name, symbol);
+        Ok(())
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.get(name)
+    }
+}
+
+/// Symbol table with scope management
+#[derive(Debug, Clone)]
+pub struct SymbolTable {
+    scopes: Vec<Scope>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self {
+            scopes: vec![Scope::new()],
+        }
+    }
+
+    /// Number of scopes in effect, the file's included
+    pub fn depth(&self) -> usize {
+        self.scopes.len()
+    }
+
+    /// Enter a new scope
+    pub fn enter_scope(&mut self) {
+        self.scopes.push(Scope::new());
+    }
+
+    /// Enter the outermost scope of a function body
+    pub fn enter_function_scope(&mut self) {
+        self.scopes.push(Scope {
+            function: true,
+            ..Scope::new()
+        });
+    }
+
+    /// Exit the current scope
+    pub fn exit_scope(&mut self) {
+        if self.depth() > 1 {
+            self.scopes.pop();
+        }
+    }
+
+    /// Insert a symbol into the current scope
+    pub fn insert(&mut self, name: String, symbol: Symbol) -> Result<(), String> {
+        if let Some(current_scope) = self.scopes.last_mut() {
+            current_scope.insert(name, symbol)
+        } else {
+            Err("No active scope".to_string())
+        }
+    }
+
+    /// Lookup a symbol in all scopes (from innermost to outermost)
+    pub fn lookup(&self, name: &str) -> Option<&Symbol> {
+        for scope in self.scopes.iter().rev() {
+            if let Some(symbol) = scope.lookup(name) {
+                return Some(symbol);
+            }
+        }
+        None
+    }
+
+    /// Bind `name` in the current scope, replacing any binding it already has
+    /// there
+    pub fn shadow(&mut self, name: String, symbol: Symbol) {
+        if let Some(current_scope) = self.scopes.last_mut() {
+            current_scope.symbols.insert(name, symbol);
+        }
+    }
+
+    /// Lookup a symbol of the outermost (file) scope, unless an inner scope
+    /// shadows it
+    pub fn lookup_global(&self, name: &str) -> Option<&Symbol> {
+        let (global, inner) = self.scopes.split_first()?;
+        if inner.iter().any(|scope| scope.lookup(name).is_some()) {
+            return None;
+        }
+        global.lookup(name)
+    }
+
+    /// Bind `label` to the loop whose body the current scope is. A label
+    /// already naming an enclosing loop of the function is an error, since
+    /// `break label;` could not tell the two apart
+    pub fn bind_label(&mut self, label: &str) -> Result<(), String> {
+        if self.lookup_label(label) {
+            return Err(format!(
+                "label '{}' is already used by an enclosing loop",
+                label
+            ));
+        }
+        if let Some(current_scope) = self.scopes.last_mut() {
+            current_scope.label = Some(label.to_string());
+        }
+        Ok(())
+    }
+
+    /// Whether `label` names a loop enclosing the current scope in the same
+    /// function
+    pub fn lookup_label(&self, label: &str) -> bool {
+        for scope in self.scopes.iter().rev() {
+            if scope.label.as_deref() == Some(label) {
+                return true;
+            }
+            if scope.function {
+                break;
+            }
+        }
+        false
+    }
+
+    /// The symbols visible from the current scope: the innermost binding of
+    /// each name, ordered by name
+    pub fn visible_symbols(&self) -> Vec<&Symbol> {
+        let mut visible = BTreeMap::new();
+        for scope in &self.scopes {
+            visible.extend(&scope.symbols);
+        }
+        visible.into_values().collect()
+    }
+
+    /// Lookup a symbol only in the current scope
+    #[allow(dead_code)]
+    pub fn lookup_in_current_scope(&self, name: &str) -> Option<&Symbol> {
+        self.scopes.last().and_then(|scope| scope.lookup(name))
+    }
+}
+
+impl Default for SymbolTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{Ident, PrimitiveType};
+
+    #[test]
+    fn test_symbol_table_creation() {
+        let table = SymbolTable::new();
+        assert_eq!(table.scopes.len(), 1);
+    }
+
+    #[test]
+    fn test_symbol_table_enter_exit_scope() {
+        let mut table = SymbolTable::new();
+        assert_eq!(table.scopes.len(), 1);
+
+        table.enter_scope();
+        assert_eq!(table.scopes.len(), 2);
+
+        table.enter_scope();
+        assert_eq!(table.scopes.len(), 3);
+
+        table.exit_scope();
+        assert_eq!(table.scopes.len(), 2);
+
+        table.exit_scope();
+        assert_eq!(table.scopes.len(), 1);
+
+        // Should not go below 1 scope
+        table.exit_scope();
+        assert_eq!(table.scopes.len(), 1);
+    }
+
+    #[test]
+    fn test_symbol_table_insert_and_lookup() {
+        let mut table = SymbolTable::new();
+
+        let symbol = Symbol::new(
+            "x".to_string(),
+            Type::Primitive(PrimitiveType::I32),
+            SymbolKind::Variable,
+            false,
+        );
+
+        assert!(table.insert("x".to_string(), symbol.clone()).is_ok());
+
+        let found = table.lookup("x");
+        assert!(found.is_some());
+        assert_eq!(found.unwrap().name, "x");
+        assert_eq!(found.unwrap().kind, SymbolKind::Variable);
+        assert!(!found.unwrap().mutable);
+    }
+
+    #[test]
+    fn test_symbol_table_duplicate_detection() {
+        let mut table = SymbolTable::new();
+
+        let symbol1 = Symbol::new(
+            "x".to_string(),
+            Type::Primitive(PrimitiveType::I32),
+            SymbolKind::Variable,
+            false,
+        );
+
+        let symbol2 = Symbol::new(
+            "x".to_string(),
+            Type::Primitive(PrimitiveType::I64),
+            SymbolKind::Variable,
+            true,
+        );
+
+        assert!(table.insert("x".to_string(), symbol1).is_ok());
+
+        // Should fail because 'x' already exists in current scope
+        let result = table.insert("x".to_string(), symbol2);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("already defined"));
+    }
+
+    #[test]
+    fn test_symbol_table_scope_shadowing() {
+        let mut table = SymbolTable::new();
+
+        // Insert 'x' in outer scope
+        let symbol1 = Symbol::new(
+            "x".to_string(),
+            Type::Primitive(PrimitiveType::I32),
+            SymbolKind::Variable,
+            false,
+        );
+        assert!(table.insert("x".to_string(), symbol1).is_ok());
+
+        // Enter new scope
+        table.enter_scope();
+
+        // Insert 'x' in inner scope (shadowing)
+        let symbol2 = Symbol::new(
+            "x".to_string(),
+            Type::Primitive(PrimitiveType::I64),
+            SymbolKind::Variable,
+            true,
+        );
+        assert!(table.insert("x".to_string(), symbol2).is_ok());
+
+        // Lookup should find inner scope's 'x'
+        let found = table.lookup("x");
+        assert!(found.is_some());
+        assert!(matches!(
+            found.unwrap().ty,
+            Type::Primitive(PrimitiveType::I64)
+        ));
+        assert!(found.unwrap().mutable);
+
+        // Exit inner scope
+        table.exit_scope();
+
+        // Lookup should now find outer scope's 'x'
+        let found = table.lookup("x");
+        assert!(found.is_some());
+        assert!(matches!(
+            found.unwrap().ty,
+            Type::Primitive(PrimitiveType::I32)
+        ));
+        assert!(!found.unwrap().mutable);
+    }
+
+    #[test]
+    fn test_symbol_table_lookup_in_current_scope() {
+        let mut table = SymbolTable::new();
+
+        // Insert 'x' in outer scope
+        let symbol1 = Symbol::new(
+            "x".to_string(),
+            Type::Primitive(PrimitiveType::I32),
+            SymbolKind::Variable,
+            false,
+        );
+        assert!(table.insert("x".to_string(), symbol1).is_ok());
+
+        // Enter new scope
+        table.enter_scope();
+
+        // lookup_in_current_scope should not find 'x' from outer scope
+        assert!(table.lookup_in_current_scope("x").is_none());
+
+        // But regular lookup should find it
+        assert!(table.lookup("x").is_some());
+
+        // Insert 'y' in inner scope
+        let symbol2 = Symbol::new(
+            "y".to_string(),
+            Type::Primitive(PrimitiveType::Bool),
+            SymbolKind::Variable,
+            false,
+        );
+        assert!(table.insert("y".to_string(), symbol2).is_ok());
+
+        // lookup_in_current_scope should find 'y'
+        assert!(table.lookup_in_current_scope("y").is_some());
+    }
+
+    #[test]
+    fn test_symbol_table_multiple_symbols() {
+        let mut table = SymbolTable::new();
+
+        let symbols = vec![
+            (
+                "x",
+                Type::Primitive(PrimitiveType::I32),
+                SymbolKind::Variable,
+            ),
+            (
+                "y",
+                Type::Primitive(PrimitiveType::Bool),
+                SymbolKind::Variable,
+            ),
+            (
+                "foo",
+                Type::Primitive(PrimitiveType::Void),
+                SymbolKind::Function,
+            ),
+            ("Point", Type::Ident(Ident::new("Point")), SymbolKind::Type),
+        ];
+
+        for (name, ty, kind) in symbols {
+            let symbol = Symbol::new(name.to_string(), ty, kind, false);
+            assert!(table.insert(name.to_string(), symbol).is_ok());
+        }
+
+        assert!(table.lookup("x").is_some());
+        assert!(table.lookup("y").is_some());
+        assert!(table.lookup("foo").is_some());
+        assert!(table.lookup("Point").is_some());
+        assert!(table.lookup("nonexistent").is_none());
+    }
+
+    #[test]
+    fn test_symbol_kinds() {
+        let kinds = [
+            SymbolKind::Variable,
+            SymbolKind::Function,
+            SymbolKind::Type,
+            SymbolKind::Const,
+        ];
+
+        assert_eq!(kinds.len(), 4);
+    }
+
+    #[test]
+    fn test_symbol_table_loop_labels() {
+        let mut table = SymbolTable::new();
+        table.enter_function_scope();
+        table.enter_scope();
+        assert!(table.bind_label("outer").is_ok());
+        table.enter_scope();
+        assert!(table.lookup_label("outer"));
+        let err = table.bind_label("outer").unwrap_err();
+        assert_eq!(err, "label 'outer' is already used by an enclosing loop");
+
+        // A nested function cannot leave the loops around it
+        table.enter_function_scope();
+        assert!(!table.lookup_label("outer"));
+        assert!(table.bind_label("outer").is_ok());
+        table.exit_scope();
+
+        table.exit_scope();
+        table.exit_scope();
+        assert!(!table.lookup_label("outer"));
+    }
+
+    #[test]
+    fn test_symbol_table_visible_symbols() {
+        let mut table = SymbolTable::new();
+        let variable = |name: &str, ty| {
+            Symbol::new(
+                name.to_string(),
+                Type::Primitive(ty),
+                SymbolKind::Variable,
+                false,
+            )
+        };
+        table
+            .insert("y".to_string(), variable("y", PrimitiveType::I32))
+            .unwrap();
+        table
+            .insert("x".to_string(), variable("x", PrimitiveType::I32))
+            .unwrap();
+        table.enter_scope();
+        table
+            .insert("x".to_string(), variable("x", PrimitiveType::Bool))
+            .unwrap();
+
+        let visible: Vec<(&str, &Type)> = table
+            .visible_symbols()
+            .into_iter()
+            .map(|symbol| (symbol.name.as_str(), &symbol.ty))
+            .collect();
+        assert_eq!(
+            visible,
+            [
+                ("x", &Type::Primitive(PrimitiveType::Bool)),
+                ("y", &Type::Primitive(PrimitiveType::I32)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,353 @@
+// Copyright (c) 2026 Mark Ferrell
+// Licensed under the MIT License. See LICENSE.txt in the project root.
+
+//! Conversion of self tail-recursion into loops.
+//!
+//! C compilers routinely turn `return f(...)` inside `f` into a jump, and
+//! ported code often depends on it for deep recursion. Rust makes no such
+//! guarantee, so this optional pass rewrites those calls before codegen:
+//!
+//! ```text
+//! int gcd(int a, int b) {          fn gcd(a: i32, b: i32) -> i32 {
+//!     if (b == 0) { return a; }        let mut a: i32 = a;
+//!     return gcd(b, a % b);            let mut b: i32 = b;
+//! }                                    'tail_call: loop {
+//!                                          if (b == 0) { return a; }
+//!                                          let __tail_a: i32 = b;
+//!                                          let __tail_b: i32 = (a % b);
+//!                                          (a = __tail_a);
+//!                                          (b = __tail_b);
+//!                                          continue 'tail_call;
+//!                                      }
+//!                                  }
+//! ```
+//!
+//! Arguments are evaluated into temporaries before any parameter is
+//! reassigned, so every argument sees the parameter values of the current
+//! iteration, exactly like the call it replaces.
+
+use crate::ast::{
+    BinaryOp, Block, Expression, File, Function, Ident, Item, Literal, Param, PrimitiveType,
+    Statement, Type,
+};
+
+/// Label of the loop that replaces the function body
+const TAIL_CALL_LABEL: &str = "tail_call";
+
+/// Prefix of the temporaries holding the next iteration's arguments
+const TAIL_TEMP_PREFIX: &str = "__tail_";
+
+/// Rewrite self tail calls in every free function of `file` into loops
+///
+/// Returns the names of the functions that were rewritten.
+pub fn eliminate_tail_calls(file: &mut File) -> Vec<String> {
+    let mut rewritten = Vec::new();
+    rewrite_items(&mut file.items, &mut rewritten);
+    rewritten
+}
+
+fn rewrite_items(items: &mut [Item], rewritten: &mut Vec<String>) {
+    for item in items {
+        match item {
+            Item::Function(func) => {
+                let converted = rewrite_function(func);
+                rewritten.extend(converted.then(|| func.name.name.clone()));
+            }
+            Item::Namespace(namespace) => rewrite_items(&mut namespace.items, rewritten),
+            _ => {}
+        }
+    }
+}
+
+/// Rewrite one function, returning whether it contained self tail calls
+///
+/// Methods are left alone, as are non-void functions whose body can fall off
+/// the end: wrapping such a body in a loop would turn the fall-through into
+/// another iteration instead of a compile error.
+fn rewrite_function(func: &mut Function) -> bool {
+    if func.params.iter().any(|param| param.name.name == "self") {
+        return false;
+    }
+    if !contains_tail_call(&func.body, &func.name.name, &func.params) {
+        return false;
+    }
+    let returns_void = matches!(
+        func.return_type,
+        None | Some(Type::Primitive(PrimitiveType::Void))
+    );
+    if !returns_void && !always_returns(&func.body) {
+        return false;
+    }
+
+    let name = func.name.name.clone();
+    let params = func.params.clone();
+    let mut assigned = vec![false; params.len()];
+    let mut body = std::mem::replace(&mut func.body, Block::empty());
+    rewrite_block(&mut body, &name, &params, &mut assigned);
+    if returns_void && !always_returns(&body) {
+        body.statements.push(Statement::Return(None));
+    }
+
+    // Parameters are immutable in Rust; rebind the ones the loop reassigns
+    let mut statements: Vec<Statement> = params
+        .iter()
+        .zip(&assigned)
+        .filter(|(_, &assigned)| assigned)
+        .map(|(param, _)| Statement::Var {
+            name: param.name.clone(),
+            ty: Some(param.ty.clone()),
+            init: Some(Expression::Ident(param.name.clone())),
+        })
+        .collect();
+    statements.push(Statement::While {
+        label: Some(Ident::new(TAIL_CALL_LABEL)),
+        condition: Expression::Literal(Literal::Bool(true)),
+        body,
+    });
+    func.body = Block::new(statements);
+    true
+}
+
+/// The arguments of `stmt` when it is `return name(args);` with one argument
+/// per parameter
+fn tail_call_args<'a>(
+    stmt: &'a Statement,
+    name: &str,
+    params: &[Param],
+) -> Option<&'a [Expression]> {
+    match stmt {
+        Statement::Return(Some(Expression::Call { func, args }))
+            if matches!(func.as_ref(), Expression::Ident(ident) if ident.name == name)
+                && args.len() == params.len() =>
+        {
+            Some(args)
+        }
+        _ => None,
+    }
+}
+
+fn contains_tail_call(block: &Block, name: &str, params: &[Param]) -> bool {
+    block.statements.iter().any(|stmt| {
+        tail_call_args(stmt, name, params).is_some()
+            || child_blocks(stmt)
+                .into_iter()
+                .any(|block| contains_tail_call(block, name, params))
+    })
+}
+
+/// Whether every path through `block` ends in a `return` or a rewritten tail
+/// call
+fn always_returns(block: &Block) -> bool {
+    match block.statements.last() {
+        Some(Statement::Return(_)) => true,
+        Some(Statement::Continue(Some(label))) => label.name == TAIL_CALL_LABEL,
+        Some(Statement::If {
+            then_block,
+            else_block: Some(else_block),
+            ..
+        }) => always_returns(then_block) && always_returns(else_block),
+        _ => false,
+    }
+}
+
+/// Blocks nested directly in `stmt` that belong to the same function body
+pub(crate) fn child_blocks(stmt: &Statement) -> Vec<&Block> {
+    match stmt {
+        Statement::If {
+            then_block,
+            else_block,
+            ..
+        } => std::iter::once(then_block).chain(else_block).collect(),
+        Statement::While { body, .. }
+        | Statement::DoWhile { body, .. }
+        | Statement::For { body, .. }
+        | Statement::ForIn { body, .. }
+        | Statement::Unsafe(body)
+        | Statement::Block(body) => vec![body],
+        Statement::Switch { cases, default, .. } => {
+            cases.iter().map(|case| &case.body).chain(default).collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+pub(crate) fn child_blocks_mut(stmt: &mut Statement) -> Vec<&mut Block> {
+    match stmt {
+        Statement::If {
+            then_block,
+            else_block,
+            ..
+        } => std::iter::once(then_block).chain(else_block).collect(),
+        Statement::While { body, .. }
+        | Statement::DoWhile { body, .. }
+        | Statement::For { body, .. }
+        | Statement::ForIn { body, .. }
+        | Statement::Unsafe(body)
+        | Statement::Block(body) => vec![body],
+        Statement::Switch { cases, default, .. } => cases
+            .iter_mut()
+            .map(|case| &mut case.body)
+            .chain(default)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Replace every `return name(args);` in `block` with parameter reassignment
+/// and a `continue` of the tail-call loop
+fn rewrite_block(block: &mut Block, name: &str, params: &[Param], assigned: &mut [bool]) {
+    let statements = std::mem::take(&mut block.statements);
+    let positions = std::mem::take(&mut block.positions);
+    for (i, mut stmt) in statements.into_iter().enumerate() {
+        let before = block.statements.len();
+        match tail_call_args(&stmt, name, params).map(<[Expression]>::to_vec) {
+            Some(args) => {
+                block
+                    .statements
+                    .extend(reassign_params(params, args, assigned));
+                block
+                    .statements
+                    .push(Statement::Continue(Some(Ident::new(TAIL_CALL_LABEL))));
+            }
+            None => {
+                for child in child_blocks_mut(&mut stmt) {
+                    rewrite_block(child, name, params, assigned);
+                }
+                block.statements.push(stmt);
+            }
+        }
+        // The statements replacing a tail call keep its position
+        if let Some(&position) = positions.get(i) {
+            let added = block.statements.len() - before;
+            block.positions.extend(std::iter::repeat_n(position, added));
+        }
+    }
+}
+
+/// Statements that move `args` into `params`, skipping arguments that pass a
+/// parameter through unchanged
+fn reassign_params(
+    params: &[Param],
+    args: Vec<Expression>,
+    assigned: &mut [bool],
+) -> Vec<Statement> {
+    let changed: Vec<(usize, Expression)> = args
+        .into_iter()
+        .enumerate()
+        .filter(|(i, arg)| !matches!(arg, Expression::Ident(ident) if ident.name == params[*i].name.name))
+        .collect();
+
+    let mut temps = Vec::new();
+    let mut assignments = Vec::new();
+    for (i, arg) in changed {
+        let param = &params[i];
+        let temp = Ident::new(format!("{}{}", TAIL_TEMP_PREFIX, param.name.name));
+        assigned[i] = true;
+        temps.push(Statement::Let {
+            name: temp.clone(),
+            ty: Some(param.ty.clone()),
+            init: Some(arg),
+            mutable: false,
+        });
+        assignments.push(Statement::Expr(Expression::Binary {
+            op: BinaryOp::Assign,
+            left: Box::new(Expression::Ident(param.name.clone())),
+            right: Box::new(Expression::Ident(temp)),
+        }));
+    }
+
+    temps.extend(assignments);
+    temps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::{CodeGenerator, TargetLanguage};
+    use crate::parser::Parser;
+
+    fn rewrite(source: &str) -> (File, Vec<String>) {
+        let mut file = Parser::new(source).unwrap().parse_file().unwrap();
+        let rewritten = eliminate_tail_calls(&mut file);
+        (file, rewritten)
+    }
+
+    fn rust(file: &File) -> String {
+        CodeGenerator::new(TargetLanguage::Rust).generate(file)
+    }
+
+    #[test]
+    fn test_rewrites_accumulator_recursion() {
+        let (file, rewritten) = rewrite(
+            "int fact(int n, int acc) {\n    if (n <= 1) { return acc; }\n    return fact(n - 1, acc * n);\n}\n",
+        );
+        assert_eq!(rewritten, vec!["fact"]);
+
+        let code = rust(&file);
+        assert!(code.contains("let mut n: i32 = n;"), "{}", code);
+        assert!(code.contains("let mut acc: i32 = acc;"), "{}", code);
+        assert!(code.contains("'tail_call: loop"), "{}", code);
+        assert!(code.contains("let __tail_n: i32 = (n - 1);"), "{}", code);
+        assert!(code.contains("(acc = __tail_acc);"), "{}", code);
+        assert!(code.contains("continue 'tail_call;"), "{}", code);
+        assert!(!code.contains("fact(("), "{}", code);
+    }
+
+    #[test]
+    fn test_unchanged_arguments_are_not_rebound() {
+        let (file, _) = rewrite(
+            "int find(int x, int i) {\n    if (i > x) { return 0 - 1; }\n    if (i == x) { return i; }\n    return find(x, i + 1);\n}\n",
+        );
+        let code = rust(&file);
+        assert!(!code.contains("let mut x"), "{}", code);
+        assert!(!code.contains("__tail_x"), "{}", code);
+        assert!(code.contains("(i = __tail_i);"), "{}", code);
+    }
+
+    #[test]
+    fn test_void_function_returns_at_end_of_loop() {
+        let (file, rewritten) = rewrite(
+            "void countdown(int n) {\n    if (n == 0) { return; }\n    __println__(\"{}\", n);\n    return countdown(n - 1);\n}\n",
+        );
+        assert_eq!(rewritten, vec!["countdown"]);
+        let Item::Function(func) = &file.items[0] else {
+            panic!("expected function");
+        };
+        let Statement::While { body, .. } = func.body.statements.last().unwrap() else {
+            panic!("expected tail-call loop");
+        };
+        assert!(always_returns(body));
+    }
+
+    #[test]
+    fn test_leaves_other_functions_alone() {
+        let source = "\
+int fib(int n) {
+    if (n < 2) { return n; }
+    return fib(n - 1) + fib(n - 2);
+}
+
+int other(int n) {
+    return fib(n);
+}
+
+int maybe(int n) {
+    if (n > 0) { return maybe(n - 1); }
+}
+";
+        let (file, rewritten) = rewrite(source);
+        assert!(rewritten.is_empty());
+        assert_eq!(file, Parser::new(source).unwrap().parse_file().unwrap());
+    }
+
+    #[test]
+    fn test_tail_calls_in_else_branch() {
+        let (file, rewritten) = rewrite(
+            "int gcd(int a, int b) {\n    if (b == 0) {\n        return a;\n    } else {\n        return gcd(b, a % b);\n    }\n}\n",
+        );
+        assert_eq!(rewritten, vec!["gcd"]);
+        let code = rust(&file);
+        assert!(code.contains("let __tail_a: i32 = b;"), "{}", code);
+        assert!(code.contains("let __tail_b: i32 = (a % b);"), "{}", code);
+    }
+}
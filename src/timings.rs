@@ -0,0 +1,305 @@
+// Copyright (c) 2026 Mark Ferrell
+// Licensed under the MIT License. See LICENSE.txt in the project root.
+
+//! Per-phase, per-file compile timings printed under `--verbose` and
+//! written by `--self-profile`.
+//!
+//! Files are parsed and analyzed on a thread pool, so timings are recorded
+//! from any thread and sorted into pipeline order when printed. The profile
+//! adds the time each item took to analyze, in one of two formats:
+//!
+//! ```text
+//! crustyc;check;main.crst 412
+//! crustyc;check;main.crst;parse_args 1830
+//! crustyc;rustc;main.crst 251044
+//! ```
+//!
+//! folded stacks in microseconds, as `flamegraph.pl` and `inferno` read
+//! them, or with a `.json` path a Chrome trace for `chrome://tracing` and
+//! Perfetto, showing which thread ran each phase and when.
+
+use std::sync::Mutex;
+use std::thread::ThreadId;
+use std::time::{Duration, Instant};
+
+/// File `--self-profile` writes when given no path
+pub const PROFILE_FILE_NAME: &str = "crustyc.folded";
+
+/// Pipeline phases, in the order the report lists them
+const PHASES: &[&str] = &[
+    "preprocess",
+    "parse",
+    "expand",
+    "analyze",
+    "check",
+    "lint",
+    "lower",
+    "codegen",
+    "rustc",
+    "compile",
+];
+
+/// How long one phase took on one file, or on one item of it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Timing {
+    pub phase: &'static str,
+    pub file: String,
+    /// Function, type or other item of `file` the time was spent on; the
+    /// file's own timing covers its items' too
+    pub item: Option<String>,
+    /// When the phase started, from the start of the compilation
+    pub start: Duration,
+    pub duration: Duration,
+    pub thread: ThreadId,
+}
+
+/// Timings collected over one compilation
+#[derive(Debug)]
+pub struct Timings {
+    started: Instant,
+    entries: Mutex<Vec<Timing>>,
+}
+
+impl Default for Timings {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            entries: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl Timings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `f`, recording how long it took as `phase` of `file`
+    pub fn time<T>(&self, phase: &'static str, file: &str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.push(phase, file, None, start, start.elapsed());
+        result
+    }
+
+    /// Record that `phase` of `file` just finished, taking `duration`
+    pub fn record(&self, phase: &'static str, file: &str, duration: Duration) {
+        let start = Instant::now().checked_sub(duration).unwrap_or(self.started);
+        self.push(phase, file, None, start, duration);
+    }
+
+    /// Record that `phase` spent `duration` on `item` of `file` from `start`
+    pub fn record_item(
+        &self,
+        phase: &'static str,
+        file: &str,
+        item: &str,
+        start: Instant,
+        duration: Duration,
+    ) {
+        self.push(phase, file, Some(item.to_string()), start, duration);
+    }
+
+    fn push(
+        &self,
+        phase: &'static str,
+        file: &str,
+        item: Option<String>,
+        start: Instant,
+        duration: Duration,
+    ) {
+        let timing = Timing {
+            phase,
+            file: file.to_string(),
+            item,
+            start: start.saturating_duration_since(self.started),
+            duration,
+            thread: std::thread::current().id(),
+        };
+        self.entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push(timing);
+    }
+
+    /// The recorded timings, by phase and then by file
+    pub fn entries(&self) -> Vec<Timing> {
+        let mut entries = self
+            .entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone();
+        entries.sort_by(|a, b| {
+            let phase = |timing: &Timing| PHASES.iter().position(|&p| p == timing.phase);
+            (phase(a), &a.file, a.start).cmp(&(phase(b), &b.file, b.start))
+        });
+        entries
+    }
+
+    /// One `phase  file  duration` line per file timing, with the columns
+    /// aligned
+    pub fn format(&self) -> String {
+        let entries: Vec<Timing> = self
+            .entries()
+            .into_iter()
+            .filter(|timing| timing.item.is_none())
+            .collect();
+        let width = entries.iter().map(|t| t.file.len()).max().unwrap_or(0);
+        let mut report = String::from("Timings:\n");
+        for timing in &entries {
+            report.push_str(&format!(
+                "  {:<8} {:<width$} {:>10.3} ms\n",
+                timing.phase,
+                timing.file,
+                timing.duration.as_secs_f64() * 1000.0,
+                width = width
+            ));
+        }
+        report
+    }
+
+    /// The timings as folded stacks, `crustyc;phase;file[;item] micros`,
+    /// with a file's line counting only the time none of its items took
+    pub fn folded(&self) -> String {
+        let entries = self.entries();
+        let mut stacks: Vec<(String, u128)> = Vec::new();
+        for timing in &entries {
+            let mut micros = timing.duration.as_micros();
+            let mut stack = format!("crustyc;{};{}", timing.phase, folded_frame(&timing.file));
+            match &timing.item {
+                Some(item) => {
+                    stack.push(';');
+                    stack.push_str(&folded_frame(item));
+                }
+                None => {
+                    let items: u128 = entries
+                        .iter()
+                        .filter(|other| {
+                            other.item.is_some()
+                                && other.phase == timing.phase
+                                && other.file == timing.file
+                        })
+                        .map(|other| other.duration.as_micros())
+                        .sum();
+                    micros = micros.saturating_sub(items);
+                }
+            }
+            match stacks.iter_mut().find(|(existing, _)| *existing == stack) {
+                Some((_, total)) => *total += micros,
+                None => stacks.push((stack, micros)),
+            }
+        }
+        stacks
+            .into_iter()
+            .map(|(stack, micros)| format!("{} {}\n", stack, micros))
+            .collect()
+    }
+
+    /// The timings as a Chrome trace: one complete event per timing, on a
+    /// track for the thread that recorded it
+    pub fn chrome_trace(&self) -> String {
+        let entries = self.entries();
+        let mut threads: Vec<ThreadId> = Vec::new();
+        let events: Vec<serde_json::Value> = entries
+            .iter()
+            .map(|timing| {
+                let tid = match threads.iter().position(|&id| id == timing.thread) {
+                    Some(index) => index,
+                    None => {
+                        threads.push(timing.thread);
+                        threads.len() - 1
+                    }
+                };
+                serde_json::json!({
+                    "name": timing.item.as_deref().unwrap_or(&timing.file),
+                    "cat": timing.phase,
+                    "ph": "X",
+                    "ts": timing.start.as_micros() as u64,
+                    "dur": timing.duration.as_micros() as u64,
+                    "pid": 1,
+                    "tid": tid,
+                    "args": { "file": timing.file },
+                })
+            })
+            .collect();
+        serde_json::json!({ "traceEvents": events, "displayTimeUnit": "ms" }).to_string()
+    }
+}
+
+/// `name` as one frame of a folded stack, where `;` separates frames and
+/// the last space the count
+fn folded_frame(name: &str) -> String {
+    name.replace(';', ":").replace(' ', "_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timings_are_listed_in_pipeline_order() {
+        let timings = Timings::new();
+        timings.record("codegen", "main.crst", Duration::from_micros(1500));
+        timings.record("parse", "util.crst", Duration::from_micros(250));
+        let value = timings.time("parse", "main.crst", || 7);
+        assert_eq!(value, 7);
+
+        let order: Vec<(&str, String)> = timings
+            .entries()
+            .into_iter()
+            .map(|timing| (timing.phase, timing.file))
+            .collect();
+        assert_eq!(
+            order,
+            [
+                ("parse", "main.crst".to_string()),
+                ("parse", "util.crst".to_string()),
+                ("codegen", "main.crst".to_string()),
+            ]
+        );
+        let report = timings.format();
+        assert!(report.starts_with("Timings:\n"), "{}", report);
+        assert!(
+            report.contains("  codegen  main.crst      1.500 ms\n"),
+            "{}",
+            report
+        );
+    }
+
+    #[test]
+    fn test_profile_formats() {
+        let timings = Timings::new();
+        let start = Instant::now();
+        timings.record_item(
+            "check",
+            "main.crst",
+            "parse_args",
+            start,
+            Duration::from_micros(300),
+        );
+        timings.record_item(
+            "check",
+            "main.crst",
+            "main",
+            start,
+            Duration::from_micros(200),
+        );
+        timings.record("check", "main.crst", Duration::from_micros(1000));
+        timings.record("rustc", "main.crst", Duration::from_micros(4000));
+
+        assert_eq!(
+            timings.folded(),
+            "crustyc;check;main.crst 500\ncrustyc;check;main.crst;parse_args 300\ncrustyc;check;main.crst;main 200\ncrustyc;rustc;main.crst 4000\n"
+        );
+
+        let trace: serde_json::Value = serde_json::from_str(&timings.chrome_trace()).unwrap();
+        let events = trace["traceEvents"].as_array().unwrap();
+        assert_eq!(events.len(), 4);
+        assert_eq!(events[1]["name"], "parse_args");
+        assert_eq!(events[1]["cat"], "check");
+        assert_eq!(events[1]["ph"], "X");
+        assert_eq!(events[1]["dur"], 300);
+        assert_eq!(events[1]["args"]["file"], "main.crst");
+        assert_eq!(events[3]["tid"], 0);
+    }
+}
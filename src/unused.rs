@@ -0,0 +1,550 @@
+// Copyright (c) 2026 Mark Ferrell
+// Licensed under the MIT License. See LICENSE.txt in the project root.
+
+//! Detection of variables, parameters, private functions and struct fields
+//! that are never used.
+//!
+//! C compilers warn about these with `-Wunused`; left alone they are
+//! clutter at best and at worst a sign that a value computed for a reason
+//! is never looked at:
+//!
+//! ```text
+//! static int helper(int x) { return x; }   // never called
+//! int area(int w, int h) {                 // 'h' is never used
+//!     let int scale = 2;                   // 'scale' is never used
+//!     return w * w;
+//! }
+//! ```
+//!
+//! A variable is used when its value is read. Assigning it, incrementing
+//! it or adding to it is not a use, as the value stored is then never
+//! read. A name mentioned by a macro call or a `rust { }` block counts as
+//! read. A private (`static`) function is unused when nothing reachable
+//! from the public items, `main` or a `#[test]`, `#[bench]` or
+//! `#[no_mangle]` function calls it, and a field is unused when no file of
+//! the program reads it.
+//!
+//! Names starting with `_` are unused on purpose. `#[allow(unused)]` on a
+//! function, struct or field silences the warnings about it and, for a
+//! function, about its parameters and variables; `#[allow(unused_variables)]`
+//! and `#[allow(dead_code)]` silence only those kinds. The parameters of a
+//! trait method are set by the trait and are not reported.
+
+use crate::ast::{
+    Attribute, AttributeArg, Block, Expression, File, Function, Item, Param, Statement, Struct,
+    Type, UnaryOp, Visibility,
+};
+use crate::deadcode::{find_dead_code, SourceFile};
+use crate::error::Position;
+use crate::macros::{child_expressions, is_name_token, pattern_bindings};
+use std::collections::HashSet;
+
+/// What kind of declaration is unused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnusedKind {
+    /// A `let` or `var` local, or the variable of a `for` loop
+    Variable,
+    Parameter,
+    /// A private function
+    Function,
+    /// A field of the struct named
+    Field(String),
+}
+
+/// A declaration nothing uses
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unused {
+    pub kind: UnusedKind,
+    pub name: String,
+    /// Function a variable or parameter belongs to
+    pub function: Option<String>,
+    /// Where the declared name is written, when it is known
+    pub position: Option<Position>,
+}
+
+impl std::fmt::Display for Unused {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            UnusedKind::Variable => write!(f, "unused variable '{}'", self.name),
+            UnusedKind::Parameter => write!(f, "unused parameter '{}'", self.name),
+            UnusedKind::Function => write!(f, "function '{}' is never used", self.name),
+            UnusedKind::Field(ty) => {
+                write!(f, "field '{}' of struct '{}' is never read", self.name, ty)
+            }
+        }
+    }
+}
+
+/// Find the unused declarations of `file`, whose text is `source`
+///
+/// `read_fields` are the fields read anywhere in the program, as collected
+/// by [`read_fields`] from every file, since a struct's fields may be read
+/// only by the files importing it. `entry` is the function the program
+/// starts with when it is not `main`.
+pub fn find_unused(
+    file: &File,
+    source: &str,
+    read_fields: &HashSet<String>,
+    entry: Option<&str>,
+) -> Vec<Unused> {
+    let mut found = Vec::new();
+    items(&file.items, read_fields, &mut found);
+
+    let private: HashSet<&str> = private_functions(&file.items);
+    let dead = find_dead_code(
+        &[SourceFile {
+            path: "",
+            source,
+            file,
+        }],
+        None,
+    );
+    for item in dead.unreachable {
+        if item.kind == "function"
+            && private.contains(item.name.as_str())
+            && item.name != "main"
+            && Some(item.name.as_str()) != entry
+            && !item.name.starts_with('_')
+        {
+            found.push(Unused {
+                kind: UnusedKind::Function,
+                name: item.name,
+                function: None,
+                position: item.position,
+            });
+        }
+    }
+    found
+}
+
+/// Every field name the expressions of `file` read
+pub fn read_fields(file: &File) -> HashSet<String> {
+    let mut walker = Walker::default();
+    walker.items_fields(&file.items);
+    walker.fields
+}
+
+/// Whether `attributes` allow the lint `lint` (`unused` allows them all)
+fn allows(attributes: &[Attribute], lint: &str) -> bool {
+    attributes.iter().any(|attr| {
+        attr.name.name == "allow"
+            && attr.args.iter().any(|arg| {
+                matches!(arg, AttributeArg::Ident(name) if name.name == "unused" || name.name == lint)
+            })
+    })
+}
+
+/// Private functions without `#[allow(unused)]` or `#[allow(dead_code)]`
+fn private_functions(items: &[Item]) -> HashSet<&str> {
+    let mut names = HashSet::new();
+    for item in items {
+        match item {
+            Item::Function(func)
+                if func.visibility == Visibility::Private
+                    && !allows(&func.attributes, "dead_code") =>
+            {
+                names.insert(func.name.name.as_str());
+            }
+            Item::Namespace(namespace) => names.extend(private_functions(&namespace.items)),
+            _ => {}
+        }
+    }
+    names
+}
+
+fn items(items: &[Item], read_fields: &HashSet<String>, found: &mut Vec<Unused>) {
+    for item in items {
+        match item {
+            Item::Function(func) => function(func, true, found),
+            Item::Struct(s) => {
+                fields(s, read_fields, found);
+                let allowed = allows(&s.attributes, "unused_variables");
+                for method in s.methods.iter().filter(|_| !allowed) {
+                    function(method, true, found);
+                }
+            }
+            // The trait decides the parameters of its methods
+            Item::Impl(i) => i.methods.iter().for_each(|m| function(m, false, found)),
+            Item::Trait(t) => t
+                .methods
+                .iter()
+                .filter(|m| m.body.is_some())
+                .for_each(|m| function(&m.as_function(), false, found)),
+            Item::Namespace(namespace) => self::items(&namespace.items, read_fields, found),
+            _ => {}
+        }
+    }
+}
+
+fn fields(s: &Struct, read_fields: &HashSet<String>, found: &mut Vec<Unused>) {
+    if allows(&s.attributes, "dead_code") {
+        return;
+    }
+    for field in &s.fields {
+        let name = &field.name.name;
+        if !read_fields.contains(name)
+            && !name.starts_with('_')
+            && !allows(&field.attributes, "dead_code")
+        {
+            found.push(Unused {
+                kind: UnusedKind::Field(s.name.name.clone()),
+                name: name.clone(),
+                function: None,
+                position: field.name.position(),
+            });
+        }
+    }
+}
+
+fn function(func: &Function, check_params: bool, found: &mut Vec<Unused>) {
+    if allows(&func.attributes, "unused_variables") {
+        return;
+    }
+    let mut walker = Walker::default();
+    walker.function(&func.name.name, &func.params, check_params, &func.body);
+    found.extend(walker.found);
+}
+
+/// A local in scope and whether it has been read
+struct Binding {
+    name: String,
+    kind: UnusedKind,
+    position: Option<Position>,
+    used: bool,
+    /// Pattern bindings and the like are tracked for shadowing only
+    reported: bool,
+}
+
+#[derive(Default)]
+struct Walker {
+    /// Innermost scope last
+    scopes: Vec<Vec<Binding>>,
+    /// Function of the innermost scope
+    functions: Vec<String>,
+    found: Vec<Unused>,
+    fields: HashSet<String>,
+}
+
+impl Walker {
+    fn items_fields(&mut self, items: &[Item]) {
+        for item in items {
+            match item {
+                Item::Function(func) => self.block(&func.body),
+                Item::Struct(s) => s.methods.iter().for_each(|m| self.block(&m.body)),
+                Item::Impl(i) => i.methods.iter().for_each(|m| self.block(&m.body)),
+                Item::Trait(t) => t
+                    .methods
+                    .iter()
+                    .filter_map(|m| m.body.as_ref())
+                    .for_each(|body| self.block(body)),
+                Item::Const(c) => self.expression(&c.value),
+                Item::Static(s) => self.expression(&s.value),
+                Item::Namespace(namespace) => self.items_fields(&namespace.items),
+                _ => {}
+            }
+        }
+    }
+
+    /// `check_params` tells whether unused parameters are reported
+    fn function(&mut self, name: &str, params: &[Param], check_params: bool, body: &Block) {
+        self.functions.push(name.to_string());
+        self.scopes.push(Vec::new());
+        for param in params {
+            let name = &param.name.name;
+            let reported = check_params && name != "self";
+            self.declare(name, UnusedKind::Parameter, param.name.position(), reported);
+        }
+        self.block(body);
+        self.pop_scope();
+        self.functions.pop();
+    }
+
+    fn declare(
+        &mut self,
+        name: &str,
+        kind: UnusedKind,
+        position: Option<Position>,
+        reported: bool,
+    ) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push(Binding {
+                name: name.to_string(),
+                kind,
+                position,
+                used: false,
+                reported: reported && !name.starts_with('_'),
+            });
+        }
+    }
+
+    fn pop_scope(&mut self) {
+        let function = self.functions.last().cloned();
+        for binding in self.scopes.pop().unwrap_or_default() {
+            if binding.reported && !binding.used {
+                self.found.push(Unused {
+                    kind: binding.kind,
+                    name: binding.name,
+                    function: function.clone(),
+                    position: binding.position,
+                });
+            }
+        }
+    }
+
+    fn read(&mut self, name: &str) {
+        let binding = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .flat_map(|scope| scope.iter_mut().rev())
+            .find(|binding| binding.name == name);
+        if let Some(binding) = binding {
+            binding.used = true;
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        self.scopes.push(Vec::new());
+        for (i, stmt) in block.statements.iter().enumerate() {
+            self.statement(stmt, block.positions.get(i).copied());
+        }
+        self.pop_scope();
+    }
+
+    fn statement(&mut self, stmt: &Statement, position: Option<Position>) {
+        match stmt {
+            Statement::Let { name, init, .. } | Statement::Var { name, init, .. } => {
+                if let Some(init) = init {
+                    self.expression(init);
+                }
+                let position = name.position().or(position);
+                self.declare(&name.name, UnusedKind::Variable, position, true);
+            }
+            Statement::Const { name, value, .. } => {
+                self.expression(value);
+                let position = name.position().or(position);
+                self.declare(&name.name, UnusedKind::Variable, position, false);
+            }
+            Statement::Expr(expr) => self.discarded(expr),
+            Statement::Return(value) => {
+                if let Some(value) = value {
+                    self.expression(value);
+                }
+            }
+            Statement::If {
+                condition,
+                then_block,
+                else_block,
+            } => {
+                self.expression(condition);
+                self.block(then_block);
+                if let Some(else_block) = else_block {
+                    self.block(else_block);
+                }
+            }
+            Statement::While {
+                condition, body, ..
+            }
+            | Statement::DoWhile { body, condition } => {
+                self.expression(condition);
+                self.block(body);
+            }
+            Statement::For {
+                init,
+                condition,
+                increment,
+                body,
+                ..
+            } => {
+                self.scopes.push(Vec::new());
+                self.statement(init, position);
+                self.expression(condition);
+                self.discarded(increment);
+                self.block(body);
+                self.pop_scope();
+            }
+            Statement::ForIn {
+                var, iter, body, ..
+            } => {
+                self.expression(iter);
+                self.scopes.push(Vec::new());
+                let position = var.position().or(position);
+                self.declare(&var.name, UnusedKind::Variable, position, true);
+                self.block(body);
+                self.pop_scope();
+            }
+            Statement::Switch {
+                expr,
+                cases,
+                default,
+            } => {
+                self.expression(expr);
+                for case in cases {
+                    case.values.iter().for_each(|value| self.expression(value));
+                    self.block(&case.body);
+                }
+                if let Some(default) = default {
+                    self.block(default);
+                }
+            }
+            Statement::Unsafe(block) | Statement::Block(block) => self.block(block),
+            Statement::StaticAssert(assert) => self.expression(&assert.condition),
+            Statement::NestedFunction {
+                name, params, body, ..
+            } => self.function(&name.name, params, true, body),
+            Statement::Break(_) | Statement::Continue(_) => {}
+        }
+    }
+
+    /// An expression whose value is dropped: storing into a variable or
+    /// stepping it there does not read it
+    fn discarded(&mut self, expr: &Expression) {
+        match expr {
+            Expression::Unary {
+                op: UnaryOp::PreInc | UnaryOp::PreDec | UnaryOp::PostInc | UnaryOp::PostDec,
+                expr,
+            } if matches!(expr.as_ref(), Expression::Ident(_)) => {}
+            _ => self.expression(expr),
+        }
+    }
+
+    fn expression(&mut self, expr: &Expression) {
+        match expr {
+            Expression::Ident(name) => self.read(&name.name),
+            Expression::Binary { op, left, right } if op.is_assignment() => {
+                self.expression(right);
+                match left.as_ref() {
+                    Expression::Ident(_) => {}
+                    // Storing into a field writes it without reading it,
+                    // but reads the variable holding it
+                    Expression::FieldAccess { expr, .. } => self.expression(expr),
+                    place => self.expression(place),
+                }
+            }
+            Expression::FieldAccess { expr, field } => {
+                self.fields.insert(field.name.clone());
+                self.expression(expr);
+            }
+            Expression::Match { expr, arms } => {
+                self.expression(expr);
+                for arm in arms {
+                    let mut bound = HashSet::new();
+                    pattern_bindings(&arm.pattern, &mut bound);
+                    self.scopes.push(Vec::new());
+                    for name in &bound {
+                        self.declare(name, UnusedKind::Variable, None, false);
+                    }
+                    self.expression(&arm.body);
+                    self.pop_scope();
+                }
+            }
+            // A lambda's parameters follow the function type it is passed
+            // as, so they are not reported
+            Expression::Lambda { params, body, .. } => {
+                self.scopes.push(Vec::new());
+                for param in params {
+                    self.declare(&param.name.name, UnusedKind::Parameter, None, false);
+                }
+                self.block(body);
+                self.pop_scope();
+            }
+            // `sizeof(buffer)` names a variable as a type
+            Expression::Sizeof { ty } | Expression::Alignof { ty } => {
+                if let Type::Ident(name) = ty {
+                    self.read(&name.name);
+                }
+            }
+            Expression::MacroCall { args: tokens, .. } | Expression::RustBlock { tokens } => {
+                let mut after_dot = false;
+                for token in tokens {
+                    if is_name_token(token) {
+                        // A name after a dot is a field, as in `p.x`
+                        if after_dot {
+                            self.fields.insert(token.text.clone());
+                        } else {
+                            self.read(&token.text);
+                        }
+                    }
+                    after_dot = token.text == ".";
+                }
+            }
+            _ => {
+                for child in child_expressions(expr) {
+                    self.expression(child);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn find(source: &str) -> Vec<String> {
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+        find_unused(&file, source, &read_fields(&file), None)
+            .iter()
+            .map(Unused::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_finds_unused_variables_and_parameters() {
+        let found = find(
+            "int area(int w, int h, int _depth, int[] sizes) {\n    let int scale = 2;\n    var int count = 0;\n    count = count + 1;\n    var int steps = 0;\n    steps++;\n    steps = 2;\n    let int shown = w;\n    __println__(\"{}\", shown);\n    for (i in sizes) {\n        count = 1;\n    }\n    return w * w;\n}\n",
+        );
+        assert_eq!(
+            found,
+            [
+                "unused variable 'i'",
+                "unused variable 'scale'",
+                "unused variable 'steps'",
+                "unused parameter 'h'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_points_at_the_unused_names() {
+        let source = "struct Point {\n    int x;\n}\nint f(int w, int h) {\n    let int scale = 2;\n    for (i in [1, 2]) {\n    }\n    return w;\n}\n";
+        let file = Parser::new(source).unwrap().parse_file().unwrap();
+        let found: Vec<(String, Option<Position>)> =
+            find_unused(&file, source, &read_fields(&file), None)
+                .into_iter()
+                .map(|unused| (unused.name, unused.position))
+                .collect();
+        assert_eq!(
+            found,
+            [
+                ("x".to_string(), Some(Position::new(2, 9))),
+                ("i".to_string(), Some(Position::new(6, 10))),
+                ("scale".to_string(), Some(Position::new(5, 13))),
+                ("h".to_string(), Some(Position::new(4, 18))),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_finds_unused_functions_and_fields() {
+        let found = find(
+            "struct Point {\n    int x;\n    int y;\n    #[allow(unused)]\n    int z;\n}\nstatic int helper() {\n    return 1;\n}\nstatic int used() {\n    return 2;\n}\n#[allow(unused)]\nstatic int kept(int a) {\n    return 3;\n}\nint main() {\n    var Point p = (Point){ .x = 1, .y = 2, .z = 3 };\n    p.y = 4;\n    return p.x + used();\n}\n",
+        );
+        assert_eq!(
+            found,
+            [
+                "field 'y' of struct 'Point' is never read",
+                "function 'helper' is never used",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reads_inside_lambdas() {
+        let found = find(
+            "static int twice(int n) {\n    return n * 2;\n}\nint main() {\n    let int k = 2;\n    let scale = fn(int x, int unused) -> int {\n        let int spare = 1;\n        return twice(x * k);\n    };\n    return scale(1, 0);\n}\n",
+        );
+        assert_eq!(found, ["unused variable 'spare'"]);
+    }
+}
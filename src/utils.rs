@@ -3,10 +3,122 @@
 
 //! Utility functions and helpers.
 
+use crate::error::Span;
+use crate::lexer::{Lexer, Token, TokenKind};
+
+/// Find the declaration of a top-level item in Crusty source
+///
+/// The AST does not carry spans, so this looks for the first occurrence of the
+/// name followed by `(` (functions, macros), `{` (structs, enums) or `;`
+/// (typedefs) and returns the span of the name.
+pub fn locate_item(source: &str, name: &str) -> Option<Span> {
+    find_declaration(&mut Lexer::new(source), name).map(|(span, _)| span)
+}
+
+/// Find the full extent of a top-level item in Crusty source
+///
+/// The span starts at the item's name, as found by [`locate_item`], and ends
+/// at the closing brace of its body, or at the `;` of a bodiless declaration.
+pub fn locate_item_extent(source: &str, name: &str) -> Option<Span> {
+    let mut lexer = Lexer::new(source);
+    let (start, mut token) = find_declaration(&mut lexer, name)?;
+    let mut depth = 0usize;
+
+    loop {
+        match token.kind {
+            TokenKind::LBrace => depth += 1,
+            TokenKind::RBrace => {
+                depth = depth.checked_sub(1)?;
+                if depth == 0 {
+                    return Some(Span::new(start.start, token.span.end));
+                }
+            }
+            TokenKind::Semicolon if depth == 0 => {
+                return Some(Span::new(start.start, token.span.end));
+            }
+            TokenKind::Eof => return None,
+            _ => {}
+        }
+        token = lexer.next_token().ok()?;
+    }
+}
+
+/// Stack size of the threads the compiler's recursive passes run on
+///
+/// The parser, analyzer and generator each recurse once per level of an
+/// expression, and a level costs tens of kilobytes of stack in unoptimized
+/// builds, so the caller's stack (8 MiB for `main`, 2 MiB for a spawned
+/// thread) may not fit the deepest expression the parser accepts.
+pub const DEEP_STACK_BYTES: usize = 64 * 1024 * 1024;
+
+/// Run `f` on a thread named `name` with a stack of `DEEP_STACK_BYTES`,
+/// waiting for its result
+///
+/// A panic in `f` is passed on to the caller.
+pub fn on_deep_stack<T: Send>(name: &str, f: impl FnOnce() -> T + Send) -> T {
+    std::thread::scope(|scope| {
+        std::thread::Builder::new()
+            .name(name.to_string())
+            .stack_size(DEEP_STACK_BYTES)
+            .spawn_scoped(scope, f)
+            .expect("failed to spawn compiler thread")
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+}
+
+/// Advance `lexer` past the declaration of `name`, returning the span of the
+/// name and the token that follows it
+fn find_declaration(lexer: &mut Lexer, name: &str) -> Option<(Span, Token)> {
+    let mut previous: Option<Token> = None;
+
+    loop {
+        let token = lexer.next_token().ok()?;
+        if token.kind == TokenKind::Eof {
+            return None;
+        }
+        if let Some(prev) = previous.take() {
+            if prev.kind == TokenKind::Ident(name.to_string())
+                && matches!(
+                    token.kind,
+                    TokenKind::LParen | TokenKind::LBrace | TokenKind::Semicolon
+                )
+            {
+                return Some((prev.span, token));
+            }
+        }
+        previous = Some(token);
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::error::Position;
+
     #[test]
     fn placeholder() {
         // Placeholder test
     }
+
+    #[test]
+    fn test_locate_item_skips_uses() {
+        let source = "int x = 1;\nvoid f() {\n    x = 2;\n}\nstruct x {\n}\n";
+        let span = locate_item(source, "f").unwrap();
+        assert_eq!(span.start, Position::new(2, 6));
+        assert_eq!(span.end, Position::new(2, 7));
+        assert_eq!(locate_item(source, "missing"), None);
+    }
+
+    #[test]
+    fn test_locate_item_extent_spans_body() {
+        let source =
+            "int x = 1;\nvoid f() {\n    if (x) {\n        x = 2;\n    }\n}\ntypedef int t;\n";
+        let span = locate_item_extent(source, "f").unwrap();
+        assert_eq!(span.start, Position::new(2, 6));
+        assert_eq!(span.end.line, 6);
+        let span = locate_item_extent(source, "t").unwrap();
+        assert_eq!((span.start.line, span.end.line), (7, 7));
+        assert_eq!(locate_item_extent("void g() {", "g"), None);
+    }
 }